@@ -0,0 +1,154 @@
+//! `comically-cli analyze`: what the pipeline sees in each page of a comic, see
+//! [`comically::image::analyze`]
+
+use anyhow::{Context, Result};
+
+use std::path::Path;
+
+use comically::image::PageStats;
+use comically::ComicFile;
+
+pub fn run(archive: &Path, json: bool) -> Result<()> {
+    if !archive.exists() {
+        anyhow::bail!("Input file does not exist: {}", archive.display());
+    }
+    let comic = ComicFile::new(archive.to_path_buf());
+    let mut files: Vec<_> = comically::archive::unarchive_comic_iter(&comic)
+        .context("Failed to open comic archive")?
+        .filter_map(|file| file.ok())
+        .collect();
+    files.sort_by(|a, b| comically::archive::natural_cmp(&a.file_name, &b.file_name));
+
+    let pages: Vec<(String, Option<PageStats>)> = files
+        .iter()
+        .map(|file| {
+            let name = file.file_name.to_string_lossy().into_owned();
+            match comically::image::decode::decode(&file.data) {
+                Ok(page) => (name, Some(comically::image::analyze(&page))),
+                Err(e) => {
+                    log::warn!("Failed to decode {name}: {e:#}");
+                    (name, None)
+                }
+            }
+        })
+        .collect();
+
+    if json {
+        let pages: Vec<_> = pages
+            .iter()
+            .enumerate()
+            .map(|(i, (name, stats))| page_json(i + 1, name, stats.as_ref()))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&pages)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:>4}  {:<24} {:>9}  {:<5}  {:<6}  {:<19} {:>6} {:>6} {:>8} {:>8} {:>8}",
+        "PAGE",
+        "NAME",
+        "SIZE",
+        "COLOR",
+        "SPREAD",
+        "MARGINS T/R/B/L",
+        "MEDIAN",
+        "DETAIL",
+        "JPEG",
+        "PNG",
+        "WEBP"
+    );
+    let yes = |value: bool| if value { "yes" } else { "no" };
+    for (i, (name, stats)) in pages.iter().enumerate() {
+        let Some(stats) = stats else {
+            println!("{:>4}  {:<24} unreadable", i + 1, truncate(name));
+            continue;
+        };
+        let (width, height) = stats.dimensions;
+        let margins = stats.margins.map_or("none".to_string(), |m| {
+            format!("{}/{}/{}/{}", m.top, m.right, m.bottom, m.left)
+        });
+        let sizes = stats.estimated_sizes;
+        println!(
+            "{:>4}  {:<24} {:>9}  {:<5}  {:<6}  {:<19} {:>6} {:>6.2} {:>8} {:>8} {:>8}",
+            i + 1,
+            truncate(name),
+            format!("{width}x{height}"),
+            yes(stats.is_color),
+            yes(stats.is_spread),
+            margins,
+            stats.median_level(),
+            stats.complexity,
+            kilobytes(sizes.jpeg),
+            kilobytes(sizes.png),
+            kilobytes(sizes.webp),
+        );
+    }
+
+    let analyzed: Vec<&PageStats> = pages
+        .iter()
+        .filter_map(|(_, stats)| stats.as_ref())
+        .collect();
+    let total = |size: fn(&PageStats) -> usize| {
+        analyzed.iter().map(|stats| size(stats)).sum::<usize>() as f64 / 1_000_000.0
+    };
+    println!();
+    println!(
+        "{} pages, {} in color, {} spreads, {} with margins",
+        pages.len(),
+        analyzed.iter().filter(|stats| stats.is_color).count(),
+        analyzed.iter().filter(|stats| stats.is_spread).count(),
+        analyzed
+            .iter()
+            .filter(|stats| stats.margins.is_some())
+            .count(),
+    );
+    println!(
+        "estimated at the source size: {:.1} MB as JPEG, {:.1} MB as PNG, {:.1} MB as WebP",
+        total(|stats| stats.estimated_sizes.jpeg),
+        total(|stats| stats.estimated_sizes.png),
+        total(|stats| stats.estimated_sizes.webp),
+    );
+    Ok(())
+}
+
+fn page_json(page: usize, name: &str, stats: Option<&PageStats>) -> serde_json::Value {
+    let Some(stats) = stats else {
+        return serde_json::json!({ "page": page, "name": name, "error": "unreadable" });
+    };
+    let (width, height) = stats.dimensions;
+    let sizes = stats.estimated_sizes;
+    serde_json::json!({
+        "page": page,
+        "name": name,
+        "width": width,
+        "height": height,
+        "color": stats.is_color,
+        "spread": stats.is_spread,
+        "margins": stats.margins.map(|m| serde_json::json!({
+            "top": m.top,
+            "right": m.right,
+            "bottom": m.bottom,
+            "left": m.left,
+        })),
+        "complexity": stats.complexity,
+        "histogram": stats.histogram.as_slice(),
+        "estimated_sizes": {
+            "jpeg": sizes.jpeg,
+            "png": sizes.png,
+            "webp": sizes.webp,
+        },
+    })
+}
+
+fn kilobytes(bytes: usize) -> String {
+    format!("{} KB", bytes.div_ceil(1000))
+}
+
+// the end of long names, which tells the pages apart
+fn truncate(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    match chars.len() > 24 {
+        true => format!("…{}", chars[chars.len() - 23..].iter().collect::<String>()),
+        false => name.to_string(),
+    }
+}
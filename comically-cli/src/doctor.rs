@@ -0,0 +1,397 @@
+//! `comically-cli doctor`: checks what conversions depend on and says how to fix what's
+//! missing, before it shows up as a failed comic
+
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+
+use comically::temp::TempRoot;
+use comically::{Backend, ComicConfig, ImageFormat, OutputFormat, UpscalePolicy};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    // works, but not as well as it could
+    Warn,
+    // conversions will fail
+    Fail,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: Status,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Prints the result of every check, failing when one of them did
+pub fn run(config_path: Option<&Path>) -> anyhow::Result<()> {
+    let (config, mut checks) = check_config(config_path);
+    checks.push(check_kindlegen(&config));
+    checks.push(check_temp_dir(&config));
+    checks.extend(check_upscaler(&config));
+    checks.extend(check_gpu(&config));
+    checks.extend(check_ocr(&config));
+    checks.extend(check_archives());
+    checks.push(check_terminal());
+    report(&checks, &mut std::io::stdout().lock())
+}
+
+// one line per check, with how to fix the problems under them
+fn report(checks: &[Check], out: &mut impl Write) -> anyhow::Result<()> {
+    for check in checks {
+        let status = match check.status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        writeln!(out, "[{status:>4}] {}: {}", check.name, check.detail)?;
+        if let Some(fix) = &check.fix {
+            writeln!(out, "       fix: {fix}")?;
+        }
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{failed} check(s) failed");
+    }
+    Ok(())
+}
+
+// the config conversions start from, with the checks of the file and its settings
+fn check_config(path: Option<&Path>) -> (ComicConfig, Vec<Check>) {
+    const NAME: &str = "config";
+    let explicit = path.is_some();
+    let Some(path) = path
+        .map(Path::to_path_buf)
+        .or_else(ComicConfig::config_path)
+    else {
+        let check = Check::problem(
+            NAME,
+            Status::Warn,
+            "no home directory, the built-in defaults are used",
+            "set HOME, or pass --config to the commands that take one",
+        );
+        return (ComicConfig::default(), vec![check]);
+    };
+    if !explicit && !path.exists() {
+        let detail = format!(
+            "{} doesn't exist, the built-in defaults are used",
+            path.display()
+        );
+        return (ComicConfig::default(), vec![Check::ok(NAME, detail)]);
+    }
+
+    let config = match ComicConfig::load_from(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            let fix = match explicit {
+                true => "fix the file, the error says where it's wrong".to_string(),
+                false => format!(
+                    "fix the file, or delete it and save the settings again from the TUI \
+                     (the CLI ignores it with a warning until then): {}",
+                    path.display()
+                ),
+            };
+            let check = Check::problem(NAME, Status::Fail, format!("{e:#}"), fix);
+            return (ComicConfig::default(), vec![check]);
+        }
+    };
+
+    let mut checks = vec![Check::ok(NAME, format!("{} is valid", path.display()))];
+    if config.output_format == OutputFormat::Mobi
+        && !matches!(config.image_format, ImageFormat::Jpeg { .. })
+    {
+        checks.push(Check::problem(
+            NAME,
+            Status::Warn,
+            "MOBI output with PNG or WebP pages, JPEG is used instead",
+            "set the image format to JPEG to silence the warning",
+        ));
+    }
+    if let Some(watermark) = &config.watermark {
+        if !watermark.path.is_file() {
+            checks.push(Check::problem(
+                NAME,
+                Status::Fail,
+                format!("the watermark {} doesn't exist", watermark.path.display()),
+                "point `watermark.path` at an image, or remove the watermark",
+            ));
+        }
+    }
+    (config, checks)
+}
+
+fn check_kindlegen(config: &ComicConfig) -> Check {
+    const NAME: &str = "kindlegen";
+    match comically::kindlegen_version() {
+        Some(version) => Check::ok(NAME, version),
+        None => {
+            // only a problem for the conversions that make MOBIs
+            let status = match config.output_format {
+                OutputFormat::Mobi => Status::Fail,
+                _ => Status::Warn,
+            };
+            Check::problem(
+                NAME,
+                status,
+                "not found in the PATH, MOBI output won't work",
+                "install Kindle Previewer 3, which includes kindlegen, and add the folder \
+                 with kindlegen to the PATH, or convert to EPUB or CBZ",
+            )
+        }
+    }
+}
+
+fn check_temp_dir(config: &ComicConfig) -> Check {
+    const NAME: &str = "temp dir";
+    let temp = TempRoot::for_config(config);
+    let written = temp.create("doctor").and_then(|dir| {
+        std::fs::write(dir.path().join("probe"), b"comically")?;
+        Ok(())
+    });
+    match written {
+        Ok(()) => Check::ok(NAME, format!("{} is writable", temp.dir().display())),
+        Err(e) => Check::problem(
+            NAME,
+            Status::Fail,
+            format!("{e:#}"),
+            "pass --temp-dir with a writable directory, or set `temp_dir` in the config file",
+        ),
+    }
+}
+
+// only checked when the config asks for super-resolution
+fn check_upscaler(config: &ComicConfig) -> Option<Check> {
+    if config.upscale != UpscalePolicy::SuperResolution {
+        return None;
+    }
+    Some(match comically::image::upscale::check() {
+        Ok(()) => Check::ok("upscaler", "realesrgan-ncnn-vulkan runs"),
+        Err(reason) => Check::problem(
+            "upscaler",
+            Status::Warn,
+            format!("{reason}, pages are resampled instead"),
+            "put realesrgan-ncnn-vulkan in the PATH or set COMICALLY_REALESRGAN, and build \
+             comically-cli with `--features super-resolution`",
+        ),
+    })
+}
+
+// only checked when the config asks for the GPU
+fn check_gpu(config: &ComicConfig) -> Option<Check> {
+    if config.backend != Backend::Gpu {
+        return None;
+    }
+    Some(match comically::image::gpu::check() {
+        Ok(()) => Check::ok("gpu", "a GPU adapter was found"),
+        Err(reason) => Check::problem(
+            "gpu",
+            Status::Warn,
+            format!("{reason}, pages are processed on the CPU instead"),
+            "build comically-cli with `--features gpu` on a machine with Vulkan, Metal, \
+             DirectX 12 or OpenGL drivers",
+        ),
+    })
+}
+
+// only checked when the config reads text pages
+fn check_ocr(config: &ComicConfig) -> Option<Check> {
+    if !config.text_pages {
+        return None;
+    }
+    Some(match comically::image::ocr::check() {
+        Ok(()) => Check::ok("ocr", "tesseract runs"),
+        Err(reason) => Check::problem(
+            "ocr",
+            Status::Warn,
+            format!("{reason}, text pages stay images"),
+            "put tesseract in the PATH or set COMICALLY_TESSERACT, and build comically-cli \
+             with `--features ocr`",
+        ),
+    })
+}
+
+fn check_archives() -> Vec<Check> {
+    let mut checks = vec![
+        Check::ok(
+            "archives",
+            "CBZ/ZIP and CBR/RAR are read with built-in libraries",
+        ),
+        Check::ok(
+            "pages",
+            "JPEG, PNG, TIFF and BMP pages are read with built-in decoders",
+        ),
+    ];
+    checks.push(match cfg!(feature = "heif") {
+        true => Check::ok("heif", "HEIC/HEIF pages are read with libheif"),
+        false => Check::problem(
+            "heif",
+            Status::Warn,
+            "HEIC/HEIF pages are skipped",
+            "install libheif and build comically-cli with `--features heif`",
+        ),
+    });
+    checks
+}
+
+fn check_terminal() -> Check {
+    const NAME: &str = "terminal";
+    if !std::io::stdout().is_terminal() {
+        return Check::ok(
+            NAME,
+            "not a terminal, run doctor in the TUI's terminal to check it",
+        );
+    }
+    let env = |name: &str| std::env::var(name).ok();
+    let detail = match image_protocol(env) {
+        Some(protocol) => format!("TUI previews will likely use {protocol}"),
+        None => {
+            return Check::problem(
+                NAME,
+                Status::Warn,
+                "no image protocol detected, TUI previews fall back to blurry half blocks",
+                "use a terminal with kitty graphics, sixel or iTerm2 images (kitty, WezTerm, \
+                 Ghostty, foot, Konsole, iTerm2)",
+            )
+        }
+    };
+    match env("TMUX") {
+        Some(_) => Check::problem(
+            NAME,
+            Status::Warn,
+            format!("{detail}, but tmux may not pass images through"),
+            "`set -g allow-passthrough on` in ~/.tmux.conf, or run the TUI outside of tmux",
+        ),
+        None => Check::ok(NAME, detail),
+    }
+}
+
+// guessed from the environment, the TUI asks the terminal itself
+fn image_protocol(env: impl Fn(&str) -> Option<String>) -> Option<&'static str> {
+    let term = env("TERM").unwrap_or_default();
+    let program = env("TERM_PROGRAM").unwrap_or_default();
+    if env("KITTY_WINDOW_ID").is_some()
+        || term == "xterm-kitty"
+        || term == "xterm-ghostty"
+        || program == "ghostty"
+        || program == "WezTerm"
+    {
+        Some("kitty graphics")
+    } else if program == "iTerm.app" || env("LC_TERMINAL").as_deref() == Some("iTerm2") {
+        Some("iTerm2 inline images")
+    } else if term.starts_with("foot")
+        || term.starts_with("mlterm")
+        || term.contains("sixel")
+        || env("KONSOLE_VERSION").is_some()
+    {
+        Some("sixel")
+    } else {
+        None
+    }
+}
+
+#[test]
+fn report_fails_on_failed_checks() {
+    let passing = [
+        Check::ok("archives", "read with built-in libraries"),
+        Check::problem("heif", Status::Warn, "pages are skipped", "install libheif"),
+    ];
+    let mut out = Vec::new();
+    report(&passing, &mut out).unwrap();
+    let lines = [
+        "[  ok] archives: read with built-in libraries",
+        "[warn] heif: pages are skipped",
+        "       fix: install libheif",
+    ];
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        lines.map(|line| format!("{line}\n")).concat()
+    );
+
+    let failing = [
+        Check::ok("config", "valid"),
+        Check::problem("kindlegen", Status::Fail, "not found", "install it"),
+        Check::problem("temp dir", Status::Fail, "read-only", "pass --temp-dir"),
+    ];
+    let mut out = Vec::new();
+    let e = report(&failing, &mut out).unwrap_err();
+    assert_eq!(e.to_string(), "2 check(s) failed");
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("[FAIL] kindlegen: not found\n       fix: install it\n"));
+    assert!(out.contains("[FAIL] temp dir: read-only\n"));
+}
+
+#[test]
+fn config_checks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    let status = |path: &Path| {
+        let (_, checks) = check_config(Some(path));
+        checks.iter().map(|check| check.status).collect::<Vec<_>>()
+    };
+
+    // an explicit path has to exist
+    assert_eq!(status(&path), [Status::Fail]);
+
+    let mut config = ComicConfig::default();
+    std::fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+    assert_eq!(status(&path), [Status::Ok]);
+
+    config.output_format = OutputFormat::Mobi;
+    config.image_format = ImageFormat::WebP { quality: 80 };
+    config.watermark = Some(comically::Watermark::new(dir.path().join("missing.png")));
+    std::fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+    assert_eq!(status(&path), [Status::Ok, Status::Warn, Status::Fail]);
+
+    std::fs::write(&path, "{ not json").unwrap();
+    assert_eq!(status(&path), [Status::Fail]);
+}
+
+#[test]
+fn image_protocols_come_from_the_environment() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    };
+    assert_eq!(
+        image_protocol(env(&[("TERM", "xterm-kitty")])),
+        Some("kitty graphics")
+    );
+    assert_eq!(
+        image_protocol(env(&[("LC_TERMINAL", "iTerm2")])),
+        Some("iTerm2 inline images")
+    );
+    assert_eq!(image_protocol(env(&[("TERM", "foot")])), Some("sixel"));
+    assert_eq!(image_protocol(env(&[("TERM", "xterm-256color")])), None);
+}
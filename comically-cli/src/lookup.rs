@@ -0,0 +1,84 @@
+//! `--fetch-metadata`: finding each comic's series online before converting it
+
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::sync::Mutex;
+
+use comically::metadata::fetch::{self, Candidate, Source, SourceConfig};
+use comically::ComicConfig;
+
+pub struct Lookup {
+    source: Source,
+    sources: SourceConfig,
+    // picking a match needs someone to ask, otherwise the best match is taken
+    interactive: bool,
+    // volumes of a series search for the same name, they're asked about once
+    chosen: HashMap<String, Option<Candidate>>,
+}
+
+impl Lookup {
+    pub fn new(source: Option<Source>, sources: SourceConfig) -> Self {
+        Self {
+            source: source.unwrap_or(sources.default),
+            sources,
+            interactive: std::io::stdin().is_terminal() && std::io::stderr().is_terminal(),
+            chosen: HashMap::new(),
+        }
+    }
+
+    /// The series `query` confirmed, `None` when nothing matched or the match was skipped.
+    /// Failed lookups are only logged, the comic is converted without the metadata.
+    pub fn find(&mut self, query: &str) -> Option<Candidate> {
+        if let Some(chosen) = self.chosen.get(query) {
+            return chosen.clone();
+        }
+
+        let candidates = match fetch::search(self.source, &self.sources, query) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                log::warn!("Failed to look up `{query}` on {}: {e:#}", self.source);
+                return None;
+            }
+        };
+        let chosen = self.choose(query, candidates);
+        self.chosen.insert(query.to_string(), chosen.clone());
+        chosen
+    }
+
+    fn choose(&self, query: &str, mut candidates: Vec<Candidate>) -> Option<Candidate> {
+        if candidates.is_empty() {
+            log::warn!("No match for `{query}` on {}", self.source);
+            return None;
+        }
+        if !self.interactive {
+            let best = candidates.swap_remove(0);
+            log::info!("Using {} for `{query}`", best.label());
+            return Some(best);
+        }
+
+        let mut items: Vec<String> = candidates.iter().map(Candidate::label).collect();
+        items.push("None of these".into());
+        let picked = dialoguer::Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
+            .with_prompt(format!("Series of `{query}` on {}", self.source))
+            .items(&items)
+            .default(0)
+            .interact()
+            .ok()?;
+        (picked < candidates.len()).then(|| candidates.swap_remove(picked))
+    }
+}
+
+/// Fills in the series `lookup` finds for the comic titled `title`, searching for the
+/// series the comic already names or else the one in its file name
+pub fn apply(lookup: &Mutex<Lookup>, title: &str, config: &mut ComicConfig) {
+    let query = match &config.series {
+        Some(series) => series.name.clone(),
+        None => comically::filename::parse(title).series,
+    };
+    let Ok(mut lookup) = lookup.lock() else {
+        return;
+    };
+    if let Some(candidate) = lookup.find(&query) {
+        candidate.apply(config);
+    }
+}
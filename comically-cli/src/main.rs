@@ -1,36 +1,73 @@
+mod analyze;
+mod doctor;
+mod lookup;
+mod notify;
+mod sync;
+mod wizard;
+
 use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use clap::builder::{PossibleValue, TypedValueParser};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use imageproc::image::DynamicImage;
 
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use comically::archive::{FilenameEncoding, SkipReason, SkippedEntry};
+use comically::batch::{self, Batch, ComicReport, Event, Input, PageStats, Stage};
+use comically::checkpoint::Checkpoint;
+use comically::contact_sheet::ContactSheet;
 use comically::device::Device;
-use comically::{ComicConfig, ComicFile, ImageFormat, OutputFormat, PngCompression, SplitStrategy};
+use comically::dir_config::DirConfig;
+use comically::metadata::fetch::{Source, SourceConfig};
+use comically::sample::{SampleArchive, SampleOptions};
+use comically::source::MoveSource;
+use comically::temp::TempRoot;
+use comically::{
+    AdaptiveQuality, AnimatedPages, Backend, ComicConfig, ComicFile, Concurrency, FixedCrop,
+    GammaCurve, ImageFormat, Lut, MarginColor, OutputFormat, OutputNaming, PageBackground,
+    PageFilter, PageRanges, PageSide, PlaceholderPages, PngCompression, Rotation, Series,
+    SplitStrategy, StampPosition, TitlePage, TitlePagePosition, TranslationOverlay, UpscalePolicy,
+    Watermark,
+};
+use lookup::Lookup;
+use notify::Notifications;
 
 #[derive(Parser)]
 #[command(name = "comically-cli")]
 #[command(about = "Convert comic archives (CBZ/CBR) to e-reader formats", long_about = None)]
 #[command(version)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Input comic file (CBZ or CBR)
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input comic files (CBZ or CBR), prompts for files and settings when omitted in a terminal
     #[arg(value_name = "INPUT")]
-    input: PathBuf,
+    inputs: Vec<PathBuf>,
 
     /// Output directory
     #[arg(short, long, value_name = "DIR", default_value = ".")]
     output_dir: PathBuf,
 
+    /// Config file to start from, defaults to the one saved by the TUI
+    #[arg(short, long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
     /// Output format
-    #[arg(short, long, value_enum, default_value_t = OutputFormatArg::Cbz)]
-    format: OutputFormatArg,
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormatArg>,
 
     /// Device preset
-    #[arg(
-        short,
-        long,
-        value_name = "DEVICE",
-        default_value = "kindle-paperwhite"
-    )]
-    device: String,
+    #[arg(short, long, value_name = "DEVICE", value_parser = DeviceParser)]
+    device: Option<String>,
+
+    /// Convert for several devices in one pass, e.g. `kindle-pw-11:mobi` or `1072x1448:epub`.
+    /// Repeatable, each target is written to `<output-dir>/<device>/`
+    #[arg(long, value_name = "DEVICE:FORMAT", value_parser = parse_target, conflicts_with_all = ["device", "format"])]
+    target: Vec<Target>,
 
     /// Custom device width (requires --device custom)
     #[arg(long, value_name = "PIXELS")]
@@ -40,47 +77,323 @@ struct Args {
     #[arg(long, value_name = "PIXELS")]
     height: Option<u32>,
 
+    /// Fit pages so their longest side is this, instead of the device's screen, for
+    /// EPUBs read on screens of unknown size
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    max_dimension: Option<u32>,
+
+    /// Also embed a copy of each EPUB page with this longest side, which readers that
+    /// support `srcset` pick on small screens
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    srcset: Option<u32>,
+
+    /// Stack the pages of CBZs into strips this tall, for readers that scroll webtoons
+    /// continuously
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    long_strip: Option<u32>,
+
+    /// Read pages of plain text, like afterwords, with OCR into text EPUB readers reflow,
+    /// needs Tesseract and comically-cli built with the `ocr` feature
+    #[arg(long)]
+    text_pages: bool,
+
     /// Image format
-    #[arg(long, value_enum, default_value_t = ImageFormatArg::Jpeg)]
-    image_format: ImageFormatArg,
+    #[arg(long, value_enum)]
+    image_format: Option<ImageFormatArg>,
 
     /// JPEG/WebP quality (0-100)
-    #[arg(long, value_name = "QUALITY", default_value_t = 85)]
-    quality: u8,
+    #[arg(long, value_name = "QUALITY")]
+    quality: Option<u8>,
+
+    /// Pick JPEG/WebP quality per page within MIN-MAX from its detail level, or `off`
+    #[arg(long, value_name = "MIN-MAX", value_parser = parse_adaptive_quality)]
+    adaptive_quality: Option<AdaptiveQualityArg>,
 
     /// PNG compression level
-    #[arg(long, value_enum, default_value_t = PngCompressionArg::Default)]
-    png_compression: PngCompressionArg,
+    #[arg(long, value_enum)]
+    png_compression: Option<PngCompressionArg>,
 
     /// Brightness adjustment (-100 to +100)
-    #[arg(
-        long,
-        value_name = "VALUE",
-        default_value_t = -10,
-        allow_hyphen_values = true
-    )]
-    brightness: i32,
+    #[arg(long, value_name = "VALUE", allow_hyphen_values = true)]
+    brightness: Option<i32>,
+
+    /// Bring yellowed or gray paper to white, estimating the paper color from the
+    /// borders of each page
+    #[arg(long)]
+    paper_white: bool,
+
+    /// Contrast as an S-curve around mid-gray (-100 to +100), unlike gamma it keeps
+    /// black and white in place
+    #[arg(long, value_name = "VALUE", allow_hyphen_values = true)]
+    contrast: Option<i32>,
 
     /// Gamma correction (0.1 to 3.0)
-    #[arg(long, value_name = "VALUE", default_value_t = 1.8)]
-    gamma: f32,
+    #[arg(long, value_name = "VALUE")]
+    gamma: Option<f32>,
+
+    /// Suggest brightness, contrast and gamma from the tones of about 10 pages sampled
+    /// over the inputs, in place of --brightness, --contrast and --gamma
+    #[arg(long, conflicts_with_all = ["brightness", "contrast", "gamma"])]
+    auto_tune: bool,
 
-    /// Margin color
-    #[arg(long, value_enum, default_value_t = MarginColorArg::None)]
-    margin_color: MarginColorArg,
+    /// Shape of the gamma correction: power, srgb (on linear light) or the path of a
+    /// file with the 256 output levels of a calibrated device, which replaces --gamma
+    #[arg(long, value_name = "CURVE", value_parser = parse_gamma_curve)]
+    gamma_curve: Option<GammaCurve>,
+
+    /// Margin color: none, black, white, auto, a gray level (0-255) or #rrggbb
+    #[arg(long, value_name = "COLOR", value_parser = parse_margin_color)]
+    margin_color: Option<MarginColorArg>,
 
     /// Page split strategy
-    #[arg(long, value_enum, default_value_t = SplitStrategyArg::RotateSplit)]
-    split: SplitStrategyArg,
+    #[arg(long, value_enum)]
+    split: Option<SplitStrategyArg>,
+
+    /// Keep double-page spreads whole as landscape pages, shown on their own by readers
+    /// that support spreads (implies `--split none`)
+    #[arg(long)]
+    landscape_spreads: bool,
+
+    /// Pipe each page through this shell command after the tone adjustments, e.g. an
+    /// upscaler: the page is written to its stdin as PNG and read back from its stdout
+    #[arg(long, value_name = "COMMAND")]
+    page_filter: Option<String>,
+
+    /// How many pages go through --page-filter at once
+    #[arg(long, value_name = "N", requires = "page_filter", value_parser = clap::value_parser!(u32).range(1..))]
+    page_filter_jobs: Option<u32>,
+
+    /// Turn the cover upright when the release has it sideways (a landscape file with
+    /// portrait art), so library thumbnails aren't sideways
+    #[arg(long)]
+    auto_rotate_cover: bool,
+
+    /// Start each chapter (folder in the archive) on this side of a two-page spread in
+    /// EPUB/MOBI, leaving the page before it alone when needed
+    #[arg(long, value_enum, value_name = "SIDE")]
+    chapter_side: Option<PageSideArg>,
+
+    /// What becomes of animated GIF, PNG and WebP pages: their first or middle frame is
+    /// converted, or they're skipped
+    #[arg(long, value_enum, value_name = "POLICY")]
+    animated_pages: Option<AnimatedPagesArg>,
+
+    /// What becomes of empty files and placeholder images (e.g. 1x1) among the pages:
+    /// they're skipped with a warning, or fail the comic
+    #[arg(long, value_enum, value_name = "POLICY")]
+    placeholder_pages: Option<PlaceholderPagesArg>,
+
+    /// Pages of a comic decoded at the same time: `auto` takes in as many as fit in the
+    /// available memory, judging by their size, or a fixed number
+    #[arg(long, value_name = "auto|N", value_parser = parse_concurrency)]
+    concurrency: Option<Concurrency>,
+
+    /// Rotate every output page, for devices read in landscape
+    #[arg(long, value_enum)]
+    rotate: Option<RotationArg>,
+
+    /// How pages smaller than the screen are enlarged
+    #[arg(long, value_enum, value_name = "POLICY")]
+    upscale: Option<UpscalePolicyArg>,
+
+    /// Where pages are resized and their tones adjusted
+    #[arg(long, value_enum)]
+    backend: Option<BackendArg>,
+
+    /// Background around EPUB/MOBI pages, black suits readers in night mode
+    #[arg(long, value_enum)]
+    page_background: Option<PageBackgroundArg>,
+
+    /// Stamp the page number in a corner of every page
+    #[arg(long, value_enum, value_name = "CORNER")]
+    page_numbers: Option<StampPositionArg>,
+
+    /// Height of the page numbers in pixels
+    #[arg(long, value_name = "PX", requires = "page_numbers")]
+    page_number_size: Option<u32>,
+
+    /// Opacity of the page numbers, from 0.0 to 1.0
+    #[arg(long, value_name = "OPACITY", requires = "page_numbers")]
+    page_number_opacity: Option<f32>,
+
+    /// Put the chapter (the page's folder in the archive) before the page number
+    #[arg(long, requires = "page_numbers")]
+    page_number_chapter: bool,
+
+    /// Overlay an image (e.g. a PNG library stamp) in a corner of every page
+    #[arg(long, value_name = "IMAGE")]
+    watermark: Option<PathBuf>,
+
+    /// Corner the watermark is drawn in
+    #[arg(long, value_enum, value_name = "CORNER", requires = "watermark")]
+    watermark_position: Option<StampPositionArg>,
+
+    /// Opacity of the watermark, from 0.0 to 1.0
+    #[arg(long, value_name = "OPACITY", requires = "watermark")]
+    watermark_opacity: Option<f32>,
+
+    /// Only put the watermark on the cover
+    #[arg(long, requires = "watermark")]
+    watermark_cover_only: bool,
+
+    /// Letter translations from `<comic>.translations.json` over the pages
+    #[arg(long)]
+    translations: bool,
+
+    /// Font for the translations, instead of the built-in bitmap font
+    #[arg(long, value_name = "FILE", requires = "translations")]
+    translation_font: Option<PathBuf>,
+
+    /// Add a page with the title and credits from the series and metadata of the comic
+    #[arg(long, value_enum, value_name = "POSITION")]
+    title_page: Option<TitlePagePositionArg>,
+
+    /// Font for the title page, instead of the built-in bitmap font
+    #[arg(long, value_name = "FILE", requires = "title_page")]
+    title_page_font: Option<PathBuf>,
 
     /// Right-to-left reading direction (manga mode)
-    #[arg(long, default_value_t = true)]
+    #[arg(long, overrides_with_all = ["ltr", "detect_direction"])]
     rtl: bool,
 
+    /// Left-to-right reading direction
+    #[arg(long, overrides_with_all = ["rtl", "detect_direction"])]
+    ltr: bool,
+
+    /// Detect the reading direction of each comic, falling back to the configured one
+    #[arg(long, overrides_with_all = ["rtl", "ltr"])]
+    detect_direction: bool,
+
+    /// Enable automatic cropping
+    #[arg(long, overrides_with = "no_auto_crop")]
+    auto_crop: bool,
+
     /// Disable automatic cropping
-    #[arg(long, default_value_t)]
+    #[arg(long, overrides_with = "auto_crop")]
     no_auto_crop: bool,
 
+    /// Dither pages to the 16 gray levels of e-ink screens, on by default for them
+    #[arg(long, overrides_with = "no_dither")]
+    dither: bool,
+
+    /// Don't dither pages
+    #[arg(long, overrides_with = "dither")]
+    no_dither: bool,
+
+    /// Keep color pages in color, on by default for color screens
+    #[arg(long, overrides_with = "grayscale")]
+    keep_color: bool,
+
+    /// Convert color pages to grayscale
+    #[arg(long, overrides_with = "keep_color")]
+    grayscale: bool,
+
+    /// Write a .sha256 checksum next to each output, checked by `inspect`
+    #[arg(long)]
+    checksum: bool,
+
+    /// Refresh an OPDS catalog of the output directory after converting
+    #[arg(long)]
+    opds: bool,
+
+    /// Don't reuse or store pages from earlier conversions
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Trim the page cache to this many megabytes after converting, least recently used
+    /// pages first (2048 by default, 0 never trims it)
+    #[arg(long, value_name = "MB", conflicts_with = "no_cache")]
+    cache_size: Option<u32>,
+
+    /// Copy grayscale JPEGs that already fit the device at about the target quality
+    /// through without re-encoding them, leaving their tones alone
+    #[arg(long)]
+    reuse_pages: bool,
+
+    /// Directory for temporary files in place of the system's, e.g. on a fast SSD
+    #[arg(long, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// Also read the pages of zip/cbz archives nested in the comic's archive
+    #[arg(long)]
+    nested_archives: bool,
+
+    /// How zip entries named without the UTF-8 flag are read: UTF-8 for some macOS and
+    /// Linux tools, Shift-JIS for Japanese versions of Windows, CP437 for old DOS tools,
+    /// or auto to detect it for each archive
+    #[arg(long, value_name = "ENCODING")]
+    filename_encoding: Option<FilenameEncodingArg>,
+
+    /// Pixels always cut from the edges of every page before auto-crop, e.g.
+    /// `bottom=60` for a footer banner
+    #[arg(long, value_name = "REGIONS")]
+    fixed_crop: Option<FixedCrop>,
+
+    /// Only convert these pages, numbered from 1 in file name order, e.g. `5-120,130-`
+    #[arg(long, value_name = "RANGES")]
+    pages: Option<PageRanges>,
+
+    /// Produce identical files for identical input: content derived book ids and
+    /// fixed zip timestamps
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Name outputs like Kindle Comic Converter does, e.g. `.kepub.epub` for Kobo
+    #[arg(long)]
+    kcc_naming: bool,
+
+    /// Name outputs from their series and numbering, e.g. `{series} v{volume:2}`
+    #[arg(long, value_name = "TEMPLATE")]
+    output_template: Option<String>,
+
+    /// Split MOBI outputs whose pages take more than this many megabytes into parts,
+    /// KindleGen fails on EPUBs around 650 MB (600 by default, 0 never splits)
+    #[arg(long, value_name = "MB")]
+    mobi_part_size: Option<u32>,
+
+    /// Print how the settings map to Kindle Comic Converter flags, with the equivalent
+    /// KCC command, instead of converting
+    #[arg(long)]
+    compare_kcc: bool,
+
+    /// Series the comics belong to, written to EPUB/MOBI metadata. Defaults to the
+    /// `Series` of each comic's ComicInfo.xml, or its file name when numbered
+    #[arg(long, value_name = "NAME")]
+    series: Option<String>,
+
+    /// Volume number in the series, defaults to the `Number` of the ComicInfo.xml or
+    /// the number in the file name
+    #[arg(long, value_name = "N", requires = "series")]
+    series_index: Option<f32>,
+
+    /// Look up each comic's series on AniList or ComicVine for its authors and
+    /// description. Defaults to the `metadata_sources` section of the config
+    #[arg(long, value_name = "SOURCE", num_args = 0..=1)]
+    fetch_metadata: Option<Option<MetadataSourceArg>>,
+
+    /// Convert every input again instead of resuming an interrupted batch
+    #[arg(long)]
+    restart: bool,
+
+    /// Convert only the comics the last batch in the output directory failed on, with
+    /// the settings and targets it used
+    #[arg(long, conflicts_with_all = ["inputs", "target", "restart"])]
+    retry_failed: bool,
+
+    /// Move each comic that converted without problems to a `converted` folder next to
+    /// it, or to the trash, once its outputs are written
+    #[arg(long, value_enum, value_name = "WHERE")]
+    move_source: Option<MoveSourceArg>,
+
+    /// Comics converted at the same time, their pages are processed in parallel either way
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
+
+    /// Convert in the background: at a lower priority, on half the cores and pausing
+    /// between pages, one comic at a time
+    #[arg(long, conflicts_with = "jobs")]
+    nice: bool,
+
     /// Verbose output
     #[arg(short, long, default_value_t)]
     verbose: bool,
@@ -91,8 +404,8 @@ struct Args {
 }
 
 impl Args {
-    fn parse_device(&self) -> Result<Device> {
-        if self.device == "custom" {
+    fn parse_device(&self, device: &str) -> Result<Device> {
+        if device == "custom" {
             let w = self
                 .width
                 .context("--width is required when using --device custom")?;
@@ -105,10 +418,286 @@ impl Args {
             });
         }
 
-        comically::device::Preset::try_from(self.device.as_str())
-            .map(Into::into)
-            .map_err(|e| anyhow::anyhow!(e))
+        // also KCC profile names, for users coming from it, and WIDTHxHEIGHT
+        Ok(device.parse()?)
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a shell completion script to stdout
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print the manpage to stdout
+    Manpage,
+    /// Show information about a converted file and verify its checksum
+    Inspect {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Write or refresh an OPDS catalog of the converted files in a directory
+    Opds {
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Download comics from a Komga or Kavita server and convert them
+    Sync {
+        /// Server URL, with its credentials in the `servers` section of the config file
+        #[arg(long, value_name = "URL")]
+        server: String,
+
+        /// Only sync this library (ID on the server)
+        #[arg(long, value_name = "ID")]
+        library: Option<String>,
+
+        /// Directory to store `<series>/<book>` folders in
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        output_dir: PathBuf,
+
+        /// The output directory is the server's library folder, rescan it afterwards
+        #[arg(long)]
+        push: bool,
+
+        /// Config file with the conversion settings and server credentials
+        #[arg(short, long, value_name = "PATH")]
+        config: Option<PathBuf>,
+
+        /// Quiet mode (minimal output)
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Extract the cover of a comic, optionally resized or processed like a page
+    Cover {
+        #[arg(value_name = "ARCHIVE")]
+        archive: PathBuf,
+
+        /// Output image, encoded as JPEG, PNG or WebP from its extension
+        /// [default: <title>.jpg]
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Maximum width of the cover
+        #[arg(long)]
+        width: Option<u32>,
+
+        /// Maximum height of the cover
+        #[arg(long)]
+        height: Option<u32>,
+
+        /// Run the cover through the conversion settings (crop, gamma, margins...)
+        #[arg(long)]
+        process: bool,
+
+        /// Config file with the conversion settings used by --process
+        #[arg(short, long, value_name = "PATH")]
+        config: Option<PathBuf>,
+    },
+    /// Render a grid of page thumbnails of a comic into one image
+    ContactSheet {
+        #[arg(value_name = "ARCHIVE")]
+        archive: PathBuf,
+
+        /// Output image, encoded as JPEG, PNG or WebP from its extension
+        /// [default: <title>.sheet.jpg]
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Thumbnails per row
+        #[arg(long, default_value_t = 4)]
+        columns: u32,
+
+        /// Rows of thumbnails, pages are sampled evenly when they don't all fit
+        #[arg(long, default_value_t = 4)]
+        rows: u32,
+
+        /// Width of each thumbnail in pixels
+        #[arg(long, default_value_t = 300)]
+        cell_width: u32,
+    },
+    /// Show what the conversion sees in each page of a comic: its size, whether it's in
+    /// color or a spread, the margins auto-crop removes, its tones and detail, and what
+    /// it would weigh in each image format
+    Analyze {
+        #[arg(value_name = "ARCHIVE")]
+        archive: PathBuf,
+
+        /// Print as JSON, with the luminance histogram of each page
+        #[arg(long)]
+        json: bool,
+    },
+    /// Salvage the readable pages of a damaged CBZ or CBR into a new CBZ
+    Repair {
+        #[arg(value_name = "ARCHIVE")]
+        archive: PathBuf,
+
+        /// Repaired archive [default: <title>.repaired.cbz]
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Manage the cache of converted pages
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Manage the temporary files of conversions
+    Temp {
+        #[command(subcommand)]
+        command: TempCommand,
+    },
+    /// Start the TUI, the same as the `comically` binary
+    #[cfg(feature = "tui")]
+    Tui(comically_tui::Args),
+    /// Check KindleGen, the config file, the temporary directory, the archive formats and
+    /// the terminal, and print how to fix what's wrong
+    Doctor {
+        /// Config file to check in place of the one saved by the TUI
+        #[arg(short, long, value_name = "PATH")]
+        config: Option<PathBuf>,
+    },
+    /// Write a comic of generated pages, to reproduce a bug without sharing the comic
+    #[command(hide = true)]
+    Sample {
+        #[arg(value_name = "PATH", default_value = "sample.cbz")]
+        output: PathBuf,
+
+        /// Number of pages
+        #[arg(long, default_value_t = 10)]
+        pages: usize,
+
+        /// Size of the pages, repeat it to alternate between sizes
+        #[arg(long = "size", value_name = "WxH", value_parser = parse_size, default_value = "600x800")]
+        sizes: Vec<(u32, u32)>,
+
+        /// Make every Nth page a double page spread
+        #[arg(long, value_name = "N")]
+        spread_every: Option<usize>,
+
+        /// Make every Nth page a color page
+        #[arg(long, value_name = "N")]
+        color_every: Option<usize>,
+
+        /// Number of pages that can't be decoded
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        broken: usize,
+
+        /// Add the files macOS, Windows and scanlators leave in archives
+        #[arg(long)]
+        junk: bool,
+    },
+    /// List the device presets accepted by --device
+    Devices {
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Remove pages that weren't used recently
+    Gc {
+        /// Remove pages unused for this many days, 0 empties the cache
+        #[arg(long, value_name = "DAYS", default_value_t = 30)]
+        max_age: u64,
+        /// Then remove the least recently used pages until the rest take this many
+        /// megabytes
+        #[arg(long, value_name = "MB")]
+        max_size: Option<u64>,
+    },
+    /// Print the cache location
+    Path,
+}
+
+#[derive(Subcommand)]
+enum TempCommand {
+    /// Remove what conversions that crashed or were killed left behind
+    Sweep,
+    /// Print the location of temporary files and how much space they take
+    Path,
+}
+
+// accepts anything `Device::from_str` does, listing the preset ids for help and completions
+#[derive(Clone)]
+struct DeviceParser;
+
+impl TypedValueParser for DeviceParser {
+    type Value = String;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        clap::builder::StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        let presets = comically::device::Preset::iter()
+            .map(|preset| PossibleValue::new(preset.id()).help(preset.name().to_string()));
+        let custom = PossibleValue::new("custom").help("Use --width and --height");
+        Some(Box::new(presets.chain(std::iter::once(custom))))
+    }
+}
+
+#[derive(Clone)]
+struct Target {
+    device: Device,
+    format: OutputFormat,
+}
+
+fn parse_target(s: &str) -> Result<Target, String> {
+    let (device, format) = s
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected DEVICE:FORMAT, got `{s}`"))?;
+
+    let device: Device = device.parse().map_err(|e| format!("{e}"))?;
+    let format = OutputFormatArg::from_str(format, true)
+        .map_err(|_| format!("unsupported format `{format}`, expected cbz, epub or mobi"))?
+        .into();
+
+    Ok(Target { device, format })
+}
+
+#[derive(Copy, Clone)]
+struct AdaptiveQualityArg(Option<AdaptiveQuality>);
+
+fn parse_adaptive_quality(s: &str) -> Result<AdaptiveQualityArg, String> {
+    if s == "off" {
+        return Ok(AdaptiveQualityArg(None));
     }
+
+    let (min, max) = s
+        .split_once('-')
+        .ok_or_else(|| "expected MIN-MAX, e.g. 70-90, or off".to_string())?;
+    let min: u8 = min.parse().map_err(|e| format!("invalid minimum: {e}"))?;
+    let max: u8 = max.parse().map_err(|e| format!("invalid maximum: {e}"))?;
+    if min > max || max > 100 {
+        return Err("expected 0 <= MIN <= MAX <= 100".to_string());
+    }
+
+    Ok(AdaptiveQualityArg(Some(AdaptiveQuality { min, max })))
+}
+
+fn parse_concurrency(s: &str) -> Result<Concurrency, String> {
+    if s == "auto" {
+        return Ok(Concurrency::Auto);
+    }
+    match s.parse::<usize>() {
+        Ok(0) | Err(_) => Err("expected auto or a number of pages above 0".to_string()),
+        Ok(pages) => Ok(Concurrency::Fixed(pages)),
+    }
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let size = s.split_once('x').and_then(|(width, height)| {
+        let width = width.parse::<u32>().ok().filter(|&width| width > 0)?;
+        let height = height.parse::<u32>().ok().filter(|&height| height > 0)?;
+        Some((width, height))
+    });
+    size.ok_or_else(|| "expected a size in pixels like 600x800".to_string())
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
@@ -135,9 +724,10 @@ enum ImageFormatArg {
     Webp,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[derive(Copy, Clone, PartialEq, Eq, Default, ValueEnum)]
 enum PngCompressionArg {
     Fast,
+    #[default]
     Default,
     Best,
 }
@@ -152,204 +742,1455 @@ impl From<PngCompressionArg> for PngCompression {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
-enum MarginColorArg {
-    None,
-    Black,
-    White,
+fn parse_gamma_curve(s: &str) -> Result<GammaCurve, String> {
+    match s {
+        "power" => Ok(GammaCurve::Power),
+        "srgb" => Ok(GammaCurve::Srgb),
+        path => Lut::load(Path::new(path))
+            .map(GammaCurve::Lut)
+            .map_err(|e| format!("{e:#}")),
+    }
+}
+
+#[derive(Copy, Clone)]
+struct MarginColorArg(Option<MarginColor>);
+
+fn parse_margin_color(s: &str) -> Result<MarginColorArg, String> {
+    if s == "none" {
+        return Ok(MarginColorArg(None));
+    }
+    s.parse().map(|color| MarginColorArg(Some(color)))
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
-enum SplitStrategyArg {
+enum RotationArg {
     None,
-    Split,
-    Rotate,
-    RotateSplit,
+    Clockwise,
+    CounterClockwise,
 }
 
-impl From<SplitStrategyArg> for SplitStrategy {
-    fn from(arg: SplitStrategyArg) -> Self {
+impl From<RotationArg> for Rotation {
+    fn from(arg: RotationArg) -> Self {
         match arg {
-            SplitStrategyArg::None => SplitStrategy::None,
-            SplitStrategyArg::Split => SplitStrategy::Split,
-            SplitStrategyArg::Rotate => SplitStrategy::Rotate,
-            SplitStrategyArg::RotateSplit => SplitStrategy::RotateAndSplit,
+            RotationArg::None => Rotation::None,
+            RotationArg::Clockwise => Rotation::Clockwise,
+            RotationArg::CounterClockwise => Rotation::CounterClockwise,
         }
     }
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-
-    // Setup logging
-    setup_logging(args.verbose, args.quiet);
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum TitlePagePositionArg {
+    BeforeCover,
+    AfterCover,
+}
 
-    // Validate input file
-    if !args.input.exists() {
-        anyhow::bail!("Input file does not exist: {}", args.input.display());
+impl From<TitlePagePositionArg> for TitlePagePosition {
+    fn from(arg: TitlePagePositionArg) -> Self {
+        match arg {
+            TitlePagePositionArg::BeforeCover => TitlePagePosition::BeforeCover,
+            TitlePagePositionArg::AfterCover => TitlePagePosition::AfterCover,
+        }
     }
+}
 
-    // Create output directory if it doesn't exist
-    if !args.output_dir.exists() {
-        std::fs::create_dir_all(&args.output_dir).context("Failed to create output directory")?;
-    }
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum MoveSourceArg {
+    Converted,
+    Trash,
+}
 
-    // Build config
-    let config = build_config(&args)?;
-    let output_format = config.output_format;
+impl From<MoveSourceArg> for MoveSource {
+    fn from(arg: MoveSourceArg) -> Self {
+        match arg {
+            MoveSourceArg::Converted => MoveSource::Converted,
+            MoveSourceArg::Trash => MoveSource::Trash,
+        }
+    }
+}
 
-    // Create comic
-    let comic = ComicFile::new(args.input.clone());
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum FilenameEncodingArg {
+    Auto,
+    Utf8,
+    ShiftJis,
+    Cp437,
+}
 
-    if !args.quiet {
-        log::info!(
-            "Converting: `{}` to {output_format:?}",
-            args.input.display()
-        );
+impl From<FilenameEncodingArg> for FilenameEncoding {
+    fn from(arg: FilenameEncodingArg) -> Self {
+        match arg {
+            FilenameEncodingArg::Auto => FilenameEncoding::Auto,
+            FilenameEncodingArg::Utf8 => FilenameEncoding::Utf8,
+            FilenameEncodingArg::ShiftJis => FilenameEncoding::ShiftJis,
+            FilenameEncodingArg::Cp437 => FilenameEncoding::Cp437,
+        }
     }
+}
 
-    // Open archive
-    let archive: Vec<_> = comically::archive::unarchive_comic_iter(&comic)
-        .context("Failed to open comic archive")?
-        .filter_map(|result| {
-            result
-                .map_err(|e| log::warn!("Failed to load archive file: {}", e))
-                .ok()
-        })
-        .collect();
-    let num_images = archive.len();
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum UpscalePolicyArg {
+    Resample,
+    SuperResolution,
+}
 
-    if !args.quiet {
-        log::info!("Found {num_images} images");
+impl From<UpscalePolicyArg> for UpscalePolicy {
+    fn from(arg: UpscalePolicyArg) -> Self {
+        match arg {
+            UpscalePolicyArg::Resample => UpscalePolicy::Resample,
+            UpscalePolicyArg::SuperResolution => UpscalePolicy::SuperResolution,
+        }
     }
+}
 
-    // Process images
-    if !args.quiet {
-        log::info!("Processing images...");
-    }
-    let images =
-        comically::image::process_batch(archive, &config).context("Failed to process images")?;
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum BackendArg {
+    Cpu,
+    Gpu,
+}
 
-    if !args.quiet {
-        log::info!("Processed {} images", images.len());
+impl From<BackendArg> for Backend {
+    fn from(arg: BackendArg) -> Self {
+        match arg {
+            BackendArg::Cpu => Backend::Cpu,
+            BackendArg::Gpu => Backend::Gpu,
+        }
     }
+}
 
-    // Build output
-    if !args.quiet {
-        log::info!("Building {output_format:?}...");
-    }
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum PageBackgroundArg {
+    White,
+    Black,
+}
 
-    let bytes = match output_format {
-        OutputFormat::Cbz => comically::cbz::build(&images),
-        OutputFormat::Epub => comically::epub::build(comic.title(), &config, &images),
-        OutputFormat::Mobi => {
-            if !comically::is_kindlegen_available() {
-                anyhow::bail!(
-                    "KindleGen is not available. Please install it to create MOBI files."
-                );
-            }
-            let bytes = comically::epub::build(comic.title(), &config, &images);
-            let epub_path = args
-                .output_dir
-                .join(comic.with_extension(OutputFormat::Epub));
-            std::fs::write(&epub_path, bytes).context("Failed to write EPUB file")?;
-
-            let output_mobi = args.output_dir.join(comic.with_extension(output_format));
-            let spawned = comically::mobi::create(epub_path, output_mobi.clone())
-                .context("Failed to start MOBI conversion")?;
-            spawned.wait().context("MOBI conversion failed")?;
-            return Ok(());
+impl From<PageBackgroundArg> for PageBackground {
+    fn from(arg: PageBackgroundArg) -> Self {
+        match arg {
+            PageBackgroundArg::White => PageBackground::White,
+            PageBackgroundArg::Black => PageBackground::Black,
         }
-    };
+    }
+}
 
-    let output_path = args.output_dir.join(comic.with_extension(output_format));
-    std::fs::write(&output_path, bytes).context("Failed to write output file")?;
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum StampPositionArg {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
 
-    if !args.quiet {
-        log::info!("Done: {}", output_path.display());
+impl From<StampPositionArg> for StampPosition {
+    fn from(arg: StampPositionArg) -> Self {
+        match arg {
+            StampPositionArg::TopLeft => StampPosition::TopLeft,
+            StampPositionArg::TopRight => StampPosition::TopRight,
+            StampPositionArg::BottomLeft => StampPosition::BottomLeft,
+            StampPositionArg::BottomRight => StampPosition::BottomRight,
+        }
     }
+}
 
-    Ok(())
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum MetadataSourceArg {
+    Anilist,
+    Comicvine,
 }
 
-fn setup_logging(verbose: bool, quiet: bool) {
-    if quiet {
-        return;
+impl From<MetadataSourceArg> for Source {
+    fn from(arg: MetadataSourceArg) -> Self {
+        match arg {
+            MetadataSourceArg::Anilist => Source::AniList,
+            MetadataSourceArg::Comicvine => Source::ComicVine,
+        }
     }
+}
 
-    let level = if verbose {
-        log::LevelFilter::Debug
-    } else {
-        log::LevelFilter::Info
-    };
-
-    env_logger::Builder::from_default_env()
-        .filter_level(level)
-        .format_timestamp(None)
-        .format_target(false)
-        .init();
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum PageSideArg {
+    Left,
+    Right,
 }
 
-fn build_config(args: &Args) -> Result<ComicConfig> {
-    // Validate quality
-    if args.quality > 100 {
-        anyhow::bail!("Quality must be between 0 and 100");
+impl From<PageSideArg> for PageSide {
+    fn from(arg: PageSideArg) -> Self {
+        match arg {
+            PageSideArg::Left => PageSide::Left,
+            PageSideArg::Right => PageSide::Right,
+        }
     }
+}
 
-    // Validate brightness
-    if args.brightness < -100 || args.brightness > 100 {
-        anyhow::bail!("Brightness must be between -100 and 100");
-    }
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum AnimatedPagesArg {
+    First,
+    Middle,
+    Skip,
+}
 
-    // Validate gamma
-    if args.gamma < 0.1 || args.gamma > 3.0 {
-        anyhow::bail!("Gamma must be between 0.1 and 3.0");
+impl From<AnimatedPagesArg> for AnimatedPages {
+    fn from(arg: AnimatedPagesArg) -> Self {
+        match arg {
+            AnimatedPagesArg::First => AnimatedPages::FirstFrame,
+            AnimatedPagesArg::Middle => AnimatedPages::MiddleFrame,
+            AnimatedPagesArg::Skip => AnimatedPages::Skip,
+        }
     }
+}
 
-    // Build device preset
-    let device = args.parse_device()?;
-
-    // Build image format
-    let image_format = match args.image_format {
-        ImageFormatArg::Jpeg => ImageFormat::Jpeg {
-            quality: args.quality,
-        },
-        ImageFormatArg::Png => ImageFormat::Png {
-            compression: args.png_compression.into(),
-        },
-        ImageFormatArg::Webp => ImageFormat::WebP {
-            quality: args.quality,
-        },
-    };
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum PlaceholderPagesArg {
+    Skip,
+    Fail,
+}
 
-    // MOBI requires JPEG
-    let image_format = if args.format == OutputFormatArg::Mobi {
-        if args.image_format != ImageFormatArg::Jpeg {
-            log::warn!("MOBI format requires JPEG images, overriding image format");
-        }
-        ImageFormat::Jpeg {
-            quality: args.quality,
+impl From<PlaceholderPagesArg> for PlaceholderPages {
+    fn from(arg: PlaceholderPagesArg) -> Self {
+        match arg {
+            PlaceholderPagesArg::Skip => PlaceholderPages::Skip,
+            PlaceholderPagesArg::Fail => PlaceholderPages::Fail,
         }
-    } else {
-        image_format
-    };
+    }
+}
 
-    // Build margin color
-    let margin_color = match args.margin_color {
-        MarginColorArg::None => None,
-        MarginColorArg::Black => Some(0),
-        MarginColorArg::White => Some(255),
-    };
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum SplitStrategyArg {
+    None,
+    Split,
+    Rotate,
+    RotateSplit,
+}
+
+impl From<SplitStrategyArg> for SplitStrategy {
+    fn from(arg: SplitStrategyArg) -> Self {
+        match arg {
+            SplitStrategyArg::None => SplitStrategy::None,
+            SplitStrategyArg::Split => SplitStrategy::Split,
+            SplitStrategyArg::Rotate => SplitStrategy::Rotate,
+            SplitStrategyArg::RotateSplit => SplitStrategy::RotateAndSplit,
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        Some(Command::Completions { shell }) => {
+            let mut cmd = Args::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+            return Ok(());
+        }
+        Some(Command::Manpage) => {
+            clap_mangen::Man::new(Args::command())
+                .render(&mut std::io::stdout())
+                .context("Failed to write manpage")?;
+            return Ok(());
+        }
+        Some(Command::Inspect { file }) => return inspect(&file),
+        Some(Command::Opds { dir }) => {
+            let catalog = comically::opds::write_catalog(&dir)?;
+            println!("{}", catalog.display());
+            return Ok(());
+        }
+        Some(Command::Cover {
+            archive,
+            output,
+            width,
+            height,
+            process,
+            config,
+        }) => {
+            let config = match &config {
+                Some(path) => ComicConfig::load_from(path)?,
+                None => ComicConfig::load().unwrap_or_default(),
+            };
+            return cover(
+                &archive,
+                output,
+                (width, height),
+                process.then_some(&config),
+            );
+        }
+        Some(Command::ContactSheet {
+            archive,
+            output,
+            columns,
+            rows,
+            cell_width,
+        }) => {
+            if !archive.exists() {
+                anyhow::bail!("Input file does not exist: {}", archive.display());
+            }
+            let comic = ComicFile::new(archive);
+            let output =
+                output.unwrap_or_else(|| PathBuf::from(format!("{}.sheet.jpg", comic.title())));
+            let sheet = ContactSheet {
+                columns,
+                rows,
+                cell_width,
+            };
+            let img = sheet.build(&comic)?;
+            write_image(&img, &output, 85)?;
+            println!("{}", output.display());
+            return Ok(());
+        }
+        Some(Command::Analyze { archive, json }) => return analyze::run(&archive, json),
+        Some(Command::Repair { archive, output }) => {
+            if !archive.exists() {
+                anyhow::bail!("Input file does not exist: {}", archive.display());
+            }
+            setup_logging(false, false);
+            let comic = ComicFile::new(archive);
+            let output =
+                output.unwrap_or_else(|| PathBuf::from(format!("{}.repaired.cbz", comic.title())));
+            let report = comically::repair::repair(&comic, &output)?;
+            for page in &report.lost {
+                log::warn!("Lost {}: {}", page.path.display(), page.reason);
+            }
+            println!(
+                "Recovered {} pages, lost {}: {}",
+                report.recovered,
+                report.lost.len(),
+                output.display()
+            );
+            return Ok(());
+        }
+        Some(Command::Cache { command }) => {
+            let cache = comically::cache::Cache::open_default()
+                .context("Failed to find the home directory")?;
+            match command {
+                CacheCommand::Gc { max_age, max_size } => {
+                    let mut stats = cache.gc(std::time::Duration::from_secs(max_age * 86_400))?;
+                    if let Some(size) = max_size {
+                        let trimmed = cache.trim(size * 1_000_000)?;
+                        stats.entries += trimmed.entries;
+                        stats.bytes += trimmed.bytes;
+                    }
+                    println!(
+                        "Removed {} pages ({:.1} MB)",
+                        stats.entries,
+                        stats.bytes as f64 / 1_000_000.0
+                    );
+                }
+                CacheCommand::Path => println!("{}", cache.dir().display()),
+            }
+            return Ok(());
+        }
+        Some(Command::Temp { command }) => {
+            let config = ComicConfig::load().unwrap_or_default();
+            let temp = TempRoot::for_config(&config);
+            match command {
+                TempCommand::Sweep => {
+                    let stats = temp.sweep();
+                    println!(
+                        "Removed {} directories ({:.1} MB)",
+                        stats.dirs,
+                        stats.bytes as f64 / 1_000_000.0
+                    );
+                }
+                TempCommand::Path => println!(
+                    "{} ({:.1} MB)",
+                    temp.dir().display(),
+                    temp.size() as f64 / 1_000_000.0
+                ),
+            }
+            return Ok(());
+        }
+        Some(Command::Doctor { config }) => return doctor::run(config.as_deref()),
+        // before the logger is set up, the TUI logs to a file of its own
+        #[cfg(feature = "tui")]
+        Some(Command::Tui(args)) => return comically_tui::run(args),
+        Some(Command::Sample {
+            output,
+            pages,
+            sizes,
+            spread_every,
+            color_every,
+            broken,
+            junk,
+        }) => {
+            let options = SampleOptions {
+                pages,
+                sizes,
+                spread_every,
+                color_every,
+                broken,
+                junk,
+            };
+            SampleArchive::generate(&options).write(&output)?;
+            println!("{}", output.display());
+            return Ok(());
+        }
+        Some(Command::Devices { json }) => {
+            print_devices(json)?;
+            return Ok(());
+        }
+        Some(Command::Sync {
+            server,
+            library,
+            output_dir,
+            push,
+            config,
+            quiet,
+        }) => {
+            setup_logging(false, quiet);
+
+            let server = sync::ServerConfig::find(config.as_deref(), &server)?;
+            let mut comic_config = match &config {
+                Some(path) => ComicConfig::load_from(path)?,
+                None => ComicConfig::load().unwrap_or_default(),
+            };
+            require_jpeg_for_mobi(&mut comic_config);
+            sweep_temp(&comic_config);
+
+            let options = sync::SyncOptions {
+                library: library.as_deref(),
+                output_dir: &output_dir,
+                push,
+                quiet,
+            };
+            sync::run(&server, &comic_config, options)?;
+
+            if comic_config.opds_catalog {
+                comically::opds::write_catalog(&output_dir)
+                    .context("Failed to write OPDS catalog")?;
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    // Setup logging
+    setup_logging(args.verbose, args.quiet);
+    if args.nice {
+        comically::nice::init_global()?;
+        log::debug!(
+            "Converting in the background on {} threads",
+            comically::nice::threads()
+        );
+    }
+
+    // Build config
+    let mut config = build_config(&args)?;
+    sweep_temp(&config);
+
+    if args.compare_kcc {
+        let input = args
+            .inputs
+            .first()
+            .map_or("<input>".into(), |input| input.display().to_string());
+        print_kcc_comparison(&config, &input);
+        return Ok(());
+    }
+
+    // the inputs and targets of the last batch, in place of the ones given
+    let failures = match args.retry_failed {
+        true => Some(
+            Checkpoint::failures(&args.output_dir)
+                .filter(|failures| !failures.inputs.is_empty())
+                .with_context(|| {
+                    format!(
+                        "No failed comics of an earlier batch in {}",
+                        args.output_dir.display()
+                    )
+                })?,
+        ),
+        false => None,
+    };
+
+    let mut inputs = if let Some(failures) = &failures {
+        failures.inputs.clone()
+    } else if !args.inputs.is_empty() {
+        args.inputs.clone()
+    } else if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        wizard::run(&mut config)?
+    } else {
+        anyhow::bail!("No input file given, pass <INPUT> or run in a terminal");
+    };
+
+    // every target goes in its own directory so that outputs of the same format don't collide
+    let mut targets: Vec<batch::Target> = match failures {
+        Some(failures) => failures.targets,
+        None => build_targets(&args, &config)?,
+    };
+    for target in &mut targets {
+        require_jpeg_for_mobi(&mut target.config);
+    }
+
+    // Validate input files
+    for input in &inputs {
+        if !input.exists() {
+            anyhow::bail!("Input file does not exist: {}", input.display());
+        }
+    }
+
+    if args.auto_tune {
+        auto_tune(&inputs, &mut targets)?;
+    }
+
+    // Create output directories if they don't exist
+    for target in &targets {
+        if !target.output_dir.exists() {
+            std::fs::create_dir_all(&target.output_dir)
+                .context("Failed to create output directory")?;
+        }
+    }
+
+    // batches keep a journal so an interrupted run can pick up where it stopped, and a
+    // retry so the comics that fail again can be retried again
+    let mut checkpoint = if inputs.len() > 1 || args.retry_failed {
+        Some(open_checkpoint(&args, &targets, &mut inputs)?)
+    } else {
+        None
+    };
+    if let Some(checkpoint) = &mut checkpoint {
+        for input in &inputs {
+            checkpoint.queued(input);
+        }
+    }
+    let checkpoint = checkpoint.map(Mutex::new);
+
+    let lookup = match args.fetch_metadata {
+        Some(source) => {
+            let sources = SourceConfig::load(args.config.as_deref())?;
+            Some(Arc::new(Mutex::new(Lookup::new(
+                source.map(Source::from),
+                sources,
+            ))))
+        }
+        None => None,
+    };
+
+    let inputs: Vec<Input> = inputs
+        .into_iter()
+        .map(|input| {
+            let comic = ComicFile::new(input);
+            let title = comic.title().to_string();
+            let mut input = Input::new(comic);
+            // the settings of the comic's directory, under the series looked up for it
+            if let Some(local) = DirConfig::for_comic(input.comic.as_path()) {
+                input = input.with_overrides(move |config| local.apply(config));
+            }
+            match lookup.clone() {
+                Some(lookup) => {
+                    input.with_overrides(move |config| lookup::apply(&lookup, &title, config))
+                }
+                None => input,
+            }
+        })
+        .collect();
+    let nested_archives = targets[0].config.archive_options().nested_archives;
+    let batch = Batch {
+        targets,
+        jobs: args.jobs,
+    };
+
+    let notifications = match args.quiet {
+        true => Notifications::default(),
+        false => Notifications::load(args.config.as_deref()),
+    };
+    let start = Instant::now();
+    let reports = batch.run(&inputs, |index, event| {
+        let input = inputs[index].comic.as_path();
+        let checkpoint = checkpoint.as_ref().and_then(|c| c.lock().ok());
+        match event {
+            Event::StageStarted(Stage::Process) => {
+                if let Some(mut checkpoint) = checkpoint {
+                    checkpoint.started(input);
+                }
+            }
+            Event::Opened { skipped, .. } if !args.quiet => log_skipped(skipped, nested_archives),
+            Event::Finished(report) if report.is_ok() => {
+                log::debug!(
+                    "Converted {} in {:.1?}",
+                    input.display(),
+                    report.timings.total()
+                );
+                if let Some(mut checkpoint) = checkpoint {
+                    checkpoint.completed(input);
+                }
+            }
+            Event::Finished(report) => {
+                if let Some(e) = &report.error {
+                    notifications.comic_failed(input, e);
+                }
+                if let Some(mut checkpoint) = checkpoint {
+                    checkpoint.failed(input);
+                }
+            }
+            _ => {}
+        }
+    });
+
+    for target in &batch.targets {
+        if target.config.opds_catalog {
+            comically::opds::write_catalog(&target.output_dir)
+                .context("Failed to write OPDS catalog")?;
+        }
+    }
+
+    log_slow_pages(&reports);
+    log_settings(&reports);
+    if let Some(to) = args.move_source {
+        move_sources(&reports, to.into());
+    }
+
+    let total = reports.len();
+    let mut failed: Vec<(PathBuf, anyhow::Error)> = reports
+        .into_iter()
+        .filter_map(|report| Some((report.input, report.error?)))
+        .collect();
+    notifications.batch_finished(total, failed.len(), start.elapsed());
+    match failed.len() {
+        0 => Ok(()),
+        _ if total == 1 => Err(failed.remove(0).1),
+        n => {
+            let list: String = failed
+                .iter()
+                .map(|(input, e)| format!("\n  {}: {e:#}", input.display()))
+                .collect();
+            anyhow::bail!("{n} of {total} comics failed to convert:{list}")
+        }
+    }
+}
+
+// offers to skip the inputs an interrupted batch with the same settings already converted
+fn open_checkpoint(
+    args: &Args,
+    targets: &[batch::Target],
+    inputs: &mut Vec<PathBuf>,
+) -> Result<Checkpoint> {
+    let configs: Vec<ComicConfig> = targets.iter().map(|t| t.config.clone()).collect();
+    let resume = Checkpoint::find(&args.output_dir, &configs).filter(|resume| {
+        let done = inputs
+            .iter()
+            .filter(|input| resume.is_completed(input))
+            .count();
+        if args.restart || done == 0 {
+            return false;
+        }
+
+        let interrupted = resume
+            .interrupted
+            .as_deref()
+            .and_then(Path::file_name)
+            .map(|name| format!(", `{}` was interrupted", name.to_string_lossy()))
+            .unwrap_or_default();
+        let summary = format!(
+            "An earlier batch converted {done} of these {} comics{interrupted}",
+            inputs.len()
+        );
+        if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+            dialoguer::Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                .with_prompt(format!("{summary}. Resume it?"))
+                .default(true)
+                .interact()
+                .unwrap_or(true)
+        } else {
+            log::info!("{summary}, resuming (pass --restart to convert everything again)");
+            true
+        }
+    });
+
+    if let Some(resume) = &resume {
+        inputs.retain(|input| !resume.is_completed(input));
+    }
+    Checkpoint::create(&args.output_dir, targets, resume.as_ref())
+        .context("Failed to create the batch checkpoint")
+}
+
+// only comics converted without warnings are moved, a source with unreadable pages may be
+// worth another try
+fn move_sources(reports: &[ComicReport], to: MoveSource) {
+    for report in reports.iter().filter(|report| report.is_ok()) {
+        let input = report.input.display();
+        if !report.warnings.is_empty() {
+            log::warn!("Leaving {input} in place, it was converted with warnings");
+            continue;
+        }
+        match comically::source::move_source(&report.input, &report.outputs, to) {
+            Ok(moved) => log::info!("Moved {input} to {}", moved.display()),
+            Err(e) => log::warn!("Leaving {input} in place: {e:#}"),
+        }
+    }
+}
+
+// removes what conversions that crashed or were killed left in the temporary directory
+fn sweep_temp(config: &ComicConfig) {
+    let stats = TempRoot::for_config(config).sweep();
+    if stats.dirs > 0 {
+        log::info!(
+            "Removed {} stale temporary directories ({:.1} MB)",
+            stats.dirs,
+            stats.bytes as f64 / 1_000_000.0
+        );
+    }
+}
+
+fn require_jpeg_for_mobi(config: &mut ComicConfig) {
+    if config.output_format == OutputFormat::Mobi {
+        if !matches!(config.image_format, ImageFormat::Jpeg { .. }) {
+            log::warn!("MOBI format requires JPEG images, overriding image format");
+        }
+        let quality = match config.image_format {
+            ImageFormat::Jpeg { quality } | ImageFormat::WebP { quality } => quality,
+            ImageFormat::Png { .. } => 85,
+        };
+        config.image_format = ImageFormat::Jpeg { quality };
+    }
+}
+
+// lists what isn't a page, so a page count lower than the archive's file count isn't a mystery
+fn log_skipped(skipped: &[SkippedEntry], nested_archives: bool) {
+    if skipped.is_empty() {
+        return;
+    }
+
+    log::info!("Skipped {} entries that aren't pages:", skipped.len());
+    for entry in skipped {
+        log::info!("  {} ({})", entry.path.display(), entry.reason);
+    }
+    let has_nested = skipped
+        .iter()
+        .any(|entry| entry.reason == SkipReason::NestedArchive);
+    if has_nested && !nested_archives {
+        log::info!("Pass --nested-archives to read the pages of nested zip/cbz archives");
+    }
+}
+
+// which settings made which outputs, when comics of the batch were converted differently
+fn log_settings(reports: &[ComicReport]) {
+    let mut fingerprints: Vec<&String> = reports
+        .iter()
+        .flat_map(|report| &report.fingerprints)
+        .collect();
+    fingerprints.sort_unstable();
+    fingerprints.dedup();
+    if fingerprints.len() <= 1 {
+        return;
+    }
+    log::info!("Converted with {} different settings:", fingerprints.len());
+    for report in reports.iter().filter(|report| report.is_ok()) {
+        for (output, fingerprint) in report.outputs.iter().zip(&report.fingerprints) {
+            log::info!("  {fingerprint}: {}", output.display());
+        }
+    }
+}
+
+// where the time of the batch went, to find the pages worth shrinking before converting
+fn log_slow_pages(reports: &[ComicReport]) {
+    if !log::log_enabled!(log::Level::Debug) {
+        return;
+    }
+    let Some(stats) = PageStats::new(reports.iter().flat_map(|report| &report.pages)) else {
+        return;
+    };
+    log::debug!(
+        "Processed {} pages: median {:.1?}, p90 {:.1?}, p99 {:.1?}, max {:.1?}",
+        stats.pages,
+        stats.p50,
+        stats.p90,
+        stats.p99,
+        stats.max
+    );
+
+    let mut pages: Vec<_> = reports
+        .iter()
+        .flat_map(|report| report.pages.iter().map(move |page| (&report.input, page)))
+        .filter(|(_, page)| !page.cached && !page.reused)
+        .collect();
+    pages.sort_by_key(|(_, page)| std::cmp::Reverse(page.total()));
+    log::debug!("Slowest pages:");
+    for (input, page) in pages.iter().take(5) {
+        log::debug!(
+            "  {} in {}: {:.1?} (decode {:.1?}, transform {:.1?}, encode {:.1?})",
+            page.file.display(),
+            input.display(),
+            page.total(),
+            page.decode,
+            page.transform,
+            page.encode
+        );
+    }
+}
+
+fn inspect(path: &Path) -> Result<()> {
+    let metadata =
+        std::fs::metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    println!("file:     {}", path.display());
+    println!("size:     {} bytes", metadata.len());
+
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    if let Some("cbz" | "cbr" | "zip" | "rar") = extension {
+        let comic = ComicFile::new(path.to_path_buf());
+        let archive = comically::archive::unarchive_comic_iter(&comic)
+            .context("Failed to open comic archive")?;
+        println!("pages:    {}", archive.num_images());
+        for entry in archive.skipped() {
+            println!("skipped:  {} ({})", entry.path.display(), entry.reason);
+        }
+    }
+    if let Some("mobi" | "azw3" | "azw") = extension {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let book = comically::mobi::MobiInfo::read(&data).context("Failed to read MOBI headers")?;
+        println!("title:    {}", book.title);
+        for author in &book.authors {
+            println!("author:   {author}");
+        }
+        if let Some(sort_title) = &book.sort_title {
+            println!("sort as:  {sort_title}");
+        }
+        println!("asin:     {}", book.asin.as_deref().unwrap_or("none"));
+        println!("type:     {}", book.cde_type.as_deref().unwrap_or("none"));
+        println!("kf8:      {}", if book.kf8 { "yes" } else { "no" });
+        println!("cover:    {}", if book.cover { "yes" } else { "none" });
+        println!("pages:    {}", book.pages);
+        if let Err(e) = book.check(None) {
+            println!("mobi:     INVALID");
+            return Err(e.context(format!("{} isn't a valid book", path.display())));
+        }
+    }
+
+    match comically::output::verify_checksum(path)? {
+        Some(true) => println!("checksum: ok"),
+        Some(false) => {
+            println!("checksum: MISMATCH");
+            anyhow::bail!("{} does not match its checksum", path.display());
+        }
+        None => println!("checksum: none"),
+    }
+
+    Ok(())
+}
+
+fn cover(
+    archive: &Path,
+    output: Option<PathBuf>,
+    (width, height): (Option<u32>, Option<u32>),
+    process: Option<&ComicConfig>,
+) -> Result<()> {
+    if !archive.exists() {
+        anyhow::bail!("Input file does not exist: {}", archive.display());
+    }
+    let comic = ComicFile::new(archive.to_path_buf());
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{}.jpg", comic.title())));
+
+    let img = match process {
+        Some(config) => {
+            let size = (width.is_some() || height.is_some()).then(|| {
+                let (device_width, device_height) = config.device.dimensions();
+                (
+                    width.unwrap_or(device_width),
+                    height.unwrap_or(device_height),
+                )
+            });
+            comically::cover::processed(&comic, config, size)?
+        }
+        None if width.is_some() || height.is_some() => comically::cover::thumbnail(
+            &comic,
+            width.unwrap_or(u32::MAX),
+            height.unwrap_or(u32::MAX),
+        )?,
+        None => comically::cover::extract(&comic)?,
+    };
+
+    let quality = match process.map(|config| config.image_format) {
+        Some(ImageFormat::Jpeg { quality } | ImageFormat::WebP { quality }) => quality,
+        _ => 85,
+    };
+    write_image(&img, &output, quality)?;
+
+    println!("{}", output.display());
+    Ok(())
+}
+
+// encodes as JPEG, PNG or WebP depending on the extension of `path`
+fn write_image(img: &DynamicImage, path: &Path, quality: u8) -> Result<()> {
+    let format = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => ImageFormat::Png {
+            compression: PngCompression::Default,
+        },
+        Some("webp") => ImageFormat::WebP { quality },
+        Some("jpg" | "jpeg") | None => ImageFormat::Jpeg { quality },
+        Some(other) => anyhow::bail!("Unsupported image format: {other}"),
+    };
+
+    let data = comically::image::encode::encode_image(img, &format);
+    comically::output::write_atomic(path, &data)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn print_devices(json: bool) -> Result<()> {
+    let presets = comically::device::Preset::iter();
+
+    if json {
+        let devices: Vec<_> = presets
+            .map(|preset| {
+                let (width, height) = preset.dimensions();
+                let tuning = preset.tuning();
+                serde_json::json!({
+                    "id": preset.id(),
+                    "name": preset.name(),
+                    "width": width,
+                    "height": height,
+                    "dpi": preset.dpi(),
+                    "color": preset.color(),
+                    "format": tuning.output_format.map(OutputFormat::as_str),
+                    "gamma": tuning.gamma,
+                    "brightness": tuning.brightness,
+                    "dither": tuning.dither,
+                    "keep_color": tuning.keep_color,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<16} {:<16} {:>11} {:>5}  {:<5}  {:<6} GAMMA",
+        "ID", "NAME", "RESOLUTION", "DPI", "COLOR", "FORMAT"
+    );
+    for preset in presets {
+        let (width, height) = preset.dimensions();
+        let tuning = preset.tuning();
+        println!(
+            "{:<16} {:<16} {:>11} {:>5}  {:<5}  {:<6} {:.1}",
+            preset.id(),
+            preset.name(),
+            format!("{width}x{height}"),
+            preset.dpi(),
+            if preset.color() { "yes" } else { "no" },
+            tuning.output_format.map_or("", OutputFormat::as_str),
+            tuning.gamma,
+        );
+    }
+
+    Ok(())
+}
+
+fn print_kcc_comparison(config: &ComicConfig, input: &str) {
+    println!("{:<18} {:<18} {:<24} NOTE", "SETTING", "VALUE", "KCC");
+    for mapping in comically::kcc::compare(config) {
+        let flags = match mapping.flags.is_empty() {
+            true => "(default)".to_string(),
+            false => mapping.flags.join(" "),
+        };
+        println!(
+            "{:<18} {:<18} {:<24} {}",
+            mapping.setting,
+            mapping.value,
+            flags,
+            mapping.note.unwrap_or_default()
+        );
+    }
+    println!();
+    println!("{}", comically::kcc::command_line(config, input));
+}
+
+fn setup_logging(verbose: bool, quiet: bool) {
+    if quiet {
+        return;
+    }
+
+    let level = if verbose {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+
+    env_logger::Builder::from_default_env()
+        .filter_level(level)
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+}
+
+// the config given, or one per `--target` with its device and format
+fn build_targets(args: &Args, config: &ComicConfig) -> Result<Vec<batch::Target>> {
+    if args.target.is_empty() {
+        return Ok(vec![batch::Target {
+            config: config.clone(),
+            output_dir: args.output_dir.clone(),
+        }]);
+    }
+    args.target
+        .iter()
+        .map(|target| {
+            let mut config = config.clone();
+            config.set_device(target.device.clone());
+            config.output_format = target.format;
+            apply_flags(args, &mut config)?;
+            Ok(batch::Target {
+                config,
+                output_dir: args.output_dir.join(target.device.id()),
+            })
+        })
+        .collect()
+}
+
+// tuned once for the batch, its volumes are usually of one series
+fn auto_tune(inputs: &[PathBuf], targets: &mut [batch::Target]) -> Result<()> {
+    let comics: Vec<ComicFile> = inputs.iter().cloned().map(ComicFile::new).collect();
+    for target in targets {
+        let tone = comically::tune::suggest(&comics, &target.config)?;
+        log::info!("Auto-tuned for {}: {tone}", target.config.device);
+        tone.apply(&mut target.config);
+    }
+    Ok(())
+}
+
+// the saved config (or `--config`) is the base, flags only override what they set
+fn build_config(args: &Args) -> Result<ComicConfig> {
+    let mut config = match &args.config {
+        Some(path) => ComicConfig::load_from(path)?,
+        None => ComicConfig::load().unwrap_or_default(),
+    };
+
+    // Build device preset, its tuning comes first so that explicit options win
+    if let Some(device) = &args.device {
+        config.set_device(args.parse_device(device)?);
+    } else if args.width.is_some() || args.height.is_some() {
+        config.set_device(args.parse_device("custom")?);
+    }
+
+    apply_flags(args, &mut config)?;
+    Ok(config)
+}
+
+// every option but the device, applied after it so that they win over its tuning
+fn apply_flags(args: &Args, config: &mut ComicConfig) -> Result<()> {
+    if let Some(format) = args.format {
+        config.output_format = format.into();
+    }
+
+    // Build image format, keeping the configured quality unless overridden
+    let current_quality = match config.image_format {
+        ImageFormat::Jpeg { quality } | ImageFormat::WebP { quality } => quality,
+        ImageFormat::Png { .. } => 85,
+    };
+    let quality = args.quality.unwrap_or(current_quality);
+
+    if let Some(image_format) = args.image_format {
+        config.image_format = match image_format {
+            ImageFormatArg::Jpeg => ImageFormat::Jpeg { quality },
+            ImageFormatArg::Png => ImageFormat::Png {
+                compression: args.png_compression.unwrap_or_default().into(),
+            },
+            ImageFormatArg::Webp => ImageFormat::WebP { quality },
+        };
+    } else {
+        match &mut config.image_format {
+            ImageFormat::Jpeg { quality: q } | ImageFormat::WebP { quality: q } => *q = quality,
+            ImageFormat::Png { compression } => {
+                if let Some(png_compression) = args.png_compression {
+                    *compression = png_compression.into();
+                }
+            }
+        }
+    }
+
+    if let Some(max) = args.max_dimension {
+        config.max_dimension = Some(max);
+    }
+
+    if let Some(longest) = args.srcset {
+        config.srcset = Some(longest);
+        if args.target.is_empty() && config.output_format != OutputFormat::Epub {
+            log::warn!("--srcset only applies to EPUB output");
+        }
+    }
+
+    if args.text_pages {
+        config.text_pages = true;
+        if args.target.is_empty() && config.output_format != OutputFormat::Epub {
+            log::warn!("--text-pages only applies to EPUB output");
+        }
+    }
+
+    if let Some(height) = args.long_strip {
+        config.long_strip = Some(height);
+        if args.target.is_empty() && config.output_format != OutputFormat::Cbz {
+            log::warn!("--long-strip only applies to CBZ output");
+        }
+    }
+
+    if let Some(AdaptiveQualityArg(adaptive)) = args.adaptive_quality {
+        config.adaptive_quality = adaptive;
+    }
+
+    if let Some(brightness) = args.brightness {
+        config.brightness = brightness;
+    }
+
+    if args.paper_white {
+        config.paper_white = true;
+    }
+
+    if let Some(contrast) = args.contrast {
+        config.contrast = contrast;
+    }
+
+    if let Some(gamma) = args.gamma {
+        config.gamma = gamma;
+    }
+
+    if let Some(curve) = &args.gamma_curve {
+        config.gamma_curve = curve.clone();
+    }
+
+    // Build margin color
+    if let Some(MarginColorArg(margin_color)) = args.margin_color {
+        config.margin_color = margin_color;
+    }
+
+    if let Some(split) = args.split {
+        config.split = split.into();
+    }
+
+    if let Some(side) = args.chapter_side {
+        config.chapter_side = Some(side.into());
+    }
+
+    if let Some(animated_pages) = args.animated_pages {
+        config.animated_pages = animated_pages.into();
+    }
+
+    if let Some(placeholder_pages) = args.placeholder_pages {
+        config.placeholder_pages = placeholder_pages.into();
+    }
+
+    if let Some(concurrency) = args.concurrency {
+        config.concurrency = concurrency;
+    }
+
+    if args.auto_rotate_cover {
+        config.auto_rotate_cover = true;
+    }
+
+    if let Some(command) = &args.page_filter {
+        let mut filter = PageFilter::new(command);
+        if let Some(jobs) = args.page_filter_jobs {
+            filter.jobs = jobs as usize;
+        }
+        config.page_filter = Some(filter);
+    }
+
+    if args.landscape_spreads {
+        config.landscape_spreads = true;
+        match args.split {
+            None => config.split = SplitStrategy::None,
+            Some(SplitStrategyArg::None) => {}
+            Some(_) => log::warn!("--landscape-spreads only applies with --split none"),
+        }
+    }
+
+    if args.checksum {
+        config.write_checksum = true;
+    }
+
+    if args.kcc_naming {
+        config.output_naming = OutputNaming::Kcc;
+    }
+
+    if let Some(template) = &args.output_template {
+        comically::filename::check_template(template)?;
+        config.output_template = Some(template.clone());
+    }
+
+    if let Some(size) = args.mobi_part_size {
+        config.mobi_part_mb = size;
+    }
+
+    if args.opds {
+        config.opds_catalog = true;
+    }
+
+    if args.no_cache {
+        config.page_cache = false;
+    }
+
+    if let Some(size) = args.cache_size {
+        config.page_cache_mb = size;
+    }
+
+    if args.reuse_pages {
+        config.reuse_pages = true;
+    }
+
+    if let Some(dir) = &args.temp_dir {
+        config.temp_dir = Some(dir.clone());
+    }
+
+    if args.nested_archives {
+        config.nested_archives = true;
+    }
+
+    if let Some(encoding) = args.filename_encoding {
+        config.filename_encoding = encoding.into();
+    }
+
+    if let Some(pages) = &args.pages {
+        config.pages = Some(pages.clone());
+    }
+
+    if let Some(crop) = args.fixed_crop {
+        config.fixed_crop = Some(crop);
+    }
+
+    if args.deterministic {
+        config.deterministic = true;
+    }
+
+    if let Some(rotate) = args.rotate {
+        config.rotation = rotate.into();
+    }
+
+    if let Some(upscale) = args.upscale {
+        config.upscale = upscale.into();
+    }
+
+    if let Some(backend) = args.backend {
+        config.backend = backend.into();
+    }
+
+    if let Some(background) = args.page_background {
+        config.page_background = background.into();
+    }
+
+    if let Some(name) = &args.series {
+        config.series = Some(Series {
+            name: name.clone(),
+            index: args.series_index,
+        });
+    }
+
+    if let Some(position) = args.page_numbers {
+        let mut stamp = config.page_stamp.unwrap_or_default();
+        stamp.position = position.into();
+        if let Some(size) = args.page_number_size {
+            stamp.size = size;
+        }
+        if let Some(opacity) = args.page_number_opacity {
+            stamp.opacity = opacity.clamp(0.0, 1.0);
+        }
+        stamp.chapter |= args.page_number_chapter;
+        config.page_stamp = Some(stamp);
+    }
+
+    if let Some(path) = &args.watermark {
+        let mut watermark = Watermark::new(path.clone());
+        if let Some(position) = args.watermark_position {
+            watermark.position = position.into();
+        }
+        if let Some(opacity) = args.watermark_opacity {
+            watermark.opacity = opacity.clamp(0.0, 1.0);
+        }
+        watermark.cover_only = args.watermark_cover_only;
+        config.watermark = Some(watermark);
+    }
+
+    if args.translations {
+        config.translation_overlay = Some(TranslationOverlay {
+            font: args.translation_font.clone(),
+        });
+    }
+
+    if let Some(position) = args.title_page {
+        config.title_page = Some(TitlePage {
+            position: position.into(),
+            font: args.title_page_font.clone(),
+        });
+    }
+
+    if args.rtl {
+        config.right_to_left = true;
+        config.detect_direction = false;
+    } else if args.ltr {
+        config.right_to_left = false;
+        config.detect_direction = false;
+    } else if args.detect_direction {
+        config.detect_direction = true;
+    }
+
+    if args.auto_crop {
+        config.auto_crop = true;
+    } else if args.no_auto_crop {
+        config.auto_crop = false;
+    }
+
+    if args.dither {
+        config.dither = true;
+    } else if args.no_dither {
+        config.dither = false;
+    }
+
+    if args.keep_color {
+        config.keep_color = true;
+    } else if args.grayscale {
+        config.keep_color = false;
+    }
+
+    // Validate quality
+    if quality > 100 {
+        anyhow::bail!("Quality must be between 0 and 100");
+    }
+
+    // Validate brightness
+    if config.brightness < -100 || config.brightness > 100 {
+        anyhow::bail!("Brightness must be between -100 and 100");
+    }
+
+    // Validate contrast
+    if config.contrast < -100 || config.contrast > 100 {
+        anyhow::bail!("Contrast must be between -100 and 100");
+    }
+
+    // Validate gamma
+    if config.gamma < 0.1 || config.gamma > 3.0 {
+        anyhow::bail!("Gamma must be between 0.1 and 3.0");
+    }
+
+    Ok(())
+}
+
+// arguments with a config file of defaults, so the one saved by the TUI isn't read
+#[cfg(test)]
+fn test_args(args: &[&str]) -> (Args, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.json");
+    std::fs::write(
+        &config,
+        serde_json::to_string(&ComicConfig::default()).unwrap(),
+    )
+    .unwrap();
+    let config = config.to_str().unwrap().to_string();
+    let args = ["comically-cli", "--config", &config]
+        .into_iter()
+        .chain(args.iter().copied());
+    (Args::try_parse_from(args).unwrap(), dir)
+}
+
+#[test]
+fn targets_get_their_device_and_format() {
+    let (args, _dir) = test_args(&[
+        "comic.cbz",
+        "-o",
+        "out",
+        "--target",
+        "kindle-pw-11:mobi",
+        "--target",
+        "1072x1448:epub",
+        "--gamma",
+        "1.2",
+        "--grayscale",
+        "--contrast",
+        "20",
+    ]);
+    let targets = build_targets(&args, &build_config(&args).unwrap()).unwrap();
+    assert_eq!(targets.len(), 2);
+    assert_eq!(
+        targets[0].config.device,
+        Device::Preset(comically::device::Preset::KindlePw11)
+    );
+    assert_eq!(targets[0].config.output_format, OutputFormat::Mobi);
+    assert_eq!(targets[0].output_dir, Path::new("out/kindle-pw-11"));
+    assert_eq!(
+        targets[1].config.device,
+        Device::Custom {
+            width: 1072,
+            height: 1448
+        }
+    );
+    assert_eq!(targets[1].config.output_format, OutputFormat::Epub);
+    assert_eq!(targets[1].output_dir, Path::new("out/1072x1448"));
+    // explicit options win over the tuning of each device
+    assert!(targets.iter().all(|target| target.config.gamma == 1.2));
+    assert!(targets.iter().all(|target| !target.config.keep_color));
+    assert!(targets.iter().all(|target| target.config.contrast == 20));
+
+    // without --target, the config goes in the output directory
+    let (args, _dir) = test_args(&["comic.cbz", "-o", "out", "-d", "kobo-libra-2", "-f", "cbz"]);
+    let config = build_config(&args).unwrap();
+    let targets = build_targets(&args, &config).unwrap();
+    assert_eq!(targets.len(), 1);
+    assert_eq!(targets[0].output_dir, Path::new("out"));
+    assert_eq!(targets[0].config.output_format, OutputFormat::Cbz);
+    assert_eq!(targets[0].config.fingerprint(), config.fingerprint());
+
+    assert!(parse_target("kindle-pw-11").is_err());
+    assert!(parse_target("kindle-pw-11:pdf").is_err());
+    assert!(
+        Args::try_parse_from(["comically-cli", "--target", "kpw5:epub", "-d", "kpw5"]).is_err()
+    );
+}
+
+#[test]
+fn devices_are_parsed() {
+    let (args, _dir) = test_args(&[]);
+    let preset = Device::Preset(comically::device::Preset::KindlePw11);
+    assert_eq!(args.parse_device("kindle-pw-11").unwrap(), preset);
+    // KCC profiles, for users coming from it
+    assert_eq!(args.parse_device("KPW5").unwrap(), preset);
+    assert_eq!(
+        args.parse_device("600x800").unwrap(),
+        Device::Custom {
+            width: 600,
+            height: 800
+        }
+    );
+    assert!(args.parse_device("kindle-pw-99").is_err());
+    assert!(args.parse_device("custom").is_err());
+
+    let (args, _dir) = test_args(&["--width", "900", "--height", "1200"]);
+    let custom = Device::Custom {
+        width: 900,
+        height: 1200,
+    };
+    assert_eq!(args.parse_device("custom").unwrap(), custom);
+    // the size alone picks a custom device
+    assert_eq!(build_config(&args).unwrap().device, custom);
+}
+
+#[test]
+fn auto_tune_tunes_every_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("comic.cbz");
+    SampleArchive::with_pages(4).write(&input).unwrap();
+    let input = input.to_str().unwrap();
+
+    let (args, _dir) = test_args(&[
+        input,
+        "--auto-tune",
+        "--target",
+        "kindle-pw-11:epub",
+        "--target",
+        "ipad-mini:cbz",
+    ]);
+    assert!(args.auto_tune);
+    let mut targets = build_targets(&args, &build_config(&args).unwrap()).unwrap();
+    let expected: Vec<_> = targets
+        .iter()
+        .map(|target| {
+            let comics = [ComicFile::new(input.into())];
+            let mut config = target.config.clone();
+            comically::tune::suggest(&comics, &config)
+                .unwrap()
+                .apply(&mut config);
+            config
+        })
+        .collect();
+    auto_tune(&args.inputs, &mut targets).unwrap();
+    for (target, expected) in targets.iter().zip(&expected) {
+        assert_eq!(target.config.fingerprint(), expected.fingerprint());
+    }
 
-    Ok(ComicConfig {
-        output_format: args.format.into(),
-        device,
-        image_format,
-        brightness: args.brightness,
-        gamma: args.gamma,
-        split: args.split.into(),
-        right_to_left: args.rtl,
-        auto_crop: !args.no_auto_crop,
-        margin_color,
-    })
+    assert!(auto_tune(&[dir.path().join("missing.cbz")], &mut targets).is_err());
+    // the tones are suggested in place of the given ones
+    assert!(Args::try_parse_from(["comically-cli", "--auto-tune", "--gamma", "1.2"]).is_err());
 }
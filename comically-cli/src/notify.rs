@@ -0,0 +1,80 @@
+//! Desktop notifications for batches left running in a terminal nobody watches, turned
+//! on in the `notifications` section of the config file:
+//!
+//! ```json
+//! "notifications": { "finished": true, "failed": true }
+//! ```
+
+use serde::Deserialize;
+
+use std::path::Path;
+use std::time::Duration;
+
+use comically::ComicConfig;
+
+/// The `notifications` section of the config file
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Notifications {
+    /// When the batch is done, with how many comics failed
+    #[serde(default)]
+    pub finished: bool,
+    /// As soon as a comic fails to convert
+    #[serde(default)]
+    pub failed: bool,
+}
+
+impl Notifications {
+    /// Reads the section from the config file, all off when the file can't be read
+    pub fn load(config_path: Option<&Path>) -> Self {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            notifications: Notifications,
+        }
+
+        let Some(path) = config_path
+            .map(Path::to_path_buf)
+            .or_else(ComicConfig::config_path)
+        else {
+            return Self::default();
+        };
+        std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<File>(&contents).ok())
+            .map(|file| file.notifications)
+            .unwrap_or_default()
+    }
+
+    pub fn comic_failed(&self, input: &Path, error: &anyhow::Error) {
+        if self.failed {
+            let name = input.file_name().unwrap_or(input.as_os_str());
+            show(
+                &format!("Failed to convert {}", name.to_string_lossy()),
+                &format!("{error:#}"),
+            );
+        }
+    }
+
+    pub fn batch_finished(&self, total: usize, failed: usize, elapsed: Duration) {
+        if !self.finished {
+            return;
+        }
+        let summary = match failed {
+            0 => format!("Converted {total} comics"),
+            failed => format!("{failed} of {total} comics failed to convert"),
+        };
+        show(&summary, &format!("in {:.0?}", elapsed));
+    }
+}
+
+// a desktop without a notification service isn't worth failing the batch over
+fn show(summary: &str, body: &str) {
+    let shown = notify_rust::Notification::new()
+        .appname("comically")
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(e) = shown {
+        log::debug!("Failed to show a notification: {e}");
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{RemoteBook, Server, ServerConfig};
+
+pub struct Kavita {
+    url: String,
+    // JWT from the login, sent as a bearer token
+    token: String,
+    agent: ureq::Agent,
+}
+
+#[derive(Deserialize)]
+struct Login {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct Library {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Series {
+    id: u64,
+    name: String,
+    library_id: u64,
+}
+
+#[derive(Deserialize)]
+struct Volume {
+    chapters: Vec<Chapter>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Chapter {
+    id: u64,
+    #[serde(default)]
+    title_name: String,
+    #[serde(default)]
+    range: String,
+    #[serde(default)]
+    files: Vec<ChapterFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChapterFile {
+    file_path: String,
+}
+
+impl Kavita {
+    pub fn new(config: &ServerConfig) -> Result<Self> {
+        let url = config.url.trim_end_matches('/').to_string();
+        let agent = super::agent();
+
+        let mut response = match (&config.api_key, &config.username, &config.password) {
+            (Some(key), _, _) => agent
+                .post(format!("{url}/api/Plugin/authenticate"))
+                .query("apiKey", key)
+                .query("pluginName", "comically")
+                .send_empty(),
+            (None, Some(username), Some(password)) => agent
+                .post(format!("{url}/api/Account/login"))
+                .send_json(serde_json::json!({ "username": username, "password": password })),
+            _ => anyhow::bail!("Kavita needs an api_key or a username and password"),
+        }
+        .context("Failed to log in to Kavita")?;
+
+        let login: Login = response
+            .body_mut()
+            .read_json()
+            .context("Unexpected Kavita login response")?;
+
+        Ok(Self {
+            url,
+            token: format!("Bearer {}", login.token),
+            agent,
+        })
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T> {
+        let mut request = self
+            .agent
+            .get(format!("{}{path}", self.url))
+            .header("Authorization", &self.token);
+        for (key, value) in query {
+            request = request.query(*key, *value);
+        }
+        request
+            .call()
+            .with_context(|| format!("Kavita request failed: {path}"))?
+            .body_mut()
+            .read_json()
+            .with_context(|| format!("Unexpected Kavita response: {path}"))
+    }
+}
+
+impl Server for Kavita {
+    fn books(&self, library: Option<&str>) -> Result<Vec<RemoteBook>> {
+        let libraries: Vec<String> = match library {
+            Some(library) => vec![library.to_string()],
+            None => self
+                .get::<Vec<Library>>("/api/Library/libraries", &[])?
+                .into_iter()
+                .map(|library| library.id.to_string())
+                .collect(),
+        };
+
+        let mut books = Vec::new();
+        for library in libraries {
+            let series: Vec<Series> = self
+                .agent
+                .post(format!("{}/api/Series/all", self.url))
+                .header("Authorization", &self.token)
+                .query("libraryId", &library)
+                .send_json(serde_json::json!({}))
+                .context("Failed to list Kavita series")?
+                .body_mut()
+                .read_json()
+                .context("Unexpected response listing Kavita series")?;
+
+            for series in series {
+                let id = series.id.to_string();
+                let volumes: Vec<Volume> = self.get("/api/Series/volumes", &[("seriesId", &id)])?;
+
+                for chapter in volumes.into_iter().flat_map(|volume| volume.chapters) {
+                    let Some(file) = chapter.files.first() else {
+                        continue;
+                    };
+                    let file_name = file
+                        .file_path
+                        .rsplit(['/', '\\'])
+                        .next()
+                        .unwrap_or(&file.file_path);
+                    let (stem, extension) = file_name.rsplit_once('.').unwrap_or((file_name, ""));
+
+                    let title = match (chapter.title_name.is_empty(), chapter.range.is_empty()) {
+                        (false, _) => chapter.title_name.clone(),
+                        (true, false) => format!("{} {}", series.name, chapter.range),
+                        (true, true) => stem.to_string(),
+                    };
+
+                    books.push(RemoteBook {
+                        id: chapter.id.to_string(),
+                        library_id: series.library_id.to_string(),
+                        series: series.name.clone(),
+                        title,
+                        extension: extension.to_lowercase(),
+                    });
+                }
+            }
+        }
+
+        Ok(books)
+    }
+
+    fn download(&self, book: &RemoteBook) -> Result<Vec<u8>> {
+        let response = self
+            .agent
+            .get(format!("{}/api/Download/chapter", self.url))
+            .header("Authorization", &self.token)
+            .query("chapterId", &book.id)
+            .call()?;
+        super::read_bytes(response)
+    }
+
+    fn scan(&self, library_id: &str) -> Result<()> {
+        self.agent
+            .post(format!("{}/api/Library/scan", self.url))
+            .header("Authorization", &self.token)
+            .query("libraryId", library_id)
+            .send_empty()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{RemoteBook, Server, ServerConfig};
+
+pub struct Komga {
+    url: String,
+    // header name and value
+    auth: (&'static str, String),
+    agent: ureq::Agent,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    content: Vec<Book>,
+    last: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Book {
+    id: String,
+    library_id: String,
+    series_title: String,
+    name: String,
+    // path of the file on the server
+    url: String,
+}
+
+impl Komga {
+    pub fn new(config: &ServerConfig) -> Result<Self> {
+        let auth = match (&config.api_key, config.basic_auth()) {
+            (Some(key), _) => ("X-API-Key", key.clone()),
+            (None, Some(basic)) => ("Authorization", basic),
+            (None, None) => anyhow::bail!("Komga needs an api_key or a username and password"),
+        };
+
+        Ok(Self {
+            url: config.url.trim_end_matches('/').to_string(),
+            auth,
+            agent: super::agent(),
+        })
+    }
+}
+
+impl Server for Komga {
+    fn books(&self, library: Option<&str>) -> Result<Vec<RemoteBook>> {
+        let mut books = Vec::new();
+
+        for page in 0.. {
+            let mut request = self
+                .agent
+                .get(format!("{}/api/v1/books", self.url))
+                .header(self.auth.0, &self.auth.1)
+                .query("page", page.to_string())
+                .query("size", "500");
+            if let Some(library) = library {
+                request = request.query("library_id", library);
+            }
+
+            let page: Page = request
+                .call()
+                .context("Failed to list Komga books")?
+                .body_mut()
+                .read_json()
+                .context("Unexpected response listing Komga books")?;
+
+            books.extend(page.content.into_iter().map(|book| {
+                RemoteBook {
+                    extension: book
+                        .url
+                        .rsplit_once('.')
+                        .map(|(_, ext)| ext.to_lowercase())
+                        .unwrap_or_default(),
+                    id: book.id,
+                    library_id: book.library_id,
+                    series: book.series_title,
+                    title: book.name,
+                }
+            }));
+
+            if page.last {
+                break;
+            }
+        }
+
+        Ok(books)
+    }
+
+    fn download(&self, book: &RemoteBook) -> Result<Vec<u8>> {
+        let response = self
+            .agent
+            .get(format!("{}/api/v1/books/{}/file", self.url, book.id))
+            .header(self.auth.0, &self.auth.1)
+            .call()?;
+        super::read_bytes(response)
+    }
+
+    fn scan(&self, library_id: &str) -> Result<()> {
+        self.agent
+            .post(format!("{}/api/v1/libraries/{library_id}/scan", self.url))
+            .header(self.auth.0, &self.auth.1)
+            .send_empty()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+//! Pulling comics from a Komga or Kavita server, converting them, and storing the
+//! results in the `<library>/<series>/<book>` layout both servers scan
+
+mod kavita;
+mod komga;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde::Deserialize;
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use comically::batch::{Batch, Input};
+use comically::filename::sanitize;
+use comically::{nonblocking, ComicConfig, ComicFile};
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerKind {
+    Komga,
+    Kavita,
+}
+
+/// An entry of the `servers` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerConfig {
+    pub url: String,
+    pub kind: ServerKind,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl ServerConfig {
+    /// Finds the credentials for `url` in the config file
+    pub fn find(config_path: Option<&Path>, url: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            servers: Vec<ServerConfig>,
+        }
+
+        let path = match config_path {
+            Some(path) => path.to_path_buf(),
+            None => ComicConfig::config_path().context("No config directory found")?,
+        };
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        let file: File = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse config {}", path.display()))?;
+
+        let url = url.trim_end_matches('/');
+        file.servers
+            .into_iter()
+            .find(|server| server.url.trim_end_matches('/') == url)
+            .with_context(|| format!("No entry for {url} in the `servers` section of the config"))
+    }
+
+    fn basic_auth(&self) -> Option<String> {
+        let (username, password) = (self.username.as_ref()?, self.password.as_ref()?);
+        let encoded =
+            base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+        Some(format!("Basic {encoded}"))
+    }
+}
+
+/// A book on the server that can be downloaded as an archive
+pub struct RemoteBook {
+    pub id: String,
+    pub library_id: String,
+    pub series: String,
+    pub title: String,
+    /// extension of the file on the server, e.g. `cbz`
+    pub extension: String,
+}
+
+trait Server {
+    fn books(&self, library: Option<&str>) -> Result<Vec<RemoteBook>>;
+    fn download(&self, book: &RemoteBook) -> Result<Vec<u8>>;
+    /// Asks the server to pick up new files in a library
+    fn scan(&self, library_id: &str) -> Result<()>;
+}
+
+pub struct SyncOptions<'a> {
+    pub library: Option<&'a str>,
+    pub output_dir: &'a Path,
+    /// `output_dir` is the server's library folder, rescan it after converting
+    pub push: bool,
+    pub quiet: bool,
+}
+
+pub fn run(server: &ServerConfig, config: &ComicConfig, options: SyncOptions) -> Result<()> {
+    let server: Arc<dyn Server + Send + Sync> = match server.kind {
+        ServerKind::Komga => Arc::new(komga::Komga::new(server)?),
+        ServerKind::Kavita => Arc::new(kavita::Kavita::new(server)?),
+    };
+    // requests and file I/O on the blocking threads, pages on rayon's pool
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .context("Failed to start the async runtime")?
+        .block_on(sync(server, config, options))
+}
+
+// each book is downloaded while the one before it converts
+async fn sync(
+    server: Arc<dyn Server + Send + Sync>,
+    config: &ComicConfig,
+    options: SyncOptions<'_>,
+) -> Result<()> {
+    let books = {
+        let server = server.clone();
+        let library = options.library.map(str::to_string);
+        blocking(move || server.books(library.as_deref())).await?
+    };
+    log::info!("Found {} books on the server", books.len());
+
+    let download_dir = comically::temp::TempRoot::for_config(config)
+        .create("download")
+        .context("Failed to create download directory")?;
+    let mut touched_libraries = BTreeSet::new();
+    let mut converting: Option<JoinHandle<Result<()>>> = None;
+
+    for book in books {
+        if !matches!(book.extension.as_str(), "cbz" | "cbr" | "zip" | "rar") {
+            log::debug!(
+                "Skipping {}: unsupported format {}",
+                book.title,
+                book.extension
+            );
+            continue;
+        }
+
+        // names from the server end up as path components
+        let series_dir = options.output_dir.join(sanitize(&book.series));
+        let title = sanitize(&book.title);
+        let named = ComicFile::new(format!("{title}.{}", book.extension).into());
+        let output = series_dir.join(comically::batch::output_name(&named, config));
+        if tokio::fs::try_exists(&output).await.unwrap_or(false) {
+            log::debug!("Skipping {}: already converted", book.title);
+            continue;
+        }
+
+        if !options.quiet {
+            log::info!("Downloading: {} / {}", book.series, book.title);
+        }
+        let book = Arc::new(book);
+        let data = {
+            let (server, book) = (server.clone(), book.clone());
+            blocking(move || server.download(&book)).await
+        }
+        .with_context(|| format!("Failed to download {}", book.title))?;
+
+        let source: PathBuf = download_dir
+            .path()
+            .join(format!("{title}.{}", book.extension));
+        tokio::fs::write(&source, data)
+            .await
+            .context("Failed to save download")?;
+        tokio::fs::create_dir_all(&series_dir)
+            .await
+            .context("Failed to create series directory")?;
+
+        // one conversion at a time, it already processes its pages in parallel
+        if let Some(previous) = converting.take() {
+            previous.await??;
+        }
+        let batch = Arc::new(Batch::new(config.clone(), series_dir));
+        converting = Some(tokio::spawn(convert(batch, source)));
+
+        touched_libraries.insert(book.library_id.clone());
+    }
+    if let Some(last) = converting {
+        last.await??;
+    }
+
+    if options.push {
+        for library in touched_libraries {
+            let server = server.clone();
+            blocking(move || {
+                server
+                    .scan(&library)
+                    .with_context(|| format!("Failed to trigger a scan of library {library}"))?;
+                log::info!("Triggered a scan of library {library}");
+                Ok(())
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// converts a downloaded book, which is removed afterwards
+async fn convert(batch: Arc<Batch>, source: PathBuf) -> Result<()> {
+    let report = nonblocking::convert(batch, Input::new(ComicFile::new(source.clone()))).await;
+    tokio::fs::remove_file(&source).await.ok();
+    report.into_result()?;
+    Ok(())
+}
+
+// a blocking request, off the runtime's thread
+async fn blocking<T: Send + 'static>(
+    request: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(request)
+        .await
+        .context("Server request failed")?
+}
+
+fn agent() -> ureq::Agent {
+    ureq::Agent::new_with_defaults()
+}
+
+fn read_bytes(mut response: ureq::http::Response<ureq::Body>) -> Result<Vec<u8>> {
+    response
+        .body_mut()
+        .with_config()
+        .limit(u64::MAX)
+        .read_to_vec()
+        .context("Failed to read response")
+}
+
+#[test]
+fn sync_converts_the_books_of_the_server() {
+    use comically::sample::SampleArchive;
+    use std::sync::Mutex;
+
+    struct Fake {
+        scanned: Mutex<Vec<String>>,
+    }
+    impl Server for Fake {
+        fn books(&self, _library: Option<&str>) -> Result<Vec<RemoteBook>> {
+            let book = |id: &str, extension: &str| RemoteBook {
+                id: id.into(),
+                library_id: "1".into(),
+                series: "Series: One".into(),
+                title: format!("Vol. {id}"),
+                extension: extension.into(),
+            };
+            Ok(vec![book("1", "cbz"), book("2", "pdf"), book("3", "cbz")])
+        }
+        fn download(&self, _book: &RemoteBook) -> Result<Vec<u8>> {
+            Ok(SampleArchive::with_pages(2).to_bytes())
+        }
+        fn scan(&self, library_id: &str) -> Result<()> {
+            self.scanned.lock().unwrap().push(library_id.into());
+            Ok(())
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = ComicConfig {
+        output_format: comically::OutputFormat::Cbz,
+        page_cache: false,
+        temp_dir: Some(dir.path().join("temp")),
+        ..ComicConfig::default()
+    };
+    let server = Arc::new(Fake {
+        scanned: Mutex::new(Vec::new()),
+    });
+    let options = SyncOptions {
+        library: None,
+        output_dir: dir.path(),
+        push: true,
+        quiet: true,
+    };
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(sync(server.clone(), &config, options))
+        .unwrap();
+
+    let series = dir.path().join("Series_ One");
+    assert!(series.join("Vol. 1.cbz").exists());
+    assert!(!series.join("Vol. 2.cbz").exists());
+    assert!(series.join("Vol. 3.cbz").exists());
+    assert_eq!(*server.scanned.lock().unwrap(), ["1"]);
+}
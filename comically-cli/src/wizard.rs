@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
+
+use std::path::PathBuf;
+
+use comically::device::{Device, Preset};
+use comically::{ComicConfig, ComicFile, OutputFormat};
+
+const FORMATS: [OutputFormat; 3] = [OutputFormat::Cbz, OutputFormat::Epub, OutputFormat::Mobi];
+
+/// Prompts for the comics to convert in the current directory, then the device and
+/// output format, starting from the values already in `config`
+pub fn run(config: &mut ComicConfig) -> Result<Vec<PathBuf>> {
+    let theme = ColorfulTheme::default();
+
+    let files = find_comic_files()?;
+    if files.is_empty() {
+        anyhow::bail!("No comic files (cbz, cbr, zip, rar) found in the current directory");
+    }
+
+    let titles: Vec<&str> = files.iter().map(ComicFile::title).collect();
+    let selected = MultiSelect::with_theme(&theme)
+        .with_prompt("Comics to convert (space to select, enter to confirm)")
+        .items(&titles)
+        .interact()
+        .context("Failed to read file selection")?;
+    if selected.is_empty() {
+        anyhow::bail!("No comic selected");
+    }
+
+    let presets: Vec<Preset> = Preset::iter().collect();
+    let names: Vec<&str> = presets.iter().map(Preset::name).collect();
+    let current = match &config.device {
+        Device::Preset(preset) => presets.iter().position(|p| p == preset).unwrap_or(0),
+        Device::Custom { .. } => 0,
+    };
+    let preset = Select::with_theme(&theme)
+        .with_prompt("Device")
+        .items(&names)
+        .default(current)
+        .interact()
+        .context("Failed to read device selection")?;
+    config.set_device(presets[preset].into());
+
+    let current = FORMATS
+        .iter()
+        .position(|format| *format == config.output_format)
+        .unwrap_or(0);
+    let format = Select::with_theme(&theme)
+        .with_prompt("Output format")
+        .items(&FORMATS.map(OutputFormat::as_str))
+        .default(current)
+        .interact()
+        .context("Failed to read format selection")?;
+    config.output_format = FORMATS[format];
+
+    Ok(selected
+        .into_iter()
+        .map(|i| files[i].as_path().to_path_buf())
+        .collect())
+}
+
+fn find_comic_files() -> Result<Vec<ComicFile>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(".").context("Failed to read current directory")? {
+        let path = entry.context("Failed to read directory entry")?.path();
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("cbz") | Some("cbr") | Some("zip") | Some("rar") if path.is_file() => {
+                files.push(ComicFile::new(path));
+            }
+            _ => {}
+        }
+    }
+
+    files.sort_by(|a, b| a.title().cmp(b.title()));
+    Ok(files)
+}
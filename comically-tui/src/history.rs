@@ -0,0 +1,198 @@
+//! Conversions done in the TUI, kept in `history.json` next to the config file so past
+//! jobs can be reviewed and run again with the settings they used.
+
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use comically::{metadata::fetch::Candidate, ComicConfig, ComicFile};
+
+use crate::pipeline::{FileChoices, FileOverrides, PageChoices, SeriesChoices};
+
+// oldest entries are dropped past this, the file is rewritten after every conversion
+const MAX_ENTRIES: usize = 200;
+
+// workers finishing at the same time would otherwise drop each other's entries
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// One converted comic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub input: PathBuf,
+    /// The settings of the conversion, with the series and pages picked for the comic
+    pub config: ComicConfig,
+    pub output_dir: PathBuf,
+    /// The written files, empty when it failed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<PathBuf>,
+    /// Seconds since the epoch when it was done
+    pub finished: u64,
+    pub duration_secs: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Entry {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Past conversions, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    pub entries: Vec<Entry>,
+}
+
+impl History {
+    pub fn path() -> Option<PathBuf> {
+        Some(ComicConfig::config_path()?.with_file_name("history.json"))
+    }
+
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| {
+                serde_json::from_str(&contents)
+                    .inspect_err(|e| log::warn!("failed to parse history: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn push(&mut self, entry: Entry) {
+        self.entries.push(entry);
+        let excess = self.entries.len().saturating_sub(MAX_ENTRIES);
+        self.entries.drain(..excess);
+    }
+
+    /// Series, pages, output formats and crops picked for `inputs` the last time they
+    /// were converted, to convert them again on top of `config`, the settings of their
+    /// batch
+    pub fn choices(
+        &self,
+        inputs: &[PathBuf],
+        config: &ComicConfig,
+    ) -> (SeriesChoices, PageChoices, FileChoices) {
+        let mut series = SeriesChoices::new();
+        let mut pages = PageChoices::new();
+        let mut overrides = FileChoices::new();
+        for input in inputs {
+            let Some(entry) = self
+                .entries
+                .iter()
+                .rev()
+                .find(|entry| &entry.input == input)
+            else {
+                continue;
+            };
+            if let Some(picked) = &entry.config.pages {
+                pages.insert(input.clone(), picked.clone());
+            }
+            let picked = FileOverrides {
+                output_format: Some(entry.config.output_format)
+                    .filter(|&format| format != config.output_format),
+                fixed_crop: (entry.config.fixed_crop != config.fixed_crop)
+                    .then(|| entry.config.fixed_crop.unwrap_or_default()),
+                // what the file says is read again
+                series: None,
+            };
+            if !picked.is_empty() {
+                overrides.insert(input.clone(), picked);
+            }
+            let Some(name) = &entry.config.series else {
+                continue;
+            };
+            if entry.config.series != config.series || entry.config.metadata != config.metadata {
+                let parsed = comically::filename::parse(ComicFile::new(input.clone()).title());
+                series.insert(
+                    parsed.series,
+                    Candidate {
+                        series: name.name.clone(),
+                        year: None,
+                        metadata: entry.config.metadata.clone().unwrap_or_default(),
+                    },
+                );
+            }
+        }
+        (series, pages, overrides)
+    }
+
+    /// Adds `entry` to the file, a history that can't be written only costs the entry
+    pub fn record(entry: Entry) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        let _guard = FILE_LOCK.lock();
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+
+        let mut history = Self::load();
+        history.push(entry);
+        let written = serde_json::to_string(&history)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(fs::write(&path, json)?));
+        if let Err(e) = written {
+            log::warn!("failed to write history {}: {e}", path.display());
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[test]
+fn history_keeps_the_latest_entries() {
+    let entry = |finished: u64| Entry {
+        input: PathBuf::from(format!("{finished}.cbz")),
+        config: ComicConfig::default(),
+        output_dir: PathBuf::from("out"),
+        outputs: Vec::new(),
+        finished,
+        duration_secs: 1.5,
+        error: Some("failed".into()),
+    };
+
+    let mut history = History::default();
+    for finished in 0..MAX_ENTRIES as u64 + 5 {
+        history.push(entry(finished));
+    }
+    assert_eq!(history.entries.len(), MAX_ENTRIES);
+    assert_eq!(history.entries[0].finished, 5);
+
+    let json = serde_json::to_string(&history).unwrap();
+    let read: History = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.entries.len(), MAX_ENTRIES);
+    assert_eq!(read.entries[0].input, PathBuf::from("5.cbz"));
+    assert!(!read.entries[0].is_ok());
+
+    // formats and crops other than the batch's are picked again
+    let mut cbz = entry(100);
+    cbz.config.output_format = comically::OutputFormat::Cbz;
+    history.push(cbz);
+    let mut cropped = entry(101);
+    cropped.config.fixed_crop = Some(comically::FixedCrop::top(40));
+    history.push(cropped);
+    let inputs = ["100.cbz", "101.cbz", "6.cbz"].map(PathBuf::from);
+    let (_, _, overrides) = history.choices(&inputs, &ComicConfig::default());
+    assert_eq!(overrides.len(), 2);
+    assert_eq!(
+        overrides[&inputs[0]].output_format,
+        Some(comically::OutputFormat::Cbz)
+    );
+    assert_eq!(
+        overrides[&inputs[1]],
+        FileOverrides {
+            output_format: None,
+            fixed_crop: Some(comically::FixedCrop::top(40)),
+            series: None,
+        }
+    );
+}
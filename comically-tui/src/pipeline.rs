@@ -1,257 +1,465 @@
-use anyhow::Result;
-use rayon::iter::{ParallelBridge, ParallelIterator};
-
 use std::{
-    path::PathBuf,
-    sync::mpsc,
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Condvar, Mutex, MutexGuard, PoisonError},
     thread,
-    time::{Duration, Instant},
+    time::Instant,
 };
 
-use comically::{ComicConfig, ComicFile, OutputFormat};
+use comically::{
+    archive::ArchiveOptions,
+    batch::{self, Batch, Input, Stage},
+    checkpoint::{Checkpoint, Resume},
+    dir_config::DirConfig,
+    metadata::fetch::Candidate,
+    ComicConfig, ComicFile, FixedCrop, OutputFormat, PageRanges, Series,
+};
 
-use crate::tui::progress::{ComicStage, ComicStatus, ProgressEvent};
+use crate::history::{self, History};
+use crate::tui::progress::{ComicStatus, ProgressEvent};
 use crate::Event;
 
-pub fn process_files(
-    files: Vec<ComicFile>,
+/// Commands sent from the progress screen to the long-lived queue worker
+pub enum QueueCommand {
+    /// Register and queue new comics at the back of the queue
+    Add(Vec<ComicFile>),
+    /// Re-queue a comic that previously failed, optionally tracing it to a log file
+    Retry { id: usize, verbose: bool },
+    /// New order for the comics that are still waiting to be processed
+    Reorder(Vec<usize>),
+}
+
+/// Series confirmed online, by the search terms of the comics' titles so the choice
+/// applies to every volume of the series
+pub type SeriesChoices = HashMap<String, Candidate>;
+
+/// Pages picked in the preview for single comics, by path
+pub type PageChoices = HashMap<PathBuf, PageRanges>;
+
+/// Settings picked for single comics, by path, see [`FileOverrides`]
+pub type FileChoices = HashMap<PathBuf, FileOverrides>;
+
+/// Settings of a single comic in place of the batch's, and of its directory's for the crop
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileOverrides {
+    pub output_format: Option<OutputFormat>,
+    // an empty crop cuts nothing
+    pub fixed_crop: Option<FixedCrop>,
+    // typed in, in place of the one from its ComicInfo.xml or file name
+    pub series: Option<Series>,
+}
+
+impl FileOverrides {
+    pub fn apply(&self, config: &mut ComicConfig) {
+        if let Some(format) = self.output_format {
+            config.set_output_format(format);
+        }
+        if let Some(crop) = self.fixed_crop {
+            config.fixed_crop = (!crop.is_empty()).then_some(crop);
+        }
+        if let Some(series) = &self.series {
+            config.series = Some(series.clone());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Journal of the batch, shared by the workers
+type SharedCheckpoint = Arc<Mutex<Checkpoint>>;
+
+/// Runs until the command channel is closed and every queued comic is converted.
+#[allow(clippy::too_many_arguments)]
+pub fn process_queue(
+    commands: mpsc::Receiver<QueueCommand>,
     config: ComicConfig,
     output_dir: PathBuf,
+    resume: Option<Resume>,
+    series: SeriesChoices,
+    pages: PageChoices,
+    overrides: FileChoices,
+    nice: bool,
     event_tx: mpsc::Sender<Event>,
 ) {
     log::info!("processing with config: {:?}", config);
-    log::info!("processing {} files", files.len());
 
-    let (kindlegen_tx, kindlegen_rx) =
-        mpsc::channel::<(usize, PathBuf, PathBuf, mpsc::Sender<Event>)>();
+    let batch = Batch::new(config.clone(), output_dir);
+    let checkpoint = match Checkpoint::create(
+        &batch.targets[0].output_dir,
+        &batch.targets,
+        resume.as_ref(),
+    ) {
+        Ok(checkpoint) => Some(Arc::new(Mutex::new(checkpoint))),
+        Err(e) => {
+            log::warn!("converting without a checkpoint: {e}");
+            None
+        }
+    };
+
+    let shared = Shared {
+        queue: Mutex::new(Queue {
+            archive_options: config.archive_options(),
+            checkpoint: checkpoint.clone(),
+            files: Vec::new(),
+            pending: VecDeque::new(),
+            active: 0,
+            closed: false,
+            event_tx: event_tx.clone(),
+        }),
+        ready: Condvar::new(),
+    };
+    // in the background, comics are converted on low priority threads, one at a time
+    let pool = match nice {
+        true => comically::nice::pool()
+            .inspect_err(|e| log::warn!("converting at the normal priority: {e:#}"))
+            .ok(),
+        false => None,
+    };
+    let worker = Worker {
+        shared: &shared,
+        batch,
+        series,
+        pages,
+        overrides,
+        pool,
+        checkpoint,
+        event_tx,
+    };
+    // with a second worker, a comic waits on KindleGen while the next one is processed
+    let mobi = std::iter::once(&config.output_format)
+        .chain(
+            worker
+                .overrides
+                .values()
+                .filter_map(|o| o.output_format.as_ref()),
+        )
+        .any(|&format| format == OutputFormat::Mobi);
+    let workers = if mobi && worker.pool.is_none() { 2 } else { 1 };
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| worker.run());
+        }
+        // the progress screen keeps the channel open
+        let config_for = |comic: &ComicFile| worker.config_for(comic);
+        for command in commands {
+            shared.lock().apply(command, &config_for);
+            shared.ready.notify_all();
+        }
+        shared.lock().closed = true;
+        shared.ready.notify_all();
+    });
+}
 
-    if config.output_format == OutputFormat::Mobi {
-        let event_tx = event_tx.clone();
-        thread::spawn(move || {
-            poll_kindlegen(kindlegen_rx);
-            // after all the comics have finished conversion to mobi, send the complete event
-            processing_complete(&event_tx);
-        });
+fn refresh_catalog(output_dir: &Path) {
+    if let Err(e) = comically::opds::write_catalog(output_dir) {
+        log::warn!("failed to write OPDS catalog: {e}");
     }
+}
 
-    let comics: Vec<_> = files
-        .into_iter()
-        .enumerate()
-        // Register all comics ahead of time for progress tracking
-        .inspect(|(id, comic)| register_comic(&event_tx, *id, comic.title().to_owned()))
-        .filter_map(|(id, comic)| {
-            let iter = match comically::archive::unarchive_comic_iter(&comic) {
-                Ok(iter) => iter,
-                Err(e) => {
-                    log::error!("Error in comic: {} {e}", comic.title());
-                    error(&event_tx, id, e);
-                    return None;
-                }
-            };
-            update_stats(&event_tx, id, iter.num_images());
-            Some((id, comic, iter))
-        })
-        .collect();
-
-    // Reusable buffer for building archives - avoids repeated allocations
-    // Reserves 200MB, which should be enough for most comics
-    let mut build_buffer = Vec::with_capacity(200 * 1024 * 1024);
-
-    for (id, comic, archive_iter) in comics {
-        // Process images
-        let start = Instant::now();
-
-        send_comic_update(&event_tx, id, ComicStatus::ImageProcessingStart { start });
-
-        // Collect archive files
-        let Ok(files) = archive_iter.par_bridge().collect::<Result<Vec<_>>>() else {
-            error(
-                &event_tx,
-                id,
-                anyhow::anyhow!("Failed to collect archive files"),
-            );
-            continue;
-        };
+/// Where the trace log of a verbose retry is written
+pub fn verbose_log_path(output_dir: &Path, comic: &ComicFile) -> PathBuf {
+    output_dir.join(format!("{}.log", comic.title()))
+}
 
-        let on_processed = || {
-            send_comic_update(&event_tx, id, ComicStatus::ImageProcessed);
-        };
+// logs everything emitted on the worker thread while the comic is processed
+fn verbose_subscriber(path: &Path) -> std::io::Result<impl tracing::Subscriber + Send + Sync> {
+    let file = std::fs::File::create(path)?;
+    Ok(tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_file(true)
+        .with_line_number(true)
+        .with_ansi(false)
+        .with_writer(std::sync::Mutex::new(file))
+        .finish())
+}
 
-        let images =
-            match comically::image::process_batch_with_progress(files, &config, on_processed) {
-                Ok(imgs) => imgs,
-                Err(e) => {
-                    log::error!("Error processing images for {}: {e}", comic.title());
-                    error(&event_tx, id, e);
-                    continue;
-                }
-            };
+struct PendingComic {
+    id: usize,
+    verbose: bool,
+}
 
-        stage_complete(&event_tx, id, ComicStage::Process, &start);
-
-        log::info!("Processed {} images for {}", images.len(), comic.title());
-
-        // Build output format
-        let build_start = Instant::now();
-        send_comic_update(
-            &event_tx,
-            id,
-            ComicStatus::Progress {
-                stage: ComicStage::Package,
-                progress: 75.0,
-                start: build_start,
-            },
-        );
-
-        let build_result = match config.output_format {
-            OutputFormat::Cbz => {
-                comically::cbz::build_into(&images, &mut build_buffer);
-
-                let output_path = output_dir.join(comic.with_extension(config.output_format));
-                std::fs::write(&output_path, &build_buffer)
-                    .inspect(|_| log::info!("Created CBZ: {:?}", output_path))
-                    .map_err(|e| anyhow::anyhow!("Failed to write CBZ: {}", e))
+struct Queue {
+    archive_options: ArchiveOptions,
+    checkpoint: Option<SharedCheckpoint>,
+    /// every comic ever queued, indexed by id
+    files: Vec<ComicFile>,
+    pending: VecDeque<PendingComic>,
+    /// comics the workers are converting
+    active: usize,
+    /// no more commands are coming, workers stop once the queue is empty
+    closed: bool,
+    event_tx: mpsc::Sender<Event>,
+}
+
+impl Queue {
+    // `config_for` gives the settings a comic is converted with
+    fn apply(&mut self, command: QueueCommand, config_for: &dyn Fn(&ComicFile) -> ComicConfig) {
+        match command {
+            QueueCommand::Add(files) => {
+                log::info!("queueing {} files", files.len());
+                for comic in files {
+                    let id = self.files.len();
+                    let config = config_for(&comic);
+                    // Register comics ahead of time for progress tracking
+                    send_progress(
+                        &self.event_tx,
+                        ProgressEvent::RegisterComic {
+                            id,
+                            file_name: comic.title().to_owned(),
+                            fingerprint: config.fingerprint(),
+                            output_format: config.output_format,
+                        },
+                    );
+                    if let Some(Ok(mut checkpoint)) = self.checkpoint.as_ref().map(|c| c.lock()) {
+                        checkpoint.queued(comic.as_path());
+                    }
+                    self.files.push(comic);
+                    self.enqueue(id, false);
+                }
             }
-            OutputFormat::Epub => {
-                comically::epub::build_into(comic.title(), &config, &images, &mut build_buffer);
-                let output_path = output_dir.join(comic.with_extension(config.output_format));
-                std::fs::write(&output_path, &build_buffer)
-                    .inspect(|_| log::info!("Created EPUB: {:?}", output_path))
-                    .map_err(|e| anyhow::anyhow!("Failed to write EPUB: {}", e))
+            QueueCommand::Retry { id, verbose } => {
+                let already_pending = self.pending.iter().any(|pending| pending.id == id);
+                if id < self.files.len() && !already_pending {
+                    log::info!("retrying {}", self.files[id].title());
+                    send_comic_update(&self.event_tx, id, ComicStatus::Waiting);
+                    self.enqueue(id, verbose);
+                }
             }
-            OutputFormat::Mobi => {
-                comically::epub::build_into(comic.title(), &config, &images, &mut build_buffer);
-
-                let epub_path = output_dir.join(comic.with_extension(OutputFormat::Epub));
-                std::fs::write(&epub_path, &build_buffer)
-                    .inspect(|_| {
-                        log::info!("Created EPUB for MOBI: {:?}", epub_path);
-                        let output_mobi = output_dir.join(comic.with_extension(OutputFormat::Mobi));
-                        kindlegen_tx
-                            .send((id, epub_path, output_mobi, event_tx.clone()))
-                            .ok();
-                    })
-                    .map_err(|e| anyhow::anyhow!("Failed to write EPUB: {}", e))
+            QueueCommand::Reorder(order) => {
+                self.pending.make_contiguous().sort_by_key(|pending| {
+                    order
+                        .iter()
+                        .position(|ordered| *ordered == pending.id)
+                        .unwrap_or(usize::MAX)
+                });
             }
-        };
+        }
+    }
 
-        match build_result {
-            Ok(_) => {
-                stage_complete(&event_tx, id, ComicStage::Package, &build_start);
-                // For MOBI, we continue to kindlegen processing
-                if config.output_format != OutputFormat::Mobi {
-                    send_comic_update(&event_tx, id, ComicStatus::Success);
+    // the archive is listed up front so the progress screen knows every page count
+    fn enqueue(&mut self, id: usize, verbose: bool) {
+        let comic = &self.files[id];
+        match comically::archive::unarchive_comic_iter_with(comic, self.archive_options) {
+            Ok(archive_iter) => {
+                update_stats(&self.event_tx, id, archive_iter.num_images());
+                for entry in archive_iter.skipped() {
+                    log::info!(
+                        "{}: skipped {} ({})",
+                        comic.title(),
+                        entry.path.display(),
+                        entry.reason
+                    );
                 }
+                self.pending.push_back(PendingComic { id, verbose });
             }
             Err(e) => {
-                log::error!("Error building output for {}: {e}", comic.title());
-                error(&event_tx, id, e);
+                log::error!("Error in comic: {} {e}", comic.title());
+                if let Some(Ok(mut checkpoint)) = self.checkpoint.as_ref().map(|c| c.lock()) {
+                    checkpoint.failed(comic.as_path());
+                }
+                error(&self.event_tx, id, e);
             }
         }
     }
+}
 
-    match config.output_format {
-        OutputFormat::Epub | OutputFormat::Cbz => {
-            processing_complete(&event_tx);
-        }
-        _ => {}
-    }
+struct Shared {
+    queue: Mutex<Queue>,
+    /// signalled when comics are queued or the queue is closed
+    ready: Condvar,
 }
 
-pub fn poll_kindlegen(tx: mpsc::Receiver<(usize, PathBuf, PathBuf, mpsc::Sender<Event>)>) {
-    struct KindleGenStatus {
-        id: usize,
-        spawned: comically::mobi::SpawnedKindleGen,
-        start: Instant,
-        event_tx: mpsc::Sender<Event>,
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
     }
+}
 
-    let mut pending = Vec::<Option<KindleGenStatus>>::new();
-
-    'outer: loop {
-        loop {
-            let result = tx.try_recv();
-
-            match result {
-                Ok((id, epub_path, output_mobi, event_tx)) => {
-                    let start = Instant::now();
-                    send_comic_update(
-                        &event_tx,
-                        id,
-                        ComicStatus::Progress {
-                            stage: ComicStage::Convert,
-                            progress: 75.0,
-                            start,
-                        },
-                    );
+struct Worker<'a> {
+    shared: &'a Shared,
+    batch: Batch,
+    series: SeriesChoices,
+    pages: PageChoices,
+    overrides: FileChoices,
+    pool: Option<rayon::ThreadPool>,
+    checkpoint: Option<SharedCheckpoint>,
+    event_tx: mpsc::Sender<Event>,
+}
 
-                    match comically::mobi::create(epub_path, output_mobi) {
-                        Ok(spawned) => {
-                            pending.push(Some(KindleGenStatus {
-                                id,
-                                spawned,
-                                start,
-                                event_tx,
-                            }));
-                        }
+impl Worker<'_> {
+    fn run(&self) {
+        while let Some((id, comic, verbose)) = self.next() {
+            let convert = || {
+                if verbose {
+                    let output_dir = &self.batch.targets[0].output_dir;
+                    match verbose_subscriber(&verbose_log_path(output_dir, &comic)) {
+                        Ok(subscriber) => tracing::subscriber::with_default(subscriber, || {
+                            self.convert(id, &comic)
+                        }),
                         Err(e) => {
-                            log::error!("Error creating MOBI: {e}");
-                            error(&event_tx, id, e);
+                            log::warn!("failed to create verbose log: {e}");
+                            self.convert(id, &comic)
                         }
                     }
+                } else {
+                    self.convert(id, &comic)
                 }
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    if pending.is_empty() {
-                        break 'outer;
-                    } else {
-                        break;
-                    }
-                }
-                Err(mpsc::TryRecvError::Empty) => {
-                    break;
-                }
+            };
+            let converted = match &self.pool {
+                Some(pool) => pool.install(convert),
+                None => convert(),
+            };
+
+            let mut queue = self.shared.lock();
+            queue.active -= 1;
+            let idle = queue.pending.is_empty() && queue.active == 0;
+            drop(queue);
+
+            let config = &self.batch.targets[0].config;
+            if converted && idle && config.opds_catalog {
+                refresh_catalog(&self.batch.targets[0].output_dir);
             }
         }
+    }
 
-        for s in pending.iter_mut() {
-            let is_done = match s {
-                Some(status) => match status.spawned.try_wait() {
-                    Ok(Some(_)) => true,
-                    Ok(None) => false,
-                    Err(e) => {
-                        log::error!("error waiting for kindlegen: {}", e);
-                        true
-                    }
+    // blocks until a comic is queued, `None` once the queue is closed and empty
+    fn next(&self) -> Option<(usize, ComicFile, bool)> {
+        let mut queue = self.shared.lock();
+        loop {
+            if let Some(pending) = queue.pending.pop_front() {
+                queue.active += 1;
+                let comic = queue.files[pending.id].clone();
+                return Some((pending.id, comic, pending.verbose));
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self
+                .shared
+                .ready
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    // converts a comic, reporting its progress to the progress screen
+    fn convert(&self, id: usize, comic: &ComicFile) -> bool {
+        if let Some(Ok(mut checkpoint)) = self.checkpoint.as_ref().map(|c| c.lock()) {
+            checkpoint.started(comic.as_path());
+        }
+
+        // from the least to the most specific: the directory, the series, the file
+        let mut input = Input::new(comic.clone());
+        if let Some(local) = DirConfig::for_comic(comic.as_path()) {
+            input = input.with_overrides(move |config| local.apply(config));
+        }
+        let parsed = comically::filename::parse(comic.title());
+        if let Some(candidate) = self.series.get(&parsed.series).cloned() {
+            input = input.with_overrides(move |config| candidate.apply(config));
+        }
+        if let Some(pages) = self.pages.get(comic.as_path()) {
+            input = input.with_pages(pages.clone());
+        }
+        if let Some(overrides) = self.overrides.get(comic.as_path()).cloned() {
+            input = input.with_overrides(move |config| overrides.apply(config));
+        }
+
+        let event_tx = &self.event_tx;
+        let report = self.batch.convert(&input, &|event| {
+            let status = match event {
+                batch::Event::StageStarted(Stage::Process) => ComicStatus::ImageProcessingStart {
+                    start: Instant::now(),
                 },
-                _ => false,
+                // KindleGen reports how far along it is, packaging is too short to
+                batch::Event::StageStarted(stage) => ComicStatus::Progress {
+                    stage,
+                    progress: 0.0,
+                    start: Instant::now(),
+                },
+                batch::Event::Opened { pages, .. } => {
+                    update_stats(event_tx, id, pages);
+                    return;
+                }
+                batch::Event::Extracted(extracted) => ComicStatus::Extracting { extracted },
+                batch::Event::PageProcessed(_) => ComicStatus::ImageProcessed,
+                batch::Event::Converting(progress) => ComicStatus::Converting {
+                    progress: f64::from(progress) * 100.0,
+                },
+                batch::Event::StageCompleted { stage, duration } => {
+                    ComicStatus::StageCompleted { stage, duration }
+                }
+                batch::Event::Finished(_) => return,
             };
+            send_comic_update(event_tx, id, status);
+        });
 
-            if is_done {
-                if let Some(status) = s.take() {
-                    log::debug!("KindleGen process completed");
-                    match status.spawned.wait() {
-                        Ok(_) => {
-                            stage_complete(
-                                &status.event_tx,
-                                status.id,
-                                ComicStage::Convert,
-                                &status.start,
-                            );
-                            send_comic_update(&status.event_tx, status.id, ComicStatus::Success);
-                            log::debug!("MOBI conversion successful");
-                        }
-                        Err(e) => {
-                            log::error!("MOBI conversion failed: {e}");
-                            error(&status.event_tx, status.id, e);
-                        }
-                    }
+        for warning in &report.warnings {
+            log::warn!("{}: {warning}", comic.title());
+        }
+        self.record(comic, &report);
+        match report.error {
+            None => {
+                log::info!(
+                    "Converted {} with settings {}: {:?}",
+                    comic.title(),
+                    report.fingerprints.join(", "),
+                    report.outputs
+                );
+                send_comic_update(event_tx, id, ComicStatus::Success);
+                if let Some(Ok(mut checkpoint)) = self.checkpoint.as_ref().map(|c| c.lock()) {
+                    checkpoint.completed(comic.as_path());
                 }
+                true
+            }
+            Some(e) => {
+                log::error!("Error converting {}: {e}", comic.title());
+                if let Some(Ok(mut checkpoint)) = self.checkpoint.as_ref().map(|c| c.lock()) {
+                    checkpoint.failed(comic.as_path());
+                }
+                error(event_tx, id, e);
+                false
             }
         }
+    }
 
-        pending.retain(|s| s.is_some());
+    // the settings of the first target with the choices made for `comic`
+    fn config_for(&self, comic: &ComicFile) -> ComicConfig {
+        let mut config = self.batch.targets[0].config.clone();
+        if let Some(local) = DirConfig::for_comic(comic.as_path()) {
+            local.apply(&mut config);
+        }
+        let parsed = comically::filename::parse(comic.title());
+        if let Some(candidate) = self.series.get(&parsed.series) {
+            candidate.apply(&mut config);
+        }
+        if let Some(pages) = self.pages.get(comic.as_path()) {
+            config.pages = Some(pages.clone());
+        }
+        if let Some(overrides) = self.overrides.get(comic.as_path()) {
+            overrides.apply(&mut config);
+        }
+        config
+    }
 
-        thread::sleep(Duration::from_millis(100));
+    // the settings the comic was converted with, so running it again from the history
+    // doesn't depend on the choices of this batch
+    fn record(&self, comic: &ComicFile, report: &batch::ComicReport) {
+        let Some(target) = self.batch.targets.first() else {
+            return;
+        };
+
+        History::record(history::Entry {
+            input: comic.as_path().to_path_buf(),
+            config: self.config_for(comic),
+            output_dir: target.output_dir.clone(),
+            outputs: report.outputs.clone(),
+            finished: history::now(),
+            duration_secs: report.timings.total().as_secs_f64(),
+            error: report.error.as_ref().map(|e| format!("{e:#}")),
+        });
     }
 }
 
@@ -268,25 +476,6 @@ fn send_comic_update(tx: &mpsc::Sender<Event>, id: usize, status: ComicStatus) {
     send_progress(tx, ProgressEvent::ComicUpdate { id, status });
 }
 
-fn register_comic(tx: &mpsc::Sender<Event>, id: usize, file_name: String) {
-    send_progress(tx, ProgressEvent::RegisterComic { id, file_name });
-}
-
 fn update_stats(tx: &mpsc::Sender<Event>, id: usize, total_images: usize) {
     send_progress(tx, ProgressEvent::ComicStats { id, total_images });
 }
-
-fn processing_complete(tx: &mpsc::Sender<Event>) {
-    send_progress(tx, ProgressEvent::ProcessingComplete);
-}
-
-fn stage_complete(tx: &mpsc::Sender<Event>, id: usize, stage: ComicStage, start: &Instant) {
-    send_comic_update(
-        tx,
-        id,
-        ComicStatus::StageCompleted {
-            stage,
-            duration: start.elapsed(),
-        },
-    );
-}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+
+use comically::ComicConfig;
+
+use crate::tui::{i18n::Language, keymap::Keymap, theme::Palette};
+
+/// Everything the TUI persists to the shared config file.
+///
+/// The comic settings are flattened so the file stays readable by [`ComicConfig::load`],
+/// with TUI-only sections stored next to them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(flatten)]
+    pub comic: ComicConfig,
+    #[serde(default)]
+    pub keymap: Keymap,
+    // taken from the locale when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    // name of a built-in theme or one of `themes`, detected from the terminal when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    // whether batches are converted in the background, see `comically::nice`, the CLI
+    // has `--nice` instead
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub nice: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub themes: Vec<Palette>,
+    // sections owned by other frontends (e.g. the CLI's `servers`), kept as-is on save
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Settings {
+    pub fn language(&self) -> Language {
+        self.language.unwrap_or_else(Language::from_env)
+    }
+
+    pub fn load() -> Option<Self> {
+        let config_path = ComicConfig::config_path()?;
+
+        let contents = fs::read_to_string(&config_path).ok()?;
+        comically::migrate::upgrade_file(&config_path, &contents)
+            .and_then(|settings| Ok(serde_json::from_value(settings)?))
+            .inspect_err(|e| log::warn!("failed to parse config: {e}"))
+            .ok()
+    }
+
+    pub fn save(&self) -> Option<()> {
+        let config_path = ComicConfig::config_path()?;
+
+        // Create config directory if it doesn't exist
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).ok()?;
+        }
+
+        serde_json::to_string_pretty(self)
+            .ok()
+            .and_then(|json| fs::write(&config_path, json).ok())
+    }
+}
+
+#[test]
+fn settings_keep_unknown_sections() {
+    let mut json = serde_json::to_value(Settings::default()).unwrap();
+    json["servers"] = serde_json::json!([{ "url": "http://komga", "kind": "komga" }]);
+
+    let settings: Settings = serde_json::from_value(json).unwrap();
+    let saved = serde_json::to_value(&settings).unwrap();
+
+    assert_eq!(saved["servers"][0]["url"], "http://komga");
+    // fields of the flattened config aren't duplicated into the leftovers
+    assert!(!settings.other.contains_key("device"));
+}
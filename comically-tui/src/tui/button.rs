@@ -118,18 +118,14 @@ where
         if let Some(mouse) = self.mouse_event {
             if area.contains(Position::new(mouse.column, mouse.row)) {
                 match mouse.kind {
-                    MouseEventKind::Down(MouseButton::Left) => {
-                        if self.enabled {
-                            self.state = State::Pressed;
-                        }
+                    MouseEventKind::Down(MouseButton::Left) if self.enabled => {
+                        self.state = State::Pressed;
                     }
-                    MouseEventKind::Up(MouseButton::Left) => {
-                        if self.enabled {
-                            if let Some(on_click) = self.on_click.take() {
-                                on_click.call_once();
-                            }
-                            self.state = State::Normal;
+                    MouseEventKind::Up(MouseButton::Left) if self.enabled => {
+                        if let Some(on_click) = self.on_click.take() {
+                            on_click.call_once();
                         }
+                        self.state = State::Normal;
                     }
                     _ => {}
                 }
@@ -0,0 +1,98 @@
+use imageproc::drawing::draw_hollow_rect_mut;
+use imageproc::image::{DynamicImage, Rgb};
+use imageproc::rect::Rect as PixelRect;
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::Style,
+    text::Line,
+    widgets::{Paragraph, Sparkline, Widget},
+};
+
+use comically::image::PageStats;
+
+use crate::tui::{i18n::Strings, Theme};
+
+// the margins auto-crop removes are darkened by this factor outside the outline
+const MARGIN_SHADE: f32 = 0.6;
+const OUTLINE: Rgb<u8> = Rgb([230, 40, 40]);
+
+/// The page as it is in the archive, with the margins auto-crop removes shaded and the
+/// part it keeps outlined
+pub fn crop_overlay(source: &DynamicImage, stats: &PageStats) -> DynamicImage {
+    let mut page = source.to_rgb8();
+    let Some(margins) = stats.margins else {
+        return page.into();
+    };
+    let (left, top, width, height) = margins.crop(stats.dimensions);
+    let kept =
+        |x: u32, y: u32| (left..left + width).contains(&x) && (top..top + height).contains(&y);
+    for (x, y, pixel) in page.enumerate_pixels_mut() {
+        if !kept(x, y) {
+            pixel.0 = pixel.0.map(|level| (f32::from(level) * MARGIN_SHADE) as u8);
+        }
+    }
+    // a few pixels thick, so it still shows once scaled down to the terminal
+    let thickness = (page.width().max(page.height()) / 300).max(1);
+    for inset in 0..thickness {
+        let outline = PixelRect::at((left + inset) as i32, (top + inset) as i32).of_size(
+            width.saturating_sub(2 * inset).max(1),
+            height.saturating_sub(2 * inset).max(1),
+        );
+        draw_hollow_rect_mut(&mut page, outline, OUTLINE);
+    }
+    page.into()
+}
+
+/// The luminance histogram of the page under a line of what else was measured
+pub struct AnalysisWidget<'a> {
+    pub stats: &'a PageStats,
+    pub theme: &'a Theme,
+    pub strings: &'static Strings,
+}
+
+impl Widget for AnalysisWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let [summary_area, histogram_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(area);
+
+        Paragraph::new(Line::from((self.strings.page_analysis)(self.stats)))
+            .style(Style::default().fg(self.theme.border))
+            .centered()
+            .render(summary_area, buf);
+
+        Sparkline::default()
+            .data(histogram_bars(&self.stats.histogram, histogram_area.width))
+            .style(Style::default().fg(self.theme.content))
+            .render(histogram_area, buf);
+    }
+}
+
+// the 256 levels of the histogram in `bars` buckets, from black to white
+fn histogram_bars(histogram: &[u32; 256], bars: u16) -> Vec<u64> {
+    let bars = usize::from(bars).clamp(1, histogram.len());
+    (0..bars)
+        .map(|bar| {
+            let levels = bar * histogram.len() / bars..(bar + 1) * histogram.len() / bars;
+            histogram[levels]
+                .iter()
+                .map(|&count| u64::from(count))
+                .sum()
+        })
+        .collect()
+}
+
+#[test]
+fn histogram_buckets_keep_every_pixel() {
+    let mut histogram = [1; 256];
+    histogram[255] = 1000;
+    for bars in [1, 7, 64, 256, 400] {
+        let buckets = histogram_bars(&histogram, bars);
+        assert_eq!(buckets.iter().sum::<u64>(), 255 + 1000);
+        assert_eq!(
+            *buckets.last().unwrap(),
+            buckets.iter().copied().max().unwrap()
+        );
+    }
+    assert_eq!(histogram_bars(&histogram, 400).len(), 256);
+}
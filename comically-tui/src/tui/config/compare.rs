@@ -0,0 +1,219 @@
+use imageproc::image::{imageops, DynamicImage, GrayImage, Luma};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, Paragraph, StatefulWidget, Widget},
+};
+use ratatui_image::{protocol::StatefulProtocol, FilterType, Resize, StatefulImage};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use comically::ComicConfig;
+
+use crate::tui::{
+    config::{calculate_centered_image_area, render_image_placeholder, ConfigState, ModalState},
+    i18n::Strings,
+    keymap::Action,
+    utils::popup_block,
+};
+
+// space between the variants in the side by side image
+const GAP: u32 = 16;
+
+/// Tone settings tried side by side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variant {
+    pub label: &'static str,
+    pub gamma: f32,
+    pub brightness: i32,
+    pub contrast: i32,
+}
+
+impl Variant {
+    /// The current settings next to a lighter, a darker and an uncorrected take
+    pub fn around(config: &ComicConfig, strings: &Strings) -> Vec<Variant> {
+        let (gamma, brightness, contrast) = (config.gamma, config.brightness, config.contrast);
+        vec![
+            Variant {
+                label: strings.variant_current,
+                gamma,
+                brightness,
+                contrast,
+            },
+            Variant {
+                label: strings.variant_lighter,
+                gamma: (gamma - 0.4).max(0.1),
+                brightness: (brightness + 10).min(100),
+                contrast,
+            },
+            Variant {
+                label: strings.variant_darker,
+                gamma: (gamma + 0.4).min(3.0),
+                brightness: (brightness - 10).max(-100),
+                contrast,
+            },
+            Variant {
+                label: strings.variant_neutral,
+                gamma: 1.0,
+                brightness: 0,
+                contrast: 0,
+            },
+        ]
+    }
+
+    pub fn apply(&self, config: &mut ComicConfig) {
+        config.gamma = self.gamma;
+        config.brightness = self.brightness;
+        config.contrast = self.contrast;
+    }
+}
+
+pub struct CompareState {
+    pub variants: Vec<Variant>,
+    pub selected: usize,
+    // side by side rendering of every variant, once the worker is done
+    image: Option<(StatefulProtocol, (u32, u32))>,
+}
+
+impl CompareState {
+    pub fn new(variants: Vec<Variant>) -> Self {
+        Self {
+            variants,
+            selected: 0,
+            image: None,
+        }
+    }
+
+    pub fn set_image(&mut self, protocol: StatefulProtocol, dimensions: (u32, u32)) {
+        self.image = Some((protocol, dimensions));
+    }
+
+    pub fn selected_variant(&self) -> Option<&Variant> {
+        self.variants.get(self.selected)
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.variants.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// Runs the page through every variant in parallel and lays the results out in a row
+pub fn render_variants(
+    img: &DynamicImage,
+    config: &ComicConfig,
+    variants: &[Variant],
+) -> DynamicImage {
+    let pages: Vec<GrayImage> = variants
+        .par_iter()
+        .filter_map(|variant| {
+            let mut config = config.clone();
+            variant.apply(&mut config);
+            comically::image::process(img.clone(), &config)
+                .into_iter()
+                .next()
+                .map(DynamicImage::into_luma8)
+        })
+        .collect();
+
+    let cell_width = pages.iter().map(GrayImage::width).max().unwrap_or(1);
+    let cell_height = pages.iter().map(GrayImage::height).max().unwrap_or(1);
+    let count = pages.len().max(1) as u32;
+
+    let mut sheet = GrayImage::from_pixel(
+        count * cell_width + (count - 1) * GAP,
+        cell_height,
+        Luma([128]),
+    );
+    for (i, page) in pages.iter().enumerate() {
+        let x = i as u32 * (cell_width + GAP) + (cell_width - page.width()) / 2;
+        let y = (cell_height - page.height()) / 2;
+        imageops::overlay(&mut sheet, page, x.into(), y.into());
+    }
+
+    DynamicImage::ImageLuma8(sheet)
+}
+
+pub fn render_compare_popup(area: Rect, buf: &mut Buffer, state: &mut ConfigState) {
+    let popup_area = Rect::new(
+        area.left() + area.width / 20,
+        area.top() + area.height / 10,
+        area.width * 9 / 10,
+        area.height * 4 / 5,
+    );
+
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(state.strings.compare, &state.theme);
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let [image_area, label_area, hint_area] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .spacing(1)
+    .areas(inner);
+
+    let keymap = &state.keymap;
+    let hint = format!(
+        "{}: {} | {}: {} | {}: {}",
+        keymap.keys(&[Action::Decrease, Action::Increase]),
+        state.strings.select,
+        keymap.keys(&[Action::Start]),
+        state.strings.apply,
+        keymap.keys(&[Action::Cancel]),
+        state.strings.cancel,
+    );
+    Paragraph::new(hint)
+        .style(Style::default().fg(state.theme.accent))
+        .alignment(Alignment::Center)
+        .render(hint_area, buf);
+
+    let font_size = state.preview_state.picker.font_size();
+    let ModalState::Compare(compare) = &mut state.modal_state else {
+        return;
+    };
+
+    let Some((protocol, dimensions)) = &mut compare.image else {
+        render_image_placeholder(image_area, buf, &state.theme, state.strings.loading);
+        return;
+    };
+
+    let image_area = calculate_centered_image_area(image_area, *dimensions, font_size);
+    StatefulImage::new()
+        .resize(Resize::Scale(Some(FilterType::Triangle)))
+        .render(image_area, buf, protocol);
+
+    // one label under each variant, lined up with the image
+    let label_area = Rect::new(image_area.x, label_area.y, image_area.width, 1);
+    let columns =
+        Layout::horizontal(vec![Constraint::Fill(1); compare.variants.len()]).split(label_area);
+    for (i, (variant, column)) in compare.variants.iter().zip(columns.iter()).enumerate() {
+        let style = if i == compare.selected {
+            Style::default()
+                .fg(state.theme.accent)
+                .add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(state.theme.content)
+        };
+        let contrast = match variant.contrast {
+            0 => String::new(),
+            contrast => format!(" ◐{contrast:+}"),
+        };
+        Paragraph::new(Line::from(format!(
+            "{} γ{:.2} {:+}{contrast}",
+            variant.label, variant.gamma, variant.brightness
+        )))
+        .style(style)
+        .alignment(Alignment::Center)
+        .render(*column, buf);
+    }
+}
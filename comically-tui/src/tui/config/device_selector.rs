@@ -1,14 +1,16 @@
 use ratatui::{
     buffer::Buffer,
-    crossterm::event::{KeyCode, KeyEvent},
     layout::{Constraint, Layout, Rect},
     style::{Modifier, Style},
     widgets::{Clear, List, ListItem, ListState, StatefulWidget, Widget},
 };
 
+use comically::device::{Device, Preset};
+
 use crate::tui::{
     button::{Button, ButtonVariant},
     config::{ConfigState, ModalState},
+    keymap::Action,
     utils::popup_block,
 };
 
@@ -18,8 +20,12 @@ pub struct DeviceSelectorState {
 }
 
 impl DeviceSelectorState {
-    pub fn new(current_preset: comically::device::Preset) -> Self {
-        let selected_index = current_preset as usize;
+    // custom devices start at the top of the list
+    pub fn new(current: &Device) -> Self {
+        let selected_index = match current {
+            Device::Preset(preset) => *preset as usize,
+            Device::Custom { .. } => 0,
+        };
 
         let mut list_state = ListState::default();
         list_state.select(Some(selected_index));
@@ -30,10 +36,10 @@ impl DeviceSelectorState {
         }
     }
 
-    pub fn confirm_selection(&mut self) -> Option<comically::device::Preset> {
+    pub fn confirm_selection(&mut self) -> Option<Device> {
         if let Some(selected) = self.list_state.selected() {
             self.selected_index = selected;
-            (selected as u8).try_into().ok()
+            Preset::try_from(selected as u8).ok().map(Into::into)
         } else {
             None
         }
@@ -41,7 +47,7 @@ impl DeviceSelectorState {
 
     pub fn select_next(&mut self) {
         if let Some(selected) = self.list_state.selected() {
-            if selected < comically::device::Preset::len() - 1 {
+            if selected < Preset::len() - 1 {
                 self.list_state.select(Some(selected + 1));
             }
         }
@@ -55,14 +61,14 @@ impl DeviceSelectorState {
         }
     }
 
-    // returns device preset if it was selected
-    pub fn handle_key(&mut self, key: KeyEvent) -> Option<comically::device::Preset> {
-        match key.code {
-            KeyCode::Enter => return self.confirm_selection(),
-            KeyCode::Up | KeyCode::Char('k') => {
+    // returns the device if one was selected
+    pub fn handle_action(&mut self, action: Action) -> Option<Device> {
+        match action {
+            Action::Start => return self.confirm_selection(),
+            Action::Up => {
                 self.select_previous();
             }
-            KeyCode::Down | KeyCode::Char('j') => {
+            Action::Down => {
                 self.select_next();
             }
             _ => {}
@@ -82,7 +88,7 @@ pub fn render_device_selector_popup(area: Rect, buf: &mut Buffer, state: &mut Co
 
     Clear.render(popup_area, buf);
 
-    let block = popup_block("select device", &state.theme);
+    let block = popup_block(state.strings.select_device, &state.theme);
 
     let inner = block.inner(popup_area);
     block.render(popup_area, buf);
@@ -93,12 +99,10 @@ pub fn render_device_selector_popup(area: Rect, buf: &mut Buffer, state: &mut Co
         .areas(inner);
 
     // Render device list
-    let current_preset = &state.config.device;
-    let items: Vec<ListItem> = comically::device::Preset::iter()
+    let current = &state.config.device;
+    let items: Vec<ListItem> = Preset::iter()
         .map(|preset| {
-            let checkmark = if preset.name() == current_preset.name()
-                && preset.dimensions() == current_preset.dimensions()
-            {
+            let checkmark = if *current == Device::Preset(preset) {
                 " ✓"
             } else {
                 "  "
@@ -128,12 +132,15 @@ pub fn render_device_selector_popup(area: Rect, buf: &mut Buffer, state: &mut Co
             .spacing(2)
             .areas(button_area);
 
-    Button::new("confirm", state.theme)
-        .hint("[enter]")
+    let confirm_hint = state.keymap.hint(Action::Start);
+    let cancel_hint = state.keymap.hint(Action::Cancel);
+
+    Button::new(state.strings.confirm, state.theme)
+        .hint(&confirm_hint)
         .on_click(|| {
             if let ModalState::DeviceSelector(selector_state) = &mut state.modal_state {
-                if let Some(preset) = selector_state.confirm_selection() {
-                    state.config.device = preset.into();
+                if let Some(device) = selector_state.confirm_selection() {
+                    state.config.set_device(device);
                 }
             }
             state.modal_state = ModalState::None;
@@ -141,8 +148,8 @@ pub fn render_device_selector_popup(area: Rect, buf: &mut Buffer, state: &mut Co
         .mouse_event(state.last_mouse_click)
         .render(confirm_area, buf);
 
-    Button::new("cancel", state.theme)
-        .hint("[esc]")
+    Button::new(state.strings.cancel, state.theme)
+        .hint(&cancel_hint)
         .on_click(|| {
             state.modal_state = ModalState::None;
         })
@@ -7,125 +7,74 @@ use ratatui::{
 };
 
 use crate::tui::{
+    i18n::Strings,
+    keymap::{Action, Keymap},
     utils::{popup_block, themed_block},
     Theme,
 };
 
 pub struct Keybinding {
-    pub key: &'static str,
+    pub key: String,
     pub action: &'static str,
     pub docs: &'static str,
 }
 
+// keys shown for each entry of `Strings::help_entries`
+const HELP_ACTIONS: [&[Action]; 34] = [
+    &[Action::Up, Action::Down],
+    &[Action::ToggleFile],
+    &[Action::ToggleAll],
+    &[Action::Start],
+    &[Action::ReadingDirection],
+    &[Action::Split],
+    &[Action::AutoCrop],
+    &[Action::CropFooter],
+    &[Action::FileCrop],
+    &[Action::Series],
+    &[Action::OutputFormat],
+    &[Action::FileFormat],
+    &[Action::ImageFormat],
+    &[Action::Quality],
+    &[Action::Brightness],
+    &[Action::Contrast],
+    &[Action::Gamma],
+    &[Action::Decrease, Action::Increase],
+    &[Action::DeviceSelector],
+    &[Action::MarginColor],
+    &[Action::Nice],
+    &[Action::Preview],
+    &[Action::Compare],
+    &[Action::ExportPreview],
+    &[Action::Analysis],
+    &[Action::AutoTune],
+    &[Action::FetchMetadata],
+    &[Action::FirstPage, Action::LastPage],
+    &[Action::History, Action::RetryFailed],
+    &[Action::Help],
+    &[Action::Theme],
+    &[Action::ThemePicker],
+    &[Action::Quit],
+    &[Action::Cancel],
+];
+
 pub struct HelpState {
     pub keybindings: Vec<Keybinding>,
     pub list_state: ListState,
+    close_hint: String,
+    strings: &'static Strings,
 }
 
 impl HelpState {
-    pub fn new() -> Self {
-        let keybindings = vec![
-            Keybinding {
-                key: "↑/↓/j/k",
-                action: "navigate files",
-                docs: "move up and down through the file list in the left pane. arrow keys or vim-style navigation both work",
-            },
-            Keybinding {
-                key: "space",
-                action: "toggle file selection",
-                docs: "select or deselect the current focused file in the left pane. selected files show [✓] and will be processed when you press enter",
-            },
-            Keybinding {
-                key: "a",
-                action: "toggle all files",
-                docs: "select or deselect all files at once. if all files are currently selected, this deselects all. otherwise selects all",
-            },
-            Keybinding {
-                key: "enter",
-                action: "start processing",
-                docs: "begin converting all selected files with current settings. files are saved to the output directory with the chosen format",
-            },
-            Keybinding {
-                key: "m",
-                action: "reading direction",
-                docs: "toggle between reading modes:\n\n• left to right: standard western comics\n• right to left: manga style\n\naffects page order in output files",
-            },
-            Keybinding {
-                key: "s",
-                action: "spread splitter",
-                docs: "cycle through double-page handling:\n\n• none: keep spreads as-is\n• split: cut spreads into separate pages\n• rotate: rotate spreads 90° for vertical viewing\n• rotate & split: show twice - rotated then split",
-            },
-            Keybinding {
-                key: "c",
-                action: "auto crop",
-                docs: "toggle automatic margin removal. when enabled, detects and removes blank space around page content for better screen fit",
-            },
-            Keybinding {
-                key: "f",
-                action: "output format",
-                docs: "cycle through output formats:\n\n• azw3/mobi: amazon kindle format\n• epub: standard e-book format\n• cbz: comic book archive (zip)\n\nnote: mobi forces jpeg image format",
-            },
-            Keybinding {
-                key: "i",
-                action: "image format",
-                docs: "cycle compression formats:\n\n• jpeg: lossy, smaller files\n• png: lossless, larger files\n• webp: modern, good compression\n\ndisabled for mobi output",
-            },
-            Keybinding {
-                key: "u",
-                action: "quality/compression",
-                docs: "select quality setting for adjustment\n\n• jpeg/webp: quality 0-100\n• png: fast/default/best compression\n\nuse ←/→ arrows to adjust value",
-            },
-            Keybinding {
-                key: "b",
-                action: "brightness",
-                docs: "select brightness for adjustment\n\nrange: -100 to +100\n• negative values: darker image\n• positive values: brighter image\n\nuse ←/→ arrows to adjust",
-            },
-            Keybinding {
-                key: "g",
-                action: "gamma",
-                docs: "select gamma correction for adjustment\n\nrange: 0.1 to 3.0\n• < 1.0: lower contrast, lifted shadows\n• > 1.0: higher contrast, deeper blacks\n• = 1.0: no adjustment\n\nuse ←/→ arrows to adjust",
-            },
-            Keybinding {
-                key: "←/→",
-                action: "adjust values",
-                docs: "decrease/increase selected setting (quality, brightness, or gamma)\n\nhold shift for fine adjustments:\n• quality: ±1 instead of ±5\n• brightness: ±1 instead of ±5\n• gamma: ±0.05 instead of ±0.1",
-            },
-            Keybinding {
-                key: "d",
-                action: "device presets",
-                docs: "open device selector to choose from common e-reader presets. automatically sets optimal dimensions for your target device",
-            },
-            Keybinding {
-                key: "o",
-                action: "margin color",
-                docs: "cycle margin fill when image doesn't fill screen:\n\n• none: preserve original aspect ratio\n• black: fill empty space with black\n• white: fill empty space with white",
-            },
-            Keybinding {
-                key: "p",
-                action: "load preview",
-                docs: "load preview of selected file with current settings applied. updates when settings change. useful for testing before batch processing",
-            },
-            Keybinding {
-                key: "h",
-                action: "toggle help",
-                docs: "show or hide this help menu. press h or esc to close",
-            },
-            Keybinding {
-                key: "t",
-                action: "toggle theme",
-                docs: "switch between light and dark color themes",
-            },
-            Keybinding {
-                key: "q",
-                action: "quit",
-                docs: "exit the application. any unsaved settings will be lost",
-            },
-            Keybinding {
-                key: "esc",
-                action: "cancel/close",
-                docs: "context sensitive:\n• close modal dialogs\n• deselect adjustment fields\n• cancel current operation",
-            },
-        ];
+    pub fn new(keymap: &Keymap, strings: &'static Strings) -> Self {
+        let keybindings = HELP_ACTIONS
+            .into_iter()
+            .zip(strings.help_entries)
+            .map(|(actions, (action, docs))| Keybinding {
+                key: keymap.keys(actions),
+                action,
+                docs,
+            })
+            .collect();
 
         let mut list_state = ListState::default();
         list_state.select(Some(0));
@@ -133,6 +82,12 @@ impl HelpState {
         Self {
             keybindings,
             list_state,
+            close_hint: format!(
+                "[{} {}]",
+                keymap.keys(&[Action::Cancel, Action::Help]),
+                strings.to_close
+            ),
+            strings,
         }
     }
 
@@ -176,7 +131,8 @@ pub fn render_help_popup(area: Rect, buf: &mut Buffer, theme: &Theme, help_state
 
     Clear.render(popup_area, buf);
 
-    let block = popup_block("help", theme).title(Line::from("[esc/h to close]").right_aligned());
+    let block = popup_block(help_state.strings.help_title, theme)
+        .title(Line::from(help_state.close_hint.as_str()).right_aligned());
     let inner = block.inner(popup_area);
     block.render(popup_area, buf);
 
@@ -195,7 +151,7 @@ pub fn render_help_popup(area: Rect, buf: &mut Buffer, theme: &Theme, help_state
         .collect();
 
     let list = List::new(items)
-        .block(themed_block(Some("keybindings"), theme))
+        .block(themed_block(Some(help_state.strings.keybindings), theme))
         .highlight_style(
             Style::default()
                 .fg(theme.accent)
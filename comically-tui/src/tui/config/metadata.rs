@@ -0,0 +1,181 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget, Wrap},
+};
+
+use comically::metadata::fetch::Candidate;
+
+use crate::tui::{
+    config::{ConfigState, ModalState},
+    keymap::Action,
+    utils::{center, popup_block},
+};
+
+/// Series found online for the focused file, waiting to be confirmed
+pub struct MetadataState {
+    /// Search terms taken from the file name, shared by the volumes of the series
+    pub query: String,
+    pub lookup: Lookup,
+}
+
+pub enum Lookup {
+    Searching,
+    /// The candidates followed by a "none of these" entry
+    Found {
+        candidates: Vec<Candidate>,
+        list_state: ListState,
+    },
+    Failed(String),
+}
+
+impl MetadataState {
+    pub fn new(query: String) -> Self {
+        Self {
+            query,
+            lookup: Lookup::Searching,
+        }
+    }
+
+    pub fn found(&mut self, result: Result<Vec<Candidate>, String>) {
+        self.lookup = match result {
+            Ok(candidates) => Lookup::Found {
+                candidates,
+                list_state: ListState::default().with_selected(Some(0)),
+            },
+            Err(e) => Lookup::Failed(e),
+        };
+    }
+
+    pub fn select_previous(&mut self) {
+        if let Lookup::Found { list_state, .. } = &mut self.lookup {
+            list_state.select_previous();
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if let Lookup::Found {
+            candidates,
+            list_state,
+        } = &mut self.lookup
+        {
+            let last = candidates.len();
+            list_state.select(Some(list_state.selected().map_or(0, |i| (i + 1).min(last))));
+        }
+    }
+
+    /// Whether the search finished, with or without matches
+    pub fn is_found(&self) -> bool {
+        matches!(self.lookup, Lookup::Found { .. })
+    }
+
+    /// The confirmed series, `None` for "none of these"
+    pub fn selected(&self) -> Option<&Candidate> {
+        match &self.lookup {
+            Lookup::Found {
+                candidates,
+                list_state,
+            } => candidates.get(list_state.selected()?),
+            _ => None,
+        }
+    }
+}
+
+pub fn render_metadata_popup(area: Rect, buf: &mut Buffer, state: &mut ConfigState) {
+    let ModalState::Metadata(metadata) = &mut state.modal_state else {
+        return;
+    };
+    let strings = state.strings;
+    let theme = &state.theme;
+
+    let popup_area = center(area, Constraint::Length(70), Constraint::Length(20));
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(strings.series_lookup, theme);
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let [query_area, list_area, details_area, hint_area] = Layout::vertical([
+        Constraint::Length(2),
+        Constraint::Length(7),
+        Constraint::Fill(1),
+        Constraint::Length(1),
+    ])
+    .areas(inner);
+
+    Paragraph::new(format!("\"{}\"", metadata.query))
+        .style(Style::default().fg(theme.content))
+        .alignment(Alignment::Center)
+        .render(query_area, buf);
+
+    let details = match &mut metadata.lookup {
+        Lookup::Searching => Some(strings.searching.to_string()),
+        Lookup::Failed(e) => Some(e.clone()),
+        Lookup::Found { candidates, .. } if candidates.is_empty() => {
+            Some(strings.no_matches.to_string())
+        }
+        Lookup::Found {
+            candidates,
+            list_state,
+        } => {
+            let items: Vec<ListItem> = candidates
+                .iter()
+                .map(|candidate| ListItem::new(candidate.label()))
+                .chain([ListItem::new(strings.skip_lookup)])
+                .collect();
+            let list = List::new(items)
+                .style(Style::default().fg(theme.content))
+                .highlight_style(
+                    Style::default()
+                        .fg(theme.accent)
+                        .add_modifier(Modifier::REVERSED),
+                );
+            StatefulWidget::render(list, list_area, buf, list_state);
+            None
+        }
+    };
+
+    match details {
+        Some(message) => Paragraph::new(message)
+            .style(Style::default().fg(theme.content))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .render(list_area, buf),
+        None => {
+            if let Some(candidate) = metadata.selected() {
+                let mut lines = Vec::new();
+                if !candidate.metadata.authors.is_empty() {
+                    lines.push(Line::from(format!(
+                        "{} {}",
+                        strings.by,
+                        candidate.metadata.authors.join(", ")
+                    )));
+                }
+                if let Some(description) = &candidate.metadata.description {
+                    lines.push(Line::from(description.as_str()));
+                }
+                Paragraph::new(lines)
+                    .style(Style::default().fg(theme.content))
+                    .wrap(Wrap { trim: true })
+                    .render(details_area, buf);
+            }
+        }
+    }
+
+    let keymap = &state.keymap;
+    let hint = format!(
+        "{}: {} | {}: {} | {}: {}",
+        keymap.keys(&[Action::Up, Action::Down]),
+        strings.select,
+        keymap.keys(&[Action::Start]),
+        strings.apply,
+        keymap.keys(&[Action::Cancel]),
+        strings.cancel,
+    );
+    Paragraph::new(hint)
+        .style(Style::default().fg(theme.accent))
+        .alignment(Alignment::Center)
+        .render(hint_area, buf);
+}
@@ -1,10 +1,15 @@
+pub mod analysis;
+pub mod compare;
 pub mod device_selector;
 pub mod help;
+pub mod metadata;
+pub mod resume;
+pub mod series;
 
 use imageproc::image::DynamicImage;
 use ratatui::{
     buffer::Buffer,
-    crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind},
+    crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind},
     layout::{Alignment, Constraint, Direction, Flex, Layout, Position, Rect},
     style::{Modifier, Style, Stylize},
     text::Line,
@@ -16,16 +21,35 @@ use ratatui_image::{
     FilterType, Resize, ResizeEncodeRender, StatefulImage,
 };
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::mpsc;
 use std::thread;
 
-use comically::{ComicConfig, ComicFile, ImageFormat, OutputFormat, PngCompression, SplitStrategy};
+use comically::{
+    archive::ArchiveFile,
+    checkpoint::{Checkpoint, Resume},
+    dir_config::DirConfig,
+    image::PageStats,
+    metadata::fetch::{self, Candidate, SourceConfig},
+    tune::{self, Tone},
+    ComicConfig, ComicFile, FixedCrop, GammaCurve, ImageFormat, MarginColor, OutputFormat,
+    PageRange, PageRanges, PngCompression, ProcessedImage, SplitStrategy,
+};
 
+use crate::pipeline::{FileChoices, FileOverrides};
+use crate::settings::Settings;
 use crate::tui::{
     button::{Button, ButtonVariant},
+    config::compare::{CompareState, Variant},
     config::device_selector::DeviceSelectorState,
     config::help::{render_help_popup, HelpState},
+    config::metadata::MetadataState,
+    config::resume::ResumeState,
+    config::series::{SeriesInput, SeriesState},
+    i18n::Strings,
+    keymap::{Action, Keymap},
+    slider::Slider,
     utils::{padding, themed_block, Side},
     Theme,
 };
@@ -40,21 +64,42 @@ pub struct ConfigState {
     pub theme: Theme,
     pub event_tx: std::sync::mpsc::Sender<crate::Event>,
     pub last_mouse_click: Option<MouseEvent>,
+    // slider being dragged, it keeps following the mouse until the button is released
+    pub dragging: Option<SelectedField>,
     pub output_dir: PathBuf,
+    // series confirmed in the lookup popup, by search terms, see `pipeline::SeriesChoices`
+    pub series_choices: HashMap<String, Candidate>,
+    // pages marked in the preview, by file, see `pipeline::PageChoices`
+    pub page_choices: HashMap<PathBuf, PageRanges>,
+    // settings picked for single files, see `pipeline::FileChoices`
+    pub file_choices: FileChoices,
+    // the configs of the files' directories, see `comically::dir_config`
+    dir_configs: HashMap<PathBuf, Option<DirConfig>>,
+    // whether the batch is converted in the background, see `Settings::nice`
+    pub nice: bool,
+    // the file tones are being suggested for, see `comically::tune`
+    pub tuning: Option<ComicFile>,
 
     pub modal_state: ModalState,
+    pub keymap: Keymap,
+    pub strings: &'static Strings,
 }
 
 pub enum ModalState {
     None,
     Help(HelpState),
     DeviceSelector(DeviceSelectorState),
+    Compare(CompareState),
+    Resume(ResumeState),
+    Metadata(MetadataState),
+    Series(SeriesState),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SelectedField {
     Quality,
     Brightness,
+    Contrast,
     Gamma,
 }
 
@@ -70,6 +115,8 @@ pub struct PreviewState {
     preview_tx: mpsc::Sender<PreviewRequest>,
     resize_tx: mpsc::Sender<ResizeRequest>,
     loaded_image: Option<LoadedPreviewImage>,
+    // the page as it is in the archive, with what auto-crop removes, see `analysis`
+    show_analysis: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -78,9 +125,24 @@ pub struct LoadedPreviewImage {
     page_idx: usize,
     total_pages: usize,
     archive_path: ComicFile,
-    width: u32,
-    height: u32,
+    // the page as converted, and as it is in the archive with the crop overlay
+    image: DynamicImage,
+    source: DynamicImage,
+    stats: PageStats,
     config: ComicConfig,
+    // encoded as in the output, for exporting it
+    page: ProcessedImage,
+    // name the page was last exported as
+    exported: Option<String>,
+}
+
+impl LoadedPreviewImage {
+    fn shown(&self, analysis: bool) -> &DynamicImage {
+        match analysis {
+            true => &self.source,
+            false => &self.image,
+        }
+    }
 }
 
 enum PreviewRequest {
@@ -90,6 +152,12 @@ enum PreviewRequest {
         page_idx: Option<usize>,
         file_idx: usize,
     },
+    Compare {
+        archive_path: ComicFile,
+        config: ComicConfig,
+        page_idx: usize,
+        variants: Vec<Variant>,
+    },
 }
 
 pub enum ConfigEvent {
@@ -99,9 +167,21 @@ pub enum ConfigEvent {
         total_pages: usize,
         archive_path: ComicFile,
         image: DynamicImage,
-        config: ComicConfig,
+        source: DynamicImage,
+        stats: Box<PageStats>,
+        page: Box<ProcessedImage>,
+        config: Box<ComicConfig>,
     },
     ResizeComplete(ResizeResponse),
+    VariantsLoaded(DynamicImage),
+    MetadataFound {
+        query: String,
+        result: Result<Vec<Candidate>, String>,
+    },
+    Tuned {
+        file: ComicFile,
+        result: Result<Tone, String>,
+    },
     Error(String),
 }
 
@@ -112,6 +192,7 @@ impl ConfigState {
         files: Vec<ComicFile>,
         theme: Theme,
         output_dir: PathBuf,
+        settings: &Settings,
     ) -> Self {
         let files: Vec<(ComicFile, bool)> = files.into_iter().map(|f| (f, true)).collect();
 
@@ -130,12 +211,21 @@ impl ConfigState {
             preview_worker(worker_rx, resize_rx, event_tx_clone);
         });
 
-        let config = ComicConfig::load().unwrap_or_default();
+        let mut dir_configs = HashMap::new();
+        for (file, _) in &files {
+            let Some(dir) = file.as_path().parent() else {
+                continue;
+            };
+            if !dir_configs.contains_key(dir) {
+                dir_configs.insert(dir.to_path_buf(), DirConfig::for_comic(file.as_path()));
+            }
+        }
 
         let mut state = Self {
             files,
             file_list_state: list_state,
-            config,
+            strings: settings.language().strings(),
+            config: settings.comic.clone(),
             selected_field: None,
             preview_state: PreviewState {
                 picker,
@@ -143,12 +233,21 @@ impl ConfigState {
                 preview_tx,
                 resize_tx,
                 loaded_image: None,
+                show_analysis: false,
             },
             theme,
             event_tx,
             last_mouse_click: None,
+            dragging: None,
             output_dir,
+            series_choices: HashMap::new(),
+            page_choices: HashMap::new(),
+            file_choices: FileChoices::new(),
+            dir_configs,
+            nice: settings.nice,
+            tuning: None,
             modal_state: ModalState::None,
+            keymap: settings.keymap.clone(),
         };
 
         // Auto-load the first image
@@ -161,62 +260,133 @@ impl ConfigState {
         !matches!(self.modal_state, ModalState::None)
     }
 
+    pub fn is_typing(&self) -> bool {
+        matches!(self.modal_state, ModalState::Series(_))
+    }
+
     pub fn handle_key(&mut self, key: KeyEvent) {
-        if key.code == KeyCode::Esc {
+        if self.keymap.matches(Action::Cancel, &key) {
             self.modal_state = ModalState::None;
             self.selected_field = None;
             return;
         }
 
+        // typed into the fields, the keymap doesn't apply
+        if let ModalState::Series(series) = &mut self.modal_state {
+            if let SeriesInput::Confirmed(picked) = series.handle_key(&key) {
+                self.modal_state = ModalState::None;
+                self.update_file_choice(|choice, _| choice.series = picked);
+            }
+            return;
+        }
+
+        let Some(action) = self.keymap.action(&key) else {
+            return;
+        };
+
         match &mut self.modal_state {
             ModalState::DeviceSelector(selector) => {
-                if key.code == KeyCode::Char('d') {
+                if action == Action::DeviceSelector {
                     self.modal_state = ModalState::None;
                     return;
                 }
 
-                if let Some(preset) = selector.handle_key(key) {
+                if let Some(device) = selector.handle_action(action) {
                     self.modal_state = ModalState::None;
-                    self.config.device = preset.into();
+                    self.config.set_device(device);
                     return;
                 }
             }
-            ModalState::Help(help_state) => match key.code {
-                KeyCode::Char('h') => {
+            ModalState::Help(help_state) => match action {
+                Action::Help => {
                     self.modal_state = ModalState::None;
                     return;
                 }
-                KeyCode::Up | KeyCode::Char('k') => {
+                Action::Up => {
                     help_state.select_previous();
                 }
-                KeyCode::Down | KeyCode::Char('j') => {
+                Action::Down => {
                     help_state.select_next();
                 }
                 _ => {}
             },
+            ModalState::Compare(compare) => {
+                match action {
+                    Action::Decrease | Action::Up => compare.select_previous(),
+                    Action::Increase | Action::Down => compare.select_next(),
+                    Action::Start => {
+                        if let Some(variant) = compare.selected_variant() {
+                            variant.apply(&mut self.config);
+                        }
+                        self.modal_state = ModalState::None;
+                        self.load_preview();
+                    }
+                    Action::Compare => self.modal_state = ModalState::None,
+                    _ => {}
+                }
+                return;
+            }
+            ModalState::Resume(resume) => {
+                match action {
+                    Action::Decrease | Action::Increase => resume.toggle(),
+                    Action::Start => {
+                        if let ModalState::Resume(resume) =
+                            std::mem::replace(&mut self.modal_state, ModalState::None)
+                        {
+                            let (files, resume) = resume.into_batch();
+                            self.start_processing(files, resume);
+                        }
+                    }
+                    _ => {}
+                }
+                return;
+            }
+            ModalState::Metadata(metadata) => {
+                match action {
+                    Action::Up => metadata.select_previous(),
+                    Action::Down => metadata.select_next(),
+                    Action::Start if metadata.is_found() => {
+                        match metadata.selected() {
+                            Some(candidate) => {
+                                self.series_choices
+                                    .insert(metadata.query.clone(), candidate.clone());
+                            }
+                            None => {
+                                self.series_choices.remove(&metadata.query);
+                            }
+                        }
+                        self.modal_state = ModalState::None;
+                    }
+                    Action::FetchMetadata => self.modal_state = ModalState::None,
+                    _ => {}
+                }
+                return;
+            }
+            // typed keys never get here
+            ModalState::Series(_) => return,
             ModalState::None => {}
         }
 
-        match key.code {
-            KeyCode::Char('h') => {
-                self.modal_state = ModalState::Help(HelpState::new());
+        match action {
+            Action::Help => {
+                self.modal_state = ModalState::Help(HelpState::new(&self.keymap, self.strings));
             }
-            KeyCode::Enter => {
+            Action::Start => {
                 self.send_start_processing();
             }
             // File list navigation
-            KeyCode::Up | KeyCode::Char('k') => {
+            Action::Up => {
                 self.select_previous();
             }
-            KeyCode::Down | KeyCode::Char('j') => {
+            Action::Down => {
                 self.select_next();
             }
-            KeyCode::Char(' ') => {
+            Action::ToggleFile => {
                 if let Some(selected) = self.file_list_state.selected() {
                     self.files[selected].1 = !self.files[selected].1;
                 }
             }
-            KeyCode::Char('a') => {
+            Action::ToggleAll => {
                 let all_selected = self.files.iter().all(|(_, selected)| *selected);
                 for (_, selected) in &mut self.files {
                     *selected = !all_selected;
@@ -224,10 +394,8 @@ impl ConfigState {
             }
 
             // Settings toggles
-            KeyCode::Char('m') => {
-                self.config.right_to_left = !self.config.right_to_left;
-            }
-            KeyCode::Char('s') => {
+            Action::ReadingDirection => cycle_reading_direction(&mut self.config),
+            Action::Split => {
                 use comically::comic::SplitStrategy;
                 self.config.split = match self.config.split {
                     SplitStrategy::None => SplitStrategy::Split,
@@ -236,10 +404,13 @@ impl ConfigState {
                     SplitStrategy::RotateAndSplit => SplitStrategy::None,
                 };
             }
-            KeyCode::Char('c') => {
+            Action::AutoCrop => {
                 self.config.auto_crop = !self.config.auto_crop;
             }
-            KeyCode::Char('f') => {
+            Action::CropFooter => {
+                self.config.fixed_crop = FixedCrop::next_preset(self.config.fixed_crop);
+            }
+            Action::OutputFormat => {
                 self.config.output_format = match self.config.output_format {
                     OutputFormat::Mobi => OutputFormat::Epub,
                     OutputFormat::Epub => OutputFormat::Cbz,
@@ -255,53 +426,59 @@ impl ConfigState {
                     self.config.image_format = ImageFormat::Jpeg { quality };
                 }
             }
-            KeyCode::Char('u') => {
+            Action::Quality => {
                 self.selected_field = Some(SelectedField::Quality);
             }
-            KeyCode::Char('b') => {
+            Action::Brightness => {
                 self.selected_field = Some(SelectedField::Brightness);
             }
-            KeyCode::Char('g') => {
-                self.selected_field = Some(SelectedField::Gamma);
+            Action::Contrast => {
+                self.selected_field = Some(SelectedField::Contrast);
             }
-            KeyCode::Char('d') => {
-                self.modal_state = ModalState::DeviceSelector(DeviceSelectorState::new(
-                    self.config
-                        .device
-                        .clone()
-                        .try_as_preset()
-                        .unwrap_or(comically::device::Preset::KindlePw11),
-                ));
+            Action::Gamma => {
+                // pressed again, switches between the curves
+                if self.selected_field == Some(SelectedField::Gamma) {
+                    self.config.gamma_curve = self.config.gamma_curve.cycle();
+                }
+                self.selected_field = Some(SelectedField::Gamma);
             }
-            KeyCode::Char('o') => {
-                self.config.margin_color = match self.config.margin_color {
-                    None => Some(0),
-                    Some(0) => Some(255),
-                    Some(_) => None,
-                };
+            Action::DeviceSelector => {
+                self.modal_state =
+                    ModalState::DeviceSelector(DeviceSelectorState::new(&self.config.device));
             }
-            KeyCode::Char('i') => {
-                if self.config.output_format != OutputFormat::Mobi {
-                    self.config.image_format = self.config.image_format.cycle();
-                }
+            Action::MarginColor => cycle_margin_color(&mut self.config),
+            Action::Nice => self.nice = !self.nice,
+            Action::ImageFormat if self.config.output_format != OutputFormat::Mobi => {
+                self.config.image_format = self.config.image_format.cycle();
             }
-            KeyCode::Char('p') => {
+            Action::Preview => {
                 self.load_preview();
             }
-            KeyCode::Left => {
-                if let Some(field) = self.selected_field {
-                    let is_fine = key
-                        .modifiers
-                        .contains(ratatui::crossterm::event::KeyModifiers::SHIFT);
-                    self.adjust_setting(field, false, is_fine);
-                }
+            Action::Compare => {
+                self.open_compare();
             }
-            KeyCode::Right => {
+            Action::ExportPreview => self.export_preview(),
+            Action::Analysis => {
+                self.preview_state.show_analysis = !self.preview_state.show_analysis;
+                self.show_loaded_image();
+            }
+            Action::FileFormat => self.cycle_file_format(),
+            Action::FileCrop => self.cycle_file_crop(),
+            Action::Series => self.open_series(),
+            Action::AutoTune => {
+                self.auto_tune();
+            }
+            Action::FetchMetadata => {
+                self.fetch_metadata();
+            }
+            Action::FirstPage => self.mark_page(true),
+            Action::LastPage => self.mark_page(false),
+            Action::Decrease | Action::Increase => {
                 if let Some(field) = self.selected_field {
                     let is_fine = key
                         .modifiers
                         .contains(ratatui::crossterm::event::KeyModifiers::SHIFT);
-                    self.adjust_setting(field, true, is_fine);
+                    self.adjust_setting(field, action == Action::Increase, is_fine);
                 }
             }
 
@@ -309,7 +486,7 @@ impl ConfigState {
         }
     }
 
-    fn send_start_processing(&self) {
+    fn send_start_processing(&mut self) {
         let selected_paths: Vec<ComicFile> = self
             .files
             .iter()
@@ -318,27 +495,56 @@ impl ConfigState {
             .cloned()
             .collect();
 
-        if !selected_paths.is_empty() {
+        // offer to skip what an interrupted batch with the same settings converted
+        let resume = Checkpoint::find(&self.output_dir, std::slice::from_ref(&self.config))
+            .map(|resume| ResumeState::new(resume, selected_paths.clone()))
+            .filter(|resume| resume.completed() > 0);
+        match resume {
+            Some(resume) => self.modal_state = ModalState::Resume(resume),
+            None => self.start_processing(selected_paths, None),
+        }
+    }
+
+    fn start_processing(&self, files: Vec<ComicFile>, resume: Option<Resume>) {
+        if !files.is_empty() {
             let _ = self.event_tx.send(crate::Event::StartProcessing {
-                files: selected_paths,
-                config: self.config.clone(),
+                files,
+                config: Box::new(self.config.clone()),
                 output_dir: self.output_dir.clone(),
+                resume,
+                series: self.series_choices.clone(),
+                pages: self.page_choices.clone(),
+                overrides: self.file_choices.clone(),
+                nice: self.nice,
+                save_settings: true,
             });
         }
     }
 
     pub fn handle_mouse(&mut self, mouse: ratatui::crossterm::event::MouseEvent) {
         match mouse.kind {
-            MouseEventKind::Up(MouseButton::Left) | MouseEventKind::Down(MouseButton::Left) => {
+            MouseEventKind::Up(MouseButton::Left)
+            | MouseEventKind::Down(MouseButton::Left)
+            | MouseEventKind::Drag(MouseButton::Left) => {
                 self.last_mouse_click = Some(mouse);
             }
             MouseEventKind::ScrollUp => match &mut self.modal_state {
                 ModalState::DeviceSelector(s) => {
                     s.select_previous();
                 }
+                ModalState::Compare(compare) => {
+                    compare.select_previous();
+                }
+                ModalState::Resume(resume) => {
+                    resume.toggle();
+                }
+                ModalState::Metadata(metadata) => {
+                    metadata.select_previous();
+                }
                 ModalState::Help(help_state) => {
                     help_state.select_previous();
                 }
+                ModalState::Series(_) => {}
                 ModalState::None => {
                     self.select_previous();
                 }
@@ -347,9 +553,19 @@ impl ConfigState {
                 ModalState::DeviceSelector(s) => {
                     s.select_next();
                 }
+                ModalState::Compare(compare) => {
+                    compare.select_next();
+                }
+                ModalState::Resume(resume) => {
+                    resume.toggle();
+                }
+                ModalState::Metadata(metadata) => {
+                    metadata.select_next();
+                }
                 ModalState::Help(help_state) => {
                     help_state.select_next();
                 }
+                ModalState::Series(_) => {}
                 ModalState::None => {
                     self.select_next();
                 }
@@ -377,7 +593,7 @@ impl ConfigState {
                     .preview_tx
                     .send(PreviewRequest::LoadFile {
                         archive_path: file.clone(),
-                        config: self.config.clone(),
+                        config: self.config_for(file),
                         page_idx: Some(idx),
                         file_idx,
                     });
@@ -385,6 +601,235 @@ impl ConfigState {
         }
     }
 
+    // the settings `file` is converted with: the batch's, its directory's and its own
+    fn config_for(&self, file: &ComicFile) -> ComicConfig {
+        let mut config = self.config.clone();
+        let local = file
+            .as_path()
+            .parent()
+            .and_then(|dir| self.dir_configs.get(dir))
+            .and_then(Option::as_ref);
+        if let Some(local) = local {
+            local.apply(&mut config);
+        }
+        if let Some(overrides) = self.file_choices.get(file.as_path()) {
+            overrides.apply(&mut config);
+        }
+        config
+    }
+
+    // changes the settings picked for the focused file, forgetting them once they're all
+    // the batch's again
+    fn update_file_choice(&mut self, update: impl FnOnce(&mut FileOverrides, &ComicConfig)) {
+        let Some((file, _)) = self
+            .file_list_state
+            .selected()
+            .and_then(|idx| self.files.get(idx))
+        else {
+            return;
+        };
+        let path = file.as_path().to_path_buf();
+        let choice = self.file_choices.entry(path.clone()).or_default();
+        update(choice, &self.config);
+        if choice.is_empty() {
+            self.file_choices.remove(&path);
+        }
+    }
+
+    // cycles the output format of the focused file through the others and back to the
+    // batch's
+    fn cycle_file_format(&mut self) {
+        self.update_file_choice(|choice, config| {
+            let current = choice.output_format.unwrap_or(config.output_format);
+            let format = match current {
+                OutputFormat::Mobi => OutputFormat::Epub,
+                OutputFormat::Epub => OutputFormat::Cbz,
+                OutputFormat::Cbz => OutputFormat::Mobi,
+            };
+            choice.output_format = (format != config.output_format).then_some(format);
+        });
+    }
+
+    // cycles the crop of the focused file through none and the presets, and back to its
+    // directory's or the batch's
+    fn cycle_file_crop(&mut self) {
+        self.update_file_choice(|choice, _| {
+            choice.fixed_crop = match choice.fixed_crop {
+                None => Some(FixedCrop::default()),
+                Some(crop) if crop.is_empty() => FixedCrop::next_preset(None),
+                Some(crop) => FixedCrop::next_preset(Some(crop)),
+            };
+        });
+    }
+
+    // series typed in for the focused file, in place of its ComicInfo.xml's or name's
+    fn open_series(&mut self) {
+        let Some((file, _)) = self
+            .file_list_state
+            .selected()
+            .and_then(|idx| self.files.get(idx))
+        else {
+            return;
+        };
+        let picked = self
+            .file_choices
+            .get(file.as_path())
+            .and_then(|choice| choice.series.as_ref());
+        self.modal_state = ModalState::Series(SeriesState::new(file.clone(), picked));
+    }
+
+    // shows the loaded page, converted or with its analysis
+    fn show_loaded_image(&mut self) {
+        let Some(loaded) = &self.preview_state.loaded_image else {
+            return;
+        };
+        let image = loaded.shown(self.preview_state.show_analysis).clone();
+        let protocol = self.preview_state.picker.new_resize_protocol(image);
+        let thread_protocol =
+            ThreadProtocol::new(self.preview_state.resize_tx.clone(), Some(protocol));
+        self.preview_state.protocol_state = PreviewProtocolState::PendingResize { thread_protocol };
+    }
+
+    // writes the previewed page as it's encoded in the output to `previews` in the output
+    // directory, for looking at it full size or attaching it to a bug report
+    fn export_preview(&mut self) {
+        let Some(loaded) = &mut self.preview_state.loaded_image else {
+            return;
+        };
+        let name = format!(
+            "{} p{:03}.{}",
+            loaded.archive_path.title(),
+            loaded.page_idx + 1,
+            loaded.page.format.extension()
+        );
+        let dir = self.output_dir.join("previews");
+        let written = std::fs::create_dir_all(&dir)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| comically::output::write_atomic(&dir.join(&name), &loaded.page.data));
+        match written {
+            Ok(()) => loaded.exported = Some(name),
+            Err(e) => log::warn!("Failed to export {name}: {e:#}"),
+        }
+    }
+
+    // render the previewed page (or the first one) under a few tone variants
+    fn open_compare(&mut self) {
+        let Some(file_idx) = self.file_list_state.selected() else {
+            return;
+        };
+        let Some((file, _)) = self.files.get(file_idx) else {
+            return;
+        };
+
+        let page_idx = self
+            .preview_state
+            .loaded_image
+            .as_ref()
+            .filter(|i| i.file_idx == file_idx)
+            .map_or(0, |i| i.page_idx);
+
+        let variants = Variant::around(&self.config, self.strings);
+        let _ = self.preview_state.preview_tx.send(PreviewRequest::Compare {
+            archive_path: file.clone(),
+            config: self.config_for(file),
+            page_idx,
+            variants: variants.clone(),
+        });
+        self.modal_state = ModalState::Compare(CompareState::new(variants));
+    }
+
+    // suggests tones for the selected file in the background, the settings are filled in
+    // once it's done
+    fn auto_tune(&mut self) {
+        let Some((file, _)) = self
+            .file_list_state
+            .selected()
+            .and_then(|idx| self.files.get(idx))
+        else {
+            return;
+        };
+        if self.tuning.is_some() {
+            return;
+        }
+
+        let event_tx = self.event_tx.clone();
+        let config = self.config.clone();
+        let comic = file.clone();
+        thread::spawn(move || {
+            let result =
+                tune::suggest(std::slice::from_ref(&comic), &config).map_err(|e| format!("{e:#}"));
+            let _ = event_tx.send(crate::Event::Config(ConfigEvent::Tuned {
+                file: comic,
+                result,
+            }));
+        });
+        self.tuning = Some(file.clone());
+    }
+
+    // searches the series of the selected file in the background, the popup waits for it
+    fn fetch_metadata(&mut self) {
+        let Some((file, _)) = self
+            .file_list_state
+            .selected()
+            .and_then(|idx| self.files.get(idx))
+        else {
+            return;
+        };
+
+        let query = comically::filename::parse(file.title()).series;
+        let event_tx = self.event_tx.clone();
+        let search = query.clone();
+        thread::spawn(move || {
+            let result = SourceConfig::load(None)
+                .and_then(|sources| fetch::search(sources.default, &sources, &search))
+                .map_err(|e| format!("{e:#}"));
+            let _ = event_tx.send(crate::Event::Config(ConfigEvent::MetadataFound {
+                query: search,
+                result,
+            }));
+        });
+        self.modal_state = ModalState::Metadata(MetadataState::new(query));
+    }
+
+    // converts the previewed file from or up to the previewed page, marking the same page
+    // again converts from the start or to the end again
+    fn mark_page(&mut self, first: bool) {
+        let Some(loaded) = &self.preview_state.loaded_image else {
+            return;
+        };
+        let path = loaded.archive_path.as_path().to_path_buf();
+        let page = loaded.page_idx + 1;
+
+        let whole = PageRange {
+            first: 1,
+            last: None,
+        };
+        let mut range = self
+            .page_choices
+            .get(&path)
+            .and_then(|ranges| ranges.ranges().first().copied())
+            .unwrap_or(whole);
+        if first {
+            range.first = if range.first == page { 1 } else { page };
+            range.last = range.last.filter(|&last| last >= range.first);
+        } else {
+            range.last = if range.last == Some(page) {
+                None
+            } else {
+                Some(page)
+            };
+            if range.first > page {
+                range.first = 1;
+            }
+        }
+
+        if range == whole {
+            self.page_choices.remove(&path);
+        } else {
+            self.page_choices.insert(path, PageRanges::new(vec![range]));
+        }
+    }
+
     // request a random page preview for the selected file
     fn request_random_preview_for_current(&mut self) {
         if let Some(file) = self.preview_state.loaded_image.as_ref() {
@@ -395,7 +840,7 @@ impl ConfigState {
                 .preview_tx
                 .send(PreviewRequest::LoadFile {
                     archive_path: file.archive_path.clone(),
-                    config: self.config.clone(),
+                    config: self.config_for(&file.archive_path),
                     page_idx: None,
                     file_idx: file.file_idx,
                 });
@@ -414,7 +859,7 @@ impl ConfigState {
                 .preview_tx
                 .send(PreviewRequest::LoadFile {
                     archive_path: file.archive_path.clone(),
-                    config: self.config.clone(),
+                    config: self.config_for(&file.archive_path),
                     page_idx: Some(next_idx),
                     file_idx: file.file_idx,
                 });
@@ -433,7 +878,7 @@ impl ConfigState {
                 .preview_tx
                 .send(PreviewRequest::LoadFile {
                     archive_path: file.archive_path.clone(),
-                    config: self.config.clone(),
+                    config: self.config_for(&file.archive_path),
                     page_idx: Some(prev_idx),
                     file_idx: file.file_idx,
                 });
@@ -450,7 +895,7 @@ impl ConfigState {
                 .preview_tx
                 .send(PreviewRequest::LoadFile {
                     archive_path: loaded_image.archive_path.clone(),
-                    config: self.config.clone(),
+                    config: self.config_for(&loaded_image.archive_path),
                     page_idx: Some(loaded_image.page_idx),
                     file_idx: loaded_image.file_idx,
                 });
@@ -487,6 +932,15 @@ impl ConfigState {
                     (current - step).max(-100)
                 };
             }
+            SelectedField::Contrast => {
+                let step = if is_fine { 1 } else { 5 };
+                let current = self.config.contrast;
+                self.config.contrast = if increase {
+                    (current + step).min(100)
+                } else {
+                    (current - step).max(-100)
+                };
+            }
             SelectedField::Gamma => {
                 let step = if is_fine { 0.05 } else { 0.1 };
                 let current = self.config.gamma;
@@ -499,6 +953,54 @@ impl ConfigState {
         };
     }
 
+    // position of the setting within its range, for the sliders
+    fn setting_ratio(&self, field: SelectedField) -> f64 {
+        match field {
+            SelectedField::Quality => match self.config.image_format {
+                ImageFormat::Jpeg { quality } | ImageFormat::WebP { quality } => {
+                    quality as f64 / 100.0
+                }
+                ImageFormat::Png { compression } => match compression {
+                    PngCompression::Fast => 0.0,
+                    PngCompression::Default => 0.5,
+                    PngCompression::Best => 1.0,
+                },
+            },
+            SelectedField::Brightness => (self.config.brightness + 100) as f64 / 200.0,
+            SelectedField::Contrast => (self.config.contrast + 100) as f64 / 200.0,
+            SelectedField::Gamma => (self.config.gamma as f64 - 0.1) / 2.9,
+        }
+    }
+
+    fn set_setting_ratio(&mut self, field: SelectedField, ratio: f64) {
+        let ratio = ratio.clamp(0.0, 1.0);
+        match field {
+            SelectedField::Quality => match &mut self.config.image_format {
+                ImageFormat::Jpeg { quality } | ImageFormat::WebP { quality } => {
+                    *quality = (ratio * 100.0).round() as u8;
+                }
+                ImageFormat::Png { compression } => {
+                    *compression = match (ratio * 2.0).round() as u8 {
+                        0 => PngCompression::Fast,
+                        1 => PngCompression::Default,
+                        _ => PngCompression::Best,
+                    };
+                }
+            },
+            SelectedField::Brightness => {
+                self.config.brightness = (ratio * 200.0).round() as i32 - 100;
+            }
+            SelectedField::Contrast => {
+                self.config.contrast = (ratio * 200.0).round() as i32 - 100;
+            }
+            SelectedField::Gamma => {
+                // snap to the fine adjustment step
+                let gamma = 0.1 + ratio * 2.9;
+                self.config.gamma = ((gamma / 0.05).round() * 0.05).clamp(0.1, 3.0) as f32;
+            }
+        }
+    }
+
     pub fn handle_event(&mut self, event: ConfigEvent) {
         match event {
             ConfigEvent::ImageLoaded {
@@ -506,6 +1008,9 @@ impl ConfigState {
                 page_idx,
                 total_pages,
                 image,
+                source,
+                stats,
+                page,
                 archive_path,
                 config,
             } => {
@@ -514,15 +1019,14 @@ impl ConfigState {
                     page_idx,
                     total_pages,
                     archive_path,
-                    width: image.width(),
-                    height: image.height(),
-                    config,
+                    image,
+                    source,
+                    stats: *stats,
+                    config: *config,
+                    page: *page,
+                    exported: None,
                 });
-                let protocol = self.preview_state.picker.new_resize_protocol(image);
-                let thread_protocol =
-                    ThreadProtocol::new(self.preview_state.resize_tx.clone(), Some(protocol));
-                self.preview_state.protocol_state =
-                    PreviewProtocolState::PendingResize { thread_protocol };
+                self.show_loaded_image();
             }
             ConfigEvent::ResizeComplete(response) => match &mut self.preview_state.protocol_state {
                 PreviewProtocolState::PendingResize { thread_protocol } => {
@@ -545,6 +1049,33 @@ impl ConfigState {
                     log::warn!("ResizeComplete received but no protocol exists");
                 }
             },
+            ConfigEvent::VariantsLoaded(image) => {
+                if let ModalState::Compare(compare) = &mut self.modal_state {
+                    let dimensions = (image.width(), image.height());
+                    let protocol = self.preview_state.picker.new_resize_protocol(image);
+                    compare.set_image(protocol, dimensions);
+                }
+            }
+            ConfigEvent::MetadataFound { query, result } => {
+                // the popup may have been closed, or opened for another series since
+                if let ModalState::Metadata(metadata) = &mut self.modal_state {
+                    if metadata.query == query {
+                        metadata.found(result);
+                    }
+                }
+            }
+            ConfigEvent::Tuned { file, result } => {
+                if self.tuning.as_ref() == Some(&file) {
+                    self.tuning = None;
+                }
+                match result {
+                    Ok(tone) => {
+                        tone.apply(&mut self.config);
+                        self.load_preview();
+                    }
+                    Err(e) => log::warn!("Failed to tune {}: {e}", file.title()),
+                }
+            }
             ConfigEvent::Error(err) => {
                 tracing::warn!("Preview error: {}", err);
             }
@@ -588,10 +1119,39 @@ impl<'a> Widget for ConfigScreen<'a> {
 
         PreviewWidget::new(self.state).render(preview_area, buf);
 
+        let keymap = &self.state.keymap;
+        let t = self.state.strings;
         let footer_text = if self.state.selected_field.is_some() {
-            "←/→: adjust | shift+←/→: fine adjust | esc: cancel | h: help | t: theme | q: quit"
+            let adjust = keymap.keys(&[Action::Decrease, Action::Increase]);
+            format!(
+                "{adjust}: {} | shift+{adjust}: {} | {}: {} | {}: {} | {}: {} | {}: {}",
+                t.adjust,
+                t.fine_adjust,
+                keymap.keys(&[Action::Cancel]),
+                t.cancel,
+                keymap.keys(&[Action::Help]),
+                t.help,
+                keymap.keys(&[Action::Theme]),
+                t.theme,
+                keymap.keys(&[Action::Quit]),
+                t.quit,
+            )
         } else {
-            "↑/↓/j/k: navigate | space: toggle | a: all | h: help | t: theme | q: quit"
+            format!(
+                "{}: {} | {}: {} | {}: {} | {}: {} | {}: {} | {}: {}",
+                keymap.keys(&[Action::Up, Action::Down]),
+                t.navigate,
+                keymap.keys(&[Action::ToggleFile]),
+                t.toggle,
+                keymap.keys(&[Action::ToggleAll]),
+                t.all,
+                keymap.keys(&[Action::Help]),
+                t.help,
+                keymap.keys(&[Action::Theme]),
+                t.theme,
+                keymap.keys(&[Action::Quit]),
+                t.quit,
+            )
         };
         let footer = Paragraph::new(footer_text)
             .style(Style::default().fg(self.state.theme.content))
@@ -606,6 +1166,18 @@ impl<'a> Widget for ConfigScreen<'a> {
             ModalState::DeviceSelector(_) => {
                 device_selector::render_device_selector_popup(area, buf, self.state);
             }
+            ModalState::Compare(_) => {
+                compare::render_compare_popup(area, buf, self.state);
+            }
+            ModalState::Resume(_) => {
+                resume::render_resume_popup(area, buf, self.state);
+            }
+            ModalState::Metadata(_) => {
+                metadata::render_metadata_popup(area, buf, self.state);
+            }
+            ModalState::Series(_) => {
+                series::render_series_popup(area, buf, self.state);
+            }
             ModalState::None => {}
         }
 
@@ -637,7 +1209,26 @@ impl<'a> Widget for FileListWidget<'a> {
             .iter()
             .map(|(file, selected)| {
                 let checkbox = if *selected { "[✓]" } else { "[ ]" };
-                let content = format!("{} {}", checkbox, file.title());
+                let mut content = format!("{} {}", checkbox, file.title());
+                if let Some(pages) = self.state.page_choices.get(file.as_path()) {
+                    content.push_str(&format!(" ({} {pages})", self.state.strings.pages));
+                }
+                if let Some(choice) = self.state.file_choices.get(file.as_path()) {
+                    let format = choice
+                        .output_format
+                        .map(|format| format.as_str().to_string());
+                    let crop = choice.fixed_crop.map(|crop| match crop.is_empty() {
+                        true => self.state.strings.no_crop.to_string(),
+                        false => crop.to_string(),
+                    });
+                    let series = choice.series.as_ref().map(|series| match series.index {
+                        Some(index) => format!("{} #{index}", series.name),
+                        None => series.name.clone(),
+                    });
+                    let picked: Vec<String> =
+                        format.into_iter().chain(crop).chain(series).collect();
+                    content.push_str(&format!(" [{}]", picked.join(", ")));
+                }
                 ListItem::new(content).style(self.state.theme.content)
             })
             .collect();
@@ -651,7 +1242,7 @@ impl<'a> Widget for FileListWidget<'a> {
 
         let list = List::new(items)
             .block(
-                themed_block(Some("files"), &self.state.theme).title(
+                themed_block(Some(self.state.strings.files), &self.state.theme).title(
                     Line::from(format!("{selected_count}"))
                         .right_aligned()
                         .style(self.state.theme.accent),
@@ -673,18 +1264,16 @@ impl<'a> SettingsWidget<'a> {
         Self { state }
     }
 
-    #[allow(clippy::too_many_arguments)]
     fn render_adjustable_setting(
         &mut self,
+        field: SelectedField,
         label: &str,
         value: &str,
         key: &str,
         area: Rect,
         buf: &mut Buffer,
-        selected: bool,
-        mut on_select: impl FnMut(&mut ConfigState),
-        mut on_adjust: impl FnMut(&mut ConfigState, bool),
     ) {
+        let selected = self.state.selected_field == Some(field);
         let style = if selected {
             Style::default().fg(self.state.theme.accent).underlined()
         } else {
@@ -695,7 +1284,7 @@ impl<'a> SettingsWidget<'a> {
             Layout::vertical([Constraint::Length(1), Constraint::Length(3)]).areas(area);
 
         let [text_area, shortcut_area] = Layout::horizontal([
-            Constraint::Length(label.len() as u16 + 1),
+            Constraint::Length(Line::from(label).width() as u16 + 1),
             Constraint::Length(key.len() as u16 + 1),
         ])
         .flex(Flex::SpaceBetween)
@@ -725,14 +1314,39 @@ impl<'a> SettingsWidget<'a> {
         // Render [-] button
         base_button("-", self.state)
             .on_click(|| {
-                on_select(self.state);
-                on_adjust(self.state, false);
+                self.state.selected_field = Some(field);
+                self.state.adjust_setting(field, false, false);
             })
             .render(minus_area, buf);
 
-        let [value_layout] = Layout::vertical([Constraint::Length(1)])
-            .flex(Flex::Center)
-            .areas(value_area);
+        let [value_layout, slider_layout] =
+            Layout::vertical([Constraint::Length(1), Constraint::Length(1)])
+                .flex(Flex::Center)
+                .areas(value_area);
+        let slider_layout = padding(slider_layout, Constraint::Length(1), Side::Left);
+        let slider_layout = padding(slider_layout, Constraint::Length(1), Side::Right);
+
+        // the whole value area is the hit box, the knob follows the mouse while the button is held
+        if let Some(mouse) = self.state.last_mouse_click {
+            let on_slider = value_area.contains(Position::new(mouse.column, mouse.row));
+            let dragging = self.state.dragging == Some(field);
+
+            match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) if on_slider => {
+                    self.state.selected_field = Some(field);
+                    self.state.dragging = Some(field);
+                }
+                MouseEventKind::Up(MouseButton::Left) if dragging => {
+                    self.state.dragging = None;
+                }
+                _ => {}
+            }
+
+            if self.state.dragging == Some(field) {
+                let ratio = Slider::ratio_at(slider_layout, mouse.column);
+                self.state.set_setting_ratio(field, ratio);
+            }
+        }
 
         Paragraph::new(value)
             .style(
@@ -743,10 +1357,14 @@ impl<'a> SettingsWidget<'a> {
             .alignment(Alignment::Center)
             .render(value_layout, buf);
 
+        Slider::new(self.state.setting_ratio(field), self.state.theme)
+            .active(selected)
+            .render(slider_layout, buf);
+
         base_button("+", self.state)
             .on_click(|| {
-                on_select(self.state);
-                on_adjust(self.state, true);
+                self.state.selected_field = Some(field);
+                self.state.adjust_setting(field, true, false);
             })
             .render(plus_area, buf);
     }
@@ -760,24 +1378,18 @@ impl<'a> SettingsWidget<'a> {
             .on_click(|| {
                 // make sure the mouse click is not used in the popup layer
                 self.state.last_mouse_click = None;
-                self.state.modal_state = ModalState::DeviceSelector(DeviceSelectorState::new(
-                    self.state
-                        .config
-                        .device
-                        .clone()
-                        .try_as_preset()
-                        .unwrap_or(comically::device::Preset::KindlePw11),
-                ));
+                self.state.modal_state =
+                    ModalState::DeviceSelector(DeviceSelectorState::new(&self.state.config.device));
             })
-            .label("device")
-            .hint("[d]")
+            .label(self.state.strings.device)
+            .hint(&self.state.keymap.hint(Action::DeviceSelector))
             .render(area, buf);
     }
 }
 
 impl<'a> Widget for SettingsWidget<'a> {
     fn render(mut self, area: Rect, buf: &mut Buffer) {
-        let block = themed_block(Some("settings"), &self.state.theme);
+        let block = themed_block(Some(self.state.strings.settings), &self.state.theme);
         let inner = block.inner(area);
         block.render(area, buf);
 
@@ -793,43 +1405,43 @@ impl<'a> Widget for SettingsWidget<'a> {
             .spacing(1)
             .areas(padding(inner, Constraint::Length(1), Side::Top));
 
-        let [reading_direction_area, split_double_pages_area, auto_crop_area] =
-            Layout::horizontal([Constraint::Ratio(1, 3); 3])
+        let [reading_direction_area, split_double_pages_area, auto_crop_area, crop_footer_area] =
+            Layout::horizontal([Constraint::Ratio(1, 4); 4])
                 .spacing(2)
                 .areas(row1);
 
-        // Second row: output format, image format, margin color
-        let [output_format_area, image_format_area, margin_color_area] =
-            Layout::horizontal([Constraint::Ratio(1, 3); 3])
+        // Second row: output format, image format, margin color, background
+        let [output_format_area, image_format_area, margin_color_area, nice_area] =
+            Layout::horizontal([Constraint::Ratio(1, 4); 4])
                 .spacing(2)
                 .areas(row2);
 
         base_button(
-            if self.state.config.right_to_left {
-                "right to left (manga)"
+            if self.state.config.detect_direction {
+                self.state.strings.detect_direction
+            } else if self.state.config.right_to_left {
+                self.state.strings.right_to_left
             } else {
-                "left to right"
+                self.state.strings.left_to_right
             },
             self.state,
         )
-        .label("reading direction")
-        .hint("[m]")
-        .on_click(|| {
-            self.state.config.right_to_left = !self.state.config.right_to_left;
-        })
+        .label(self.state.strings.reading_direction)
+        .hint(&self.state.keymap.hint(Action::ReadingDirection))
+        .on_click(|| cycle_reading_direction(&mut self.state.config))
         .render(reading_direction_area, buf);
 
         base_button(
             match self.state.config.split {
-                SplitStrategy::None => "none",
-                SplitStrategy::Split => "split",
-                SplitStrategy::Rotate => "rotate",
-                SplitStrategy::RotateAndSplit => "split & rotate",
+                SplitStrategy::None => self.state.strings.split_none,
+                SplitStrategy::Split => self.state.strings.split,
+                SplitStrategy::Rotate => self.state.strings.rotate,
+                SplitStrategy::RotateAndSplit => self.state.strings.split_and_rotate,
             },
             self.state,
         )
-        .label("spread splitter")
-        .hint("[s]")
+        .label(self.state.strings.spread_splitter)
+        .hint(&self.state.keymap.hint(Action::Split))
         .on_click(|| {
             self.state.config.split = match self.state.config.split {
                 SplitStrategy::None => SplitStrategy::Split,
@@ -842,19 +1454,37 @@ impl<'a> Widget for SettingsWidget<'a> {
 
         base_button(
             if self.state.config.auto_crop {
-                "yes"
+                self.state.strings.yes
             } else {
-                "no"
+                self.state.strings.no
             },
             self.state,
         )
-        .label("auto crop")
-        .hint("[c]")
+        .label(self.state.strings.auto_crop)
+        .hint(&self.state.keymap.hint(Action::AutoCrop))
         .on_click(|| {
             self.state.config.auto_crop = !self.state.config.auto_crop;
         })
         .render(auto_crop_area, buf);
 
+        base_button(
+            match self.state.config.fixed_crop {
+                Some(crop) if crop == FixedCrop::bottom(crop.bottom) => {
+                    format!("{} px", crop.bottom)
+                }
+                // the other presets, and regions set in the config file
+                Some(crop) if !crop.is_empty() => crop.to_string(),
+                _ => self.state.strings.no.to_string(),
+            },
+            self.state,
+        )
+        .label(self.state.strings.crop_footer)
+        .hint(&self.state.keymap.hint(Action::CropFooter))
+        .on_click(|| {
+            self.state.config.fixed_crop = FixedCrop::next_preset(self.state.config.fixed_crop);
+        })
+        .render(crop_footer_area, buf);
+
         // Second row buttons
         base_button(
             match self.state.config.output_format {
@@ -864,8 +1494,8 @@ impl<'a> Widget for SettingsWidget<'a> {
             },
             self.state,
         )
-        .label("output format")
-        .hint("[f]")
+        .label(self.state.strings.output_format)
+        .hint(&self.state.keymap.hint(Action::OutputFormat))
         .on_click(|| {
             self.state.config.output_format = match self.state.config.output_format {
                 OutputFormat::Mobi => OutputFormat::Epub,
@@ -891,8 +1521,8 @@ impl<'a> Widget for SettingsWidget<'a> {
         };
 
         base_button(format_text, self.state)
-            .label("image format")
-            .hint("[i]")
+            .label(self.state.strings.image_format)
+            .hint(&self.state.keymap.hint(Action::ImageFormat))
             .on_click(|| {
                 self.state.config.image_format = self.state.config.image_format.cycle();
             })
@@ -901,93 +1531,92 @@ impl<'a> Widget for SettingsWidget<'a> {
 
         base_button(
             match self.state.config.margin_color {
-                None => "none",
-                Some(0) => "black",
-                Some(_) => "white",
+                None => self.state.strings.margin_none.to_string(),
+                Some(MarginColor::BLACK) => self.state.strings.black.to_string(),
+                Some(MarginColor::WHITE) => self.state.strings.white.to_string(),
+                Some(MarginColor::Auto) => self.state.strings.margin_auto.to_string(),
+                // custom colors from the config file
+                Some(color) => color.to_string(),
             },
             self.state,
         )
-        .label("margin color")
-        .hint("[o]")
-        .on_click(|| {
-            self.state.config.margin_color = match self.state.config.margin_color {
-                None => Some(0),      // none -> black
-                Some(0) => Some(255), // black -> white
-                Some(_) => None,      // white -> none
-            };
-        })
+        .label(self.state.strings.margin_color)
+        .hint(&self.state.keymap.hint(Action::MarginColor))
+        .on_click(|| cycle_margin_color(&mut self.state.config))
         .render(margin_color_area, buf);
 
-        // Create a horizontal layout for the three adjustable settings
-        let [quality_area, brightness_area, contrast_area] =
-            Layout::horizontal([Constraint::Ratio(1, 3); 3])
+        base_button(
+            if self.state.nice {
+                self.state.strings.yes
+            } else {
+                self.state.strings.no
+            },
+            self.state,
+        )
+        .label(self.state.strings.nice)
+        .hint(&self.state.keymap.hint(Action::Nice))
+        .on_click(|| self.state.nice = !self.state.nice)
+        .render(nice_area, buf);
+
+        // Create a horizontal layout for the four adjustable settings
+        let [quality_area, brightness_area, contrast_area, gamma_area] =
+            Layout::horizontal([Constraint::Ratio(1, 4); 4])
                 .flex(Flex::SpaceBetween)
                 .spacing(2)
                 .areas(buttons_area);
 
         // Quality/Compression adjuster based on image format
         let (quality_label, quality_value) = match self.state.config.image_format {
-            ImageFormat::Jpeg { quality } => ("quality", format!("{:3}", quality)),
+            ImageFormat::Jpeg { quality } => (self.state.strings.quality, format!("{:3}", quality)),
             ImageFormat::Png { compression } => {
                 let comp_text = match compression {
-                    PngCompression::Fast => "Fast",
-                    PngCompression::Default => "Default",
-                    PngCompression::Best => "Best",
+                    PngCompression::Fast => self.state.strings.png_fast,
+                    PngCompression::Default => self.state.strings.png_default,
+                    PngCompression::Best => self.state.strings.png_best,
                 };
-                ("compression", comp_text.to_string())
+                (self.state.strings.compression, comp_text.to_string())
             }
-            ImageFormat::WebP { quality } => ("quality", format!("{:3}", quality)),
+            ImageFormat::WebP { quality } => (self.state.strings.quality, format!("{:3}", quality)),
         };
 
         self.render_adjustable_setting(
+            SelectedField::Quality,
             quality_label,
             &quality_value,
-            "[u]",
+            &self.state.keymap.hint(Action::Quality),
             quality_area,
             buf,
-            self.state.selected_field == Some(SelectedField::Quality),
-            |state| {
-                state.selected_field = Some(SelectedField::Quality);
-            },
-            |state, increase| {
-                if let Some(SelectedField::Quality) = state.selected_field {
-                    state.config.image_format.adjust_quality(increase, false);
-                }
+        );
+
+        self.render_adjustable_setting(
+            SelectedField::Gamma,
+            self.state.strings.gamma,
+            &match &self.state.config.gamma_curve {
+                GammaCurve::Power => format!("{:3.2}", self.state.config.gamma),
+                GammaCurve::Srgb => format!("{:3.2} sRGB", self.state.config.gamma),
+                GammaCurve::Lut(_) => "LUT".to_string(),
             },
+            &self.state.keymap.hint(Action::Gamma),
+            gamma_area,
+            buf,
         );
 
         self.render_adjustable_setting(
-            "gamma",
-            &format!("{:3.2}", self.state.config.gamma),
-            "[g]",
+            SelectedField::Contrast,
+            self.state.strings.contrast,
+            &format!("{:4}", self.state.config.contrast),
+            &self.state.keymap.hint(Action::Contrast),
             contrast_area,
             buf,
-            self.state.selected_field == Some(SelectedField::Gamma),
-            |state| {
-                state.selected_field = Some(SelectedField::Gamma);
-            },
-            |state, increase| {
-                if let Some(SelectedField::Gamma) = state.selected_field {
-                    state.adjust_setting(SelectedField::Gamma, increase, false);
-                }
-            },
         );
 
         self.render_adjustable_setting(
-            "brightness",
+            SelectedField::Brightness,
+            self.state.strings.brightness,
             &format!("{:4}", self.state.config.brightness),
-            "[b]",
+            &self.state.keymap.hint(Action::Brightness),
             brightness_area,
             buf,
-            self.state.selected_field == Some(SelectedField::Brightness),
-            |state| {
-                state.selected_field = Some(SelectedField::Brightness);
-            },
-            |state, increase| {
-                if let Some(SelectedField::Brightness) = state.selected_field {
-                    state.adjust_setting(SelectedField::Brightness, increase, false);
-                }
-            },
         );
 
         self.render_device_selector_button(device_selector_area, buf);
@@ -998,8 +1627,8 @@ impl<'a> Widget for SettingsWidget<'a> {
             .constraints([Constraint::Length(4)])
             .areas(process_button_area);
 
-        base_button("start ⏵", self.state)
-            .hint("[enter]")
+        base_button(self.state.strings.start, self.state)
+            .hint(&self.state.keymap.hint(Action::Start))
             .on_click(|| {
                 self.state.send_start_processing();
             })
@@ -1020,7 +1649,7 @@ impl<'a> PreviewWidget<'a> {
 
 impl<'a> Widget for PreviewWidget<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let block = themed_block(Some("preview"), &self.state.theme);
+        let block = themed_block(Some(self.state.strings.preview), &self.state.theme);
 
         let inner = block.inner(area);
         block.render(area, buf);
@@ -1036,7 +1665,7 @@ impl<'a> Widget for PreviewWidget<'a> {
             .preview_state
             .loaded_image
             .as_ref()
-            .map(|loaded| loaded.config != self.state.config)
+            .map(|loaded| loaded.config != self.state.config_for(&loaded.archive_path))
             .unwrap_or(true);
 
         let file_changed = self
@@ -1055,25 +1684,56 @@ impl<'a> Widget for PreviewWidget<'a> {
 
         let modal_open = self.state.is_modal_open();
 
-        // Split buttons area: 1 button on top, 3 buttons below
+        // Split buttons area: 2 buttons on top, 3 buttons below
         let [top_button_area, bottom_buttons_area] = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(4), // Load preview button
+                Constraint::Length(4), // Load preview, export and auto-tune buttons
                 Constraint::Length(3), // Navigation buttons
             ])
             .spacing(1)
             .flex(Flex::End)
             .areas(buttons_area);
 
-        // Load preview button (full width)
-        base_button("load preview", self.state)
-            .hint("[p]")
+        let [preview_button_area, export_button_area, tune_button_area] = Layout::horizontal([
+            Constraint::Percentage(40),
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+        ])
+        .spacing(1)
+        .areas(top_button_area);
+
+        base_button(self.state.strings.load_preview, self.state)
+            .hint(&self.state.keymap.hint(Action::Preview))
             .on_click(|| {
                 self.state.load_preview();
             })
             .enabled((config_changed || file_changed) && !modal_open)
-            .render(top_button_area, buf);
+            .render(preview_button_area, buf);
+
+        let loaded = self.state.preview_state.loaded_image.is_some();
+        base_button(self.state.strings.export_preview, self.state)
+            .hint(&self.state.keymap.hint(Action::ExportPreview))
+            .on_click(|| {
+                self.state.export_preview();
+            })
+            .enabled(loaded && !modal_open)
+            .render(export_button_area, buf);
+
+        let tuning = self.state.tuning.is_some();
+        base_button(
+            match tuning {
+                true => self.state.strings.tuning,
+                false => self.state.strings.auto_tune,
+            },
+            self.state,
+        )
+        .hint(&self.state.keymap.hint(Action::AutoTune))
+        .on_click(|| {
+            self.state.auto_tune();
+        })
+        .enabled(!tuning && !modal_open)
+        .render(tune_button_area, buf);
 
         // Split bottom area into 3 buttons
         let [prev_button_area, random_button_area, next_button_area] = Layout::default()
@@ -1086,19 +1746,19 @@ impl<'a> Widget for PreviewWidget<'a> {
             .spacing(1)
             .areas(bottom_buttons_area);
 
-        base_button("◀ prev", self.state)
+        base_button(self.state.strings.prev, self.state)
             .on_click(|| {
                 self.state.previous_preview_page();
             })
             .render(prev_button_area, buf);
 
-        base_button("random", self.state)
+        base_button(self.state.strings.random, self.state)
             .on_click(|| {
                 self.state.request_random_preview_for_current();
             })
             .render(random_button_area, buf);
 
-        base_button("next ▶", self.state)
+        base_button(self.state.strings.next, self.state)
             .on_click(|| {
                 self.state.next_preview_page();
             })
@@ -1112,11 +1772,19 @@ impl<'a> Widget for PreviewWidget<'a> {
 
             let file_name = loaded_image.archive_path.title();
 
-            let page_info = format!(
-                "page {} of {}",
-                loaded_image.page_idx + 1,
-                loaded_image.total_pages
-            );
+            let mut page_info =
+                (self.state.strings.page_of)(loaded_image.page_idx + 1, loaded_image.total_pages);
+            let left_out = self
+                .state
+                .page_choices
+                .get(loaded_image.archive_path.as_path())
+                .is_some_and(|pages| !pages.contains(loaded_image.page_idx + 1));
+            if left_out {
+                page_info.push_str(&format!(", {}", self.state.strings.left_out));
+            }
+            if let Some(name) = &loaded_image.exported {
+                page_info.push_str(&format!(", {}", (self.state.strings.exported_as)(name)));
+            }
 
             let text = vec![
                 Line::from(file_name),
@@ -1128,15 +1796,37 @@ impl<'a> Widget for PreviewWidget<'a> {
                 .alignment(Alignment::Center)
                 .render(title_area, buf);
 
+            let analysis = self.state.preview_state.show_analysis;
+            let image_area = match analysis {
+                true => {
+                    let [image_area, analysis_area] =
+                        Layout::vertical([Constraint::Min(0), Constraint::Length(6)])
+                            .areas(image_area);
+                    analysis::AnalysisWidget {
+                        stats: &loaded_image.stats,
+                        theme: &self.state.theme,
+                        strings: self.state.strings,
+                    }
+                    .render(analysis_area, buf);
+                    image_area
+                }
+                false => image_area,
+            };
+            let shown = loaded_image.shown(analysis);
             let image_area = calculate_centered_image_area(
                 image_area,
-                loaded_image,
+                (shown.width(), shown.height()),
                 self.state.preview_state.picker.font_size(),
             );
 
             match &mut self.state.preview_state.protocol_state {
                 PreviewProtocolState::None => {
-                    render_image_placeholder(image_area, buf, &self.state.theme);
+                    render_image_placeholder(
+                        image_area,
+                        buf,
+                        &self.state.theme,
+                        self.state.strings.loading,
+                    );
                 }
                 PreviewProtocolState::PendingResize { thread_protocol } => {
                     if let Some(rect) =
@@ -1144,7 +1834,12 @@ impl<'a> Widget for PreviewWidget<'a> {
                     {
                         thread_protocol.resize_encode(&Resize::Scale(None), rect);
                     }
-                    render_image_placeholder(image_area, buf, &self.state.theme);
+                    render_image_placeholder(
+                        image_area,
+                        buf,
+                        &self.state.theme,
+                        self.state.strings.loading,
+                    );
                 }
                 PreviewProtocolState::Ready { thread_protocol } => {
                     StatefulWidget::render(image, image_area, buf, thread_protocol);
@@ -1172,14 +1867,17 @@ fn preview_worker(
                     let result = load_and_process_preview(&path, &config, page_idx);
 
                     match result {
-                        Ok((image, idx, total_pages)) => {
+                        Ok(loaded) => {
                             let _ = tx.send(crate::Event::Config(ConfigEvent::ImageLoaded {
                                 file_idx,
-                                page_idx: idx,
-                                total_pages,
+                                page_idx: loaded.idx,
+                                total_pages: loaded.total_pages,
                                 archive_path: path,
-                                image,
-                                config,
+                                image: loaded.image,
+                                source: loaded.source,
+                                stats: Box::new(loaded.stats),
+                                page: Box::new(loaded.page),
+                                config: Box::new(config),
                             }));
                         }
                         Err(e) => {
@@ -1188,6 +1886,20 @@ fn preview_worker(
                         }
                     }
                 }
+                PreviewRequest::Compare {
+                    archive_path,
+                    config,
+                    page_idx,
+                    variants,
+                } => {
+                    let event = match load_preview_page(&archive_path, &config, page_idx) {
+                        Ok((img, config)) => ConfigEvent::VariantsLoaded(compare::render_variants(
+                            &img, &config, &variants,
+                        )),
+                        Err(e) => ConfigEvent::Error(e.to_string()),
+                    };
+                    let _ = tx.send(crate::Event::Config(event));
+                }
             }
         }
 
@@ -1208,6 +1920,28 @@ fn preview_worker(
 
 // - default enabled = !modal_open
 // - default mouse_event = last_mouse_click
+// right to left -> left to right -> detected, the last fixed direction is the fallback
+fn cycle_reading_direction(config: &mut ComicConfig) {
+    if config.detect_direction {
+        config.detect_direction = false;
+        config.right_to_left = true;
+    } else if config.right_to_left {
+        config.right_to_left = false;
+    } else {
+        config.detect_direction = true;
+    }
+}
+
+// none -> black -> white -> auto -> none, custom colors go back to none
+fn cycle_margin_color(config: &mut ComicConfig) {
+    config.margin_color = match config.margin_color {
+        None => Some(MarginColor::BLACK),
+        Some(MarginColor::BLACK) => Some(MarginColor::WHITE),
+        Some(MarginColor::WHITE) => Some(MarginColor::Auto),
+        Some(_) => None,
+    };
+}
+
 fn base_button<'input, 'state>(
     text: impl Into<ratatui::text::Text<'input>>,
     config: &'state ConfigState,
@@ -1217,23 +1951,25 @@ fn base_button<'input, 'state>(
         .mouse_event(config.last_mouse_click)
 }
 
-fn load_and_process_preview(
+// pages of the archive in the order page ranges number them, with the config adjusted
+// for the comic and the index of the page to preview
+fn load_preview_pages(
     path: &ComicFile,
     config: &ComicConfig,
     page_index: Option<usize>,
-) -> anyhow::Result<(DynamicImage, usize, usize)> {
-    let mut archive_files: Vec<_> = comically::archive::unarchive_comic_iter(path)?
-        .filter_map(|r| r.ok())
-        .collect();
+) -> anyhow::Result<(Vec<ArchiveFile>, ComicConfig, usize)> {
+    let mut archive_files: Vec<_> =
+        comically::archive::unarchive_comic_iter_with(path, config.archive_options())?
+            .filter_map(|r| r.ok())
+            .collect();
 
-    // Sort by filename to ensure consistent ordering
-    archive_files.sort_by(|a, b| a.file_stem().cmp(b.file_stem()));
+    archive_files.sort_by(|a, b| comically::archive::natural_cmp(&a.file_name, &b.file_name));
 
     if archive_files.is_empty() {
         return Err(anyhow::anyhow!("No images in archive"));
     }
 
-    let total_pages = archive_files.len();
+    let config = config.for_comic(path, &archive_files);
 
     let idx = match page_index {
         None => {
@@ -1243,27 +1979,49 @@ fn load_and_process_preview(
         Some(idx) => idx.clamp(0, archive_files.len() - 1),
     };
 
-    let archive_file = archive_files.into_iter().nth(idx).unwrap();
-
-    let img = imageproc::image::load_from_memory(&archive_file.data)?;
-
-    let processed_images = comically::image::process(img, config);
-
-    let first_image = processed_images
-        .into_iter()
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("No processed images"))?;
+    Ok((archive_files, config, idx))
+}
 
-    let mut compressed_buffer = Vec::with_capacity(first_image.as_bytes().len());
-    let quality = match config.image_format {
-        ImageFormat::Jpeg { quality } | ImageFormat::WebP { quality } => quality,
-        _ => 85, // Default quality for preview
-    };
-    comically::image::compress_to_jpeg(&first_image, &mut compressed_buffer, quality)?;
+// decoded page of the archive, with the config adjusted for the comic
+fn load_preview_page(
+    path: &ComicFile,
+    config: &ComicConfig,
+    page_index: usize,
+) -> anyhow::Result<(DynamicImage, ComicConfig)> {
+    let (archive_files, config, idx) = load_preview_pages(path, config, Some(page_index))?;
+    let img = comically::image::decode::decode(&archive_files[idx].data)?;
+    Ok((img, config))
+}
 
-    let compressed_img = imageproc::image::load_from_memory(&compressed_buffer)?;
+struct ProcessedPreview {
+    image: DynamicImage,
+    page: ProcessedImage,
+    source: DynamicImage,
+    stats: PageStats,
+    idx: usize,
+    total_pages: usize,
+}
 
-    Ok((compressed_img, idx, total_pages))
+// the page as it's converted, through the library's pipeline, decoded and as encoded,
+// and the page of the archive with its analysis
+fn load_and_process_preview(
+    path: &ComicFile,
+    config: &ComicConfig,
+    page_index: Option<usize>,
+) -> anyhow::Result<ProcessedPreview> {
+    let (archive_files, config, idx) = load_preview_pages(path, config, page_index)?;
+    let page = comically::image::preview(&archive_files, idx, &config)?;
+    let image = comically::image::decode::decode(&page.data)?;
+    let source = comically::image::decode::decode(&archive_files[idx].data)?;
+    let stats = comically::image::analyze(&source);
+    Ok(ProcessedPreview {
+        image,
+        page,
+        source: analysis::crop_overlay(&source, &stats),
+        stats,
+        idx,
+        total_pages: archive_files.len(),
+    })
 }
 
 fn get_latest<T>(rx: &mpsc::Receiver<T>) -> Option<T> {
@@ -1274,9 +2032,9 @@ fn get_latest<T>(rx: &mpsc::Receiver<T>) -> Option<T> {
     latest
 }
 
-fn calculate_centered_image_area(
+pub(crate) fn calculate_centered_image_area(
     area: Rect,
-    img: &LoadedPreviewImage,
+    (width, height): (u32, u32),
     font_size: (u16, u16),
 ) -> Rect {
     // Get terminal cell dimensions from picker (pixels per cell)
@@ -1284,7 +2042,7 @@ fn calculate_centered_image_area(
     let cell_height_px = font_size.1 as f32;
 
     // Calculate image aspect ratio
-    let img_aspect = img.width as f32 / img.height as f32;
+    let img_aspect = width as f32 / height as f32;
     let area_aspect = (area.width as f32 * cell_width_px) / (area.height as f32 * cell_height_px);
 
     let (target_width_cells, target_height_cells) = if img_aspect > area_aspect {
@@ -1317,7 +2075,12 @@ fn calculate_centered_image_area(
     final_area
 }
 
-fn render_image_placeholder(area: Rect, buf: &mut Buffer, theme: &Theme) {
+pub(crate) fn render_image_placeholder(
+    area: Rect,
+    buf: &mut Buffer,
+    theme: &Theme,
+    loading_text: &str,
+) {
     for y in area.top()..area.bottom() {
         for x in area.left()..area.right() {
             if let Some(cell) = buf.cell_mut(Position::new(x, y)) {
@@ -1327,8 +2090,7 @@ fn render_image_placeholder(area: Rect, buf: &mut Buffer, theme: &Theme) {
         }
     }
 
-    let loading_text = "loading...";
-    let text_width = loading_text.len() as u16;
+    let text_width = Line::from(loading_text).width() as u16;
     let text_x = area.left() + (area.width.saturating_sub(text_width)) / 2;
     let text_y = area.top() + area.height / 2;
 
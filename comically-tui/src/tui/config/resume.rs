@@ -0,0 +1,144 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Flex, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, Paragraph, Widget},
+};
+
+use comically::{checkpoint::Resume, ComicFile};
+
+use crate::tui::{
+    config::{ConfigState, ModalState},
+    keymap::Action,
+    utils::{center, popup_block},
+};
+
+/// Offered when the selected comics are part of a batch that was interrupted
+pub struct ResumeState {
+    pub resume: Resume,
+    pub files: Vec<ComicFile>,
+    /// Skip the completed comics, or convert everything again
+    pub skip_completed: bool,
+}
+
+impl ResumeState {
+    pub fn new(resume: Resume, files: Vec<ComicFile>) -> Self {
+        Self {
+            resume,
+            files,
+            skip_completed: true,
+        }
+    }
+
+    pub fn completed(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| self.resume.is_completed(file.as_path()))
+            .count()
+    }
+
+    pub fn toggle(&mut self) {
+        self.skip_completed = !self.skip_completed;
+    }
+
+    /// The comics to convert and the state of the batch to carry over
+    pub fn into_batch(self) -> (Vec<ComicFile>, Option<Resume>) {
+        if !self.skip_completed {
+            return (self.files, None);
+        }
+
+        let resume = self.resume;
+        let files = self
+            .files
+            .into_iter()
+            .filter(|file| !resume.is_completed(file.as_path()))
+            .collect();
+        (files, Some(resume))
+    }
+}
+
+pub fn render_resume_popup(area: Rect, buf: &mut Buffer, state: &ConfigState) {
+    let ModalState::Resume(resume) = &state.modal_state else {
+        return;
+    };
+    let strings = state.strings;
+    let theme = &state.theme;
+
+    let popup_area = center(area, Constraint::Length(60), Constraint::Length(9));
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(strings.resume_batch, theme);
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let [summary_area, interrupted_area, options_area, hint_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .spacing(1)
+    .flex(Flex::Center)
+    .areas(inner);
+
+    Paragraph::new(format!(
+        "{}/{} {}",
+        resume.completed(),
+        resume.files.len(),
+        strings.already_converted
+    ))
+    .style(Style::default().fg(theme.content))
+    .alignment(Alignment::Center)
+    .render(summary_area, buf);
+
+    if let Some(name) = resume
+        .resume
+        .interrupted
+        .as_deref()
+        .and_then(|path| path.file_name())
+    {
+        Paragraph::new(format!(
+            "{}: {}",
+            strings.interrupted,
+            name.to_string_lossy()
+        ))
+        .style(Style::default().fg(theme.content))
+        .alignment(Alignment::Center)
+        .render(interrupted_area, buf);
+    }
+
+    let options = [
+        (strings.resume, resume.skip_completed),
+        (strings.start_over, !resume.skip_completed),
+    ];
+    let columns = Layout::horizontal([Constraint::Fill(1); 2]).split(options_area);
+    for ((label, selected), column) in options.into_iter().zip(columns.iter()) {
+        let style = if selected {
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(theme.content)
+        };
+        Paragraph::new(Line::from(format!(" {label} ")))
+            .style(style)
+            .alignment(Alignment::Center)
+            .render(*column, buf);
+    }
+
+    let keymap = &state.keymap;
+    let hint = format!(
+        "{}: {} | {}: {} | {}: {}",
+        keymap.keys(&[Action::Decrease, Action::Increase]),
+        strings.select,
+        keymap.keys(&[Action::Start]),
+        strings.start,
+        keymap.keys(&[Action::Cancel]),
+        strings.cancel,
+    );
+    Paragraph::new(hint)
+        .style(Style::default().fg(theme.accent))
+        .alignment(Alignment::Center)
+        .render(hint_area, buf);
+}
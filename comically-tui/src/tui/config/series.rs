@@ -0,0 +1,213 @@
+use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    layout::{Alignment, Constraint, Flex, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, Paragraph, Widget},
+};
+
+use comically::{ComicFile, Series};
+
+use crate::tui::{
+    config::{ConfigState, ModalState},
+    keymap::Action,
+    utils::{center, popup_block},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeriesField {
+    Name,
+    Index,
+}
+
+/// Series name and volume number typed in for the focused file, written to the OPF so
+/// e-readers and Calibre group its volumes
+pub struct SeriesState {
+    pub file: ComicFile,
+    pub name: String,
+    pub index: String,
+    pub field: SeriesField,
+}
+
+/// What a key does to the popup
+pub enum SeriesInput {
+    Editing,
+    /// The series to use for the file, `None` to go back to its `ComicInfo.xml` or name
+    Confirmed(Option<Series>),
+}
+
+impl SeriesState {
+    /// Filled with the series picked before, or else the one read from the file name
+    pub fn new(file: ComicFile, picked: Option<&Series>) -> Self {
+        let (name, index) = match picked {
+            Some(series) => (series.name.clone(), series.index),
+            None => {
+                let parsed = comically::filename::parse(file.title());
+                (parsed.series, parsed.volume.or(parsed.issue))
+            }
+        };
+        Self {
+            file,
+            name,
+            index: index.map(|index| index.to_string()).unwrap_or_default(),
+            field: SeriesField::Name,
+        }
+    }
+
+    /// The typed volume number, `Err` when it isn't one
+    pub fn parsed_index(&self) -> Result<Option<f32>, ()> {
+        match self.index.trim() {
+            "" => Ok(None),
+            index => index.parse().map(Some).map_err(|_| ()),
+        }
+    }
+
+    /// Types, deletes and moves between the fields, the keymap doesn't apply while typing
+    pub fn handle_key(&mut self, key: &KeyEvent) -> SeriesInput {
+        let text = match self.field {
+            SeriesField::Name => &mut self.name,
+            SeriesField::Index => &mut self.index,
+        };
+        match key.code {
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                self.field = match self.field {
+                    SeriesField::Name => SeriesField::Index,
+                    SeriesField::Index => SeriesField::Name,
+                };
+            }
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL) => {
+                text.push(c);
+            }
+            KeyCode::Enter => {
+                let name = self.name.trim();
+                if name.is_empty() {
+                    return SeriesInput::Confirmed(None);
+                }
+                if let Ok(index) = self.parsed_index() {
+                    return SeriesInput::Confirmed(Some(Series {
+                        name: name.to_string(),
+                        index,
+                    }));
+                }
+                self.field = SeriesField::Index;
+            }
+            _ => {}
+        }
+        SeriesInput::Editing
+    }
+}
+
+pub fn render_series_popup(area: Rect, buf: &mut Buffer, state: &ConfigState) {
+    let ModalState::Series(series) = &state.modal_state else {
+        return;
+    };
+    let strings = state.strings;
+    let theme = &state.theme;
+
+    let popup_area = center(area, Constraint::Length(60), Constraint::Length(9));
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(strings.series, theme);
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let [file_area, name_area, index_area, hint_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .spacing(1)
+    .flex(Flex::Center)
+    .areas(inner);
+
+    Paragraph::new(series.file.title())
+        .style(Style::default().fg(theme.border))
+        .alignment(Alignment::Center)
+        .render(file_area, buf);
+
+    let invalid = series.parsed_index().is_err();
+    let fields = [
+        (SeriesField::Name, strings.series_name, &series.name, false),
+        (
+            SeriesField::Index,
+            strings.series_index,
+            &series.index,
+            invalid,
+        ),
+    ];
+    for ((field, label, text, invalid), area) in fields.into_iter().zip([name_area, index_area]) {
+        let focused = series.field == field;
+        let cursor = if focused { "▏" } else { "" };
+        let style = match (focused, invalid) {
+            (_, true) => Style::default().fg(theme.content).bg(theme.error_bg),
+            (true, false) => Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD),
+            (false, false) => Style::default().fg(theme.content),
+        };
+        Paragraph::new(Line::from(format!(" {label:>8}: {text}{cursor}")))
+            .style(style)
+            .render(area, buf);
+    }
+
+    let keymap = &state.keymap;
+    let hint = format!(
+        "tab: {} | {}: {} | {}: {}",
+        strings.next_field,
+        keymap.keys(&[Action::Start]),
+        strings.confirm,
+        keymap.keys(&[Action::Cancel]),
+        strings.cancel,
+    );
+    Paragraph::new(hint)
+        .style(Style::default().fg(theme.accent))
+        .alignment(Alignment::Center)
+        .render(hint_area, buf);
+}
+
+#[test]
+fn typed_series_are_confirmed() {
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    let file = ComicFile::new("Dungeon Meshi v03.cbz".into());
+    let mut state = SeriesState::new(file, None);
+    assert_eq!(
+        (state.name.as_str(), state.index.as_str()),
+        ("Dungeon Meshi", "3")
+    );
+
+    state.handle_key(&key(KeyCode::Tab));
+    state.handle_key(&key(KeyCode::Backspace));
+    state.handle_key(&key(KeyCode::Char('x')));
+    // not a number, enter stays on the volume
+    assert!(matches!(
+        state.handle_key(&key(KeyCode::Enter)),
+        SeriesInput::Editing
+    ));
+    state.handle_key(&key(KeyCode::Backspace));
+    for c in "4.5".chars() {
+        state.handle_key(&key(KeyCode::Char(c)));
+    }
+    let SeriesInput::Confirmed(Some(series)) = state.handle_key(&key(KeyCode::Enter)) else {
+        panic!("the series should be confirmed");
+    };
+    assert_eq!(
+        series,
+        Series {
+            name: "Dungeon Meshi".into(),
+            index: Some(4.5)
+        }
+    );
+
+    // clearing the name goes back to what the file says
+    let mut state = SeriesState::new(ComicFile::new("x.cbz".into()), Some(&series));
+    state.name.clear();
+    assert!(matches!(
+        state.handle_key(&key(KeyCode::Enter)),
+        SeriesInput::Confirmed(None)
+    ));
+}
@@ -0,0 +1,154 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget, Wrap},
+};
+
+use crate::history::{self, Entry, History};
+use crate::tui::{i18n::Strings, utils::popup_block, Theme};
+
+pub struct HistoryState {
+    // newest first
+    pub entries: Vec<Entry>,
+    pub list_state: ListState,
+    pub status: Option<String>,
+}
+
+impl HistoryState {
+    pub fn new(history: History) -> Self {
+        let mut entries = history.entries;
+        entries.reverse();
+
+        let mut list_state = ListState::default();
+        list_state.select((!entries.is_empty()).then_some(0));
+
+        Self {
+            entries,
+            list_state,
+            status: None,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.list_state.selected().and_then(|i| self.entries.get(i))
+    }
+
+    pub fn select_next(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected + 1 < self.entries.len() {
+                self.list_state.select(Some(selected + 1));
+            }
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected > 0 {
+                self.list_state.select(Some(selected - 1));
+            }
+        }
+    }
+}
+
+// how long ago, in the largest unit that isn't 0
+fn age(finished: u64, strings: &Strings) -> String {
+    let minutes = history::now().saturating_sub(finished) / 60;
+    let age = match minutes {
+        0..60 => format!("{}m", minutes.max(1)),
+        60..1440 => format!("{}h", minutes / 60),
+        _ => format!("{}d", minutes / 1440),
+    };
+    (strings.ago)(&age)
+}
+
+pub fn render_history(
+    area: Rect,
+    buf: &mut Buffer,
+    theme: &Theme,
+    strings: &Strings,
+    state: &mut HistoryState,
+) {
+    let popup_width = 72.min(area.width * 3 / 4);
+    let popup_height = (state.entries.len() as u16 + 7).clamp(9, 24.min(area.height * 3 / 4));
+
+    let popup_x = area.left() + (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = area.top() + (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(strings.history, theme).title(Line::from("[esc]").right_aligned());
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let [list_area, detail_area, hint_area] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(2),
+        Constraint::Length(1),
+    ])
+    .areas(inner);
+
+    if state.entries.is_empty() {
+        Paragraph::new(strings.no_history)
+            .style(theme.content)
+            .alignment(Alignment::Center)
+            .render(list_area, buf);
+    }
+
+    let items: Vec<ListItem> = state
+        .entries
+        .iter()
+        .map(|entry| {
+            let name = entry
+                .input
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let mark = if entry.is_ok() { "✓" } else { "✗" };
+            ListItem::new(format!(
+                "{mark} {name}  {} {:.1}s, {}",
+                entry.config.output_format.as_str(),
+                entry.duration_secs,
+                age(entry.finished, strings)
+            ))
+            .style(theme.content)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    StatefulWidget::render(list, list_area, buf, &mut state.list_state);
+
+    // the outputs of the selected conversion, or why it failed
+    let detail = state.status.clone().or_else(|| {
+        state.selected().map(|entry| match &entry.error {
+            Some(error) => error.clone(),
+            None => entry
+                .outputs
+                .iter()
+                .map(|output| output.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        })
+    });
+    if let Some(detail) = detail {
+        Paragraph::new(detail)
+            .style(Style::default().fg(theme.accent))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .render(detail_area, buf);
+    }
+
+    Paragraph::new(format!(
+        "enter: {} | r: {} | esc: {}",
+        strings.run_again, strings.retry_failed, strings.cancel
+    ))
+    .style(Style::default().fg(theme.accent))
+    .alignment(Alignment::Center)
+    .render(hint_area, buf);
+}
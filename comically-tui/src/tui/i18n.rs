@@ -0,0 +1,780 @@
+use serde::{Deserialize, Serialize};
+
+/// Language of the TUI strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "ja")]
+    Japanese,
+}
+
+impl Language {
+    /// Picks the language from the usual locale variables, falling back to English
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .map(|locale| Self::from_locale(&locale))
+            .unwrap_or_default()
+    }
+
+    fn from_locale(locale: &str) -> Self {
+        if locale.starts_with("ja") {
+            Language::Japanese
+        } else {
+            Language::English
+        }
+    }
+
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Language::English => &EN,
+            Language::Japanese => &JA,
+        }
+    }
+}
+
+/// Every user-facing string of the TUI for one language.
+///
+/// Being a plain struct, a bundle missing a string fails to compile.
+pub struct Strings {
+    // config screen
+    pub files: &'static str,
+    pub settings: &'static str,
+    pub preview: &'static str,
+    pub device: &'static str,
+    pub reading_direction: &'static str,
+    pub right_to_left: &'static str,
+    pub left_to_right: &'static str,
+    pub detect_direction: &'static str,
+    pub spread_splitter: &'static str,
+    pub split_none: &'static str,
+    pub split: &'static str,
+    pub rotate: &'static str,
+    pub split_and_rotate: &'static str,
+    pub auto_crop: &'static str,
+    pub crop_footer: &'static str,
+    pub no_crop: &'static str,
+    pub yes: &'static str,
+    pub no: &'static str,
+    pub output_format: &'static str,
+    pub image_format: &'static str,
+    pub margin_color: &'static str,
+    pub margin_none: &'static str,
+    pub black: &'static str,
+    pub white: &'static str,
+    pub margin_auto: &'static str,
+    pub nice: &'static str,
+    pub quality: &'static str,
+    pub compression: &'static str,
+    pub png_fast: &'static str,
+    pub png_default: &'static str,
+    pub png_best: &'static str,
+    pub brightness: &'static str,
+    pub contrast: &'static str,
+    pub gamma: &'static str,
+    pub start: &'static str,
+    pub load_preview: &'static str,
+    pub prev: &'static str,
+    pub random: &'static str,
+    pub next: &'static str,
+    pub page_of: fn(usize, usize) -> String,
+    pub pages: &'static str,
+    pub left_out: &'static str,
+    pub loading: &'static str,
+    pub select_device: &'static str,
+    pub confirm: &'static str,
+    pub cancel: &'static str,
+    pub series: &'static str,
+    pub series_name: &'static str,
+    pub series_index: &'static str,
+    pub next_field: &'static str,
+
+    // footer hints
+    pub navigate: &'static str,
+    pub toggle: &'static str,
+    pub all: &'static str,
+    pub help: &'static str,
+    pub theme: &'static str,
+    pub quit: &'static str,
+    pub adjust: &'static str,
+    pub fine_adjust: &'static str,
+    pub select: &'static str,
+    pub reorder: &'static str,
+    pub details: &'static str,
+    pub retry: &'static str,
+    pub retry_verbose: &'static str,
+    pub add: &'static str,
+    pub menu: &'static str,
+    pub scroll: &'static str,
+    pub close: &'static str,
+    pub queue: &'static str,
+
+    // theme picker
+    pub themes: &'static str,
+    pub history: &'static str,
+    pub no_history: &'static str,
+    pub run_again: &'static str,
+    pub retry_failed: &'static str,
+    pub no_failures: &'static str,
+    pub missing_input: &'static str,
+    pub ago: fn(&str) -> String,
+    pub apply: &'static str,
+
+    // compare popup
+    pub compare: &'static str,
+    pub export_preview: &'static str,
+    pub exported_as: fn(&str) -> String,
+    pub page_analysis: fn(&comically::image::PageStats) -> String,
+    pub auto_tune: &'static str,
+    pub tuning: &'static str,
+    pub variant_current: &'static str,
+    pub variant_lighter: &'static str,
+    pub variant_darker: &'static str,
+    pub variant_neutral: &'static str,
+
+    // resume popup
+    pub resume_batch: &'static str,
+    pub already_converted: &'static str,
+    pub interrupted: &'static str,
+    pub resume: &'static str,
+    pub start_over: &'static str,
+
+    // metadata popup
+    pub series_lookup: &'static str,
+    pub searching: &'static str,
+    pub no_matches: &'static str,
+    pub skip_lookup: &'static str,
+    pub by: &'static str,
+
+    // help popup
+    pub help_title: &'static str,
+    pub keybindings: &'static str,
+    pub to_close: &'static str,
+    /// (action, documentation), in the order of the help popup entries
+    pub help_entries: [(&'static str, &'static str); 34],
+
+    // progress screen
+    pub progress: &'static str,
+    pub status: &'static str,
+    pub total: &'static str,
+    pub waiting: &'static str,
+    pub extracting: &'static str,
+    pub add_to_queue: &'static str,
+    pub done: &'static str,
+    pub open_output_dir: &'static str,
+    pub reveal_file: &'static str,
+    pub copy_to_device: &'static str,
+    pub convert_more: &'static str,
+    pub no_device: &'static str,
+    pub copying_to: &'static str,
+    pub copied_files: fn(usize, &str) -> String,
+    pub copy_failed: &'static str,
+    pub move_to_converted: &'static str,
+    pub move_to_trash: &'static str,
+    pub confirm_move: fn(usize, char) -> String,
+    pub moved_sources: fn(usize) -> String,
+    pub move_failed: &'static str,
+    pub pages_per_sec: &'static str,
+    pub time_left: fn(&str) -> String,
+    pub failed_during: &'static str,
+    pub open_archive: &'static str,
+    pub caused_by: &'static str,
+    pub verbose_log: &'static str,
+    pub kindlegen_output: &'static str,
+}
+
+pub static EN: Strings = Strings {
+    files: "files",
+    settings: "settings",
+    preview: "preview",
+    device: "device",
+    reading_direction: "reading direction",
+    right_to_left: "right to left (manga)",
+    left_to_right: "left to right",
+    detect_direction: "auto (detect)",
+    spread_splitter: "spread splitter",
+    split_none: "none",
+    split: "split",
+    rotate: "rotate",
+    split_and_rotate: "split & rotate",
+    auto_crop: "auto crop",
+    crop_footer: "fixed crop",
+    no_crop: "no crop",
+    yes: "yes",
+    no: "no",
+    output_format: "output format",
+    image_format: "image format",
+    margin_color: "margin color",
+    margin_none: "none",
+    black: "black",
+    white: "white",
+    margin_auto: "auto",
+    nice: "background",
+    quality: "quality",
+    compression: "compression",
+    png_fast: "Fast",
+    png_default: "Default",
+    png_best: "Best",
+    brightness: "brightness",
+    contrast: "contrast",
+    gamma: "gamma",
+    start: "start ⏵",
+    load_preview: "load preview",
+    prev: "◀ prev",
+    random: "random",
+    next: "next ▶",
+    page_of: |page, total| format!("page {page} of {total}"),
+    pages: "pages",
+    left_out: "left out",
+    loading: "loading...",
+    select_device: "select device",
+    confirm: "confirm",
+    cancel: "cancel",
+    series: "series",
+    series_name: "name",
+    series_index: "volume",
+    next_field: "next field",
+
+    navigate: "navigate",
+    toggle: "toggle",
+    all: "all",
+    help: "help",
+    theme: "theme",
+    quit: "quit",
+    adjust: "adjust",
+    fine_adjust: "fine adjust",
+    select: "select",
+    reorder: "reorder",
+    details: "details",
+    retry: "retry",
+    retry_verbose: "retry with verbose log",
+    add: "add",
+    menu: "menu",
+    scroll: "scroll",
+    close: "close",
+    queue: "queue",
+
+    themes: "themes",
+    history: "history",
+    no_history: "no conversions yet",
+    run_again: "run again",
+    retry_failed: "retry failed",
+    no_failures: "the last batch has no failed comics left",
+    missing_input: "the file isn't there anymore",
+    ago: |age| format!("{age} ago"),
+    apply: "apply",
+
+    compare: "compare settings",
+    export_preview: "export page",
+    exported_as: |name| format!("exported as {name}"),
+    page_analysis: |stats| {
+        let (width, height) = stats.dimensions;
+        let mut line = format!("{width}x{height}");
+        if stats.is_color {
+            line.push_str(", color");
+        }
+        if stats.is_spread {
+            line.push_str(", spread");
+        }
+        match stats.margins {
+            Some(m) => line.push_str(&format!(
+                ", crops {}/{}/{}/{}",
+                m.top, m.right, m.bottom, m.left
+            )),
+            None => line.push_str(", no margins"),
+        }
+        format!(
+            "{line}, median {}, detail {:.2}",
+            stats.median_level(),
+            stats.complexity
+        )
+    },
+    auto_tune: "auto-tune",
+    tuning: "tuning...",
+    variant_current: "current",
+    variant_lighter: "lighter",
+    variant_darker: "darker",
+    variant_neutral: "neutral",
+
+    resume_batch: "resume interrupted batch",
+    already_converted: "already converted",
+    interrupted: "interrupted",
+    resume: "skip converted",
+    start_over: "convert all again",
+
+    series_lookup: "look up series",
+    searching: "searching...",
+    no_matches: "no matches",
+    skip_lookup: "none of these",
+    by: "by",
+
+    help_title: "help",
+    keybindings: "keybindings",
+    to_close: "to close",
+    help_entries: [
+        (
+            "navigate files",
+            "move up and down through the file list in the left pane. arrow keys or vim-style navigation both work",
+        ),
+        (
+            "toggle file selection",
+            "select or deselect the current focused file in the left pane. selected files show [✓] and will be processed when you press enter",
+        ),
+        (
+            "toggle all files",
+            "select or deselect all files at once. if all files are currently selected, this deselects all. otherwise selects all",
+        ),
+        (
+            "start processing",
+            "begin converting all selected files with current settings. files are saved to the output directory with the chosen format",
+        ),
+        (
+            "reading direction",
+            "cycle through reading modes:\n\n• right to left: manga style\n• left to right: standard western comics\n• auto: detect each comic from its ComicInfo.xml, japanese file names or color pages, keeping the previous mode when unsure\n\naffects page order in output files",
+        ),
+        (
+            "spread splitter",
+            "cycle through double-page handling:\n\n• none: keep spreads as-is\n• split: cut spreads into separate pages\n• rotate: rotate spreads 90° for vertical viewing\n• rotate & split: show twice - rotated then split",
+        ),
+        (
+            "auto crop",
+            "toggle automatic margin removal. when enabled, detects and removes blank space around page content for better screen fit",
+        ),
+        (
+            "fixed crop",
+            "cycle through the regions always cut from every page, before auto crop: off, 40, 60, 80 or 120 pixels at the bottom for footer banners and page numbers, 40 or 60 at the top for headers, 40 at the top and bottom, or 40 on the sides for the binding's shadow. other regions are set with fixed_crop in the config file, or for a whole directory in its .comically.json",
+        ),
+        (
+            "file crop",
+            "cycle the fixed crop of the focused file only: no crop, then the presets, then back to the crop of the batch, or of its directory's .comically.json. shown next to the file",
+        ),
+        (
+            "series",
+            "type the series and volume of the focused file, in place of the ones from its ComicInfo.xml or file name. written to the epub and mobi so e-readers and calibre group the volumes. an empty name goes back to the file's own. shown next to the file",
+        ),
+        (
+            "output format",
+            "cycle through output formats:\n\n• azw3/mobi: amazon kindle format\n• epub: standard e-book format\n• cbz: comic book archive (zip)\n\nnote: mobi forces jpeg image format",
+        ),
+        (
+            "file output format",
+            "cycle the output format of the focused file only, e.g. cbz for an ongoing series in a batch of mobis. the format is shown next to the file, and goes back to the batch's after the last one",
+        ),
+        (
+            "image format",
+            "cycle compression formats:\n\n• jpeg: lossy, smaller files\n• png: lossless, larger files\n• webp: modern, good compression\n\ndisabled for mobi output",
+        ),
+        (
+            "quality/compression",
+            "select quality setting for adjustment\n\n• jpeg/webp: quality 0-100\n• png: fast/default/best compression\n\nuse the adjust keys to change the value",
+        ),
+        (
+            "brightness",
+            "select brightness for adjustment\n\nrange: -100 to +100\n• negative values: darker image\n• positive values: brighter image\n\nuse the adjust keys to change it",
+        ),
+        (
+            "contrast",
+            "select contrast for adjustment\n\nrange: -100 to +100, an s-curve around mid-gray\n• negative values: flatter midtones\n• positive values: deeper shadows, brighter highlights\n\nunlike gamma, black and white stay where they are. use the adjust keys to change it",
+        ),
+        (
+            "gamma",
+            "select gamma correction for adjustment\n\nrange: 0.1 to 3.0\n• < 1.0: lower contrast, lifted shadows\n• > 1.0: higher contrast, deeper blacks\n• = 1.0: no adjustment\n\nuse the adjust keys to change it. press gamma again to switch between a power curve and the same curve on linear light (sRGB). a LUT measured for a device is set with `gamma_curve` in the config file",
+        ),
+        (
+            "adjust values",
+            "decrease/increase selected setting (quality, brightness, contrast or gamma)\n\nhold shift for fine adjustments:\n• quality: ±1 instead of ±5\n• brightness and contrast: ±1 instead of ±5\n• gamma: ±0.05 instead of ±0.1\n\nwith the mouse, click or drag the slider under a value to jump straight to it",
+        ),
+        (
+            "device presets",
+            "open device selector to choose from common e-reader presets. automatically sets optimal dimensions for your target device",
+        ),
+        (
+            "margin color",
+            "cycle margin fill when image doesn't fill screen:\n\n• none: preserve original aspect ratio\n• black: fill empty space with black\n• white: fill empty space with white\n• auto: match the color of the page edges\n\nother gray levels and #rrggbb colors can be set in the config file",
+        ),
+        (
+            "background",
+            "convert in the background, to keep using the computer during long batches: at a lower priority, on half the cores and pausing between pages, one comic at a time. slower, remembered for the next batches",
+        ),
+        (
+            "load preview",
+            "load preview of selected file with current settings applied. updates when settings change. useful for testing before batch processing",
+        ),
+        (
+            "compare settings",
+            "show the previewed page with the current, a lighter, a darker and an uncorrected gamma/brightness side by side. pick one with left/right and press enter to apply it",
+        ),
+        (
+            "export page",
+            "write the previewed page to the previews folder of the output directory, as it's encoded in the output and at the device's resolution. open it in an image viewer, or attach it when reporting how a page converts",
+        ),
+        (
+            "page analysis",
+            "show the previewed page as it is in the archive, with the margins auto-crop removes shaded and outlined, and its luminance histogram from black to white under it. the line above the histogram says whether it's a color page or a spread, its median gray level and how detailed it is. press again for the converted page",
+        ),
+        (
+            "auto-tune",
+            "suggest brightness, contrast and gamma for the selected file from the tones of about 10 of its pages, starting from the device's defaults and correcting for dark or faded midtones, gray paper and washed out ink. the suggestion replaces the current values, which the adjust keys still fine-tune",
+        ),
+        (
+            "look up series",
+            "search AniList (or the `metadata_sources` default) for the series of the focused file. the match you confirm fills in the authors and description of every volume of that series",
+        ),
+        (
+            "first/last page",
+            "convert the previewed file from or up to the previewed page, e.g. to leave out scanlation credits or to try settings on part of a large volume. marking the same page again converts from the start or to the end again\n\nthe pages of each file show next to it in the file list",
+        ),
+        (
+            "history",
+            "list past conversions with their outputs or why they failed. enter converts the selected file again with the settings it was converted with, without changing the current ones. retry failed converts only the comics the last batch failed on, with the settings of that batch",
+        ),
+        (
+            "toggle help",
+            "show or hide this help menu. press help or cancel again to close",
+        ),
+        (
+            "toggle theme",
+            "switch between light and dark color themes",
+        ),
+        (
+            "choose theme",
+            "pick a built-in theme or one of the custom palettes from the \"themes\" section of the config file. the config file is re-read every time the picker opens, so edits show up without restarting",
+        ),
+        (
+            "quit",
+            "exit the application. any unsaved settings will be lost",
+        ),
+        (
+            "cancel/close",
+            "context sensitive:\n• close modal dialogs\n• deselect adjustment fields\n• cancel current operation",
+        ),
+    ],
+
+    progress: "progress",
+    status: "status",
+    total: "total",
+    waiting: "waiting",
+    extracting: "extracting",
+    add_to_queue: "add to queue",
+    done: "done",
+    open_output_dir: "open output folder",
+    reveal_file: "reveal selected file",
+    copy_to_device: "copy to connected device",
+    convert_more: "convert more",
+    no_device: "no e-reader found, is it mounted?",
+    copying_to: "copying to",
+    copied_files: |count, device| format!("copied {count} files to {device}"),
+    copy_failed: "copy failed",
+    move_to_converted: "move sources to converted/",
+    move_to_trash: "move sources to trash",
+    confirm_move: |count, key| format!("move {count} converted sources? press {key} again"),
+    moved_sources: |count| format!("moved {count} sources"),
+    move_failed: "move failed",
+    pages_per_sec: "pages/s",
+    time_left: |time| format!("{time} left"),
+    failed_during: "failed during",
+    open_archive: "open archive",
+    caused_by: "caused by",
+    verbose_log: "verbose log",
+    kindlegen_output: "kindlegen output",
+};
+
+pub static JA: Strings = Strings {
+    files: "ファイル",
+    settings: "設定",
+    preview: "プレビュー",
+    device: "端末",
+    reading_direction: "読む方向",
+    right_to_left: "右から左 (漫画)",
+    left_to_right: "左から右",
+    detect_direction: "自動 (判定)",
+    spread_splitter: "見開き",
+    split_none: "なし",
+    split: "分割",
+    rotate: "回転",
+    split_and_rotate: "分割と回転",
+    auto_crop: "自動トリミング",
+    crop_footer: "固定トリミング",
+    no_crop: "トリミングなし",
+    yes: "はい",
+    no: "いいえ",
+    output_format: "出力形式",
+    image_format: "画像形式",
+    margin_color: "余白の色",
+    margin_none: "なし",
+    black: "黒",
+    white: "白",
+    margin_auto: "自動",
+    nice: "バックグラウンド",
+    quality: "画質",
+    compression: "圧縮",
+    png_fast: "高速",
+    png_default: "標準",
+    png_best: "最大",
+    brightness: "明るさ",
+    contrast: "コントラスト",
+    gamma: "ガンマ",
+    start: "開始 ⏵",
+    load_preview: "プレビューを読み込む",
+    prev: "◀ 前へ",
+    random: "ランダム",
+    next: "次へ ▶",
+    page_of: |page, total| format!("{total}ページ中{page}ページ"),
+    pages: "ページ",
+    left_out: "変換しない",
+    loading: "読み込み中...",
+    select_device: "端末を選択",
+    confirm: "決定",
+    cancel: "キャンセル",
+    series: "シリーズ",
+    series_name: "名前",
+    series_index: "巻数",
+    next_field: "次の欄",
+
+    navigate: "移動",
+    toggle: "選択",
+    all: "すべて",
+    help: "ヘルプ",
+    theme: "テーマ",
+    quit: "終了",
+    adjust: "調整",
+    fine_adjust: "微調整",
+    select: "選択",
+    reorder: "並べ替え",
+    details: "詳細",
+    retry: "再試行",
+    retry_verbose: "詳細ログ付きで再試行",
+    add: "追加",
+    menu: "メニュー",
+    scroll: "スクロール",
+    close: "閉じる",
+    queue: "キューに追加",
+
+    themes: "テーマ",
+    history: "変換履歴",
+    no_history: "まだ変換していません",
+    run_again: "もう一度変換",
+    retry_failed: "失敗分を再変換",
+    no_failures: "前回のバッチに失敗したコミックはありません",
+    missing_input: "ファイルが見つかりません",
+    ago: |age| format!("{age}前"),
+    apply: "適用",
+
+    compare: "設定の比較",
+    export_preview: "ページの書き出し",
+    exported_as: |name| format!("{name}に書き出し済み"),
+    page_analysis: |stats| {
+        let (width, height) = stats.dimensions;
+        let mut line = format!("{width}x{height}");
+        if stats.is_color {
+            line.push_str("、カラー");
+        }
+        if stats.is_spread {
+            line.push_str("、見開き");
+        }
+        match stats.margins {
+            Some(m) => line.push_str(&format!(
+                "、余白 {}/{}/{}/{}",
+                m.top, m.right, m.bottom, m.left
+            )),
+            None => line.push_str("、余白なし"),
+        }
+        format!(
+            "{line}、中央値 {}、細かさ {:.2}",
+            stats.median_level(),
+            stats.complexity
+        )
+    },
+    auto_tune: "自動調整",
+    tuning: "調整中...",
+    variant_current: "現在",
+    variant_lighter: "明るめ",
+    variant_darker: "暗め",
+    variant_neutral: "補正なし",
+
+    resume_batch: "中断したバッチの再開",
+    already_converted: "変換済み",
+    interrupted: "中断",
+    resume: "変換済みをスキップ",
+    start_over: "すべて再変換",
+
+    series_lookup: "シリーズの検索",
+    searching: "検索中...",
+    no_matches: "見つかりませんでした",
+    skip_lookup: "該当なし",
+    by: "作者",
+
+    help_title: "ヘルプ",
+    keybindings: "キー操作",
+    to_close: "で閉じる",
+    help_entries: [
+        (
+            "ファイル間の移動",
+            "左側のファイル一覧を上下に移動します。矢印キーとvim風のキーのどちらも使えます",
+        ),
+        (
+            "ファイルの選択切り替え",
+            "左側で選択中のファイルを選択または解除します。選択したファイルには[✓]が付き、開始すると変換されます",
+        ),
+        (
+            "すべて選択切り替え",
+            "すべてのファイルをまとめて選択または解除します。すべて選択済みなら解除し、そうでなければすべて選択します",
+        ),
+        (
+            "変換を開始",
+            "選択したすべてのファイルを現在の設定で変換します。ファイルは選んだ形式で出力先に保存されます",
+        ),
+        (
+            "読む方向",
+            "読む方向を切り替えます:\n\n• 右から左: 漫画\n• 左から右: 一般的な欧米のコミック\n• 自動: ComicInfo.xml、日本語のファイル名、カラーページから作品ごとに判定します。判定できない場合は直前の方向を使います\n\n出力ファイルのページ順に影響します",
+        ),
+        (
+            "見開き",
+            "見開きページの扱いを切り替えます:\n\n• なし: 見開きのまま\n• 分割: 見開きを別々のページに分ける\n• 回転: 縦向きで読めるよう90°回転する\n• 分割と回転: 回転したものと分割したものを両方入れる",
+        ),
+        (
+            "自動トリミング",
+            "余白の自動削除を切り替えます。有効にすると、ページの周りの空白を検出して削除し、画面に合わせやすくします",
+        ),
+        (
+            "固定トリミング",
+            "すべてのページから自動トリミングの前に必ず削除する範囲を切り替えます: なし、帯やページ番号のための下端40、60、80、120ピクセル、見出しのための上端40、60、上下40、綴じ目の影のための左右40。ほかの範囲は設定ファイルのfixed_crop、ディレクトリごとにはその.comically.jsonで指定します",
+        ),
+        (
+            "ファイルごとのトリミング",
+            "選択中のファイルだけの固定トリミングを切り替えます: トリミングなし、各プリセット、そして全体の設定(またはディレクトリの.comically.json)に戻ります。ファイル名の横に表示されます",
+        ),
+        (
+            "シリーズ",
+            "選択中のファイルのシリーズ名と巻数を入力します。ComicInfo.xmlやファイル名から読み取ったものの代わりに使われ、epubとmobiに書き込まれるので電子書籍リーダーやcalibreで巻がまとめられます。名前を空にするとファイル自身のものに戻ります。ファイル名の横に表示されます",
+        ),
+        (
+            "出力形式",
+            "出力形式を切り替えます:\n\n• azw3/mobi: amazon kindle形式\n• epub: 標準的な電子書籍形式\n• cbz: コミックアーカイブ (zip)\n\n注意: mobiでは画像はjpegになります",
+        ),
+        (
+            "ファイルごとの出力形式",
+            "選択中のファイルだけの出力形式を切り替えます。mobiでまとめて変換するなかで連載中のシリーズだけcbzにする、といった使い方ができます。形式はファイル名の横に表示され、最後の形式の次は全体の設定に戻ります",
+        ),
+        (
+            "画像形式",
+            "圧縮形式を切り替えます:\n\n• jpeg: 非可逆、ファイルが小さい\n• png: 可逆、ファイルが大きい\n• webp: 新しく、圧縮率が高い\n\nmobi出力では使えません",
+        ),
+        (
+            "画質/圧縮",
+            "画質の設定を調整対象にします\n\n• jpeg/webp: 画質 0-100\n• png: 高速/標準/最大の圧縮\n\n調整キーで値を変更します",
+        ),
+        (
+            "明るさ",
+            "明るさを調整対象にします\n\n範囲: -100 から +100\n• 負の値: 暗くなる\n• 正の値: 明るくなる\n\n調整キーで値を変更します",
+        ),
+        (
+            "コントラスト",
+            "コントラストを調整対象にします\n\n範囲: -100 から +100、中間のグレーを中心にしたSカーブ\n• 負の値: 中間調が平坦になる\n• 正の値: 暗部が締まり、明部が明るくなる\n\nガンマと違い、黒と白は変わりません。調整キーで値を変更します",
+        ),
+        (
+            "ガンマ",
+            "ガンマ補正を調整対象にします\n\n範囲: 0.1 から 3.0\n• 1.0未満: コントラストが下がり、暗部が持ち上がる\n• 1.0超: コントラストが上がり、黒が締まる\n• 1.0: 補正なし\n\n調整キーで値を変更します。もう一度ガンマを押すと、べき乗カーブと、同じカーブをリニア光に適用するもの(sRGB)を切り替えます。デバイスに合わせて測定したLUTは設定ファイルの`gamma_curve`で指定します",
+        ),
+        (
+            "値の調整",
+            "選択中の設定 (画質、明るさ、コントラスト、ガンマ) を増減します\n\nshiftを押しながらで微調整:\n• 画質: ±5ではなく±1\n• 明るさとコントラスト: ±5ではなく±1\n• ガンマ: ±0.1ではなく±0.05\n\nマウスでは、値の下のスライダーをクリックまたはドラッグして直接設定できます",
+        ),
+        (
+            "端末プリセット",
+            "端末の選択画面を開き、一般的な電子書籍リーダーのプリセットから選びます。端末に最適なサイズが自動で設定されます",
+        ),
+        (
+            "余白の色",
+            "画像が画面を埋めないときの余白を切り替えます:\n\n• なし: 元の縦横比を保つ\n• 黒: 空いた部分を黒で埋める\n• 白: 空いた部分を白で埋める\n• 自動: ページの端の色に合わせる\n\nその他の灰色や #rrggbb の色は設定ファイルで指定できます",
+        ),
+        (
+            "バックグラウンド",
+            "長い変換の間もパソコンを使えるよう、バックグラウンドで変換します: 低い優先度で、半分のコアを使い、ページの間に少し休み、一冊ずつ変換します。遅くなります。次の変換でも使われます",
+        ),
+        (
+            "プレビューを読み込む",
+            "選択したファイルを現在の設定でプレビューします。設定を変えると更新されます。まとめて変換する前の確認に便利です",
+        ),
+        (
+            "設定の比較",
+            "プレビュー中のページを現在・明るめ・暗め・補正なしのガンマ/明るさで並べて表示します。左右で選び、Enterで適用します",
+        ),
+        (
+            "ページの書き出し",
+            "プレビュー中のページを、出力先のpreviewsフォルダに書き出します。出力と同じ形式・端末の解像度で保存されるので、画像ビューアで確認したり、変換の不具合を報告するときに添付したりできます",
+        ),
+        (
+            "ページの分析",
+            "プレビュー中のページをアーカイブ内の元の状態で表示し、自動トリミングで取り除かれる余白を暗くして枠で囲みます。下には黒から白までの輝度ヒストグラムを表示し、その上の行にカラーページか見開きか、明るさの中央値、絵の細かさを示します。もう一度押すと変換後のページに戻ります",
+        ),
+        (
+            "自動調整",
+            "選択中のファイルのおよそ10ページの階調から、明るさ・コントラスト・ガンマを提案します。端末の既定値を基準に、暗すぎる・薄すぎる中間調、灰色の紙、かすれたインクを補正します。提案は現在の値を置き換え、調整キーで微調整できます",
+        ),
+        (
+            "シリーズの検索",
+            "選択中のファイルのシリーズをAniList(または `metadata_sources` の既定)で検索します。確定した結果の作者と説明が、そのシリーズのすべての巻に書き込まれます",
+        ),
+        (
+            "最初/最後のページ",
+            "プレビュー中のファイルを、プレビュー中のページから、またはそのページまで変換します。スキャンレーションのクレジットを除いたり、大きな巻の一部で設定を試したりするときに使います。同じページをもう一度指定すると、先頭から、または末尾まで変換する状態に戻ります\n\n各ファイルの変換するページはファイル一覧に表示されます",
+        ),
+        (
+            "変換履歴",
+            "過去の変換と、その出力ファイルまたは失敗した理由を一覧にします。Enterで選択したファイルを当時の設定でもう一度変換します。現在の設定は変わりません。失敗分の再変換では、前回のバッチで失敗したコミックだけをそのバッチの設定で変換し直します",
+        ),
+        (
+            "ヘルプの表示切り替え",
+            "このヘルプを表示または非表示にします。もう一度ヘルプかキャンセルを押すと閉じます",
+        ),
+        (
+            "テーマの切り替え",
+            "ライトテーマとダークテーマを切り替えます",
+        ),
+        (
+            "テーマの選択",
+            "組み込みのテーマか、設定ファイルの\"themes\"にあるカスタムパレットを選びます。選択画面を開くたびに設定ファイルを読み直すので、再起動せずに変更が反映されます",
+        ),
+        (
+            "終了",
+            "アプリケーションを終了します。保存していない設定は失われます",
+        ),
+        (
+            "キャンセル/閉じる",
+            "状況に応じて:\n• ダイアログを閉じる\n• 調整中の項目の選択を解除する\n• 現在の操作を取り消す",
+        ),
+    ],
+
+    progress: "進行状況",
+    status: "状態",
+    total: "合計",
+    waiting: "待機中",
+    extracting: "展開中",
+    add_to_queue: "キューに追加",
+    done: "完了",
+    open_output_dir: "出力フォルダを開く",
+    reveal_file: "選択したファイルを表示",
+    copy_to_device: "接続した端末にコピー",
+    convert_more: "続けて変換",
+    no_device: "電子書籍リーダーが見つかりません。マウントされていますか?",
+    copying_to: "コピー中:",
+    copied_files: |count, device| format!("{count}個のファイルを{device}にコピーしました"),
+    copy_failed: "コピーに失敗しました",
+    move_to_converted: "変換元をconverted/に移動",
+    move_to_trash: "変換元をゴミ箱に移動",
+    confirm_move: |count, key| format!("変換元の{count}個のファイルを移動しますか?もう一度{key}で確定"),
+    moved_sources: |count| format!("{count}個の変換元を移動しました"),
+    move_failed: "移動に失敗しました",
+    pages_per_sec: "ページ/秒",
+    time_left: |time| format!("残り{time}"),
+    failed_during: "失敗した段階",
+    open_archive: "アーカイブを開く",
+    caused_by: "原因",
+    verbose_log: "詳細ログ",
+    kindlegen_output: "kindlegenの出力",
+};
@@ -0,0 +1,441 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Everything that can be triggered from the keyboard and remapped in the config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    Up,
+    Down,
+    ToggleFile,
+    ToggleAll,
+    Start,
+    ReadingDirection,
+    Split,
+    AutoCrop,
+    CropFooter,
+    FileCrop,
+    Series,
+    OutputFormat,
+    FileFormat,
+    ImageFormat,
+    Quality,
+    Brightness,
+    Contrast,
+    Gamma,
+    Decrease,
+    Increase,
+    DeviceSelector,
+    MarginColor,
+    Nice,
+    Preview,
+    Compare,
+    ExportPreview,
+    Analysis,
+    AutoTune,
+    FetchMetadata,
+    FirstPage,
+    LastPage,
+    History,
+    RetryFailed,
+    Help,
+    Theme,
+    ThemePicker,
+    Quit,
+    Cancel,
+}
+
+impl Action {
+    pub const ALL: [Action; 38] = [
+        Action::Up,
+        Action::Down,
+        Action::ToggleFile,
+        Action::ToggleAll,
+        Action::Start,
+        Action::ReadingDirection,
+        Action::Split,
+        Action::AutoCrop,
+        Action::CropFooter,
+        Action::FileCrop,
+        Action::Series,
+        Action::OutputFormat,
+        Action::FileFormat,
+        Action::ImageFormat,
+        Action::Quality,
+        Action::Brightness,
+        Action::Contrast,
+        Action::Gamma,
+        Action::Decrease,
+        Action::Increase,
+        Action::DeviceSelector,
+        Action::MarginColor,
+        Action::Nice,
+        Action::Preview,
+        Action::Compare,
+        Action::ExportPreview,
+        Action::Analysis,
+        Action::AutoTune,
+        Action::FetchMetadata,
+        Action::FirstPage,
+        Action::LastPage,
+        Action::History,
+        Action::RetryFailed,
+        Action::Help,
+        Action::Theme,
+        Action::ThemePicker,
+        Action::Quit,
+        Action::Cancel,
+    ];
+
+    /// Name used in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::ToggleFile => "toggle_file",
+            Action::ToggleAll => "toggle_all",
+            Action::Start => "start",
+            Action::ReadingDirection => "reading_direction",
+            Action::Split => "split",
+            Action::AutoCrop => "auto_crop",
+            Action::CropFooter => "crop_footer",
+            Action::FileCrop => "file_crop",
+            Action::Series => "series",
+            Action::OutputFormat => "output_format",
+            Action::FileFormat => "file_format",
+            Action::ImageFormat => "image_format",
+            Action::Quality => "quality",
+            Action::Brightness => "brightness",
+            Action::Contrast => "contrast",
+            Action::Gamma => "gamma",
+            Action::Decrease => "decrease",
+            Action::Increase => "increase",
+            Action::DeviceSelector => "device_selector",
+            Action::MarginColor => "margin_color",
+            Action::Nice => "nice",
+            Action::Preview => "preview",
+            Action::Compare => "compare",
+            Action::ExportPreview => "export_preview",
+            Action::Analysis => "analysis",
+            Action::AutoTune => "auto_tune",
+            Action::FetchMetadata => "fetch_metadata",
+            Action::FirstPage => "first_page",
+            Action::LastPage => "last_page",
+            Action::History => "history",
+            Action::RetryFailed => "retry_failed",
+            Action::Help => "help",
+            Action::Theme => "theme",
+            Action::ThemePicker => "theme_picker",
+            Action::Quit => "quit",
+            Action::Cancel => "cancel",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Up => &["up", "k"],
+            Action::Down => &["down", "j"],
+            Action::ToggleFile => &["space"],
+            Action::ToggleAll => &["a"],
+            Action::Start => &["enter"],
+            Action::ReadingDirection => &["m"],
+            Action::Split => &["s"],
+            Action::AutoCrop => &["c"],
+            Action::CropFooter => &["e"],
+            Action::FileCrop => &["E"],
+            Action::Series => &["S"],
+            Action::OutputFormat => &["f"],
+            Action::FileFormat => &["F"],
+            Action::ImageFormat => &["i"],
+            Action::Quality => &["u"],
+            Action::Brightness => &["b"],
+            Action::Contrast => &["C"],
+            Action::Gamma => &["g"],
+            Action::Decrease => &["left"],
+            Action::Increase => &["right"],
+            Action::DeviceSelector => &["d"],
+            Action::MarginColor => &["o"],
+            Action::Nice => &["n"],
+            Action::Preview => &["p"],
+            Action::Compare => &["v"],
+            Action::ExportPreview => &["w"],
+            Action::Analysis => &["z"],
+            Action::AutoTune => &["A"],
+            Action::FetchMetadata => &["l"],
+            Action::FirstPage => &["["],
+            Action::LastPage => &["]"],
+            Action::History => &["H"],
+            Action::RetryFailed => &["r"],
+            Action::Help => &["h"],
+            Action::Theme => &["t"],
+            Action::ThemePicker => &["T"],
+            Action::Quit => &["q"],
+            Action::Cancel => &["esc"],
+        }
+    }
+}
+
+/// A single key, optionally with ctrl/alt held.
+///
+/// Shift is never part of a binding: uppercase characters already encode it, and
+/// shift on the adjust keys switches to fine adjustments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const BINDING_MODIFIERS: KeyModifiers = KeyModifiers::CONTROL.union(KeyModifiers::ALT);
+
+impl KeyBinding {
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        self.code == key.code && self.modifiers == key.modifiers & BINDING_MODIFIERS
+    }
+
+    /// Short form shown in hints and the help popup
+    pub fn label(&self) -> String {
+        let key = match self.code {
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            _ => return self.to_string(),
+        };
+        self.with_modifiers(key)
+    }
+
+    fn with_modifiers(&self, key: String) -> String {
+        let mut out = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            out.push_str("ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            out.push_str("alt+");
+        }
+        out.push_str(&key);
+        out
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key = match self.code {
+            KeyCode::Char(' ') => "space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Up => "up".to_string(),
+            KeyCode::Down => "down".to_string(),
+            KeyCode::Left => "left".to_string(),
+            KeyCode::Right => "right".to_string(),
+            KeyCode::Enter => "enter".to_string(),
+            KeyCode::Esc => "esc".to_string(),
+            KeyCode::Tab => "tab".to_string(),
+            KeyCode::Backspace => "backspace".to_string(),
+            KeyCode::Home => "home".to_string(),
+            KeyCode::End => "end".to_string(),
+            KeyCode::PageUp => "pageup".to_string(),
+            KeyCode::PageDown => "pagedown".to_string(),
+            KeyCode::F(n) => format!("f{n}"),
+            _ => "?".to_string(),
+        };
+        f.write_str(&self.with_modifiers(key))
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseKeyError(String);
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Invalid key binding: ")?;
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseKeyError {}
+
+impl FromStr for KeyBinding {
+    type Err = ParseKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(r) = rest.strip_prefix("ctrl+") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("alt+") {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                other => match other.strip_prefix('f').map(u8::from_str) {
+                    Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(ParseKeyError(s.to_string())),
+                },
+            },
+        };
+
+        Ok(KeyBinding { code, modifiers })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Key bindings for every [`Action`].
+///
+/// Stored in the config file as `{ "action": "key" }` or `{ "action": ["key", ...] }`.
+/// Actions missing from the file keep their defaults, and invalid entries are skipped
+/// with a warning instead of discarding the whole config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<String, Keys>",
+    into = "BTreeMap<String, Vec<String>>"
+)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .map(|key| key.parse().expect("default key bindings are valid"))
+                    .collect();
+                (action, keys)
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl From<BTreeMap<String, Keys>> for Keymap {
+    fn from(overrides: BTreeMap<String, Keys>) -> Self {
+        let mut keymap = Keymap::default();
+
+        for (name, keys) in overrides {
+            let Some(action) = Action::from_name(&name) else {
+                log::warn!("unknown action in keymap: {name}");
+                continue;
+            };
+
+            let keys = match keys {
+                Keys::One(key) => vec![key],
+                Keys::Many(keys) => keys,
+            };
+
+            let bindings: Vec<KeyBinding> = keys
+                .iter()
+                .filter_map(|key| {
+                    key.parse()
+                        .inspect_err(|e| log::warn!("keymap entry {name}: {e}"))
+                        .ok()
+                })
+                .collect();
+
+            if !bindings.is_empty() {
+                keymap.bindings.insert(action, bindings);
+            }
+        }
+
+        keymap
+    }
+}
+
+impl From<Keymap> for BTreeMap<String, Vec<String>> {
+    fn from(keymap: Keymap) -> Self {
+        keymap
+            .bindings
+            .into_iter()
+            .map(|(action, keys)| {
+                let keys = keys.iter().map(ToString::to_string).collect();
+                (action.name().to_string(), keys)
+            })
+            .collect()
+    }
+}
+
+impl Keymap {
+    pub fn matches(&self, action: Action, key: &KeyEvent) -> bool {
+        self.bindings
+            .get(&action)
+            .is_some_and(|keys| keys.iter().any(|binding| binding.matches(key)))
+    }
+
+    /// First action bound to the key, in [`Action::ALL`] order
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| self.matches(*action, key))
+    }
+
+    /// All keys bound to the actions, joined for display (e.g. `↑/↓/k/j`)
+    pub fn keys(&self, actions: &[Action]) -> String {
+        actions
+            .iter()
+            .filter_map(|action| self.bindings.get(action))
+            .flatten()
+            .map(KeyBinding::label)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Bracketed hint shown next to buttons, e.g. `[m]`
+    pub fn hint(&self, action: Action) -> String {
+        format!("[{}]", self.keys(&[action]))
+    }
+}
+
+#[test]
+fn keymap_overrides_merge_with_defaults() {
+    let keymap: Keymap =
+        serde_json::from_str(r#"{ "down": ["n", "ctrl+down"], "quit": "x", "bogus": "z" }"#)
+            .unwrap();
+
+    let key = |code, modifiers| KeyEvent::new(code, modifiers);
+
+    assert!(keymap.matches(Action::Down, &key(KeyCode::Char('n'), KeyModifiers::NONE)));
+    assert!(keymap.matches(Action::Down, &key(KeyCode::Down, KeyModifiers::CONTROL)));
+    assert!(!keymap.matches(Action::Down, &key(KeyCode::Char('j'), KeyModifiers::NONE)));
+    assert_eq!(
+        keymap.action(&key(KeyCode::Char('x'), KeyModifiers::NONE)),
+        Some(Action::Quit)
+    );
+    // untouched actions keep their defaults, shift doesn't get in the way
+    assert!(keymap.matches(Action::Increase, &key(KeyCode::Right, KeyModifiers::SHIFT)));
+}
@@ -1,9 +1,14 @@
 pub mod button;
 pub mod config;
 pub mod error;
+pub mod history;
+pub mod i18n;
+pub mod keymap;
 pub mod progress;
+pub mod slider;
 pub mod splash;
 pub mod theme;
+pub mod theme_picker;
 pub mod utils;
 
 use anyhow::Context;
@@ -23,21 +28,164 @@ use std::{
 };
 
 use crate::{
+    history::History,
+    settings::Settings,
     tui::{
         error::ErrorInfo,
+        history::{render_history, HistoryState},
+        keymap::Action,
         splash::{splash_title, SplashScreen},
+        theme_picker::{render_theme_picker, ThemePickerState},
     },
     Event,
 };
-use comically::{ComicFile, OutputFormat};
+use comically::{checkpoint::Checkpoint, ComicFile, OutputFormat};
 
 pub use theme::{Theme, ThemeMode};
 
 pub struct App {
     pub state: AppState,
     pub theme: Theme,
+    pub settings: Settings,
+    pub theme_picker: Option<ThemePickerState>,
+    pub history: Option<HistoryState>,
+    // config screen kept around while processing, so "convert more" keeps the file list
+    pub parked_config: Option<config::ConfigState>,
 }
 
+impl App {
+    fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        match &mut self.state {
+            AppState::Config(config_state) => {
+                config_state.theme = theme;
+            }
+            AppState::Processing(processing_state) => {
+                processing_state.theme = theme;
+            }
+        }
+    }
+
+    // palettes are re-read from disk so edits to the config file show up without a restart
+    fn open_theme_picker(&mut self) {
+        if let Some(on_disk) = Settings::load() {
+            self.settings.themes = on_disk.themes;
+        }
+
+        self.theme_picker = Some(ThemePickerState::new(
+            self.settings.themes.clone(),
+            self.settings.theme.as_deref(),
+            self.theme,
+        ));
+    }
+
+    fn handle_theme_picker_key(&mut self, key: event::KeyEvent) {
+        let Some(picker) = &mut self.theme_picker else {
+            return;
+        };
+
+        let keymap = &self.settings.keymap;
+        if keymap.matches(Action::Cancel, &key) || keymap.matches(Action::ThemePicker, &key) {
+            let original = picker.original;
+            self.theme_picker = None;
+            self.set_theme(original);
+            return;
+        }
+
+        if keymap.matches(Action::Start, &key) {
+            self.settings.theme = picker.selected_name().map(String::from);
+            self.theme_picker = None;
+            let _ = self.settings.save();
+            return;
+        }
+
+        if keymap.matches(Action::Up, &key) {
+            picker.select_previous();
+        } else if keymap.matches(Action::Down, &key) {
+            picker.select_next();
+        }
+
+        // preview the theme under the cursor
+        if let Some(theme) = picker.selected_theme() {
+            self.set_theme(theme);
+        }
+    }
+}
+
+impl App {
+    fn handle_history_key(&mut self, key: event::KeyEvent, event_tx: &mpsc::Sender<Event>) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
+
+        let keymap = &self.settings.keymap;
+        if keymap.matches(Action::Cancel, &key) || keymap.matches(Action::History, &key) {
+            self.history = None;
+        } else if keymap.matches(Action::Up, &key) {
+            history.select_previous();
+        } else if keymap.matches(Action::Down, &key) {
+            history.select_next();
+        } else if keymap.matches(Action::Start, &key) {
+            let Some(entry) = history.selected() else {
+                return;
+            };
+            if !entry.input.is_file() {
+                history.status = Some(self.settings.language().strings().missing_input.into());
+                return;
+            }
+            if let Err(e) = create_dir_all(&entry.output_dir) {
+                history.status = Some(format!("{}: {e}", entry.output_dir.display()));
+                return;
+            }
+
+            let _ = event_tx.send(Event::StartProcessing {
+                files: vec![ComicFile::new(entry.input.clone())],
+                config: Box::new(entry.config.clone()),
+                output_dir: entry.output_dir.clone(),
+                resume: None,
+                series: Default::default(),
+                pages: Default::default(),
+                overrides: Default::default(),
+                nice: self.settings.nice,
+                save_settings: false,
+            });
+            self.history = None;
+        } else if keymap.matches(Action::RetryFailed, &key) {
+            // the journal of the latest batch lists what it failed on and its settings
+            let failures = history
+                .entries
+                .first()
+                .and_then(|entry| Checkpoint::failures(&entry.output_dir))
+                .map(|mut failures| {
+                    failures.inputs.retain(|input| input.is_file());
+                    failures
+                })
+                .filter(|failures| !failures.inputs.is_empty());
+            let Some((target, inputs)) = failures
+                .and_then(|failures| Some((failures.targets.into_iter().next()?, failures.inputs)))
+            else {
+                history.status = Some(self.settings.language().strings().no_failures.into());
+                return;
+            };
+
+            let (series, pages, overrides) = History::load().choices(&inputs, &target.config);
+            let _ = event_tx.send(Event::StartProcessing {
+                files: inputs.into_iter().map(ComicFile::new).collect(),
+                config: Box::new(target.config),
+                output_dir: target.output_dir,
+                resume: None,
+                series,
+                pages,
+                overrides,
+                nice: self.settings.nice,
+                save_settings: false,
+            });
+            self.history = None;
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum AppState {
     Config(config::ConfigState),
     Processing(progress::ProgressState),
@@ -58,6 +206,14 @@ pub fn run(
         input_dir.unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
     let output_dir = output_dir.unwrap_or_else(|| input_dir.join("comically"));
 
+    let settings = Settings::load().unwrap_or_default();
+    if let Some(name) = &settings.theme {
+        match theme.by_name(name, &settings.themes) {
+            Some(saved) => theme = saved,
+            None => log::warn!("unknown theme in config: {name}"),
+        }
+    }
+
     let files = match init(&input_dir, &output_dir) {
         Ok(files) => files,
         Err(e) => {
@@ -86,6 +242,7 @@ pub fn run(
         &mut event_rx,
         picker,
         theme,
+        settings,
     ) {
         Ok(()) => {}
         Err(e) => {
@@ -197,10 +354,10 @@ fn run_fatal_error(
 ) -> anyhow::Result<()> {
     while let Ok(event) = event_rx.recv() {
         match event {
-            Event::Key(key) => {
-                if key.code == event::KeyCode::Char('q') || key.code == event::KeyCode::Esc {
-                    return Ok(());
-                }
+            Event::Key(key)
+                if key.code == event::KeyCode::Char('q') || key.code == event::KeyCode::Esc =>
+            {
+                return Ok(());
             }
             Event::Resize(_) => {
                 terminal.autoresize()?;
@@ -215,6 +372,7 @@ fn run_fatal_error(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_main(
     manga_files: Vec<ComicFile>,
     output_dir: PathBuf,
@@ -223,12 +381,24 @@ fn run_main(
     event_rx: &mut mpsc::Receiver<Event>,
     picker: ratatui_image::picker::Picker,
     theme: Theme,
+    settings: Settings,
 ) -> Result<(), ErrorInfo> {
-    let state = config::ConfigState::new(event_tx.clone(), picker, manga_files, theme, output_dir);
+    let state = config::ConfigState::new(
+        event_tx.clone(),
+        picker,
+        manga_files,
+        theme,
+        output_dir,
+        &settings,
+    );
 
     let mut app = App {
         state: AppState::Config(state),
         theme,
+        settings,
+        theme_picker: None,
+        history: None,
+        parked_config: None,
     };
     let mut pending_events = Vec::new();
 
@@ -262,6 +432,28 @@ fn run_main(
                         }
                     }
 
+                    if let Some(history) = &mut app.history {
+                        let strings = app.settings.language().strings();
+                        render_history(
+                            frame.area(),
+                            frame.buffer_mut(),
+                            &app.theme,
+                            strings,
+                            history,
+                        );
+                    }
+
+                    if let Some(picker) = &mut app.theme_picker {
+                        let strings = app.settings.language().strings();
+                        render_theme_picker(
+                            frame.area(),
+                            frame.buffer_mut(),
+                            &app.theme,
+                            strings,
+                            picker,
+                        );
+                    }
+
                     let render_time = render_start.elapsed();
 
                     if render_time > std::time::Duration::from_millis(50) {
@@ -289,6 +481,18 @@ fn process_events(
 ) -> Result<bool, ErrorInfo> {
     for event in pending_events.drain(..) {
         match event {
+            Event::Mouse(mouse) if app.theme_picker.is_some() => {
+                if let Some(picker) = &mut app.theme_picker {
+                    match mouse.kind {
+                        event::MouseEventKind::ScrollUp => picker.select_previous(),
+                        event::MouseEventKind::ScrollDown => picker.select_next(),
+                        _ => {}
+                    }
+                }
+                if let Some(theme) = app.theme_picker.as_ref().and_then(|p| p.selected_theme()) {
+                    app.set_theme(theme);
+                }
+            }
             Event::Mouse(mouse) => match &mut app.state {
                 AppState::Config(c) => {
                     c.handle_mouse(mouse);
@@ -298,20 +502,44 @@ fn process_events(
                 }
             },
             Event::Key(key) => {
-                if key.code == event::KeyCode::Char('q') {
+                if app.theme_picker.is_some() {
+                    app.handle_theme_picker_key(key);
+                    continue;
+                }
+                if app.history.is_some() {
+                    app.handle_history_key(key, event_tx);
+                    continue;
+                }
+                // text fields take every key, even the global ones
+                if let AppState::Config(c) = &mut app.state {
+                    if c.is_typing() {
+                        c.handle_key(key);
+                        continue;
+                    }
+                }
+
+                let keymap = &app.settings.keymap;
+
+                if keymap.matches(Action::Quit, &key) {
                     return Ok(false);
                 }
 
-                if key.code == event::KeyCode::Char('t') {
-                    app.theme.toggle();
-                    match &mut app.state {
-                        AppState::Config(config_state) => {
-                            config_state.theme = app.theme;
-                        }
-                        AppState::Processing(processing_state) => {
-                            processing_state.theme = app.theme;
-                        }
-                    }
+                if keymap.matches(Action::Theme, &key) {
+                    let mut theme = app.theme;
+                    theme.toggle();
+                    app.set_theme(theme);
+                    continue;
+                }
+
+                if keymap.matches(Action::ThemePicker, &key) {
+                    app.open_theme_picker();
+                    continue;
+                }
+
+                // runs from the history take over the screen, so only before a batch
+                if keymap.matches(Action::History, &key) && matches!(app.state, AppState::Config(_))
+                {
+                    app.history = Some(HistoryState::new(History::load()));
                     continue;
                 }
 
@@ -328,7 +556,19 @@ fn process_events(
                     }
                 }
             }
-            Event::Tick => {}
+            Event::Tick => {
+                if let AppState::Processing(p) = &mut app.state {
+                    p.tick();
+                }
+            }
+            Event::ReturnToConfig => {
+                if let Some(mut c) = app.parked_config.take() {
+                    c.theme = app.theme;
+                    c.last_mouse_click = None;
+                    c.dragging = None;
+                    app.state = AppState::Config(c);
+                }
+            }
             Event::Progress(event) => {
                 if let AppState::Processing(processing_state) = &mut app.state {
                     processing_state.handle_event(event);
@@ -343,10 +583,17 @@ fn process_events(
                 files,
                 config,
                 output_dir,
+                resume,
+                series,
+                pages,
+                overrides,
+                nice,
+                save_settings,
             } => {
-                if config.output_format == OutputFormat::Mobi
-                    && !comically::is_kindlegen_available()
-                {
+                let mobi = std::iter::once(&config.output_format)
+                    .chain(overrides.values().filter_map(|o| o.output_format.as_ref()))
+                    .any(|&format| format == OutputFormat::Mobi);
+                if mobi && !comically::is_kindlegen_available() {
                     return Err(ErrorInfo::error(
                             "KindleGen not installed",
                             "Please install KindleGen and make sure it's in your PATH",
@@ -354,15 +601,42 @@ fn process_events(
                         ));
                 }
 
-                let _ = config.save();
-                app.state = AppState::Processing(progress::ProgressState::new(
+                if save_settings {
+                    app.settings.comic = (*config).clone();
+                    app.settings.nice = nice;
+                    let _ = app.settings.save();
+                }
+
+                let available_files = match &app.state {
+                    AppState::Config(c) => c.files.iter().map(|(file, _)| file.clone()).collect(),
+                    AppState::Processing(_) => Vec::new(),
+                };
+
+                let (queue_tx, queue_rx) = mpsc::channel();
+                let _ = queue_tx.send(crate::pipeline::QueueCommand::Add(files));
+
+                let progress_state = progress::ProgressState::new(
                     app.theme,
                     config.output_format,
-                ));
+                    queue_tx,
+                    available_files,
+                    output_dir.clone(),
+                    event_tx.clone(),
+                    app.settings.keymap.clone(),
+                    app.settings.language().strings(),
+                );
+                if let AppState::Config(c) =
+                    std::mem::replace(&mut app.state, AppState::Processing(progress_state))
+                {
+                    app.parked_config = Some(c);
+                }
 
                 let event_tx = event_tx.clone();
-                rayon::spawn(move || {
-                    crate::pipeline::process_files(files, config, output_dir, event_tx);
+                std::thread::spawn(move || {
+                    crate::pipeline::process_queue(
+                        queue_rx, *config, output_dir, resume, series, pages, overrides, nice,
+                        event_tx,
+                    );
                 });
             }
         }
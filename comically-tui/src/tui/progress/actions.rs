@@ -0,0 +1,304 @@
+use anyhow::Context;
+use ratatui::{
+    buffer::Buffer,
+    crossterm::event::KeyCode,
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget},
+};
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::tui::{i18n::Strings, utils::popup_block, Theme};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionAction {
+    OpenOutputDir,
+    RevealFile,
+    CopyToDevice,
+    MoveToConverted,
+    MoveToTrash,
+    ConvertMore,
+}
+
+impl CompletionAction {
+    pub const ALL: [CompletionAction; 6] = [
+        CompletionAction::OpenOutputDir,
+        CompletionAction::RevealFile,
+        CompletionAction::CopyToDevice,
+        CompletionAction::MoveToConverted,
+        CompletionAction::MoveToTrash,
+        CompletionAction::ConvertMore,
+    ];
+
+    pub fn key(self) -> char {
+        match self {
+            CompletionAction::OpenOutputDir => 'o',
+            CompletionAction::RevealFile => 'v',
+            CompletionAction::CopyToDevice => 'c',
+            CompletionAction::MoveToConverted => 's',
+            CompletionAction::MoveToTrash => 't',
+            CompletionAction::ConvertMore => 'b',
+        }
+    }
+
+    pub fn label(self, strings: &'static Strings) -> &'static str {
+        match self {
+            CompletionAction::OpenOutputDir => strings.open_output_dir,
+            CompletionAction::RevealFile => strings.reveal_file,
+            CompletionAction::CopyToDevice => strings.copy_to_device,
+            CompletionAction::MoveToConverted => strings.move_to_converted,
+            CompletionAction::MoveToTrash => strings.move_to_trash,
+            CompletionAction::ConvertMore => strings.convert_more,
+        }
+    }
+
+    pub fn from_key(key: KeyCode) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| key == KeyCode::Char(action.key()))
+    }
+}
+
+pub struct CompletionMenuState {
+    pub list_state: ListState,
+    pub status: Option<String>,
+    // a move of the sources waiting for its key to be pressed again
+    pub pending_move: Option<CompletionAction>,
+    copy_rx: Option<mpsc::Receiver<anyhow::Result<(usize, PathBuf)>>>,
+    strings: &'static Strings,
+}
+
+impl CompletionMenuState {
+    pub fn new(strings: &'static Strings) -> Self {
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+
+        Self {
+            list_state,
+            status: None,
+            pending_move: None,
+            copy_rx: None,
+            strings,
+        }
+    }
+
+    pub fn selected_action(&self) -> Option<CompletionAction> {
+        self.list_state
+            .selected()
+            .and_then(|i| CompletionAction::ALL.get(i).copied())
+    }
+
+    pub fn select_next(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected + 1 < CompletionAction::ALL.len() {
+                self.list_state.select(Some(selected + 1));
+            }
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected > 0 {
+                self.list_state.select(Some(selected - 1));
+            }
+        }
+    }
+
+    pub fn is_copying(&self) -> bool {
+        self.copy_rx.is_some()
+    }
+
+    // copies in the background, the result is picked up by `poll_copy` on the next render
+    pub fn start_copy(&mut self, files: Vec<PathBuf>) {
+        let Some(device) = find_device_dir() else {
+            self.status = Some(self.strings.no_device.into());
+            return;
+        };
+
+        let (tx, rx) = mpsc::channel();
+        self.status = Some(format!(
+            "{} {}...",
+            self.strings.copying_to,
+            device.display()
+        ));
+        self.copy_rx = Some(rx);
+
+        thread::spawn(move || {
+            let _ = tx.send(copy_files(&files, &device).map(|count| (count, device)));
+        });
+    }
+
+    pub fn poll_copy(&mut self) {
+        let Some(rx) = &self.copy_rx else {
+            return;
+        };
+
+        match rx.try_recv() {
+            Ok(Ok((count, device))) => {
+                self.status = Some((self.strings.copied_files)(
+                    count,
+                    &device.display().to_string(),
+                ));
+                self.copy_rx = None;
+            }
+            Ok(Err(e)) => {
+                log::error!("copy to device failed: {e:?}");
+                self.status = Some(format!("{}: {e}", self.strings.copy_failed));
+                self.copy_rx = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.copy_rx = None;
+            }
+        }
+    }
+}
+
+/// Open a file or directory with the platform's default handler
+pub fn open_path(path: &Path) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        Command::new("explorer")
+    } else {
+        Command::new("xdg-open")
+    };
+
+    command
+        .arg(path)
+        .spawn()
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    Ok(())
+}
+
+/// Show a file in the platform's file manager, selecting it where supported
+pub fn reveal_path(path: &Path) -> anyhow::Result<()> {
+    if cfg!(target_os = "macos") {
+        Command::new("open").arg("-R").arg(path).spawn()?;
+    } else if cfg!(target_os = "windows") {
+        Command::new("explorer")
+            .arg(format!("/select,{}", path.display()))
+            .spawn()?;
+    } else {
+        // xdg-open has no notion of selecting a file, open the containing folder instead
+        let parent = path.parent().unwrap_or(path);
+        return open_path(parent);
+    }
+    Ok(())
+}
+
+// (volume name, folder on the volume where books go)
+const DEVICE_VOLUMES: &[(&str, &str)] = &[
+    ("Kindle", "documents"),
+    ("KOBOeReader", ""),
+    ("PocketBook", ""),
+    ("NOOK", "My Files/Books"),
+];
+
+/// Find the books folder of a mounted e-reader
+pub fn find_device_dir() -> Option<PathBuf> {
+    let user = std::env::var("USER").unwrap_or_default();
+    let mount_roots = [
+        PathBuf::from("/Volumes"),
+        PathBuf::from("/media").join(&user),
+        PathBuf::from("/run/media").join(&user),
+        PathBuf::from("/media"),
+        PathBuf::from("/mnt"),
+    ];
+
+    mount_roots.iter().find_map(|root| {
+        DEVICE_VOLUMES.iter().find_map(|(volume, books_dir)| {
+            let volume = root.join(volume);
+            volume.is_dir().then(|| volume.join(books_dir))
+        })
+    })
+}
+
+fn copy_files(files: &[PathBuf], device: &Path) -> anyhow::Result<usize> {
+    std::fs::create_dir_all(device)
+        .with_context(|| format!("failed to create {}", device.display()))?;
+    // Kindles keep the covers of their home screen next to `documents`
+    let thumbnails = device
+        .parent()
+        .map(|root| root.join("system"))
+        .filter(|system| system.is_dir())
+        .map(|system| system.join("thumbnails"));
+
+    for file in files {
+        let file_name = file
+            .file_name()
+            .with_context(|| format!("not a file: {}", file.display()))?;
+        std::fs::copy(file, device.join(file_name))
+            .with_context(|| format!("failed to copy {}", file.display()))?;
+
+        let is_mobi = file.extension().is_some_and(|ext| ext == "mobi");
+        if let (Some(dir), true) = (&thumbnails, is_mobi) {
+            if let Some(thumbnail) = comically::mobi::kindle_thumbnail(file) {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create {}", dir.display()))?;
+                std::fs::copy(
+                    &thumbnail,
+                    dir.join(thumbnail.file_name().unwrap_or_default()),
+                )
+                .with_context(|| format!("failed to copy {}", thumbnail.display()))?;
+            }
+        }
+    }
+
+    Ok(files.len())
+}
+
+pub fn render_completion_menu(
+    area: Rect,
+    buf: &mut Buffer,
+    theme: &Theme,
+    state: &mut CompletionMenuState,
+) {
+    let popup_width = 44.min(area.width * 3 / 4);
+    let popup_height = 12.min(area.height * 3 / 4);
+
+    let popup_x = area.left() + (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = area.top() + (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(state.strings.done, theme).title(Line::from("[esc]").right_aligned());
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let [list_area, status_area] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(2)]).areas(inner);
+
+    let items: Vec<ListItem> = CompletionAction::ALL
+        .iter()
+        .map(|action| {
+            ListItem::new(format!(
+                "[{}] {}",
+                action.key(),
+                action.label(state.strings)
+            ))
+            .style(theme.content)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    StatefulWidget::render(list, list_area, buf, &mut state.list_state);
+
+    if let Some(status) = &state.status {
+        Paragraph::new(status.as_str())
+            .style(Style::default().fg(theme.accent))
+            .alignment(Alignment::Center)
+            .wrap(ratatui::widgets::Wrap { trim: true })
+            .render(status_area, buf);
+    }
+}
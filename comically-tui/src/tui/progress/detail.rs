@@ -0,0 +1,136 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Clear, Paragraph, Widget, Wrap},
+};
+
+use std::path::Path;
+
+use comically::mobi::KindleGenError;
+
+use crate::tui::{
+    i18n::Strings,
+    progress::ComicStage,
+    utils::{popup_block, themed_block},
+    Theme,
+};
+
+pub struct ErrorDetailState {
+    pub id: usize,
+    pub scroll: u16,
+}
+
+impl ErrorDetailState {
+    pub fn new(id: usize) -> Self {
+        Self { id, scroll: 0 }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_add(1);
+    }
+}
+
+pub struct ErrorDetail<'a> {
+    pub title: &'a str,
+    /// see [`comically::ComicConfig::fingerprint`]
+    pub fingerprint: &'a str,
+    pub stage: Option<ComicStage>,
+    pub error: &'a anyhow::Error,
+    pub verbose_log: Option<&'a Path>,
+}
+
+pub fn render_error_detail(
+    area: Rect,
+    buf: &mut Buffer,
+    theme: &Theme,
+    strings: &Strings,
+    detail: ErrorDetail,
+    state: &mut ErrorDetailState,
+) {
+    let popup_width = (area.width * 4 / 5).min(100);
+    let popup_height = (area.height * 4 / 5).min(30);
+
+    let popup_x = area.left() + (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = area.top() + (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(detail.title, theme).title(Line::from("[esc]").right_aligned());
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let kindlegen = detail
+        .error
+        .chain()
+        .find_map(|e| e.downcast_ref::<KindleGenError>());
+
+    let [error_area, kindlegen_area, hint_area] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Fill(if kindlegen.is_some() { 1 } else { 0 }),
+        Constraint::Length(1),
+    ])
+    .areas(inner);
+
+    let stage = match detail.stage {
+        Some(stage) => stage.to_string(),
+        None => strings.open_archive.to_string(),
+    };
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::raw(format!("{}: ", strings.failed_during)),
+            Span::styled(stage, Style::default().fg(theme.accent)),
+        ]),
+        Line::from(format!("{}: {}", strings.settings, detail.fingerprint)),
+        Line::from(""),
+    ];
+
+    // first entry is the error itself, the rest are its causes
+    for (i, cause) in detail.error.chain().enumerate() {
+        let line = if i == 0 {
+            Line::from(cause.to_string()).add_modifier(Modifier::BOLD)
+        } else {
+            Line::from(format!("  {}: {cause}", strings.caused_by))
+        };
+        lines.push(line);
+    }
+
+    if let Some(log) = detail.verbose_log {
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!("{}: {}", strings.verbose_log, log.display())).italic());
+    }
+
+    Paragraph::new(lines)
+        .style(Style::default().fg(theme.content))
+        .wrap(Wrap { trim: false })
+        .scroll((state.scroll, 0))
+        .render(error_area, buf);
+
+    if let Some(kindlegen) = kindlegen {
+        let block = themed_block(Some(strings.kindlegen_output), theme);
+        let kindlegen_inner = block.inner(kindlegen_area);
+        block.render(kindlegen_area, buf);
+
+        Paragraph::new(kindlegen.output.as_str())
+            .style(Style::default().fg(theme.content))
+            .wrap(Wrap { trim: false })
+            .scroll((state.scroll, 0))
+            .render(kindlegen_inner, buf);
+    }
+
+    Paragraph::new(format!(
+        "↑/↓/j/k: {} | r: {} | v: {} | esc: {}",
+        strings.scroll, strings.retry, strings.retry_verbose, strings.close
+    ))
+    .style(Style::default().fg(theme.accent))
+    .alignment(Alignment::Center)
+    .render(hint_area, buf);
+}
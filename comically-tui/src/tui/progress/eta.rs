@@ -0,0 +1,256 @@
+//! Estimates of the time left, from how fast pages were processed lately and how long
+//! packaging and KindleGen took next to processing for the comics already done.
+
+use comically::OutputFormat;
+
+use std::time::{Duration, Instant};
+
+use super::{ComicStage, ComicState, ComicStatus};
+
+// the rate is measured over windows this long, shorter ones jump around with page sizes
+const WINDOW: Duration = Duration::from_secs(1);
+// weight of the latest window in the smoothed rate
+const SMOOTHING: f64 = 0.3;
+// seconds of packaging per second of processing until a comic is done, KindleGen
+// takes a lot longer than zipping
+const PACKAGE_RATIO: f64 = 0.05;
+const CONVERT_RATIO: f64 = 0.5;
+
+/// Pages processed per second, smoothed over the ticks of the progress screen
+pub struct Throughput {
+    rate: Option<f64>,
+    window_start: Instant,
+    window_pages: usize,
+}
+
+impl Throughput {
+    pub fn new() -> Self {
+        Self {
+            rate: None,
+            window_start: Instant::now(),
+            window_pages: 0,
+        }
+    }
+
+    pub fn rate(&self) -> Option<f64> {
+        self.rate
+    }
+
+    /// Called on every tick with the pages processed so far. Time spent packaging,
+    /// when no pages are processed, doesn't slow the rate down
+    pub fn tick(&mut self, processed: usize, processing: bool, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if !processing {
+            self.window_start = now;
+            self.window_pages = processed;
+            return;
+        }
+        if elapsed < WINDOW {
+            return;
+        }
+
+        // a retried comic starts counting from 0 again
+        let pages = processed.saturating_sub(self.window_pages);
+        let latest = pages as f64 / elapsed.as_secs_f64();
+        self.rate = Some(match self.rate {
+            Some(rate) => rate + SMOOTHING * (latest - rate),
+            None => latest,
+        });
+        self.window_start = now;
+        self.window_pages = processed;
+    }
+}
+
+/// What the estimates of one render are computed from
+pub struct Estimator {
+    rate: Option<f64>,
+    // packaging next to processing for EPUBs and CBZs, and for MOBIs
+    post_ratio: f64,
+    mobi_post_ratio: f64,
+    average_pages: Option<f64>,
+    // with MOBI, KindleGen runs on a comic while the next one is processed
+    overlapping: bool,
+}
+
+// seconds left processing pages, and packaging or converting after that
+struct Remaining {
+    process: f64,
+    post: f64,
+}
+
+impl Estimator {
+    pub fn new(comics: &[ComicState], throughput: &Throughput) -> Self {
+        // measured on the comics of the format already done
+        let post_ratio = |mobi: bool, default: f64| {
+            let (mut process, mut post) = (0.0, 0.0);
+            let done = comics.iter().filter(|comic| {
+                matches!(comic.status, ComicStatus::Success)
+                    && (comic.output_format == OutputFormat::Mobi) == mobi
+            });
+            for stage in done.flat_map(|comic| &comic.timings.stages) {
+                match stage.stage {
+                    ComicStage::Process => process += stage.duration.as_secs_f64(),
+                    _ => post += stage.duration.as_secs_f64(),
+                }
+            }
+            match process > 0.0 {
+                true => post / process,
+                false => default,
+            }
+        };
+
+        let sizes: Vec<usize> = comics
+            .iter()
+            .map(|comic| comic.total_images)
+            .filter(|&pages| pages > 0)
+            .collect();
+        let average_pages =
+            (!sizes.is_empty()).then(|| sizes.iter().sum::<usize>() as f64 / sizes.len() as f64);
+
+        Self {
+            rate: throughput.rate().filter(|&rate| rate > 0.0),
+            post_ratio: post_ratio(false, PACKAGE_RATIO),
+            mobi_post_ratio: post_ratio(true, PACKAGE_RATIO + CONVERT_RATIO),
+            average_pages,
+            overlapping: comics
+                .iter()
+                .any(|comic| comic.output_format == OutputFormat::Mobi),
+        }
+    }
+
+    /// Until `comic` is done, `None` until pages were processed to measure a rate
+    pub fn comic(&self, comic: &ComicState) -> Option<Duration> {
+        self.remaining(comic)
+            .map(|left| Duration::from_secs_f64(left.process + left.post))
+    }
+
+    /// Until every comic of the queue is done
+    pub fn total(&self, comics: &[ComicState]) -> Option<Duration> {
+        let (mut process, mut post) = (0.0, 0.0_f64);
+        for comic in comics {
+            let left = self.remaining(comic)?;
+            process += left.process;
+            post = match self.overlapping {
+                true => post.max(left.post),
+                false => post + left.post,
+            };
+        }
+        Some(Duration::from_secs_f64(process + post))
+    }
+
+    fn remaining(&self, comic: &ComicState) -> Option<Remaining> {
+        let post_ratio = match comic.output_format {
+            OutputFormat::Mobi => self.mobi_post_ratio,
+            _ => self.post_ratio,
+        };
+        let pages = match comic.total_images {
+            0 => self.average_pages,
+            pages => Some(pages as f64),
+        };
+        match &comic.status {
+            ComicStatus::Success | ComicStatus::Failed { .. } => Some(Remaining {
+                process: 0.0,
+                post: 0.0,
+            }),
+            ComicStatus::Progress { start, .. } => {
+                let processing: f64 = comic
+                    .timings
+                    .stages
+                    .iter()
+                    .filter(|stage| stage.stage == ComicStage::Process)
+                    .map(|stage| stage.duration.as_secs_f64())
+                    .sum();
+                let spent =
+                    (comic.timings.total() - Duration::from_secs_f64(processing)) + start.elapsed();
+                Some(Remaining {
+                    process: 0.0,
+                    post: (processing * post_ratio - spent.as_secs_f64()).max(0.0),
+                })
+            }
+            _ => {
+                let (rate, pages) = (self.rate?, pages?);
+                let left = (pages - comic.images_processed as f64).max(0.0);
+                Some(Remaining {
+                    process: left / rate,
+                    post: pages / rate * post_ratio,
+                })
+            }
+        }
+    }
+}
+
+/// `42s`, `3m05s` or `1h02m`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[test]
+fn estimates_time_left() {
+    use super::StageTimings;
+
+    let comic = |status: ComicStatus, total_images: usize, images_processed: usize| ComicState {
+        id: 0,
+        title: String::new(),
+        fingerprint: String::new(),
+        output_format: OutputFormat::Epub,
+        status,
+        stage: None,
+        verbose_log: None,
+        timings: StageTimings::new(),
+        image_processing_start: None,
+        images_processed,
+        total_images,
+    };
+
+    let start = Instant::now();
+    let mut throughput = Throughput::new();
+    throughput.tick(0, true, start);
+    // packaging doesn't count against the rate
+    throughput.tick(20, false, start + Duration::from_secs(2));
+    throughput.tick(40, true, start + Duration::from_secs(4));
+    assert_eq!(throughput.rate(), Some(10.0));
+
+    let mut done = comic(ComicStatus::Success, 100, 100);
+    done.timings
+        .add_stage(ComicStage::Process, Duration::from_secs(10));
+    done.timings
+        .add_stage(ComicStage::Package, Duration::from_secs(1));
+    let comics = [
+        done,
+        comic(ComicStatus::ImageProcessed, 100, 50),
+        comic(ComicStatus::Waiting, 0, 0),
+    ];
+    let estimator = Estimator::new(&comics, &throughput);
+    // 50 pages at 10 a second, then a tenth of 10 seconds packaging
+    assert_eq!(estimator.comic(&comics[1]), Some(Duration::from_secs(6)));
+    // as long as the average comic, 100 pages
+    assert_eq!(estimator.comic(&comics[2]), Some(Duration::from_secs(11)));
+    assert_eq!(estimator.total(&comics), Some(Duration::from_secs(17)));
+    assert_eq!(
+        Estimator::new(&comics, &Throughput::new()).total(&comics),
+        None
+    );
+
+    // MOBIs of the same run wait on KindleGen as well
+    let mut mobi = comic(ComicStatus::Waiting, 100, 0);
+    mobi.output_format = OutputFormat::Mobi;
+    let mixed = [comic(ComicStatus::Waiting, 100, 0), mobi];
+    let estimator = Estimator::new(&mixed, &throughput);
+    assert_eq!(
+        estimator.comic(&mixed[0]),
+        Some(Duration::from_millis(10_500))
+    );
+    assert_eq!(
+        estimator.comic(&mixed[1]),
+        Some(Duration::from_millis(15_500))
+    );
+
+    assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+    assert_eq!(format_duration(Duration::from_secs(185)), "3m05s");
+    assert_eq!(format_duration(Duration::from_secs(3720)), "1h02m");
+}
@@ -0,0 +1,1152 @@
+pub mod actions;
+pub mod detail;
+mod eta;
+pub mod queue;
+
+use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{self, KeyEvent, MouseEvent, MouseEventKind},
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::Span,
+    widgets::{Block, Gauge, Padding, Paragraph, StatefulWidget, Widget},
+};
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use comically::source::MoveSource;
+use comically::{ComicFile, OutputFormat};
+
+use crate::pipeline::QueueCommand;
+use crate::tui::{
+    i18n::Strings,
+    keymap::{Action, Keymap},
+    progress::actions::{render_completion_menu, CompletionAction, CompletionMenuState},
+    progress::detail::{render_error_detail, ErrorDetail, ErrorDetailState},
+    progress::eta::{format_duration, Estimator, Throughput},
+    progress::queue::{render_add_files_popup, AddFilesState},
+    render_title,
+    utils::{themed_block, themed_block_title},
+    Theme,
+};
+
+/// Stages of a comic's conversion, shared with the library's batch API
+pub use comically::batch::Stage as ComicStage;
+
+#[derive(Debug)]
+pub enum ComicStatus {
+    Waiting,
+    Progress {
+        stage: ComicStage,
+        // how far along the stage is in percent, the gauge shows the whole conversion
+        progress: f64,
+        start: Instant,
+    },
+    ImageProcessingStart {
+        start: Instant,
+    },
+    // pages read from the archive so far, before processing starts
+    Extracting {
+        extracted: usize,
+    },
+    ImageProcessed,
+    // how far along KindleGen is in percent, for the gauge of the Convert stage
+    Converting {
+        progress: f64,
+    },
+    StageCompleted {
+        stage: ComicStage,
+        duration: Duration,
+    },
+    Success,
+    Failed {
+        error: anyhow::Error,
+    },
+}
+
+pub enum ProgressEvent {
+    RegisterComic {
+        id: usize,
+        file_name: String,
+        // see `ComicConfig::fingerprint`, the settings with the comic's overrides
+        fingerprint: String,
+        // the batch's, or the one picked for the comic
+        output_format: OutputFormat,
+    },
+    ComicStats {
+        id: usize,
+        total_images: usize,
+    },
+    ComicUpdate {
+        id: usize,
+        status: ComicStatus,
+    },
+}
+
+pub struct ProgressState {
+    start: Instant,
+    // in queue order, which can differ from id order once pending comics are reordered
+    comics: Vec<ComicState>,
+    complete: Option<Duration>,
+    throughput: Throughput,
+    scroll_offset: usize,
+    selected: usize,
+    queue_tx: mpsc::Sender<QueueCommand>,
+    // every file found in the input directory, used to offer files that aren't queued yet
+    available_files: Vec<ComicFile>,
+    add_files: Option<AddFilesState>,
+    completion_menu: Option<CompletionMenuState>,
+    error_detail: Option<ErrorDetailState>,
+    output_dir: PathBuf,
+    event_tx: mpsc::Sender<crate::Event>,
+    keymap: Keymap,
+    strings: &'static Strings,
+    pub theme: Theme,
+    pub output_format: OutputFormat,
+}
+
+#[derive(Debug)]
+struct ComicState {
+    id: usize,
+    title: String,
+    fingerprint: String,
+    output_format: OutputFormat,
+    status: ComicStatus,
+    // last stage that started, kept around to explain failures
+    stage: Option<ComicStage>,
+    verbose_log: Option<PathBuf>,
+    timings: StageTimings,
+    image_processing_start: Option<Instant>,
+    images_processed: usize,
+    total_images: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct StageTimings {
+    stages: Vec<StageMetrics>,
+}
+
+impl StageTimings {
+    pub fn add_stage(&mut self, stage: ComicStage, duration: Duration) {
+        self.stages.push(StageMetrics { stage, duration });
+    }
+
+    pub fn new() -> Self {
+        Self { stages: Vec::new() }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|s| s.duration).sum()
+    }
+
+    // time spent in `stage`, over every target
+    fn of(&self, stage: ComicStage) -> Duration {
+        self.stages
+            .iter()
+            .filter(|s| s.stage == stage)
+            .map(|s| s.duration)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StageMetrics {
+    stage: ComicStage,
+    duration: Duration,
+}
+
+impl ComicState {
+    fn current_status(&self) -> &ComicStatus {
+        &self.status
+    }
+
+    fn is_waiting(&self) -> bool {
+        matches!(self.status, ComicStatus::Waiting)
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            ComicStatus::Success | ComicStatus::Failed { .. }
+        )
+    }
+}
+
+impl ProgressState {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        theme: Theme,
+        output_format: OutputFormat,
+        queue_tx: mpsc::Sender<QueueCommand>,
+        available_files: Vec<ComicFile>,
+        output_dir: PathBuf,
+        event_tx: mpsc::Sender<crate::Event>,
+        keymap: Keymap,
+        strings: &'static Strings,
+    ) -> Self {
+        Self {
+            start: Instant::now(),
+            comics: Vec::new(),
+            complete: None,
+            throughput: Throughput::new(),
+            scroll_offset: 0,
+            selected: 0,
+            queue_tx,
+            available_files,
+            add_files: None,
+            completion_menu: None,
+            error_detail: None,
+            output_dir,
+            event_tx,
+            keymap,
+            strings,
+            theme,
+            output_format,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let processed = self.comics.iter().map(|c| c.images_processed).sum();
+        let processing = self.comics.iter().any(|comic| {
+            matches!(
+                comic.status,
+                ComicStatus::ImageProcessingStart { .. } | ComicStatus::ImageProcessed
+            )
+        });
+        self.throughput.tick(processed, processing, Instant::now());
+    }
+
+    fn comic_mut(&mut self, id: usize) -> Option<&mut ComicState> {
+        self.comics.iter_mut().find(|comic| comic.id == id)
+    }
+
+    pub fn handle_event(&mut self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::RegisterComic {
+                id,
+                file_name,
+                fingerprint,
+                output_format,
+            } => {
+                debug_assert!(
+                    self.comics.iter().all(|comic| comic.id != id),
+                    "comic already registered"
+                );
+
+                self.comics.push(ComicState {
+                    id,
+                    title: file_name,
+                    fingerprint,
+                    output_format,
+                    status: ComicStatus::Waiting,
+                    stage: None,
+                    verbose_log: None,
+                    timings: StageTimings::new(),
+                    image_processing_start: None,
+                    images_processed: 0,
+                    total_images: 0,
+                });
+            }
+            ProgressEvent::ComicStats { id, total_images } => {
+                if let Some(comic) = self.comic_mut(id) {
+                    comic.total_images = total_images;
+                }
+            }
+            ProgressEvent::ComicUpdate { id, status } => {
+                if let Some(comic) = self.comic_mut(id) {
+                    match &status {
+                        ComicStatus::StageCompleted { stage, duration } => {
+                            comic.timings.add_stage(*stage, *duration);
+                            // Not storing this status
+                            return;
+                        }
+                        ComicStatus::Converting { progress } => {
+                            if let ComicStatus::Progress {
+                                progress: gauge, ..
+                            } = &mut comic.status
+                            {
+                                *gauge = *progress;
+                            }
+                            // Not storing this status either
+                            return;
+                        }
+                        ComicStatus::Progress { stage, .. } => {
+                            comic.stage = Some(*stage);
+                        }
+                        ComicStatus::ImageProcessingStart { start } => {
+                            comic.stage = Some(ComicStage::Process);
+                            comic.images_processed = 0;
+                            comic.image_processing_start = Some(*start);
+                        }
+                        ComicStatus::ImageProcessed => {
+                            comic.images_processed += 1;
+                        }
+                        ComicStatus::Waiting => {
+                            // re-queued after a failure, start over
+                            comic.stage = None;
+                            comic.timings = StageTimings::new();
+                            comic.image_processing_start = None;
+                            comic.images_processed = 0;
+                        }
+                        _ => {}
+                    }
+                    comic.status = status;
+                } else {
+                    panic!("Comic state not found for id: {}", id);
+                }
+            }
+        }
+
+        self.update_complete();
+    }
+
+    // the queue is long-lived, so "complete" just means nothing is left to do right now
+    fn update_complete(&mut self) {
+        let all_finished = self.comics.iter().all(ComicState::is_finished);
+        match (all_finished, self.complete) {
+            (true, None) => {
+                self.complete = Some(self.start.elapsed());
+                self.completion_menu = Some(CompletionMenuState::new(self.strings));
+            }
+            (false, Some(_)) => self.complete = None,
+            _ => {}
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if let Some(add_files) = &mut self.add_files {
+            match key.code {
+                event::KeyCode::Esc | event::KeyCode::Char('a') => {
+                    self.add_files = None;
+                }
+                event::KeyCode::Enter => {
+                    let files = add_files.confirm_selection();
+                    self.add_files = None;
+                    self.enqueue(files);
+                }
+                _ if self.keymap.matches(Action::Up, &key) => add_files.select_previous(),
+                _ if self.keymap.matches(Action::Down, &key) => add_files.select_next(),
+                _ if self.keymap.matches(Action::ToggleFile, &key) => add_files.toggle_selected(),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(detail) = &mut self.error_detail {
+            match key.code {
+                event::KeyCode::Esc | event::KeyCode::Enter => {
+                    self.error_detail = None;
+                }
+                _ if self.keymap.matches(Action::Up, &key) => detail.scroll_up(),
+                _ if self.keymap.matches(Action::Down, &key) => detail.scroll_down(),
+                event::KeyCode::Char('r') => {
+                    let id = detail.id;
+                    self.error_detail = None;
+                    self.retry(id, false);
+                }
+                event::KeyCode::Char('v') => {
+                    let id = detail.id;
+                    self.error_detail = None;
+                    self.retry(id, true);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(menu) = &mut self.completion_menu {
+            match key.code {
+                event::KeyCode::Esc => {
+                    self.completion_menu = None;
+                }
+                event::KeyCode::Enter => {
+                    if let Some(action) = menu.selected_action() {
+                        self.run_action(action);
+                    }
+                }
+                code => match CompletionAction::from_key(code) {
+                    Some(action) => self.run_action(action),
+                    None if self.keymap.matches(Action::Up, &key) => menu.select_previous(),
+                    None if self.keymap.matches(Action::Down, &key) => menu.select_next(),
+                    None => {}
+                },
+            }
+            return;
+        }
+
+        if self.complete.is_some() {
+            if key.code == event::KeyCode::Char('m') {
+                self.completion_menu = Some(CompletionMenuState::new(self.strings));
+                return;
+            }
+            if let Some(action) = CompletionAction::from_key(key.code) {
+                self.run_action(action);
+                return;
+            }
+        }
+
+        match key.code {
+            _ if self.keymap.matches(Action::Up, &key) => self.select_previous(),
+            _ if self.keymap.matches(Action::Down, &key) => self.select_next(),
+            event::KeyCode::Char('K') => self.move_selected(false),
+            event::KeyCode::Char('J') => self.move_selected(true),
+            event::KeyCode::Char('r') => self.retry_selected(),
+            event::KeyCode::Char('a') => self.open_add_files(),
+            event::KeyCode::Enter => self.open_error_detail(),
+            _ => {}
+        }
+    }
+
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if let Some(detail) = &mut self.error_detail {
+            match mouse.kind {
+                MouseEventKind::ScrollUp => detail.scroll_up(),
+                MouseEventKind::ScrollDown => detail.scroll_down(),
+                _ => {}
+            }
+            return;
+        }
+
+        match (&mut self.add_files, &mut self.completion_menu, mouse.kind) {
+            (Some(add_files), _, MouseEventKind::ScrollUp) => add_files.select_previous(),
+            (Some(add_files), _, MouseEventKind::ScrollDown) => add_files.select_next(),
+            (None, Some(menu), MouseEventKind::ScrollUp) => menu.select_previous(),
+            (None, Some(menu), MouseEventKind::ScrollDown) => menu.select_next(),
+            (None, None, MouseEventKind::ScrollUp) => self.select_previous(),
+            (None, None, MouseEventKind::ScrollDown) => self.select_next(),
+            _ => {}
+        }
+    }
+
+    fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.comics.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn selected_comic(&self) -> Option<&ComicState> {
+        self.comics.get(self.selected)
+    }
+
+    // swap the selected comic with its neighbour, only while both are still waiting
+    fn move_selected(&mut self, down: bool) {
+        let target = if down {
+            self.selected + 1
+        } else {
+            match self.selected.checked_sub(1) {
+                Some(target) => target,
+                None => return,
+            }
+        };
+
+        let both_waiting = self
+            .comics
+            .get(self.selected)
+            .is_some_and(ComicState::is_waiting)
+            && self.comics.get(target).is_some_and(ComicState::is_waiting);
+        if !both_waiting {
+            return;
+        }
+
+        self.comics.swap(self.selected, target);
+        self.selected = target;
+
+        let order = self
+            .comics
+            .iter()
+            .filter(|comic| comic.is_waiting())
+            .map(|comic| comic.id)
+            .collect();
+        let _ = self.queue_tx.send(QueueCommand::Reorder(order));
+    }
+
+    fn retry_selected(&mut self) {
+        if let Some(comic) = self.selected_comic() {
+            self.retry(comic.id, false);
+        }
+    }
+
+    fn retry(&mut self, id: usize, verbose: bool) {
+        let verbose_log = verbose
+            .then(|| {
+                let comic = self.comics.iter().find(|comic| comic.id == id)?;
+                let file = self
+                    .available_files
+                    .iter()
+                    .find(|file| file.title() == comic.title)?;
+                Some(crate::pipeline::verbose_log_path(&self.output_dir, file))
+            })
+            .flatten();
+
+        if let Some(comic) = self.comic_mut(id) {
+            if matches!(comic.status, ComicStatus::Failed { .. }) {
+                if verbose_log.is_some() {
+                    comic.verbose_log = verbose_log;
+                }
+                let _ = self.queue_tx.send(QueueCommand::Retry { id, verbose });
+            }
+        }
+    }
+
+    fn open_error_detail(&mut self) {
+        if let Some(comic) = self.selected_comic() {
+            if matches!(comic.status, ComicStatus::Failed { .. }) {
+                self.error_detail = Some(ErrorDetailState::new(comic.id));
+            }
+        }
+    }
+
+    fn open_add_files(&mut self) {
+        let unqueued: Vec<ComicFile> = self
+            .available_files
+            .iter()
+            .filter(|file| self.comics.iter().all(|comic| comic.title != file.title()))
+            .cloned()
+            .collect();
+
+        if !unqueued.is_empty() {
+            self.add_files = Some(AddFilesState::new(unqueued));
+        }
+    }
+
+    fn output_path(&self, comic: &ComicState) -> Option<PathBuf> {
+        self.available_files
+            .iter()
+            .find(|file| file.title() == comic.title)
+            .map(|file| {
+                self.output_dir
+                    .join(file.with_extension(comic.output_format))
+            })
+    }
+
+    fn run_action(&mut self, action: CompletionAction) {
+        let strings = self.strings;
+        let pending_move = self
+            .completion_menu
+            .as_mut()
+            .and_then(|menu| menu.pending_move.take());
+
+        let result = match action {
+            CompletionAction::OpenOutputDir => actions::open_path(&self.output_dir),
+            CompletionAction::RevealFile => match self
+                .selected_comic()
+                .filter(|comic| matches!(comic.status, ComicStatus::Success))
+                .and_then(|comic| self.output_path(comic))
+            {
+                Some(path) => actions::reveal_path(&path),
+                None => Err(anyhow::anyhow!("selected file wasn't converted")),
+            },
+            CompletionAction::CopyToDevice => {
+                let files: Vec<PathBuf> = self
+                    .comics
+                    .iter()
+                    .filter(|comic| matches!(comic.status, ComicStatus::Success))
+                    .filter_map(|comic| self.output_path(comic))
+                    .collect();
+
+                let menu = self
+                    .completion_menu
+                    .get_or_insert_with(|| CompletionMenuState::new(strings));
+                if !menu.is_copying() {
+                    menu.start_copy(files);
+                }
+                Ok(())
+            }
+            CompletionAction::MoveToConverted | CompletionAction::MoveToTrash => {
+                let to = match action {
+                    CompletionAction::MoveToTrash => MoveSource::Trash,
+                    _ => MoveSource::Converted,
+                };
+                let sources = self.converted_sources();
+                let confirmed = pending_move == Some(action);
+                let status = match confirmed {
+                    true => self.move_sources(sources, to),
+                    false => (strings.confirm_move)(sources.len(), action.key()),
+                };
+                let menu = self
+                    .completion_menu
+                    .get_or_insert_with(|| CompletionMenuState::new(strings));
+                menu.pending_move = (!confirmed).then_some(action);
+                menu.status = Some(status);
+                Ok(())
+            }
+            CompletionAction::ConvertMore => {
+                let _ = self.event_tx.send(crate::Event::ReturnToConfig);
+                Ok(())
+            }
+        };
+
+        if let Err(e) = result {
+            self.completion_menu
+                .get_or_insert_with(|| CompletionMenuState::new(strings))
+                .status = Some(e.to_string());
+        }
+    }
+
+    // the sources of the converted comics, with what they were converted to
+    fn converted_sources(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.comics
+            .iter()
+            .filter(|comic| matches!(comic.status, ComicStatus::Success))
+            .filter_map(|comic| {
+                let source = self
+                    .available_files
+                    .iter()
+                    .find(|file| file.title() == comic.title)?;
+                Some((source.as_path().to_path_buf(), self.output_path(comic)?))
+            })
+            .collect()
+    }
+
+    fn move_sources(&mut self, sources: Vec<(PathBuf, PathBuf)>, to: MoveSource) -> String {
+        let mut moved = 0;
+        let mut failed = None;
+        for (source, output) in sources {
+            match comically::source::move_source(&source, &[output], to) {
+                Ok(destination) => {
+                    log::info!("Moved {} to {}", source.display(), destination.display());
+                    self.available_files.retain(|file| file.as_path() != source);
+                    moved += 1;
+                }
+                Err(e) => {
+                    log::warn!("Leaving {} in place: {e:#}", source.display());
+                    failed.get_or_insert(e);
+                }
+            }
+        }
+
+        match failed {
+            Some(e) => format!("{}: {e}", self.strings.move_failed),
+            None => (self.strings.moved_sources)(moved),
+        }
+    }
+
+    fn enqueue(&mut self, files: Vec<ComicFile>) {
+        if !files.is_empty() {
+            let _ = self.queue_tx.send(QueueCommand::Add(files));
+        }
+    }
+}
+
+pub struct ProgressScreen<'a> {
+    state: &'a mut ProgressState,
+}
+
+impl<'a> ProgressScreen<'a> {
+    pub fn new(state: &'a mut ProgressState) -> Self {
+        Self { state }
+    }
+}
+
+impl<'a> Widget for ProgressScreen<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        buf.set_style(area, Style::default().bg(self.state.theme.background));
+
+        let vertical = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(4),
+            Constraint::Length(1),
+        ])
+        .margin(1);
+
+        let [header_area, main_area, footer_area] = vertical.areas(area);
+
+        let theme = self.state.theme;
+        let estimator = Estimator::new(&self.state.comics, &self.state.throughput);
+        draw_header(buf, self.state, &estimator, header_area, &theme);
+        draw_main_content(buf, self.state, &estimator, main_area, &theme);
+        draw_footer(buf, self.state, footer_area, &theme);
+
+        if let Some(menu) = &mut self.state.completion_menu {
+            menu.poll_copy();
+            render_completion_menu(area, buf, &theme, menu);
+        }
+
+        if let Some(detail_state) = &mut self.state.error_detail {
+            let comic = self
+                .state
+                .comics
+                .iter()
+                .find(|comic| comic.id == detail_state.id);
+
+            if let Some(comic) = comic {
+                if let ComicStatus::Failed { error } = &comic.status {
+                    let detail = ErrorDetail {
+                        title: &comic.title,
+                        fingerprint: &comic.fingerprint,
+                        stage: comic.stage,
+                        error,
+                        verbose_log: comic.verbose_log.as_deref(),
+                    };
+                    render_error_detail(
+                        area,
+                        buf,
+                        &theme,
+                        self.state.strings,
+                        detail,
+                        detail_state,
+                    );
+                }
+            }
+        }
+
+        if let Some(add_files) = &mut self.state.add_files {
+            render_add_files_popup(area, buf, &theme, self.state.strings, add_files);
+        }
+    }
+}
+
+fn draw_header(
+    buf: &mut Buffer,
+    state: &ProgressState,
+    estimator: &Estimator,
+    header_area: Rect,
+    theme: &Theme,
+) {
+    let [title_area, progress] =
+        Layout::horizontal([Constraint::Percentage(15), Constraint::Percentage(85)])
+            .areas(header_area);
+
+    render_title(theme).render(title_area, buf);
+
+    let total = state.comics.len();
+
+    let successful = state
+        .comics
+        .iter()
+        .filter(|state| matches!(state.current_status(), ComicStatus::Success))
+        .count();
+
+    let total_work: usize = state.comics.iter().map(|c| c.total_images).sum();
+    let completed_work: usize = state.comics.iter().map(|c| c.images_processed).sum();
+
+    let progress_ratio = if total_work > 0 {
+        completed_work as f64 / total_work as f64
+    } else {
+        0.0
+    };
+    let elapsed = state.complete.unwrap_or_else(|| state.start.elapsed());
+    let mut label = format!("{}/{} ({:.1}s)", successful, total, elapsed.as_secs_f64());
+    if state.complete.is_none() {
+        if let Some(rate) = state.throughput.rate() {
+            label.push_str(&format!(", {rate:.1} {}", state.strings.pages_per_sec));
+        }
+        if let Some(left) = estimator.total(&state.comics) {
+            label.push_str(&format!(
+                ", {}",
+                (state.strings.time_left)(&format_duration(left))
+            ));
+        }
+    }
+
+    Gauge::default()
+        .gauge_style(Style::default().fg(theme.primary_bg))
+        .label(Span::styled(label, Style::default().fg(theme.gauge_label)))
+        .ratio(progress_ratio)
+        .block(themed_block(Some(state.strings.progress), theme))
+        .render(progress, buf);
+}
+
+fn draw_main_content(
+    buf: &mut Buffer,
+    state: &mut ProgressState,
+    estimator: &Estimator,
+    area: Rect,
+    theme: &Theme,
+) {
+    let [names_area, status_area] =
+        Layout::horizontal([Constraint::Percentage(15), Constraint::Percentage(85)]).areas(area);
+
+    let [status_area, scrollbar_area] =
+        Layout::horizontal([Constraint::Min(0), Constraint::Length(1)])
+            .spacing(1)
+            .areas(status_area);
+
+    let names_block = themed_block(Some(state.strings.files), theme);
+
+    let status_block = themed_block(Some(state.strings.status), theme)
+        .title(themed_block_title(state.strings.total, theme).right_aligned());
+
+    let names_inner_area = names_block.inner(names_area);
+    let status_inner_area = status_block.inner(status_area);
+
+    names_block.render(names_area, buf);
+    status_block.render(status_area, buf);
+
+    if state.comics.is_empty() {
+        return;
+    }
+
+    let visible_height = names_inner_area.height as usize;
+
+    state.selected = state.selected.min(state.comics.len() - 1);
+
+    // keep the selected row in view
+    if state.selected < state.scroll_offset {
+        state.scroll_offset = state.selected;
+    } else if state.selected >= state.scroll_offset + visible_height {
+        state.scroll_offset = state.selected + 1 - visible_height;
+    }
+
+    let max_scroll = state.comics.len().saturating_sub(visible_height);
+    if state.scroll_offset > max_scroll {
+        state.scroll_offset = max_scroll;
+    }
+
+    let visible_items = {
+        let end_idx = (state.scroll_offset + visible_height).min(state.comics.len());
+        &state.comics[state.scroll_offset..end_idx]
+    };
+
+    let names_layout =
+        Layout::vertical(vec![Constraint::Length(1); visible_items.len()]).split(names_inner_area);
+    let status_layout =
+        Layout::vertical(vec![Constraint::Length(1); visible_items.len()]).split(status_inner_area);
+
+    for (i, comic) in visible_items.iter().enumerate() {
+        let selected = state.scroll_offset + i == state.selected;
+        draw_file_title(buf, comic, names_layout[i], selected, theme);
+    }
+
+    for (i, comic) in visible_items.iter().enumerate() {
+        let left = estimator
+            .comic(comic)
+            .map(|left| format!(", {}", (state.strings.time_left)(&format_duration(left))));
+        let left = left.as_deref().unwrap_or_default();
+        draw_file_status(buf, comic, status_layout[i], theme, state.strings, left);
+    }
+
+    draw_scrollbar(
+        buf,
+        state,
+        scrollbar_area,
+        state.comics.len(),
+        visible_height,
+        theme,
+    );
+}
+
+fn draw_file_title(
+    buf: &mut Buffer,
+    comic_state: &ComicState,
+    area: Rect,
+    selected: bool,
+    theme: &Theme,
+) {
+    let style = if selected {
+        Style::default()
+            .fg(theme.content)
+            .add_modifier(Modifier::REVERSED)
+    } else {
+        Style::default().fg(theme.content)
+    };
+
+    Paragraph::new(comic_state.title.clone())
+        .style(style)
+        .alignment(Alignment::Left)
+        .block(Block::default().padding(Padding::horizontal(1)))
+        .render(area, buf);
+}
+
+fn draw_file_status(
+    buf: &mut Buffer,
+    comic_state: &ComicState,
+    area: Rect,
+    theme: &Theme,
+    strings: &Strings,
+    // the estimate of the time left, with its separator
+    left: &str,
+) {
+    let output_format = comic_state.output_format;
+    match comic_state.current_status() {
+        ComicStatus::Waiting => {
+            let label = Span::styled(strings.waiting, Style::default().fg(theme.content));
+            let gauge = Gauge::default()
+                .gauge_style(theme.border)
+                .ratio(0.0)
+                .label(label);
+
+            gauge.render(area, buf);
+        }
+        ComicStatus::Progress {
+            stage,
+            progress,
+            start,
+        } => {
+            let elapsed = start.elapsed();
+            let color = stage_color(*stage, theme);
+            let label = Span::styled(
+                format!("{} {:.1}s{left}", stage, elapsed.as_secs_f64()),
+                Style::default().fg(theme.gauge_label),
+            );
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .ratio(stage.progress(output_format, *progress / 100.0))
+                .label(label);
+
+            gauge.render(area, buf);
+        }
+        ComicStatus::Extracting { extracted } => {
+            let color = stage_color(ComicStage::Process, theme);
+            let progress_ratio = if comic_state.total_images > 0 {
+                *extracted as f64 / comic_state.total_images as f64
+            } else {
+                0.0
+            };
+            let label = Span::styled(
+                format!(
+                    "{:3}/{:3} {}",
+                    extracted, comic_state.total_images, strings.extracting
+                ),
+                Style::default().fg(theme.gauge_label),
+            );
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .ratio(progress_ratio)
+                .label(label);
+
+            gauge.render(area, buf);
+        }
+        ComicStatus::ImageProcessingStart { .. } | ComicStatus::ImageProcessed => {
+            let elapsed = comic_state
+                .image_processing_start
+                .map(|s| s.elapsed())
+                .unwrap_or_default();
+            let color = stage_color(ComicStage::Process, theme);
+            let progress_ratio = if comic_state.total_images > 0 {
+                let done = comic_state.images_processed as f64 / comic_state.total_images as f64;
+                ComicStage::Process.progress(output_format, done)
+            } else {
+                0.0
+            };
+            let label = Span::styled(
+                format!(
+                    "{:3}/{:3} images {:.1}s{left}",
+                    comic_state.images_processed,
+                    comic_state.total_images,
+                    elapsed.as_secs_f64()
+                ),
+                Style::default().fg(theme.gauge_label),
+            );
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .ratio(progress_ratio)
+                .label(label);
+
+            gauge.render(area, buf);
+        }
+        ComicStatus::StageCompleted { .. } | ComicStatus::Converting { .. } => {
+            unreachable!("not storing this status")
+        }
+        ComicStatus::Success => {
+            StageTimingBar::new(&comic_state.timings, output_format, theme)
+                .width(area.width)
+                .render(area, buf);
+        }
+        ComicStatus::Failed { error, .. } => {
+            let error_text = error.to_string();
+            let label = Span::styled(error_text, Style::default().fg(theme.content));
+
+            let gauge = Gauge::default()
+                .gauge_style(theme.error_bg)
+                .ratio(1.0)
+                .label(label);
+
+            gauge.render(area, buf);
+        }
+    }
+}
+
+fn draw_scrollbar(
+    buf: &mut Buffer,
+    state: &mut ProgressState,
+    area: Rect,
+    total_items: usize,
+    visible_height: usize,
+    theme: &Theme,
+) {
+    let show_scrollbar = total_items > visible_height;
+    if show_scrollbar {
+        let mut scroll_state = ratatui::widgets::ScrollbarState::default()
+            .content_length(total_items.saturating_sub(visible_height))
+            .position(state.scroll_offset);
+
+        StatefulWidget::render(
+            ratatui::widgets::Scrollbar::default()
+                .orientation(ratatui::widgets::ScrollbarOrientation::VerticalRight)
+                .style(Style::default().fg(theme.border))
+                .thumb_style(Style::default().fg(theme.scrollbar_thumb)),
+            area,
+            buf,
+            &mut scroll_state,
+        );
+    }
+}
+
+fn draw_footer(buf: &mut Buffer, state: &ProgressState, area: Rect, theme: &Theme) {
+    let show_scrollbar = !state.comics.is_empty();
+
+    let [controls_area, legend_area] =
+        Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(area);
+
+    let keymap = &state.keymap;
+    let t = state.strings;
+    let select = format!("{}: {}", keymap.keys(&[Action::Up, Action::Down]), t.select);
+    let queue = format!("enter: {} | r: {} | a: {}", t.details, t.retry, t.add);
+    let global = format!(
+        "{}: {} | {}: {}",
+        keymap.keys(&[Action::Theme]),
+        t.theme,
+        keymap.keys(&[Action::Quit]),
+        t.quit
+    );
+    let keys = if state.complete.is_some() {
+        format!("{select} | {queue} | m: {} | {global}", t.menu)
+    } else if show_scrollbar {
+        format!("{select} | J/K: {} | {queue} | {global}", t.reorder)
+    } else {
+        format!("a: {} | {global}", t.add)
+    };
+
+    let keys = Paragraph::new(keys)
+        .style(theme.content)
+        .alignment(ratatui::layout::Alignment::Center);
+
+    keys.render(controls_area, buf);
+
+    if !state.comics.is_empty() {
+        // every stage when any comic goes through KindleGen
+        let format = state
+            .comics
+            .iter()
+            .map(|comic| comic.output_format)
+            .find(|&format| format == OutputFormat::Mobi)
+            .unwrap_or(state.output_format);
+        draw_stage_legend(buf, legend_area, theme, format);
+    }
+}
+
+fn draw_stage_legend(buf: &mut Buffer, area: Rect, theme: &Theme, output_format: OutputFormat) {
+    let stages = ComicStage::for_format(output_format);
+
+    let constraints = vec![Constraint::Length(16); stages.len()];
+
+    let layout = Layout::horizontal(constraints).flex(ratatui::layout::Flex::Start);
+
+    let areas = layout.split(area);
+
+    for (i, &stage) in stages.iter().enumerate() {
+        let color = stage_color(stage, theme);
+
+        let [block_area, text_area] =
+            Layout::horizontal([Constraint::Length(2), Constraint::Fill(1)]).areas(areas[i]);
+
+        buf.set_style(block_area, Style::default().bg(color));
+
+        Paragraph::new(stage.to_string())
+            .style(theme.content)
+            .alignment(Alignment::Left)
+            .block(Block::default().padding(Padding::horizontal(1)))
+            .render(text_area, buf);
+    }
+}
+
+fn stage_color(stage: ComicStage, theme: &Theme) -> Color {
+    match stage {
+        ComicStage::Process => theme.stage_colors.process,
+        ComicStage::Package => theme.stage_colors.epub,
+        ComicStage::Convert => theme.stage_colors.mobi,
+    }
+}
+
+struct StageTimingBar<'a> {
+    timing: &'a StageTimings,
+    // one segment per stage of the format, in order
+    output_format: OutputFormat,
+    theme: &'a Theme,
+    width: u16,
+}
+
+impl<'a> StageTimingBar<'a> {
+    fn new(timing: &'a StageTimings, output_format: OutputFormat, theme: &'a Theme) -> Self {
+        Self {
+            timing,
+            output_format,
+            theme,
+            width: 0,
+        }
+    }
+
+    fn width(mut self, width: u16) -> Self {
+        self.width = width;
+        self
+    }
+}
+
+impl<'a> Widget for StageTimingBar<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let total = self.timing.total().as_secs_f64();
+        if total == 0.0 || self.width == 0 {
+            return;
+        }
+
+        let horizontal = Layout::horizontal([Constraint::Fill(4), Constraint::Length(15)])
+            .flex(ratatui::layout::Flex::Start)
+            .split(area);
+
+        let bar_area = horizontal[0];
+        let total_label_area = horizontal[1];
+
+        let stages: Vec<(ComicStage, Duration)> = ComicStage::for_format(self.output_format)
+            .iter()
+            .map(|&stage| (stage, self.timing.of(stage)))
+            .filter(|(_, duration)| !duration.is_zero())
+            .collect();
+        if !stages.is_empty() {
+            // Create Fill constraints proportional to each stage's duration
+            let constraints: Vec<Constraint> = stages
+                .iter()
+                .map(|(_, duration)| {
+                    Constraint::Fill((duration.as_secs_f64() / total * 100.0).round() as u16)
+                })
+                .collect();
+
+            let stage_areas = Layout::horizontal(&constraints)
+                .flex(ratatui::layout::Flex::Start)
+                .split(bar_area);
+
+            for ((stage, duration), area) in stages.iter().zip(stage_areas.iter()) {
+                let color = stage_color(*stage, self.theme);
+
+                buf.set_style(*area, Style::default().bg(color));
+
+                if area.width >= 10 {
+                    let label = format!("{:.1}s", duration.as_secs_f64());
+
+                    Paragraph::new(label)
+                        .style(Style::default().fg(self.theme.gauge_label))
+                        .alignment(ratatui::layout::Alignment::Center)
+                        .render(*area, buf);
+                }
+            }
+        }
+
+        let total_label = format!("{:.1}s", total);
+
+        Paragraph::new(total_label)
+            .style(
+                Style::default()
+                    .fg(self.theme.gauge_label)
+                    .bg(self.theme.primary_bg)
+                    .bold(),
+            )
+            .alignment(ratatui::layout::Alignment::Center)
+            .render(total_label_area, buf);
+    }
+}
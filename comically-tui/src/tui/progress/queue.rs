@@ -0,0 +1,119 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget},
+};
+
+use comically::ComicFile;
+
+use crate::tui::{i18n::Strings, utils::popup_block, Theme};
+
+pub struct AddFilesState {
+    pub files: Vec<(ComicFile, bool)>,
+    pub list_state: ListState,
+}
+
+impl AddFilesState {
+    pub fn new(files: Vec<ComicFile>) -> Self {
+        let files: Vec<(ComicFile, bool)> = files.into_iter().map(|f| (f, false)).collect();
+
+        let mut list_state = ListState::default();
+        if !files.is_empty() {
+            list_state.select(Some(0));
+        }
+
+        Self { files, list_state }
+    }
+
+    // returns the checked files, or the focused one if nothing was checked
+    pub fn confirm_selection(&mut self) -> Vec<ComicFile> {
+        let checked: Vec<ComicFile> = self
+            .files
+            .iter()
+            .filter(|(_, checked)| *checked)
+            .map(|(file, _)| file.clone())
+            .collect();
+
+        if !checked.is_empty() {
+            return checked;
+        }
+
+        self.list_state
+            .selected()
+            .and_then(|selected| self.files.get(selected))
+            .map(|(file, _)| vec![file.clone()])
+            .unwrap_or_default()
+    }
+
+    pub fn select_next(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected + 1 < self.files.len() {
+                self.list_state.select(Some(selected + 1));
+            }
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected > 0 {
+                self.list_state.select(Some(selected - 1));
+            }
+        }
+    }
+
+    pub fn toggle_selected(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            self.files[selected].1 = !self.files[selected].1;
+        }
+    }
+}
+
+pub fn render_add_files_popup(
+    area: Rect,
+    buf: &mut Buffer,
+    theme: &Theme,
+    strings: &Strings,
+    state: &mut AddFilesState,
+) {
+    let popup_width = 60.min(area.width * 3 / 4);
+    let popup_height = 20.min(area.height * 3 / 4);
+
+    let popup_x = area.left() + (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = area.top() + (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(strings.add_to_queue, theme).title(Line::from("[esc]").right_aligned());
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let [list_area, hint_area] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+
+    let items: Vec<ListItem> = state
+        .files
+        .iter()
+        .map(|(file, checked)| {
+            let checkbox = if *checked { "[✓]" } else { "[ ]" };
+            ListItem::new(format!("{} {}", checkbox, file.title())).style(theme.content)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    StatefulWidget::render(list, list_area, buf, &mut state.list_state);
+
+    Paragraph::new(format!(
+        "space: {} | enter: {}",
+        strings.toggle, strings.queue
+    ))
+    .style(Style::default().fg(theme.accent))
+    .alignment(Alignment::Center)
+    .render(hint_area, buf);
+}
@@ -0,0 +1,67 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Position, Rect},
+    style::Style,
+    widgets::Widget,
+};
+
+use crate::tui::Theme;
+
+/// A one-line track with a knob, `ratio` is the knob position from 0.0 to 1.0
+pub struct Slider {
+    ratio: f64,
+    theme: Theme,
+    active: bool,
+}
+
+impl Slider {
+    pub fn new(ratio: f64, theme: Theme) -> Self {
+        Self {
+            ratio: ratio.clamp(0.0, 1.0),
+            theme,
+            active: false,
+        }
+    }
+
+    pub fn active(mut self, active: bool) -> Self {
+        self.active = active;
+        self
+    }
+
+    /// Ratio under the given column of a track rendered in `area`, clamped to the track ends
+    pub fn ratio_at(area: Rect, column: u16) -> f64 {
+        if area.width <= 1 {
+            return 0.0;
+        }
+        let offset = column.saturating_sub(area.x).min(area.width - 1);
+        offset as f64 / (area.width - 1) as f64
+    }
+}
+
+impl Widget for Slider {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        let knob = (self.ratio * (area.width - 1) as f64).round() as u16;
+        let filled = if self.active {
+            self.theme.accent
+        } else {
+            self.theme.primary
+        };
+
+        for i in 0..area.width {
+            let (symbol, color) = match i.cmp(&knob) {
+                std::cmp::Ordering::Less => ("━", filled),
+                std::cmp::Ordering::Equal => ("●", filled),
+                std::cmp::Ordering::Greater => ("─", self.theme.border),
+            };
+
+            if let Some(cell) = buf.cell_mut(Position::new(area.x + i, area.y)) {
+                cell.set_symbol(symbol)
+                    .set_style(Style::default().fg(color));
+            }
+        }
+    }
+}
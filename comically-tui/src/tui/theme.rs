@@ -1,12 +1,33 @@
 use ratatui::style::{palette, Color};
+use serde::{Deserialize, Serialize};
 use supports_color::Stream;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ThemeMode {
+    #[default]
     Dark,
     Light,
 }
 
+/// A user-defined theme from the config file.
+///
+/// Starts from the built-in theme of `base` and overrides the colors listed in `colors`,
+/// keyed by [`Theme`] field name (`stage_process`, `stage_mobi` and `stage_epub` for the
+/// stage colors). Values are anything ratatui can parse: `#rrggbb`, color names or
+/// 256-color indices.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    #[serde(default)]
+    pub base: ThemeMode,
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ColorCapability {
     TrueColor,
@@ -191,6 +212,64 @@ impl Theme {
 }
 
 impl Theme {
+    pub const BUILT_IN: [&str; 2] = ["dark", "light"];
+
+    /// Built-in theme or user palette with the given name, keeping the terminal's color capability
+    pub fn by_name(&self, name: &str, palettes: &[Palette]) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark_with_capability(self.color_capability)),
+            "light" => Some(Self::light_with_capability(self.color_capability)),
+            _ => palettes
+                .iter()
+                .find(|palette| palette.name == name)
+                .map(|palette| self.with_palette(palette)),
+        }
+    }
+
+    fn with_palette(&self, palette: &Palette) -> Self {
+        let mut theme = match palette.base {
+            ThemeMode::Dark => Self::dark_with_capability(self.color_capability),
+            ThemeMode::Light => Self::light_with_capability(self.color_capability),
+        };
+
+        for (key, value) in &palette.colors {
+            let Some(slot) = theme.color_mut(key) else {
+                log::warn!("unknown color in theme {}: {key}", palette.name);
+                continue;
+            };
+            match Color::from_str(value) {
+                Ok(color) => *slot = self.adapt_color(color),
+                Err(_) => log::warn!("invalid color in theme {}: {value}", palette.name),
+            }
+        }
+
+        theme
+    }
+
+    fn color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        let color = match name {
+            "border" => &mut self.border,
+            "content" => &mut self.content,
+            "background" => &mut self.background,
+            "accent" => &mut self.accent,
+            "primary" => &mut self.primary,
+            "primary_bg" => &mut self.primary_bg,
+            "primary_pressed" => &mut self.primary_pressed,
+            "secondary" => &mut self.secondary,
+            "secondary_bg" => &mut self.secondary_bg,
+            "secondary_pressed" => &mut self.secondary_pressed,
+            "error_bg" => &mut self.error_bg,
+            "scrollbar_thumb" => &mut self.scrollbar_thumb,
+            "gauge_label" => &mut self.gauge_label,
+            "muted" => &mut self.muted,
+            "stage_process" => &mut self.stage_colors.process,
+            "stage_mobi" => &mut self.stage_colors.mobi,
+            "stage_epub" => &mut self.stage_colors.epub,
+            _ => return None,
+        };
+        Some(color)
+    }
+
     pub fn is_dark(&self) -> bool {
         self.mode == ThemeMode::Dark
     }
@@ -0,0 +1,118 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::tui::{
+    i18n::Strings,
+    theme::{Palette, Theme},
+    utils::popup_block,
+};
+
+pub struct ThemePickerState {
+    pub names: Vec<String>,
+    pub palettes: Vec<Palette>,
+    pub list_state: ListState,
+    // restored when the picker is cancelled, since moving through the list previews each theme
+    pub original: Theme,
+}
+
+impl ThemePickerState {
+    pub fn new(palettes: Vec<Palette>, current: Option<&str>, original: Theme) -> Self {
+        let names: Vec<String> = Theme::BUILT_IN
+            .iter()
+            .map(|name| name.to_string())
+            .chain(palettes.iter().map(|palette| palette.name.clone()))
+            .collect();
+
+        let selected = current
+            .and_then(|current| names.iter().position(|name| name == current))
+            .unwrap_or(if original.is_dark() { 0 } else { 1 });
+
+        let mut list_state = ListState::default();
+        list_state.select(Some(selected));
+
+        Self {
+            names,
+            palettes,
+            list_state,
+            original,
+        }
+    }
+
+    pub fn selected_name(&self) -> Option<&str> {
+        self.list_state
+            .selected()
+            .and_then(|i| self.names.get(i))
+            .map(String::as_str)
+    }
+
+    pub fn selected_theme(&self) -> Option<Theme> {
+        self.selected_name()
+            .and_then(|name| self.original.by_name(name, &self.palettes))
+    }
+
+    pub fn select_next(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected + 1 < self.names.len() {
+                self.list_state.select(Some(selected + 1));
+            }
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if let Some(selected) = self.list_state.selected() {
+            if selected > 0 {
+                self.list_state.select(Some(selected - 1));
+            }
+        }
+    }
+}
+
+pub fn render_theme_picker(
+    area: Rect,
+    buf: &mut Buffer,
+    theme: &Theme,
+    strings: &Strings,
+    state: &mut ThemePickerState,
+) {
+    let popup_width = 40.min(area.width * 3 / 4);
+    let popup_height = (state.names.len() as u16 + 4).clamp(6, 20.min(area.height * 3 / 4));
+
+    let popup_x = area.left() + (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = area.top() + (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    Clear.render(popup_area, buf);
+
+    let block = popup_block(strings.themes, theme).title(Line::from("[esc]").right_aligned());
+    let inner = block.inner(popup_area);
+    block.render(popup_area, buf);
+
+    let [list_area, hint_area] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+
+    let items: Vec<ListItem> = state
+        .names
+        .iter()
+        .map(|name| ListItem::new(name.as_str()).style(theme.content))
+        .collect();
+
+    let list = List::new(items)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    StatefulWidget::render(list, list_area, buf, &mut state.list_state);
+
+    Paragraph::new(format!(
+        "enter: {} | esc: {}",
+        strings.apply, strings.cancel
+    ))
+    .style(Style::default().fg(theme.accent))
+    .alignment(Alignment::Center)
+    .render(hint_area, buf);
+}
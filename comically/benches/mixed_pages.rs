@@ -0,0 +1,88 @@
+//! Processing an archive that mixes double-page spreads with single pages, the spreads
+//! at the end like the color inserts of many volumes.
+//!
+//! Spreads take about three times longer (rotated, then split in two), so splitting the
+//! pages into one fixed chunk per thread leaves most threads idle while the last chunk
+//! works through them. The pipeline schedules one task per page, the largest first.
+//!
+//! Both schedules run for real in rayon pools of 2 to 16 threads. The gain depends on
+//! the cores of the machine: pools with more threads than cores only share them.
+//!
+//! ```sh
+//! cargo bench -p comically --features test-utils --bench mixed_pages
+//! ```
+
+use std::time::{Duration, Instant};
+
+use comically::archive::ArchiveFile;
+use comically::image::process_batch;
+use comically::testing::sample_page;
+use comically::ComicConfig;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+const RUNS: usize = 3;
+
+const THREADS: [usize; 4] = [2, 4, 8, 16];
+
+fn main() {
+    let mut files: Vec<ArchiveFile> = (0..48)
+        .map(|i| ArchiveFile {
+            file_name: format!("{i:03}.jpg").into(),
+            data: sample_page(1200, 1700, i),
+        })
+        .collect();
+    files.extend((48..56).map(|i| ArchiveFile {
+        file_name: format!("{i:03}.jpg").into(),
+        data: sample_page(2400, 1700, i),
+    }));
+    let config = ComicConfig {
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+
+    println!(
+        "{} pages, {} of them spreads",
+        files.len(),
+        files.len() - 48
+    );
+
+    println!("threads   chunked   per page   speedup");
+    for threads in THREADS {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let chunked = median(|| pool.install(|| chunked(&files, &config)));
+        let per_page = median(|| {
+            pool.install(|| assert!(!process_batch(files.clone(), &config).unwrap().is_empty()))
+        });
+        println!(
+            "{threads:>7}   {chunked:>7.1?}   {per_page:>8.1?}   {:>6.2}x",
+            chunked.as_secs_f64() / per_page.as_secs_f64()
+        );
+    }
+}
+
+// what the pipeline used to do: the pages in archive order, split by rayon into about
+// one run of pages per thread
+fn chunked(files: &[ArchiveFile], config: &ComicConfig) {
+    let pages: usize = files
+        .par_iter()
+        .map(|file| process_batch(vec![file.clone()], config).unwrap().len())
+        .sum();
+    assert!(pages > 0);
+}
+
+// of a few runs, after a warm-up one
+fn median(mut run: impl FnMut()) -> Duration {
+    run();
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
@@ -1,12 +1,16 @@
 use anyhow::Context;
 use unrar::Archive;
+use zip::read::ZipFile;
 use zip::{HasZipMetadata, ZipArchive};
 
+use std::cmp::Ordering;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::{Path, PathBuf};
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
 
-use crate::comic::ArchiveExt;
+mod shift_jis;
+
+use crate::comic::{ArchiveExt, PageRanges};
 use crate::ComicFile;
 
 #[derive(Debug, Clone)]
@@ -25,21 +29,162 @@ impl ArchiveFile {
     }
 }
 
-pub enum ArchiveIter {
+/// Reported by [`ArchiveIter`] after each image is read from the archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractProgress {
+    pub extracted: usize,
+    pub total: usize,
+}
+
+/// How archives are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveOptions {
+    /// Read the pages of zip/cbz archives found inside a zip/cbz, one level deep.
+    /// Archives nested in RAR files are always skipped.
+    pub nested_archives: bool,
+    pub filename_encoding: FilenameEncoding,
+}
+
+/// How the names of zip entries written without the UTF-8 flag are read. Zips made on
+/// Japanese versions of Windows name their entries in Shift-JIS, which comes out garbled
+/// when read as the CP437 the zip format specifies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum FilenameEncoding {
+    /// The first of UTF-8, Shift-JIS and CP437 every name of the archive is valid in, so
+    /// the pages of a folder all get the same folder name
+    #[default]
+    Auto,
+    /// For zips made by tools that leave out the flag, invalid names are read as CP437
+    Utf8,
+    /// Invalid names are read as CP437
+    ShiftJis,
+    Cp437,
+}
+
+impl FilenameEncoding {
+    // the encoding of the names of `archive`, picked once for all of them
+    fn detect<R: Read + Seek>(self, archive: &mut ZipArchive<R>) -> Self {
+        if self != FilenameEncoding::Auto {
+            return self;
+        }
+
+        let mut names = Vec::new();
+        for index in 0..archive.len() {
+            let Ok(file) = archive.by_index_raw(index) else {
+                continue;
+            };
+            if !file.get_metadata().is_utf8 && !file.name_raw().is_ascii() {
+                names.push(file.name_raw().to_vec());
+            }
+        }
+        let detected = if names.iter().all(|name| std::str::from_utf8(name).is_ok()) {
+            FilenameEncoding::Utf8
+        } else if names.iter().all(|name| shift_jis::decode(name).is_some()) {
+            FilenameEncoding::ShiftJis
+        } else {
+            FilenameEncoding::Cp437
+        };
+        if !names.is_empty() {
+            log::debug!("Reading the names of zip entries as {detected:?}");
+        }
+        detected
+    }
+}
+
+/// Why an entry of an archive isn't one of its pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Hidden files and OS metadata such as `__MACOSX` or `Thumbs.db`
+    System,
+    /// Paths escaping the archive, e.g. `../page.jpg`
+    UnsafePath,
+    /// Anything but an image comically reads, e.g. `ComicInfo.xml` or text files
+    NotAnImage,
+    /// An archive inside the archive, see [`ArchiveOptions::nested_archives`]
+    NestedArchive,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            SkipReason::System => "system file",
+            SkipReason::UnsafePath => "unsafe path",
+            SkipReason::NotAnImage => "not an image",
+            SkipReason::NestedArchive => "nested archive",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// Reads the images of an archive one at a time, in archive order
+pub struct ArchiveIter {
+    reader: Reader,
+    skipped: Vec<SkippedEntry>,
+    extracted: usize,
+    on_progress: Option<Box<dyn FnMut(ExtractProgress) + Send>>,
+}
+
+enum Reader {
     Zip(ZipReader),
     Rar(RarReader),
 }
 
 impl ArchiveIter {
+    /// Number of images the iterator yields, known from the archive's listing
     pub fn num_images(&self) -> usize {
-        match self {
-            ArchiveIter::Zip(reader) => reader
-                .archive
-                .file_names()
-                .filter(|name| validate_file(name).is_some())
-                .count(),
-            ArchiveIter::Rar(reader) => reader.files.len(),
+        self.images().len()
+    }
+
+    /// Paths of the images in the archive, in the order they are yielded
+    pub fn images(&self) -> &[PathBuf] {
+        match &self.reader {
+            Reader::Zip(reader) => &reader.names,
+            Reader::Rar(reader) => &reader.files,
+        }
+    }
+
+    /// Entries that aren't pages, and why
+    pub fn skipped(&self) -> &[SkippedEntry] {
+        &self.skipped
+    }
+
+    /// Keeps the images in `ranges`, numbered from 1 in the natural order of their paths
+    pub fn select(mut self, ranges: &PageRanges) -> Self {
+        let names = self.images();
+        let mut order: Vec<usize> = (0..names.len()).collect();
+        order.sort_by(|&a, &b| natural_cmp(&names[a], &names[b]));
+        let mut keep = vec![false; names.len()];
+        for (page, index) in order.into_iter().enumerate() {
+            keep[index] = ranges.contains(page + 1);
+        }
+
+        match &mut self.reader {
+            Reader::Zip(reader) => {
+                let mut selected = keep.iter();
+                reader.entries.retain(|_| *selected.next().unwrap());
+                let mut selected = keep.iter();
+                reader.names.retain(|_| *selected.next().unwrap());
+            }
+            Reader::Rar(reader) => {
+                let mut selected = keep.iter();
+                reader.files.retain(|_| *selected.next().unwrap());
+            }
         }
+        self
+    }
+
+    /// Calls `on_progress` after each image is extracted
+    pub fn with_progress(
+        mut self,
+        on_progress: impl FnMut(ExtractProgress) + Send + 'static,
+    ) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
     }
 }
 
@@ -47,82 +192,201 @@ impl Iterator for ArchiveIter {
     type Item = anyhow::Result<ArchiveFile>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self {
-            ArchiveIter::Zip(reader) => reader.next(),
-            ArchiveIter::Rar(reader) => reader.next(),
+        let item = match &mut self.reader {
+            Reader::Zip(reader) => reader.next(),
+            Reader::Rar(reader) => reader.next(),
+        }?;
+
+        self.extracted += 1;
+        let total = self.num_images();
+        if let Some(on_progress) = &mut self.on_progress {
+            on_progress(ExtractProgress {
+                extracted: self.extracted,
+                total,
+            });
         }
+        Some(item)
     }
 }
 
 pub fn unarchive_comic_iter(comic_file: &ComicFile) -> anyhow::Result<ArchiveIter> {
+    unarchive_comic_iter_with(comic_file, ArchiveOptions::default())
+}
+
+pub fn unarchive_comic_iter_with(
+    comic_file: &ComicFile,
+    options: ArchiveOptions,
+) -> anyhow::Result<ArchiveIter> {
+    let mut skipped = Vec::new();
     let reader = match comic_file.extension() {
         ArchiveExt::Cbz | ArchiveExt::Zip => {
-            let file = File::open(comic_file.as_path()).context("Failed to open zip file")?;
-            ArchiveIter::Zip(ZipReader::new(file)?)
+            let file = File::open(crate::output::long_path(comic_file.as_path()))
+                .context("Failed to open zip file")?;
+            Reader::Zip(ZipReader::new(file, options, &mut skipped)?)
         }
         ArchiveExt::Cbr | ArchiveExt::Rar => {
-            ArchiveIter::Rar(RarReader::new(comic_file.as_path())?)
+            Reader::Rar(RarReader::new(comic_file.as_path(), &mut skipped)?)
         }
     };
 
-    Ok(reader)
+    Ok(ArchiveIter {
+        reader,
+        skipped,
+        extracted: 0,
+        on_progress: None,
+    })
 }
 
-pub struct ZipReader {
-    index: usize,
+/// Paths of the images in an archive, without decompressing any of them
+pub fn list_images(comic_file: &ComicFile) -> anyhow::Result<Vec<PathBuf>> {
+    let iter = unarchive_comic_iter(comic_file)?;
+    Ok(iter.images().to_vec())
+}
+
+enum ZipEntry {
+    Outer(usize),
+    // index in `ZipReader::nested`, then in that archive
+    Nested(usize, usize),
+}
+
+struct ZipReader {
     archive: ZipArchive<BufReader<File>>,
+    nested: Vec<ZipArchive<Cursor<Vec<u8>>>>,
+    // every image, listed up front from the central directories
+    entries: Vec<ZipEntry>,
+    names: Vec<PathBuf>,
+    next: usize,
 }
 
 impl ZipReader {
-    fn new(file: File) -> anyhow::Result<Self> {
+    fn new(
+        file: File,
+        options: ArchiveOptions,
+        skipped: &mut Vec<SkippedEntry>,
+    ) -> anyhow::Result<Self> {
         let reader = BufReader::new(file);
-        let archive = ZipArchive::new(reader).context("Failed to parse file as zip archive")?;
-        Ok(Self { index: 0, archive })
-    }
-}
+        let mut archive = ZipArchive::new(reader).context("Failed to parse file as zip archive")?;
+        let encoding = options.filename_encoding.detect(&mut archive);
+        let mut reader = Self {
+            archive,
+            nested: Vec::new(),
+            entries: Vec::new(),
+            names: Vec::new(),
+            next: 0,
+        };
 
-impl Iterator for ZipReader {
-    type Item = anyhow::Result<ArchiveFile>;
+        for index in 0..reader.archive.len() {
+            let file = reader.archive.by_index_raw(index)?;
+            if file.is_dir() {
+                continue;
+            }
+            let Some(path) = entry_path(&file, encoding) else {
+                skipped.push(SkippedEntry {
+                    path: PathBuf::from(file.name()),
+                    reason: SkipReason::UnsafePath,
+                });
+                continue;
+            };
+            drop(file);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        while self.index < self.archive.len() {
-            let current_index = self.index;
-            self.index += 1;
+            match classify(&path) {
+                Ok(()) => {
+                    reader.entries.push(ZipEntry::Outer(index));
+                    reader.names.push(path);
+                }
+                Err(SkipReason::NestedArchive) if options.nested_archives => {
+                    if let Err(e) =
+                        reader.add_nested(index, &path, options.filename_encoding, skipped)
+                    {
+                        log::warn!("Failed to read nested archive {}: {e}", path.display());
+                        skipped.push(SkippedEntry {
+                            path,
+                            reason: SkipReason::NestedArchive,
+                        });
+                    }
+                }
+                Err(reason) => skipped.push(SkippedEntry { path, reason }),
+            }
+        }
 
-            let mut file = match self.archive.by_index(current_index) {
-                Ok(f) => f,
-                Err(e) => return Some(Err(e.into())),
-            };
+        Ok(reader)
+    }
 
+    // pages of a nested archive are named after it, e.g. `extras.cbz/01.jpg` becomes
+    // `extras/01.jpg`, and archives nested any deeper are skipped
+    fn add_nested(
+        &mut self,
+        index: usize,
+        path: &Path,
+        encoding: FilenameEncoding,
+        skipped: &mut Vec<SkippedEntry>,
+    ) -> anyhow::Result<()> {
+        let data = read_zip_entry(&mut self.archive, index)?;
+        let mut nested = ZipArchive::new(Cursor::new(data))?;
+        // made separately from the outer archive, maybe on another system
+        let encoding = encoding.detect(&mut nested);
+        let prefix = path.with_extension("");
+
+        let nested_index = self.nested.len();
+        for inner in 0..nested.len() {
+            let file = nested.by_index_raw(inner)?;
             if file.is_dir() {
                 continue;
             }
-
-            let outpath = match file.enclosed_name() {
-                Some(path) => path.to_owned(),
-                None => continue,
-            };
-
-            let file_name = match validate_file(&outpath) {
-                Some(name) => name,
-                None => continue,
+            let (inner_path, classified) = match entry_path(&file, encoding) {
+                Some(inner_path) => {
+                    let classified = classify(&inner_path);
+                    (prefix.join(inner_path), classified)
+                }
+                None => (path.join(file.name()), Err(SkipReason::UnsafePath)),
             };
 
-            let mut data = Vec::with_capacity(file.get_metadata().uncompressed_size as usize);
-            if let Err(e) = Read::read_to_end(&mut file, &mut data) {
-                return Some(Err(e.into()));
+            match classified {
+                Ok(()) => {
+                    self.entries.push(ZipEntry::Nested(nested_index, inner));
+                    self.names.push(inner_path);
+                }
+                Err(reason) => skipped.push(SkippedEntry {
+                    path: inner_path,
+                    reason,
+                }),
             }
-
-            return Some(Ok(ArchiveFile { file_name, data }));
         }
+        self.nested.push(nested);
+
+        Ok(())
+    }
+}
+
+fn read_zip_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let mut file = archive.by_index(index)?;
+    let mut data = Vec::with_capacity(file.get_metadata().uncompressed_size as usize);
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+impl Iterator for ZipReader {
+    type Item = anyhow::Result<ArchiveFile>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.get(self.next)?;
+        let file_name = self.names[self.next].clone();
+        self.next += 1;
 
-        None
+        let data = match *entry {
+            ZipEntry::Outer(index) => read_zip_entry(&mut self.archive, index),
+            ZipEntry::Nested(archive, index) => read_zip_entry(&mut self.nested[archive], index),
+        };
+        Some(data.map(|data| ArchiveFile { file_name, data }))
     }
 }
 
-pub struct RarReader {
+struct RarReader {
     archive: Option<unrar::OpenArchive<unrar::Process, unrar::CursorBeforeHeader>>,
-    files: Vec<unrar::FileHeader>,
+    files: Vec<PathBuf>,
     finished: bool,
 }
 
@@ -130,14 +394,23 @@ pub struct RarReader {
 unsafe impl Send for RarReader {}
 
 impl RarReader {
-    fn new(path: &Path) -> anyhow::Result<Self> {
-        let files: Vec<unrar::FileHeader> = Archive::new(path)
+    fn new(path: &Path, skipped: &mut Vec<SkippedEntry>) -> anyhow::Result<Self> {
+        let mut files = Vec::new();
+        let listing = Archive::new(path)
             .open_for_listing()
-            .context("Failed to open RAR file")?
-            .filter_map(|header| header.ok())
-            .filter(|header| !header.is_directory())
-            .filter(|header| validate_file(&header.filename).is_some())
-            .collect();
+            .context("Failed to open RAR file")?;
+        for header in listing.filter_map(|header| header.ok()) {
+            if header.is_directory() {
+                continue;
+            }
+            match classify(&header.filename) {
+                Ok(()) => files.push(header.filename),
+                Err(reason) => skipped.push(SkippedEntry {
+                    path: header.filename,
+                    reason,
+                }),
+            }
+        }
 
         let archive = Archive::new(path)
             .open_for_processing()
@@ -179,7 +452,10 @@ impl Iterator for RarReader {
                     return self.next();
                 }
 
-                let Some(file_name) = validate_file(file_path) else {
+                // pages left out by `ArchiveIter::select` are skipped too
+                let Some(file_name) =
+                    validate_file(file_path).filter(|file_name| self.files.contains(file_name))
+                else {
                     let Ok(archive) = header.skip() else {
                         return None;
                     };
@@ -187,8 +463,12 @@ impl Iterator for RarReader {
                     return self.next();
                 };
 
-                let Ok((data, new_archive)) = header.read() else {
-                    return None;
+                let (data, new_archive) = match header.read() {
+                    Ok(read) => read,
+                    Err(e) => {
+                        self.finished = true;
+                        return Some(Err(e.into()));
+                    }
                 };
                 self.archive = Some(new_archive);
 
@@ -202,16 +482,236 @@ impl Iterator for RarReader {
     }
 }
 
-fn validate_file(path: impl AsRef<Path>) -> Option<PathBuf> {
-    let path = path.as_ref();
-    let file_name = path.file_name()?;
-    let file_name = file_name.to_string_lossy();
-    if should_skip_file(&file_name) || !has_image_extension(path) {
+/// Orders paths the way pages are numbered, `2.jpg` before `10.jpg`
+pub fn natural_cmp(a: &Path, b: &Path) -> Ordering {
+    let (a, b) = (a.to_string_lossy(), b.to_string_lossy());
+    let (mut a, mut b) = (a.as_ref(), b.as_ref());
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (number_a, rest_a) =
+                a.split_at(a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len()));
+            let (number_b, rest_b) =
+                b.split_at(b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len()));
+            let (trimmed_a, trimmed_b) = (
+                number_a.trim_start_matches('0'),
+                number_b.trim_start_matches('0'),
+            );
+            // by value, then `01` after `1`
+            let ordering = trimmed_a
+                .len()
+                .cmp(&trimmed_b.len())
+                .then_with(|| trimmed_a.cmp(trimmed_b))
+                .then_with(|| number_a.len().cmp(&number_b.len()));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            (a, b) = (rest_a, rest_b);
+        } else if x != y {
+            return x.cmp(&y);
+        } else {
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+        }
+    }
+}
+
+/// Reads only the first page of the archive, in file name order, without
+/// decompressing the rest
+pub fn read_first_image(comic_file: &ComicFile) -> anyhow::Result<ArchiveFile> {
+    match comic_file.extension() {
+        ArchiveExt::Cbz | ArchiveExt::Zip => {
+            let file = File::open(crate::output::long_path(comic_file.as_path()))
+                .context("Failed to open zip file")?;
+            let mut archive = ZipArchive::new(BufReader::new(file))
+                .context("Failed to parse file as zip archive")?;
+
+            let encoding = FilenameEncoding::Auto.detect(&mut archive);
+            let mut pages = Vec::new();
+            for index in 0..archive.len() {
+                let file = archive.by_index_raw(index)?;
+                let path = entry_path(&file, encoding).and_then(validate_file);
+                pages.extend(path.map(|path| (index, path)));
+            }
+            let (index, file_name) = pages
+                .into_iter()
+                .min_by(|a, b| a.1.cmp(&b.1))
+                .context("No images in archive")?;
+
+            let mut entry = archive.by_index(index)?;
+            let mut data = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut data)?;
+            Ok(ArchiveFile { file_name, data })
+        }
+        ArchiveExt::Cbr | ArchiveExt::Rar => {
+            let first = Archive::new(comic_file.as_path())
+                .open_for_listing()
+                .context("Failed to open RAR file")?
+                .filter_map(|header| header.ok())
+                .filter(|header| !header.is_directory())
+                .filter_map(|header| validate_file(&header.filename))
+                .min()
+                .context("No images in archive")?;
+
+            let mut archive = Archive::new(comic_file.as_path())
+                .open_for_processing()
+                .context("Failed to open RAR file")?;
+            while let Some(header) = archive.read_header()? {
+                if header.entry().filename == first {
+                    let (data, _) = header.read()?;
+                    return Ok(ArchiveFile {
+                        file_name: first,
+                        data,
+                    });
+                }
+                archive = header.skip()?;
+            }
+            anyhow::bail!("Failed to read {}", first.display())
+        }
+    }
+}
+
+/// Path of a zip entry with its name decoded as `encoding` says when it was written
+/// without the UTF-8 flag, `None` when it escapes the archive. [`FilenameEncoding::Auto`]
+/// decides for each name, for entries read one by one without a listing.
+pub(crate) fn entry_path<R: Read>(
+    file: &ZipFile<'_, R>,
+    encoding: FilenameEncoding,
+) -> Option<PathBuf> {
+    let raw = file.name_raw();
+    if file.get_metadata().is_utf8 || raw.is_ascii() {
+        return file.enclosed_name();
+    }
+
+    let decoded = match encoding {
+        FilenameEncoding::Auto => std::str::from_utf8(raw)
+            .ok()
+            .map(str::to_string)
+            .or_else(|| shift_jis::decode(raw)),
+        FilenameEncoding::Utf8 => std::str::from_utf8(raw).ok().map(str::to_string),
+        FilenameEncoding::ShiftJis => shift_jis::decode(raw),
+        FilenameEncoding::Cp437 => None,
+    };
+    match decoded {
+        Some(name) => enclosed(&name),
+        // already read as CP437 by the zip crate
+        None => file.enclosed_name(),
+    }
+}
+
+// like `ZipFile::enclosed_name`, for names decoded here
+fn enclosed(name: &str) -> Option<PathBuf> {
+    if name.contains('\0') {
         return None;
     }
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !path.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+/// Value of a field of a `ComicInfo.xml`. The format is flat, a full XML parser would
+/// be overkill
+pub(crate) fn comic_info_field<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(xml[start..end].trim())
+}
+
+/// Text of a `ComicInfo.xml` field with its entities decoded
+pub(crate) fn comic_info_text(xml: &str, tag: &str) -> Option<String> {
+    let text = comic_info_field(xml, tag)?
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+    Some(text)
+}
+
+/// Contents of the archive's `ComicInfo.xml`, if it has one
+pub fn read_comic_info(comic_file: &ComicFile) -> Option<String> {
+    let is_comic_info = |name: &Path| {
+        name.file_name()
+            .is_some_and(|name| name.eq_ignore_ascii_case("comicinfo.xml"))
+    };
+
+    match comic_file.extension() {
+        ArchiveExt::Cbz | ArchiveExt::Zip => {
+            let file = File::open(crate::output::long_path(comic_file.as_path())).ok()?;
+            let mut archive = ZipArchive::new(BufReader::new(file)).ok()?;
+            let name = archive
+                .file_names()
+                .find(|name| is_comic_info(Path::new(name)))?
+                .to_string();
+            let mut contents = String::new();
+            archive
+                .by_name(&name)
+                .ok()?
+                .read_to_string(&mut contents)
+                .ok()?;
+            Some(contents)
+        }
+        ArchiveExt::Cbr | ArchiveExt::Rar => {
+            let mut archive = Archive::new(comic_file.as_path())
+                .open_for_processing()
+                .ok()?;
+            while let Some(header) = archive.read_header().ok()? {
+                if is_comic_info(&header.entry().filename) {
+                    let (data, _) = header.read().ok()?;
+                    return String::from_utf8(data).ok();
+                }
+                archive = header.skip().ok()?;
+            }
+            None
+        }
+    }
+}
+
+pub(crate) fn validate_file(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = path.as_ref();
+    classify(path).ok()?;
     Some(path.to_path_buf())
 }
 
+// whether an entry is a page
+fn classify(path: &Path) -> Result<(), SkipReason> {
+    let file_name = path
+        .file_name()
+        .ok_or(SkipReason::NotAnImage)?
+        .to_string_lossy();
+    if should_skip_file(&file_name) {
+        Err(SkipReason::System)
+    } else if has_image_extension(path) {
+        Ok(())
+    } else if is_archive(path) {
+        Err(SkipReason::NestedArchive)
+    } else {
+        Err(SkipReason::NotAnImage)
+    }
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        ["zip", "cbz", "rar", "cbr"]
+            .iter()
+            .any(|archive| ext.eq_ignore_ascii_case(archive))
+    })
+}
+
 fn should_skip_file(file_name: &str) -> bool {
     file_name.starts_with(".")
         || file_name.contains("__MACOSX")
@@ -220,7 +720,9 @@ fn should_skip_file(file_name: &str) -> bool {
 }
 
 fn has_image_extension(path: &Path) -> bool {
-    static VALID_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
+    static VALID_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "tif", "tiff", "bmp"];
+    // not images comically can read without libheif
+    static HEIF_EXTENSIONS: &[&str] = &["heic", "heif"];
     if let Some(ext) = path.extension() {
         let ext_str = ext.to_string_lossy().to_lowercase();
         for valid_ext in VALID_EXTENSIONS {
@@ -228,6 +730,9 @@ fn has_image_extension(path: &Path) -> bool {
                 return true;
             }
         }
+        if cfg!(feature = "heif") && HEIF_EXTENSIONS.contains(&ext_str.as_str()) {
+            return true;
+        }
     }
     false
 }
@@ -240,3 +745,171 @@ fn test_unarchive_comic_iter() {
         .collect::<Vec<_>>();
     println!("{:?}", files.len());
 }
+
+#[test]
+fn listing_matches_extraction() {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    let options = zip::write::SimpleFileOptions::default();
+    let mut extras = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for name in ["01.jpg", "readme.txt"] {
+        extras.start_file(name, options).unwrap();
+        extras.write_all(b"data").unwrap();
+    }
+    let extras = extras.finish().unwrap().into_inner();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("comic.cbz");
+    let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+    zip.add_directory("vol1/", options).unwrap();
+    for name in [
+        "vol1/002.png",
+        "vol1/001.jpg",
+        "vol1/.hidden.jpg",
+        "notes.txt",
+    ] {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(b"data").unwrap();
+    }
+    zip.start_file("extras.cbz", options).unwrap();
+    zip.write_all(&extras).unwrap();
+    zip.finish().unwrap();
+
+    let comic = ComicFile::new(path);
+    let iter = unarchive_comic_iter(&comic).unwrap();
+    assert_eq!(
+        iter.images(),
+        [PathBuf::from("vol1/002.png"), PathBuf::from("vol1/001.jpg")]
+    );
+    let reasons: Vec<_> = iter.skipped().iter().map(|entry| entry.reason).collect();
+    assert_eq!(
+        reasons,
+        [
+            SkipReason::System,
+            SkipReason::NotAnImage,
+            SkipReason::NestedArchive
+        ]
+    );
+
+    let nested = ArchiveOptions {
+        nested_archives: true,
+        ..ArchiveOptions::default()
+    };
+    let iter = unarchive_comic_iter_with(&comic, nested).unwrap();
+    let listed = iter.images().to_vec();
+    assert_eq!(listed.last(), Some(&PathBuf::from("extras/01.jpg")));
+    assert_eq!(
+        iter.skipped().last().map(|entry| &entry.path),
+        Some(&PathBuf::from("extras/readme.txt"))
+    );
+
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let reported = progress.clone();
+    let extracted: Vec<_> = iter
+        .with_progress(move |p| reported.lock().unwrap().push(p.extracted))
+        .map(|file| file.unwrap().file_name)
+        .collect();
+    assert_eq!(extracted, listed);
+    assert_eq!(*progress.lock().unwrap(), [1, 2, 3]);
+}
+
+#[test]
+fn selects_pages_in_natural_order() {
+    use std::io::Write;
+
+    let names = ["10.jpg", "credits.jpg", "2.jpg", "1.jpg", "01.jpg"];
+    let mut sorted = names.map(PathBuf::from);
+    sorted.sort_by(|a, b| natural_cmp(a, b));
+    assert_eq!(
+        sorted,
+        ["1.jpg", "01.jpg", "2.jpg", "10.jpg", "credits.jpg"].map(PathBuf::from)
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("comic.cbz");
+    let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+    for name in names {
+        zip.start_file(name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(name.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let ranges: PageRanges = "2-3,5-".parse().unwrap();
+    let iter = unarchive_comic_iter(&ComicFile::new(path))
+        .unwrap()
+        .select(&ranges);
+    assert_eq!(
+        iter.images(),
+        ["credits.jpg", "2.jpg", "01.jpg"].map(PathBuf::from)
+    );
+    let read: Vec<_> = iter.map(|file| file.unwrap().data).collect();
+    assert_eq!(
+        read,
+        [
+            b"credits.jpg".to_vec(),
+            b"2.jpg".to_vec(),
+            b"01.jpg".to_vec()
+        ]
+    );
+}
+
+#[test]
+fn decodes_legacy_entry_names() {
+    use crate::testing::SampleArchive;
+
+    // a cover and a chapter folder named in Shift-JIS
+    let dir = tempfile::tempdir().unwrap();
+    let comic = SampleArchive::default()
+        .raw_page(b"\x95\x5C\x8E\x86.jpg", 60, 80)
+        .raw_page(b"\x91\xE6\x31\x98\x62/01.jpg", 60, 80)
+        .raw_page(b"\x91\xE6\x31\x98\x62/02.jpg", 60, 80)
+        .write(dir.path().join("sjis.cbz"))
+        .unwrap();
+    let names = |comic: &ComicFile, encoding| {
+        let options = ArchiveOptions {
+            filename_encoding: encoding,
+            ..ArchiveOptions::default()
+        };
+        unarchive_comic_iter_with(comic, options)
+            .unwrap()
+            .images()
+            .to_vec()
+    };
+    assert_eq!(
+        names(&comic, FilenameEncoding::Auto),
+        ["表紙.jpg", "第1話/01.jpg", "第1話/02.jpg"].map(PathBuf::from)
+    );
+    assert_eq!(
+        names(&comic, FilenameEncoding::ShiftJis),
+        names(&comic, FilenameEncoding::Auto)
+    );
+    assert_ne!(
+        names(&comic, FilenameEncoding::Cp437)[0],
+        Path::new("表紙.jpg")
+    );
+    assert_eq!(
+        read_first_image(&comic).unwrap().file_name,
+        PathBuf::from("第1話/01.jpg")
+    );
+
+    // UTF-8 without the flag, as written by some macOS and Linux tools
+    let comic = SampleArchive::default()
+        .raw_page("第2話/01.jpg".as_bytes(), 60, 80)
+        .write(dir.path().join("utf8.cbz"))
+        .unwrap();
+    assert_eq!(
+        names(&comic, FilenameEncoding::Auto),
+        [PathBuf::from("第2話/01.jpg")]
+    );
+
+    // a name that isn't valid Shift-JIS keeps its folder with the others
+    let comic = SampleArchive::default()
+        .raw_page(b"\x91\xE6\x31\x98\x62/01.jpg", 60, 80)
+        .raw_page(b"\x91\xE6\x31\x98\x62/\x80.jpg", 60, 80)
+        .write(dir.path().join("mixed.cbz"))
+        .unwrap();
+    let names = names(&comic, FilenameEncoding::Auto);
+    assert_eq!(names[0].parent(), names[1].parent());
+}
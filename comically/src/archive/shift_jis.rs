@@ -0,0 +1,68 @@
+//! Shift-JIS (Windows code page 932) names of zip entries, which Japanese versions of
+//! Windows write without the UTF-8 flag and the zip format would have read as CP437.
+//!
+//! Double byte characters are looked up in `cp932.bin`, the little endian code points of
+//! every lead and trail byte pair, `0` for the unassigned ones, generated with Python:
+//!
+//! ```python
+//! import struct
+//! with open("cp932.bin", "wb") as table:
+//!     for lead in [*range(0x81, 0xA0), *range(0xE0, 0xFD)]:
+//!         for trail in range(0x40, 0xFD):
+//!             try:
+//!                 char = ord(bytes([lead, trail]).decode("cp932"))
+//!             except UnicodeDecodeError:
+//!                 char = 0
+//!             table.write(struct.pack("<H", char))
+//! ```
+
+static TABLE: &[u8; 22680] = include_bytes!("cp932.bin");
+// trail bytes per lead byte in the table, 0x40 to 0xFC
+const TRAILS: usize = 0xFD - 0x40;
+
+/// `None` when `bytes` aren't valid Shift-JIS
+pub fn decode(bytes: &[u8]) -> Option<String> {
+    let mut decoded = String::with_capacity(bytes.len() * 2);
+    let mut bytes = bytes.iter().copied();
+    while let Some(byte) = bytes.next() {
+        let char = match byte {
+            0x00..=0x7F => byte as char,
+            // half-width katakana
+            0xA1..=0xDF => char::from_u32(0xFF61 + u32::from(byte - 0xA1))?,
+            0x81..=0x9F | 0xE0..=0xFC => double(byte, bytes.next()?)?,
+            _ => return None,
+        };
+        decoded.push(char);
+    }
+    Some(decoded)
+}
+
+fn double(lead: u8, trail: u8) -> Option<char> {
+    if !(0x40..0xFD).contains(&trail) {
+        return None;
+    }
+    let row = match lead {
+        0x81..=0x9F => lead - 0x81,
+        _ => lead - 0xC1,
+    };
+    let index = (usize::from(row) * TRAILS + usize::from(trail - 0x40)) * 2;
+    let code = u16::from_le_bytes([TABLE[index], TABLE[index + 1]]);
+    match code {
+        0 => None,
+        code => char::from_u32(u32::from(code)),
+    }
+}
+
+#[test]
+fn decodes_shift_jis() {
+    // ページ01.jpg, with a trail byte that's a backslash in ASCII: 表
+    assert_eq!(
+        decode(b"\x83\x79\x81\x5B\x83\x57\x30\x31.jpg").as_deref(),
+        Some("ページ01.jpg")
+    );
+    assert_eq!(decode(b"\x95\x5C/01.jpg").as_deref(), Some("表/01.jpg"));
+    assert_eq!(decode(b"\xB6\xDE").as_deref(), Some("ｶﾞ"));
+    // a lead byte without its trail byte, and one that can't be a lead byte
+    assert_eq!(decode(b"\x83"), None);
+    assert_eq!(decode(b"\x80a"), None);
+}
@@ -0,0 +1,818 @@
+//! Converting a list of comics, the conversion behind both the CLI and the TUI.
+//!
+//! Every comic is read once, processed and packaged for each [`Target`], and ends up as
+//! a [`ComicReport`]: a comic failing doesn't stop the rest of the batch.
+
+use anyhow::{bail, Context, Result};
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::archive::{ArchiveFile, SkippedEntry};
+use crate::comic::{
+    ComicConfig, ComicFile, OutputFormat, OutputNaming, PageRanges, ProcessedImage,
+};
+use crate::image::PageTiming;
+
+/// Where and how every comic of the batch is written
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Target {
+    pub config: ComicConfig,
+    pub output_dir: PathBuf,
+}
+
+/// Changes to the settings of a single comic, made to the config of every target once
+/// its archive has been read
+pub type Overrides = Arc<dyn Fn(&mut ComicConfig) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Input {
+    pub comic: ComicFile,
+    pub overrides: Option<Overrides>,
+    /// Pages of this comic to convert, instead of [`ComicConfig::pages`]
+    pub pages: Option<PageRanges>,
+}
+
+impl Input {
+    pub fn new(comic: ComicFile) -> Self {
+        Self {
+            comic,
+            overrides: None,
+            pages: None,
+        }
+    }
+
+    /// Adjusts the settings of this comic only, for every target, e.g. with the series
+    /// picked for it. Overrides run in the order they're added.
+    pub fn with_overrides(
+        mut self,
+        overrides: impl Fn(&mut ComicConfig) + Send + Sync + 'static,
+    ) -> Self {
+        self.overrides = Some(match self.overrides.take() {
+            Some(before) => Arc::new(move |config: &mut ComicConfig| {
+                before(config);
+                overrides(config);
+            }),
+            None => Arc::new(overrides),
+        });
+        self
+    }
+
+    /// Converts only `pages` of this comic, e.g. to leave out its scanlation credits
+    pub fn with_pages(mut self, pages: PageRanges) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Stage {
+    /// Reading the pages from the archive and processing them
+    Process,
+    /// Building the CBZ or EPUB
+    Package,
+    /// Converting the EPUB to MOBI with KindleGen
+    Convert,
+}
+
+impl Stage {
+    /// The stages of a conversion to `format`, in order
+    pub fn for_format(format: OutputFormat) -> &'static [Stage] {
+        match format {
+            OutputFormat::Mobi => &[Stage::Process, Stage::Package, Stage::Convert],
+            OutputFormat::Epub | OutputFormat::Cbz => &[Stage::Process, Stage::Package],
+        }
+    }
+
+    /// Share of a conversion to `format` the stage typically takes, 0 for stages it
+    /// doesn't go through. The weights of [`Stage::for_format`] add up to 1.
+    pub fn weight(self, format: OutputFormat) -> f64 {
+        match (format, self) {
+            // KindleGen takes about half as long as processing the pages
+            (OutputFormat::Mobi, Stage::Process) => 0.6,
+            (OutputFormat::Mobi, Stage::Package) => 0.1,
+            (OutputFormat::Mobi, Stage::Convert) => 0.3,
+            (_, Stage::Process) => 0.9,
+            (_, Stage::Package) => 0.1,
+            (_, Stage::Convert) => 0.0,
+        }
+    }
+
+    /// How far along a conversion to `format` is, from 0 to 1, once `done` (from 0 to 1)
+    /// of this stage is
+    pub fn progress(self, format: OutputFormat, done: f64) -> f64 {
+        let stages = Stage::for_format(format);
+        let before: f64 = stages
+            .iter()
+            .take_while(|&&stage| stage != self)
+            .map(|stage| stage.weight(format))
+            .sum();
+        before + self.weight(format) * done.clamp(0.0, 1.0)
+    }
+}
+
+/// Progress of a comic, in the order it happens
+pub enum Event<'a> {
+    StageStarted(Stage),
+    /// The archive was opened, `pages` are about to be read
+    Opened {
+        pages: usize,
+        skipped: &'a [SkippedEntry],
+    },
+    /// Pages read from the archive so far
+    Extracted(usize),
+    /// Called from the thread pool as each page is done
+    PageProcessed(&'a PageTiming),
+    /// How far along KindleGen is, from 0 to 1, as it goes
+    Converting(f32),
+    StageCompleted {
+        stage: Stage,
+        duration: Duration,
+    },
+    Finished(&'a ComicReport),
+}
+
+/// Time spent in each stage, over all targets
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub process: Duration,
+    pub package: Duration,
+    pub convert: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.process + self.package + self.convert
+    }
+}
+
+/// How long pages took, over the pages that weren't in the cache
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageStats {
+    pub pages: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl PageStats {
+    /// `None` when every page came from the cache, was copied through or was skipped
+    pub fn new<'a>(pages: impl IntoIterator<Item = &'a PageTiming>) -> Option<Self> {
+        let mut totals: Vec<Duration> = pages
+            .into_iter()
+            .filter(|timing| !timing.cached && !timing.reused && timing.skipped.is_none())
+            .map(PageTiming::total)
+            .collect();
+        totals.sort_unstable();
+        let max = *totals.last()?;
+        // nearest rank
+        let percentile = |p: usize| totals[(totals.len() * p).div_ceil(100).max(1) - 1];
+        Some(Self {
+            pages: totals.len(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max,
+        })
+    }
+}
+
+/// What became of a comic
+#[derive(Debug)]
+pub struct ComicReport {
+    pub input: PathBuf,
+    /// The files written so far, one per target, or one per part of MOBIs too big for
+    /// KindleGen, see [`ComicConfig::mobi_part_mb`]
+    pub outputs: Vec<PathBuf>,
+    /// [`ComicConfig::fingerprint`] of the settings of each target, with the comic's
+    /// overrides, and of each output once they're all written
+    pub fingerprints: Vec<String>,
+    /// Problems the comic was converted despite, like pages that couldn't be read
+    pub warnings: Vec<String>,
+    pub timings: Timings,
+    /// Every page, in the order they were done
+    pub pages: Vec<PageTiming>,
+    pub error: Option<anyhow::Error>,
+}
+
+impl ComicReport {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The written files, or why the comic failed
+    pub fn into_result(self) -> Result<Vec<PathBuf>> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.outputs),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub targets: Vec<Target>,
+    /// Comics converted at the same time. The pages of a comic are processed in parallel
+    /// either way, more jobs overlap reading archives, packaging and KindleGen.
+    pub jobs: usize,
+}
+
+impl Batch {
+    /// One comic at a time, to a single output directory
+    pub fn new(config: ComicConfig, output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            targets: vec![Target {
+                config,
+                output_dir: output_dir.into(),
+            }],
+            jobs: 1,
+        }
+    }
+
+    /// Converts every input, `jobs` at a time, and returns their reports in input order.
+    /// Events name the comic by its index in `inputs`.
+    pub fn run<F>(&self, inputs: &[Input], on_event: F) -> Vec<ComicReport>
+    where
+        F: Fn(usize, Event) + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let (report_tx, report_rx) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0..self.jobs.clamp(1, inputs.len().max(1)) {
+                let report_tx = report_tx.clone();
+                let (next, on_event) = (&next, &on_event);
+                scope.spawn(move || {
+                    // the build buffer of each worker is reused from comic to comic
+                    let mut buffer = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(index) else {
+                            break;
+                        };
+                        let on_event = |event: Event| on_event(index, event);
+                        let report = self.convert_with(input, &mut buffer, &on_event);
+                        report_tx.send((index, report)).ok();
+                    }
+                });
+            }
+        });
+        drop(report_tx);
+
+        let mut reports: Vec<(usize, ComicReport)> = report_rx.into_iter().collect();
+        reports.sort_by_key(|(index, _)| *index);
+        reports.into_iter().map(|(_, report)| report).collect()
+    }
+
+    /// Converts a single comic for every target, for callers with their own queue
+    pub fn convert(&self, input: &Input, on_event: &(dyn Fn(Event) + Sync)) -> ComicReport {
+        self.convert_with(input, &mut Vec::new(), on_event)
+    }
+
+    fn convert_with(
+        &self,
+        input: &Input,
+        buffer: &mut Vec<u8>,
+        on_event: &(dyn Fn(Event) + Sync),
+    ) -> ComicReport {
+        let mut report = ComicReport {
+            input: input.comic.as_path().to_path_buf(),
+            outputs: Vec::new(),
+            fingerprints: Vec::new(),
+            warnings: Vec::new(),
+            timings: Timings::default(),
+            pages: Vec::new(),
+            error: None,
+        };
+        if let Err(e) = self.try_convert(input, buffer, on_event, &mut report) {
+            report.error = Some(e);
+        }
+        on_event(Event::Finished(&report));
+        report
+    }
+
+    fn try_convert(
+        &self,
+        input: &Input,
+        buffer: &mut Vec<u8>,
+        on_event: &(dyn Fn(Event) + Sync),
+        report: &mut ComicReport,
+    ) -> Result<()> {
+        let Some(base) = self.targets.first() else {
+            return Ok(());
+        };
+        let comic = &input.comic;
+        log::info!("Converting: `{}`", comic.as_path().display());
+
+        let start = Instant::now();
+        on_event(Event::StageStarted(Stage::Process));
+        let mut archive_iter =
+            crate::archive::unarchive_comic_iter_with(comic, base.config.archive_options())
+                .context("Failed to open comic archive")?;
+        if let Some(ranges) = input.pages.as_ref().or(base.config.pages.as_ref()) {
+            archive_iter = archive_iter.select(ranges);
+            if archive_iter.num_images() == 0 {
+                bail!("No pages in the range {ranges}");
+            }
+        }
+        on_event(Event::Opened {
+            pages: archive_iter.num_images(),
+            skipped: archive_iter.skipped(),
+        });
+        let names = archive_iter.images().to_vec();
+
+        let warnings = &mut report.warnings;
+        let mut pages = archive_iter.enumerate().filter_map(|(extracted, result)| {
+            on_event(Event::Extracted(extracted + 1));
+            result
+                .inspect_err(|e| {
+                    log::warn!("Failed to load archive file: {e}");
+                    warnings.push(format!("Failed to load archive file: {e:#}"));
+                })
+                .ok()
+        });
+        // the reading direction is detected from the pages, and the pages of multi-page
+        // TIFFs are only numbered once read, so they're all read first
+        let detect_direction = base.config.detect_direction;
+        let tiffs = names.iter().any(|name| {
+            name.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("tif") || ext.eq_ignore_ascii_case("tiff")
+            })
+        });
+        let read_first = detect_direction || tiffs;
+        let files: Vec<ArchiveFile> = match read_first {
+            true => pages.by_ref().collect(),
+            false => Vec::new(),
+        };
+
+        let comic_config = base.config.for_comic(comic, &files);
+        if detect_direction {
+            let direction = if comic_config.right_to_left {
+                "right to left"
+            } else {
+                "left to right"
+            };
+            log::info!("Reading direction: {direction}");
+        }
+        // what was found out about the comic applies to every target, and so do the
+        // comic's own settings
+        let configs: Vec<ComicConfig> = self
+            .targets
+            .iter()
+            .map(|target| {
+                let mut config = ComicConfig {
+                    right_to_left: comic_config.right_to_left,
+                    series: comic_config.series.clone(),
+                    metadata: comic_config.metadata.clone(),
+                    translations: comic_config.translations.clone(),
+                    pages: input.pages.clone().or_else(|| target.config.pages.clone()),
+                    ..target.config.clone()
+                };
+                if let Some(overrides) = &input.overrides {
+                    overrides(&mut config);
+                }
+                config
+            })
+            .collect();
+        // the comic's overrides may pick another format, see `ComicConfig::set_output_format`
+        let formats: Vec<_> = configs
+            .iter()
+            .map(|config| format!("{:?}", config.output_format))
+            .collect();
+        log::info!("Output formats: {}", formats.join(", "));
+        report.fingerprints = configs.iter().map(ComicConfig::fingerprint).collect();
+        log::info!("Settings: {}", report.fingerprints.join(", "));
+
+        let timings = Mutex::new(Vec::new());
+        let on_progress = |timing: &PageTiming| {
+            on_event(Event::PageProcessed(timing));
+            timings.lock().unwrap().push(timing.clone());
+        };
+        let outputs = match read_first {
+            true => crate::image::process_batch_targets(files, &configs, on_progress),
+            // reading the archive overlaps with processing
+            false => crate::image::process_stream_targets(pages, &names, &configs, on_progress),
+        };
+        report.pages = timings.into_inner().unwrap();
+        let reused = report.pages.iter().filter(|page| page.reused).count();
+        if reused > 0 {
+            log::info!("Copied {reused} already optimized pages through");
+        }
+        report
+            .warnings
+            .extend(report.pages.iter().filter_map(|page| {
+                let reason = page.skipped.as_ref()?;
+                Some(format!("{}: {reason}", page.file.display()))
+            }));
+        let outputs = outputs.context("Failed to process images")?;
+        report.timings.process = start.elapsed();
+        on_event(Event::StageCompleted {
+            stage: Stage::Process,
+            duration: report.timings.process,
+        });
+
+        // one per output once they're written, the parts of a MOBI share theirs
+        let mut fingerprints = Vec::new();
+        let targets = configs.iter().zip(&self.targets).zip(outputs);
+        for (index, ((config, target), mut images)) in targets.enumerate() {
+            log::info!("Processed {} images", images.len());
+            crate::image::title_page::insert(&mut images, comic.title(), config)
+                .context("Failed to generate the title page")?;
+            let parts = mobi_parts(&images, config);
+            if parts.len() > 1 {
+                log::info!("Splitting the MOBI into {} parts", parts.len());
+            }
+            for (part, range) in parts.iter().enumerate() {
+                let (comic, config) = match parts.len() {
+                    1 => (comic.clone(), config.clone()),
+                    n => (comic.part(part + 1, n), part_config(config, part + 1, n)),
+                };
+                let output = package(
+                    &comic,
+                    &config,
+                    &images[range.clone()],
+                    &target.output_dir,
+                    buffer,
+                    on_event,
+                    report,
+                )?;
+                log::info!("Done: {}", output.display());
+                report.outputs.push(output);
+                fingerprints.push(report.fingerprints[index].clone());
+            }
+        }
+        report.fingerprints = fingerprints;
+
+        Ok(())
+    }
+}
+
+// the pages of each MOBI when they're too big for KindleGen in one, about as big as each
+// other and with the parts of a page kept together
+fn mobi_parts(images: &[ProcessedImage], config: &ComicConfig) -> Vec<Range<usize>> {
+    let limit = u64::from(config.mobi_part_mb) << 20;
+    let size = |image: &ProcessedImage| {
+        let small = image.small.as_ref().map_or(0, |small| small.data.len());
+        (image.data.len() + small) as u64
+    };
+    let total: u64 = images.iter().map(size).sum();
+    if config.output_format != OutputFormat::Mobi || limit == 0 || total <= limit {
+        return std::iter::once(0..images.len()).collect();
+    }
+    let target = total.div_ceil(total.div_ceil(limit));
+
+    let mut parts = Vec::new();
+    let (mut start, mut taken) = (0, 0);
+    for (i, image) in images.iter().enumerate() {
+        let new_page = i > 0 && image.source != images[i - 1].source;
+        if new_page && taken >= target {
+            parts.push(start..i);
+            (start, taken) = (i, 0);
+        }
+        taken += size(image);
+    }
+    parts.push(start..images.len());
+    parts
+}
+
+// the settings of a part, titled like its file
+fn part_config(config: &ComicConfig, part: usize, parts: usize) -> ComicConfig {
+    let mut config = config.clone();
+    if let Some(title) = config.metadata.as_mut().and_then(|m| m.title.as_mut()) {
+        title.push_str(&format!(" (part {part} of {parts})"));
+    }
+    config
+}
+
+/// File name of the output of `comic`, from the output template of `config` when the
+/// comic's name has every part it uses, otherwise from its naming
+pub fn output_name(comic: &ComicFile, config: &ComicConfig) -> PathBuf {
+    if let Some(template) = &config.output_template {
+        let mut parsed = crate::filename::parse(comic.title());
+        // from its ComicInfo.xml, typed in or looked up, more reliable than the name
+        if let Some(series) = &config.series {
+            parsed.series = series.name.clone();
+        }
+        match crate::filename::render(template, comic.title(), &parsed) {
+            Ok(Some(name)) => return format!("{name}.{}", config.output_format.as_str()).into(),
+            Ok(None) => log::warn!(
+                "{} is missing parts of the output template, keeping its name",
+                comic.title()
+            ),
+            Err(e) => log::warn!("{e:#}"),
+        }
+    }
+    match config.output_naming {
+        OutputNaming::Comically => comic.with_extension(config.output_format),
+        OutputNaming::Kcc => crate::kcc::output_name(comic, config),
+    }
+}
+
+// writes the comic in the format of `config`, returning the path of the output
+fn package(
+    comic: &ComicFile,
+    config: &ComicConfig,
+    images: &[ProcessedImage],
+    output_dir: &Path,
+    buffer: &mut Vec<u8>,
+    on_event: &(dyn Fn(Event) + Sync),
+    report: &mut ComicReport,
+) -> Result<PathBuf> {
+    let output_format = config.output_format;
+    if output_format == OutputFormat::Mobi && !crate::is_kindlegen_available() {
+        bail!("KindleGen is not available. Please install it to create MOBI files.");
+    }
+
+    let start = Instant::now();
+    on_event(Event::StageStarted(Stage::Package));
+    log::info!("Building {output_format:?}...");
+
+    let output_path = output_dir.join(output_name(comic, config));
+    // MOBI files are converted from an EPUB, which is kept next to them
+    let (built_path, book_id) = match output_format {
+        OutputFormat::Cbz => {
+            crate::cbz::build_into(comic.title(), config, images, buffer);
+            (output_path.clone(), None)
+        }
+        OutputFormat::Epub | OutputFormat::Mobi => {
+            let book_id = crate::epub::build_into(comic.title(), config, images, buffer);
+            let path = output_dir.join(comic.with_extension(OutputFormat::Epub));
+            (path, Some(book_id))
+        }
+    };
+    crate::output::write_atomic(&built_path, buffer).context("Failed to write output file")?;
+    if config.write_checksum && output_format != OutputFormat::Mobi {
+        crate::output::write_checksum(&built_path, buffer).context("Failed to write checksum")?;
+    }
+
+    let duration = start.elapsed();
+    report.timings.package += duration;
+    on_event(Event::StageCompleted {
+        stage: Stage::Package,
+        duration,
+    });
+
+    if output_format == OutputFormat::Mobi {
+        let start = Instant::now();
+        on_event(Event::StageStarted(Stage::Convert));
+        let mut kindlegen = crate::mobi::create(built_path, output_path.clone())
+            .context("Failed to start MOBI conversion")?;
+        let mut progress = 0.0;
+        while kindlegen.try_wait()?.is_none() {
+            if kindlegen.progress() > progress {
+                progress = kindlegen.progress();
+                on_event(Event::Converting(progress));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        for warning in kindlegen.wait().context("MOBI conversion failed")? {
+            log::warn!("KindleGen: {warning}");
+            report.warnings.push(format!("KindleGen: {warning}"));
+        }
+        if let Err(e) = crate::mobi::embed_metadata(&output_path, comic.title(), config) {
+            log::warn!("Failed to embed the metadata in the MOBI file: {e:#}");
+            report.warnings.push(format!(
+                "Failed to embed the metadata in the MOBI file: {e:#}"
+            ));
+        }
+        if let (Some(book_id), Some(cover)) = (book_id, images.first()) {
+            if let Err(e) = crate::mobi::add_kindle_thumbnail(&output_path, &book_id, cover) {
+                log::warn!("Failed to add the Kindle thumbnail: {e:#}");
+                report
+                    .warnings
+                    .push(format!("Failed to add the Kindle thumbnail: {e:#}"));
+            }
+        }
+        if config.write_checksum {
+            crate::output::write_checksum_of(&output_path).context("Failed to write checksum")?;
+        }
+
+        let duration = start.elapsed();
+        report.timings.convert += duration;
+        on_event(Event::StageCompleted {
+            stage: Stage::Convert,
+            duration,
+        });
+    }
+
+    Ok(output_path)
+}
+
+#[test]
+fn reports_every_comic_in_order() {
+    use crate::testing::SampleArchive;
+    use std::sync::Mutex;
+
+    let dir = tempfile::tempdir().unwrap();
+    SampleArchive::with_pages(2)
+        .file("003.txt", "not a page")
+        .write(dir.path().join("one.cbz"))
+        .unwrap();
+
+    let config = ComicConfig {
+        output_format: OutputFormat::Cbz,
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+    let mut batch = Batch::new(config, dir.path().join("out"));
+    batch.jobs = 2;
+    std::fs::create_dir(dir.path().join("out")).unwrap();
+
+    let inputs: Vec<Input> = ["one.cbz", "missing.cbz"]
+        .into_iter()
+        .map(|name| Input::new(ComicFile::new(dir.path().join(name))))
+        .collect();
+    let processed = Mutex::new(vec![0; inputs.len()]);
+    let reports = batch.run(&inputs, |index, event| {
+        if let Event::PageProcessed(_) = event {
+            processed.lock().unwrap()[index] += 1;
+        }
+    });
+
+    assert_eq!(processed.into_inner().unwrap(), [2, 0]);
+    assert_eq!(reports[0].input, dir.path().join("one.cbz"));
+    assert!(reports[0].is_ok());
+    assert_eq!(reports[0].outputs, [dir.path().join("out/one.cbz")]);
+    assert!(reports[0].outputs[0].exists());
+    assert_eq!(
+        reports[0].fingerprints,
+        [batch.targets[0].config.fingerprint()]
+    );
+    assert!(!reports[1].is_ok());
+    assert!(reports[1].outputs.is_empty());
+
+    // another format for this comic only
+    let input = Input::new(ComicFile::new(dir.path().join("one.cbz")))
+        .with_overrides(|config| config.set_output_format(OutputFormat::Epub));
+    let report = batch.convert(&input, &|_| {});
+    assert_eq!(report.outputs, [dir.path().join("out/one.epub")]);
+    assert_ne!(report.fingerprints, reports[0].fingerprints);
+}
+
+#[test]
+fn overrides_apply_to_every_target() {
+    use crate::comic::{FixedCrop, SplitStrategy};
+    use crate::testing::SampleArchive;
+
+    let dir = tempfile::tempdir().unwrap();
+    SampleArchive::with_pages(2)
+        .write(dir.path().join("one.cbz"))
+        .unwrap();
+    let config = ComicConfig {
+        output_format: OutputFormat::Cbz,
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+    let mut batch = Batch::new(config.clone(), dir.path().join("cbz"));
+    batch.targets.push(Target {
+        config: ComicConfig {
+            output_format: OutputFormat::Epub,
+            brightness: 20,
+            ..config
+        },
+        output_dir: dir.path().join("epub"),
+    });
+    for target in &batch.targets {
+        std::fs::create_dir(&target.output_dir).unwrap();
+    }
+
+    let input = Input::new(ComicFile::new(dir.path().join("one.cbz")))
+        .with_overrides(|config| config.fixed_crop = Some(FixedCrop::bottom(20)))
+        .with_overrides(|config| {
+            config.gamma += 0.5;
+            config.split = SplitStrategy::None;
+        });
+    let report = batch.convert(&input, &|_| {});
+    assert!(report.is_ok(), "{:?}", report.error);
+    let expected: Vec<String> = batch
+        .targets
+        .iter()
+        .map(|target| {
+            let mut config = target.config.clone();
+            config.fixed_crop = Some(FixedCrop::bottom(20));
+            config.gamma += 0.5;
+            config.split = SplitStrategy::None;
+            config.fingerprint()
+        })
+        .collect();
+    assert_eq!(report.fingerprints, expected);
+}
+
+#[test]
+fn large_mobis_are_split_between_pages() {
+    use crate::comic::PagePart;
+    use crate::testing::processed_page;
+
+    // 300 KB pages, the third one a spread split in two, 4 parts of about 825 KB
+    let mut images: Vec<ProcessedImage> = (1..=10)
+        .map(|page| processed_page(page, &format!("{page:03}.jpg"), PagePart::Whole))
+        .collect();
+    images.insert(3, processed_page(3, "003.jpg", PagePart::Left));
+    for image in &mut images {
+        image.data = vec![0; 300 << 10];
+    }
+    let config = ComicConfig {
+        output_format: OutputFormat::Mobi,
+        mobi_part_mb: 1,
+        ..ComicConfig::default()
+    };
+
+    let parts = mobi_parts(&images, &config);
+    assert_eq!(parts, [0..4, 4..7, 7..10, 10..11]);
+    for config in [
+        ComicConfig {
+            mobi_part_mb: 0,
+            ..config.clone()
+        },
+        ComicConfig {
+            output_format: OutputFormat::Epub,
+            ..config.clone()
+        },
+    ] {
+        assert_eq!(mobi_parts(&images, &config).len(), 1);
+    }
+
+    let comic = ComicFile::new("Vol. 1.cbz".into()).part(2, 3);
+    assert_eq!(comic.title(), "Vol. 1 (part 2 of 3)");
+}
+
+#[test]
+fn summarizes_page_timings() {
+    let page = |millis, cached| PageTiming {
+        decode: Duration::from_millis(millis),
+        cached,
+        ..PageTiming::default()
+    };
+    let mut pages: Vec<PageTiming> = (1..=100).map(|millis| page(millis, false)).collect();
+    pages.push(page(0, true));
+
+    let stats = PageStats::new(&pages).unwrap();
+    assert_eq!(stats.pages, 100);
+    assert_eq!(stats.p50, Duration::from_millis(50));
+    assert_eq!(stats.p90, Duration::from_millis(90));
+    assert_eq!(stats.p99, Duration::from_millis(99));
+    assert_eq!(stats.max, Duration::from_millis(100));
+
+    assert_eq!(PageStats::new(&[page(0, true)]), None);
+    assert_eq!(
+        PageStats::new(&[page(7, false)]).unwrap().p50,
+        Duration::from_millis(7)
+    );
+}
+
+#[test]
+fn stage_weights_add_up_per_format() {
+    for format in [OutputFormat::Cbz, OutputFormat::Epub, OutputFormat::Mobi] {
+        let stages = Stage::for_format(format);
+        let total: f64 = stages.iter().map(|stage| stage.weight(format)).sum();
+        assert!((total - 1.0).abs() < 1e-9, "{format:?}");
+        assert_eq!(Stage::Process.progress(format, 0.0), 0.0);
+        let last = stages.last().unwrap();
+        assert!(
+            (last.progress(format, 1.0) - 1.0).abs() < 1e-9,
+            "{format:?}"
+        );
+    }
+    assert_eq!(
+        Stage::for_format(OutputFormat::Epub),
+        [Stage::Process, Stage::Package]
+    );
+    assert_eq!(Stage::Convert.weight(OutputFormat::Cbz), 0.0);
+    assert!((Stage::Package.progress(OutputFormat::Mobi, 0.0) - 0.6).abs() < 1e-9);
+}
+
+#[test]
+fn output_names_follow_the_template() {
+    let comic = ComicFile::new("[Group] Dungeon_Meshi_Vol._03 (Digital).cbz".into());
+    let mut config = ComicConfig {
+        output_format: OutputFormat::Epub,
+        output_template: Some("{series} v{volume:2}".into()),
+        ..ComicConfig::default()
+    };
+    assert_eq!(
+        output_name(&comic, &config),
+        PathBuf::from("Dungeon Meshi v03.epub")
+    );
+
+    config.series = Some(crate::Series {
+        name: "Delicious in Dungeon".into(),
+        index: None,
+    });
+    assert_eq!(
+        output_name(&comic, &config),
+        PathBuf::from("Delicious in Dungeon v03.epub")
+    );
+
+    // without a volume in the name, it keeps its own
+    let comic = ComicFile::new("Dungeon Meshi Extra.cbz".into());
+    assert_eq!(
+        output_name(&comic, &config),
+        PathBuf::from("Dungeon Meshi Extra.epub")
+    );
+}
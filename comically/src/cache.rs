@@ -0,0 +1,447 @@
+//! On-disk cache of encoded pages, so reconverting unchanged comics or changing
+//! settings that only affect packaging skips the image pipeline.
+//!
+//! Entries are keyed by the SHA-256 of the source page and of the settings that change
+//! how pages are rendered. Each entry holds every part the page was split into.
+//!
+//! The cache is kept under `page_cache_mb` by evicting the least recently used entries
+//! after each conversion, see [`Cache::trim`].
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::archive::ArchiveFile;
+use crate::comic::ComicConfig;
+use crate::image::ImageFormat;
+use crate::ProcessedImage;
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+// bumped when the layout of entries changes, or when what they hold was wrong, so old
+// ones are never read. 4: later targets of a batch were stored with the first's tones
+const ENTRY_VERSION: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
+struct Part {
+    dimensions: (u32, u32),
+    format: ImageFormat,
+    part: crate::PagePart,
+    original_dimensions: (u32, u32),
+    cropped: bool,
+    len: usize,
+    // dimensions and length of the small copy, stored right after the part
+    small: Option<((u32, u32), usize)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+// everything in the config that changes the pixels or encoding of a page
+#[derive(Serialize)]
+struct PageSettings<'a> {
+    version: &'static str,
+    entry_version: u32,
+    device: (u32, u32),
+    max_dimension: Option<u32>,
+    srcset: Option<u32>,
+    long_strip: Option<u32>,
+    text_pages: bool,
+    right_to_left: bool,
+    split: &'a crate::SplitStrategy,
+    landscape_spreads: bool,
+    auto_rotate_cover: bool,
+    animated_pages: crate::AnimatedPages,
+    auto_crop: bool,
+    fixed_crop: Option<crate::FixedCrop>,
+    brightness: i32,
+    paper_white: bool,
+    contrast: i32,
+    gamma: f32,
+    gamma_curve: &'a crate::GammaCurve,
+    dither: bool,
+    // left out for grayscale output so the pages cached before color pages stay valid
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    keep_color: bool,
+    margin_color: &'a Option<crate::MarginColor>,
+    image_format: &'a ImageFormat,
+    rotation: &'a crate::Rotation,
+    upscale: &'a crate::UpscalePolicy,
+    // left out on the CPU so the pages cached before the GPU backend stay valid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backend: Option<crate::Backend>,
+    adaptive_quality: &'a Option<crate::AdaptiveQuality>,
+    page_stamp: &'a Option<crate::PageStamp>,
+    watermark: &'a Option<crate::Watermark>,
+    translation_overlay: &'a Option<crate::TranslationOverlay>,
+    page_filter: Option<&'a str>,
+}
+
+/// What [`Cache::gc`] removed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Location of the cache shared by the frontends
+    pub fn default_dir() -> Option<PathBuf> {
+        let home = std::env::home_dir()?;
+        Some(home.join(".cache").join("comically").join("pages"))
+    }
+
+    pub fn open_default() -> Option<Self> {
+        Self::default_dir().map(Self::new)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Hash of the settings that affect page output, shared by every page of a conversion
+    pub fn settings_key(config: &ComicConfig) -> String {
+        let settings = PageSettings {
+            version: env!("CARGO_PKG_VERSION"),
+            entry_version: ENTRY_VERSION,
+            device: config.device.dimensions(),
+            max_dimension: config.max_dimension,
+            srcset: config.small_copy(),
+            long_strip: config.long_strip(),
+            text_pages: config.text_pages(),
+            right_to_left: config.right_to_left,
+            split: &config.split,
+            landscape_spreads: config.landscape_spreads,
+            auto_rotate_cover: config.auto_rotate_cover,
+            animated_pages: config.animated_pages,
+            auto_crop: config.auto_crop,
+            fixed_crop: config.fixed_crop.filter(|crop| !crop.is_empty()),
+            brightness: config.brightness,
+            paper_white: config.paper_white,
+            contrast: config.contrast,
+            gamma: config.gamma,
+            gamma_curve: &config.gamma_curve,
+            dither: config.dither,
+            keep_color: config.keep_color,
+            margin_color: &config.margin_color,
+            image_format: &config.image_format,
+            rotation: &config.rotation,
+            upscale: &config.upscale,
+            backend: Some(config.backend).filter(|backend| *backend != crate::Backend::Cpu),
+            adaptive_quality: &config.adaptive_quality,
+            page_stamp: &config.page_stamp,
+            watermark: &config.watermark,
+            translation_overlay: &config.translation_overlay,
+            page_filter: config
+                .page_filter
+                .as_ref()
+                .map(|filter| filter.command.as_str()),
+        };
+        crate::output::sha256_of(&settings)
+    }
+
+    fn entry_path(&self, page: &[u8], settings_key: &str) -> PathBuf {
+        let mut key = page.to_vec();
+        key.extend_from_slice(settings_key.as_bytes());
+        let key = crate::output::sha256(&key);
+        self.dir.join(&key[..2]).join(key)
+    }
+
+    /// The encoded parts of a page, named after `original`, if it was converted with
+    /// the same settings before
+    pub fn get(&self, original: &ArchiveFile, settings_key: &str) -> Option<Vec<ProcessedImage>> {
+        let path = self.entry_path(&original.data, settings_key);
+        let data = fs::read(&path).ok()?;
+
+        let parts = read_entry(original, &data)
+            .inspect_err(|e| log::warn!("ignoring corrupt cache entry {}: {e}", path.display()))
+            .ok()?;
+
+        // recently used entries survive garbage collection
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(parts)
+    }
+
+    /// Stores the encoded parts of a page
+    pub fn put(&self, original: &ArchiveFile, settings_key: &str, parts: &[ProcessedImage]) {
+        let path = self.entry_path(&original.data, settings_key);
+        if let Err(e) = write_entry(&path, parts) {
+            log::warn!("failed to write cache entry {}: {e}", path.display());
+        }
+    }
+
+    /// Removes entries that weren't used in `max_age`, or every entry when it's zero
+    pub fn gc(&self, max_age: Duration) -> Result<GcStats> {
+        let mut stats = GcStats::default();
+        if !self.dir.exists() {
+            return Ok(stats);
+        }
+
+        let now = SystemTime::now();
+        for entry in walkdir::WalkDir::new(&self.dir).min_depth(2).max_depth(2) {
+            let entry = entry.context("Failed to read cache directory")?;
+            let metadata = entry.metadata().context("Failed to read cache entry")?;
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+
+            if metadata.is_file() && (max_age.is_zero() || age > max_age) {
+                fs::remove_file(entry.path())
+                    .with_context(|| format!("Failed to remove {}", entry.path().display()))?;
+                stats.entries += 1;
+                stats.bytes += metadata.len();
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Removes the least recently used entries until the rest take at most `max_bytes`
+    pub fn trim(&self, max_bytes: u64) -> Result<GcStats> {
+        let mut stats = GcStats::default();
+        if !self.dir.exists() {
+            return Ok(stats);
+        }
+
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for entry in walkdir::WalkDir::new(&self.dir).min_depth(2).max_depth(2) {
+            let entry = entry.context("Failed to read cache directory")?;
+            let metadata = entry.metadata().context("Failed to read cache entry")?;
+            if metadata.is_file() {
+                let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                total += metadata.len();
+                entries.push((used, metadata.len(), entry.into_path()));
+            }
+        }
+
+        entries.sort_unstable();
+        for (_, len, path) in entries {
+            if total <= max_bytes {
+                break;
+            }
+            // removed by another conversion trimming at the same time
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+                }
+            }
+            total -= len;
+            stats.entries += 1;
+            stats.bytes += len;
+        }
+
+        Ok(stats)
+    }
+}
+
+// a JSON header line describing the parts, followed by their data back to back
+fn write_entry(path: &Path, parts: &[ProcessedImage]) -> Result<()> {
+    let dir = path.parent().context("Cache entry has no parent")?;
+    fs::create_dir_all(dir)?;
+
+    let header: Vec<Part> = parts
+        .iter()
+        .map(|part| Part {
+            dimensions: part.dimensions,
+            format: part.format,
+            part: part.part,
+            original_dimensions: part.original_dimensions,
+            cropped: part.cropped,
+            len: part.data.len(),
+            small: part
+                .small
+                .as_ref()
+                .map(|small| (small.dimensions, small.data.len())),
+            text: part.text.clone(),
+        })
+        .collect();
+
+    // not synced, losing an entry only costs a reconversion
+    let mut file = tempfile::Builder::new()
+        .prefix(".comically-")
+        .tempfile_in(dir)?;
+    serde_json::to_writer(&mut file, &header)?;
+    file.write_all(b"\n")?;
+    for part in parts {
+        file.write_all(&part.data)?;
+        if let Some(small) = &part.small {
+            file.write_all(&small.data)?;
+        }
+    }
+    file.persist(path)?;
+    Ok(())
+}
+
+fn read_entry(original: &ArchiveFile, data: &[u8]) -> Result<Vec<ProcessedImage>> {
+    let newline = data
+        .iter()
+        .position(|&b| b == b'\n')
+        .context("missing header")?;
+    let header: Vec<Part> = serde_json::from_slice(&data[..newline])?;
+    if header.is_empty() || header.len() > 3 {
+        anyhow::bail!("unexpected number of parts: {}", header.len());
+    }
+
+    let mut rest = &data[newline + 1..];
+    let mut parts = Vec::with_capacity(header.len());
+    for (i, part) in header.into_iter().enumerate() {
+        if rest.len() < part.len {
+            anyhow::bail!("truncated");
+        }
+        let (data, tail) = rest.split_at(part.len);
+        rest = tail;
+        let small = match part.small {
+            Some((dimensions, len)) => {
+                if rest.len() < len {
+                    anyhow::bail!("truncated");
+                }
+                let (data, tail) = rest.split_at(len);
+                rest = tail;
+                Some(crate::comic::SmallCopy {
+                    data: data.to_vec(),
+                    dimensions,
+                })
+            }
+            None => None,
+        };
+
+        parts.push(ProcessedImage {
+            file_name: crate::image::encode::part_file_name(original, i, part.format),
+            data: data.to_vec(),
+            dimensions: part.dimensions,
+            format: part.format,
+            source: original.file_name.clone(),
+            // numbered with the rest of the comic
+            page: 0,
+            part: part.part,
+            original_dimensions: part.original_dimensions,
+            cropped: part.cropped,
+            small,
+            text: part.text,
+        });
+    }
+    if !rest.is_empty() {
+        anyhow::bail!("trailing data");
+    }
+
+    Ok(parts)
+}
+
+#[test]
+fn cache_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(dir.path().to_path_buf());
+    let page = ArchiveFile {
+        file_name: PathBuf::from("vol1/001.png"),
+        data: b"source page".to_vec(),
+    };
+
+    let mut config = ComicConfig::default();
+    let key = Cache::settings_key(&config);
+    assert!(cache.get(&page, &key).is_none());
+
+    let format = ImageFormat::Jpeg { quality: 85 };
+    let mut parts = [
+        (b"left".to_vec(), crate::PagePart::Left),
+        (b"right half".to_vec(), crate::PagePart::Right),
+    ]
+    .map(|(data, part)| ProcessedImage {
+        file_name: String::new(),
+        data,
+        dimensions: (10, 20),
+        format,
+        source: page.file_name.clone(),
+        page: 1,
+        part,
+        original_dimensions: (40, 20),
+        cropped: true,
+        small: None,
+        text: None,
+    });
+    parts[0].small = Some(crate::comic::SmallCopy {
+        data: b"small".to_vec(),
+        dimensions: (5, 10),
+    });
+    cache.put(&page, &key, &parts);
+
+    let cached = cache.get(&page, &key).unwrap();
+    assert_eq!(cached.len(), 2);
+    assert_eq!(cached[1].data, b"right half");
+    assert_eq!(cached[1].file_name, "vol1_001_001.jpg");
+    assert_eq!(cached[1].part, crate::PagePart::Right);
+    assert!(cached[1].cropped);
+    assert_eq!(cached[0].small.as_ref().unwrap().data, b"small");
+    assert_eq!(cached[1].small, None);
+
+    // packaging settings share entries, rendering settings don't
+    config.output_format = crate::OutputFormat::Cbz;
+    assert_eq!(Cache::settings_key(&config), key);
+    config.gamma = 1.0;
+    assert!(cache.get(&page, &Cache::settings_key(&config)).is_none());
+
+    assert_eq!(cache.gc(Duration::from_secs(3600)).unwrap().entries, 0);
+    assert_eq!(cache.gc(Duration::ZERO).unwrap().entries, 1);
+    assert!(cache.get(&page, &key).is_none());
+}
+
+#[test]
+fn trimming_keeps_recently_used_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = Cache::new(dir.path().to_path_buf());
+    let key = Cache::settings_key(&ComicConfig::default());
+    let pages: Vec<ArchiveFile> = (0..3)
+        .map(|i| ArchiveFile {
+            file_name: PathBuf::from(format!("{i}.png")),
+            data: vec![i; 10],
+        })
+        .collect();
+    let now = SystemTime::now();
+    for (i, page) in pages.iter().enumerate() {
+        let part = ProcessedImage {
+            file_name: String::new(),
+            data: vec![0; 1000],
+            dimensions: (10, 20),
+            format: ImageFormat::Jpeg { quality: 85 },
+            source: page.file_name.clone(),
+            page: 1,
+            part: crate::PagePart::Whole,
+            original_dimensions: (10, 20),
+            cropped: false,
+            small: None,
+            text: None,
+        };
+        cache.put(page, &key, &[part]);
+        // the first page was used last
+        let used = now - Duration::from_secs(60 * i as u64);
+        let path = cache.entry_path(&page.data, &key);
+        let file = fs::File::options().append(true).open(path).unwrap();
+        file.set_modified(used).unwrap();
+    }
+
+    assert_eq!(cache.trim(u64::MAX).unwrap(), GcStats::default());
+    // room for two entries
+    let stats = cache.trim(2500).unwrap();
+    assert_eq!(stats.entries, 1);
+    assert!(stats.bytes > 1000);
+    assert!(cache.get(&pages[0], &key).is_some());
+    assert!(cache.get(&pages[1], &key).is_some());
+    assert!(cache.get(&pages[2], &key).is_none());
+
+    assert_eq!(cache.trim(0).unwrap().entries, 2);
+}
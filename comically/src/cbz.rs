@@ -1,33 +1,100 @@
-use zip::write::SimpleFileOptions;
+use anyhow::Result;
 use zip::ZipWriter;
 
-use std::io::Cursor;
+use std::io::{Cursor, Seek, Write};
 
-use crate::comic::ProcessedImage;
+use crate::comic::{ComicConfig, ProcessedImage};
 
 /// Build CBZ and return the bytes
-pub fn build(images: &[ProcessedImage]) -> Vec<u8> {
+pub fn build(title: &str, config: &ComicConfig, images: &[ProcessedImage]) -> Vec<u8> {
     let cap = images.len() * images.first().map(|i| i.data.len()).unwrap_or(1);
     let mut buffer = Vec::with_capacity(cap);
-    build_into(images, &mut buffer);
+    build_into(title, config, images, &mut buffer);
     buffer
 }
 
 /// Build CBZ into the provided buffer, reusing existing allocation
-pub fn build_into(images: &[ProcessedImage], buffer: &mut Vec<u8>) {
+pub fn build_into(
+    title: &str,
+    config: &ComicConfig,
+    images: &[ProcessedImage],
+    buffer: &mut Vec<u8>,
+) {
+    let _span = crate::trace::span!("package", format = "cbz", pages = images.len()).entered();
     buffer.clear();
     let cursor = Cursor::new(buffer);
     let mut zip = ZipWriter::new(cursor);
 
-    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let options = crate::output::zip_options(config.deterministic)
+        .compression_method(zip::CompressionMethod::Stored);
 
     // Add images in order
     for image in images.iter() {
         zip.start_file(&image.file_name, options).unwrap();
-        std::io::Write::write_all(&mut zip, &image.data).unwrap();
+        zip.write_all(&image.data).unwrap();
     }
 
-    // TODO: Add ComicInfo.xml if we have metadata
+    if let Some(info) = crate::metadata::comic_info_xml(title, config) {
+        zip.start_file("ComicInfo.xml", options).unwrap();
+        zip.write_all(info.as_bytes()).unwrap();
+    }
 
     zip.finish().unwrap();
 }
+
+/// Build CBZ into `writer` from pages as they're processed, e.g. by
+/// [`ComicFile::process_iter`](crate::ComicFile::process_iter), holding one page at a
+/// time. The first page that failed fails the build.
+pub fn build_iter<W, I>(title: &str, config: &ComicConfig, images: I, writer: W) -> Result<W>
+where
+    W: Write + Seek,
+    I: IntoIterator<Item = Result<ProcessedImage>>,
+{
+    let _span = crate::trace::span!("package", format = "cbz").entered();
+    let mut zip = ZipWriter::new(writer);
+    let options = crate::output::zip_options(config.deterministic)
+        .compression_method(zip::CompressionMethod::Stored);
+
+    for image in images {
+        let image = image?;
+        zip.start_file(&image.file_name, options)?;
+        zip.write_all(&image.data)?;
+    }
+
+    if let Some(info) = crate::metadata::comic_info_xml(title, config) {
+        zip.start_file("ComicInfo.xml", options)?;
+        zip.write_all(info.as_bytes())?;
+    }
+
+    Ok(zip.finish()?)
+}
+
+#[test]
+fn matches_golden_cbz() {
+    use crate::testing::{assert_golden, golden_path, processed_page, Package};
+    use crate::PagePart;
+
+    let config = ComicConfig {
+        deterministic: true,
+        metadata: Some(crate::Metadata {
+            authors: vec!["Sample Author".into()],
+            ..Default::default()
+        }),
+        ..ComicConfig::default()
+    };
+    let images = [
+        processed_page(1, "001.jpg", PagePart::Whole),
+        processed_page(2, "002.jpg", PagePart::Whole),
+    ];
+
+    let cbz = build("Sample v02", &config, &images);
+    let package = Package::read(&cbz).unwrap();
+    assert_eq!(
+        package.names(),
+        ["001_Whole.jpg", "002_Whole.jpg", "ComicInfo.xml"]
+    );
+    assert_golden(
+        &golden_path("sample.cbz.txt"),
+        &Package::snapshot(&cbz).unwrap(),
+    );
+}
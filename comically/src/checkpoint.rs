@@ -0,0 +1,325 @@
+//! Journal of a batch conversion kept in the output directory, so a batch that was
+//! interrupted can resume without converting the comics it already finished.
+//!
+//! The journal is a JSON record per line: the settings and targets of the batch, then
+//! the comics as they are queued, started, completed or failed. It is removed once every
+//! queued comic is completed, so a journal left behind always belongs to a batch that
+//! was interrupted or failed on some comics, which [`Checkpoint::failures`] lists for
+//! converting them again.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::batch::Target;
+use crate::comic::ComicConfig;
+
+pub const FILE_NAME: &str = ".comically-checkpoint";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Record {
+    Settings(String),
+    Targets(Vec<Target>),
+    Queued(PathBuf),
+    Started(PathBuf),
+    Completed(PathBuf),
+    Failed(PathBuf),
+}
+
+/// What an interrupted batch got through
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resume {
+    /// Comics converted before the interruption
+    pub completed: HashSet<PathBuf>,
+    /// The comic that was being converted when the batch stopped
+    pub interrupted: Option<PathBuf>,
+    /// Comics that failed to convert and weren't converted since
+    pub failed: HashSet<PathBuf>,
+}
+
+/// The comics a batch failed to convert, to convert them again like it did
+#[derive(Debug, Clone)]
+pub struct Failures {
+    pub targets: Vec<Target>,
+    pub inputs: Vec<PathBuf>,
+}
+
+impl Resume {
+    pub fn is_completed(&self, input: &Path) -> bool {
+        self.completed.contains(&absolute(input))
+    }
+}
+
+pub struct Checkpoint {
+    path: PathBuf,
+    settings: String,
+    targets: Vec<Target>,
+    file: Option<File>,
+    // completed comics of the resumed batch, written with the settings
+    carried_over: Vec<PathBuf>,
+    // queued comics that aren't completed yet
+    remaining: HashSet<PathBuf>,
+}
+
+impl Checkpoint {
+    /// Reads the journal an interrupted batch left in `output_dir`, if it converted with
+    /// the same settings
+    pub fn find(output_dir: &Path, configs: &[ComicConfig]) -> Option<Resume> {
+        let journal = Journal::read(output_dir)?;
+        (journal.settings == fingerprint(configs)).then_some(journal.resume)
+    }
+
+    /// The comics the batch that left its journal in `output_dir` failed on, whatever
+    /// its settings. `None` for journals written before targets were recorded
+    pub fn failures(output_dir: &Path) -> Option<Failures> {
+        let journal = Journal::read(output_dir)?;
+        let mut inputs: Vec<PathBuf> = journal.resume.failed.into_iter().collect();
+        inputs.sort();
+        Some(Failures {
+            targets: journal.targets?,
+            inputs,
+        })
+    }
+
+    /// Starts a new journal in `output_dir`, replacing any previous one, keeping the
+    /// comics `resume` completed when continuing an interrupted batch
+    pub fn create(output_dir: &Path, targets: &[Target], resume: Option<&Resume>) -> Result<Self> {
+        let configs: Vec<ComicConfig> = targets.iter().map(|t| t.config.clone()).collect();
+        let path = output_dir.join(FILE_NAME);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("Failed to remove {}", path.display()));
+            }
+            _ => {}
+        }
+
+        Ok(Self {
+            path,
+            settings: fingerprint(&configs),
+            targets: targets
+                .iter()
+                .map(|target| Target {
+                    config: target.config.clone(),
+                    output_dir: absolute(&target.output_dir),
+                })
+                .collect(),
+            file: None,
+            carried_over: resume
+                .map(|resume| resume.completed.iter().cloned().collect())
+                .unwrap_or_default(),
+            remaining: HashSet::new(),
+        })
+    }
+
+    pub fn queued(&mut self, input: &Path) {
+        let input = absolute(input);
+        self.write(&Record::Queued(input.clone()));
+        self.remaining.insert(input);
+    }
+
+    pub fn started(&mut self, input: &Path) {
+        self.write(&Record::Started(absolute(input)));
+    }
+
+    /// Records a converted comic, removing the journal once every queued comic is done
+    pub fn completed(&mut self, input: &Path) {
+        let input = absolute(input);
+        self.write(&Record::Completed(input.clone()));
+        self.remaining.remove(&input);
+
+        if self.remaining.is_empty() {
+            self.file = None;
+            self.carried_over.clear();
+            if let Err(e) = fs::remove_file(&self.path) {
+                log::warn!("failed to remove {}: {e}", self.path.display());
+            }
+        }
+    }
+
+    /// Records a comic that failed, listed by [`Checkpoint::failures`] until it's completed
+    pub fn failed(&mut self, input: &Path) {
+        self.write(&Record::Failed(absolute(input)));
+    }
+
+    // best effort: a failed write only costs reconverting comics when resuming
+    fn write(&mut self, record: &Record) {
+        if let Err(e) = self.try_write(record) {
+            log::warn!("failed to update {}: {e}", self.path.display());
+        }
+    }
+
+    fn try_write(&mut self, record: &Record) -> Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let mut file = File::create(&self.path)?;
+                write_record(&mut file, &Record::Settings(self.settings.clone()))?;
+                write_record(&mut file, &Record::Targets(self.targets.clone()))?;
+                for path in &self.carried_over {
+                    write_record(&mut file, &Record::Completed(path.clone()))?;
+                }
+                self.file.insert(file)
+            }
+        };
+        write_record(file, record)
+    }
+}
+
+// what a journal says about its batch
+struct Journal {
+    settings: String,
+    targets: Option<Vec<Target>>,
+    resume: Resume,
+}
+
+impl Journal {
+    fn read(output_dir: &Path) -> Option<Self> {
+        let journal = fs::read_to_string(output_dir.join(FILE_NAME)).ok()?;
+        let mut records = journal
+            .lines()
+            .map_while(|line| serde_json::from_str::<Record>(line).ok());
+
+        let Some(Record::Settings(settings)) = records.next() else {
+            return None;
+        };
+        let mut targets = None;
+        let mut resume = Resume::default();
+        for record in records {
+            match record {
+                Record::Targets(recorded) => targets = Some(recorded),
+                Record::Started(path) => resume.interrupted = Some(path),
+                Record::Completed(path) => {
+                    if resume.interrupted.as_ref() == Some(&path) {
+                        resume.interrupted = None;
+                    }
+                    resume.failed.remove(&path);
+                    resume.completed.insert(path);
+                }
+                Record::Failed(path) => {
+                    if resume.interrupted.as_ref() == Some(&path) {
+                        resume.interrupted = None;
+                    }
+                    resume.failed.insert(path);
+                }
+                Record::Settings(_) | Record::Queued(_) => {}
+            }
+        }
+        Some(Self {
+            settings,
+            targets,
+            resume,
+        })
+    }
+}
+
+fn write_record(file: &mut File, record: &Record) -> Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    Ok(())
+}
+
+// a journal only applies to a batch with the same targets
+fn fingerprint(configs: &[ComicConfig]) -> String {
+    let json = serde_json::to_vec(configs).expect("configs always serialize");
+    crate::output::sha256(&json)
+}
+
+fn absolute(input: &Path) -> PathBuf {
+    std::path::absolute(input).unwrap_or_else(|_| input.to_path_buf())
+}
+
+#[test]
+fn resumes_interrupted_batch() {
+    let dir = tempfile::tempdir().unwrap();
+    let configs = [ComicConfig::default()];
+    let targets = [Target {
+        config: ComicConfig::default(),
+        output_dir: dir.path().to_path_buf(),
+    }];
+    assert!(Checkpoint::find(dir.path(), &configs).is_none());
+
+    let mut checkpoint = Checkpoint::create(dir.path(), &targets, None).unwrap();
+    for input in ["a.cbz", "b.cbz", "c.cbz"] {
+        checkpoint.queued(Path::new(input));
+    }
+    checkpoint.started(Path::new("a.cbz"));
+    checkpoint.completed(Path::new("a.cbz"));
+    checkpoint.started(Path::new("b.cbz"));
+    drop(checkpoint);
+
+    // other settings start over
+    let other = ComicConfig {
+        gamma: 1.0,
+        ..ComicConfig::default()
+    };
+    assert!(Checkpoint::find(dir.path(), &[other]).is_none());
+
+    let resume = Checkpoint::find(dir.path(), &configs).unwrap();
+    assert!(resume.is_completed(Path::new("a.cbz")));
+    assert!(!resume.is_completed(Path::new("b.cbz")));
+    assert_eq!(resume.interrupted, Some(absolute(Path::new("b.cbz"))));
+
+    let mut checkpoint = Checkpoint::create(dir.path(), &targets, Some(&resume)).unwrap();
+    for input in ["b.cbz", "c.cbz"] {
+        checkpoint.queued(Path::new(input));
+        checkpoint.started(Path::new(input));
+    }
+    checkpoint.completed(Path::new("b.cbz"));
+    let resume = Checkpoint::find(dir.path(), &configs).unwrap();
+    assert_eq!(resume.completed.len(), 2);
+    assert_eq!(resume.interrupted, Some(absolute(Path::new("c.cbz"))));
+
+    // done with the batch, nothing left to resume
+    checkpoint.completed(Path::new("c.cbz"));
+    assert!(Checkpoint::find(dir.path(), &configs).is_none());
+}
+
+#[test]
+fn lists_failed_comics() {
+    let dir = tempfile::tempdir().unwrap();
+    let targets = [Target {
+        config: ComicConfig {
+            gamma: 1.0,
+            ..ComicConfig::default()
+        },
+        output_dir: dir.path().join("out"),
+    }];
+
+    let mut checkpoint = Checkpoint::create(dir.path(), &targets, None).unwrap();
+    for input in ["a.cbz", "b.cbz", "c.cbz"] {
+        checkpoint.queued(Path::new(input));
+        checkpoint.started(Path::new(input));
+    }
+    checkpoint.failed(Path::new("a.cbz"));
+    checkpoint.completed(Path::new("b.cbz"));
+    checkpoint.failed(Path::new("c.cbz"));
+
+    // found with any settings, and a failed comic isn't an interrupted one
+    let failures = Checkpoint::failures(dir.path()).unwrap();
+    assert_eq!(
+        failures.inputs,
+        [absolute(Path::new("a.cbz")), absolute(Path::new("c.cbz"))]
+    );
+    assert_eq!(failures.targets[0].config, targets[0].config);
+    assert_eq!(
+        failures.targets[0].output_dir,
+        absolute(&targets[0].output_dir)
+    );
+    let resume = Checkpoint::find(dir.path(), &[targets[0].config.clone()]).unwrap();
+    assert_eq!(resume.interrupted, None);
+
+    // converted on a retry, it isn't listed anymore
+    let mut checkpoint = Checkpoint::create(dir.path(), &failures.targets, None).unwrap();
+    checkpoint.queued(Path::new("a.cbz"));
+    checkpoint.queued(Path::new("c.cbz"));
+    checkpoint.completed(Path::new("a.cbz"));
+    checkpoint.failed(Path::new("c.cbz"));
+    let failures = Checkpoint::failures(dir.path()).unwrap();
+    assert_eq!(failures.inputs, [absolute(Path::new("c.cbz"))]);
+}
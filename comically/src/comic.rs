@@ -1,8 +1,9 @@
 use std::path::Path;
 use std::{fs, path::PathBuf};
 
+use crate::archive::ArchiveFile;
 use crate::device::Device;
-use crate::image::ImageFormat;
+use crate::image::{AdaptiveQuality, ImageFormat};
 
 #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum SplitStrategy {
@@ -12,6 +13,622 @@ pub enum SplitStrategy {
     RotateAndSplit,
 }
 
+/// Fill color for the space around pages that don't match the device's aspect ratio.
+///
+/// Stored as a gray level (`0`-`255`), `"#rrggbb"` or `"auto"` in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "MarginColorRepr", into = "MarginColorRepr")]
+pub enum MarginColor {
+    Gray(u8),
+    // pages are processed in grayscale, so this is converted to its luma for now
+    Rgb(u8, u8, u8),
+    /// Sampled from the edges of each page so the letterboxing blends with the art
+    Auto,
+}
+
+impl MarginColor {
+    pub const BLACK: Self = MarginColor::Gray(0);
+    pub const WHITE: Self = MarginColor::Gray(255);
+
+    /// Gray level to fill with, `border` gives the page's edge color for [`MarginColor::Auto`]
+    pub fn luma(&self, border: impl FnOnce() -> u8) -> u8 {
+        match *self {
+            MarginColor::Gray(gray) => gray,
+            MarginColor::Rgb(r, g, b) => {
+                ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8
+            }
+            MarginColor::Auto => border(),
+        }
+    }
+}
+
+impl std::fmt::Display for MarginColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            &MarginColor::BLACK => f.write_str("black"),
+            &MarginColor::WHITE => f.write_str("white"),
+            MarginColor::Gray(gray) => write!(f, "{gray}"),
+            MarginColor::Rgb(r, g, b) => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+            MarginColor::Auto => f.write_str("auto"),
+        }
+    }
+}
+
+impl std::str::FromStr for MarginColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "black" => Ok(MarginColor::BLACK),
+            "white" => Ok(MarginColor::WHITE),
+            "auto" => Ok(MarginColor::Auto),
+            hex if hex.starts_with('#') => {
+                let channel = |i: usize| {
+                    hex.get(i..i + 2)
+                        .and_then(|c| u8::from_str_radix(c, 16).ok())
+                };
+                match (hex.len(), channel(1), channel(3), channel(5)) {
+                    (7, Some(r), Some(g), Some(b)) => Ok(MarginColor::Rgb(r, g, b)),
+                    _ => Err(format!("invalid hex color: {s}")),
+                }
+            }
+            gray => gray
+                .parse()
+                .map(MarginColor::Gray)
+                .map_err(|_| format!("invalid margin color: {s}")),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum MarginColorRepr {
+    Gray(u8),
+    Text(String),
+}
+
+impl TryFrom<MarginColorRepr> for MarginColor {
+    type Error = String;
+
+    fn try_from(repr: MarginColorRepr) -> Result<Self, Self::Error> {
+        match repr {
+            MarginColorRepr::Gray(gray) => Ok(MarginColor::Gray(gray)),
+            MarginColorRepr::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<MarginColor> for MarginColorRepr {
+    fn from(color: MarginColor) -> Self {
+        match color {
+            MarginColor::Gray(gray) => MarginColorRepr::Gray(gray),
+            other => MarginColorRepr::Text(other.to_string()),
+        }
+    }
+}
+
+/// Rotation applied to every output page, for devices read in landscape
+/// (e.g. a reMarkable in its folio)
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Shape of the tone curve [`ComicConfig::gamma`] sets
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GammaCurve {
+    /// A power curve on the pixel values as stored
+    #[default]
+    Power,
+    /// The same power curve on linear light, decoding and encoding the sRGB transfer
+    /// function around it, so midtones shift the way the eye expects
+    Srgb,
+    /// A curve measured for a device: the output value of each of the 256 gray levels.
+    /// Gamma is ignored
+    Lut(Lut),
+}
+
+impl GammaCurve {
+    /// Next curve picked in the TUI, a LUT comes from the config file or the CLI
+    pub fn cycle(&self) -> Self {
+        match self {
+            GammaCurve::Power => GammaCurve::Srgb,
+            GammaCurve::Srgb | GammaCurve::Lut(_) => GammaCurve::Power,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GammaCurve::Power => "power",
+            GammaCurve::Srgb => "srgb",
+            GammaCurve::Lut(_) => "lut",
+        }
+    }
+}
+
+/// 256 output gray levels, kept in the config rather than as a path so a conversion
+/// doesn't depend on the file staying around
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "Vec<u8>", into = "Vec<u8>")]
+pub struct Lut(Box<[u8; 256]>);
+
+impl Lut {
+    /// Reads a LUT file: 256 values from 0 to 255, separated by whitespace or commas.
+    /// Lines starting with `#` are comments
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read LUT {}", path.display()))?;
+        let values = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse::<u8>()
+                    .with_context(|| format!("{value:?} isn't a gray level from 0 to 255"))
+            })
+            .collect::<anyhow::Result<Vec<u8>>>()
+            .with_context(|| format!("invalid LUT {}", path.display()))?;
+        Self::try_from(values)
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("invalid LUT {}", path.display()))
+    }
+
+    pub fn table(&self) -> &[u8; 256] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<u8>> for Lut {
+    type Error = String;
+
+    fn try_from(values: Vec<u8>) -> Result<Self, Self::Error> {
+        let len = values.len();
+        let table: Box<[u8; 256]> = values
+            .into_boxed_slice()
+            .try_into()
+            .map_err(|_| format!("a LUT has 256 values, not {len}"))?;
+        Ok(Self(table))
+    }
+}
+
+impl From<Lut> for Vec<u8> {
+    fn from(lut: Lut) -> Self {
+        lut.0.to_vec()
+    }
+}
+
+/// How output files are named
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum OutputNaming {
+    /// The title of the comic with the extension of the format
+    #[default]
+    Comically,
+    /// Like Kindle Comic Converter does, see [`crate::kcc::output_name`]
+    Kcc,
+}
+
+/// How pages smaller than the screen are enlarged
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum UpscalePolicy {
+    /// Resampled while resizing, fast but blurry for low resolution scans
+    #[default]
+    Resample,
+    /// Upscaled by a neural network first, see [`crate::image::upscale`]
+    SuperResolution,
+}
+
+/// Where pages are resized and their tones adjusted
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Backend {
+    #[default]
+    Cpu,
+    /// With wgpu, see [`crate::image::gpu`], on the CPU when there's no adapter
+    Gpu,
+}
+
+/// Corner of the page an overlay is drawn in
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum StampPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// Page number overlay, handy to reference pages or check their order
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PageStamp {
+    pub position: StampPosition,
+    /// Height of the text in pixels
+    pub size: u32,
+    /// 0.0 (invisible) to 1.0 (opaque)
+    pub opacity: f32,
+    /// Put the chapter (the page's folder in the archive) before the number
+    pub chapter: bool,
+}
+
+impl Default for PageStamp {
+    fn default() -> Self {
+        Self {
+            position: StampPosition::BottomRight,
+            size: 21,
+            opacity: 0.8,
+            chapter: false,
+        }
+    }
+}
+
+/// Image overlaid on pages, e.g. a personal library stamp
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Watermark {
+    /// Image file, its transparency is kept
+    pub path: PathBuf,
+    pub position: StampPosition,
+    /// 0.0 (invisible) to 1.0 (as opaque as the image)
+    pub opacity: f32,
+    /// Only overlay the cover instead of every page
+    pub cover_only: bool,
+}
+
+impl Watermark {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            position: StampPosition::BottomRight,
+            opacity: 0.5,
+            cover_only: false,
+        }
+    }
+}
+
+/// Pixels always removed from the edges of every page before auto-crop, e.g. the
+/// footer banner or page numbers some publishers print on every page.
+///
+/// Written like `bottom=60,top=20` on the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FixedCrop {
+    #[serde(default)]
+    pub top: u32,
+    #[serde(default)]
+    pub bottom: u32,
+    #[serde(default)]
+    pub left: u32,
+    #[serde(default)]
+    pub right: u32,
+}
+
+impl FixedCrop {
+    /// The common layouts, offered by the TUI: footer banners and page numbers at the
+    /// bottom, headers at the top, page numbers at both, and the binding's shadow or
+    /// scanner edges on the sides
+    pub const PRESETS: [FixedCrop; 8] = [
+        Self::bottom(40),
+        Self::bottom(60),
+        Self::bottom(80),
+        Self::bottom(120),
+        Self::top(40),
+        Self::top(60),
+        Self {
+            top: 40,
+            bottom: 40,
+            left: 0,
+            right: 0,
+        },
+        Self {
+            top: 0,
+            bottom: 0,
+            left: 40,
+            right: 40,
+        },
+    ];
+
+    /// Just the bottom of the page, like the footer presets
+    pub const fn bottom(pixels: u32) -> Self {
+        Self {
+            top: 0,
+            bottom: pixels,
+            left: 0,
+            right: 0,
+        }
+    }
+
+    /// Just the top of the page, like the header presets
+    pub const fn top(pixels: u32) -> Self {
+        Self {
+            top: pixels,
+            bottom: 0,
+            left: 0,
+            right: 0,
+        }
+    }
+
+    /// The preset after `crop` in [`FixedCrop::PRESETS`], `None` after the last one or
+    /// when `crop` isn't a preset
+    pub fn next_preset(crop: Option<FixedCrop>) -> Option<FixedCrop> {
+        let next = match crop {
+            None => 0,
+            Some(crop) => Self::PRESETS.iter().position(|&preset| preset == crop)? + 1,
+        };
+        Self::PRESETS.get(next).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// What's kept of a `width`x`height` page as left, top, width and height, with the
+    /// regions measured in pixels of the page before it was enlarged `scale` times.
+    /// `None` when nothing is removed, or when nothing would be left.
+    pub fn bounds(&self, width: u32, height: u32, scale: u32) -> Option<(u32, u32, u32, u32)> {
+        let [top, bottom, left, right] =
+            [self.top, self.bottom, self.left, self.right].map(|edge| edge.saturating_mul(scale));
+        let kept_width = width.checked_sub(left.saturating_add(right))?;
+        let kept_height = height.checked_sub(top.saturating_add(bottom))?;
+        (!self.is_empty() && kept_width > 0 && kept_height > 0).then_some((
+            left,
+            top,
+            kept_width,
+            kept_height,
+        ))
+    }
+}
+
+impl std::fmt::Display for FixedCrop {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let edges = [
+            ("top", self.top),
+            ("bottom", self.bottom),
+            ("left", self.left),
+            ("right", self.right),
+        ];
+        let edges: Vec<String> = edges
+            .iter()
+            .filter(|(_, pixels)| *pixels > 0)
+            .map(|(edge, pixels)| format!("{edge}={pixels}"))
+            .collect();
+        f.write_str(&edges.join(","))
+    }
+}
+
+impl std::str::FromStr for FixedCrop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut crop = FixedCrop::default();
+        for region in s.split(',') {
+            let (edge, pixels) = region
+                .split_once('=')
+                .ok_or_else(|| format!("expected EDGE=PIXELS, got {}", region.trim()))?;
+            let pixels = pixels
+                .trim()
+                .trim_end_matches("px")
+                .parse()
+                .map_err(|_| format!("invalid number of pixels: {}", pixels.trim()))?;
+            match edge.trim() {
+                "top" => crop.top = pixels,
+                "bottom" => crop.bottom = pixels,
+                "left" => crop.left = pixels,
+                "right" => crop.right = pixels,
+                edge => {
+                    return Err(format!(
+                        "unknown edge {edge}, expected top, bottom, left or right"
+                    ))
+                }
+            }
+        }
+        Ok(crop)
+    }
+}
+
+/// Typesets translations from a sidecar file over the pages, see [`crate::image::overlay`]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TranslationOverlay {
+    /// TrueType or OpenType font, the built-in bitmap font when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<PathBuf>,
+}
+
+/// A generated page with the title and credits of the volume, see
+/// [`crate::image::title_page`]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TitlePage {
+    #[serde(default)]
+    pub position: TitlePagePosition,
+    /// TrueType or OpenType font, the built-in bitmap font when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TitlePagePosition {
+    /// First, where a printed volume has its cover page
+    BeforeCover,
+    /// Second, where a printed volume has its title page
+    #[default]
+    AfterCover,
+}
+
+/// Series a volume belongs to, so e-readers and Calibre group the volumes together
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Series {
+    pub name: String,
+    /// Position of the volume in the series, e.g. `3`, or `3.5` for an extra
+    pub index: Option<f32>,
+}
+
+impl Series {
+    /// The `Series` and `Number` fields of a `ComicInfo.xml`
+    pub fn from_comic_info(info: &str) -> Option<Self> {
+        let name =
+            crate::archive::comic_info_text(info, "Series").filter(|name| !name.is_empty())?;
+        let index = crate::archive::comic_info_field(info, "Number").and_then(|n| n.parse().ok());
+        Some(Series { name, index })
+    }
+}
+
+/// Pages of a comic to convert, numbered from 1 in the natural order of their file
+/// names, see [`crate::archive::natural_cmp`].
+///
+/// Written like `5-120,130-`: single pages and ranges, which can be open at either end.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PageRanges(Vec<PageRange>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRange {
+    pub first: usize,
+    /// Up to the last page when unset
+    pub last: Option<usize>,
+}
+
+impl PageRanges {
+    pub fn new(ranges: Vec<PageRange>) -> Self {
+        Self(ranges)
+    }
+
+    pub fn ranges(&self) -> &[PageRange] {
+        &self.0
+    }
+
+    /// Whether the 1-based `page` is selected
+    pub fn contains(&self, page: usize) -> bool {
+        self.0
+            .iter()
+            .any(|range| range.first <= page && range.last.is_none_or(|last| page <= last))
+    }
+}
+
+impl std::fmt::Display for PageRanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, range) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match range.last {
+                Some(last) if last == range.first => write!(f, "{last}")?,
+                Some(last) => write!(f, "{}-{last}", range.first)?,
+                None => write!(f, "{}-", range.first)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for PageRanges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let page = |page: &str| match page.trim().parse::<usize>() {
+            Ok(0) => Err("pages are numbered from 1".to_string()),
+            Ok(page) => Ok(page),
+            Err(_) => Err(format!("invalid page number: {page}")),
+        };
+
+        let ranges = s
+            .split(',')
+            .map(|range| {
+                let (first, last) = match range.split_once('-') {
+                    Some((first, last)) => {
+                        let first = match first.trim() {
+                            "" => 1,
+                            first => page(first)?,
+                        };
+                        let last = match last.trim() {
+                            "" => None,
+                            last => Some(page(last)?),
+                        };
+                        (first, last)
+                    }
+                    None => {
+                        let page = page(range)?;
+                        (page, Some(page))
+                    }
+                };
+                if last.is_some_and(|last| last < first) {
+                    return Err(format!("invalid page range: {}", range.trim()));
+                }
+                Ok(PageRange { first, last })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PageRanges(ranges))
+    }
+}
+
+impl TryFrom<String> for PageRanges {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PageRanges> for String {
+    fn from(ranges: PageRanges) -> Self {
+        ranges.to_string()
+    }
+}
+
+/// What becomes of animated pages (GIF, APNG, animated WebP), such as bonus pages of
+/// digital releases
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AnimatedPages {
+    #[default]
+    FirstFrame,
+    /// For animations that start on a blank or title frame
+    MiddleFrame,
+    /// Left out of the output, with a warning
+    Skip,
+}
+
+/// How many pages of a comic are decoded and processed at once, see
+/// [`crate::image::governor`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Concurrency {
+    /// As many as fit in the memory the system has available, from the size of each page
+    #[default]
+    Auto,
+    /// At most this many, whatever their size
+    Fixed(usize),
+}
+
+/// What becomes of empty files and placeholder images (a few pixels wide, e.g. 1x1)
+/// among the pages, which some archives pad their page count with
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum PlaceholderPages {
+    /// Left out of the output, with a warning
+    #[default]
+    Skip,
+    /// Fails the comic, for archives that should be checked by hand
+    Fail,
+}
+
+/// Background behind the pages of an EPUB, visible around pages that don't fill the screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum PageBackground {
+    #[default]
+    White,
+    /// Blends with the reader's night mode instead of showing white borders
+    Black,
+}
+
+impl PageBackground {
+    /// CSS color of the background
+    pub fn css_color(self) -> &'static str {
+        match self {
+            PageBackground::White => "#ffffff",
+            PageBackground::Black => "#000000",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum OutputFormat {
     Mobi,
@@ -31,30 +648,233 @@ impl OutputFormat {
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct ComicConfig {
+    // format of the config file, see `migrate`
+    #[serde(default)]
+    pub version: u32,
     pub device: Device,
     pub right_to_left: bool,
+    // guess the reading direction of each comic, `right_to_left` is the fallback
+    #[serde(default)]
+    pub detect_direction: bool,
     pub split: SplitStrategy,
     pub auto_crop: bool,
+    // regions cut from the edges of every page before auto-crop, e.g. a footer banner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_crop: Option<FixedCrop>,
     pub brightness: i32,
+    // brings yellowed paper to white before the other tone adjustments
+    #[serde(default)]
+    pub paper_white: bool,
+    // S-curve around mid-gray: -100-100, 0 leaves the tones alone
+    #[serde(default)]
+    pub contrast: i32,
     // Gamma correction: 0.0-3.0
     pub gamma: f32,
+    // shape of the gamma correction, see `GammaCurve`
+    #[serde(default)]
+    pub gamma_curve: GammaCurve,
+    // dithers pages down to the 16 gray levels of e-ink screens so gradients don't band,
+    // defaults to the device's tuning
+    #[serde(default)]
+    pub dither: bool,
+    // color pages stay in color for screens that show it, see `image::color`, defaults to
+    // the device's tuning
+    #[serde(default)]
+    pub keep_color: bool,
     pub output_format: OutputFormat,
-    pub margin_color: Option<u8>,
+    pub margin_color: Option<MarginColor>,
     pub image_format: ImageFormat,
+    #[serde(default)]
+    pub rotation: Rotation,
+    #[serde(default)]
+    pub upscale: UpscalePolicy,
+    // resizing and tone adjustments on the CPU or with wgpu, see `Backend`
+    #[serde(default)]
+    pub backend: Backend,
+    #[serde(default)]
+    pub page_background: PageBackground,
+    /// Without splitting, keeps double pages as landscape pages instead of shrinking them
+    /// to portrait, shown on their own by readers that support spreads
+    #[serde(default)]
+    pub landscape_spreads: bool,
+    /// Turns the cover upright when it was delivered sideways, see [`crate::cover::sideways`]
+    #[serde(default)]
+    pub auto_rotate_cover: bool,
+    // which frame of animated pages is converted, or whether they're left out
+    #[serde(default)]
+    pub animated_pages: AnimatedPages,
+    // whether empty files and placeholder images are left out or fail the comic
+    #[serde(default)]
+    pub placeholder_pages: PlaceholderPages,
+    // how many pages are decoded at once, throttled by their size by default
+    #[serde(default)]
+    pub concurrency: Concurrency,
+    // longest side of the pages in place of the device's screen, for EPUBs read on
+    // screens of unknown size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_dimension: Option<u32>,
+    // longest side of a smaller copy EPUBs embed with each page, offered to readers in a
+    // `srcset` so phones can pick it over the full page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub srcset: Option<u32>,
+    // height of the strips CBZ pages are stacked into for continuous scrolling, see
+    // `image::strip`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long_strip: Option<u32>,
+    // afterwords and other pages of plain text are read with OCR into reflowable EPUB
+    // pages, needs the `ocr` feature and Tesseract
+    #[serde(default)]
+    pub text_pages: bool,
+    // starts each chapter (folder in the archive) on this side of a spread when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chapter_side: Option<PageSide>,
+    // where temporary files go in place of the system's temporary directory, e.g. a fast
+    // SSD, see `temp::TempRoot`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp_dir: Option<PathBuf>,
+    // converts only these pages when set, e.g. to leave out scanlation credits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pages: Option<PageRanges>,
+    // read the pages of zips inside the comic's archive, see `archive::ArchiveOptions`
+    #[serde(default)]
+    pub nested_archives: bool,
+    // how zip entries named without the UTF-8 flag are read, see `archive::FilenameEncoding`
+    #[serde(default)]
+    pub filename_encoding: crate::archive::FilenameEncoding,
+    // stamps page numbers when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_stamp: Option<PageStamp>,
+    // overlays an image on the pages when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<Watermark>,
+    // letters translations from `<comic>.translations.json` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation_overlay: Option<TranslationOverlay>,
+    // the translations of the comic being converted, read by `for_comic`
+    #[serde(skip)]
+    pub translations: Option<crate::image::overlay::Translations>,
+    // pipes each page through an external command when set, see `image::filter`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_filter: Option<crate::image::filter::PageFilter>,
+    // per-page transforms registered by library users, see `image::hooks`
+    #[serde(skip)]
+    pub hooks: crate::image::hooks::Hooks,
+    // generated from the series and metadata, for comics without a title page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_page: Option<TitlePage>,
+    // series of the output, completed from the comic's ComicInfo.xml
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series: Option<Series>,
+    // title, authors and description of the output, see `metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<crate::metadata::Metadata>,
+    // how output files are named, see `OutputNaming`
+    #[serde(default)]
+    pub output_naming: OutputNaming,
+    // names output files from their series and numbering instead, see `filename::render`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_template: Option<String>,
+    // write a `.sha256` file next to each output
+    #[serde(default)]
+    pub write_checksum: bool,
+    // refresh an OPDS feed of the output directory after converting
+    #[serde(default)]
+    pub opds_catalog: bool,
+    // overrides the JPEG/WebP quality per page when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive_quality: Option<AdaptiveQuality>,
+    // reuse pages encoded by earlier conversions, see `cache`
+    #[serde(default = "default_page_cache")]
+    pub page_cache: bool,
+    // megabytes the page cache is trimmed to after each conversion, least recently used
+    // pages first, 0 never trims it
+    #[serde(default = "default_page_cache_mb")]
+    pub page_cache_mb: u32,
+    // copy grayscale JPEGs that already fit the device through without re-encoding them,
+    // their tones are left alone, see `image::reuse`
+    #[serde(default)]
+    pub reuse_pages: bool,
+    // same input, same output bytes: content derived book ids and fixed zip timestamps
+    #[serde(default)]
+    pub deterministic: bool,
+    // MOBI outputs whose pages take more megabytes than this are split into parts, as
+    // KindleGen fails on EPUBs around 650 MB, 0 never splits them
+    #[serde(default = "default_mobi_part_mb")]
+    pub mobi_part_mb: u32,
+}
+
+// hex digits of `ComicConfig::fingerprint`
+const FINGERPRINT_LEN: usize = 12;
+
+fn default_page_cache() -> bool {
+    true
+}
+
+fn default_page_cache_mb() -> u32 {
+    2048
+}
+
+fn default_mobi_part_mb() -> u32 {
+    600
 }
 
 impl Default for ComicConfig {
     fn default() -> Self {
         Self {
+            version: crate::migrate::CONFIG_VERSION,
             device: crate::device::Preset::KindlePw11.into(),
             right_to_left: true,
+            detect_direction: false,
             split: SplitStrategy::RotateAndSplit,
             auto_crop: true,
+            fixed_crop: None,
             brightness: -10,
+            paper_white: false,
+            contrast: 0,
             gamma: 1.8,
+            gamma_curve: GammaCurve::Power,
+            dither: true,
+            keep_color: false,
             output_format: OutputFormat::Mobi,
             margin_color: None,
             image_format: ImageFormat::Jpeg { quality: 85 },
+            rotation: Rotation::None,
+            upscale: UpscalePolicy::Resample,
+            backend: Backend::Cpu,
+            output_naming: OutputNaming::Comically,
+            output_template: None,
+            write_checksum: false,
+            opds_catalog: false,
+            adaptive_quality: None,
+            page_cache: true,
+            page_cache_mb: default_page_cache_mb(),
+            reuse_pages: false,
+            deterministic: false,
+            mobi_part_mb: default_mobi_part_mb(),
+            page_background: PageBackground::White,
+            nested_archives: false,
+            filename_encoding: crate::archive::FilenameEncoding::Auto,
+            pages: None,
+            landscape_spreads: false,
+            auto_rotate_cover: false,
+            animated_pages: AnimatedPages::FirstFrame,
+            placeholder_pages: PlaceholderPages::Skip,
+            concurrency: Concurrency::Auto,
+            max_dimension: None,
+            srcset: None,
+            long_strip: None,
+            text_pages: false,
+            chapter_side: None,
+            temp_dir: None,
+            page_stamp: None,
+            watermark: None,
+            translation_overlay: None,
+            translations: None,
+            page_filter: None,
+            hooks: Default::default(),
+            title_page: None,
+            series: None,
+            metadata: None,
         }
     }
 }
@@ -63,9 +883,25 @@ impl ComicConfig {
     pub fn load() -> Option<Self> {
         let config_path = Self::config_path()?;
 
-        fs::read_to_string(&config_path)
+        let contents = fs::read_to_string(&config_path).ok()?;
+        crate::migrate::upgrade_file(&config_path, &contents)
+            .and_then(|config| Ok(serde_json::from_value(config)?))
+            .inspect_err(|e| log::warn!("failed to load config: {e}"))
             .ok()
-            .and_then(|contents| serde_json::from_str(&contents).ok())
+    }
+
+    /// Read a config file from an explicit location, unlike [`ComicConfig::load`] failures are reported
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config {}", path.display()))?;
+        // explicit configs are upgraded in memory only, they may be shared or read-only
+        let mut config: serde_json::Value = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse config {}", path.display()))?;
+        crate::migrate::migrate(&mut config);
+        serde_json::from_value(config)
+            .with_context(|| format!("failed to parse config {}", path.display()))
     }
 
     pub fn save(&self) -> Option<()> {
@@ -81,14 +917,169 @@ impl ComicConfig {
             .and_then(|json| fs::write(&config_path, json).ok())
     }
 
-    fn config_path() -> Option<PathBuf> {
+    /// Location of the config file shared by the frontends
+    pub fn config_path() -> Option<PathBuf> {
         let home = std::env::home_dir()?;
         Some(home.join(".config").join("comically").join("config.json"))
     }
 
+    /// Config for converting one comic, with its reading direction detected when enabled
+    /// and the series filled in from its `ComicInfo.xml`, or else its file name
+    pub fn for_comic(&self, comic: &ComicFile, files: &[ArchiveFile]) -> Self {
+        let mut config = self.clone();
+        let from_info =
+            crate::archive::read_comic_info(comic).and_then(|info| Series::from_comic_info(&info));
+        match (&mut config.series, from_info) {
+            (None, from_info) => config.series = from_info,
+            (Some(series), Some(from_info)) => series.index = series.index.or(from_info.index),
+            (Some(_), None) => {}
+        }
+
+        // only numbered names like `Series v03` are taken as part of a series
+        let parsed = crate::filename::parse(comic.title());
+        if let Some(index) = parsed.index() {
+            if config.series.is_none() && !parsed.series.is_empty() {
+                config.series = Some(Series {
+                    name: parsed.series,
+                    index: None,
+                });
+            }
+            if let Some(series) = &mut config.series {
+                series.index = series.index.or(Some(index));
+            }
+        }
+
+        if self.translation_overlay.is_some() {
+            match crate::image::overlay::Translations::find(comic) {
+                Ok(translations) => config.translations = translations,
+                Err(e) => log::warn!("Not overlaying translations: {e:#}"),
+            }
+        }
+
+        if self.detect_direction {
+            if let Some(rtl) = crate::direction::detect(comic, files) {
+                config.right_to_left = rtl;
+            }
+        }
+        config
+    }
+
+    /// Switches to `device` along with its [`Tuning`](crate::device::Tuning) defaults.
+    ///
+    /// A tuned setting is only switched while it still holds the previous device's
+    /// default, so from the highest precedence down the settings come from:
+    ///
+    /// 1. settings applied after this call, like command-line flags and per-comic overrides
+    /// 2. settings changed from the previous device's defaults, e.g. in the config file
+    /// 3. the new device's defaults
+    ///
+    /// Picking the current device again keeps the existing settings.
+    pub fn set_device(&mut self, device: Device) {
+        if self.device == device {
+            return;
+        }
+        let previous = self.device.tuning();
+        let tuning = device.tuning();
+        self.device = device;
+        if self.gamma == previous.gamma {
+            self.gamma = tuning.gamma;
+        }
+        if self.brightness == previous.brightness {
+            self.brightness = tuning.brightness;
+        }
+        if self.dither == previous.dither {
+            self.dither = tuning.dither;
+        }
+        if self.keep_color == previous.keep_color {
+            self.keep_color = tuning.keep_color;
+        }
+        // custom devices have no format of their own, so the one picked with them is kept
+        let native = previous.output_format == Some(self.output_format);
+        if let Some(format) = tuning.output_format.filter(|_| native) {
+            self.set_output_format(format);
+        }
+    }
+
+    /// Switches to `format`, and to JPEG pages at the same quality for MOBI, which only
+    /// takes JPEG
+    pub fn set_output_format(&mut self, format: OutputFormat) {
+        self.output_format = format;
+        if self.output_format == OutputFormat::Mobi {
+            let quality = match self.image_format {
+                ImageFormat::Jpeg { quality } | ImageFormat::WebP { quality } => quality,
+                ImageFormat::Png { .. } => 85,
+            };
+            self.image_format = ImageFormat::Jpeg { quality };
+        }
+    }
+
     pub fn device_dimensions(&self) -> (u32, u32) {
         self.device.dimensions()
     }
+
+    /// Box the pages are fit in: the device's screen, or a square of `max_dimension`
+    /// so pages keep their own shape
+    pub fn page_box(&self) -> (u32, u32) {
+        match self.max_dimension {
+            Some(max) => (max, max),
+            None => self.device_dimensions(),
+        }
+    }
+
+    /// Longest side of the small copies of pages, only EPUBs embed them
+    pub fn small_copy(&self) -> Option<u32> {
+        self.srcset
+            .filter(|_| self.output_format == OutputFormat::Epub)
+    }
+
+    /// Height of the strips the pages are stacked into, only CBZs are made of strips
+    pub fn long_strip(&self) -> Option<u32> {
+        self.long_strip
+            .filter(|_| self.output_format == OutputFormat::Cbz)
+    }
+
+    /// Whether pages of plain text are read into reflowable pages, only EPUBs have them
+    pub fn text_pages(&self) -> bool {
+        self.text_pages && self.output_format == OutputFormat::Epub
+    }
+
+    /// Short hash of the settings, the same for conversions configured alike, to tell
+    /// which settings an output was made with when comics have their own overrides.
+    ///
+    /// What's read from the comic and what only changes how the conversion runs, like
+    /// the temporary directory, is left out.
+    pub fn fingerprint(&self) -> String {
+        let settings = ComicConfig {
+            concurrency: Concurrency::default(),
+            temp_dir: None,
+            translations: None,
+            series: None,
+            metadata: None,
+            page_cache: false,
+            page_cache_mb: 0,
+            ..self.clone()
+        };
+        let mut hash = crate::output::sha256_of(&settings);
+        hash.truncate(FINGERPRINT_LEN);
+        hash
+    }
+
+    /// How the comic's archive is read
+    pub fn archive_options(&self) -> crate::archive::ArchiveOptions {
+        crate::archive::ArchiveOptions {
+            nested_archives: self.nested_archives,
+            filename_encoding: self.filename_encoding,
+        }
+    }
+
+    /// Size of the output pages, which is the page box turned sideways when rotated
+    pub fn viewport_dimensions(&self) -> (u32, u32) {
+        let (width, height) = self.page_box();
+        match self.rotation {
+            Rotation::None => (width, height),
+            Rotation::Clockwise | Rotation::CounterClockwise => (height, width),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -97,6 +1088,59 @@ pub struct ProcessedImage {
     pub data: Vec<u8>,
     pub dimensions: (u32, u32),
     pub format: ImageFormat,
+    /// Path of the page in the source archive
+    pub source: PathBuf,
+    /// 1-based position of the source page in the archive
+    pub page: usize,
+    pub part: PagePart,
+    /// Size of the source page before cropping and resizing
+    pub original_dimensions: (u32, u32),
+    /// Whether auto-crop or the fixed crop removed margins from the source page
+    pub cropped: bool,
+    /// Smaller copy of the page, see [`ComicConfig::srcset`]
+    pub small: Option<SmallCopy>,
+    /// What a page of plain text says, shown reflowable in its place by EPUBs, see
+    /// [`crate::image::ocr`]
+    pub text: Option<String>,
+}
+
+/// A page encoded again at a lower resolution
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmallCopy {
+    pub data: Vec<u8>,
+    pub dimensions: (u32, u32),
+}
+
+impl ProcessedImage {
+    /// Human readable name, e.g. `Page 12 (left half)`
+    pub fn label(&self) -> String {
+        match self.part {
+            PagePart::Whole => format!("Page {}", self.page),
+            PagePart::Left => format!("Page {} (left half)", self.page),
+            PagePart::Right => format!("Page {} (right half)", self.page),
+            PagePart::Rotated => format!("Page {} (spread)", self.page),
+            PagePart::Spread => format!("Page {} (landscape spread)", self.page),
+        }
+    }
+}
+
+/// Side of a two-page spread on the reader's screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PageSide {
+    Left,
+    Right,
+}
+
+/// Which part of a source page an output image shows
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PagePart {
+    Whole,
+    Left,
+    Right,
+    /// A double page turned sideways to fit the screen
+    Rotated,
+    /// A double page kept whole in landscape, see [`ComicConfig::landscape_spreads`]
+    Spread,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -151,6 +1195,40 @@ impl ComicFile {
         &self.title
     }
 
+    /// The same comic titled as `part` of `parts`, for outputs written in several parts
+    pub fn part(&self, part: usize, parts: usize) -> ComicFile {
+        ComicFile {
+            title: format!("{} (part {part} of {parts})", self.title),
+            ..self.clone()
+        }
+    }
+
+    /// The pages of the comic processed with `config`, yielded in order as soon as they're
+    /// done, see [`crate::image::process_iter`]. Pages that can't be read are left out
+    /// with a warning, like conversions do.
+    pub fn process_iter(&self, config: &ComicConfig) -> crate::image::ProcessedPages {
+        use anyhow::Context;
+
+        let archive = crate::archive::unarchive_comic_iter_with(self, config.archive_options())
+            .context("Failed to open comic archive");
+        let mut archive = match archive {
+            Ok(archive) => archive,
+            Err(e) => return crate::image::ProcessedPages::failed(e),
+        };
+        if let Some(ranges) = &config.pages {
+            archive = archive.select(ranges);
+        }
+        let files: Vec<ArchiveFile> = archive
+            .filter_map(|result| {
+                result
+                    .inspect_err(|e| log::warn!("Failed to load archive file: {e}"))
+                    .ok()
+            })
+            .collect();
+        let config = config.for_comic(self, &files);
+        crate::image::process_iter(files, &config)
+    }
+
     pub fn with_extension(&self, extension: OutputFormat) -> PathBuf {
         // don't use .with_extension() bc it replaces everything after the first dot
         let mut filename = self.title.clone();
@@ -169,3 +1247,147 @@ fn output_path_with_dots() {
         PathBuf::from("Dr. STONE v01 (2018) (Digital) (1r0n).epub")
     );
 }
+
+#[test]
+fn margin_color_config_values() {
+    let colors: Vec<MarginColor> =
+        serde_json::from_str(r##"[0, 128, "#ff8000", "auto"]"##).unwrap();
+    assert_eq!(
+        colors,
+        [
+            MarginColor::BLACK,
+            MarginColor::Gray(128),
+            MarginColor::Rgb(255, 128, 0),
+            MarginColor::Auto
+        ]
+    );
+    assert_eq!(
+        serde_json::to_string(&colors).unwrap(),
+        r##"[0,128,"#ff8000","auto"]"##
+    );
+}
+
+#[test]
+fn page_range_config_values() {
+    let ranges: PageRanges = "5-120, 130-".parse().unwrap();
+    assert!(!ranges.contains(4));
+    assert!(ranges.contains(5) && ranges.contains(120));
+    assert!(!ranges.contains(125));
+    assert!(ranges.contains(130) && ranges.contains(999));
+    assert_eq!(ranges.to_string(), "5-120,130-");
+
+    let ranges: PageRanges = serde_json::from_str(r#""-3,7""#).unwrap();
+    assert_eq!(
+        ranges.ranges(),
+        [
+            PageRange {
+                first: 1,
+                last: Some(3)
+            },
+            PageRange {
+                first: 7,
+                last: Some(7)
+            }
+        ]
+    );
+    assert_eq!(serde_json::to_string(&ranges).unwrap(), r#""1-3,7""#);
+
+    for invalid in ["", "0-4", "9-3", "a-b", "1,,2"] {
+        assert!(invalid.parse::<PageRanges>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn fixed_crop_config_values() {
+    let crop: FixedCrop = "bottom=60, top=20px".parse().unwrap();
+    assert_eq!(
+        crop,
+        FixedCrop {
+            top: 20,
+            bottom: 60,
+            ..FixedCrop::default()
+        }
+    );
+    assert_eq!(crop.to_string(), "top=20,bottom=60");
+    let json = serde_json::to_string(&crop).unwrap();
+    assert_eq!(serde_json::from_str::<FixedCrop>(&json).unwrap(), crop);
+    assert_eq!(
+        serde_json::from_str::<FixedCrop>(r#"{"bottom": 60}"#).unwrap(),
+        FixedCrop::bottom(60)
+    );
+
+    assert_eq!(crop.bounds(100, 200, 1), Some((0, 20, 100, 120)));
+    // measured on the page before it was upscaled
+    assert_eq!(crop.bounds(200, 400, 2), Some((0, 40, 200, 240)));
+    // nothing left, or nothing removed
+    assert_eq!(crop.bounds(100, 80, 1), None);
+    assert_eq!(FixedCrop::default().bounds(100, 200, 1), None);
+
+    for invalid in ["", "bottom", "bottom=-5", "middle=10"] {
+        assert!(invalid.parse::<FixedCrop>().is_err(), "{invalid}");
+    }
+
+    // every preset once, then off, and regions of the config file turn off too
+    let mut presets = Vec::new();
+    let mut preset = FixedCrop::next_preset(None);
+    while let Some(crop) = preset {
+        presets.push(crop);
+        preset = FixedCrop::next_preset(preset);
+    }
+    assert_eq!(presets, FixedCrop::PRESETS);
+    assert_eq!(FixedCrop::next_preset(Some(crop)), None);
+}
+
+#[test]
+fn gamma_curve_config_values() {
+    use crate::image::transform::Image;
+    use imageproc::image::GrayImage;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("inverted.lut");
+    let values: Vec<String> = (0..=255u8).rev().map(|v| v.to_string()).collect();
+    fs::write(&path, format!("# measured\n{}\n", values.join(", "))).unwrap();
+    let curve = GammaCurve::Lut(Lut::load(&path).unwrap());
+
+    let json = serde_json::to_string(&curve).unwrap();
+    assert_eq!(serde_json::from_str::<GammaCurve>(&json).unwrap(), curve);
+    assert!(serde_json::from_str::<GammaCurve>(r#"{"Lut":[1,2,3]}"#).is_err());
+    fs::write(&path, "0 1 2").unwrap();
+    assert!(Lut::load(&path).is_err());
+
+    let ramp = || Image::from(GrayImage::from_fn(256, 1, |x, _| [x as u8].into()));
+    let inverted = ramp().tone_curve(&curve, 1.8);
+    assert_eq!(inverted.as_raw()[0], 255);
+    assert_eq!(inverted.as_raw()[255], 0);
+
+    // on linear light the same gamma lands elsewhere, but keeps black and white
+    let power = ramp().tone_curve(&GammaCurve::Power, 1.8);
+    let srgb = ramp().tone_curve(&GammaCurve::Srgb, 1.8);
+    assert_ne!(srgb.as_raw()[128], power.as_raw()[128]);
+    assert_eq!((srgb.as_raw()[0], srgb.as_raw()[255]), (0, 255));
+    assert_eq!(
+        ramp().tone_curve(&GammaCurve::Srgb, 1.0).as_raw(),
+        ramp().as_raw()
+    );
+}
+
+#[test]
+fn fingerprints_tell_settings_apart() {
+    let config = ComicConfig::default();
+    let fingerprint = config.fingerprint();
+    assert_eq!(fingerprint.len(), FINGERPRINT_LEN);
+    assert_eq!(ComicConfig::default().fingerprint(), fingerprint);
+
+    let brighter = ComicConfig {
+        brightness: 10,
+        ..config.clone()
+    };
+    assert_ne!(brighter.fingerprint(), fingerprint);
+    // where the conversion runs doesn't change what it makes
+    let elsewhere = ComicConfig {
+        temp_dir: Some("/mnt/fast".into()),
+        concurrency: Concurrency::Fixed(2),
+        ..config
+    };
+    assert_eq!(elsewhere.fingerprint(), fingerprint);
+}
@@ -0,0 +1,108 @@
+//! Contact sheets: a grid of page thumbnails in a single image, for checking a
+//! conversion at a glance
+
+use anyhow::{Context, Result};
+use imageproc::image::{imageops, imageops::FilterType, DynamicImage, Rgb, RgbImage};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::ComicFile;
+
+// space between and around cells
+const GAP: u32 = 8;
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContactSheet {
+    pub columns: u32,
+    pub rows: u32,
+    /// Width of a cell, pages are fitted in `cell_width` x `cell_width * 4/3`
+    pub cell_width: u32,
+}
+
+impl Default for ContactSheet {
+    fn default() -> Self {
+        Self {
+            columns: 4,
+            rows: 4,
+            cell_width: 300,
+        }
+    }
+}
+
+impl ContactSheet {
+    fn cell_height(&self) -> u32 {
+        self.cell_width * 4 / 3
+    }
+
+    /// Renders the pages of `comic` in a grid.
+    ///
+    /// Comics with more pages than cells are sampled evenly, always keeping the
+    /// first and last page.
+    pub fn build(&self, comic: &ComicFile) -> Result<DynamicImage> {
+        let cells = (self.columns * self.rows) as usize;
+        if cells == 0 || self.cell_width == 0 {
+            anyhow::bail!("Contact sheet needs at least one cell");
+        }
+
+        let mut files: Vec<_> = crate::archive::unarchive_comic_iter(comic)?
+            .filter_map(|result| {
+                result
+                    .inspect_err(|e| log::warn!("Failed to load archive file: {e}"))
+                    .ok()
+            })
+            .collect();
+        files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        if files.is_empty() {
+            anyhow::bail!("No images in archive");
+        }
+
+        let pages = sample(files.len(), cells);
+        let thumbnails: Vec<RgbImage> = pages
+            .into_par_iter()
+            .map(|i| {
+                let file = &files[i];
+                let img = crate::image::decode::decode(&file.data)
+                    .with_context(|| format!("Failed to decode {}", file.file_name.display()))?;
+                Ok(img
+                    .resize(self.cell_width, self.cell_height(), FilterType::Triangle)
+                    .into_rgb8())
+            })
+            .collect::<Result<_>>()?;
+
+        let rows = (thumbnails.len() as u32).div_ceil(self.columns);
+        let width = self.columns * (self.cell_width + GAP) + GAP;
+        let height = rows * (self.cell_height() + GAP) + GAP;
+        let mut sheet = RgbImage::from_pixel(width, height, BACKGROUND);
+
+        for (i, thumbnail) in thumbnails.iter().enumerate() {
+            let (column, row) = (i as u32 % self.columns, i as u32 / self.columns);
+            // centered in its cell
+            let x =
+                GAP + column * (self.cell_width + GAP) + (self.cell_width - thumbnail.width()) / 2;
+            let y = GAP
+                + row * (self.cell_height() + GAP)
+                + (self.cell_height() - thumbnail.height()) / 2;
+            imageops::overlay(&mut sheet, thumbnail, x.into(), y.into());
+        }
+
+        Ok(DynamicImage::ImageRgb8(sheet))
+    }
+}
+
+// indices of `cells` pages spread evenly over `pages`
+pub(crate) fn sample(pages: usize, cells: usize) -> Vec<usize> {
+    if pages <= cells {
+        return (0..pages).collect();
+    }
+    if cells == 1 {
+        return vec![0];
+    }
+    (0..cells).map(|i| i * (pages - 1) / (cells - 1)).collect()
+}
+
+#[test]
+fn sample_spreads_pages() {
+    assert_eq!(sample(3, 16), vec![0, 1, 2]);
+    assert_eq!(sample(10, 4), vec![0, 3, 6, 9]);
+    assert_eq!(sample(100, 1), vec![0]);
+}
@@ -0,0 +1,136 @@
+//! Cover extraction without running the whole pipeline, for catalogs and thumbnails
+
+use anyhow::{Context, Result};
+use imageproc::image::{imageops::FilterType, DynamicImage};
+
+use crate::comic::{ComicConfig, Rotation};
+use crate::device::Device;
+use crate::image::transform::Image;
+use crate::ComicFile;
+
+// width over height of a portrait cover turned on its side, covers run from about 1:1.3
+// to 1:1.6. Wider pages are wraparound covers or spreads
+const SIDEWAYS_RATIO: std::ops::RangeInclusive<f32> = 1.25..=1.65;
+// how much more the columns have to vary than the rows: an upright cover is laid out in
+// horizontal bands (title, art, credits), which turn into columns when it's sideways
+const BANDING_FACTOR: f32 = 2.0;
+
+/// The first page of the comic, decoded as-is
+pub fn extract(comic: &ComicFile) -> Result<DynamicImage> {
+    let first = crate::archive::read_first_image(comic)?;
+    crate::image::decode::decode(&first.data)
+        .with_context(|| format!("Failed to decode {}", first.file_name.display()))
+}
+
+/// The cover scaled down to fit within `width` x `height`, keeping its aspect ratio
+pub fn thumbnail(comic: &ComicFile, width: u32, height: u32) -> Result<DynamicImage> {
+    let cover = extract(comic)?;
+    if cover.width() <= width && cover.height() <= height {
+        return Ok(cover);
+    }
+    Ok(cover.resize(width, height, FilterType::Lanczos3))
+}
+
+/// The cover as it would come out of a conversion with `config`, optionally at a
+/// different size than the configured device
+pub fn processed(
+    comic: &ComicFile,
+    config: &ComicConfig,
+    size: Option<(u32, u32)>,
+) -> Result<DynamicImage> {
+    let mut config = config.clone();
+    if let Some((width, height)) = size {
+        config.device = Device::Custom { width, height };
+    }
+
+    let mut cover = extract(comic)?;
+    if config.auto_rotate_cover {
+        let gray = Image::from(cover.to_luma8());
+        if let Some(rotation) = sideways(&gray) {
+            cover = DynamicImage::ImageLuma8(
+                crate::image::transform::rotate_output(gray, rotation).into(),
+            );
+        }
+    }
+
+    crate::image::process(cover, &config)
+        .into_iter()
+        .next()
+        .context("Cover produced no pages")
+}
+
+/// The rotation that turns a cover delivered sideways (a landscape file with portrait
+/// art) upright, `None` when it looks upright already.
+///
+/// The cover has to be about as wide as a portrait page turned on its side is, and laid
+/// out in vertical bands rather than horizontal ones. The side whose edge varies the
+/// most is taken for the top, where titles usually are.
+pub fn sideways(img: &Image) -> Option<Rotation> {
+    let (width, height) = img.dimensions();
+    if height == 0 || !SIDEWAYS_RATIO.contains(&(width as f32 / height as f32)) {
+        return None;
+    }
+
+    // mean of every row and column
+    let mut rows = vec![0u64; height as usize];
+    let mut columns = vec![0u64; width as usize];
+    for (row, line) in img.as_raw().chunks_exact(width as usize).enumerate() {
+        for (column, &pixel) in line.iter().enumerate() {
+            rows[row] += u64::from(pixel);
+            columns[column] += u64::from(pixel);
+        }
+    }
+    let rows: Vec<f32> = rows.iter().map(|sum| *sum as f32 / width as f32).collect();
+    let columns: Vec<f32> = columns
+        .iter()
+        .map(|sum| *sum as f32 / height as f32)
+        .collect();
+
+    if variance(&columns) <= BANDING_FACTOR * variance(&rows) {
+        return None;
+    }
+    let edge = columns.len() / 4;
+    let left = variance(&columns[..edge]);
+    let right = variance(&columns[columns.len() - edge..]);
+    // turning clockwise brings the left edge to the top
+    Some(if left >= right {
+        Rotation::Clockwise
+    } else {
+        Rotation::CounterClockwise
+    })
+}
+
+fn variance(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32
+}
+
+#[test]
+fn detects_sideways_covers() {
+    use imageproc::image::{GrayImage, Luma};
+
+    // a dark title band across the top and a gradient below, like most covers
+    let upright = GrayImage::from_fn(140, 200, |_, y| match y {
+        10..40 => Luma([20]),
+        40.. => Luma([(y - 40) as u8]),
+        _ => Luma([255]),
+    });
+    let upright = Image::from(upright);
+    assert_eq!(sideways(&upright), None);
+
+    let turned_left = crate::image::transform::rotate_output(upright, Rotation::CounterClockwise);
+    assert_eq!(sideways(&turned_left), Some(Rotation::Clockwise));
+    let upright = crate::image::transform::rotate_output(turned_left, Rotation::Clockwise);
+    let turned_right = crate::image::transform::rotate_output(upright, Rotation::Clockwise);
+    assert_eq!(sideways(&turned_right), Some(Rotation::CounterClockwise));
+
+    // two upright pages side by side are a spread, not a sideways cover
+    let spread = GrayImage::from_fn(280, 200, |_, y| match y {
+        10..40 => Luma([20]),
+        _ => Luma([255]),
+    });
+    assert_eq!(sideways(&Image::from(spread)), None);
+}
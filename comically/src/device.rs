@@ -4,6 +4,8 @@ use strum::{EnumCount, EnumIter, EnumTryAs};
 
 use std::str::FromStr;
 
+use crate::comic::OutputFormat;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, EnumTryAs)]
 pub enum Device {
     Preset(Preset),
@@ -24,6 +26,87 @@ impl Device {
             Device::Custom { width, height } => (*width, *height),
         }
     }
+
+    /// Identifier for command lines and directory names, the preset's id or `WIDTHxHEIGHT`,
+    /// which [`Device::from_str`] reads back
+    pub fn id(&self) -> String {
+        match self {
+            Device::Preset(preset) => preset.id().to_string(),
+            Device::Custom { width, height } => format!("{width}x{height}"),
+        }
+    }
+
+    /// Pixel density of the screen, unknown for custom devices
+    pub fn dpi(&self) -> Option<u32> {
+        match self {
+            Device::Preset(preset) => Some(preset.dpi()),
+            Device::Custom { .. } => None,
+        }
+    }
+
+    /// Defaults suited to the screen, custom devices are assumed to be e-ink
+    pub fn tuning(&self) -> Tuning {
+        match self {
+            Device::Preset(preset) => preset.tuning(),
+            Device::Custom { .. } => Tuning::E_INK,
+        }
+    }
+}
+
+/// Settings that depend on the kind of screen, applied when a device is picked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    pub gamma: f32,
+    pub brightness: i32,
+    /// Whether pages are dithered to the gray levels of e-ink screens
+    pub dither: bool,
+    /// Whether color pages stay in color, see [`crate::image::color`]
+    pub keep_color: bool,
+    /// Format the device reads natively, `None` keeps the current one
+    pub output_format: Option<OutputFormat>,
+}
+
+impl Tuning {
+    /// Darker midtones and slightly lower brightness, e-ink renders art washed out, and
+    /// dithered gradients, which band on its few gray levels
+    pub const E_INK: Tuning = Tuning {
+        gamma: 1.8,
+        brightness: -10,
+        dither: true,
+        keep_color: false,
+        output_format: None,
+    };
+
+    /// LCD/OLED screens show the art as drawn, color pages included
+    pub const LCD: Tuning = Tuning {
+        gamma: 1.0,
+        brightness: 0,
+        dither: false,
+        keep_color: true,
+        output_format: None,
+    };
+}
+
+impl std::fmt::Display for Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.id())
+    }
+}
+
+impl FromStr for Device {
+    type Err = ParseError;
+
+    /// A preset id, a KCC profile name such as `KPW5`, or `WIDTHxHEIGHT`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((Ok(width), Ok(height))) =
+            s.split_once('x').map(|(w, h)| (w.parse(), h.parse()))
+        {
+            return Ok(Device::Custom { width, height });
+        }
+        Preset::try_from(s)
+            .map(Into::into)
+            .or_else(|e| crate::kcc::device(s).ok_or(e))
+    }
 }
 
 impl From<Preset> for Device {
@@ -89,6 +172,30 @@ impl Preset {
         }
     }
 
+    /// Identifier accepted by [`Preset::try_from`], e.g. `kindle-pw-12`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Preset::KindlePw11 => "kindle-pw-11",
+            Preset::KindlePw12 => "kindle-pw-12",
+            Preset::KindleOasis => "kindle-oasis",
+            Preset::KindleScribe => "kindle-scribe",
+            Preset::KindleBasic => "kindle-basic",
+            Preset::Kindle11 => "kindle-11",
+            Preset::KoboClaraHd => "kobo-clara-hd",
+            Preset::KoboClara2e => "kobo-clara-2e",
+            Preset::KoboLibra2 => "kobo-libra-2",
+            Preset::KoboSage => "kobo-sage",
+            Preset::KoboElipsa => "kobo-elipsa",
+            Preset::Remarkable2 => "remarkable-2",
+            Preset::IpadMini => "ipad-mini",
+            Preset::Ipad109 => "ipad-109",
+            Preset::IpadPro11 => "ipad-pro-11",
+            Preset::OnyxBooxNova => "onyx-boox-nova",
+            Preset::OnyxBooxNote => "onyx-boox-note",
+            Preset::PocketbookEra => "pocketbook-era",
+        }
+    }
+
     pub fn dimensions(&self) -> (u32, u32) {
         match self {
             Preset::KindlePw11 => (1236, 1648),
@@ -111,6 +218,68 @@ impl Preset {
             Preset::PocketbookEra => (1200, 1600),
         }
     }
+
+    /// Pixel density of the screen
+    pub fn dpi(&self) -> u32 {
+        match self {
+            Preset::KindleBasic => 167,
+            Preset::KoboElipsa => 227,
+            Preset::Remarkable2 => 226,
+            Preset::IpadMini => 326,
+            Preset::Ipad109 => 264,
+            Preset::IpadPro11 => 264,
+            Preset::OnyxBooxNote => 227,
+            Preset::KindlePw11
+            | Preset::KindlePw12
+            | Preset::KindleOasis
+            | Preset::KindleScribe
+            | Preset::Kindle11
+            | Preset::KoboClaraHd
+            | Preset::KoboClara2e
+            | Preset::KoboLibra2
+            | Preset::KoboSage
+            | Preset::OnyxBooxNova
+            | Preset::PocketbookEra => 300,
+        }
+    }
+
+    /// Whether the screen displays color, e-ink presets are grayscale
+    pub fn color(&self) -> bool {
+        matches!(self, Preset::IpadMini | Preset::Ipad109 | Preset::IpadPro11)
+    }
+
+    pub fn tuning(&self) -> Tuning {
+        let tuning = if self.color() {
+            Tuning::LCD
+        } else {
+            Tuning::E_INK
+        };
+        Tuning {
+            output_format: Some(self.native_format()),
+            ..tuning
+        }
+    }
+
+    fn native_format(&self) -> OutputFormat {
+        match self {
+            Preset::KindlePw11
+            | Preset::KindlePw12
+            | Preset::KindleOasis
+            | Preset::KindleScribe
+            | Preset::KindleBasic
+            | Preset::Kindle11 => OutputFormat::Mobi,
+            Preset::KoboClaraHd
+            | Preset::KoboClara2e
+            | Preset::KoboLibra2
+            | Preset::KoboSage
+            | Preset::KoboElipsa
+            | Preset::Remarkable2
+            | Preset::OnyxBooxNova
+            | Preset::OnyxBooxNote
+            | Preset::PocketbookEra => OutputFormat::Epub,
+            Preset::IpadMini | Preset::Ipad109 | Preset::IpadPro11 => OutputFormat::Cbz,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -118,7 +287,7 @@ pub struct ParseError(String);
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        f.write_str("Invalid device preset: ")?;
+        f.write_str("Invalid device: ")?;
         f.write_str(&self.0)
     }
 }
@@ -130,27 +299,9 @@ impl TryFrom<&str> for Preset {
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
         let normalized = s.to_lowercase().replace([' ', '_'], "-");
-        match normalized.as_str() {
-            "kindle-pw-11" => Ok(Preset::KindlePw11),
-            "kindle-pw-12" => Ok(Preset::KindlePw12),
-            "kindle-oasis" => Ok(Preset::KindleOasis),
-            "kindle-scribe" => Ok(Preset::KindleScribe),
-            "kindle-basic" => Ok(Preset::KindleBasic),
-            "kindle-11" => Ok(Preset::Kindle11),
-            "kobo-clara-hd" => Ok(Preset::KoboClaraHd),
-            "kobo-clara-2e" => Ok(Preset::KoboClara2e),
-            "kobo-libra-2" => Ok(Preset::KoboLibra2),
-            "kobo-sage" => Ok(Preset::KoboSage),
-            "kobo-elipsa" => Ok(Preset::KoboElipsa),
-            "remarkable-2" => Ok(Preset::Remarkable2),
-            "ipad-mini" => Ok(Preset::IpadMini),
-            "ipad-109" => Ok(Preset::Ipad109),
-            "ipad-pro-11" => Ok(Preset::IpadPro11),
-            "onyx-boox-nova" => Ok(Preset::OnyxBooxNova),
-            "onyx-boox-note" => Ok(Preset::OnyxBooxNote),
-            "pocketbook-era" => Ok(Preset::PocketbookEra),
-            _ => Err(ParseError(s.to_string())),
-        }
+        Preset::iter()
+            .find(|preset| preset.id() == normalized)
+            .ok_or_else(|| ParseError(s.to_string()))
     }
 }
 
@@ -161,3 +312,57 @@ impl FromStr for Preset {
         Self::try_from(s)
     }
 }
+
+#[test]
+fn device_tuning_applies_on_change() {
+    let mut config = crate::ComicConfig {
+        image_format: crate::ImageFormat::Png {
+            compression: crate::PngCompression::Default,
+        },
+        ..Default::default()
+    };
+
+    config.set_device(Preset::IpadPro11.into());
+    assert_eq!((config.gamma, config.brightness), (1.0, 0));
+    assert!(!config.dither && config.keep_color);
+    assert_eq!(config.output_format, OutputFormat::Cbz);
+
+    // tweaks survive picking the same device again
+    config.gamma = 1.2;
+    config.set_device(Preset::IpadPro11.into());
+    assert_eq!(config.gamma, 1.2);
+
+    // and picking another one, which only changes the settings still at the defaults
+    config.set_device(Preset::KindleScribe.into());
+    assert_eq!((config.gamma, config.brightness), (1.2, -10));
+    assert!(config.dither && !config.keep_color);
+    assert_eq!(config.output_format, OutputFormat::Mobi);
+    assert_eq!(
+        config.image_format,
+        crate::ImageFormat::Jpeg { quality: 85 }
+    );
+
+    // a format picked by hand stays too
+    config.output_format = OutputFormat::Epub;
+    config.set_device(Preset::IpadMini.into());
+    assert_eq!(config.output_format, OutputFormat::Epub);
+}
+
+#[test]
+fn devices_parse_from_their_id() {
+    for device in [
+        Device::Preset(Preset::KoboLibra2),
+        Device::Custom {
+            width: 1072,
+            height: 1448,
+        },
+    ] {
+        assert_eq!(device.to_string().parse::<Device>().unwrap(), device);
+    }
+    assert_eq!(
+        "Kindle Scribe".parse::<Device>().unwrap(),
+        Preset::KindleScribe.into()
+    );
+    assert_eq!("KO".parse::<Device>().unwrap(), Preset::KindleOasis.into());
+    assert!("1072x".parse::<Device>().is_err());
+}
@@ -0,0 +1,85 @@
+//! Settings kept with the comics they're for, in a `.comically.json` in their directory,
+//! for a series that needs them whatever batch it's converted in, like the crop of the
+//! footer banner its publisher prints on every page.
+//!
+//! They apply on top of the settings of the batch, and under the overrides of a single
+//! comic, see [`crate::batch::Input::with_overrides`].
+
+use anyhow::{Context, Result};
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::comic::{ComicConfig, FixedCrop};
+
+pub const FILE_NAME: &str = ".comically.json";
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DirConfig {
+    /// Cut from every page of the directory's comics in place of the batch's
+    /// [`ComicConfig::fixed_crop`], `{}` to cut nothing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_crop: Option<FixedCrop>,
+}
+
+impl DirConfig {
+    /// The config of `dir`, `None` when it has none
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(FILE_NAME);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// The config of the directory `comic` is in, left out with a warning when it can't
+    /// be read
+    pub fn for_comic(comic: &Path) -> Option<Self> {
+        Self::load(comic.parent()?)
+            .inspect_err(|e| log::warn!("{e:#}, the settings of the batch are used"))
+            .ok()
+            .flatten()
+    }
+
+    pub fn apply(&self, config: &mut ComicConfig) {
+        if let Some(crop) = self.fixed_crop {
+            config.fixed_crop = (!crop.is_empty()).then_some(crop);
+        }
+    }
+}
+
+#[test]
+fn directories_crop_their_comics() {
+    let dir = tempfile::tempdir().unwrap();
+    let comic = dir.path().join("Vol. 1.cbz");
+    assert_eq!(DirConfig::load(dir.path()).unwrap(), None);
+    assert_eq!(DirConfig::for_comic(&comic), None);
+
+    fs::write(
+        dir.path().join(FILE_NAME),
+        r#"{"fixed_crop": {"bottom": 60}}"#,
+    )
+    .unwrap();
+    let local = DirConfig::for_comic(&comic).unwrap();
+    let mut config = ComicConfig::default();
+    local.apply(&mut config);
+    assert_eq!(config.fixed_crop, Some(FixedCrop::bottom(60)));
+
+    // an empty crop turns off the batch's
+    fs::write(dir.path().join(FILE_NAME), r#"{"fixed_crop": {}}"#).unwrap();
+    DirConfig::for_comic(&comic).unwrap().apply(&mut config);
+    assert_eq!(config.fixed_crop, None);
+    fs::write(dir.path().join(FILE_NAME), r#"{}"#).unwrap();
+    config.fixed_crop = Some(FixedCrop::top(40));
+    DirConfig::for_comic(&comic).unwrap().apply(&mut config);
+    assert_eq!(config.fixed_crop, Some(FixedCrop::top(40)));
+
+    fs::write(dir.path().join(FILE_NAME), "not json").unwrap();
+    assert!(DirConfig::load(dir.path()).is_err());
+    assert_eq!(DirConfig::for_comic(&comic), None);
+}
@@ -0,0 +1,84 @@
+//! Reading direction detection, so mixed libraries of manga and western comics can
+//! be converted in one batch
+
+use crate::archive::{self, ArchiveFile};
+use crate::ComicFile;
+
+// pages sampled for color, taken from the middle to skip color covers and inserts
+const SAMPLED_PAGES: usize = 5;
+
+/// Guesses whether a comic reads right to left, `None` when there's nothing to go on.
+///
+/// In order of confidence:
+/// - the `Manga` and `LanguageISO` fields of `ComicInfo.xml`
+/// - Japanese script in the file name or the names of the pages
+/// - western comics being printed in color, while manga interiors are grayscale
+pub fn detect(comic: &ComicFile, files: &[ArchiveFile]) -> Option<bool> {
+    if let Some(rtl) = archive::read_comic_info(comic).and_then(|info| from_comic_info(&info)) {
+        log::debug!("{}: reading direction from ComicInfo.xml", comic.title());
+        return Some(rtl);
+    }
+
+    let page_names = files
+        .iter()
+        .any(|file| has_japanese_script(&file.file_name.to_string_lossy()));
+    if has_japanese_script(comic.title()) || page_names {
+        log::debug!(
+            "{}: Japanese file names, reading right to left",
+            comic.title()
+        );
+        return Some(true);
+    }
+
+    if is_color(files) {
+        log::debug!("{}: color pages, reading left to right", comic.title());
+        return Some(false);
+    }
+
+    None
+}
+
+fn from_comic_info(info: &str) -> Option<bool> {
+    match archive::comic_info_field(info, "Manga") {
+        Some("YesAndRightToLeft") => return Some(true),
+        Some("No") => return Some(false),
+        _ => {}
+    }
+
+    archive::comic_info_field(info, "LanguageISO").map(|lang| lang.eq_ignore_ascii_case("ja"))
+}
+
+fn has_japanese_script(s: &str) -> bool {
+    s.chars().any(|c| {
+        matches!(c,
+            '\u{3040}'..='\u{309F}' // hiragana
+            | '\u{30A0}'..='\u{30FF}' // katakana
+        )
+    })
+}
+
+fn is_color(files: &[ArchiveFile]) -> bool {
+    let start = files.len().saturating_sub(SAMPLED_PAGES) / 2;
+    let pages: Vec<bool> = files
+        .iter()
+        .skip(start)
+        .take(SAMPLED_PAGES)
+        .filter_map(|file| crate::image::decode::decode(&file.data).ok())
+        .map(|img| crate::image::stats::is_color(&img))
+        .collect();
+
+    let color_pages = pages.iter().filter(|color| **color).count();
+    !pages.is_empty() && color_pages * 2 > pages.len()
+}
+
+#[test]
+fn comic_info_direction() {
+    let manga = "<ComicInfo><Manga>YesAndRightToLeft</Manga></ComicInfo>";
+    let western = "<ComicInfo><Manga>No</Manga><LanguageISO>ja</LanguageISO></ComicInfo>";
+    let japanese = "<ComicInfo>\n  <LanguageISO>ja</LanguageISO>\n</ComicInfo>";
+
+    assert_eq!(from_comic_info(manga), Some(true));
+    assert_eq!(from_comic_info(western), Some(false));
+    assert_eq!(from_comic_info(japanese), Some(true));
+    assert_eq!(from_comic_info("<ComicInfo/>"), None);
+}
@@ -1,12 +1,11 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
-use zip::{
-    write::{SimpleFileOptions, ZipWriter},
-    CompressionMethod,
-};
+use zip::{write::ZipWriter, CompressionMethod};
 
-use std::io::{Cursor, Write};
+use std::io::{Cursor, Seek, Write};
 
-use crate::comic::{ComicConfig, ProcessedImage};
+use crate::comic::{ComicConfig, PageBackground, PagePart, PageSide, ProcessedImage};
 use crate::image::ImageFormat;
 
 /// Build EPUB and return the bytes
@@ -21,24 +20,33 @@ fn image_path(img_num: usize, format: ImageFormat) -> String {
     format!("Images/image{:03}.{}", img_num, format.extension())
 }
 
+fn small_image_path(img_num: usize, format: ImageFormat) -> String {
+    format!("Images/image{:03}-small.{}", img_num, format.extension())
+}
+
 fn html_page_path(page_num: usize) -> String {
     format!("OEBPS/page{:03}.html", page_num)
 }
 
-/// Build EPUB into the provided buffer, reusing existing allocation
+/// Build EPUB into the provided buffer, reusing existing allocation, and return the
+/// book's unique identifier
 pub fn build_into(
     title: &str,
     config: &ComicConfig,
     images: &[ProcessedImage],
     buffer: &mut Vec<u8>,
-) {
+) -> Uuid {
+    let _span = crate::trace::span!("package", format = "epub", pages = images.len()).entered();
     buffer.clear();
     let cursor = Cursor::new(buffer);
     let mut zip = ZipWriter::new(cursor);
 
-    let options_stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
-    let options_deflated =
-        SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let options = crate::output::zip_options(config.deterministic);
+    let options_stored = options.compression_method(CompressionMethod::Stored);
+    let options_deflated = options.compression_method(CompressionMethod::Deflated);
+    let book_id = book_id(title, config, images);
+    let title = crate::opds::escape(crate::metadata::title(title, config));
+    let title = title.as_str();
 
     // 1. Add mimetype (must be first and uncompressed)
     zip.start_file("mimetype", options_stored).unwrap();
@@ -49,40 +57,167 @@ pub fn build_into(
         .unwrap();
     zip.write_all(container_xml().as_bytes()).unwrap();
 
-    // 3. Add cover.html
-    zip.start_file("OEBPS/cover.html", options_deflated)
+    // 3. Add the stylesheet shared by every page
+    zip.start_file("OEBPS/style.css", options_deflated).unwrap();
+    zip.write_all(style_css(config.page_background).as_bytes())
         .unwrap();
-    zip.write_all(cover_html(config.image_format).as_bytes())
+
+    // 4. Add cover.html
+    zip.start_file("OEBPS/cover.html", options_deflated)
         .unwrap();
+    let cover_format = images.first().map_or(config.image_format, |img| img.format);
+    zip.write_all(cover_html(cover_format).as_bytes()).unwrap();
 
-    // 4. Add HTML pages for each image
+    // 5. Add HTML pages for each image
     for (i, img) in images.iter().enumerate() {
         zip.start_file(html_page_path(i + 1), options_deflated)
             .unwrap();
-        zip.write_all(page_html(i + 1, config.image_format, img.dimensions).as_bytes())
-            .unwrap();
+        zip.write_all(html_of(i, img).as_bytes()).unwrap();
     }
 
-    // 5. Add toc.ncx
+    // 6. Add toc.ncx
     zip.start_file("OEBPS/toc.ncx", options_deflated).unwrap();
-    zip.write_all(toc_ncx(title, images.len()).as_bytes())
+    zip.write_all(toc_ncx(title, &book_id, images).as_bytes())
         .unwrap();
 
-    // 6. Add content.opf
+    // 7. Add content.opf
     zip.start_file("OEBPS/content.opf", options_deflated)
         .unwrap();
-    zip.write_all(content_opf(title, config, images).as_bytes())
+    zip.write_all(content_opf(title, &book_id, config, images).as_bytes())
         .unwrap();
 
-    // 7. Add all images
+    // 8. Add all images, straight from the encoded buffers
     for (i, image) in images.iter().enumerate() {
-        let path = format!("OEBPS/{}", image_path(i + 1, config.image_format));
-        zip.start_file(&path, options_stored).unwrap();
-        zip.write_all(&image.data).unwrap();
+        write_image(&mut zip, i, image, options_stored).unwrap();
     }
 
     // Finish zip and get bytes
     zip.finish().unwrap();
+    book_id
+}
+
+/// Build EPUB into `writer` from pages as they're processed, e.g. by
+/// [`ComicFile::process_iter`](crate::ComicFile::process_iter), holding the image of one
+/// page at a time, and return it with the book's unique identifier. The first page that
+/// failed fails the build.
+pub fn build_iter<W, I>(
+    title: &str,
+    config: &ComicConfig,
+    images: I,
+    writer: W,
+) -> Result<(W, Uuid)>
+where
+    W: Write + Seek,
+    I: IntoIterator<Item = Result<ProcessedImage>>,
+{
+    let _span = crate::trace::span!("package", format = "epub").entered();
+    let mut zip = ZipWriter::new(writer);
+
+    let options = crate::output::zip_options(config.deterministic);
+    let options_stored = options.compression_method(CompressionMethod::Stored);
+    let options_deflated = options.compression_method(CompressionMethod::Deflated);
+    let mut book_id = BookId::new(title, config);
+    let title = crate::opds::escape(crate::metadata::title(title, config));
+
+    zip.start_file("mimetype", options_stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", options_deflated)?;
+    zip.write_all(container_xml().as_bytes())?;
+    zip.start_file("OEBPS/style.css", options_deflated)?;
+    zip.write_all(style_css(config.page_background).as_bytes())?;
+
+    // each page is written as it comes, what's left of it describes it in the OPF
+    let mut pages = Vec::new();
+    for (i, image) in images.into_iter().enumerate() {
+        let mut image = image?;
+        book_id.add(&image);
+        zip.start_file(html_page_path(i + 1), options_deflated)?;
+        zip.write_all(html_of(i, &image).as_bytes())?;
+        write_image(&mut zip, i, &image, options_stored)?;
+        image.data = Vec::new();
+        if let Some(small) = &mut image.small {
+            small.data = Vec::new();
+        }
+        pages.push(image);
+    }
+    let book_id = book_id.finish();
+
+    zip.start_file("OEBPS/cover.html", options_deflated)?;
+    let cover_format = pages.first().map_or(config.image_format, |img| img.format);
+    zip.write_all(cover_html(cover_format).as_bytes())?;
+    zip.start_file("OEBPS/toc.ncx", options_deflated)?;
+    zip.write_all(toc_ncx(&title, &book_id, &pages).as_bytes())?;
+    zip.start_file("OEBPS/content.opf", options_deflated)?;
+    zip.write_all(content_opf(&title, &book_id, config, &pages).as_bytes())?;
+
+    Ok((zip.finish()?, book_id))
+}
+
+// random, unless deterministic where it's derived from the title and pages
+fn book_id(title: &str, config: &ComicConfig, images: &[ProcessedImage]) -> Uuid {
+    let mut id = BookId::new(title, config);
+    for img in images {
+        id.add(img);
+    }
+    id.finish()
+}
+
+// the pages of a deterministic book hashed as they're added
+struct BookId(Option<Sha256>);
+
+impl BookId {
+    fn new(title: &str, config: &ComicConfig) -> Self {
+        Self(config.deterministic.then(|| {
+            let mut hasher = Sha256::new();
+            hasher.update(title.as_bytes());
+            hasher
+        }))
+    }
+
+    fn add(&mut self, img: &ProcessedImage) {
+        if let Some(hasher) = &mut self.0 {
+            hasher.update(img.file_name.as_bytes());
+            hasher.update(&img.data);
+        }
+    }
+
+    fn finish(self) -> Uuid {
+        let Some(hasher) = self.0 else {
+            return Uuid::new_v4();
+        };
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&hasher.finalize()[..16]);
+        uuid::Builder::from_custom_bytes(bytes).into_uuid()
+    }
+}
+
+// the XHTML page of the image at `index`
+fn html_of(index: usize, img: &ProcessedImage) -> String {
+    match &img.text {
+        Some(text) if reflows(index, img) => text_html(img, text),
+        _ => page_html(index + 1, img),
+    }
+}
+
+// the image at `index` and its small copy, straight from the encoded buffers
+fn write_image<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    index: usize,
+    image: &ProcessedImage,
+    options: zip::write::SimpleFileOptions,
+) -> zip::result::ZipResult<()> {
+    if reflows(index, image) {
+        return Ok(());
+    }
+    let path = format!("OEBPS/{}", image_path(index + 1, image.format));
+    zip.start_file(&path, options)?;
+    zip.write_all(&image.data)?;
+    if let Some(small) = &image.small {
+        let path = format!("OEBPS/{}", small_image_path(index + 1, image.format));
+        zip.start_file(&path, options)?;
+        zip.write_all(&small.data)?;
+    }
+    Ok(())
 }
 
 fn container_xml() -> &'static str {
@@ -94,6 +229,36 @@ fn container_xml() -> &'static str {
 </container>"#
 }
 
+// pages fill the viewport, anything left around them shows the background
+fn style_css(background: PageBackground) -> String {
+    format!(
+        r#"html, body {{
+  margin: 0;
+  padding: 0;
+  width: 100%;
+  height: 100%;
+  background-color: {};
+}}
+
+.cover, .image {{
+  width: 100%;
+  height: 100%;
+  display: flex;
+  align-items: center;
+  justify-content: center;
+}}
+
+img {{
+  display: block;
+  max-width: 100%;
+  max-height: 100%;
+  object-fit: contain;
+}}
+"#,
+        background.css_color()
+    )
+}
+
 fn cover_html(format: ImageFormat) -> String {
     format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -102,8 +267,9 @@ fn cover_html(format: ImageFormat) -> String {
 <head>
   <title>Cover</title>
   <meta name="viewport" content="width=device-width, height=device-height, initial-scale=1.0, maximum-scale=1.0, user-scalable=no"/>
+  <link rel="stylesheet" type="text/css" href="style.css"/>
 </head>
-<body style="background-color:#000000;">
+<body>
   <div class="cover">
     <img src="{}" alt="Cover"/>
   </div>
@@ -113,29 +279,70 @@ fn cover_html(format: ImageFormat) -> String {
     )
 }
 
-fn page_html(page_num: usize, format: ImageFormat, dimensions: (u32, u32)) -> String {
+// with a small copy, readers that understand `srcset` pick the copy that suits the screen
+// and the others show the full page
+fn page_html(page_num: usize, img: &ProcessedImage) -> String {
+    let path = image_path(page_num, img.format);
+    let srcset = match &img.small {
+        Some(small) => format!(
+            r#" srcset="{} {}w, {path} {}w" sizes="100vw""#,
+            small_image_path(page_num, img.format),
+            small.dimensions.0,
+            img.dimensions.0,
+        ),
+        None => String::new(),
+    };
     format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
 <!DOCTYPE html>
 <html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
 <head>
-  <title>Page {page_num}</title>
+  <title>{}</title>
   <meta name="viewport" content="width={}, height={}, initial-scale=1.0, maximum-scale=1.0, user-scalable=no"/>
+  <link rel="stylesheet" type="text/css" href="style.css"/>
 </head>
 <body>
   <div class="image">
-    <img src="{}"/>
+    <img src="{path}"{srcset}/>
   </div>
 </body>
 </html>"#,
-        dimensions.0,
-        dimensions.1,
-        image_path(page_num, format)
+        img.label(),
+        img.dimensions.0,
+        img.dimensions.1,
+    )
+}
+
+// pages of plain text are shown as text in place of their scan, except for the cover
+fn reflows(index: usize, img: &ProcessedImage) -> bool {
+    index > 0 && img.text.is_some()
+}
+
+// a reflowable page in the fixed layout book, in the reader's font and size
+fn text_html(img: &ProcessedImage, text: &str) -> String {
+    let paragraphs: String = text
+        .split("\n\n")
+        .map(|paragraph| format!("  <p>{}</p>\n", crate::opds::escape(paragraph)))
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+  <title>{}</title>
+  <style type="text/css">
+    body {{ margin: 1em; }}
+    p {{ margin: 0 0 0.8em; text-align: justify; }}
+  </style>
+</head>
+<body>
+{paragraphs}</body>
+</html>"#,
+        img.label(),
     )
 }
 
-fn toc_ncx(title: &str, num_pages: usize) -> String {
-    let uuid = Uuid::new_v4().to_string();
+fn toc_ncx(title: &str, uuid: &Uuid, images: &[ProcessedImage]) -> String {
     let mut nav_points = String::new();
 
     // Add cover to nav points
@@ -148,14 +355,15 @@ fn toc_ncx(title: &str, num_pages: usize) -> String {
     );
 
     // Add content pages to nav points
-    for i in 1..=num_pages {
+    for (i, img) in (1..).zip(images) {
         nav_points.push_str(&format!(
             r#"    <navPoint id="navpoint-{i}" playOrder="{}">
-      <navLabel><text>Page {i}</text></navLabel>
+      <navLabel><text>{}</text></navLabel>
       <content src="page{i:03}.html"/>
     </navPoint>
 "#,
             i + 1, // +1 because cover is 1
+            img.label(),
         ));
     }
 
@@ -175,9 +383,12 @@ fn toc_ncx(title: &str, num_pages: usize) -> String {
     )
 }
 
-fn content_opf(title: &str, config: &ComicConfig, images: &[ProcessedImage]) -> String {
-    let uuid = Uuid::new_v4().to_string();
-
+fn content_opf(
+    title: &str,
+    uuid: &Uuid,
+    config: &ComicConfig,
+    images: &[ProcessedImage],
+) -> String {
     // Build manifest items
     let mut manifest = String::new();
 
@@ -186,6 +397,10 @@ fn content_opf(title: &str, config: &ComicConfig, images: &[ProcessedImage]) ->
         .push_str(r#"    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>"#);
     manifest.push('\n');
 
+    // Add the stylesheet
+    manifest.push_str(r#"    <item id="css" href="style.css" media-type="text/css"/>"#);
+    manifest.push('\n');
+
     // Add cover HTML
     manifest.push_str(
         r#"    <item id="cover-html" href="cover.html" media-type="application/xhtml+xml"/>"#,
@@ -204,6 +419,9 @@ fn content_opf(title: &str, config: &ComicConfig, images: &[ProcessedImage]) ->
 
     // Add images
     for (i, image) in images.iter().enumerate() {
+        if reflows(i, image) {
+            continue;
+        }
         let media_type = match image.format {
             ImageFormat::Jpeg { .. } => "image/jpeg",
             ImageFormat::Png { .. } => "image/png",
@@ -222,6 +440,14 @@ fn content_opf(title: &str, config: &ComicConfig, images: &[ProcessedImage]) ->
             ));
         }
         manifest.push('\n');
+
+        if image.small.is_some() {
+            let rel_path = small_image_path(i + 1, image.format);
+            manifest.push_str(&format!(
+                r#"    <item id="image{i}-small" href="{rel_path}" media-type="{media_type}"/>"#,
+            ));
+            manifest.push('\n');
+        }
     }
 
     // Build spine items with page spread properties
@@ -232,26 +458,21 @@ fn content_opf(title: &str, config: &ComicConfig, images: &[ProcessedImage]) ->
     spine.push_str(r#"    <itemref idref="cover-html" properties="page-spread-center"/>"#);
     spine.push('\n');
 
-    let mut right_to_left = config.right_to_left;
-    for i in 1..images.len() {
-        let spread_property = if right_to_left {
-            "page-spread-right"
-        } else {
-            "page-spread-left"
+    for (i, side) in spread_sides(config, images).into_iter().enumerate().skip(1) {
+        let properties = match side {
+            _ if reflows(i, &images[i]) => "rendition:layout-reflowable",
+            Side::Left => "page-spread-left",
+            Side::Right => "page-spread-right",
+            Side::Alone => "page-spread-center rendition:spread-none",
         };
-
         spine.push_str(&format!(
-            r#"    <itemref idref="page{}" properties="{}"/>"#,
-            i + 1,
-            spread_property
+            r#"    <itemref idref="page{}" properties="{properties}"/>"#,
+            i + 1
         ));
         spine.push('\n');
-
-        // Alternate page sides
-        right_to_left = !right_to_left;
     }
 
-    let (width, height) = config.device_dimensions();
+    let (width, height) = config.viewport_dimensions();
 
     // Create the OPF content with page-progression-direction
     format!(
@@ -261,7 +482,7 @@ fn content_opf(title: &str, config: &ComicConfig, images: &[ProcessedImage]) ->
             <dc:title>{title}</dc:title>
             <dc:language>en-US</dc:language>
             <dc:identifier id="BookID">urn:uuid:{uuid}</dc:identifier>
-            <dc:creator>comically</dc:creator>
+            {dublin_core}
             <meta name="cover" content="cover-image"/>
             <meta name="fixed-layout" content="true"/>
             <meta name="original-resolution" content="{width}x{height}"/>
@@ -269,16 +490,19 @@ fn content_opf(title: &str, config: &ComicConfig, images: &[ProcessedImage]) ->
             <meta name="primary-writing-mode" content="{writing_mode}"/>
             <meta name="zero-gutter" content="true"/>
             <meta name="zero-margin" content="true"/>
-            <meta name="ke-border-color" content="#000000"/>
+            <meta name="ke-border-color" content="{border_color}"/>
             <meta name="ke-border-width" content="0"/>
             <meta name="orientation-lock" content="none"/>
             <meta name="region-mag" content="true"/>
             <meta property="rendition:spread">landscape</meta>
-            <meta property="rendition:layout">pre-paginated</meta>
+            <meta property="rendition:layout">pre-paginated</meta>{series}
           </metadata>
           <manifest>{manifest}</manifest>
           <spine toc="ncx" page-progression-direction="{progression_direction}">{spine}</spine>
         </package>"###,
+        border_color = config.page_background.css_color(),
+        dublin_core = dublin_core(config.metadata.as_ref()),
+        series = config.series.as_ref().map(series_meta).unwrap_or_default(),
         writing_mode = if config.right_to_left {
             "horizontal-rl"
         } else {
@@ -286,3 +510,317 @@ fn content_opf(title: &str, config: &ComicConfig, images: &[ProcessedImage]) ->
         },
     )
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+    /// Centered on its own, without a facing page
+    Alone,
+}
+
+impl From<PageSide> for Side {
+    fn from(side: PageSide) -> Self {
+        match side {
+            PageSide::Left => Side::Left,
+            PageSide::Right => Side::Right,
+        }
+    }
+}
+
+// side of the spread each page is shown on, the first being the cover. Pages alternate
+// from the side pairs start on, except for the halves of split spreads which go back on
+// their own side, and whole spreads and chapter starts which begin a new pair. Readers
+// leave the facing page empty when a page doesn't follow the previous one.
+fn spread_sides(config: &ComicConfig, images: &[ProcessedImage]) -> Vec<Side> {
+    let (first, second) = if config.right_to_left {
+        (Side::Right, Side::Left)
+    } else {
+        (Side::Left, Side::Right)
+    };
+    let opposite = |side| if side == first { second } else { first };
+
+    let mut sides = Vec::with_capacity(images.len());
+    let mut next = first;
+    for (i, image) in images.iter().enumerate() {
+        let chapter_start = i > 1 && images[i - 1].source.parent() != image.source.parent();
+        let side = match image.part {
+            _ if i == 0 || image.text.is_some() => Side::Alone,
+            PagePart::Spread | PagePart::Rotated => Side::Alone,
+            PagePart::Left => Side::Left,
+            PagePart::Right => Side::Right,
+            PagePart::Whole => match config.chapter_side {
+                Some(side) if chapter_start => side.into(),
+                _ => next,
+            },
+        };
+        next = match side {
+            Side::Alone => first,
+            side => opposite(side),
+        };
+        sides.push(side);
+    }
+    sides
+}
+
+// the comic's authors when known, otherwise the tool that made it
+fn dublin_core(metadata: Option<&crate::metadata::Metadata>) -> String {
+    let escape = crate::opds::escape;
+    let authors = metadata.map_or(&[][..], |metadata| &metadata.authors);
+    let mut elements: Vec<String> = authors
+        .iter()
+        .map(|author| format!("<dc:creator>{}</dc:creator>", escape(author)))
+        .collect();
+    if elements.is_empty() {
+        elements.push("<dc:creator>comically</dc:creator>".into());
+    }
+
+    if let Some(metadata) = metadata {
+        for artist in &metadata.cover_artists {
+            elements.push(format!(
+                "<dc:contributor>{}</dc:contributor>",
+                escape(artist)
+            ));
+        }
+        if let Some(description) = &metadata.description {
+            elements.push(format!(
+                "<dc:description>{}</dc:description>",
+                escape(description)
+            ));
+        }
+        if let Some(publisher) = &metadata.publisher {
+            elements.push(format!(
+                "<dc:publisher>{}</dc:publisher>",
+                escape(publisher)
+            ));
+        }
+        if let Some(url) = &metadata.source_url {
+            elements.push(format!("<dc:source>{}</dc:source>", escape(url)));
+        }
+    }
+    elements.join("\n            ")
+}
+
+// EPUB3 collections for Kobo and Apple Books, calibre's own tags for Calibre and Kindle
+fn series_meta(series: &crate::comic::Series) -> String {
+    let name = crate::opds::escape(&series.name);
+    let mut meta = format!(
+        r##"
+            <meta name="calibre:series" content="{name}"/>
+            <meta property="belongs-to-collection" id="series">{name}</meta>
+            <meta refines="#series" property="collection-type">series</meta>"##
+    );
+    if let Some(index) = series.index {
+        meta.push_str(&format!(
+            r##"
+            <meta name="calibre:series_index" content="{index}"/>
+            <meta refines="#series" property="group-position">{index}</meta>"##
+        ));
+    }
+    meta
+}
+
+#[test]
+fn series_metadata_in_opf() {
+    let series = crate::comic::Series::from_comic_info(
+        "<ComicInfo>\n  <Series>Dungeon &amp; Meshi</Series>\n  <Number>3</Number>\n</ComicInfo>",
+    )
+    .unwrap();
+    assert_eq!(series.index, Some(3.0));
+
+    let config = ComicConfig {
+        series: Some(series),
+        ..ComicConfig::default()
+    };
+    let opf = content_opf("Vol. 3", &Uuid::nil(), &config, &[]);
+    assert!(opf.contains(r##"<meta name="calibre:series_index" content="3"/>"##));
+    assert!(opf.contains(r##"<meta refines="#series" property="group-position">3</meta>"##));
+    assert!(opf.contains(r##"id="series">Dungeon &amp; Meshi</meta>"##));
+
+    let opf = content_opf("Vol. 3", &Uuid::nil(), &ComicConfig::default(), &[]);
+    assert!(!opf.contains("series"));
+}
+
+#[test]
+fn book_metadata_in_opf() {
+    let config = ComicConfig {
+        metadata: Some(crate::metadata::Metadata {
+            authors: vec!["Ryoko Kui".into()],
+            description: Some("Adventurers cook the monsters they fight".into()),
+            ..Default::default()
+        }),
+        ..ComicConfig::default()
+    };
+    let opf = content_opf("Vol. 3", &Uuid::nil(), &config, &[]);
+    assert!(opf.contains("<dc:creator>Ryoko Kui</dc:creator>"));
+    assert!(opf.contains("<dc:description>Adventurers cook"));
+    assert!(!opf.contains("<dc:creator>comically</dc:creator>"));
+}
+
+#[test]
+fn deterministic_build_is_reproducible() {
+    let config = ComicConfig {
+        deterministic: true,
+        ..ComicConfig::default()
+    };
+    let images = [ProcessedImage {
+        file_name: "vol1_001_000.jpg".to_string(),
+        data: b"page".to_vec(),
+        dimensions: (10, 20),
+        format: ImageFormat::Jpeg { quality: 85 },
+        source: "vol1/001.jpg".into(),
+        page: 1,
+        part: crate::PagePart::Whole,
+        original_dimensions: (10, 20),
+        cropped: false,
+        small: None,
+        text: None,
+    }];
+
+    let first = build("title", &config, &images);
+    assert_eq!(first, build("title", &config, &images));
+    assert_ne!(first, build("other title", &config, &images));
+}
+
+#[test]
+fn landscape_spreads_start_a_new_pair() {
+    let page = |page, part| ProcessedImage {
+        file_name: format!("{page}.jpg"),
+        data: Vec::new(),
+        dimensions: (10, 20),
+        format: ImageFormat::Jpeg { quality: 85 },
+        source: format!("{page}.jpg").into(),
+        page,
+        part,
+        original_dimensions: (10, 20),
+        cropped: false,
+        small: None,
+        text: None,
+    };
+    let images = [
+        page(1, crate::PagePart::Whole),
+        page(2, crate::PagePart::Whole),
+        page(3, crate::PagePart::Spread),
+        page(4, crate::PagePart::Whole),
+        page(5, crate::PagePart::Whole),
+    ];
+
+    let opf = content_opf("title", &Uuid::nil(), &ComicConfig::default(), &images);
+    assert!(opf.contains(r#"idref="page2" properties="page-spread-right""#));
+    assert!(opf.contains(r#"idref="page3" properties="page-spread-center rendition:spread-none""#));
+    // right to left, so the page after the spread is the right one of the next pair
+    assert!(opf.contains(r#"idref="page4" properties="page-spread-right""#));
+    assert!(opf.contains(r#"idref="page5" properties="page-spread-left""#));
+}
+
+#[test]
+fn spread_sides_follow_split_halves_and_chapters() {
+    let page = |source: &str, part| ProcessedImage {
+        file_name: String::new(),
+        data: Vec::new(),
+        dimensions: (10, 20),
+        format: ImageFormat::Jpeg { quality: 85 },
+        source: source.into(),
+        page: 1,
+        part,
+        original_dimensions: (10, 20),
+        cropped: false,
+        small: None,
+        text: None,
+    };
+    let images = [
+        page("ch1/001.jpg", PagePart::Whole),
+        page("ch1/002.jpg", PagePart::Whole),
+        // a spread split after an odd number of pages, its halves still face each other
+        page("ch1/003.jpg", PagePart::Right),
+        page("ch1/003.jpg", PagePart::Left),
+        page("ch1/004.jpg", PagePart::Whole),
+        page("ch2/001.jpg", PagePart::Whole),
+        page("ch2/002.jpg", PagePart::Whole),
+    ];
+
+    use Side::*;
+    let config = ComicConfig::default();
+    assert_eq!(
+        spread_sides(&config, &images),
+        [Alone, Right, Right, Left, Right, Left, Right]
+    );
+
+    let config = ComicConfig {
+        chapter_side: Some(PageSide::Right),
+        ..ComicConfig::default()
+    };
+    assert_eq!(
+        spread_sides(&config, &images),
+        [Alone, Right, Right, Left, Right, Right, Left]
+    );
+}
+
+#[test]
+fn matches_golden_epub() {
+    use crate::testing::{assert_golden, golden_path, processed_page, Package};
+
+    let config = ComicConfig {
+        deterministic: true,
+        series: Some(crate::Series {
+            name: "Sample & Co".into(),
+            index: Some(2.0),
+        }),
+        ..ComicConfig::default()
+    };
+    let images = [
+        processed_page(1, "ch1/001.jpg", PagePart::Whole),
+        processed_page(2, "ch1/002.jpg", PagePart::Right),
+        processed_page(2, "ch1/002.jpg", PagePart::Left),
+        processed_page(3, "ch1/003.jpg", PagePart::Spread),
+        processed_page(4, "ch2/001.jpg", PagePart::Whole),
+    ];
+
+    let epub = build("Sample v02", &config, &images);
+    let package = Package::read(&epub).unwrap();
+    package.check_epub().unwrap();
+    assert_golden(
+        &golden_path("sample.epub.txt"),
+        &Package::snapshot(&epub).unwrap(),
+    );
+}
+
+#[test]
+fn small_copies_are_offered_in_srcset() {
+    let mut page = crate::testing::processed_page(1, "001.jpg", PagePart::Whole);
+    page.dimensions = (800, 1200);
+    page.small = Some(crate::SmallCopy {
+        data: b"small".to_vec(),
+        dimensions: (300, 450),
+    });
+    let html = page_html(1, &page);
+    assert!(html.contains(
+        r#"<img src="Images/image001.jpg" srcset="Images/image001-small.jpg 300w, Images/image001.jpg 800w" sizes="100vw"/>"#
+    ));
+
+    let epub = build("title", &ComicConfig::default(), &[page]);
+    let package = crate::testing::Package::read(&epub).unwrap();
+    package.check_epub().unwrap();
+    assert_eq!(
+        package.get("OEBPS/Images/image001-small.jpg"),
+        Some(&b"small"[..])
+    );
+}
+
+#[test]
+fn text_pages_are_reflowable() {
+    let cover = crate::testing::processed_page(1, "001.jpg", PagePart::Whole);
+    let mut afterword = crate::testing::processed_page(2, "002.jpg", PagePart::Whole);
+    afterword.text = Some("Thanks & see you\n\nin volume 3".to_string());
+
+    let config = ComicConfig::default();
+    let epub = build("title", &config, &[cover, afterword]);
+    let package = crate::testing::Package::read(&epub).unwrap();
+    package.check_epub().unwrap();
+    let html = String::from_utf8(package.get("OEBPS/page002.html").unwrap().to_vec()).unwrap();
+    assert!(html.contains("<p>Thanks &amp; see you</p>\n  <p>in volume 3</p>"));
+    assert!(!html.contains("<img"));
+    assert_eq!(package.get("OEBPS/Images/image002.jpg"), None);
+    let opf = String::from_utf8(package.get("OEBPS/content.opf").unwrap().to_vec()).unwrap();
+    assert!(opf.contains(r#"idref="page2" properties="rendition:layout-reflowable""#));
+}
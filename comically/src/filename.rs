@@ -0,0 +1,445 @@
+//! Series, volume and chapters from the names comics are usually released under, like
+//! `[Group] Series v03 c021-025 (Digital)`, for comics without a `ComicInfo.xml`
+
+/// What a file name says about a comic, every part but the series is optional
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedName {
+    /// Name before the numbering, without tags, e.g. `Dungeon Meshi`
+    pub series: String,
+    /// `v03`, `Vol. 3`, `Volume 3`
+    pub volume: Option<f32>,
+    /// First and last chapter, the same for a single one: `c021-025`, `Ch. 21`
+    pub chapters: Option<(f32, f32)>,
+    /// Number without a volume or chapter marker, as comics are: `#12`, `Saga 012`
+    pub issue: Option<f32>,
+    /// Text after the numbering, e.g. the chapter title in `Series v01 - The Beginning`
+    pub title: Option<String>,
+    /// Release group, the bracketed tag the name starts with
+    pub group: Option<String>,
+    /// A bracketed year, e.g. `(2016)`
+    pub year: Option<u32>,
+    /// The other bracketed tags, e.g. `Digital`
+    pub tags: Vec<String>,
+}
+
+impl ParsedName {
+    /// Position in the series: the volume, otherwise the issue or first chapter
+    pub fn index(&self) -> Option<f32> {
+        self.volume
+            .or(self.issue)
+            .or(self.chapters.map(|(first, _)| first))
+    }
+}
+
+const EXTENSIONS: [&str; 7] = ["cbz", "cbr", "cb7", "zip", "rar", "epub", "mobi"];
+
+/// Splits a comic's file name (with or without its extension) into its parts
+pub fn parse(name: &str) -> ParsedName {
+    let name = match name.rsplit_once('.') {
+        Some((stem, extension)) if EXTENSIONS.contains(&extension.to_lowercase().as_str()) => stem,
+        _ => name,
+    };
+
+    let mut parsed = ParsedName::default();
+    let mut text = String::with_capacity(name.len());
+    for (tag, leading) in split_tags(name, &mut text) {
+        match tag.parse::<u32>() {
+            Ok(year) if (1900..2100).contains(&year) && parsed.year.is_none() => {
+                parsed.year = Some(year);
+            }
+            _ if leading && parsed.group.is_none() => parsed.group = Some(tag),
+            _ => parsed.tags.push(tag),
+        }
+    }
+
+    // `Series_v01` and `Series.v01.c002` separate words with something else than spaces
+    let mut text = text.replace('_', " ");
+    if !text.trim().contains(' ') {
+        text = text.replace('.', " ");
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut numbers = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        match numbering(words[i], words.get(i + 1).copied()) {
+            Some((number, consumed)) => {
+                numbers.push((i, number, consumed));
+                i += consumed + 1;
+            }
+            None => i += 1,
+        }
+    }
+
+    // the name ends at the first volume, chapter or issue marker. Bare numbers before it
+    // are part of the name, like `Kaiju No. 8 v01`, and so are those it starts with,
+    // like `2001 Nights`
+    let start = numbers
+        .iter()
+        .position(|(_, number, _)| !matches!(number, Number::Bare(_)))
+        .or_else(|| numbers.iter().position(|(at, _, _)| *at > 0));
+    let Some(start) = start else {
+        parsed.series = join_trimmed(&words);
+        return parsed;
+    };
+
+    let name_end = numbers[start].0;
+    parsed.series = join_trimmed(&words[..name_end]);
+    let mut numbers = numbers.into_iter().skip(start).peekable();
+    let mut title = Vec::new();
+    let mut i = name_end;
+    while i < words.len() {
+        match numbers.next_if(|(at, _, _)| *at == i) {
+            Some((_, number, consumed)) => {
+                match number {
+                    Number::Volume(volume) => parsed.volume = parsed.volume.or(Some(volume)),
+                    Number::Chapters(first, last) => {
+                        parsed.chapters = parsed.chapters.or(Some((first, last)));
+                    }
+                    Number::Issue(issue) | Number::Bare(issue) => {
+                        parsed.issue = parsed.issue.or(Some(issue));
+                    }
+                }
+                i += consumed;
+            }
+            None => title.push(words[i]),
+        }
+        i += 1;
+    }
+    parsed.title = Some(join_trimmed(&title)).filter(|title| !title.is_empty());
+    parsed
+}
+
+enum Number {
+    Volume(f32),
+    Chapters(f32, f32),
+    /// `#12`
+    Issue(f32),
+    /// A number without marker, an issue unless it's part of the name
+    Bare(f32),
+}
+
+const VOLUME_MARKERS: [&str; 5] = ["volume", "vol.", "vol", "tome", "v"];
+const CHAPTER_MARKERS: [&str; 6] = ["chapter", "chap.", "chap", "ch.", "ch", "c"];
+
+// the number `word` is, with how many following words it used up
+fn numbering(word: &str, next: Option<&str>) -> Option<(Number, usize)> {
+    let lower = word.to_lowercase();
+
+    // a marker and its number as separate words: `Vol. 3`, `Chapter 21-25`
+    if let Some((first, last)) = next.and_then(range) {
+        if VOLUME_MARKERS[..4].contains(&lower.as_str()) {
+            return Some((Number::Volume(first), 1));
+        }
+        if CHAPTER_MARKERS[..5].contains(&lower.as_str()) {
+            return Some((Number::Chapters(first, last), 1));
+        }
+    }
+
+    let number = if let Some(rest) = strip_marker(&lower, &VOLUME_MARKERS) {
+        Number::Volume(range(rest)?.0)
+    } else if let Some(rest) = strip_marker(&lower, &CHAPTER_MARKERS) {
+        let (first, last) = range(rest)?;
+        Number::Chapters(first, last)
+    } else {
+        match lower.strip_prefix('#') {
+            Some(digits) => Number::Issue(number(digits)?),
+            None => Number::Bare(number(&lower)?),
+        }
+    };
+    Some((number, 0))
+}
+
+// the rest of `word` after the marker, when it's followed by a digit
+fn strip_marker<'a>(word: &'a str, markers: &[&str]) -> Option<&'a str> {
+    markers
+        .iter()
+        .filter_map(|marker| word.strip_prefix(marker))
+        .find(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+// `21`, `21.5`, `021-025` or `021-c025`
+fn range(text: &str) -> Option<(f32, f32)> {
+    match text.split_once('-') {
+        Some((first, last)) => {
+            let last = last.trim_start_matches(|c: char| c.is_ascii_alphabetic() || c == '.');
+            Some((number(first)?, number(last)?))
+        }
+        None => number(text).map(|n| (n, n)),
+    }
+}
+
+fn number(text: &str) -> Option<f32> {
+    if !text.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+// separators left around the numbering, like the dash of `Series - v01 - Title`
+fn join_trimmed(words: &[&str]) -> String {
+    let is_separator = |word: &&str| word.chars().all(|c| matches!(c, '-' | '–' | ':' | '.'));
+    let start = words
+        .iter()
+        .position(|w| !is_separator(w))
+        .unwrap_or(words.len());
+    let end = words
+        .iter()
+        .rposition(|w| !is_separator(w))
+        .map_or(start, |i| i + 1);
+    words[start..end].join(" ")
+}
+
+// bracketed tags with whether nothing came before them, the rest of the name goes to `text`
+fn split_tags(name: &str, text: &mut String) -> Vec<(String, bool)> {
+    let mut tags = Vec::new();
+    let mut tag = String::new();
+    let mut depth = 0usize;
+    for c in name.chars() {
+        match c {
+            '[' | '(' | '{' => {
+                if depth > 0 {
+                    tag.push(c);
+                }
+                depth += 1;
+            }
+            ']' | ')' | '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let leading = text.trim().is_empty();
+                    tags.push((tag.trim().to_string(), leading));
+                    tag.clear();
+                    text.push(' ');
+                } else {
+                    tag.push(c);
+                }
+            }
+            c if depth > 0 => tag.push(c),
+            c => text.push(c),
+        }
+    }
+    tags.retain(|(tag, _)| !tag.is_empty());
+    tags
+}
+
+/// Output name from a template like `{series} v{volume:2}`, so volumes of a series are
+/// named the same whatever their source: `{series}`, `{volume}`, `{chapter}` (a range
+/// like `21-25` for several), `{issue}`, `{index}` (see [`ParsedName::index`]), `{title}`,
+/// `{group}`, `{year}`, and `{name}` for the whole name. A width after a colon pads
+/// numbers with zeros. `None` when the template uses a part the name doesn't have,
+/// characters that can't be in a file name are replaced with `_`
+pub fn render(template: &str, name: &str, parsed: &ParsedName) -> anyhow::Result<Option<String>> {
+    let mut rendered = String::with_capacity(template.len());
+    for part in template_parts(template)? {
+        let (field, width) = match part {
+            TemplatePart::Text(text) => {
+                rendered.push_str(text);
+                continue;
+            }
+            TemplatePart::Field(field, width) => (field, width),
+        };
+        let value = match field {
+            "name" => Some(name.to_string()),
+            "series" => Some(parsed.series.clone()).filter(|series| !series.is_empty()),
+            "volume" => parsed.volume.map(|volume| padded(volume, width)),
+            "chapter" => parsed.chapters.map(|(first, last)| match first == last {
+                true => padded(first, width),
+                false => format!("{}-{}", padded(first, width), padded(last, width)),
+            }),
+            "issue" => parsed.issue.map(|issue| padded(issue, width)),
+            "index" => parsed.index().map(|index| padded(index, width)),
+            "title" => parsed.title.clone(),
+            "group" => parsed.group.clone(),
+            "year" => parsed.year.map(|year| year.to_string()),
+            _ => unreachable!("checked by template_parts"),
+        };
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        rendered.push_str(&value);
+    }
+    Ok(Some(sanitize(&rendered)).filter(|name| !name.is_empty()))
+}
+
+/// Fails on unknown placeholders and unclosed braces, before any comic is converted
+pub fn check_template(template: &str) -> anyhow::Result<()> {
+    template_parts(template).map(|_| ())
+}
+
+/// `name` with the characters Windows, macOS or Linux don't allow in file names replaced
+pub fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_matches(['.', ' '])
+        .to_string()
+}
+
+const TEMPLATE_FIELDS: [&str; 9] = [
+    "name", "series", "volume", "chapter", "issue", "index", "title", "group", "year",
+];
+
+enum TemplatePart<'a> {
+    Text(&'a str),
+    /// A placeholder with the width its numbers are padded to
+    Field(&'a str, usize),
+}
+
+fn template_parts(template: &str) -> anyhow::Result<Vec<TemplatePart<'_>>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        parts.push(TemplatePart::Text(&rest[..open]));
+        let Some(close) = rest[open..].find('}') else {
+            anyhow::bail!("Unclosed `{{` in output template `{template}`");
+        };
+        let placeholder = &rest[open + 1..open + close];
+        let (field, width) = match placeholder.split_once(':') {
+            Some((field, width)) => match width.parse() {
+                Ok(width) => (field, width),
+                Err(_) => anyhow::bail!("Invalid width in `{{{placeholder}}}`"),
+            },
+            None => (placeholder, 0),
+        };
+        if !TEMPLATE_FIELDS.contains(&field) {
+            anyhow::bail!(
+                "Unknown placeholder `{{{field}}}` in output template, expected one of {}",
+                TEMPLATE_FIELDS.join(", ")
+            );
+        }
+        parts.push(TemplatePart::Field(field, width));
+        rest = &rest[open + close + 1..];
+    }
+    parts.push(TemplatePart::Text(rest));
+    Ok(parts)
+}
+
+// `3` as `03` for a width of 2, `4.5` as `04.5`
+fn padded(number: f32, width: usize) -> String {
+    let text = number.to_string();
+    match text.split_once('.') {
+        Some((whole, fraction)) => format!("{whole:0>width$}.{fraction}"),
+        None => format!("{text:0>width$}"),
+    }
+}
+
+#[test]
+fn parses_scanlation_names() {
+    let parsed = parse("[Group] Series v03 c021-025 (Digital)");
+    assert_eq!(
+        parsed,
+        ParsedName {
+            series: "Series".into(),
+            volume: Some(3.0),
+            chapters: Some((21.0, 25.0)),
+            group: Some("Group".into()),
+            tags: vec!["Digital".into()],
+            ..ParsedName::default()
+        }
+    );
+    assert_eq!(parsed.index(), Some(3.0));
+
+    let parsed = parse("[Group] Blame! v02 (2016) (Digital) (danke-Empire).cbz");
+    assert_eq!(parsed.series, "Blame!");
+    assert_eq!(parsed.year, Some(2016));
+    assert_eq!(parsed.tags, ["Digital", "danke-Empire"]);
+
+    let parsed = parse("Kaiju No. 8 - c105.5 [Raw Scans]");
+    assert_eq!(parsed.series, "Kaiju No. 8");
+    assert_eq!(parsed.chapters, Some((105.5, 105.5)));
+    assert_eq!(parsed.group, None);
+    assert_eq!(parsed.tags, ["Raw Scans"]);
+
+    let parsed = parse("One Piece - Chapter 1001 - Straw Hat Luffy");
+    assert_eq!(parsed.series, "One Piece");
+    assert_eq!(parsed.chapters, Some((1001.0, 1001.0)));
+    assert_eq!(parsed.title.as_deref(), Some("Straw Hat Luffy"));
+
+    let parsed = parse("Chainsaw Man v01 ch001-c007");
+    assert_eq!(parsed.chapters, Some((1.0, 7.0)));
+}
+
+#[test]
+fn parses_separators_and_markers() {
+    let parsed = parse("Dungeon_Meshi_Vol._03");
+    assert_eq!(parsed.series, "Dungeon Meshi");
+    assert_eq!(parsed.volume, Some(3.0));
+
+    let parsed = parse("Vagabond.v12.c105");
+    assert_eq!(parsed.series, "Vagabond");
+    assert_eq!(parsed.volume, Some(12.0));
+    assert_eq!(parsed.chapters, Some((105.0, 105.0)));
+
+    assert_eq!(parse("Berserk Volume 41").volume, Some(41.0));
+    assert_eq!(parse("Berserk Vol.41").volume, Some(41.0));
+    assert_eq!(parse("Asterix Tome 3").volume, Some(3.0));
+    assert_eq!(parse("Monster Ch. 12").chapters, Some((12.0, 12.0)));
+}
+
+#[test]
+fn parses_western_issues() {
+    let parsed = parse("Saga #012 (2013) (Digital)");
+    assert_eq!(parsed.series, "Saga");
+    assert_eq!(parsed.issue, Some(12.0));
+    assert_eq!(parsed.index(), Some(12.0));
+
+    let parsed = parse("Batman 404 (1987)");
+    assert_eq!(parsed.series, "Batman");
+    assert_eq!(parsed.issue, Some(404.0));
+    assert_eq!(parsed.year, Some(1987));
+}
+
+#[test]
+fn keeps_numbers_that_are_part_of_the_name() {
+    let parsed = parse("2001 Nights v02");
+    assert_eq!(parsed.series, "2001 Nights");
+    assert_eq!(parsed.volume, Some(2.0));
+
+    assert_eq!(parse("20th Century Boys 03").series, "20th Century Boys");
+    assert_eq!(
+        parse("Vinland Saga"),
+        ParsedName {
+            series: "Vinland Saga".into(),
+            ..ParsedName::default()
+        }
+    );
+    assert_eq!(parse("Cowboy Bebop").series, "Cowboy Bebop");
+    assert_eq!(parse("vol").series, "vol");
+    assert_eq!(parse("").index(), None);
+}
+
+#[test]
+fn renders_output_templates() {
+    let name = "[Group] Dungeon Meshi v3 c021-025 (2016) (Digital)";
+    let parsed = parse(name);
+    let named = |template| render(template, name, &parsed).unwrap();
+
+    assert_eq!(
+        named("{series} v{volume:2}").as_deref(),
+        Some("Dungeon Meshi v03")
+    );
+    assert_eq!(
+        named("{series} - c{chapter:3} ({year})").as_deref(),
+        Some("Dungeon Meshi - c021-025 (2016)")
+    );
+    assert_eq!(named("{name}").as_deref(), Some(name));
+    assert_eq!(named("{series} v{index:2}"), named("{series} v{volume:2}"));
+    // not every name has every part, those keep their own
+    assert_eq!(named("{series} #{issue}"), None);
+
+    let parsed = parse("Kaiju No. 8 - c105.5");
+    assert_eq!(
+        render("{series}: {chapter:3}", "", &parsed)
+            .unwrap()
+            .as_deref(),
+        Some("Kaiju No. 8_ 105.5")
+    );
+
+    assert!(check_template("{series} v{volume:2}").is_ok());
+    assert!(check_template("{series} {author}").is_err());
+    assert!(check_template("{series} v{volume").is_err());
+    assert!(check_template("{volume:two}").is_err());
+}
@@ -0,0 +1,173 @@
+//! Color pages for screens that show color, see [`ComicConfig::keep_color`].
+//!
+//! The pipeline works on gray levels, so the color of a page is split off into two
+//! chroma planes. They're cropped, resized and rotated like the gray page, and put back
+//! on it right before encoding. Tones, stamps and hooks only ever touch the gray levels.
+//!
+//! [`ComicConfig::keep_color`]: crate::ComicConfig::keep_color
+
+use imageproc::image::{DynamicImage, GrayImage, Rgb, RgbImage};
+
+use super::transform::{self, Image, Img};
+use super::Split;
+use crate::comic::{ComicConfig, MarginColor, PagePart, Rotation};
+
+// the weights of red and blue in the luma of `DynamicImage::to_luma8`, BT.709
+const RED: f32 = 0.2126;
+const BLUE: f32 = 0.0722;
+const GREEN: f32 = 1.0 - RED - BLUE;
+
+// chroma of gray levels
+const NEUTRAL: u8 = 128;
+
+/// The color of a page apart from its gray levels, as blue and red difference planes
+/// centered on 128
+#[derive(Clone)]
+pub struct Chroma {
+    pub blue: Image,
+    pub red: Image,
+}
+
+/// The gray levels of `img`, and its chroma when it's a color page, see
+/// [`super::stats::is_color`]
+pub fn split(img: DynamicImage) -> (Image, Option<Chroma>) {
+    if !super::stats::is_color(&img) {
+        return (img.into_luma8().into(), None);
+    }
+    let luma = img.to_luma8();
+    let rgb = img.into_rgb8();
+    let (width, height) = rgb.dimensions();
+    let mut blue = GrayImage::new(width, height);
+    let mut red = GrayImage::new(width, height);
+    for ((pixel, y), (cb, cr)) in rgb
+        .pixels()
+        .zip(luma.pixels())
+        .zip(blue.pixels_mut().zip(red.pixels_mut()))
+    {
+        let [r, _, b] = pixel.0.map(f32::from);
+        (cb.0[0], cr.0[0]) = difference(r, b, f32::from(y.0[0]));
+    }
+    let chroma = Chroma {
+        blue: blue.into(),
+        red: red.into(),
+    };
+    (luma.into(), Some(chroma))
+}
+
+/// The color page of `luma` and its `chroma`, which must be of the same size
+pub fn merge(luma: &GrayImage, chroma: &Chroma) -> RgbImage {
+    let (width, height) = luma.dimensions();
+    RgbImage::from_fn(width, height, |x, y| {
+        let luma = f32::from(luma.get_pixel(x, y).0[0]);
+        let cb = f32::from(chroma.blue.get_pixel(x, y)) - f32::from(NEUTRAL);
+        let cr = f32::from(chroma.red.get_pixel(x, y)) - f32::from(NEUTRAL);
+        let r = luma + 2.0 * (1.0 - RED) * cr;
+        let b = luma + 2.0 * (1.0 - BLUE) * cb;
+        let g = (luma - RED * r - BLUE * b) / GREEN;
+        Rgb([level(r), level(g), level(b)])
+    })
+}
+
+impl Chroma {
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.blue.dimensions()
+    }
+
+    /// Gray again under the boxes translations are lettered in, see [`super::overlay`]
+    pub fn clear(&mut self, left: u32, top: u32, width: u32, height: u32) {
+        for plane in [&mut self.blue, &mut self.red] {
+            plane.fill(left, top, width, height, NEUTRAL);
+        }
+    }
+
+    /// Turned like the gray page, see [`transform::rotate_output`]
+    pub fn rotate(self, rotation: Rotation) -> Chroma {
+        Chroma {
+            blue: transform::rotate_output(self.blue, rotation),
+            red: transform::rotate_output(self.red, rotation),
+        }
+    }
+
+    /// Brought to `(width, height)`, the size of the page once upscaled
+    pub fn scale(self, (width, height): (u32, u32)) -> Chroma {
+        if self.dimensions() == (width, height) {
+            return self;
+        }
+        let scale = |plane: &Image| transform::resample(plane, width, height, false).into();
+        Chroma {
+            blue: scale(&self.blue),
+            red: scale(&self.red),
+        }
+    }
+
+    /// The region of the page cropped to, see [`transform::Image::region`]
+    pub fn region(&self, left: u32, top: u32, width: u32, height: u32) -> Chroma {
+        Chroma {
+            blue: self.blue.region(left, top, width, height),
+            red: self.red.region(left, top, width, height),
+        }
+    }
+
+    /// The `(left, top, width, height)` crop of the page split, resized and rotated like
+    /// the gray page by [`super::render_page`], with margins in the chroma of the
+    /// config's margin color
+    pub fn render(
+        &self,
+        (left, top, width, height): (u32, u32, u32, u32),
+        config: &ComicConfig,
+    ) -> Split<(Chroma, PagePart)> {
+        let (blue, red) = match config.margin_color {
+            Some(MarginColor::Rgb(r, g, b)) => {
+                let [r, g, b] = [r, g, b].map(f32::from);
+                let luma = RED * r + GREEN * g + BLUE * b;
+                let (cb, cr) = difference(r, b, luma);
+                (MarginColor::Gray(cb), MarginColor::Gray(cr))
+            }
+            Some(MarginColor::Auto) => (MarginColor::Auto, MarginColor::Auto),
+            Some(MarginColor::Gray(_)) | None => {
+                (MarginColor::Gray(NEUTRAL), MarginColor::Gray(NEUTRAL))
+            }
+        };
+        let plane = |plane: &Image, margin| {
+            let view = plane.crop(left, top, width, height);
+            transform::split_rotate_filled(view, config, margin)
+                .into_iter()
+                .map(|(img, part)| (transform::rotate_output(img, config.rotation), part))
+        };
+        let parts = plane(&self.blue, blue)
+            .zip(plane(&self.red, red))
+            .map(|((blue, part), (red, _))| (Chroma { blue, red }, part))
+            .collect();
+        Split(parts)
+    }
+}
+
+// the blue and red differences of a color of `luma`
+fn difference(r: f32, b: f32, luma: f32) -> (u8, u8) {
+    let neutral = f32::from(NEUTRAL);
+    (
+        level((b - luma) / (2.0 * (1.0 - BLUE)) + neutral),
+        level((r - luma) / (2.0 * (1.0 - RED)) + neutral),
+    )
+}
+
+fn level(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+#[test]
+fn splits_and_merges_back() {
+    let page = RgbImage::from_fn(64, 64, |x, y| Rgb([(x * 4) as u8, 120, (y * 4) as u8]));
+    let (luma, chroma) = split(DynamicImage::ImageRgb8(page.clone()));
+    let chroma = chroma.unwrap();
+    let merged = merge(&GrayImage::from(luma), &chroma);
+    for (merged, page) in merged.pixels().zip(page.pixels()) {
+        for (merged, page) in merged.0.iter().zip(page.0) {
+            assert!(merged.abs_diff(page) <= 3, "{merged:?} {page:?}");
+        }
+    }
+
+    // gray pages have no chroma
+    let gray = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([90, 90, 90])));
+    assert!(split(gray).1.is_none());
+}
@@ -1,12 +1,809 @@
 //! Image decoding
 
-use anyhow::Result;
-use imageproc::image::{load_from_memory, DynamicImage};
+use anyhow::{Context, Result};
+use imageproc::image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+use imageproc::image::metadata::Orientation;
+use imageproc::image::{
+    AnimationDecoder, DynamicImage, Frames, GrayAlphaImage, GrayImage, ImageBuffer, ImageDecoder,
+    ImageFormat, ImageReader, Luma, RgbImage, RgbaImage,
+};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+use std::io::Cursor;
+
+use super::transform::Image;
 
 /// Decode image from memory
 ///
-/// Currently wraps the image crate's load_from_memory.
-/// Future: Add fast format-specific decoders (zune-jpeg, png crate direct)
+/// The EXIF orientation of photographed or scanned pages is applied, so the result is
+/// upright, and RGB pages with an embedded ICC profile (e.g. Adobe RGB) are converted to
+/// sRGB. The decoded pixels carry no metadata, so nothing downstream rotates them again
+/// and outputs never embed the profile.
+///
+/// CMYK JPEGs come out as RGB, through their CMYK profile when they embed one, and
+/// 16-bit pages (PNG, TIFF) as 8-bit with the range of tones they use stretched over
+/// the 8 bits, see [`to_8bit`].
+///
+/// JPEGs are decoded with zune-jpeg directly when the `fast-jpeg` feature is enabled,
+/// HEIC/HEIF pages with libheif when the `heif` feature is, and everything else and the
+/// JPEGs zune-jpeg can't decode with the image crate. See [`decode_luma`] for the
+/// pipeline's faster path.
 pub fn decode(data: &[u8]) -> Result<DynamicImage> {
-    load_from_memory(data).map_err(Into::into)
+    let span = crate::trace::span!(
+        "decode",
+        bytes = data.len(),
+        backend = tracing::field::Empty,
+        width = tracing::field::Empty,
+        height = tracing::field::Empty,
+    )
+    .entered();
+
+    let (decoded, backend) = match zune::decode(data) {
+        Some(decoded) => (decoded, "zune-jpeg"),
+        None if is_heif(data) => (heif::decode(data)?, "libheif"),
+        None => (decode_with_image(data)?, "image"),
+    };
+    let Decoded {
+        mut img,
+        orientation,
+        icc_profile,
+    } = decoded;
+    img.apply_orientation(orientation);
+    let mut img = to_8bit(img);
+
+    if let Some(icc_profile) = icc_profile {
+        match to_srgb(&img, &icc_profile) {
+            Ok(Some(converted)) => img = converted,
+            Ok(None) => {}
+            Err(e) => log::warn!("Ignoring invalid color profile: {e}"),
+        }
+    }
+
+    log::debug!("Decoded {}x{} with {backend}", img.width(), img.height());
+    span.record("backend", backend);
+    span.record("width", img.width());
+    span.record("height", img.height());
+    Ok(img)
+}
+
+/// Decode a page straight to the grayscale [`Image`] the pipeline processes
+///
+/// JPEGs are decoded to their luma channel (with the `fast-jpeg` feature) and 8-bit
+/// grayscale PNGs read as they are, into the buffer the image keeps, instead of through
+/// an RGB [`DynamicImage`] and a conversion. Pages with an EXIF orientation or a color
+/// profile to apply, and other formats, go through [`decode`].
+pub fn decode_luma(data: &[u8]) -> Result<Image> {
+    let fast = {
+        let span = crate::trace::span!(
+            "decode",
+            bytes = data.len(),
+            backend = tracing::field::Empty,
+            width = tracing::field::Empty,
+            height = tracing::field::Empty,
+        )
+        .entered();
+        let (img, backend) = match imageproc::image::guess_format(data) {
+            Ok(ImageFormat::Jpeg) => (zune::luma(data), "zune-jpeg"),
+            Ok(ImageFormat::Png) => (png_luma(data), "png"),
+            _ => (None, ""),
+        };
+        if let Some(img) = &img {
+            log::debug!(
+                "Decoded {}x{} to grayscale with {backend}",
+                img.width(),
+                img.height()
+            );
+            span.record("backend", backend);
+            span.record("width", img.width());
+            span.record("height", img.height());
+        }
+        img
+    };
+
+    match fast {
+        Some(img) => Ok(img.into()),
+        None => Ok(decode(data)?.into_luma8().into()),
+    }
+}
+
+/// Number of frames of an animated GIF, PNG or WebP, `None` for still pages
+pub fn animation_frames(data: &[u8]) -> Option<usize> {
+    let frames = frames(data)?.count();
+    (frames > 1).then_some(frames)
+}
+
+/// Frame `index` of an animated page, as a viewer shows it
+pub fn animation_frame(data: &[u8], index: usize) -> Result<DynamicImage> {
+    let frame = frames(data)
+        .context("Not an animated image")?
+        .nth(index)
+        .with_context(|| format!("No frame {index}"))??;
+    Ok(DynamicImage::ImageRgba8(frame.into_buffer()))
+}
+
+/// Side under which an image is taken for a placeholder rather than a page
+pub const PLACEHOLDER_SIZE: u32 = 16;
+
+/// Why `data` is no page: an empty file, or a placeholder image with a side under
+/// [`PLACEHOLDER_SIZE`] pixels. Only the header of the image is read.
+pub fn placeholder(data: &[u8]) -> Option<String> {
+    if data.is_empty() {
+        return Some("empty file".to_string());
+    }
+    let (width, height) = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()?;
+    (width.min(height) < PLACEHOLDER_SIZE).then(|| format!("placeholder image ({width}x{height})"))
+}
+
+/// Whether `data` is a HEIC/HEIF image, as phones save photos and scans
+pub fn is_heif(data: &[u8]) -> bool {
+    // the major brand of the file type box
+    const BRANDS: &[&[u8]] = &[
+        b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1",
+    ];
+    data.get(4..8) == Some(b"ftyp") && data.get(8..12).is_some_and(|brand| BRANDS.contains(&brand))
+}
+
+/// Number of pages of a multi-page TIFF, `None` for other images and single pages
+pub fn tiff_pages(data: &[u8]) -> Option<usize> {
+    let pages = Tiff::parse(data)?.directories.len();
+    (pages > 1).then_some(pages)
+}
+
+/// Page `index` of a multi-page TIFF, decoded like [`decode`] does
+pub fn tiff_page(data: &[u8], index: usize) -> Result<DynamicImage> {
+    let tiff = Tiff::parse(data).context("Not a TIFF")?;
+    let directory = *tiff
+        .directories
+        .get(index)
+        .with_context(|| format!("No page {index}"))?;
+    // decoders only read the page the header points at
+    let mut page = data.to_vec();
+    tiff.point_at(&mut page, directory);
+    decode(&page)
+}
+
+// where the pages of a TIFF are described
+struct Tiff {
+    big_endian: bool,
+    // BigTIFF, with 64-bit offsets
+    big: bool,
+    // offsets of the directory of each page, in order
+    directories: Vec<u64>,
+}
+
+impl Tiff {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let big_endian = match data.get(..2)? {
+            b"II" => false,
+            b"MM" => true,
+            _ => return None,
+        };
+        let uint = |at: u64, len: usize| -> Option<u64> {
+            let bytes = data.get(usize::try_from(at).ok()?..)?.get(..len)?;
+            let push = |value: u64, byte: &u8| value << 8 | *byte as u64;
+            Some(match big_endian {
+                true => bytes.iter().fold(0, push),
+                false => bytes.iter().rev().fold(0, push),
+            })
+        };
+        let big = match uint(2, 2)? {
+            42 => false,
+            43 => true,
+            _ => return None,
+        };
+        let (offset_len, count_len, entry_len) = match big {
+            false => (4, 2, 12),
+            true => (8, 8, 20),
+        };
+
+        let mut directories = Vec::new();
+        let mut next = uint(offset_len as u64, offset_len)?;
+        // a chain looping back would never end
+        while next != 0 && !directories.contains(&next) {
+            directories.push(next);
+            // the pages read so far are kept when the chain is cut short
+            let Some(entries) = uint(next, count_len) else {
+                break;
+            };
+            let Some(link) = entries
+                .checked_mul(entry_len)
+                .and_then(|len| (next + count_len as u64).checked_add(len))
+            else {
+                break;
+            };
+            next = uint(link, offset_len).unwrap_or(0);
+        }
+        Some(Self {
+            big_endian,
+            big,
+            directories,
+        })
+    }
+
+    // makes the header of `data` point at `directory`
+    fn point_at(&self, data: &mut [u8], directory: u64) {
+        let len = if self.big { 8 } else { 4 };
+        let header = &mut data[len..2 * len];
+        match self.big_endian {
+            true => header.copy_from_slice(&directory.to_be_bytes()[8 - len..]),
+            false => header.copy_from_slice(&directory.to_le_bytes()[..len]),
+        }
+    }
+}
+
+// the frames of animated images, still ones have none
+fn frames(data: &[u8]) -> Option<Frames<'_>> {
+    let cursor = Cursor::new(data);
+    match imageproc::image::guess_format(data).ok()? {
+        ImageFormat::Gif => Some(GifDecoder::new(cursor).ok()?.into_frames()),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(cursor).ok()?;
+            match decoder.is_apng().ok()? {
+                true => Some(decoder.apng().ok()?.into_frames()),
+                false => None,
+            }
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(cursor).ok()?;
+            decoder.has_animation().then(|| decoder.into_frames())
+        }
+        _ => None,
+    }
+}
+
+/// 16-bit pages as 8-bit. Scans often use a fraction of the 16-bit range, which scaling
+/// down would leave with a few dozen levels: the tones between the darkest and lightest
+/// 0.01% of pixels are stretched over the 8 bits instead. Alpha is scaled as is.
+pub fn to_8bit(img: DynamicImage) -> DynamicImage {
+    let (lo, hi) = match &img {
+        DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_)
+        | DynamicImage::ImageRgb16(_)
+        | DynamicImage::ImageRgba16(_) => tone_range(&img.to_luma16()),
+        _ => return img,
+    };
+    let span = (hi - lo).max(1) as u32;
+    let tone = |value: u16| (value.saturating_sub(lo) as u32 * 255 / span).min(255) as u8;
+    // the last of `channels` samples of a pixel is alpha when `alpha`
+    let map = |raw: &[u16], channels: usize, alpha: bool| -> Vec<u8> {
+        raw.iter()
+            .enumerate()
+            .map(|(i, &value)| match alpha && i % channels == channels - 1 {
+                true => (value >> 8) as u8,
+                false => tone(value),
+            })
+            .collect()
+    };
+
+    let (width, height) = (img.width(), img.height());
+    let converted = match &img {
+        DynamicImage::ImageLuma16(img) => {
+            GrayImage::from_raw(width, height, map(img.as_raw(), 1, false)).map(Into::into)
+        }
+        DynamicImage::ImageLumaA16(img) => {
+            GrayAlphaImage::from_raw(width, height, map(img.as_raw(), 2, true)).map(Into::into)
+        }
+        DynamicImage::ImageRgb16(img) => {
+            RgbImage::from_raw(width, height, map(img.as_raw(), 3, false)).map(Into::into)
+        }
+        DynamicImage::ImageRgba16(img) => {
+            RgbaImage::from_raw(width, height, map(img.as_raw(), 4, true)).map(Into::into)
+        }
+        _ => None,
+    };
+    converted.unwrap_or(img)
+}
+
+// darkest and lightest tones of a page, leaving out specks of dust and dead pixels
+fn tone_range(luma: &ImageBuffer<Luma<u16>, Vec<u16>>) -> (u16, u16) {
+    let mut histogram = vec![0usize; 1 << 16];
+    for &value in luma.as_raw() {
+        histogram[value as usize] += 1;
+    }
+    let outliers = luma.as_raw().len() / 10_000;
+    // first level past the outliers, counting from one end
+    let past_outliers = |levels: &mut dyn Iterator<Item = usize>| {
+        let mut seen = 0;
+        for level in levels {
+            seen += histogram[level];
+            if seen > outliers {
+                return level as u16;
+            }
+        }
+        0
+    };
+    let lo = past_outliers(&mut (0..histogram.len()));
+    let hi = past_outliers(&mut (0..histogram.len()).rev());
+    if hi > lo {
+        (lo, hi)
+    } else {
+        (0, u16::MAX)
+    }
+}
+
+// pixels before the metadata is applied
+struct Decoded {
+    img: DynamicImage,
+    orientation: Orientation,
+    icc_profile: Option<Vec<u8>>,
+}
+
+fn decode_with_image(data: &[u8]) -> Result<Decoded> {
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let icc_profile = decoder.icc_profile()?;
+    let img = DynamicImage::from_decoder(decoder)?;
+    Ok(Decoded {
+        img,
+        orientation,
+        icc_profile,
+    })
+}
+
+// the image crate decodes JPEGs with zune-jpeg too, but copies the file first and
+// can't output luma from color pages
+#[cfg(feature = "fast-jpeg")]
+mod zune {
+    use imageproc::image::metadata::Orientation;
+    use imageproc::image::{DynamicImage, GrayImage, RgbImage};
+    use zune_core::bytestream::ZCursor;
+    use zune_core::colorspace::ColorSpace;
+    use zune_core::options::DecoderOptions;
+    use zune_jpeg::JpegDecoder;
+
+    use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+    use super::{is_rotated, Decoded};
+
+    // `None` for what the image crate should decode instead, errors included: it
+    // reports them
+    pub fn decode(data: &[u8]) -> Option<Decoded> {
+        let (mut decoder, options) = decoder(data)?;
+        let input = decoder.input_colorspace()?;
+        if input == ColorSpace::CMYK {
+            return cmyk(data, decoder, options);
+        }
+        if !matches!(
+            input,
+            ColorSpace::Luma | ColorSpace::YCbCr | ColorSpace::RGB
+        ) {
+            return None;
+        }
+
+        let output = match input {
+            ColorSpace::Luma => ColorSpace::Luma,
+            _ => ColorSpace::RGB,
+        };
+        decoder.set_options(options.jpeg_set_out_colorspace(output));
+        let (width, height) = decoder.dimensions()?;
+        let (width, height) = (width as u32, height as u32);
+        let pixels = decoder.decode().ok()?;
+        let img = match output {
+            ColorSpace::Luma => {
+                DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, pixels)?)
+            }
+            _ => DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, pixels)?),
+        };
+
+        let orientation = decoder
+            .exif()
+            .and_then(|exif| Orientation::from_exif_chunk(exif))
+            .unwrap_or(Orientation::NoTransforms);
+        Some(Decoded {
+            img,
+            orientation,
+            icc_profile: decoder.icc_profile(),
+        })
+    }
+
+    // the image crate converts CMYK naively, ignoring the profile that says how the inks
+    // print
+    fn cmyk(
+        data: &[u8],
+        mut decoder: JpegDecoder<ZCursor<&[u8]>>,
+        options: DecoderOptions,
+    ) -> Option<Decoded> {
+        decoder.set_options(options.jpeg_set_out_colorspace(ColorSpace::CMYK));
+        let (width, height) = decoder.dimensions()?;
+        let mut pixels = decoder.decode().ok()?;
+        // Photoshop writes the samples inverted and marks its files with an APP14 segment
+        if !is_adobe(data) {
+            pixels.iter_mut().for_each(|value| *value = 255 - *value);
+        }
+        let profile = decoder.icc_profile();
+        let img = cmyk_to_rgb(width as u32, height as u32, pixels, profile.as_deref())?;
+
+        let orientation = decoder
+            .exif()
+            .and_then(|exif| Orientation::from_exif_chunk(exif))
+            .unwrap_or(Orientation::NoTransforms);
+        Some(Decoded {
+            img: DynamicImage::ImageRgb8(img),
+            orientation,
+            // applied already
+            icc_profile: None,
+        })
+    }
+
+    // from pixels where 255 means no ink, through a CMYK `icc_profile` when there is one
+    pub(super) fn cmyk_to_rgb(
+        width: u32,
+        height: u32,
+        inverted: Vec<u8>,
+        icc_profile: Option<&[u8]>,
+    ) -> Option<RgbImage> {
+        let profile = icc_profile
+            .and_then(|icc| ColorProfile::new_from_slice(icc).ok())
+            .filter(|profile| profile.color_space == DataColorSpace::Cmyk);
+        if let Some(profile) = profile {
+            // profiles take ink amounts
+            let ink: Vec<u8> = inverted.iter().map(|value| 255 - value).collect();
+            let srgb = ColorProfile::new_srgb();
+            let mut rgb = vec![0; ink.len() / 4 * 3];
+            match profile
+                .create_transform_8bit(
+                    Layout::Rgba,
+                    &srgb,
+                    Layout::Rgb,
+                    TransformOptions::default(),
+                )
+                .and_then(|transform| transform.transform(&ink, &mut rgb))
+            {
+                Ok(()) => return RgbImage::from_raw(width, height, rgb),
+                Err(e) => log::warn!("Ignoring invalid CMYK profile: {e}"),
+            }
+        }
+
+        let rgb = inverted
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let k = pixel[3] as u32;
+                [0, 1, 2].map(|i| (pixel[i] as u32 * k / 255) as u8)
+            })
+            .collect();
+        RgbImage::from_raw(width, height, rgb)
+    }
+
+    // whether the JPEG has Adobe's APP14 segment, read up to the first scan
+    pub(super) fn is_adobe(data: &[u8]) -> bool {
+        let mut i = 2;
+        while let [0xFF, marker, high, low, ..] = data[i.min(data.len())..] {
+            if marker == 0xDA {
+                break;
+            }
+            let len = u16::from_be_bytes([high, low]) as usize;
+            if marker == 0xEE && data.get(i + 4..i + 9) == Some(b"Adobe") {
+                return true;
+            }
+            i += 2 + len;
+        }
+        false
+    }
+
+    // `None` when the JPEG needs more than its luma channel
+    pub fn luma(data: &[u8]) -> Option<GrayImage> {
+        let (mut decoder, options) = decoder(data)?;
+        if !matches!(
+            decoder.input_colorspace()?,
+            ColorSpace::Luma | ColorSpace::YCbCr
+        ) || decoder
+            .icc_profile()
+            .is_some_and(|icc| is_rgb_profile(&icc))
+            || decoder.exif().is_some_and(|exif| is_rotated(exif))
+        {
+            return None;
+        }
+
+        decoder.set_options(options.jpeg_set_out_colorspace(ColorSpace::Luma));
+        let (width, height) = decoder.dimensions()?;
+        let pixels = decoder.decode().ok()?;
+        GrayImage::from_raw(width as u32, height as u32, pixels)
+    }
+
+    // only RGB profiles are applied by `decode`, see `to_srgb`
+    fn is_rgb_profile(icc_profile: &[u8]) -> bool {
+        ColorProfile::new_from_slice(icc_profile)
+            .map_or(true, |profile| profile.color_space == DataColorSpace::Rgb)
+    }
+
+    // with its headers read
+    fn decoder(data: &[u8]) -> Option<(JpegDecoder<ZCursor<&[u8]>>, DecoderOptions)> {
+        let options = DecoderOptions::default()
+            .set_strict_mode(false)
+            .set_max_width(usize::MAX)
+            .set_max_height(usize::MAX);
+        let mut decoder = JpegDecoder::new_with_options(ZCursor::new(data), options);
+        decoder.decode_headers().ok()?;
+        Some((decoder, options))
+    }
+}
+
+#[cfg(not(feature = "fast-jpeg"))]
+mod zune {
+    use imageproc::image::GrayImage;
+
+    use super::Decoded;
+
+    pub fn decode(_: &[u8]) -> Option<Decoded> {
+        None
+    }
+
+    pub fn luma(_: &[u8]) -> Option<GrayImage> {
+        None
+    }
+}
+
+#[cfg(feature = "heif")]
+mod heif {
+    use anyhow::{Context, Result};
+    use imageproc::image::metadata::Orientation;
+    use imageproc::image::{DynamicImage, RgbImage};
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    use super::Decoded;
+
+    pub fn decode(data: &[u8]) -> Result<Decoded> {
+        let context = HeifContext::read_from_bytes(data)?;
+        let handle = context.primary_image_handle()?;
+        let image = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+        let plane = image.planes().interleaved.context("No RGB plane")?;
+
+        // rows are padded to the stride
+        let row = plane.width as usize * 3;
+        let pixels = plane
+            .data
+            .chunks(plane.stride)
+            .flat_map(|line| &line[..row])
+            .copied()
+            .collect();
+        let img = RgbImage::from_raw(plane.width, plane.height, pixels)
+            .context("Truncated HEIF image")?;
+        Ok(Decoded {
+            img: DynamicImage::ImageRgb8(img),
+            // libheif rotates and mirrors the image itself
+            orientation: Orientation::NoTransforms,
+            icc_profile: handle.color_profile_raw().map(|profile| profile.data),
+        })
+    }
+}
+
+#[cfg(not(feature = "heif"))]
+mod heif {
+    use anyhow::{bail, Result};
+
+    use super::Decoded;
+
+    pub fn decode(_: &[u8]) -> Result<Decoded> {
+        bail!("HEIC/HEIF pages need comically built with the `heif` feature")
+    }
+}
+
+// `None` unless the PNG is 8-bit grayscale, which is how most scans are saved
+fn png_luma(data: &[u8]) -> Option<GrayImage> {
+    let mut reader = png::Decoder::new(Cursor::new(data)).read_info().ok()?;
+    let info = reader.info();
+    if (info.color_type, info.bit_depth) != (png::ColorType::Grayscale, png::BitDepth::Eight)
+        || info.exif_metadata.as_deref().is_some_and(is_rotated)
+    {
+        return None;
+    }
+
+    let (width, height) = (info.width, info.height);
+    let mut pixels = vec![0; reader.output_buffer_size()?];
+    let frame = reader.next_frame(&mut pixels).ok()?;
+    // rows are packed for 8-bit grayscale, so the buffer is the image as is
+    if frame.line_size != width as usize {
+        return None;
+    }
+    pixels.truncate(frame.buffer_size());
+    GrayImage::from_raw(width, height, pixels)
+}
+
+fn is_rotated(exif: &[u8]) -> bool {
+    Orientation::from_exif_chunk(exif)
+        .is_some_and(|orientation| orientation != Orientation::NoTransforms)
+}
+
+// pages are handled as sRGB from here on, gray and CMYK profiles are left alone
+fn to_srgb(img: &DynamicImage, icc_profile: &[u8]) -> Result<Option<DynamicImage>> {
+    let profile = ColorProfile::new_from_slice(icc_profile)?;
+    if profile.color_space != DataColorSpace::Rgb {
+        return Ok(None);
+    }
+
+    let srgb = ColorProfile::new_srgb();
+    let options = TransformOptions::default();
+    if img.color().has_alpha() {
+        let src = img.to_rgba8();
+        let transform =
+            profile.create_transform_8bit(Layout::Rgba, &srgb, Layout::Rgba, options)?;
+        let mut dst = RgbaImage::new(src.width(), src.height());
+        transform.transform(&src, &mut dst)?;
+        Ok(Some(DynamicImage::ImageRgba8(dst)))
+    } else {
+        let src = img.to_rgb8();
+        let transform = profile.create_transform_8bit(Layout::Rgb, &srgb, Layout::Rgb, options)?;
+        let mut dst = RgbImage::new(src.width(), src.height());
+        transform.transform(&src, &mut dst)?;
+        Ok(Some(DynamicImage::ImageRgb8(dst)))
+    }
+}
+
+#[test]
+fn applies_exif_orientation() {
+    use imageproc::image::{codecs::jpeg::JpegEncoder, GrayImage};
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new(&mut jpeg)
+        .encode_image(&GrayImage::new(20, 10))
+        .unwrap();
+
+    // APP1 segment with a big endian TIFF holding only Orientation = 6 (rotate 90° clockwise)
+    let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01".to_vec();
+    exif.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0]);
+    let len = (exif.len() + 2) as u16;
+    let mut segment = vec![0xff, 0xe1];
+    segment.extend_from_slice(&len.to_be_bytes());
+    segment.extend_from_slice(&exif);
+    jpeg.splice(2..2, segment);
+
+    let img = decode(&jpeg).unwrap();
+    assert_eq!((img.width(), img.height()), (10, 20));
+}
+
+#[test]
+fn converts_embedded_profiles_to_srgb() {
+    use imageproc::image::{codecs::png::PngEncoder, ImageEncoder, Rgb};
+
+    let page = RgbImage::from_pixel(4, 4, Rgb([40, 160, 40]));
+    let mut png = Vec::new();
+    let mut encoder = PngEncoder::new(&mut png);
+    let adobe_rgb = ColorProfile::new_adobe_rgb().encode().unwrap();
+    encoder.set_icc_profile(adobe_rgb).unwrap();
+    encoder
+        .write_image(
+            page.as_raw(),
+            4,
+            4,
+            imageproc::image::ExtendedColorType::Rgb8,
+        )
+        .unwrap();
+
+    let img = decode(&png).unwrap().into_rgb8();
+    let Rgb([r, g, b]) = *img.get_pixel(0, 0);
+    // Adobe RGB's green is more saturated than sRGB can show
+    assert!(r < 10 && g > 150 && b < 20, "{:?}", (r, g, b));
+}
+
+#[test]
+fn decodes_grayscale_pages_directly() {
+    use imageproc::image::{codecs::png::PngEncoder, ImageEncoder, Rgb};
+
+    let jpeg = crate::testing::sample_page(30, 20, 1);
+    #[cfg(feature = "fast-jpeg")]
+    assert!(zune::luma(&jpeg).is_some());
+    let fast = GrayImage::from(decode_luma(&jpeg).unwrap());
+    assert_eq!(fast, decode(&jpeg).unwrap().into_luma8());
+
+    let page = GrayImage::from_fn(30, 20, |x, y| imageproc::image::Luma([(x * y) as u8]));
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(
+            page.as_raw(),
+            30,
+            20,
+            imageproc::image::ExtendedColorType::L8,
+        )
+        .unwrap();
+    assert_eq!(GrayImage::from(decode_luma(&png).unwrap()), page);
+
+    // anything else is converted from what `decode` returns
+    let page = RgbImage::from_pixel(4, 4, Rgb([200, 40, 40]));
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(
+            page.as_raw(),
+            4,
+            4,
+            imageproc::image::ExtendedColorType::Rgb8,
+        )
+        .unwrap();
+    assert!(png_luma(&png).is_none());
+    let img = GrayImage::from(decode_luma(&png).unwrap());
+    assert_eq!(img, DynamicImage::ImageRgb8(page).into_luma8());
+}
+
+#[test]
+fn fast_jpeg_matches_the_image_crate() {
+    use imageproc::image::{codecs::jpeg::JpegEncoder, Rgb};
+
+    let page = RgbImage::from_fn(16, 12, |x, y| Rgb([x as u8 * 15, y as u8 * 20, 90]));
+    let mut jpeg = Vec::new();
+    JpegEncoder::new(&mut jpeg).encode_image(&page).unwrap();
+
+    let img = decode(&jpeg).unwrap();
+    assert_eq!(img, decode_with_image(&jpeg).unwrap().img);
+    #[cfg(feature = "fast-jpeg")]
+    assert!(zune::decode(&jpeg).is_some());
+}
+
+#[test]
+fn stretches_16_bit_tones() {
+    // a flat scan using 2% of the 16-bit range
+    let scan: ImageBuffer<Luma<u16>, Vec<u16>> =
+        ImageBuffer::from_fn(100, 100, |x, _| Luma([20_000 + x as u16 * 13]));
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma16(scan)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+
+    let img = decode_luma(&png).unwrap();
+    let row = &img.as_raw()[..100];
+    assert_eq!((row[0], row[99]), (0, 255));
+    // every column keeps a level of its own
+    assert!(row.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[cfg(feature = "fast-jpeg")]
+#[test]
+fn converts_cmyk_to_rgb() {
+    // no ink, cyan, and full black, with 255 meaning no ink
+    let pixels = vec![255, 255, 255, 255, 0, 255, 255, 255, 255, 255, 255, 0];
+    let img = zune::cmyk_to_rgb(3, 1, pixels, None).unwrap();
+    assert_eq!(img.as_raw(), &[255, 255, 255, 0, 255, 255, 0, 0, 0]);
+
+    let segment = |marker: u8, payload: &[u8]| {
+        let len = (payload.len() as u16 + 2).to_be_bytes();
+        [&[0xFF, marker, len[0], len[1]], payload].concat()
+    };
+    let jfif = [&[0xFF, 0xD8][..], &segment(0xE0, b"JFIF\0")].concat();
+    assert!(!zune::is_adobe(&jfif));
+    let adobe = [
+        &jfif[..],
+        &segment(0xEE, b"Adobe\0\x64\0\0\0\0\0"),
+        &[0xFF, 0xDA],
+    ]
+    .concat();
+    assert!(zune::is_adobe(&adobe));
+}
+
+#[test]
+fn multi_page_tiffs_are_read_page_by_page() {
+    use tiff::encoder::{colortype::Gray8, TiffEncoder};
+
+    let tiff = |shades: &[u8]| {
+        let mut tiff = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut tiff).unwrap();
+        for &shade in shades {
+            encoder.write_image::<Gray8>(20, 30, &[shade; 600]).unwrap();
+        }
+        tiff.into_inner()
+    };
+    assert_eq!(tiff_pages(&tiff(&[10])), None);
+
+    let scan = tiff(&[10, 200, 90]);
+    assert_eq!(tiff_pages(&scan), Some(3));
+    let shades: Vec<u8> = (0..3)
+        .map(|index| tiff_page(&scan, index).unwrap().into_luma8()[(0, 0)].0[0])
+        .collect();
+    assert_eq!(shades, [10, 200, 90]);
+    assert!(tiff_page(&scan, 3).is_err());
+}
+
+#[test]
+fn heif_files_are_recognized() {
+    let file = |brand: &[u8]| [&[0, 0, 0, 24][..], b"ftyp", brand, &[0; 12]].concat();
+    assert!(is_heif(&file(b"heic")));
+    assert!(is_heif(&file(b"mif1")));
+    assert!(!is_heif(&file(b"avif")));
+    assert!(!is_heif(&crate::testing::sample_page(20, 30, 1)));
+
+    #[cfg(not(feature = "heif"))]
+    assert!(decode(&file(b"heic")).is_err());
 }
@@ -4,8 +4,10 @@ use anyhow::{Context, Result};
 use imageproc::image::{ColorType, DynamicImage, GenericImageView};
 use webp::WebPMemory;
 
+use std::path::Path;
+
 use crate::archive::ArchiveFile;
-use crate::comic::ProcessedImage;
+use crate::comic::{PagePart, ProcessedImage};
 
 use super::ImageFormat;
 
@@ -98,27 +100,54 @@ pub fn encode_image_part(
     part_num: usize,
     format: ImageFormat,
 ) -> ProcessedImage {
-    let file_name = {
-        let file = original.parent().display();
-        let stem = original.file_stem().to_string_lossy();
-        let extension = format.extension();
-        format!("{file}_{stem}_{part_num:03}.{extension}")
-    };
-
     let dimensions = img.dimensions();
+    let span = crate::trace::span!(
+        "encode",
+        part = part_num,
+        format = format.extension(),
+        bytes = tracing::field::Empty,
+    )
+    .entered();
 
     let img = ProcessedImage {
-        file_name,
+        file_name: part_file_name(original, part_num, format),
         data: encode_image(img, &format),
         dimensions,
         format,
+        source: original.file_name.clone(),
+        // set once the whole comic is sorted
+        page: 0,
+        part: PagePart::Whole,
+        original_dimensions: dimensions,
+        cropped: false,
+        small: None,
+        text: None,
     };
 
+    span.record("bytes", img.data.len());
     log::trace!("Encoded image: {}", img.file_name);
     img
 }
 
-fn encode_image(img: &DynamicImage, format: &ImageFormat) -> Vec<u8> {
+/// Name of one part of a page in the output, derived from its path in the archive
+pub(crate) fn part_file_name(
+    original: &ArchiveFile,
+    part_num: usize,
+    format: ImageFormat,
+) -> String {
+    part_file_name_of(&original.file_name, part_num, format)
+}
+
+// the same from the page's path in the archive, before it's read
+pub(crate) fn part_file_name_of(path: &Path, part_num: usize, format: ImageFormat) -> String {
+    let file = path.parent().unwrap().display();
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    let extension = format.extension();
+    format!("{file}_{stem}_{part_num:03}.{extension}")
+}
+
+/// Encodes a whole image in memory
+pub fn encode_image(img: &DynamicImage, format: &ImageFormat) -> Vec<u8> {
     let (width, height) = img.dimensions();
     let mut buffer = Vec::with_capacity((width * height) as usize);
 
@@ -0,0 +1,185 @@
+//! Pages piped through an external command, for upscalers and cleanup tools comically
+//! can't link to. The page goes to the command's stdin as a grayscale PNG and the image
+//! it writes to stdout replaces it, before cropping and resizing.
+
+use anyhow::{bail, Context, Result};
+use imageproc::image::{ExtendedColorType, ImageEncoder};
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Condvar, Mutex};
+
+use crate::image::transform::Image;
+
+/// An external command each page is piped through
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PageFilter {
+    /// Run by the shell, e.g. `waifu2x-ncnn-vulkan -i - -o -`
+    pub command: String,
+    /// How many pages go through the command at once, GPU tools usually want 1
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+}
+
+fn default_jobs() -> usize {
+    1
+}
+
+impl PageFilter {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            jobs: default_jobs(),
+        }
+    }
+}
+
+/// Runs a [`PageFilter`] on at most `jobs` pages at a time
+pub struct Runner<'a> {
+    filter: &'a PageFilter,
+    running: Mutex<usize>,
+    done: Condvar,
+}
+
+impl<'a> Runner<'a> {
+    pub fn new(filter: &'a PageFilter) -> Self {
+        Self {
+            filter,
+            running: Mutex::new(0),
+            done: Condvar::new(),
+        }
+    }
+
+    /// `img` after the command, `file` is passed to it in `COMICALLY_PAGE_FILE`
+    pub fn run(&self, img: &Image, file: &Path) -> Result<Image> {
+        let _slot = self.slot();
+        let _span = crate::trace::span!("filter", file = %file.display()).entered();
+        filter(&self.filter.command, img, file).with_context(|| {
+            let command = &self.filter.command;
+            format!("Page filter `{command}` failed on {}", file.display())
+        })
+    }
+
+    // waits for one of the `jobs` slots, freed when the guard drops
+    fn slot(&self) -> Slot<'_, 'a> {
+        let jobs = self.filter.jobs.max(1);
+        let mut running = self.running.lock().unwrap();
+        while *running >= jobs {
+            running = self.done.wait(running).unwrap();
+        }
+        *running += 1;
+        Slot(self)
+    }
+}
+
+struct Slot<'a, 'b>(&'a Runner<'b>);
+
+impl Drop for Slot<'_, '_> {
+    fn drop(&mut self) {
+        *self.0.running.lock().unwrap() -= 1;
+        self.0.done.notify_one();
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+fn filter(command: &str, img: &Image, file: &Path) -> Result<Image> {
+    let (width, height) = img.dimensions();
+    let mut png = Vec::new();
+    imageproc::image::codecs::png::PngEncoder::new(&mut png)
+        .write_image(img.as_raw(), width, height, ExtendedColorType::L8)
+        .context("Failed to encode the page")?;
+
+    let mut child = shell(command)
+        .env("COMICALLY_PAGE_FILE", file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start the command")?;
+
+    // written from another thread, a command answering before it read everything would
+    // otherwise block on a full stdout pipe while we block on its stdin
+    let mut stdin = child.stdin.take().context("No stdin")?;
+    let writer = std::thread::spawn(move || stdin.write_all(&png));
+    let mut stderr = child.stderr.take().context("No stderr")?;
+    let errors = std::thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+
+    let mut output = Vec::new();
+    child
+        .stdout
+        .take()
+        .context("No stdout")?
+        .read_to_end(&mut output)
+        .context("Failed to read the filtered page")?;
+    let status = child.wait().context("Failed to wait for the command")?;
+    let written = writer.join().expect("the writer doesn't panic");
+    let stderr = errors.join().unwrap_or_default();
+
+    if !status.success() {
+        bail!(
+            "exited with {status}: {}",
+            stderr.lines().last().unwrap_or_default()
+        );
+    }
+    // a command may stop reading once it has what it needs, only a failure to write
+    // matters when nothing came back
+    if output.is_empty() {
+        match written {
+            Err(e) => bail!("didn't read the page: {e}"),
+            Ok(()) => bail!("wrote nothing to stdout"),
+        }
+    }
+
+    let filtered = imageproc::image::load_from_memory(&output)
+        .context("Failed to decode the filtered page")?;
+    Ok(filtered.into_luma8().into())
+}
+
+#[cfg(unix)]
+#[test]
+fn pages_go_through_the_command() {
+    use imageproc::image::{GrayImage, Luma};
+
+    let page = Image::from(GrayImage::from_pixel(30, 40, Luma([90])));
+    let file = Path::new("chapter/001.jpg");
+
+    let filter = PageFilter {
+        jobs: 2,
+        ..PageFilter::new("cat")
+    };
+    let runner = Runner::new(&filter);
+    let filtered = runner.run(&page, file).unwrap();
+    assert_eq!(filtered.dimensions(), (30, 40));
+    assert_eq!(filtered.as_raw(), page.as_raw());
+
+    let error = |command| {
+        let filter = PageFilter::new(command);
+        let error = Runner::new(&filter).run(&page, file).err().unwrap();
+        format!("{error:#}")
+    };
+    let failed = error("echo oops >&2; exit 3");
+    assert!(
+        failed.contains("exited with exit status: 3: oops"),
+        "{failed}"
+    );
+    assert!(error("cat > /dev/null").contains("wrote nothing"));
+
+    let filter = PageFilter::new(r#"test "$COMICALLY_PAGE_FILE" = chapter/001.jpg && cat"#);
+    assert!(Runner::new(&filter).run(&page, file).is_ok());
+}
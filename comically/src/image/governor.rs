@@ -0,0 +1,196 @@
+//! Limits how many pages are decoded at once. A fixed number of pages can still run out
+//! of memory on huge scans while leaving cores idle on small ones, so in the `auto` mode
+//! pages instead take the bytes they'll need decoded from a budget sized after the
+//! memory the system has available, and wait when it's spent.
+
+use imageproc::image::ImageReader;
+
+use std::io::Cursor;
+use std::sync::{Condvar, Mutex, OnceLock};
+
+use crate::comic::Concurrency;
+
+// the page decoded in color, its grayscale copy and the resized parts of the targets
+const BYTES_PER_PIXEL: u64 = 4 + 1 + 2;
+
+// undecodable headers are costed from the file, most formats compress about this much
+const COMPRESSION_RATIO: u64 = 10;
+
+// the share of the available memory pages may take, the rest is left to the outputs,
+// the other programs and what the estimate misses
+const BUDGET_SHARE: u64 = 2;
+
+// when the available memory can't be read
+const FALLBACK_BUDGET: u64 = 2 << 30;
+
+const MIN_BUDGET: u64 = 256 << 20;
+
+/// Takes pages in while what they cost fits the budget
+pub struct Governor {
+    budget: u64,
+    used: Mutex<u64>,
+    freed: Condvar,
+}
+
+impl Governor {
+    pub fn new(budget: u64) -> Self {
+        Self {
+            budget: budget.max(1),
+            used: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// One budget for every conversion of the process, so comics converted at the same
+    /// time share it too
+    pub fn global() -> &'static Governor {
+        static GLOBAL: OnceLock<Governor> = OnceLock::new();
+        GLOBAL.get_or_init(|| {
+            let budget = match available_memory() {
+                Some(available) => (available / BUDGET_SHARE).max(MIN_BUDGET),
+                None => FALLBACK_BUDGET,
+            };
+            log::debug!("Decoding pages within {} MiB", budget >> 20);
+            Governor::new(budget)
+        })
+    }
+
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Waits until `cost` fits in what's left of the budget, given back when the permit
+    /// drops. A page costing more than the whole budget waits for every other page to be
+    /// done and then runs alone.
+    pub fn acquire(&self, cost: u64) -> Permit<'_> {
+        let cost = cost.min(self.budget);
+        let mut used = self.used.lock().unwrap();
+        if *used + cost > self.budget {
+            log::trace!("Waiting for {} MiB to decode a page", cost >> 20);
+        }
+        while *used + cost > self.budget {
+            used = self.freed.wait(used).unwrap();
+        }
+        *used += cost;
+        Permit {
+            governor: self,
+            cost,
+        }
+    }
+
+    /// What the pages being processed take
+    pub fn used(&self) -> u64 {
+        *self.used.lock().unwrap()
+    }
+}
+
+pub struct Permit<'a> {
+    governor: &'a Governor,
+    cost: u64,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.governor.used.lock().unwrap() -= self.cost;
+        // pages of any cost may be waiting, the first woken may not fit
+        self.governor.freed.notify_all();
+    }
+}
+
+/// How [`Concurrency`] limits the pages of a conversion
+pub enum Limit {
+    Memory(&'static Governor),
+    // a budget of `n` pages costing 1 each
+    Pages(Governor),
+}
+
+impl Limit {
+    pub fn new(concurrency: Concurrency) -> Self {
+        match concurrency {
+            Concurrency::Auto => Self::Memory(Governor::global()),
+            Concurrency::Fixed(pages) => Self::Pages(Governor::new(pages.max(1) as u64)),
+        }
+    }
+
+    /// Waits for the page in `data` to be let in
+    pub fn acquire(&self, data: &[u8]) -> Permit<'_> {
+        match self {
+            Self::Memory(governor) => governor.acquire(page_cost(data)),
+            Self::Pages(governor) => governor.acquire(1),
+        }
+    }
+}
+
+/// Bytes a page takes while it's processed, from the dimensions in its header
+pub fn page_cost(data: &[u8]) -> u64 {
+    let dimensions = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+    match dimensions {
+        Some((width, height)) => u64::from(width) * u64::from(height) * BYTES_PER_PIXEL,
+        None => data.len() as u64 * COMPRESSION_RATIO,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(target_os = "macos")]
+fn available_memory() -> Option<u64> {
+    // what's free can't be read without the mach APIs, half the memory stands in for it
+    let output = std::process::Command::new("sysctl")
+        .args(["-n", "hw.memsize"])
+        .output()
+        .ok()?;
+    let total: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    Some(total / 2)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn available_memory() -> Option<u64> {
+    None
+}
+
+#[test]
+fn pages_wait_for_the_budget() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    let governor = Governor::new(100);
+    let first = governor.acquire(60);
+    assert_eq!(governor.used(), 60);
+
+    let admitted = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let _second = governor.acquire(60);
+            admitted.store(true, Ordering::SeqCst);
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!admitted.load(Ordering::SeqCst));
+        drop(first);
+    });
+    assert!(admitted.load(Ordering::SeqCst));
+    assert_eq!(governor.used(), 0);
+
+    // too big for the budget, runs alone instead of never
+    let huge = governor.acquire(1000);
+    assert_eq!(governor.used(), 100);
+    drop(huge);
+
+    let page = crate::testing::sample_page(60, 80, 1);
+    assert_eq!(page_cost(&page), 60 * 80 * BYTES_PER_PIXEL);
+    assert_eq!(page_cost(b"not an image"), 12 * COMPRESSION_RATIO);
+    assert!(Governor::global().budget() >= MIN_BUDGET);
+}
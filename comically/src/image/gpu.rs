@@ -0,0 +1,668 @@
+//! Resizing pages and adjusting their tones on the GPU, for machines where the cores are
+//! better left to decoding and encoding.
+//!
+//! Runs compute shaders through [wgpu](https://wgpu.rs), on Vulkan, Metal, DirectX 12 or
+//! OpenGL, when comically is built with the `gpu` feature and the config asks for
+//! [`Backend::Gpu`]. Tones come out the same as on the CPU and resized pages within a
+//! level or two of it. Without the feature or a hardware adapter, pages are processed on
+//! the CPU, with a warning saying why.
+//!
+//! Pages are sent to the GPU in batches: while one batch runs, the pages the other
+//! threads of the pipeline bring queue up, and the first of them to find the GPU idle
+//! records all of them in one command buffer, submitted and waited on once.
+
+use anyhow::Result;
+use imageproc::image::GrayImage;
+use parking_lot::Mutex;
+
+use std::sync::{mpsc, OnceLock};
+
+use super::transform::{Image, Img};
+use crate::comic::{Backend, ComicConfig};
+
+/// Whether [`Backend::Gpu`] can run, or why not
+pub fn check() -> Result<(), String> {
+    static CHECK: OnceLock<Result<(), String>> = OnceLock::new();
+    CHECK.get_or_init(device::check).clone()
+}
+
+/// The tone adjustments of [`super::adjust`] on the GPU, when the config asks for it.
+/// `None` leaves them to the CPU.
+pub fn adjust(img: &Image, config: &ComicConfig) -> Option<Image> {
+    if img.as_raw().is_empty() || !enabled(config.backend) {
+        return None;
+    }
+
+    let _span = crate::trace::span!("gpu").entered();
+    match submit(tones(img, config)) {
+        Ok(pixels) => {
+            let (width, height) = img.dimensions();
+            GrayImage::from_raw(width, height, pixels).map(Image::from)
+        }
+        Err(e) => {
+            log::warn!("Adjusting the page on the GPU failed, adjusting it on the CPU: {e:#}");
+            None
+        }
+    }
+}
+
+/// `img` resampled to `width` by `height` on the GPU with the filters of the CPU resize,
+/// when `backend` asks for it. `None` leaves it to the CPU.
+pub fn resize<I: Img>(img: &I, (width, height): (u32, u32), backend: Backend) -> Option<GrayImage> {
+    let (src_width, src_height) = img.dimensions();
+    if src_width == 0 || src_height == 0 || width == 0 || height == 0 || !enabled(backend) {
+        return None;
+    }
+
+    let _span = crate::trace::span!("gpu").entered();
+    match submit(resampling(img, (width, height))) {
+        Ok(pixels) => GrayImage::from_raw(width, height, pixels),
+        Err(e) => {
+            log::warn!("Resizing the page on the GPU failed, resizing it on the CPU: {e:#}");
+            None
+        }
+    }
+}
+
+/// Convolution filters of the resize, the ones `fast_image_resize` is used with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Lanczos3,
+    CatmullRom,
+}
+
+// a page for the GPU to process, its pixels packed four to a word by the shaders
+#[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+enum Job {
+    // mapped through `pre`, stretched like `Image::autocontrast`, then mapped through `post`
+    Tones {
+        pixels: Vec<u8>,
+        pre: Box<[u8; 256]>,
+        post: Box<[u8; 256]>,
+    },
+    // resampled from `src` to `dst` dimensions
+    Resize {
+        pixels: Vec<u8>,
+        src: (u32, u32),
+        dst: (u32, u32),
+        filter: Filter,
+    },
+}
+
+fn tones(img: &Image, config: &ComicConfig) -> Job {
+    let (pre, post) = super::transform::tone_luts(img, config);
+    Job::Tones {
+        pixels: img.as_raw().to_vec(),
+        pre: Box::new(pre),
+        post: Box::new(post),
+    }
+}
+
+fn resampling<I: Img>(img: &I, (width, height): (u32, u32)) -> Job {
+    let (src_width, src_height) = img.dimensions();
+    let mut pixels = Vec::with_capacity((src_width * src_height) as usize);
+    for y in 0..src_height {
+        pixels.extend_from_slice(img.row(y));
+    }
+    Job::Resize {
+        pixels,
+        src: (src_width, src_height),
+        dst: (width, height),
+        filter: match width < src_width || height < src_height {
+            true => Filter::Lanczos3,
+            false => Filter::CatmullRom,
+        },
+    }
+}
+
+// jobs waiting for the next batch, with where their result goes
+type Pending = Vec<(Job, mpsc::Sender<Result<Vec<u8>>>)>;
+
+static PENDING: Mutex<Pending> = Mutex::new(Vec::new());
+
+// held by the thread running a batch
+static RUNNING: Mutex<()> = Mutex::new(());
+
+// runs `job` in the next batch, which the calling thread runs when no other does
+fn submit(job: Job) -> Result<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    PENDING.lock().push((job, sender));
+
+    let _running = RUNNING.lock();
+    // the thread that ran the last batch took the job with it
+    if let Ok(result) = receiver.try_recv() {
+        return result;
+    }
+    let (jobs, senders): (Vec<Job>, Vec<_>) =
+        std::mem::take(&mut *PENDING.lock()).into_iter().unzip();
+    log::trace!("Sending {} pages to the GPU", jobs.len());
+    for (result, sender) in device::run(jobs).into_iter().zip(senders) {
+        let _ = sender.send(result);
+    }
+    receiver.recv()?
+}
+
+fn enabled(backend: Backend) -> bool {
+    if backend != Backend::Gpu {
+        return false;
+    }
+    if let Err(reason) = check() {
+        static WARNED: OnceLock<()> = OnceLock::new();
+        WARNED.get_or_init(|| log::warn!("{reason}, processing pages on the CPU"));
+        return false;
+    }
+    true
+}
+
+#[cfg(feature = "gpu")]
+mod device {
+    use anyhow::{anyhow, bail, Context, Result};
+    use wgpu::util::DeviceExt;
+
+    use std::sync::{mpsc, OnceLock};
+
+    use super::{Filter, Job};
+
+    const WORKGROUP_SIZE: u32 = 256;
+
+    // the most workgroups a dispatch can ask for in one dimension, the shaders loop
+    // over what's left
+    const MAX_WORKGROUPS: u32 = 65535;
+
+    // software adapters like llvmpipe are slower than the CPU path, but they're what
+    // the tests find on machines without a GPU
+    const ALLOW_SOFTWARE: bool = cfg!(test);
+
+    struct Gpu {
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        tones: Kernels,
+        resize: Kernels,
+    }
+
+    // the two passes of a shader and the buffers they're bound to
+    struct Kernels {
+        layout: wgpu::BindGroupLayout,
+        passes: [wgpu::ComputePipeline; 2],
+    }
+
+    // where the result of a job is copied to, and its length in bytes
+    struct Staged {
+        readback: wgpu::Buffer,
+        len: usize,
+    }
+
+    fn gpu() -> Result<&'static Gpu, String> {
+        static GPU: OnceLock<Result<Gpu, String>> = OnceLock::new();
+        GPU.get_or_init(|| Gpu::new().map_err(|e| format!("{e:#}")))
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    pub fn check() -> Result<(), String> {
+        gpu().map(|_| ())
+    }
+
+    /// The result of every job, run in one submission
+    pub fn run(jobs: Vec<Job>) -> Vec<Result<Vec<u8>>> {
+        match gpu() {
+            Ok(gpu) => gpu.run(&jobs),
+            Err(e) => jobs.iter().map(|_| Err(anyhow!(e.clone()))).collect(),
+        }
+    }
+
+    impl Gpu {
+        fn new() -> Result<Gpu> {
+            let instance = wgpu::Instance::default();
+            let adapter =
+                pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    ..Default::default()
+                }))
+                .context("No GPU found")?;
+            let info = adapter.get_info();
+            if info.device_type == wgpu::DeviceType::Cpu && !ALLOW_SOFTWARE {
+                bail!("Only a software GPU was found ({})", info.name);
+            }
+
+            let (device, queue) =
+                pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+                    label: Some("comically"),
+                    required_limits: adapter.limits(),
+                    ..Default::default()
+                }))
+                .with_context(|| format!("Failed to open {}", info.name))?;
+
+            let scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let tones = Kernels::new(
+                &device,
+                "tones",
+                include_str!("gpu/tones.wgsl"),
+                ["lookup", "stretch"],
+                &[false, true, false, false, true],
+            );
+            let resize = Kernels::new(
+                &device,
+                "resize",
+                include_str!("gpu/resize.wgsl"),
+                ["horizontal", "vertical"],
+                &[false, false, true, true],
+            );
+            if let Some(e) = pollster::block_on(scope.pop()) {
+                bail!("{} can't run the shaders: {e}", info.name);
+            }
+
+            log::debug!("Processing pages on {} ({:?})", info.name, info.backend);
+            Ok(Gpu {
+                device,
+                queue,
+                tones,
+                resize,
+            })
+        }
+
+        // records every job in one command buffer, then waits for all of them at once
+        fn run(&self, jobs: &[Job]) -> Vec<Result<Vec<u8>>> {
+            let scope = self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            let staged: Vec<Result<Staged>> = jobs
+                .iter()
+                .map(|job| self.record(&mut encoder, job))
+                .collect();
+            self.queue.submit([encoder.finish()]);
+            if let Some(e) = pollster::block_on(scope.pop()) {
+                return jobs.iter().map(|_| Err(anyhow!("{e}"))).collect();
+            }
+
+            let (sender, receiver) = mpsc::channel();
+            for (index, staged) in staged.iter().enumerate() {
+                if let Ok(staged) = staged {
+                    let sender = sender.clone();
+                    staged
+                        .readback
+                        .slice(..)
+                        .map_async(wgpu::MapMode::Read, move |result| {
+                            let _ = sender.send((index, result));
+                        });
+                }
+            }
+            if let Err(e) = self.device.poll(wgpu::PollType::wait_indefinitely()) {
+                return jobs
+                    .iter()
+                    .map(|_| Err(anyhow!("Failed to wait for the GPU: {e}")))
+                    .collect();
+            }
+            drop(sender);
+            let mut mapped: Vec<_> = jobs.iter().map(|_| None).collect();
+            for (index, result) in receiver {
+                mapped[index] = Some(result);
+            }
+
+            staged
+                .into_iter()
+                .zip(mapped)
+                .map(|(staged, mapped)| {
+                    let staged = staged?;
+                    mapped
+                        .context("The GPU dropped the page")?
+                        .context("Failed to read the page back")?;
+                    let mut output = staged
+                        .readback
+                        .slice(..)
+                        .get_mapped_range()
+                        .context("Failed to read the page back")?
+                        .to_vec();
+                    output.truncate(staged.len);
+                    Ok(output)
+                })
+                .collect()
+        }
+
+        // records the passes of `job` and the copy of its result to a buffer it can be
+        // read back from
+        fn record(&self, encoder: &mut wgpu::CommandEncoder, job: &Job) -> Result<Staged> {
+            match job {
+                Job::Tones { pixels, pre, post } => {
+                    let len = pixels.len() as u32;
+                    let pixels =
+                        self.storage("pixels", &padded(pixels), wgpu::BufferUsages::COPY_SRC)?;
+                    let pre = self.storage("pre", &lut(pre), wgpu::BufferUsages::empty())?;
+                    let post = self.storage("post", &lut(post), wgpu::BufferUsages::empty())?;
+                    let range =
+                        self.storage("range", &words(&[255, 0]), wgpu::BufferUsages::empty())?;
+                    let params = self.params(&[len, 0, 0, 0]);
+
+                    let words = len.div_ceil(4);
+                    self.dispatch(
+                        encoder,
+                        &self.tones,
+                        &[&params, &pixels, &pre, &post, &range],
+                        [words, words],
+                    );
+                    Ok(self.stage(encoder, &pixels, len as usize))
+                }
+                Job::Resize {
+                    pixels,
+                    src: (src_width, src_height),
+                    dst: (dst_width, dst_height),
+                    filter,
+                } => {
+                    let src = self.storage("src", &padded(pixels), wgpu::BufferUsages::empty())?;
+                    let mid_len = *dst_width as u64 * *src_height as u64;
+                    let mid = self.scratch("mid", mid_len * 4)?;
+                    let dst_len = *dst_width as u64 * *dst_height as u64;
+                    let dst = self.scratch("dst", dst_len.div_ceil(4) * 4)?;
+                    let filter = match filter {
+                        Filter::Lanczos3 => 0,
+                        Filter::CatmullRom => 1,
+                    };
+                    let params = self.params(&[
+                        *src_width,
+                        *src_height,
+                        *dst_width,
+                        *dst_height,
+                        filter,
+                        0,
+                        0,
+                        0,
+                    ]);
+
+                    self.dispatch(
+                        encoder,
+                        &self.resize,
+                        &[&params, &src, &mid, &dst],
+                        [mid_len as u32, dst_len.div_ceil(4) as u32],
+                    );
+                    Ok(self.stage(encoder, &dst, dst_len as usize))
+                }
+            }
+        }
+
+        // both passes of `kernels` over `invocations` pixels or words
+        fn dispatch(
+            &self,
+            encoder: &mut wgpu::CommandEncoder,
+            kernels: &Kernels,
+            buffers: &[&wgpu::Buffer],
+            invocations: [u32; 2],
+        ) {
+            let entries: Vec<wgpu::BindGroupEntry> = buffers
+                .iter()
+                .enumerate()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect();
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &kernels.layout,
+                entries: &entries,
+            });
+            for (pipeline, invocations) in kernels.passes.iter().zip(invocations) {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                let workgroups = invocations
+                    .div_ceil(WORKGROUP_SIZE)
+                    .clamp(1, MAX_WORKGROUPS);
+                pass.dispatch_workgroups(workgroups, 1, 1);
+            }
+        }
+
+        fn stage(
+            &self,
+            encoder: &mut wgpu::CommandEncoder,
+            output: &wgpu::Buffer,
+            len: usize,
+        ) -> Staged {
+            let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("readback"),
+                size: output.size(),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            encoder.copy_buffer_to_buffer(output, 0, &readback, 0, output.size());
+            Staged { readback, len }
+        }
+
+        fn params(&self, values: &[u32]) -> wgpu::Buffer {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("params"),
+                    contents: &words(values),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                })
+        }
+
+        fn storage(
+            &self,
+            label: &str,
+            contents: &[u8],
+            usage: wgpu::BufferUsages,
+        ) -> Result<wgpu::Buffer> {
+            self.fits(label, contents.len() as u64)?;
+            Ok(self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage: wgpu::BufferUsages::STORAGE | usage,
+                }))
+        }
+
+        fn scratch(&self, label: &str, size: u64) -> Result<wgpu::Buffer> {
+            self.fits(label, size)?;
+            Ok(self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }))
+        }
+
+        fn fits(&self, label: &str, size: u64) -> Result<()> {
+            let limits = self.device.limits();
+            let max = limits
+                .max_storage_buffer_binding_size
+                .min(limits.max_buffer_size);
+            if size > max {
+                bail!("The page needs a {size} bytes {label} buffer, the GPU takes up to {max}");
+            }
+            Ok(())
+        }
+    }
+
+    impl Kernels {
+        // `writable` has an entry per binding after the uniform params at 0, whether the
+        // shaders write to it
+        fn new(
+            device: &wgpu::Device,
+            label: &str,
+            source: &str,
+            entry_points: [&str; 2],
+            writable: &[bool],
+        ) -> Kernels {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            let entries: Vec<wgpu::BindGroupLayoutEntry> = writable
+                .iter()
+                .enumerate()
+                .map(|(binding, &writable)| wgpu::BindGroupLayoutEntry {
+                    binding: binding as u32,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: match binding {
+                            0 => wgpu::BufferBindingType::Uniform,
+                            _ => wgpu::BufferBindingType::Storage {
+                                read_only: !writable,
+                            },
+                        },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                })
+                .collect();
+            let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &entries,
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[Some(&layout)],
+                immediate_size: 0,
+            });
+            let passes = entry_points.map(|entry_point| {
+                device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&pipeline_layout),
+                    module: &module,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    cache: None,
+                })
+            });
+            Kernels { layout, passes }
+        }
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    // a level per word, WGSL has no bytes
+    fn lut(table: &[u8; 256]) -> Vec<u8> {
+        words(&table.map(u32::from))
+    }
+
+    // pixels packed four to a word, the last one padded
+    fn padded(pixels: &[u8]) -> Vec<u8> {
+        let mut padded = pixels.to_vec();
+        padded.resize(pixels.len().div_ceil(4) * 4, 0);
+        padded
+    }
+}
+
+#[cfg(not(feature = "gpu"))]
+mod device {
+    use super::Job;
+
+    pub fn check() -> Result<(), String> {
+        Err("comically was built without the `gpu` feature".into())
+    }
+
+    pub fn run(jobs: Vec<Job>) -> Vec<anyhow::Result<Vec<u8>>> {
+        jobs.iter()
+            .map(|_| {
+                Err(anyhow::anyhow!(
+                    "comically was built without the `gpu` feature"
+                ))
+            })
+            .collect()
+    }
+}
+
+// a gray paper border around a gradient that differs with `seed`
+#[cfg(test)]
+fn test_page(width: u32, height: u32, seed: u32) -> Image {
+    GrayImage::from_fn(width, height, |x, y| match x < 8 || y < 8 {
+        true => imageproc::image::Luma([220]),
+        false => imageproc::image::Luma([(30 + (x * 7 + y * 3 + seed * 11) % 150) as u8]),
+    })
+    .into()
+}
+
+#[test]
+fn cpu_backend_stays_on_the_cpu() {
+    let page = test_page(64, 48, 0);
+    let config = ComicConfig::default();
+    assert_eq!(config.backend, Backend::Cpu);
+    assert!(adjust(&page, &config).is_none());
+    assert!(resize(&page, (32, 24), Backend::Cpu).is_none());
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn batches_match_the_cpu() {
+    use rayon::prelude::*;
+
+    if let Err(reason) = check() {
+        eprintln!("skipping: {reason}");
+        return;
+    }
+    let on_cpu = |page: &Image, config: &ComicConfig| {
+        let (width, height) = page.dimensions();
+        let gray = GrayImage::from_raw(width, height, page.as_raw().to_vec()).unwrap();
+        let config = ComicConfig {
+            backend: Backend::Cpu,
+            ..config.clone()
+        };
+        super::prepare(imageproc::image::DynamicImage::ImageLuma8(gray), &config)
+    };
+
+    let configs = [
+        ComicConfig {
+            backend: Backend::Gpu,
+            ..ComicConfig::default()
+        },
+        ComicConfig {
+            backend: Backend::Gpu,
+            gamma: 1.0,
+            paper_white: true,
+            contrast: 30,
+            brightness: -20,
+            ..ComicConfig::default()
+        },
+    ];
+    let pages: Vec<Image> = (0..6)
+        .map(|i| test_page(301 + i * 17, 203 + i * 5, i))
+        .collect();
+    let sizes = [
+        ((120, 81), Filter::Lanczos3),
+        ((602, 406), Filter::CatmullRom),
+    ];
+
+    // pages of different sizes, tones and resizes in one submission
+    let mut jobs = Vec::new();
+    for (i, page) in pages.iter().enumerate() {
+        jobs.push(tones(page, &configs[i % 2]));
+        jobs.push(resampling(page, sizes[i % 2].0));
+    }
+    let results = device::run(jobs);
+    assert_eq!(results.len(), pages.len() * 2);
+    for (i, (page, results)) in pages.iter().zip(results.chunks(2)).enumerate() {
+        let adjusted = results[0].as_ref().unwrap();
+        assert_eq!(adjusted, on_cpu(page, &configs[i % 2]).as_raw(), "page {i}");
+
+        let ((width, height), filter) = sizes[i % 2];
+        let resampled = super::transform::resample(page, width, height, filter == Filter::Lanczos3);
+        let worst = results[1]
+            .as_ref()
+            .unwrap()
+            .iter()
+            .zip(resampled.as_raw())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max();
+        assert!(
+            worst <= Some(2),
+            "page {i} is off by {worst:?} with {filter:?}"
+        );
+    }
+
+    // pages the pipeline adjusts at the same time share batches
+    let adjusted: Vec<Image> = pages
+        .par_iter()
+        .map(|page| adjust(page, &configs[0]).unwrap())
+        .collect();
+    for (page, adjusted) in pages.iter().zip(&adjusted) {
+        assert_eq!(adjusted.as_raw(), on_cpu(page, &configs[0]).as_raw());
+    }
+}
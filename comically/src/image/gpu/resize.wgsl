@@ -0,0 +1,125 @@
+// Resampling like fast_image_resize's convolution: `horizontal` resizes the rows of
+// `src` into `mid`, then `vertical` resizes the columns of `mid` into `dst`. Pixels of
+// `src` and `dst` are packed four to a word.
+
+struct Params {
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    // 0 for Lanczos3, 1 for CatmullRom
+    kernel: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+const PI: f32 = 3.14159265358979;
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> src: array<u32>;
+@group(0) @binding(2) var<storage, read_write> mid: array<f32>;
+@group(0) @binding(3) var<storage, read_write> dst: array<u32>;
+
+fn radius() -> f32 {
+    return select(2.0, 3.0, params.kernel == 0u);
+}
+
+fn kernel(t: f32) -> f32 {
+    let x = abs(t);
+    if params.kernel == 0u {
+        if x < 1e-6 {
+            return 1.0;
+        }
+        if x >= 3.0 {
+            return 0.0;
+        }
+        let px = PI * x;
+        return 3.0 * sin(px) * sin(px / 3.0) / (px * px);
+    }
+    if x < 1.0 {
+        return (1.5 * x - 2.5) * x * x + 1.0;
+    }
+    if x < 2.0 {
+        return ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0;
+    }
+    return 0.0;
+}
+
+// the source samples output sample `i` of a line of `dst_len` is made of
+struct Window {
+    first: u32,
+    last: u32,
+    center: f32,
+    scale: f32,
+}
+
+fn window(i: u32, src_len: u32, dst_len: u32) -> Window {
+    let scale = f32(src_len) / f32(dst_len);
+    let filter_scale = max(scale, 1.0);
+    let support = radius() * filter_scale;
+    let center = (f32(i) + 0.5) * scale;
+    let first = u32(max(floor(center - support), 0.0));
+    let last = u32(min(ceil(center + support), f32(src_len)));
+    return Window(first, last, center, filter_scale);
+}
+
+fn weight(window: Window, j: u32) -> f32 {
+    return kernel((f32(j) + 0.5 - window.center) / window.scale);
+}
+
+fn src_pixel(x: u32, y: u32) -> f32 {
+    let index = y * params.src_width + x;
+    return f32((src[index / 4u] >> ((index % 4u) * 8u)) & 0xffu);
+}
+
+@compute @workgroup_size(256)
+fn horizontal(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let len = params.dst_width * params.src_height;
+    for (var i = id.x; i < len; i += groups.x * 256u) {
+        let x = i % params.dst_width;
+        let y = i / params.dst_width;
+        let w = window(x, params.src_width, params.dst_width);
+        var sum = 0.0;
+        var total = 0.0;
+        for (var j = w.first; j < w.last; j++) {
+            let k = weight(w, j);
+            sum += k * src_pixel(j, y);
+            total += k;
+        }
+        mid[i] = select(0.0, sum / total, total != 0.0);
+    }
+}
+
+@compute @workgroup_size(256)
+fn vertical(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let len = params.dst_width * params.dst_height;
+    let words = (len + 3u) / 4u;
+    for (var i = id.x; i < words; i += groups.x * 256u) {
+        var packed = 0u;
+        for (var b = 0u; b < 4u; b++) {
+            let index = i * 4u + b;
+            if index < len {
+                let x = index % params.dst_width;
+                let y = index / params.dst_width;
+                let w = window(y, params.src_height, params.dst_height);
+                var sum = 0.0;
+                var total = 0.0;
+                for (var j = w.first; j < w.last; j++) {
+                    let k = weight(w, j);
+                    sum += k * mid[j * params.dst_width + x];
+                    total += k;
+                }
+                let level = u32(clamp(round(select(0.0, sum / total, total != 0.0)), 0.0, 255.0));
+                packed |= level << (b * 8u);
+            }
+        }
+        dst[i] = packed;
+    }
+}
@@ -0,0 +1,87 @@
+// The tone adjustments of `image::adjust`: `lookup` maps every pixel through `pre`,
+// the paper white and gamma, and finds the darkest and brightest levels, then `stretch`
+// stretches them to 0-255 like imageproc's `stretch_contrast` and maps the result through
+// `post`, the contrast and brightness. Pixels are packed four to a word.
+
+struct Params {
+    len: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> pixels: array<u32>;
+@group(0) @binding(2) var<storage, read> pre: array<u32, 256>;
+@group(0) @binding(3) var<storage, read> post: array<u32, 256>;
+// darkest and brightest level, starting at 255 and 0
+@group(0) @binding(4) var<storage, read_write> range: array<atomic<u32>, 2>;
+
+var<workgroup> low: atomic<u32>;
+var<workgroup> high: atomic<u32>;
+
+@compute @workgroup_size(256)
+fn lookup(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    if local == 0u {
+        atomicStore(&low, 255u);
+        atomicStore(&high, 0u);
+    }
+    workgroupBarrier();
+
+    var darkest = 255u;
+    var brightest = 0u;
+    let words = (params.len + 3u) / 4u;
+    for (var i = id.x; i < words; i += groups.x * 256u) {
+        let word = pixels[i];
+        var mapped = 0u;
+        for (var b = 0u; b < 4u; b++) {
+            if i * 4u + b < params.len {
+                let level = pre[(word >> (b * 8u)) & 0xffu];
+                darkest = min(darkest, level);
+                brightest = max(brightest, level);
+                mapped |= level << (b * 8u);
+            }
+        }
+        pixels[i] = mapped;
+    }
+    atomicMin(&low, darkest);
+    atomicMax(&high, brightest);
+    workgroupBarrier();
+
+    if local == 0u {
+        atomicMin(&range[0], atomicLoad(&low));
+        atomicMax(&range[1], atomicLoad(&high));
+    }
+}
+
+@compute @workgroup_size(256)
+fn stretch(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let darkest = atomicLoad(&range[0]);
+    let brightest = atomicLoad(&range[1]);
+    let words = (params.len + 3u) / 4u;
+    for (var i = id.x; i < words; i += groups.x * 256u) {
+        let word = pixels[i];
+        var mapped = 0u;
+        for (var b = 0u; b < 4u; b++) {
+            var level = (word >> (b * 8u)) & 0xffu;
+            if brightest > darkest {
+                if level <= darkest {
+                    level = 0u;
+                } else if level >= brightest {
+                    level = 255u;
+                } else {
+                    level = (level - darkest) * 255u / (brightest - darkest);
+                }
+            }
+            mapped |= post[level] << (b * 8u);
+        }
+        pixels[i] = mapped;
+    }
+}
@@ -0,0 +1,128 @@
+//! Per-page transforms registered by library users, for processing the pipeline has no
+//! setting for (custom watermarks, ML cleanup) without forking the crate.
+
+use std::path::Path;
+use std::sync::Arc;
+
+pub use imageproc::image::GrayImage;
+
+use crate::comic::{ComicConfig, PagePart};
+
+/// Where in the pipeline a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// After the tone adjustments, on the whole page before it's cropped and resized
+    PreCrop,
+    /// On each part of the page once cropped, split, resized and rotated for the device
+    PostResize,
+    /// On each part right before it's encoded, after page stamps and watermarks
+    PreEncode,
+}
+
+/// The page a hook is running on
+#[derive(Debug, Clone, Copy)]
+pub struct PageContext<'a> {
+    /// Path of the page in the source archive, empty for pages that didn't come from one
+    pub file: &'a Path,
+    /// 1-based position of the page in the comic, 0 when unknown
+    pub page: usize,
+    /// Which part of the page this is, `None` before it's split
+    pub part: Option<PagePart>,
+    pub config: &'a ComicConfig,
+}
+
+type Hook = dyn Fn(GrayImage, &PageContext) -> GrayImage + Send + Sync;
+
+/// The hooks of a config, run in the order they were added. Pages converted with hooks
+/// skip the page cache, which can't tell what a hook does.
+#[derive(Clone, Default)]
+pub struct Hooks(Vec<(Stage, Arc<Hook>)>);
+
+impl Hooks {
+    pub fn add<F>(&mut self, stage: Stage, hook: F) -> &mut Self
+    where
+        F: Fn(GrayImage, &PageContext) -> GrayImage + Send + Sync + 'static,
+    {
+        self.0.push((stage, Arc::new(hook)));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether any hook runs at `stage`
+    pub fn runs(&self, stage: Stage) -> bool {
+        self.0.iter().any(|(hook_stage, _)| *hook_stage == stage)
+    }
+
+    /// `img` after every hook of `stage`
+    pub fn run(&self, stage: Stage, img: GrayImage, context: &PageContext) -> GrayImage {
+        self.0
+            .iter()
+            .filter(|(hook_stage, _)| *hook_stage == stage)
+            .fold(img, |img, (_, hook)| hook(img, context))
+    }
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|(stage, _)| stage))
+            .finish()
+    }
+}
+
+// the same hooks, closures can't be compared otherwise
+impl PartialEq for Hooks {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(&other.0)
+                .all(|((a, a_hook), (b, b_hook))| a == b && Arc::ptr_eq(a_hook, b_hook))
+    }
+}
+
+#[test]
+fn hooks_run_at_their_stage() {
+    use std::sync::Mutex;
+
+    use crate::archive::ArchiveFile;
+
+    let files: Vec<ArchiveFile> = (1..=2)
+        .map(|i| ArchiveFile {
+            file_name: format!("{i:03}.jpg").into(),
+            data: crate::testing::sample_page(60, 80, i),
+        })
+        .collect();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut config = ComicConfig {
+        output_format: crate::OutputFormat::Cbz,
+        image_format: crate::ImageFormat::Png {
+            compression: crate::PngCompression::Fast,
+        },
+        ..ComicConfig::default()
+    };
+    let pages = seen.clone();
+    config
+        .hooks
+        .add(Stage::PreCrop, move |img, context| {
+            assert_eq!(context.part, None);
+            pages.lock().unwrap().push(context.page);
+            img
+        })
+        .add(Stage::PreEncode, |img, context| {
+            assert_eq!(context.part, Some(PagePart::Whole));
+            GrayImage::from_pixel(img.width(), img.height(), imageproc::image::Luma([7]))
+        });
+
+    let images = crate::image::process_batch(files, &config).unwrap();
+    let mut seen = seen.lock().unwrap().clone();
+    seen.sort_unstable();
+    assert_eq!(seen, [1, 2]);
+    let page = imageproc::image::load_from_memory(&images[0].data).unwrap();
+    assert!(page.into_luma8().pixels().all(|pixel| pixel.0 == [7]));
+    assert_eq!(config.clone(), config);
+}
@@ -1,18 +1,47 @@
 //! Image processing pipeline for manga/comic optimization
 
+pub mod color;
 pub mod decode;
 pub mod encode;
+pub mod filter;
+pub mod governor;
+pub mod gpu;
+pub mod hooks;
+pub mod ocr;
+pub mod overlay;
+pub mod reuse;
+pub mod stamp;
+pub mod stats;
+pub mod strip;
+pub mod title_page;
 pub mod transform;
+pub mod upscale;
 
 // Re-export public API
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use arrayvec::ArrayVec;
 pub use encode::{compress_to_jpeg, compress_to_png, compress_to_webp, PngCompression};
 use imageproc::image::DynamicImage;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelBridge,
+    ParallelIterator,
+};
+pub use stats::{analyze, PageStats};
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::archive::ArchiveFile;
-use crate::comic::{ComicConfig, ProcessedImage};
+use crate::comic::{
+    AnimatedPages, ComicConfig, GammaCurve, PagePart, PlaceholderPages, ProcessedImage, Rotation,
+    SmallCopy,
+};
+
+// gray levels e-ink screens show, what pages are dithered to with `ComicConfig::dither`
+const E_INK_LEVELS: u8 = 16;
 
 #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum ImageFormat {
@@ -69,6 +98,35 @@ impl ImageFormat {
     }
 }
 
+/// Quality band for JPEG/WebP pages picked from each page's complexity, so flat
+/// dialogue pages are encoded towards `min` and detailed spreads towards `max`
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AdaptiveQuality {
+    pub min: u8,
+    pub max: u8,
+}
+
+impl AdaptiveQuality {
+    /// `complexity` is in 0.0-1.0, see [`stats::complexity`]
+    pub fn quality(&self, complexity: f32) -> u8 {
+        let span = self.max.saturating_sub(self.min) as f32;
+        self.min + (span * complexity.clamp(0.0, 1.0)).round() as u8
+    }
+
+    /// Format for a page, with the quality replaced when the format has one
+    pub fn apply(&self, format: ImageFormat, img: &DynamicImage) -> ImageFormat {
+        match format {
+            ImageFormat::Jpeg { .. } => ImageFormat::Jpeg {
+                quality: self.quality(stats::complexity(img)),
+            },
+            ImageFormat::WebP { .. } => ImageFormat::WebP {
+                quality: self.quality(stats::complexity(img)),
+            },
+            ImageFormat::Png { .. } => format,
+        }
+    }
+}
+
 /// Stack-allocated container for 1-3 images (no heap allocation)
 pub struct Split<T>(ArrayVec<T, 3>);
 
@@ -93,6 +151,16 @@ impl<T> Split<T> {
         Split(ArrayVec::from([t1, t2, t3]))
     }
 
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     #[inline(always)]
     pub fn map<U, F: FnMut(T) -> U>(self, f: F) -> Split<U> {
         Split(self.0.into_iter().map(f).collect())
@@ -109,6 +177,29 @@ impl<T> IntoIterator for Split<T> {
     }
 }
 
+/// How long a page took in each step, to tell the pathological pages of a batch (giant
+/// PNGs, 60MP scans) from the rest. Steps the cache skipped took no time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageTiming {
+    pub file: PathBuf,
+    pub decode: Duration,
+    /// Everything between decoding and encoding, for every target
+    pub transform: Duration,
+    pub encode: Duration,
+    /// Every target came from the page cache
+    pub cached: bool,
+    /// Every target copied the page through as it was, see [`reuse`]
+    pub reused: bool,
+    /// Why the page was left out of the output, e.g. an empty file
+    pub skipped: Option<String>,
+}
+
+impl PageTiming {
+    pub fn total(&self) -> Duration {
+        self.decode + self.transform + self.encode
+    }
+}
+
 #[inline(always)]
 pub fn process_batch(files: Vec<ArchiveFile>, config: &ComicConfig) -> Result<Vec<ProcessedImage>> {
     process_batch_with_progress(files, config, || {})
@@ -122,56 +213,1586 @@ pub fn process_batch_with_progress<F>(
 where
     F: Fn() + Send + Sync,
 {
-    log::info!("Processing {} archive images", files.len());
+    let on_progress = |_: &PageTiming| on_progress();
+    let mut images = process_batch_targets(files, std::slice::from_ref(config), on_progress)?;
+    Ok(images.pop().unwrap_or_default())
+}
 
-    // Parallel stage: decode + process + encode
-    // This eliminates intermediate Vec allocation and keeps data hot in cache
-    let mut images: Vec<ProcessedImage> = files
+/// Page `index` of `pages` as it would be in the comic's output: processed like the rest
+/// of the comic, stamps, watermarks, filters, hooks and encoding included, so previews
+/// look like what's converted. The other pages only number it, the first part of a split
+/// page is returned, and the page isn't cached.
+pub fn preview(
+    pages: &[ArchiveFile],
+    index: usize,
+    config: &ComicConfig,
+) -> Result<ProcessedImage> {
+    let page = pages.get(index).context("No such page")?;
+    let names: Vec<PathBuf> = pages.iter().map(|page| page.file_name.clone()).collect();
+    let config = ComicConfig {
+        page_cache: false,
+        long_strip: None,
+        ..config.clone()
+    };
+    let processed = Pages::new(&names, std::slice::from_ref(&config), |_| {})?
+        .context("No config")?
+        .process(page)?;
+    processed
+        .into_iter()
+        .flatten()
+        .next()
+        .context("The page is left out of the output")
+}
+
+/// Processes the pages once for several targets, returning the images of each config in order.
+///
+/// Decoding is shared by all the configs. They're grouped by their tones (paper white,
+/// gamma curve, gamma, contrast and brightness), and each page is adjusted once per
+/// group, so e-ink and LCD targets each get their own tuning. Beyond the tones, the
+/// configs must only differ in what [`render`] uses: device, cropping, splitting,
+/// margins, rotation, dithering, color and encoding. The page filter and translations
+/// come from the first config. `on_progress` is called with the timing of each page as
+/// it's done. Multi-page TIFFs are expanded first, see [`expand_pages`].
+pub fn process_batch_targets<F>(
+    files: Vec<ArchiveFile>,
+    configs: &[ComicConfig],
+    on_progress: F,
+) -> Result<Vec<Vec<ProcessedImage>>>
+where
+    F: Fn(&PageTiming) + Send + Sync,
+{
+    let files: Vec<ArchiveFile> = files
+        .into_par_iter()
+        .map(expand_pages)
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    let names: Vec<PathBuf> = files.iter().map(|file| file.file_name.clone()).collect();
+    let Some(pages) = Pages::new(&names, configs, on_progress)? else {
+        return Ok(Vec::new());
+    };
+
+    // The largest pages first, usually spreads that take a few times longer than the
+    // rest: started last, they'd keep a thread busy long after the others ran out of work.
+    // The output is sorted by name afterwards either way.
+    let mut order: Vec<&ArchiveFile> = files.iter().collect();
+    order.sort_by_key(|file| std::cmp::Reverse(file.data.len()));
+
+    // Parallel stage: decode + process + encode, one task per page for idle threads to
+    // steal. This eliminates intermediate Vec allocation and keeps data hot in cache
+    let processed = order
         .par_iter()
-        .map(|archive_file| {
-            // Decode image
-            let img = decode::decode(&archive_file.data)?;
+        .with_max_len(1)
+        .map(|file| pages.process(file))
+        .collect::<Result<_>>()?;
+    pages.finish(processed)
+}
 
-            // Process image (transform, crop, resize, split)
-            let processed_images = process(img, config);
+/// Processes pages as they're read, like [`process_batch_targets`] does once they all are.
+///
+/// `files` is read on the calling thread while the pool decodes, renders and encodes
+/// the pages read so far, so reading the archive and processing overlap. A few pages
+/// per thread wait in between at most: a slow disk doesn't leave the pool idle and a
+/// slow pool doesn't pile up pages in memory. `names` are the paths of every page
+/// `files` yields, see [`ArchiveIter::images`](crate::archive::ArchiveIter::images),
+/// to number the pages before they're read: the pages of multi-page TIFFs, only known
+/// once they're read, go unnumbered, which [`process_batch_targets`] avoids.
+pub fn process_stream_targets<I, F>(
+    files: I,
+    names: &[PathBuf],
+    configs: &[ComicConfig],
+    on_progress: F,
+) -> Result<Vec<Vec<ProcessedImage>>>
+where
+    I: IntoIterator<Item = ArchiveFile>,
+    F: Fn(&PageTiming) + Send + Sync,
+{
+    let Some(pages) = Pages::new(names, configs, on_progress)? else {
+        return Ok(Vec::new());
+    };
 
-            let mut encoded_images = ArrayVec::<ProcessedImage, 3>::new();
+    let (file_tx, file_rx) = mpsc::sync_channel(rayon::current_num_threads() * 2);
+    let processed = thread::scope(|scope| {
+        let pool = scope.spawn(|| {
+            file_rx
+                .into_iter()
+                .par_bridge()
+                .map(|file| {
+                    let files = expand_pages(file)?;
+                    files.iter().map(|file| pages.process(file)).collect()
+                })
+                .collect::<Result<Vec<Vec<_>>>>()
+        });
+        // archive readers aren't always `Send`, so they stay on this thread
+        for file in files {
+            // the pool stopped at a page it couldn't process
+            if file_tx.send(file).is_err() {
+                break;
+            }
+        }
+        drop(file_tx);
+        pool.join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })?;
+    pages.finish(processed.into_iter().flatten().collect())
+}
 
-            // Encode immediately while data is hot in cache
-            for (i, img) in processed_images.into_iter().enumerate() {
-                let processed =
-                    encode::encode_image_part(archive_file, &img, i, config.image_format);
-                encoded_images.push(processed);
+/// Processes the pages on the pool, yielding their images in the order of the comic as
+/// soon as they and the pages before them are done, for storing pages as they come
+/// instead of once the whole comic is.
+///
+/// Pages are started in order, a page that takes longer than the ones after it holds
+/// them back until it's done. The first page that fails to process ends the iterator
+/// with its error. Pages aren't stacked into long strips, which need every page.
+pub fn process_iter(files: Vec<ArchiveFile>, config: &ComicConfig) -> ProcessedPages {
+    let config = ComicConfig {
+        long_strip: None,
+        ..config.clone()
+    };
+    let (results, pages) = ProcessedPages::channel();
+    thread::spawn(move || {
+        let files: Result<Vec<ArchiveFile>> = files
+            .into_par_iter()
+            .map(expand_pages)
+            .collect::<Result<Vec<_>>>()
+            .map(|files| files.into_iter().flatten().collect());
+        let mut files = match files {
+            Ok(files) => files,
+            Err(e) => {
+                let _ = results.send((0, Err(e)));
+                return;
             }
+        };
+        // the order `Pages::finish` sorts the images in
+        files.sort_by_cached_key(|file| {
+            encode::part_file_name_of(&file.file_name, 0, config.image_format)
+        });
+        let names: Vec<PathBuf> = files.iter().map(|file| file.file_name.clone()).collect();
+        let configs = [config];
+        let pages = match Pages::new(&names, &configs, |_: &PageTiming| {}) {
+            Ok(Some(pages)) => pages,
+            Ok(None) => return,
+            Err(e) => {
+                let _ = results.send((0, Err(e)));
+                return;
+            }
+        };
+        // stops once nobody is reading the pages
+        let _ = files.iter().enumerate().par_bridge().try_for_each_with(
+            results,
+            |results, (index, file)| {
+                let images = pages
+                    .process(file)
+                    .map(|mut targets| targets.pop().map(Vec::from_iter).unwrap_or_default());
+                results.send((index, images))
+            },
+        );
+        pages.trim_cache();
+    });
+    pages
+}
+
+// a page's images by its position in the comic
+type Processed = (usize, Result<Vec<ProcessedImage>>);
+
+/// The images of [`process_iter`], numbered and without duplicate names like
+/// [`process_batch`] returns them
+pub struct ProcessedPages {
+    results: mpsc::Receiver<Processed>,
+    // pages done before the ones ahead of them
+    done: BTreeMap<usize, Result<Vec<ProcessedImage>>>,
+    next: usize,
+    ready: std::vec::IntoIter<ProcessedImage>,
+    // name and source of the image yielded last
+    last: Option<(String, PathBuf)>,
+    page: usize,
+    failed: bool,
+}
+
+impl ProcessedPages {
+    fn channel() -> (mpsc::Sender<Processed>, Self) {
+        let (results, received) = mpsc::channel();
+        let pages = Self {
+            results: received,
+            done: BTreeMap::new(),
+            next: 0,
+            ready: Vec::new().into_iter(),
+            last: None,
+            page: 0,
+            failed: false,
+        };
+        (results, pages)
+    }
+
+    /// Yields `error` and nothing else, for comics that couldn't be read
+    pub fn failed(error: anyhow::Error) -> Self {
+        let (results, pages) = Self::channel();
+        let _ = results.send((0, Err(error)));
+        pages
+    }
+}
 
-            // Report progress after processing this file
-            on_progress();
+impl Iterator for ProcessedPages {
+    type Item = Result<ProcessedImage>;
 
-            Ok(encoded_images)
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(mut image) = self.ready.next() {
+                let last = self.last.as_ref();
+                if last.is_some_and(|(name, _)| *name == image.file_name) {
+                    continue;
+                }
+                // parts of a page share its number, see `number_pages`
+                if last.is_none_or(|(_, source)| *source != image.source) {
+                    self.page += 1;
+                }
+                image.page = self.page;
+                self.last = Some((image.file_name.clone(), image.source.clone()));
+                return Some(Ok(image));
+            }
+            if self.failed {
+                return None;
+            }
+            let Some(page) = self.done.remove(&self.next) else {
+                // every page was sent once the sender is gone
+                let (index, page) = self.results.recv().ok()?;
+                self.done.insert(index, page);
+                continue;
+            };
+            self.next += 1;
+            match page {
+                Ok(images) => self.ready = images.into_iter(),
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// The pages of `file`: a multi-page TIFF becomes a lossless PNG per page, in order,
+/// named after the TIFF and the page (`scan.tif` gives `scan-001.png`, `scan-002.png`...),
+/// and other files are a page of their own
+pub fn expand_pages(file: ArchiveFile) -> Result<Vec<ArchiveFile>> {
+    let Some(pages) = decode::tiff_pages(&file.data) else {
+        return Ok(vec![file]);
+    };
+    log::info!("{}: expanding {pages} pages", file.file_name.display());
+    let stem = file.file_stem().to_string_lossy();
+    let format = ImageFormat::Png {
+        compression: PngCompression::Fast,
+    };
+    (0..pages)
+        .map(|index| {
+            let img = decode::tiff_page(&file.data, index).with_context(|| {
+                format!(
+                    "Failed to decode page {} of {}",
+                    index + 1,
+                    file.file_name.display()
+                )
+            })?;
+            Ok(ArchiveFile {
+                file_name: file.parent().join(format!("{stem}-{:03}.png", index + 1)),
+                data: encode::encode_image(&img, &format),
+            })
         })
-        .collect::<Result<Vec<_>>>()?
-        .into_iter()
-        .flatten()
+        .collect()
+}
+
+// what the pages of a conversion share
+struct Pages<'a, F> {
+    configs: &'a [ComicConfig],
+    cache: Option<crate::cache::Cache>,
+    watermarks: Vec<Option<imageproc::image::GrayAlphaImage>>,
+    settings_keys: Vec<String>,
+    // for each target, the first target with the same tones, whose adjusted page it uses
+    tone_groups: Vec<usize>,
+    translations: Option<&'a overlay::Translations>,
+    font: Option<ab_glyph::FontVec>,
+    font_key: Option<String>,
+    filter: Option<filter::Runner<'a>>,
+    limit: governor::Limit,
+    // 1-based, for the pages whose output depends on where they land in the comic
+    numbers: Option<HashMap<&'a Path, usize>>,
+    // only read by spans, which compile to nothing without the `instrument` feature
+    #[cfg_attr(not(feature = "instrument"), allow(dead_code))]
+    span: crate::trace::Span,
+    on_progress: F,
+}
+
+impl<'a, F: Fn(&PageTiming) + Send + Sync> Pages<'a, F> {
+    fn new(
+        names: &'a [PathBuf],
+        configs: &'a [ComicConfig],
+        on_progress: F,
+    ) -> Result<Option<Self>> {
+        let Some(base) = configs.first() else {
+            return Ok(None);
+        };
+        // entered on this thread only, pages name it as their parent from the pool
+        let span = crate::trace::span!("process", pages = names.len(), targets = configs.len());
+        let _span = span.clone().entered();
+        log::info!(
+            "Processing {} archive images for {} target(s)",
+            names.len(),
+            configs.len()
+        );
+
+        // the cache can't tell what a hook does
+        let hooked = configs.iter().any(|config| !config.hooks.is_empty());
+        let cache = (base.page_cache && !hooked)
+            .then(crate::cache::Cache::open_default)
+            .flatten();
+        let watermarks: Vec<Option<imageproc::image::GrayAlphaImage>> = configs
+            .iter()
+            .map(|config| {
+                let watermark = config.watermark.as_ref()?;
+                Some(stamp::load_watermark(&watermark.path))
+            })
+            .map(Option::transpose)
+            .collect::<Result<_>>()?;
+        // the watermark file can change under the same path
+        let settings_keys: Vec<String> = configs
+            .iter()
+            .zip(&watermarks)
+            .map(|(config, mark)| {
+                let key = crate::cache::Cache::settings_key(config);
+                match mark {
+                    Some(mark) => format!("{key}-{}", crate::output::sha256(mark.as_raw())),
+                    None => key,
+                }
+            })
+            .collect();
+        // targets for devices tuned apart, like e-ink and LCD ones, are adjusted apart
+        let tone_groups = configs
+            .iter()
+            .map(|config| {
+                configs
+                    .iter()
+                    .position(|other| same_tones(other, config))
+                    .unwrap_or(0)
+            })
+            .collect();
+        // translations are lettered on the original page, before any target crops or resizes it
+        let overlay = base.translation_overlay.as_ref();
+        let translations = overlay.and(base.translations.as_ref());
+        let font = overlay
+            .filter(|_| translations.is_some())
+            .and_then(|overlay| overlay.font.as_deref())
+            .map(overlay::load_font)
+            .transpose()?;
+        let font_key = font
+            .as_ref()
+            .map(|font| crate::output::sha256(ab_glyph::Font::font_data(font)));
+
+        // run once per page and set of tones, before the targets crop and resize it
+        let filter = base.page_filter.as_ref().map(filter::Runner::new);
+
+        let numbered = translations.is_some()
+            || hooked
+            || base.auto_rotate_cover
+            || configs.iter().any(|config| {
+                config.page_stamp.is_some()
+                    || config
+                        .watermark
+                        .as_ref()
+                        .is_some_and(|watermark| watermark.cover_only)
+            });
+        let numbers = numbered.then(|| page_numbers(names, base.image_format));
+
+        Ok(Some(Self {
+            configs,
+            cache,
+            watermarks,
+            settings_keys,
+            tone_groups,
+            translations,
+            font,
+            font_key,
+            filter,
+            limit: governor::Limit::new(base.concurrency),
+            numbers,
+            span,
+            on_progress,
+        }))
+    }
+
+    // the images of a page for each target
+    fn process(&self, archive_file: &ArchiveFile) -> Result<Vec<ArrayVec<ProcessedImage, 3>>> {
+        let _page = crate::trace::span!(
+            parent: &self.span,
+            "page",
+            file = %archive_file.file_name.display(),
+            bytes = archive_file.data.len(),
+        )
+        .entered();
+        crate::nice::pause();
+        let base = &self.configs[0];
+        let page = self.numbers.as_ref().map_or(0, |numbers| {
+            numbers
+                .get(archive_file.file_name.as_path())
+                .copied()
+                .unwrap_or(0)
+        });
+
+        // left out rather than failing to decode or coming out blank
+        if let Some(placeholder) = decode::placeholder(&archive_file.data) {
+            if base.placeholder_pages == PlaceholderPages::Fail {
+                bail!("{}: {placeholder}", archive_file.file_name.display());
+            }
+            return Ok(self.skip(archive_file, format!("skipped {placeholder}")));
+        }
+
+        // only counting the frames tells animated pages apart
+        let frames = match base.animated_pages {
+            AnimatedPages::FirstFrame => None,
+            AnimatedPages::MiddleFrame | AnimatedPages::Skip => {
+                decode::animation_frames(&archive_file.data)
+            }
+        };
+        if let (Some(frames), AnimatedPages::Skip) = (frames, base.animated_pages) {
+            let reason = format!("skipped animated page ({frames} frames)");
+            return Ok(self.skip(archive_file, reason));
+        }
+
+        let boxes = self
+            .translations
+            .map_or(&[][..], |translations| translations.boxes(page));
+        // stamped pages depend on where the page lands in the comic
+        let page_keys: Vec<String> = self
+            .settings_keys
+            .iter()
+            .map(|key| match self.numbers {
+                Some(_) => format!("{key}-{page}"),
+                None => key.clone(),
+            })
+            .map(|key| match boxes {
+                [] => key,
+                boxes => {
+                    let text = serde_json::to_vec(boxes).unwrap_or_default();
+                    let font = self.font_key.as_deref().unwrap_or("bitmap");
+                    format!("{key}-{}-{font}", crate::output::sha256(&text))
+                }
+            })
+            .collect();
+
+        // lettered, turned or animated pages are decoded either way
+        let reusable = boxes.is_empty()
+            && frames.is_none()
+            && !(base.auto_rotate_cover && page == 1)
+            && self.configs.iter().any(reuse::applies);
+        let mut reused = 0;
+        let mut targets: Vec<Option<ArrayVec<ProcessedImage, 3>>> = page_keys
+            .iter()
+            .zip(self.configs)
+            .map(|(key, config)| {
+                if let Some(page) = reusable
+                    .then(|| reuse::reuse(archive_file, config))
+                    .flatten()
+                {
+                    reused += 1;
+                    return Some(std::iter::once(page).collect());
+                }
+                let cached = self.cache.as_ref()?.get(archive_file, key)?;
+                Some(cached.into_iter().collect())
+            })
+            .collect();
+
+        let done = targets.iter().all(Option::is_some);
+        let mut timing = PageTiming {
+            file: archive_file.file_name.clone(),
+            cached: done && reused == 0,
+            reused: reused == self.configs.len(),
+            ..PageTiming::default()
+        };
+        if reused > 0 {
+            log::debug!(
+                "{}: copied through for {reused} target(s)",
+                archive_file.file_name.display()
+            );
+        }
+
+        // Decode and adjust tones only if a target missed the cache
+        if !done {
+            // held until the page is encoded for every target
+            let _permit = self.limit.acquire(&archive_file.data);
+            let start = Instant::now();
+            // the color of color pages is only split off for the targets that keep it
+            let keep_color = targets
+                .iter()
+                .zip(self.configs)
+                .any(|(target, config)| target.is_none() && config.keep_color);
+            let gray = |img: DynamicImage| (img.into_luma8().into(), None);
+            let (img, mut chroma) = match frames {
+                Some(frames) => {
+                    decode::animation_frame(&archive_file.data, frames / 2).map(|img| {
+                        if keep_color {
+                            color::split(img)
+                        } else {
+                            gray(img)
+                        }
+                    })
+                }
+                None if keep_color => decode::decode(&archive_file.data).map(color::split),
+                None => decode::decode_luma(&archive_file.data).map(|img| (img, None)),
+            }
+            .with_context(|| format!("Failed to decode {}", archive_file.file_name.display()))?;
+            timing.decode = start.elapsed();
+            let start = Instant::now();
+            let original_dimensions = img.dimensions();
+            let img = match boxes {
+                [] => img,
+                boxes => {
+                    let mut gray = imageproc::image::GrayImage::from(img);
+                    overlay::draw(&mut gray, boxes, self.font.as_ref());
+                    // the boxes are lettered in black on white
+                    if let Some(chroma) = &mut chroma {
+                        for text in boxes {
+                            chroma.clear(text.x, text.y, text.width, text.height);
+                        }
+                    }
+                    gray.into()
+                }
+            };
+            // after lettering, translations are placed on the page as delivered
+            let cover = base.auto_rotate_cover && page == 1;
+            let img = match cover.then(|| crate::cover::sideways(&img)).flatten() {
+                Some(rotation) => {
+                    log::info!(
+                        "{}: turning the sideways cover {rotation:?}",
+                        archive_file.file_name.display()
+                    );
+                    chroma = chroma.map(|chroma| chroma.rotate(rotation));
+                    transform::rotate_output(img, rotation)
+                }
+                None => img,
+            };
+            // adjusted once for each set of tones the targets left to render have
+            let mut groups: Vec<usize> = targets
+                .iter()
+                .zip(&self.tone_groups)
+                .filter(|(target, _)| target.is_none())
+                .map(|(_, &group)| group)
+                .collect();
+            groups.sort_unstable();
+            groups.dedup();
+            let mut adjusted: Vec<Option<transform::Image>> = vec![None; self.configs.len()];
+            let mut source = Some(img);
+            for (i, &group) in groups.iter().enumerate() {
+                // the last group takes the page instead of a copy
+                let img = match i + 1 == groups.len() {
+                    true => source.take().expect("adjusted once per group"),
+                    false => source.clone().expect("adjusted once per group"),
+                };
+                let img = adjust(img, &self.configs[group]);
+                adjusted[group] = Some(match &self.filter {
+                    Some(filter) => filter.run(&img, &archive_file.file_name)?,
+                    None => img,
+                });
+            }
+            // read once for the targets that reflow text pages
+            let text = self
+                .configs
+                .iter()
+                .zip(&self.tone_groups)
+                .find(|(config, &group)| config.text_pages() && adjusted[group].is_some())
+                .and_then(|(config, &group)| ocr::text(adjusted[group].as_ref()?, config));
+
+            let targets_iter = targets.iter_mut().zip(self.configs).zip(&page_keys);
+            let targets_iter = targets_iter.zip(&self.watermarks).zip(&self.tone_groups);
+            for ((((target, config), key), mark), &group) in targets_iter {
+                let (None, Some(img)) = (&target, &adjusted[group]) else {
+                    continue;
+                };
+
+                // Crop, resize and split for this target
+                let context = hooks::PageContext {
+                    file: &archive_file.file_name,
+                    page,
+                    part: None,
+                    config,
+                };
+                let chroma = chroma.as_ref().filter(|_| config.keep_color);
+                let rendered = render_color(img, chroma, &context);
+
+                let mut encoded_images = ArrayVec::<ProcessedImage, 3>::new();
+                // the text of a page split in parts can't be told apart
+                let text = text
+                    .as_ref()
+                    .filter(|_| config.text_pages() && rendered.parts.len() == 1);
+
+                let mut colors = rendered.chroma.map(Split::into_iter);
+
+                // Encode immediately while data is hot in cache
+                for (i, (mut img, part)) in rendered.parts.into_iter().enumerate() {
+                    let chroma = colors.as_mut().and_then(Iterator::next);
+                    if let (Some(stamp), Some(gray)) = (&config.page_stamp, img.as_mut_luma8()) {
+                        let chapter = archive_file
+                            .parent()
+                            .file_name()
+                            .map(|name| name.to_string_lossy());
+                        let text = stamp::label(stamp, page, chapter.as_deref());
+                        stamp::stamp(gray, &text, stamp);
+                    }
+                    if let (Some(watermark), Some(mark), Some(gray)) =
+                        (&config.watermark, mark, img.as_mut_luma8())
+                    {
+                        if !watermark.cover_only || (page == 1 && i == 0) {
+                            stamp::watermark(gray, mark, watermark);
+                        }
+                    }
+
+                    if config.hooks.runs(hooks::Stage::PreEncode) {
+                        let context = hooks::PageContext {
+                            part: Some(part),
+                            ..context
+                        };
+                        let gray = img.into_luma8();
+                        img = config
+                            .hooks
+                            .run(hooks::Stage::PreEncode, gray, &context)
+                            .into();
+                    }
+                    // unless a hook resized the part
+                    let size = (img.width(), img.height());
+                    if let Some(chroma) = chroma.filter(|chroma| chroma.dimensions() == size) {
+                        img = DynamicImage::ImageRgb8(color::merge(&img.to_luma8(), &chroma));
+                    }
+
+                    let format = match (config.long_strip(), config.adaptive_quality) {
+                        (Some(_), _) => strip::PAGE_FORMAT,
+                        (None, Some(adaptive)) => adaptive.apply(config.image_format, &img),
+                        (None, None) => config.image_format,
+                    };
+                    let encoding = Instant::now();
+                    let mut processed = encode::encode_image_part(archive_file, &img, i, format);
+                    processed.small = config
+                        .small_copy()
+                        .and_then(|longest| small_copy(&img, longest, format));
+                    timing.encode += encoding.elapsed();
+                    processed.part = part;
+                    processed.original_dimensions = original_dimensions;
+                    processed.cropped = rendered.crop.is_some();
+                    processed.text = text.cloned();
+                    encoded_images.push(processed);
+                }
+
+                if let Some(cache) = &self.cache {
+                    cache.put(archive_file, key, &encoded_images);
+                }
+                *target = Some(encoded_images);
+            }
+            timing.transform = start.elapsed().saturating_sub(timing.encode);
+            log::debug!(
+                "{}: decoded in {:.1?}, transformed in {:.1?}, encoded in {:.1?}",
+                archive_file.file_name.display(),
+                timing.decode,
+                timing.transform,
+                timing.encode
+            );
+        }
+
+        // Report progress after processing this file
+        (self.on_progress)(&timing);
+
+        Ok(targets.into_iter().flatten().collect())
+    }
+
+    // no images for any target, reporting why
+    fn skip(&self, archive_file: &ArchiveFile, reason: String) -> Vec<ArrayVec<ProcessedImage, 3>> {
+        log::warn!("{}: {reason}", archive_file.file_name.display());
+        (self.on_progress)(&PageTiming {
+            file: archive_file.file_name.clone(),
+            skipped: Some(reason),
+            ..PageTiming::default()
+        });
+        vec![ArrayVec::new(); self.configs.len()]
+    }
+
+    // the images of each target, in page order
+    fn finish(
+        &self,
+        pages: Vec<Vec<ArrayVec<ProcessedImage, 3>>>,
+    ) -> Result<Vec<Vec<ProcessedImage>>> {
+        self.trim_cache();
+        let mut outputs: Vec<Vec<ProcessedImage>> = vec![Vec::new(); self.configs.len()];
+        for page in pages {
+            for (output, images) in outputs.iter_mut().zip(page) {
+                output.extend(images);
+            }
+        }
+
+        // Serial sort + dedup (fast, no benefit from parallelism)
+        for (images, config) in outputs.iter_mut().zip(self.configs) {
+            images.sort_unstable_by(|a, b| a.file_name.cmp(&b.file_name));
+            images.dedup_by(|a, b| a.file_name == b.file_name);
+            number_pages(images);
+            if let Some(height) = config.long_strip() {
+                *images = strip::merge(std::mem::take(images), height, config)?;
+            }
+        }
+        Ok(outputs)
+    }
+
+    // evicts what doesn't fit in the cache anymore once the pages are stored
+    fn trim_cache(&self) {
+        let max_mb = self.configs[0].page_cache_mb;
+        let Some(cache) = self.cache.as_ref().filter(|_| max_mb > 0) else {
+            return;
+        };
+        match cache.trim(u64::from(max_mb) * 1_000_000) {
+            Ok(stats) if stats.entries > 0 => log::info!(
+                "Evicted {} pages ({:.1} MB) from the page cache",
+                stats.entries,
+                stats.bytes as f64 / 1_000_000.0
+            ),
+            Ok(_) => {}
+            Err(e) => log::warn!("Failed to trim the page cache: {e:#}"),
+        }
+    }
+}
+
+// 1-based page numbers the files will have in the sorted output, see `number_pages`
+fn page_numbers(names: &[PathBuf], format: ImageFormat) -> HashMap<&Path, usize> {
+    let mut sorted: Vec<(String, &Path)> = names
+        .iter()
+        .map(|name| (encode::part_file_name_of(name, 0, format), name.as_path()))
         .collect();
+    sorted.sort();
+    sorted
+        .into_iter()
+        .zip(1..)
+        .map(|((_, name), page)| (name, page))
+        .collect()
+}
 
-    // Serial sort + dedup (fast, no benefit from parallelism)
-    images.sort_unstable_by(|a, b| a.file_name.cmp(&b.file_name));
-    images.dedup_by(|a, b| a.file_name == b.file_name);
+// the page shrunk to `longest` and encoded like it
+fn small_copy(img: &DynamicImage, longest: u32, format: ImageFormat) -> Option<SmallCopy> {
+    let small = match img {
+        DynamicImage::ImageLuma8(gray) => {
+            let small = transform::shrink(gray.clone().into(), longest)?;
+            DynamicImage::ImageLuma8(small.into())
+        }
+        // color pages, see `color`
+        img if img.width().max(img.height()) > longest => img.resize(
+            longest,
+            longest,
+            imageproc::image::imageops::FilterType::Lanczos3,
+        ),
+        _ => return None,
+    };
+    Some(SmallCopy {
+        data: encode::encode_image(&small, &format),
+        dimensions: (small.width(), small.height()),
+    })
+}
 
-    Ok(images)
+// parts of a page are next to each other once sorted
+fn number_pages(images: &mut [ProcessedImage]) {
+    let mut page = 0;
+    for i in 0..images.len() {
+        if i == 0 || images[i].source != images[i - 1].source {
+            page += 1;
+        }
+        images[i].page = page;
+    }
 }
 
 /// Process a single image file with Kindle-optimized transformations
 pub fn process(img: DynamicImage, config: &ComicConfig) -> Split<DynamicImage> {
-    let img = transform::Image::from(img.into_luma8())
-        .gamma(config.gamma)
+    render(&prepare(img, config), config)
+        .parts
+        .map(|(img, _)| img)
+}
+
+/// Device independent half of [`process`]: grayscale conversion and tone adjustments
+pub fn prepare(img: DynamicImage, config: &ComicConfig) -> transform::Image {
+    adjust(transform::Image::from(img.into_luma8()), config)
+}
+
+// whether `adjust` makes the same page for both configs
+fn same_tones(a: &ComicConfig, b: &ComicConfig) -> bool {
+    a.paper_white == b.paper_white
+        && a.gamma_curve == b.gamma_curve
+        && a.gamma == b.gamma
+        && a.contrast == b.contrast
+        && a.brightness == b.brightness
+}
+
+/// Tone adjustments of [`prepare`], for pages already decoded to grayscale with
+/// [`decode::decode_luma`]
+pub fn adjust(img: transform::Image, config: &ComicConfig) -> transform::Image {
+    let _span = crate::trace::span!("prepare").entered();
+    if let Some(adjusted) = gpu::adjust(&img, config) {
+        return adjusted;
+    }
+    let img = match config.paper_white {
+        true => img.paper_white(),
+        false => img,
+    };
+    img.tone_curve(&config.gamma_curve, config.gamma)
         .autocontrast()
-        .brightness(config.brightness);
+        .contrast(config.contrast)
+        .brightness(config.brightness)
+}
+
+/// A source page after [`render`]
+pub struct Rendered {
+    pub parts: Split<(DynamicImage, PagePart)>,
+    /// What auto-crop and the fixed crop kept of the page as left, top, width and
+    /// height, `None` when neither removed anything
+    pub crop: Option<(u32, u32, u32, u32)>,
+    /// Whether the page was upscaled with super-resolution, see [`upscale::upscale`]
+    pub upscaled: bool,
+    /// The color of each part, for color pages rendered with [`render_color`]. Put back
+    /// on the parts with [`color::merge`] once they're stamped
+    pub chroma: Option<Split<color::Chroma>>,
+}
+
+/// Device specific half of [`process`]: cropping, splitting, resizing and output rotation
+pub fn render(img: &transform::Image, config: &ComicConfig) -> Rendered {
+    let context = hooks::PageContext {
+        file: Path::new(""),
+        page: 0,
+        part: None,
+        config,
+    };
+    render_page(img, &context)
+}
+
+/// [`render`] for the page of `context`, which is passed to the hooks of its config
+pub fn render_page(img: &transform::Image, context: &hooks::PageContext) -> Rendered {
+    render_color(img, None, context)
+}
+
+/// [`render_page`] for a color page split with [`color::split`], whose `chroma` goes
+/// through the same crops, splits and rotations. Pages a hook or filter resized stay gray.
+pub fn render_color(
+    img: &transform::Image,
+    chroma: Option<&color::Chroma>,
+    context: &hooks::PageContext,
+) -> Rendered {
+    use transform::Img;
+
+    let config = context.config;
+    let hooked;
+    let img = match config.hooks.runs(hooks::Stage::PreCrop) {
+        true => {
+            let gray = config
+                .hooks
+                .run(hooks::Stage::PreCrop, img.clone().into(), context);
+            hooked = transform::Image::from(gray);
+            &hooked
+        }
+        false => img,
+    };
+
+    let chroma = chroma.filter(|chroma| chroma.dimensions() == img.dimensions());
+    let (original_width, _) = img.dimensions();
+    let upscaled = upscale::upscale(img, config);
+    let img = upscaled.as_ref().unwrap_or(img);
+    let chroma = chroma.map(|chroma| chroma.clone().scale(img.dimensions()));
 
-    if config.auto_crop {
-        transform::split_rotate(img.auto_crop(), config)
-    } else {
-        transform::split_rotate(img, config)
+    let span = crate::trace::span!(
+        "render",
+        device = ?config.device.dimensions(),
+        cropped = tracing::field::Empty,
+    )
+    .entered();
+
+    // the regions are measured on the page as delivered
+    let (width, height) = img.dimensions();
+    let scale = (width / original_width.max(1)).max(1);
+    let fixed = config
+        .fixed_crop
+        .and_then(|crop| crop.bounds(width, height, scale));
+    let trimmed;
+    let img = match fixed {
+        Some((left, top, width, height)) => {
+            trimmed = img.region(left, top, width, height);
+            &trimmed
+        }
+        None => img,
+    };
+    let chroma = match (chroma, fixed) {
+        (Some(chroma), Some((left, top, width, height))) => {
+            Some(chroma.region(left, top, width, height))
+        }
+        (chroma, _) => chroma,
+    };
+
+    let (width, height) = img.dimensions();
+    let auto = config.auto_crop.then(|| img.crop_bounds()).flatten();
+    let (left, top, crop_width, crop_height) = auto.unwrap_or((0, 0, width, height));
+    let view = img.crop(left, top, crop_width, crop_height);
+    let crop = match fixed {
+        Some((fixed_left, fixed_top, _, _)) => {
+            Some((fixed_left + left, fixed_top + top, crop_width, crop_height))
+        }
+        None => auto,
+    };
+    span.record("cropped", crop.is_some());
+
+    let chroma = chroma.map(|chroma| {
+        let crop = (left, top, crop_width, crop_height);
+        chroma.render(crop, config).map(|(chroma, _)| chroma)
+    });
+
+    let parts = transform::split_rotate(view, config).map(|(img, part)| {
+        let img = transform::rotate_output(img, config.rotation);
+        // color pages are left alone, their screens aren't limited to a few gray levels
+        let img = match config.dither && chroma.is_none() {
+            true => img.dither(E_INK_LEVELS),
+            false => img,
+        }
+        .into();
+        let context = hooks::PageContext {
+            part: Some(part),
+            ..*context
+        };
+        let img = config.hooks.run(hooks::Stage::PostResize, img, &context);
+        (DynamicImage::ImageLuma8(img), part)
+    });
+
+    Rendered {
+        parts,
+        crop,
+        upscaled: upscaled.is_some(),
+        chroma,
     }
-    .map(|img| DynamicImage::ImageLuma8(img.into()))
+}
+
+/// A part of a page after [`transform_gray`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayPart {
+    pub width: u32,
+    pub height: u32,
+    /// One byte per pixel, in row-major order
+    pub pixels: Vec<u8>,
+    pub part: PagePart,
+}
+
+/// What [`transform_gray`] did to a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedOperations {
+    /// The paper level brought to white, see [`transform::Image::paper_level`]
+    pub paper_level: Option<u8>,
+    /// The gamma the page was corrected with, `None` when it was left alone or a LUT
+    /// replaced it
+    pub gamma: Option<f32>,
+    pub gamma_curve: GammaCurve,
+    /// Brightness offset, 0 when it was left alone
+    pub brightness: i32,
+    /// Strength of the S-curve, 0 when it was left alone
+    pub contrast: i32,
+    /// Whether the page was upscaled with super-resolution before resizing
+    pub upscaled: bool,
+    /// What auto-crop and the fixed crop kept as left, top, width and height, in pixels
+    /// of the upscaled page when it was upscaled
+    pub crop: Option<(u32, u32, u32, u32)>,
+    /// Rotation of every part
+    pub rotation: Rotation,
+}
+
+/// The transforms of a conversion on a raw grayscale page, without reading an archive
+/// or encoding the result: tone adjustments, auto-crop, splitting, resizing and
+/// rotation, as [`process`] applies them.
+///
+/// `pixels` holds `width * height` bytes in row-major order. The parts come in reading
+/// order of the split, see [`PagePart`].
+pub fn transform_gray(
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    config: &ComicConfig,
+) -> Result<(Vec<GrayPart>, AppliedOperations)> {
+    anyhow::ensure!(width > 0 && height > 0, "Empty {width}x{height} page");
+    let img = imageproc::image::GrayImage::from_raw(width, height, pixels.to_vec()).with_context(
+        || {
+            format!(
+                "{} bytes don't make a {width}x{height} grayscale page",
+                pixels.len()
+            )
+        },
+    )?;
+
+    let img = transform::Image::from(img);
+    let paper_level = config.paper_white.then(|| img.paper_level()).flatten();
+    let rendered = render(&adjust(img, config), config);
+    let parts = rendered
+        .parts
+        .into_iter()
+        .map(|(img, part)| {
+            let img = img.into_luma8();
+            GrayPart {
+                width: img.width(),
+                height: img.height(),
+                pixels: img.into_raw(),
+                part,
+            }
+        })
+        .collect();
+    let applied = AppliedOperations {
+        paper_level,
+        gamma: match config.gamma_curve {
+            GammaCurve::Lut(_) => None,
+            GammaCurve::Power | GammaCurve::Srgb => transform::applied_gamma(config.gamma),
+        },
+        gamma_curve: config.gamma_curve.clone(),
+        brightness: config.brightness,
+        contrast: config.contrast,
+        upscaled: rendered.upscaled,
+        crop: rendered.crop,
+        rotation: config.rotation,
+    };
+    Ok((parts, applied))
+}
+
+#[test]
+fn adaptive_quality_follows_complexity() {
+    use imageproc::image::{GrayImage, Luma};
+
+    let adaptive = AdaptiveQuality { min: 70, max: 90 };
+    let flat = DynamicImage::ImageLuma8(GrayImage::from_pixel(64, 64, Luma([255])));
+    let busy = DynamicImage::ImageLuma8(GrayImage::from_fn(64, 64, |x, y| {
+        Luma([if (x + y) % 2 == 0 { 0 } else { 255 }])
+    }));
+
+    let quality = |img| match adaptive.apply(ImageFormat::Jpeg { quality: 85 }, img) {
+        ImageFormat::Jpeg { quality } => quality,
+        _ => unreachable!(),
+    };
+    assert_eq!(quality(&flat), 70);
+    assert_eq!(quality(&busy), 90);
+}
+
+#[test]
+fn streamed_pages_match_batched_ones() {
+    let files: Vec<ArchiveFile> = (0..4)
+        .map(|i| ArchiveFile {
+            file_name: format!("chapter/{i:03}.jpg").into(),
+            data: crate::testing::sample_page(60, 80, i),
+        })
+        .collect();
+    let names: Vec<PathBuf> = files.iter().map(|file| file.file_name.clone()).collect();
+    // stamped pages need their number before they're all read
+    let config = ComicConfig {
+        page_stamp: Some(crate::PageStamp::default()),
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+    let configs = [config];
+
+    let summary = |outputs: Vec<Vec<ProcessedImage>>| -> Vec<_> {
+        outputs[0]
+            .iter()
+            .map(|image| (image.file_name.clone(), image.page, image.data.clone()))
+            .collect()
+    };
+    let batched = process_batch_targets(files.clone(), &configs, |_| {}).unwrap();
+    // in any order, as the pool picks them up
+    let streamed =
+        process_stream_targets(files.into_iter().rev(), &names, &configs, |_| {}).unwrap();
+    assert_eq!(summary(streamed), summary(batched));
+}
+
+#[test]
+fn previews_match_converted_pages() {
+    let files: Vec<ArchiveFile> = (0..3)
+        .map(|i| ArchiveFile {
+            file_name: format!("{i:03}.jpg").into(),
+            data: crate::testing::sample_page(60, 80, i),
+        })
+        .collect();
+    // numbered as the third page, not the only one
+    let config = ComicConfig {
+        page_stamp: Some(crate::PageStamp::default()),
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+    let converted = process_batch(files.clone(), &config).unwrap();
+    let expected = decode::decode(&converted[2].data).unwrap();
+    let shown = preview(&files, 2, &config).unwrap();
+    assert_eq!(shown.file_name, converted[2].file_name);
+    let shown = decode::decode(&shown.data).unwrap();
+    assert_eq!(shown.to_luma8(), expected.to_luma8());
+    assert!(preview(&files, 3, &config).is_err());
+}
+
+#[test]
+fn transforms_raw_gray_pages() {
+    use crate::comic::SplitStrategy;
+
+    // a spread with white margins around its content
+    let (width, height) = (400, 200);
+    let pixels: Vec<u8> = (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            if (40..360).contains(&x) && (20..180).contains(&y) {
+                (x % 200) as u8
+            } else {
+                255
+            }
+        })
+        .collect();
+    let config = ComicConfig {
+        split: SplitStrategy::Split,
+        auto_crop: true,
+        gamma: 1.8,
+        ..ComicConfig::default()
+    };
+
+    let (parts, applied) = transform_gray(width, height, &pixels, &config).unwrap();
+    let sides: Vec<PagePart> = parts.iter().map(|part| part.part).collect();
+    assert_eq!(sides, [PagePart::Right, PagePart::Left]);
+    for part in &parts {
+        assert_eq!(part.pixels.len(), (part.width * part.height) as usize);
+    }
+    assert_eq!(applied.gamma, Some(1.8));
+    assert!(applied
+        .crop
+        .is_some_and(|(left, top, ..)| left > 0 && top > 0));
+
+    assert!(transform_gray(width, height, &pixels[1..], &config).is_err());
+    assert!(transform_gray(0, 0, &[], &config).is_err());
+
+    // a footer printed under the art is cut before auto-crop, which then finds the art
+    let footer: Vec<u8> = (0..width * height)
+        .map(|i| match (i % width, i / width) {
+            (_, 190..) => 0,
+            (40..360, 20..150) => 100,
+            _ => 255,
+        })
+        .collect();
+    let config = ComicConfig {
+        split: SplitStrategy::None,
+        fixed_crop: Some(crate::FixedCrop::bottom(10)),
+        ..config
+    };
+    let (_, applied) = transform_gray(width, height, &footer, &config).unwrap();
+    let (_, top, _, height) = applied.crop.unwrap();
+    assert!(top > 0 && top + height < 160, "{:?}", applied.crop);
+}
+
+#[test]
+fn contrast_bends_midtones_around_mid_gray() {
+    use imageproc::image::GrayImage;
+
+    let ramp = || transform::Image::from(GrayImage::from_fn(256, 1, |x, _| [x as u8].into()));
+    let stronger = ramp().contrast(60);
+    let weaker = ramp().contrast(-60);
+    for img in [&stronger, &weaker] {
+        assert_eq!((img.as_raw()[0], img.as_raw()[255]), (0, 255));
+        assert!(img.as_raw()[128].abs_diff(128) <= 1);
+    }
+    assert!(stronger.as_raw()[64] < 64 && stronger.as_raw()[192] > 192);
+    assert!(weaker.as_raw()[64] > 64 && weaker.as_raw()[192] < 192);
+    assert_eq!(ramp().contrast(0).as_raw(), ramp().as_raw());
+}
+
+#[test]
+fn yellowed_paper_becomes_white() {
+    use imageproc::image::{GrayImage, Luma};
+
+    // gray paper around a panel of ink
+    let page = GrayImage::from_fn(100, 150, |x, y| match (x, y) {
+        (20..80, 30..120) => Luma([30]),
+        _ => Luma([200]),
+    });
+    let img = transform::Image::from(page);
+    assert_eq!(img.paper_level(), Some(200));
+    let normalized = img.paper_white();
+    assert_eq!(normalized.as_raw()[0], 255);
+    assert_eq!(normalized.as_raw()[50 * 100 + 50], 38);
+
+    // white paper and pages bled to the edges are left alone
+    let white = transform::Image::from(GrayImage::from_pixel(100, 150, Luma([250])));
+    assert_eq!(white.paper_level(), None);
+    let bled = transform::Image::from(GrayImage::from_pixel(100, 150, Luma([40])));
+    assert_eq!(bled.paper_level(), None);
+}
+
+#[test]
+fn max_dimension_and_small_copies() {
+    let files = vec![ArchiveFile {
+        file_name: "001.jpg".into(),
+        data: crate::testing::sample_page(600, 900, 1),
+    }];
+    let config = ComicConfig {
+        output_format: crate::OutputFormat::Epub,
+        auto_crop: false,
+        margin_color: Some(crate::MarginColor::BLACK),
+        max_dimension: Some(1200),
+        srcset: Some(450),
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+    let images = process_batch(files.clone(), &config).unwrap();
+    // fit by the longest side, without margins squaring the page
+    assert_eq!(images[0].dimensions, (800, 1200));
+    let small = images[0].small.as_ref().unwrap();
+    assert_eq!(small.dimensions, (300, 450));
+
+    // only EPUBs embed small copies
+    let config = ComicConfig {
+        output_format: crate::OutputFormat::Cbz,
+        ..config
+    };
+    assert_eq!(process_batch(files, &config).unwrap()[0].small, None);
+}
+
+#[test]
+fn animated_pages_follow_the_policy() {
+    use imageproc::image::codecs::gif::GifEncoder;
+    use imageproc::image::{Frame, Rgba, RgbaImage};
+
+    let mut gif = Vec::new();
+    let frames = [0, 128, 255].map(|level| {
+        Frame::new(RgbaImage::from_pixel(
+            60,
+            80,
+            Rgba([level, level, level, 255]),
+        ))
+    });
+    GifEncoder::new(&mut gif).encode_frames(frames).unwrap();
+    assert_eq!(decode::animation_frames(&gif), Some(3));
+    assert_eq!(
+        decode::animation_frames(&crate::testing::sample_page(60, 80, 1)),
+        None
+    );
+
+    let files = vec![ArchiveFile {
+        file_name: "bonus.gif".into(),
+        data: gif,
+    }];
+    let convert = |animated_pages| {
+        let config = ComicConfig {
+            animated_pages,
+            output_format: crate::OutputFormat::Cbz,
+            image_format: ImageFormat::Png {
+                compression: PngCompression::Fast,
+            },
+            auto_crop: false,
+            brightness: 0,
+            gamma: 1.0,
+            dither: false,
+            page_cache: false,
+            ..ComicConfig::default()
+        };
+        let images = process_batch(files.clone(), &config).unwrap();
+        images.first().map(|image| {
+            let page = imageproc::image::load_from_memory(&image.data).unwrap();
+            page.into_luma8().get_pixel(30, 40).0[0]
+        })
+    };
+    assert_eq!(convert(AnimatedPages::FirstFrame), Some(0));
+    assert_eq!(convert(AnimatedPages::MiddleFrame), Some(128));
+    assert_eq!(convert(AnimatedPages::Skip), None);
+}
+
+#[test]
+fn multi_page_tiffs_expand_in_order() {
+    use std::io::Cursor;
+    use tiff::encoder::{colortype::Gray8, TiffEncoder};
+
+    let mut scan = Cursor::new(Vec::new());
+    let mut encoder = TiffEncoder::new(&mut scan).unwrap();
+    for shade in [40, 120, 200] {
+        encoder
+            .write_image::<Gray8>(60, 80, &[shade; 4800])
+            .unwrap();
+    }
+    let mut bmp = Cursor::new(Vec::new());
+    DynamicImage::ImageLuma8(imageproc::image::GrayImage::from_pixel(
+        60,
+        80,
+        imageproc::image::Luma([250]),
+    ))
+    .write_to(&mut bmp, imageproc::image::ImageFormat::Bmp)
+    .unwrap();
+
+    let files = vec![
+        ArchiveFile {
+            file_name: "ch1/z-back.bmp".into(),
+            data: bmp.into_inner(),
+        },
+        ArchiveFile {
+            file_name: "ch1/scan.tif".into(),
+            data: scan.into_inner(),
+        },
+    ];
+    let config = ComicConfig {
+        output_format: crate::OutputFormat::Cbz,
+        image_format: ImageFormat::Png {
+            compression: PngCompression::Fast,
+        },
+        auto_crop: false,
+        brightness: 0,
+        gamma: 1.0,
+        dither: false,
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+    let images = process_batch(files, &config).unwrap();
+    let sources: Vec<&Path> = images.iter().map(|image| image.source.as_path()).collect();
+    assert_eq!(
+        sources,
+        [
+            "ch1/scan-001.png",
+            "ch1/scan-002.png",
+            "ch1/scan-003.png",
+            "ch1/z-back.bmp"
+        ]
+        .map(Path::new)
+    );
+    let shades: Vec<u8> = images
+        .iter()
+        .map(|image| {
+            let page = imageproc::image::load_from_memory(&image.data).unwrap();
+            page.into_luma8().get_pixel(30, 40).0[0]
+        })
+        .collect();
+    assert_eq!(shades, [40, 120, 200, 250]);
+}
+
+#[test]
+fn placeholder_pages_are_skipped_or_fail() {
+    let mut dot = Vec::new();
+    DynamicImage::ImageLuma8(imageproc::image::GrayImage::new(1, 1))
+        .write_to(
+            &mut std::io::Cursor::new(&mut dot),
+            imageproc::image::ImageFormat::Png,
+        )
+        .unwrap();
+    assert_eq!(
+        decode::placeholder(&dot).as_deref(),
+        Some("placeholder image (1x1)")
+    );
+    let page = crate::testing::sample_page(60, 80, 1);
+    assert_eq!(decode::placeholder(&page), None);
+
+    let files = vec![
+        ArchiveFile {
+            file_name: "001.jpg".into(),
+            data: page,
+        },
+        ArchiveFile {
+            file_name: "002.jpg".into(),
+            data: Vec::new(),
+        },
+        ArchiveFile {
+            file_name: "003.png".into(),
+            data: dot,
+        },
+    ];
+    let config = ComicConfig {
+        output_format: crate::OutputFormat::Cbz,
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+    let skipped = std::sync::Mutex::new(Vec::new());
+    let images = process_batch_targets(files.clone(), std::slice::from_ref(&config), |timing| {
+        if let Some(reason) = &timing.skipped {
+            skipped.lock().unwrap().push(reason.clone());
+        }
+    })
+    .unwrap();
+    assert_eq!(images[0].len(), 1);
+    let mut skipped = skipped.into_inner().unwrap();
+    skipped.sort();
+    assert_eq!(
+        skipped,
+        ["skipped empty file", "skipped placeholder image (1x1)"]
+    );
+
+    let config = ComicConfig {
+        placeholder_pages: PlaceholderPages::Fail,
+        ..config
+    };
+    // either placeholder fails the comic, whichever a thread reaches first
+    let error = format!("{:#}", process_batch(files, &config).err().unwrap());
+    assert!(
+        error.contains("002.jpg: empty file") || error.contains("003.png: placeholder image"),
+        "{error}"
+    );
+}
+
+#[test]
+fn pages_are_yielded_in_order_as_they_are_done() {
+    use std::io::Cursor;
+
+    use crate::testing::{Package, SampleArchive};
+
+    let dir = tempfile::tempdir().unwrap();
+    let comic = SampleArchive::with_pages(0)
+        .page("ch2/001.jpg", 60, 80)
+        .page("ch1/002.jpg", 60, 80)
+        .page("ch1/001.jpg", 140, 80)
+        .write(dir.path().join("sample v01.cbz"))
+        .unwrap();
+    let config = ComicConfig {
+        page_cache: false,
+        deterministic: true,
+        output_format: crate::OutputFormat::Cbz,
+        ..ComicConfig::default()
+    };
+
+    let files: Vec<ArchiveFile> = crate::archive::unarchive_comic_iter(&comic)
+        .unwrap()
+        .collect::<Result<_>>()
+        .unwrap();
+    let batch = process_batch(files, &config).unwrap();
+    let streamed: Vec<ProcessedImage> = comic.process_iter(&config).collect::<Result<_>>().unwrap();
+    let summary = |images: &[ProcessedImage]| -> Vec<(String, usize, usize)> {
+        images
+            .iter()
+            .map(|image| (image.file_name.clone(), image.page, image.data.len()))
+            .collect()
+    };
+    assert_eq!(summary(&streamed), summary(&batch));
+    // the parts of the spread ch1/001.jpg share its number
+    let pages: Vec<usize> = streamed.iter().map(|image| image.page).collect();
+    assert_eq!(pages, [1, 1, 1, 2, 3]);
+
+    let cbz = crate::cbz::build_iter(
+        comic.title(),
+        &config,
+        comic.process_iter(&config),
+        Cursor::new(Vec::new()),
+    )
+    .unwrap()
+    .into_inner();
+    assert_eq!(cbz, crate::cbz::build(comic.title(), &config, &batch));
+
+    let config = ComicConfig {
+        output_format: crate::OutputFormat::Epub,
+        ..config
+    };
+    let (epub, id) = crate::epub::build_iter(
+        comic.title(),
+        &config,
+        comic.process_iter(&config),
+        Cursor::new(Vec::new()),
+    )
+    .unwrap();
+    let package = Package::read(epub.get_ref()).unwrap();
+    package.check_epub().unwrap();
+    assert!(package.text("OEBPS/content.opf").contains(&id.to_string()));
+
+    let missing = crate::ComicFile::new(dir.path().join("missing.cbz"));
+    let mut pages = missing.process_iter(&config);
+    assert!(pages.next().unwrap().is_err());
+    assert!(pages.next().is_none());
+}
+
+#[test]
+fn targets_keep_their_own_tones() {
+    use crate::device::{Device, Preset};
+
+    let page = ArchiveFile {
+        file_name: "001.jpg".into(),
+        data: crate::testing::sample_page(300, 400, 1),
+    };
+    let target = |preset| {
+        let mut config = ComicConfig {
+            page_cache: false,
+            auto_crop: false,
+            ..ComicConfig::default()
+        };
+        config.set_device(Device::Preset(preset));
+        config
+    };
+    let e_ink = target(Preset::KoboLibra2);
+    let lcd = target(Preset::IpadMini);
+    assert_ne!(e_ink.gamma, lcd.gamma);
+
+    let single = |config: &ComicConfig| {
+        let outputs =
+            process_batch_targets(vec![page.clone()], std::slice::from_ref(config), |_| {})
+                .unwrap();
+        outputs[0][0].data.clone()
+    };
+    let outputs =
+        process_batch_targets(vec![page.clone()], &[e_ink.clone(), lcd.clone()], |_| {}).unwrap();
+    assert_eq!(outputs[0][0].data, single(&e_ink));
+    assert_eq!(outputs[1][0].data, single(&lcd));
+    assert_ne!(outputs[0][0].data, outputs[1][0].data);
+}
+
+#[test]
+fn dithering_keeps_the_tones_in_e_ink_levels() {
+    use imageproc::image::GrayImage;
+
+    let ramp = GrayImage::from_fn(256, 64, |x, _| [x as u8].into());
+    let dithered = transform::Image::from(ramp).dither(E_INK_LEVELS);
+    let step = 255 / (E_INK_LEVELS - 1);
+    assert!(dithered.as_raw().iter().all(|level| level % step == 0));
+    // each column averages out to its gray level
+    for x in [10, 100, 200] {
+        let column: u32 = (0..64)
+            .map(|y| u32::from(dithered.as_raw()[y * 256 + x]))
+            .sum();
+        assert!(
+            (column / 64).abs_diff(x as u32) <= 4,
+            "column {x}: {}",
+            column / 64
+        );
+    }
+}
+
+#[test]
+fn color_pages_stay_in_color() {
+    use imageproc::image::{codecs::png::PngEncoder, ImageEncoder, Rgb, RgbImage};
+
+    // a red panel framed in black on white paper
+    let page = RgbImage::from_fn(300, 400, |x, y| match (x, y) {
+        (30..270, 40..360) => Rgb([200, 30, 40]),
+        (20..280, 30..370) => Rgb([0, 0, 0]),
+        _ => Rgb([255, 255, 255]),
+    });
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(
+            page.as_raw(),
+            300,
+            400,
+            imageproc::image::ExtendedColorType::Rgb8,
+        )
+        .unwrap();
+    let files = vec![ArchiveFile {
+        file_name: "001.png".into(),
+        data: png,
+    }];
+    let config = ComicConfig {
+        auto_crop: false,
+        page_cache: false,
+        keep_color: true,
+        dither: false,
+        gamma: 1.0,
+        brightness: 0,
+        ..ComicConfig::default()
+    };
+
+    let output = |config: &ComicConfig| {
+        let images = process_batch(files.clone(), config).unwrap();
+        decode::decode(&images[0].data).unwrap()
+    };
+    let color = output(&config);
+    assert!(stats::is_color(&color));
+    let center = color
+        .to_rgb8()
+        .get_pixel(color.width() / 2, color.height() / 2)
+        .0;
+    assert!(
+        center[0] > 150 && center[1] < 90 && center[2] < 90,
+        "{center:?}"
+    );
+
+    let gray = output(&ComicConfig {
+        keep_color: false,
+        ..config
+    });
+    assert!(!stats::is_color(&gray));
 }
@@ -0,0 +1,218 @@
+//! Pages of plain text, such as the afterword some volumes end with, read into text that
+//! EPUBs reflow instead of a scan shrunk to fit small screens.
+//!
+//! Pages are told apart from artwork by how their ink is laid out, and only those go
+//! through [Tesseract](https://github.com/tesseract-ocr/tesseract)'s `tesseract`
+//! executable, looked up in the PATH or at `COMICALLY_TESSERACT` and only run when
+//! comically is built with the `ocr` feature. Otherwise text pages stay images, with a
+//! warning saying why.
+
+use std::sync::OnceLock;
+
+use super::transform::Image;
+use crate::comic::ComicConfig;
+
+// darker than this is ink
+const INK: u8 = 128;
+
+// lines of text a page needs, fewer is a caption or a title page
+const MIN_LINES: usize = 8;
+
+// what OCR has to read for the page to be text, fewer is noise read off artwork
+const MIN_WORDS: usize = 20;
+
+/// Whether reading text pages can run, or why not
+pub fn check() -> Result<(), String> {
+    static CHECK: OnceLock<Result<(), String>> = OnceLock::new();
+    CHECK.get_or_init(tesseract::check).clone()
+}
+
+/// What the page says when the config reads text pages and the page is one, `None`
+/// keeps it an image
+pub fn text(img: &Image, config: &ComicConfig) -> Option<String> {
+    if !config.text_pages() || !is_text_page(img) {
+        return None;
+    }
+    if let Err(reason) = check() {
+        static WARNED: OnceLock<()> = OnceLock::new();
+        WARNED.get_or_init(|| log::warn!("{reason}, text pages are kept as images"));
+        return None;
+    }
+
+    let _span = crate::trace::span!("ocr").entered();
+    let text = match tesseract::run(img) {
+        Ok(text) => text,
+        Err(e) => {
+            log::warn!("Reading a text page failed, it's kept as an image: {e:#}");
+            return None;
+        }
+    };
+    (text.split_whitespace().count() >= MIN_WORDS).then(|| paragraphs(&text))
+}
+
+/// Whether the ink of `img` lies in many thin lines with space between them, the way
+/// text does, rather than in the tall blocks of panels and artwork
+pub fn is_text_page(img: &Image) -> bool {
+    let (width, height) = img.dimensions();
+    if width == 0 || height < 100 {
+        return false;
+    }
+    // a few specks don't make a line
+    let min_ink = (width as usize / 200).max(1);
+    let inked: Vec<bool> = img
+        .as_raw()
+        .chunks_exact(width as usize)
+        .map(|row| row.iter().filter(|&&pixel| pixel < INK).count() >= min_ink)
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut start = None;
+    for (y, &ink) in inked.iter().chain([&false]).enumerate() {
+        match (ink, start) {
+            (true, None) => start = Some(y),
+            (false, Some(from)) => {
+                lines.push(y - from);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if lines.len() < MIN_LINES {
+        return false;
+    }
+
+    let tallest = lines.iter().copied().max().unwrap_or(0);
+    let inked_rows: usize = lines.iter().sum();
+    lines.sort_unstable();
+    let median = lines[lines.len() / 2];
+    // lines are a few percent of the page, artwork runs much taller, and text leaves
+    // as much space between lines as there are lines
+    median * 25 < height as usize && tallest * 8 < height as usize && inked_rows * 2 < inked.len()
+}
+
+// the lines Tesseract wrote joined into paragraphs, words hyphenated at the end of a
+// line joined back together
+fn paragraphs(text: &str) -> String {
+    text.split("\n\n")
+        .map(|paragraph| {
+            paragraph
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .fold(String::new(), |mut joined, line| {
+                    match joined.strip_suffix('-') {
+                        Some(hyphenated) => joined.truncate(hyphenated.len()),
+                        None if !joined.is_empty() => joined.push(' '),
+                        None => {}
+                    }
+                    joined.push_str(line);
+                    joined
+                })
+        })
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(feature = "ocr")]
+mod tesseract {
+    use anyhow::{bail, Context, Result};
+    use imageproc::image::{ExtendedColorType, ImageEncoder};
+
+    use std::ffi::OsString;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    use crate::image::transform::Image;
+
+    fn executable() -> OsString {
+        std::env::var_os("COMICALLY_TESSERACT").unwrap_or_else(|| OsString::from("tesseract"))
+    }
+
+    pub fn check() -> Result<(), String> {
+        match Command::new(executable()).arg("--version").output() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Can't run {}: {e}", executable().to_string_lossy())),
+        }
+    }
+
+    pub fn run(img: &Image) -> Result<String> {
+        let (width, height) = img.dimensions();
+        let mut png = Vec::new();
+        imageproc::image::codecs::png::PngEncoder::new(&mut png)
+            .write_image(img.as_raw(), width, height, ExtendedColorType::L8)
+            .context("Failed to encode the page")?;
+
+        let mut child = Command::new(executable())
+            .args(["stdin", "stdout"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run tesseract")?;
+        // Tesseract reads the whole image before it writes anything
+        child
+            .stdin
+            .take()
+            .context("No stdin")?
+            .write_all(&png)
+            .context("Failed to pass the page to tesseract")?;
+        let output = child
+            .wait_with_output()
+            .context("Failed to wait for tesseract")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!(
+                "tesseract exited with {}: {}",
+                output.status,
+                stderr.lines().last().unwrap_or_default()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(not(feature = "ocr"))]
+mod tesseract {
+    use crate::image::transform::Image;
+
+    pub fn check() -> Result<(), String> {
+        Err("comically was built without the `ocr` feature".into())
+    }
+
+    pub fn run(_: &Image) -> anyhow::Result<String> {
+        anyhow::bail!("comically was built without the `ocr` feature")
+    }
+}
+
+#[test]
+fn text_pages_are_told_apart_from_artwork() {
+    use imageproc::image::{GrayImage, Luma};
+
+    // lines of words 12 pixels tall, 24 pixels apart
+    let text = GrayImage::from_fn(600, 900, |x, y| {
+        let in_line = (60..840).contains(&y) && y % 36 < 12;
+        let in_word = (50..550).contains(&x) && x % 40 < 30;
+        Luma([if in_line && in_word { 20 } else { 250 }])
+    });
+    assert!(is_text_page(&text.into()));
+
+    let page = crate::testing::sample_page(600, 900, 1);
+    let page = imageproc::image::load_from_memory(&page).unwrap();
+    assert!(!is_text_page(&page.into_luma8().into()));
+    // a dark panel and a caption
+    let panels = GrayImage::from_fn(600, 900, |x, y| {
+        let panel = (40..560).contains(&x) && (40..700).contains(&y);
+        Luma([if panel || (750..762).contains(&y) {
+            0
+        } else {
+            255
+        }])
+    });
+    assert!(!is_text_page(&panels.into()));
+
+    assert_eq!(
+        paragraphs("It was a long time com-\ning.\n  Thanks \n\n\nfor reading\n"),
+        "It was a long time coming. Thanks\n\nfor reading"
+    );
+}
@@ -0,0 +1,222 @@
+//! Translations typeset over the original lettering of the pages, to read a fan
+//! translation without editing the scans. They come from a sidecar file next to the
+//! comic, `Series v01.translations.json` for `Series v01.cbz`:
+//!
+//! ```json
+//! { "pages": [{ "page": 3, "boxes": [
+//!     { "x": 120, "y": 80, "width": 200, "height": 90, "text": "Where are we?" }
+//! ] }] }
+//! ```
+//!
+//! Pages are numbered from 1 in reading order and boxes are in pixels of the original
+//! page. Each box is painted white and its text wrapped and centered in it, as large as
+//! fits, with the configured font or else the built-in bitmap font in capitals.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ab_glyph::{FontVec, PxScale};
+use anyhow::{Context, Result};
+use imageproc::drawing::{draw_text_mut, text_size};
+use imageproc::image::{GrayImage, Luma};
+
+use super::stamp;
+use crate::comic::ComicFile;
+
+/// The translated text of a comic, by page
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Translations {
+    pub pages: Vec<PageTranslation>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PageTranslation {
+    /// 1-based, in reading order
+    pub page: usize,
+    pub boxes: Vec<TextBox>,
+}
+
+/// Text replacing what's in a rectangle of the page, usually a speech bubble
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TextBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub text: String,
+}
+
+impl Translations {
+    /// Where the translations of `comic` are looked for
+    pub fn sidecar_path(comic: &ComicFile) -> PathBuf {
+        comic.as_path().with_extension("translations.json")
+    }
+
+    /// Reads the sidecar of `comic`, `None` when it has none
+    pub fn find(comic: &ComicFile) -> Result<Option<Self>> {
+        let path = Self::sidecar_path(comic);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let translations = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(translations))
+    }
+
+    /// Boxes of a page, empty when it isn't translated
+    pub fn boxes(&self, page: usize) -> &[TextBox] {
+        self.pages
+            .iter()
+            .find(|translation| translation.page == page)
+            .map_or(&[], |translation| &translation.boxes)
+    }
+}
+
+/// Reads a TrueType or OpenType font once per conversion
+pub fn load_font(path: &Path) -> Result<FontVec> {
+    let data = fs::read(path).with_context(|| format!("Failed to read font {}", path.display()))?;
+    FontVec::try_from_vec(data).with_context(|| format!("Failed to load font {}", path.display()))
+}
+
+/// Paints each box white and writes its text in it
+pub fn draw(img: &mut GrayImage, boxes: &[TextBox], font: Option<&FontVec>) {
+    let lettering = match font {
+        Some(font) => Lettering::Font(font),
+        None => Lettering::Bitmap,
+    };
+
+    for text_box in boxes {
+        let (width, height) = img.dimensions();
+        let (x, y) = (text_box.x.min(width), text_box.y.min(height));
+        let (box_width, box_height) = (
+            text_box.width.min(width - x),
+            text_box.height.min(height - y),
+        );
+        for py in y..y + box_height {
+            for px in x..x + box_width {
+                img.put_pixel(px, py, Luma([255]));
+            }
+        }
+
+        // keeps the text off the outline of rounded bubbles
+        let padding = (box_width.min(box_height) / 10).min(12);
+        let inner = (box_width - padding * 2, box_height - padding * 2);
+        let Some((size, lines)) = lettering.fit(&text_box.text, inner) else {
+            log::debug!("`{}` doesn't fit its box", text_box.text);
+            continue;
+        };
+
+        let line_height = lettering.line_height(size);
+        let top = y + padding + (inner.1 - line_height * lines.len() as u32) / 2;
+        for (i, line) in lines.iter().enumerate() {
+            let left = x + padding + (inner.0 - lettering.width(line, size)) / 2;
+            lettering.draw(img, line, (left, top + i as u32 * line_height), size);
+        }
+    }
+}
+
+enum Lettering<'a> {
+    Bitmap,
+    Font(&'a FontVec),
+}
+
+impl Lettering<'_> {
+    // the largest size the text fits the box at, with its lines
+    fn fit(&self, text: &str, (width, height): (u32, u32)) -> Option<(u32, Vec<String>)> {
+        let sizes: Box<dyn Iterator<Item = u32>> = match self {
+            // integer scales of the 5x7 glyphs
+            Lettering::Bitmap => Box::new((1..=height / stamp::GLYPH_HEIGHT).rev()),
+            // pixel heights, anything smaller is unreadable on an e-reader anyway
+            Lettering::Font(_) => Box::new((8..=height.min(96)).rev()),
+        };
+        sizes.into_iter().find_map(|size| {
+            let lines = wrap(text, width, |line| self.width(line, size))?;
+            let fits = lines.len() as u32 * self.line_height(size) <= height;
+            fits.then_some((size, lines))
+        })
+    }
+
+    fn line_height(&self, size: u32) -> u32 {
+        match self {
+            Lettering::Bitmap => (stamp::GLYPH_HEIGHT + 2) * size,
+            Lettering::Font(_) => size + size / 5,
+        }
+    }
+
+    fn width(&self, text: &str, size: u32) -> u32 {
+        match self {
+            Lettering::Bitmap => stamp::text_width(text, size),
+            Lettering::Font(font) => text_size(PxScale::from(size as f32), *font, text).0,
+        }
+    }
+
+    fn draw(&self, img: &mut GrayImage, text: &str, (left, top): (u32, u32), size: u32) {
+        match self {
+            Lettering::Bitmap => stamp::draw_text(img, text, (left, top), size, 1.0),
+            Lettering::Font(font) => draw_text_mut(
+                img,
+                Luma([0]),
+                left as i32,
+                top as i32,
+                PxScale::from(size as f32),
+                *font,
+                text,
+            ),
+        }
+    }
+}
+
+// fills lines word by word, `None` when a word alone is wider than the box
+fn wrap(text: &str, width: u32, measure: impl Fn(&str) -> u32) -> Option<Vec<String>> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_string(),
+                false => format!("{line} {word}"),
+            };
+            if measure(&candidate) <= width {
+                line = candidate;
+            } else if measure(word) <= width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                return None;
+            }
+        }
+        lines.push(line);
+    }
+    Some(lines)
+}
+
+#[test]
+fn letters_translations_in_their_boxes() {
+    let mut img = GrayImage::from_pixel(200, 100, Luma([40]));
+    let boxes = [TextBox {
+        x: 20,
+        y: 10,
+        width: 100,
+        height: 60,
+        text: "Where are we going?".into(),
+    }];
+    draw(&mut img, &boxes, None);
+
+    // the box is painted, the rest of the page is untouched
+    assert_eq!(img.get_pixel(21, 11).0, [255]);
+    assert_eq!(img.get_pixel(150, 50).0, [40]);
+    // with black text inside it
+    let inked = (20..120)
+        .flat_map(|x| (10..70).map(move |y| (x, y)))
+        .filter(|&(x, y)| img.get_pixel(x, y).0 == [0])
+        .count();
+    assert!(inked > 50);
+
+    let measure = |line: &str| stamp::text_width(line, 2);
+    assert_eq!(
+        wrap("Where are we going?", 80, measure).unwrap(),
+        ["Where", "are we", "going?"]
+    );
+    assert_eq!(wrap("Unbreakable", 60, measure), None);
+}
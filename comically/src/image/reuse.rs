@@ -0,0 +1,223 @@
+//! Pages that are already what a conversion would make of them, grayscale JPEGs that
+//! fit the device at about the quality they'd be encoded with, copied into the output as
+//! they are instead of being decoded and encoded again. Libraries converted before, or
+//! optimized by other tools, then convert in the time it takes to read them.
+//!
+//! Grayscale means a single gray channel, as most tools write grayscale JPEGs, pages
+//! stored in color with gray pixels can't be told apart without decoding them. Only the
+//! JPEG's headers are read, so the tone adjustments aren't applied to copied
+//! pages either. That's why reusing pages is opt in, see `ComicConfig::reuse_pages`.
+
+use crate::archive::ArchiveFile;
+use crate::comic::{ComicConfig, PagePart, ProcessedImage};
+use crate::image::{encode, ImageFormat};
+
+/// How far the quality of a page may be from the target's and still be copied
+pub const QUALITY_TOLERANCE: u8 = 5;
+
+// the luminance table of the JPEG standard, which encoders scale by the quality
+const STD_LUMINANCE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// What the headers of a JPEG say about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JpegInfo {
+    pub width: u32,
+    pub height: u32,
+    pub components: u8,
+    /// Estimated from the first quantization table, for tables scaled like libjpeg's
+    pub quality: u8,
+}
+
+/// Reads the headers of `data` up to the first scan, `None` when it isn't a baseline or
+/// progressive JPEG
+pub fn jpeg_info(data: &[u8]) -> Option<JpegInfo> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut quality = None;
+    // height, width and components, encoders write the tables before or after it
+    let mut frame = None;
+    let mut pos = 2;
+    while quality.is_none() || frame.is_none() {
+        // markers may be padded with fill bytes
+        while *data.get(pos)? == 0xFF && *data.get(pos + 1)? == 0xFF {
+            pos += 1;
+        }
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + len)?;
+        match marker {
+            0xDB if quality.is_none() => quality = table_quality(segment),
+            // baseline, extended and progressive frames
+            0xC0..=0xC2 => {
+                let height = u16::from_be_bytes([*segment.get(1)?, *segment.get(2)?]);
+                let width = u16::from_be_bytes([*segment.get(3)?, *segment.get(4)?]);
+                frame = Some((width.into(), height.into(), *segment.get(5)?));
+            }
+            // lossless, arithmetic coded and other frames the decoder may not read, or
+            // the scan without the tables
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF | 0xDA | 0xD9 => return None,
+            _ => {}
+        }
+        pos += 2 + len;
+    }
+    let (width, height, components) = frame?;
+    Some(JpegInfo {
+        width,
+        height,
+        components,
+        quality: quality?,
+    })
+}
+
+// quality of the first table of a DQT segment, from how much the standard table was
+// scaled, the inverse of libjpeg's `jpeg_quality_scaling`
+fn table_quality(segment: &[u8]) -> Option<u8> {
+    let (&info, values) = segment.split_first()?;
+    let sum: u32 = match info >> 4 {
+        0 => values.get(..64)?.iter().map(|&q| u32::from(q)).sum(),
+        _ => values
+            .get(..128)?
+            .chunks_exact(2)
+            .map(|q| u32::from(u16::from_be_bytes([q[0], q[1]])))
+            .sum(),
+    };
+    let standard: u32 = STD_LUMINANCE.iter().map(|&q| u32::from(q)).sum();
+    let scale = sum as f32 * 100.0 / standard as f32;
+    let quality = match scale <= 100.0 {
+        true => (200.0 - scale) / 2.0,
+        false => 5000.0 / scale,
+    };
+    Some(quality.round().clamp(1.0, 100.0) as u8)
+}
+
+/// Whether any page could be copied through for `config`: nothing but the tones would
+/// change them, and the tones are left alone when pages are reused, dithering included
+pub fn applies(config: &ComicConfig) -> bool {
+    config.reuse_pages
+        && matches!(config.image_format, ImageFormat::Jpeg { .. })
+        && config.adaptive_quality.is_none()
+        && !config.auto_crop
+        && config.fixed_crop.is_none_or(|crop| crop.is_empty())
+        && config.margin_color.is_none()
+        && config.rotation == crate::comic::Rotation::None
+        && config.page_stamp.is_none()
+        && config.watermark.is_none()
+        && config.page_filter.is_none()
+        && config.hooks.is_empty()
+        && config.long_strip().is_none()
+        && config.small_copy().is_none()
+        && !config.text_pages()
+}
+
+/// `page` as it is in the output of `config`, when it's a grayscale portrait JPEG that
+/// fits the page box at about the target quality. Pages are never split or cropped
+/// here, callers check what else is done to the page, like lettering translations.
+pub fn reuse(page: &ArchiveFile, config: &ComicConfig) -> Option<ProcessedImage> {
+    let ImageFormat::Jpeg { quality } = config.image_format else {
+        return None;
+    };
+    if !applies(config) {
+        return None;
+    }
+    let info = jpeg_info(&page.data)?;
+    let (max_width, max_height) = config.page_box();
+    let fits = info.width <= max_width && info.height <= max_height;
+    // landscape pages are split or rotated by default
+    let portrait = info.height >= info.width;
+    let close = info.quality.abs_diff(quality) <= QUALITY_TOLERANCE;
+    if info.components != 1 || !fits || !portrait || !close || info.width == 0 {
+        return None;
+    }
+
+    let dimensions = (info.width, info.height);
+    Some(ProcessedImage {
+        file_name: encode::part_file_name(page, 0, config.image_format),
+        data: page.data.clone(),
+        dimensions,
+        format: config.image_format,
+        source: page.file_name.clone(),
+        // set once the whole comic is sorted
+        page: 0,
+        part: PagePart::Whole,
+        original_dimensions: dimensions,
+        cropped: false,
+        small: None,
+        text: None,
+    })
+}
+
+#[test]
+fn optimized_pages_are_copied_through() {
+    use crate::testing::{sample_color_page, sample_page};
+
+    // with a single gray channel, as the gray pixels of a `GrayImage` are encoded
+    let jpeg = |width, height, quality| {
+        let img = imageproc::image::load_from_memory(&sample_page(width, height, 1)).unwrap();
+        let mut jpeg = Vec::new();
+        imageproc::image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality)
+            .encode_image(&img.into_luma8())
+            .unwrap();
+        jpeg
+    };
+    let info = jpeg_info(&jpeg(300, 400, 85)).unwrap();
+    assert_eq!((info.width, info.height, info.components), (300, 400, 1));
+    assert!(info.quality.abs_diff(85) <= 1, "{info:?}");
+    assert!(jpeg_info(&jpeg(300, 400, 40)).unwrap().quality.abs_diff(40) <= 1);
+    assert_eq!(jpeg_info(b"not a jpeg"), None);
+
+    let config = ComicConfig {
+        reuse_pages: true,
+        auto_crop: false,
+        ..ComicConfig::default()
+    };
+    let page = |data| ArchiveFile {
+        file_name: "chapter/001.jpg".into(),
+        data,
+    };
+    let reused = reuse(&page(jpeg(300, 400, 85)), &config).unwrap();
+    assert_eq!(reused.data, jpeg(300, 400, 85));
+    assert_eq!(reused.dimensions, (300, 400));
+    assert_eq!(reused.file_name, "chapter_001_000.jpg");
+
+    // re-encoded at a lower quality, too big, split, in color or not opted in
+    assert!(reuse(&page(jpeg(300, 400, 60)), &config).is_none());
+    assert!(reuse(&page(jpeg(3000, 4000, 85)), &config).is_none());
+    assert!(reuse(&page(jpeg(400, 300, 85)), &config).is_none());
+    assert!(reuse(&page(sample_color_page(300, 400, 1)), &config).is_none());
+    let cropped = ComicConfig {
+        auto_crop: true,
+        ..config.clone()
+    };
+    assert!(reuse(&page(jpeg(300, 400, 85)), &cropped).is_none());
+    assert!(reuse(&page(jpeg(300, 400, 85)), &ComicConfig::default()).is_none());
+
+    // the page isn't decoded for the target that copies it, and is for the other
+    let config = ComicConfig {
+        page_cache: false,
+        ..config
+    };
+    let timings = std::sync::Mutex::new(Vec::new());
+    let outputs = crate::image::process_batch_targets(
+        vec![page(jpeg(300, 400, 85))],
+        &[config.clone(), cropped],
+        |timing| timings.lock().unwrap().push(timing.clone()),
+    )
+    .unwrap();
+    assert_eq!(outputs[0][0].data, jpeg(300, 400, 85));
+    assert_ne!(outputs[1][0].data, jpeg(300, 400, 85));
+    assert!(!timings.lock().unwrap()[0].reused);
+    let outputs =
+        crate::image::process_batch_targets(vec![page(jpeg(300, 400, 85))], &[config], |timing| {
+            assert!(timing.reused && !timing.cached)
+        })
+        .unwrap();
+    assert_eq!(outputs[0][0].page, 1);
+}
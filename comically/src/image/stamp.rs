@@ -0,0 +1,270 @@
+//! Overlays drawn on rendered pages: page numbers, with a built-in 5x7 bitmap font so
+//! no font file is needed, and watermark images. Translations use the same font, see
+//! [`super::overlay`]
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use imageproc::image::{imageops, GrayAlphaImage, GrayImage, Luma, LumaA};
+
+use crate::comic::{PageStamp, StampPosition, Watermark};
+
+pub(super) const GLYPH_WIDTH: u32 = 5;
+pub(super) const GLYPH_HEIGHT: u32 = 7;
+
+/// Text stamped on a page: its number, after the chapter when enabled
+pub fn label(stamp: &PageStamp, page: usize, chapter: Option<&str>) -> String {
+    match chapter {
+        Some(chapter) if stamp.chapter => format!("{} - {page}", chapter.to_uppercase()),
+        _ => page.to_string(),
+    }
+}
+
+/// Draws `text` in a box in the configured corner, blended with the page by the stamp's opacity
+pub fn stamp(img: &mut GrayImage, text: &str, stamp: &PageStamp) {
+    let scale = (stamp.size / GLYPH_HEIGHT).max(1);
+    let padding = scale * 2;
+    let chars = text.chars().count() as u32;
+    if chars == 0 {
+        return;
+    }
+
+    let box_width = text_width(text, scale) + padding * 2;
+    let box_height = GLYPH_HEIGHT * scale + padding * 2;
+    let margin = stamp.size / 2;
+    let Some((left, top)) = corner(
+        img.dimensions(),
+        (box_width, box_height),
+        margin,
+        stamp.position,
+    ) else {
+        return;
+    };
+
+    let opacity = stamp.opacity.clamp(0.0, 1.0);
+
+    // black text on a white box reads on both light and dark art
+    for y in top..top + box_height {
+        for x in left..left + box_width {
+            blend(img, x, y, 255, opacity);
+        }
+    }
+
+    draw_text(img, text, (left + padding, top + padding), scale, opacity);
+}
+
+/// Writes `text` in black with the bitmap font, each pixel of a glyph `scale` pixels wide
+pub(super) fn draw_text(
+    img: &mut GrayImage,
+    text: &str,
+    (left, top): (u32, u32),
+    scale: u32,
+    opacity: f32,
+) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph_left = left + i as u32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                let x = glyph_left + col * scale;
+                let y = top + row as u32 * scale;
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        if x + dx < img.width() && y + dy < img.height() {
+                            blend(img, x + dx, y + dy, 0, opacity);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Width of `text` written with the bitmap font at `scale`
+pub(super) fn text_width(text: &str, scale: u32) -> u32 {
+    (text.chars().count() as u32 * (GLYPH_WIDTH + 1) * scale).saturating_sub(scale)
+}
+
+/// Reads a watermark image once per conversion, keeping its transparency
+pub fn load_watermark(path: &Path) -> Result<GrayAlphaImage> {
+    let data =
+        fs::read(path).with_context(|| format!("Failed to read watermark {}", path.display()))?;
+    let img = super::decode::decode(&data)
+        .with_context(|| format!("Failed to decode watermark {}", path.display()))?;
+    Ok(img.into_luma_alpha8())
+}
+
+/// Draws `mark` in the configured corner, shrunk to at most a third of the page's width
+pub fn watermark(img: &mut GrayImage, mark: &GrayAlphaImage, watermark: &Watermark) {
+    let max_width = (img.width() / 3).max(1);
+    let resized;
+    let mark = if mark.width() > max_width {
+        let height = (mark.height() as u64 * max_width as u64 / mark.width() as u64).max(1);
+        resized = imageops::resize(
+            mark,
+            max_width,
+            height as u32,
+            imageops::FilterType::Triangle,
+        );
+        &resized
+    } else {
+        mark
+    };
+
+    let margin = img.width().min(img.height()) / 40;
+    let Some((left, top)) = corner(
+        img.dimensions(),
+        mark.dimensions(),
+        margin,
+        watermark.position,
+    ) else {
+        return;
+    };
+
+    let opacity = watermark.opacity.clamp(0.0, 1.0);
+    for (x, y, &LumaA([value, alpha])) in mark.enumerate_pixels() {
+        if alpha > 0 {
+            blend(
+                img,
+                left + x,
+                top + y,
+                value,
+                opacity * alpha as f32 / 255.0,
+            );
+        }
+    }
+}
+
+// top left of a `size` box `margin` away from the edges, if it fits in the page
+fn corner(
+    (width, height): (u32, u32),
+    (box_width, box_height): (u32, u32),
+    margin: u32,
+    position: StampPosition,
+) -> Option<(u32, u32)> {
+    if box_width + margin > width || box_height + margin > height {
+        return None;
+    }
+
+    Some(match position {
+        StampPosition::TopLeft => (margin, margin),
+        StampPosition::TopRight => (width - margin - box_width, margin),
+        StampPosition::BottomLeft => (margin, height - margin - box_height),
+        StampPosition::BottomRight => (width - margin - box_width, height - margin - box_height),
+    })
+}
+
+fn blend(img: &mut GrayImage, x: u32, y: u32, value: u8, opacity: f32) {
+    let Luma([current]) = *img.get_pixel(x, y);
+    let mixed = current as f32 * (1.0 - opacity) + value as f32 * opacity;
+    img.put_pixel(x, y, Luma([mixed.round() as u8]));
+}
+
+// rows from top to bottom, the low 5 bits from left to right
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'A' => [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        ' ' => [0; 7],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        '#' => [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a],
+        '/' => [0x01, 0x02, 0x02, 0x04, 0x08, 0x08, 0x10],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '\'' | '’' => [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '"' | '“' | '”' => [0x0a, 0x0a, 0x14, 0x00, 0x00, 0x00, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '…' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x15],
+        // anything the font doesn't cover
+        _ => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+#[test]
+fn stamps_the_configured_corner() {
+    let stamp_config = PageStamp {
+        position: StampPosition::BottomRight,
+        size: 14,
+        opacity: 1.0,
+        chapter: false,
+    };
+    let mut img = GrayImage::from_pixel(200, 100, Luma([128]));
+    stamp(&mut img, "12", &stamp_config);
+
+    // the margin and the rest of the page are untouched, the box is white with black text
+    assert_eq!(img.get_pixel(0, 0).0, [128]);
+    assert_eq!(img.get_pixel(195, 95).0, [128]);
+    let corner = (150..193).flat_map(|x| (60..93).map(move |y| (x, y)));
+    let values: Vec<u8> = corner.map(|(x, y)| img.get_pixel(x, y).0[0]).collect();
+    assert!(values.contains(&255) && values.contains(&0));
+
+    assert_eq!(label(&stamp_config, 3, Some("ch01")), "3");
+    let with_chapter = PageStamp {
+        chapter: true,
+        ..stamp_config
+    };
+    assert_eq!(label(&with_chapter, 3, Some("ch01")), "CH01 - 3");
+}
+
+#[test]
+fn watermarks_only_opaque_pixels() {
+    let mark = GrayAlphaImage::from_fn(20, 10, |x, _| LumaA([0, if x < 10 { 255 } else { 0 }]));
+    let config = Watermark {
+        opacity: 1.0,
+        ..Watermark::new("stamp.png".into())
+    };
+    let mut img = GrayImage::from_pixel(400, 200, Luma([200]));
+    watermark(&mut img, &mark, &config);
+
+    // 5px margin from the bottom right corner, the transparent half shows the page
+    assert_eq!(img.get_pixel(375, 190).0, [0]);
+    assert_eq!(img.get_pixel(390, 190).0, [200]);
+    assert_eq!(img.get_pixel(10, 10).0, [200]);
+
+    // too wide for the page, shrunk to a third of it
+    let mut narrow = GrayImage::from_pixel(30, 30, Luma([200]));
+    watermark(&mut narrow, &mark, &config);
+    let dark = narrow.pixels().filter(|p| p.0[0] < 100).count();
+    assert!(dark > 0 && dark <= 5 * 5);
+}
@@ -0,0 +1,263 @@
+//! What the pipeline sees in a page, for tools built on the library: the margins
+//! auto-crop would remove, whether it's a color page or a spread, and what it would
+//! weigh in each output format. The measures are the ones conversions use, so a
+//! preview agrees with what a conversion does to the page.
+
+use imageproc::image::{imageops, DynamicImage, GenericImageView, GrayImage};
+
+use super::transform::Image;
+use super::{encode, ImageFormat, PngCompression};
+
+// share of strongly saturated pixels above which a page counts as color
+const COLOR_PAGE_THRESHOLD: f32 = 0.05;
+// side of the central square encoded to estimate sizes, large enough to hold art and
+// small enough to keep analysis fast
+const SIZE_SAMPLE: u32 = 512;
+
+/// Statistics of a page, see [`analyze`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageStats {
+    pub dimensions: (u32, u32),
+    /// Pixels of each luminance, from black to white
+    pub histogram: [u32; 256],
+    /// White margins auto-crop would remove, `None` when it keeps the whole page
+    pub margins: Option<Margins>,
+    pub is_color: bool,
+    /// Wider than tall, a double page split or rotated by the configured strategy
+    pub is_spread: bool,
+    /// In 0.0-1.0, what adaptive quality picks a quality from, see [`complexity`]
+    pub complexity: f32,
+    pub estimated_sizes: EstimatedSizes,
+}
+
+/// Widths of the margins of a page, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Margins {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl PageStats {
+    /// The gray level half the pixels are darker than
+    pub fn median_level(&self) -> u8 {
+        let half = self
+            .histogram
+            .iter()
+            .map(|&count| u64::from(count))
+            .sum::<u64>()
+            / 2;
+        let mut seen = 0;
+        for (level, &count) in self.histogram.iter().enumerate() {
+            seen += u64::from(count);
+            if seen > half {
+                return level as u8;
+            }
+        }
+        255
+    }
+}
+
+impl Margins {
+    /// What's left of a page of `(width, height)`, as `(left, top, width, height)`
+    pub fn crop(&self, (width, height): (u32, u32)) -> (u32, u32, u32, u32) {
+        (
+            self.left,
+            self.top,
+            width.saturating_sub(self.left + self.right),
+            height.saturating_sub(self.top + self.bottom),
+        )
+    }
+}
+
+/// Bytes the grayscale page would take in each format, at their default settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EstimatedSizes {
+    pub jpeg: usize,
+    pub png: usize,
+    pub webp: usize,
+}
+
+/// Measures a decoded page
+pub fn analyze(page: &DynamicImage) -> PageStats {
+    let (width, height) = page.dimensions();
+    let luma = page.to_luma8();
+
+    let mut histogram = [0u32; 256];
+    for pixel in luma.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+
+    let luma = Image::from(luma);
+    let margins = luma
+        .crop_bounds()
+        .map(|(left, top, crop_width, crop_height)| Margins {
+            top,
+            right: width - left - crop_width,
+            bottom: height - top - crop_height,
+            left,
+        });
+    let luma = GrayImage::from(luma);
+
+    PageStats {
+        dimensions: (width, height),
+        histogram,
+        margins,
+        is_color: is_color(page),
+        is_spread: width > height,
+        complexity: complexity(page),
+        estimated_sizes: estimate_sizes(&luma),
+    }
+}
+
+/// Edge density of a page from 0.0 (flat) to 1.0 (busy), what adaptive quality picks a
+/// quality from, see [`super::AdaptiveQuality`].
+///
+/// Counts the sampled pixels whose gradient crosses an edge threshold, scaled so
+/// that heavily detailed artwork saturates at 1.0.
+pub fn complexity(img: &DynamicImage) -> f32 {
+    const STEP: u32 = 2;
+    const EDGE_THRESHOLD: i32 = 48;
+    const SATURATION: f32 = 0.25;
+
+    let luma;
+    let img = match img.as_luma8() {
+        Some(img) => img,
+        None => {
+            luma = img.to_luma8();
+            &luma
+        }
+    };
+
+    let (width, height) = img.dimensions();
+    if width < 2 || height < 2 {
+        return 0.0;
+    }
+
+    let mut edges = 0u32;
+    let mut samples = 0u32;
+    for y in (0..height - 1).step_by(STEP as usize) {
+        for x in (0..width - 1).step_by(STEP as usize) {
+            let p = img.get_pixel(x, y)[0] as i32;
+            let dx = (img.get_pixel(x + 1, y)[0] as i32 - p).abs();
+            let dy = (img.get_pixel(x, y + 1)[0] as i32 - p).abs();
+            if dx + dy > EDGE_THRESHOLD {
+                edges += 1;
+            }
+            samples += 1;
+        }
+    }
+
+    (edges as f32 / samples as f32 / SATURATION).min(1.0)
+}
+
+/// Whether enough of the page is saturated to count as a color page
+pub fn is_color(page: &DynamicImage) -> bool {
+    color_ratio(page) > COLOR_PAGE_THRESHOLD
+}
+
+// share of sampled pixels whose channels differ enough to not be gray
+fn color_ratio(img: &DynamicImage) -> f32 {
+    const STEP: u32 = 4;
+    const MIN_SPREAD: u8 = 40;
+
+    if img.color().channel_count() < 3 {
+        return 0.0;
+    }
+
+    let (width, height) = img.dimensions();
+    let mut colored = 0u32;
+    let mut samples = 0u32;
+    for y in (0..height).step_by(STEP as usize) {
+        for x in (0..width).step_by(STEP as usize) {
+            let [r, g, b, _] = img.get_pixel(x, y).0;
+            let spread = r.max(g).max(b) - r.min(g).min(b);
+            if spread > MIN_SPREAD {
+                colored += 1;
+            }
+            samples += 1;
+        }
+    }
+
+    if samples == 0 {
+        0.0
+    } else {
+        colored as f32 / samples as f32
+    }
+}
+
+// encodes the middle of the page and scales by area, the middle being where the art is
+fn estimate_sizes(page: &GrayImage) -> EstimatedSizes {
+    let (width, height) = page.dimensions();
+    let (sample_width, sample_height) = (width.min(SIZE_SAMPLE), height.min(SIZE_SAMPLE));
+    if sample_width == 0 || sample_height == 0 {
+        return EstimatedSizes {
+            jpeg: 0,
+            png: 0,
+            webp: 0,
+        };
+    }
+    let sample = imageops::crop_imm(
+        page,
+        (width - sample_width) / 2,
+        (height - sample_height) / 2,
+        sample_width,
+        sample_height,
+    )
+    .to_image();
+    let sample = DynamicImage::ImageLuma8(sample);
+
+    let scale = (width as f64 * height as f64) / (sample_width as f64 * sample_height as f64);
+    let size = |format: ImageFormat| {
+        (encode::encode_image(&sample, &format).len() as f64 * scale).round() as usize
+    };
+    EstimatedSizes {
+        jpeg: size(ImageFormat::Jpeg { quality: 85 }),
+        png: size(ImageFormat::Png {
+            compression: PngCompression::Default,
+        }),
+        webp: size(ImageFormat::WebP { quality: 85 }),
+    }
+}
+
+#[test]
+fn measures_pages() {
+    use imageproc::image::{Luma, Rgb, RgbImage};
+
+    // a gray page with wide white margins
+    let mut page = GrayImage::from_pixel(400, 600, Luma([255]));
+    for y in 100..500 {
+        for x in 80..320 {
+            page.put_pixel(x, y, Luma([((x + y) % 200) as u8]));
+        }
+    }
+    let stats = analyze(&DynamicImage::ImageLuma8(page));
+    assert_eq!(stats.dimensions, (400, 600));
+    assert_eq!(stats.histogram.iter().sum::<u32>(), 400 * 600);
+    assert!(stats.histogram[255] >= 400 * 600 - 240 * 400);
+    assert_eq!(stats.median_level(), 255);
+    let margins = stats.margins.unwrap();
+    assert!(margins.top > 50 && margins.top <= 100);
+    assert!(margins.left > 30 && margins.left <= 80);
+    let (left, top, width, height) = margins.crop(stats.dimensions);
+    assert_eq!(
+        (left + width + margins.right, top + height + margins.bottom),
+        (400, 600)
+    );
+    assert!(!stats.is_color);
+    assert!(!stats.is_spread);
+    assert!(stats.estimated_sizes.jpeg > 0);
+    assert!(stats.estimated_sizes.png > 0);
+    assert!(stats.estimated_sizes.webp > 0);
+
+    // a color spread, filled to the edges
+    let spread = RgbImage::from_fn(800, 600, |x, _| match x < 400 {
+        true => Rgb([200, 40, 40]),
+        false => Rgb([40, 40, 200]),
+    });
+    let stats = analyze(&DynamicImage::ImageRgb8(spread));
+    assert!(stats.is_color);
+    assert!(stats.is_spread);
+    assert_eq!(stats.margins, None);
+}
@@ -0,0 +1,153 @@
+//! Long strips: the pages of a comic stacked into a few tall images, for readers of
+//! webtoons and other continuously scrolled comics. Pages are encoded losslessly in
+//! between, the strips are encoded in the comic's format.
+
+use anyhow::{Context, Result};
+use imageproc::image::{DynamicImage, GrayImage};
+
+use std::sync::Mutex;
+
+use crate::comic::{ComicConfig, PageBackground, PagePart, ProcessedImage};
+use crate::image::{decode, encode, ImageFormat, PngCompression};
+
+/// How the pages are encoded before they're stacked
+pub const PAGE_FORMAT: ImageFormat = ImageFormat::Png {
+    compression: PngCompression::Fast,
+};
+
+/// `pages`, in order, stacked into strips `height` pixels tall, the last one shorter.
+///
+/// Strips are as wide as the widest page, narrower pages are centered on the page
+/// background. Each strip is numbered and named after its position, its `source` is the
+/// page it starts with.
+pub fn merge(
+    pages: Vec<ProcessedImage>,
+    height: u32,
+    config: &ComicConfig,
+) -> Result<Vec<ProcessedImage>> {
+    let _span = crate::trace::span!("strips", pages = pages.len(), height).entered();
+    let Some(width) = pages.iter().map(|page| page.dimensions.0).max() else {
+        return Ok(Vec::new());
+    };
+    let height = height.max(1);
+    let rows: u64 = pages.iter().map(|page| u64::from(page.dimensions.1)).sum();
+    let digits = rows.div_ceil(u64::from(height)).to_string().len().max(3);
+    let background = match config.page_background {
+        PageBackground::White => 255,
+        PageBackground::Black => 0,
+    };
+
+    let strips = Mutex::new(Vec::new());
+    let finish_strip = |pixels: Vec<u8>, number: usize, source: std::path::PathBuf| {
+        let rows = (pixels.len() / width as usize) as u32;
+        let img = GrayImage::from_raw(width, rows, pixels).expect("whole rows");
+        let img = DynamicImage::ImageLuma8(img);
+        let format = match config.adaptive_quality {
+            Some(adaptive) => adaptive.apply(config.image_format, &img),
+            None => config.image_format,
+        };
+        let strip = ProcessedImage {
+            file_name: format!("strip_{number:0digits$}.{}", format.extension()),
+            data: encode::encode_image(&img, &format),
+            dimensions: (width, rows),
+            format,
+            source,
+            page: number,
+            part: PagePart::Whole,
+            original_dimensions: (width, rows),
+            cropped: false,
+            small: None,
+            text: None,
+        };
+        strips.lock().unwrap().push(strip);
+    };
+
+    let finish_strip = &finish_strip;
+    // stacked on this thread, the strips are encoded in the pool as they fill up
+    rayon::scope(|scope| -> Result<()> {
+        let strip_len = width as usize * height as usize;
+        let mut pixels = Vec::with_capacity(strip_len);
+        let mut source = None;
+        let mut number = 0;
+        for page in &pages {
+            let img = decode::decode_luma(&page.data)
+                .with_context(|| format!("Failed to decode {}", page.file_name))?;
+            let img = GrayImage::from(img);
+            let left = (width - img.width()) as usize / 2;
+            let right = width as usize - left - img.width() as usize;
+            for row in img.rows() {
+                if pixels.is_empty() {
+                    source = Some(page.source.clone());
+                }
+                pixels.resize(pixels.len() + left, background);
+                pixels.extend(row.map(|pixel| pixel.0[0]));
+                pixels.resize(pixels.len() + right, background);
+                if pixels.len() == strip_len {
+                    number += 1;
+                    let full = std::mem::replace(&mut pixels, Vec::with_capacity(strip_len));
+                    let source = source.take().unwrap_or_default();
+                    scope.spawn(move |_| finish_strip(full, number, source));
+                }
+            }
+        }
+        if !pixels.is_empty() {
+            finish_strip(pixels, number + 1, source.unwrap_or_default());
+        }
+        Ok(())
+    })?;
+
+    let mut strips = strips.into_inner().unwrap();
+    strips.sort_unstable_by_key(|strip| strip.page);
+    log::info!("Stacked {} pages into {} strips", pages.len(), strips.len());
+    Ok(strips)
+}
+
+#[test]
+fn pages_are_stacked_into_strips() {
+    use imageproc::image::Luma;
+
+    let page = |shade, width, height| {
+        let img = DynamicImage::ImageLuma8(GrayImage::from_pixel(width, height, Luma([shade])));
+        ProcessedImage {
+            file_name: format!("{shade:03}.png"),
+            data: encode::encode_image(&img, &PAGE_FORMAT),
+            dimensions: (width, height),
+            format: PAGE_FORMAT,
+            source: format!("{shade:03}.jpg").into(),
+            page: 0,
+            part: PagePart::Whole,
+            original_dimensions: (width, height),
+            cropped: false,
+            small: None,
+            text: None,
+        }
+    };
+    let config = ComicConfig {
+        image_format: PAGE_FORMAT,
+        ..ComicConfig::default()
+    };
+    let pages = vec![page(10, 40, 30), page(20, 40, 50), page(30, 20, 25)];
+
+    let strips = merge(pages, 40, &config).unwrap();
+    let names: Vec<&str> = strips
+        .iter()
+        .map(|strip| strip.file_name.as_str())
+        .collect();
+    assert_eq!(names, ["strip_001.png", "strip_002.png", "strip_003.png"]);
+    let sizes: Vec<(u32, u32)> = strips.iter().map(|strip| strip.dimensions).collect();
+    assert_eq!(sizes, [(40, 40), (40, 40), (40, 25)]);
+    assert_eq!(strips[1].source, std::path::Path::new("020.jpg"));
+
+    let first = imageproc::image::load_from_memory(&strips[0].data)
+        .unwrap()
+        .into_luma8();
+    assert_eq!(first.get_pixel(0, 29).0, [10]);
+    assert_eq!(first.get_pixel(0, 30).0, [20]);
+    // the narrow page is centered on white
+    let last = imageproc::image::load_from_memory(&strips[2].data)
+        .unwrap()
+        .into_luma8();
+    assert_eq!(last.get_pixel(5, 10).0, [255]);
+    assert_eq!(last.get_pixel(20, 10).0, [30]);
+    assert_eq!(last.get_pixel(35, 10).0, [255]);
+}
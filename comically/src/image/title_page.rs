@@ -0,0 +1,182 @@
+//! A generated page with the title and credits of a volume, for comics without one, like
+//! volumes merged from chapters. The text comes from the series and metadata of the
+//! comic and is lettered the way [`overlay`] letters translations.
+
+use ab_glyph::FontVec;
+use anyhow::Result;
+use imageproc::image::{DynamicImage, GrayImage, Luma};
+
+use super::overlay::{self, TextBox};
+use crate::comic::{ComicConfig, PagePart, ProcessedImage, TitlePagePosition};
+
+/// Adds the title page to the images of a comic when the config asks for one
+pub fn insert(
+    images: &mut Vec<ProcessedImage>,
+    file_title: &str,
+    config: &ComicConfig,
+) -> Result<()> {
+    let Some(title_page) = &config.title_page else {
+        return Ok(());
+    };
+    let font = title_page
+        .font
+        .as_deref()
+        .map(overlay::load_font)
+        .transpose()?;
+
+    let mut page = render(file_title, config, font.as_ref());
+    // named to keep its place in CBZs, which readers sort by file name
+    let extension = config.image_format.extension();
+    let index = match (title_page.position, images.first()) {
+        (TitlePagePosition::AfterCover, Some(cover)) => {
+            let stem = cover
+                .file_name
+                .rsplit_once('.')
+                .map_or("", |(stem, _)| stem);
+            page.file_name = format!("{stem}_title_page.{extension}");
+            1
+        }
+        _ => {
+            page.file_name = format!("!title_page.{extension}");
+            0
+        }
+    };
+    images.insert(index, page);
+    Ok(())
+}
+
+/// The title page at the size of the device
+pub fn render(file_title: &str, config: &ComicConfig, font: Option<&FontVec>) -> ProcessedImage {
+    let (width, height) = config.device_dimensions();
+    let mut img = GrayImage::from_pixel(width, height, Luma([255]));
+
+    let margin = width / 10;
+    let text_box = |top: f32, size: f32, text: String| TextBox {
+        x: margin,
+        y: (height as f32 * top) as u32,
+        width: width - margin * 2,
+        height: (height as f32 * size) as u32,
+        text,
+    };
+    let boxes: Vec<TextBox> = [
+        text_box(0.2, 0.22, heading(file_title, config)),
+        text_box(0.45, 0.1, subheading(config)),
+        text_box(0.65, 0.2, credits(config)),
+    ]
+    .into_iter()
+    .filter(|text_box| !text_box.text.is_empty())
+    .collect();
+    overlay::draw(&mut img, &boxes, font);
+
+    ProcessedImage {
+        data: super::encode::encode_image(&DynamicImage::ImageLuma8(img), &config.image_format),
+        file_name: String::new(),
+        dimensions: (width, height),
+        format: config.image_format,
+        source: "title page".into(),
+        page: 0,
+        part: PagePart::Whole,
+        original_dimensions: (width, height),
+        cropped: false,
+        small: None,
+        text: None,
+    }
+}
+
+// the series, or the title without one
+fn heading(file_title: &str, config: &ComicConfig) -> String {
+    match &config.series {
+        Some(series) => series.name.clone(),
+        None => crate::metadata::title(file_title, config).to_string(),
+    }
+}
+
+// the volume and its own title, under the series
+fn subheading(config: &ComicConfig) -> String {
+    let Some(series) = &config.series else {
+        return String::new();
+    };
+    let volume = series.index.map(|index| format!("Vol. {index}"));
+    let title = config
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.title.clone());
+    [volume, title]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn credits(config: &ComicConfig) -> String {
+    let Some(metadata) = &config.metadata else {
+        return String::new();
+    };
+    let mut lines = Vec::new();
+    if !metadata.authors.is_empty() {
+        lines.push(format!("By {}", metadata.authors.join(", ")));
+    }
+    if !metadata.cover_artists.is_empty() {
+        lines.push(format!("Cover by {}", metadata.cover_artists.join(", ")));
+    }
+    lines.extend(metadata.publisher.clone());
+    lines.join("\n")
+}
+
+#[test]
+fn generates_title_pages() {
+    use crate::comic::{Series, TitlePage};
+    use crate::device::Device;
+    use crate::metadata::Metadata;
+
+    let mut config = ComicConfig {
+        device: Device::Custom {
+            width: 600,
+            height: 800,
+        },
+        series: Some(Series {
+            name: "Sample".into(),
+            index: Some(3.0),
+        }),
+        metadata: Some(Metadata {
+            authors: vec!["A. Author".into()],
+            publisher: Some("Publisher".into()),
+            ..Metadata::default()
+        }),
+        title_page: Some(TitlePage::default()),
+        ..ComicConfig::default()
+    };
+    assert_eq!(heading("sample v03", &config), "Sample");
+    assert_eq!(subheading(&config), "Vol. 3");
+    assert_eq!(credits(&config), "By A. Author\nPublisher");
+
+    let page = render("sample v03", &config, None);
+    assert_eq!(page.dimensions, (600, 800));
+    let img = super::decode::decode(&page.data).unwrap().into_luma8();
+    let inked = |top: u32, bottom: u32| {
+        (top..bottom)
+            .flat_map(|y| (0..600).map(move |x| (x, y)))
+            .filter(|&(x, y)| img.get_pixel(x, y).0[0] < 128)
+            .count()
+    };
+    assert!(inked(160, 336) > 100);
+    assert!(inked(520, 680) > 100);
+    assert_eq!(inked(0, 150), 0);
+
+    let mut images = vec![
+        crate::testing::processed_page(1, "001.jpg", PagePart::Whole),
+        crate::testing::processed_page(2, "002.jpg", PagePart::Whole),
+    ];
+    insert(&mut images, "sample v03", &config).unwrap();
+    assert_eq!(images[1].file_name, "001_Whole_title_page.jpg");
+    assert!(images[0].file_name < images[1].file_name);
+    assert!(images[1].file_name < images[2].file_name);
+
+    config.title_page = Some(TitlePage {
+        position: TitlePagePosition::BeforeCover,
+        ..TitlePage::default()
+    });
+    insert(&mut images, "sample v03", &config).unwrap();
+    assert_eq!(images[0].file_name, "!title_page.jpg");
+    assert_eq!(images.len(), 4);
+}
@@ -8,12 +8,18 @@ use imageproc::image::{imageops, GrayImage, Luma};
 use parking_lot::RwLock;
 
 use super::Split;
-use crate::comic::{ComicConfig, SplitStrategy};
+use crate::comic::{
+    Backend, ComicConfig, GammaCurve, MarginColor, PagePart, Rotation, SplitStrategy,
+};
 
 // Pixel values above this are considered "white"
 const WHITE_THRESHOLD: u8 = 230;
 // Minimum width to consider cropping
 const MIN_MARGIN_WIDTH: u32 = 10;
+// Border strips sampled for the paper color, as a fraction of the page
+const PAPER_BORDER: u32 = 20;
+// Paper darker than this is a page bled to the edges rather than yellowed paper
+const MIN_PAPER: u8 = 128;
 // Extra margin to keep, avoiding cutting content
 const SAFETY_MARGIN: u32 = 2;
 
@@ -21,6 +27,7 @@ const SAFETY_MARGIN: u32 = 2;
 /// Only computed once per unique gamma value (256 iterations) to avoid slow float operations
 struct GammaLut {
     gamma: f32,
+    srgb: bool,
     lut: [u8; 256],
 }
 
@@ -29,15 +36,21 @@ impl GammaLut {
         // NEG_INFINITY is used to indicate that the lut needs to be recomputed
         Self {
             gamma: f32::NEG_INFINITY,
+            srgb: false,
             lut: [0u8; 256],
         }
     }
 
-    fn recompute(&mut self, gamma: f32) {
+    fn recompute(&mut self, gamma: f32, srgb: bool) {
         self.gamma = gamma;
+        self.srgb = srgb;
         for (i, pixel) in self.lut.iter_mut().enumerate() {
             let normalized = i as f32 / 255.0;
-            let corrected = normalized.powf(gamma);
+            let corrected = if srgb {
+                srgb_encode(srgb_decode(normalized).powf(gamma))
+            } else {
+                normalized.powf(gamma)
+            };
             *pixel = (corrected * 255.0).round().clamp(0.0, 255.0) as u8;
         }
     }
@@ -47,18 +60,105 @@ impl GammaLut {
 /// Only computed once per unique gamma value (256 iterations) to avoid slow float operations
 static GAMMA_LUT: RwLock<GammaLut> = RwLock::new(GammaLut::new());
 
-fn gamma_lut(gamma: f32) -> [u8; 256] {
+fn gamma_lut(gamma: f32, srgb: bool) -> [u8; 256] {
     let lut = GAMMA_LUT.read();
-    if (lut.gamma - gamma).abs() >= 0.001 {
+    if (lut.gamma - gamma).abs() >= 0.001 || lut.srgb != srgb {
         drop(lut);
         let mut lut = GAMMA_LUT.write();
-        lut.recompute(gamma);
+        lut.recompute(gamma, srgb);
         lut.lut
     } else {
         lut.lut
     }
 }
 
+// a sigmoid scaled to go through (0, 0) and (1, 1), or its inverse for negative contrast
+fn contrast_lut(contrast: i32) -> [u8; 256] {
+    let steepness = contrast.clamp(-100, 100).unsigned_abs() as f32 / 10.0;
+    let sigmoid = |x: f32| 1.0 / (1.0 + (-steepness * (x - 0.5)).exp());
+    let (low, high) = (sigmoid(0.0), sigmoid(1.0));
+
+    let mut lut = [0u8; 256];
+    for (i, pixel) in lut.iter_mut().enumerate() {
+        let x = i as f32 / 255.0;
+        let y = if contrast > 0 {
+            (sigmoid(x) - low) / (high - low)
+        } else {
+            let s = low + x * (high - low);
+            0.5 - (1.0 / s - 1.0).ln() / steepness
+        };
+        *pixel = (y * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    lut
+}
+
+// sRGB value to linear light, both 0.0-1.0
+fn srgb_decode(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// linear light to sRGB value, both 0.0-1.0
+fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// scales gray levels so `paper` becomes white
+fn paper_lut(paper: u8) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    for (i, pixel) in lut.iter_mut().enumerate() {
+        *pixel = (i as f32 * 255.0 / paper as f32).round().min(255.0) as u8;
+    }
+    lut
+}
+
+// the gray levels `curve` maps to, `None` when it leaves them alone
+fn tone_lut(curve: &GammaCurve, gamma: f32) -> Option<[u8; 256]> {
+    match curve {
+        GammaCurve::Lut(lut) => Some(*lut.table()),
+        GammaCurve::Power | GammaCurve::Srgb => {
+            applied_gamma(gamma).map(|gamma| gamma_lut(gamma, matches!(curve, GammaCurve::Srgb)))
+        }
+    }
+}
+
+/// The tone adjustments of [`crate::image::adjust`] as lookup tables, for backends that
+/// apply them in one go: the paper white and gamma of `img` before autocontrast, and the
+/// contrast and brightness after it
+pub fn tone_luts(img: &Image, config: &ComicConfig) -> ([u8; 256], [u8; 256]) {
+    let identity: [u8; 256] = std::array::from_fn(|i| i as u8);
+    let paper = match config.paper_white {
+        true => img.paper_level().map(paper_lut),
+        false => None,
+    };
+    let tone = tone_lut(&config.gamma_curve, config.gamma);
+    let before = identity.map(|level| {
+        let level = paper.map_or(level, |lut| lut[level as usize]);
+        tone.map_or(level, |lut| lut[level as usize])
+    });
+
+    let contrast = (config.contrast != 0).then(|| contrast_lut(config.contrast));
+    let after = identity.map(|level| {
+        let level = contrast.map_or(level, |lut| lut[level as usize]);
+        (level as i32 + config.brightness).clamp(0, 255) as u8
+    });
+    (before, after)
+}
+
+/// The gamma [`Image::gamma`] corrects with, clamped to 0.1-3.0, `None` when it's close
+/// enough to 1.0 to leave the image alone
+pub fn applied_gamma(gamma: f32) -> Option<f32> {
+    let gamma = gamma.clamp(0.1, 3.0);
+    ((gamma - 1.0).abs() > 0.01).then_some(gamma)
+}
+
 /// Trait for zero-copy image views compatible with fast_image_resize.
 ///
 /// This trait abstracts over owned images ([`Image`]) and borrowed views
@@ -75,6 +175,12 @@ pub trait Img {
     /// May panic if coordinates are out of bounds.
     fn get_pixel(&self, x: u32, y: u32) -> u8;
 
+    /// The pixels of row `y`, for copying the image out row by row.
+    ///
+    /// # Panics
+    /// May panic if `y` is out of bounds.
+    fn row(&self, y: u32) -> &[u8];
+
     /// Creates a zero-copy cropped view of this image.
     fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> CroppedImage<'_>;
 
@@ -87,6 +193,7 @@ pub trait Img {
 /// This type integrates with `fast_image_resize` to avoid unnecessary copies
 /// during cropping and resizing operations. Pixel data is stored in row-major
 /// order as a flat `Vec<u8>`.
+#[derive(Clone)]
 pub struct Image {
     width: u32,
     height: u32,
@@ -99,18 +206,60 @@ impl Image {
         (self.width, self.height)
     }
 
+    /// Pixels in row-major order
+    pub fn as_raw(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// A copy of the `width`x`height` region at `x`, `y`
+    pub fn region(&self, x: u32, y: u32, width: u32, height: u32) -> Image {
+        let data = self
+            .data
+            .chunks_exact(self.width as usize)
+            .skip(y as usize)
+            .take(height as usize)
+            .flat_map(|row| &row[x as usize..(x + width) as usize])
+            .copied()
+            .collect();
+        Image {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Sets the `width`x`height` region at `x`, `y` to `level`, clipped to the image
+    pub fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, level: u8) {
+        if self.width == 0 {
+            return;
+        }
+        let right = x.saturating_add(width).min(self.width) as usize;
+        let bottom = y.saturating_add(height).min(self.height) as usize;
+        let rows = self.data.chunks_exact_mut(self.width as usize);
+        for row in rows.take(bottom).skip(y as usize) {
+            if let Some(pixels) = row.get_mut(x as usize..right) {
+                pixels.fill(level);
+            }
+        }
+    }
+
     /// Apply gamma correction to an image
     ///
     /// gamma - 0.1 to 3.0, where 1.0 = no change, <1 = brighter, >1 = more contrast
     #[inline]
-    pub fn gamma(mut self, gamma: f32) -> Image {
-        let gamma = gamma.clamp(0.1, 3.0);
-        // only apply gamma if it's not 1.0
-        if (gamma - 1.0).abs() > 0.01 {
-            let lut = gamma_lut(gamma);
-            for pixel in self.data.iter_mut() {
-                *pixel = lut[*pixel as usize];
-            }
+    pub fn gamma(self, gamma: f32) -> Image {
+        self.tone_curve(&GammaCurve::Power, gamma)
+    }
+
+    /// Apply gamma correction along `curve`, a LUT replaces every gray level with its
+    /// entry and ignores `gamma`
+    #[inline]
+    pub fn tone_curve(mut self, curve: &GammaCurve, gamma: f32) -> Image {
+        let Some(lut) = tone_lut(curve, gamma) else {
+            return self;
+        };
+        for pixel in self.data.iter_mut() {
+            *pixel = lut[*pixel as usize];
         }
         self
     }
@@ -129,6 +278,103 @@ impl Image {
         }
     }
 
+    /// Brings yellowed or gray paper to white, scaling every pixel by the paper level
+    /// estimated with [`Image::paper_level`]. Pages without a paper-like border are
+    /// left alone
+    #[inline]
+    pub fn paper_white(mut self) -> Image {
+        let Some(paper) = self.paper_level() else {
+            return self;
+        };
+        let lut = paper_lut(paper);
+        for pixel in self.data.iter_mut() {
+            *pixel = lut[*pixel as usize];
+        }
+        self
+    }
+
+    /// The gray level of the paper, sampled along the borders of the page where there's
+    /// usually nothing but paper. `None` when the paper is white already or the borders
+    /// are too dark to be paper
+    pub fn paper_level(&self) -> Option<u8> {
+        let (width, height) = self.dimensions();
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let (band_x, band_y) = (
+            (width / PAPER_BORDER).max(1),
+            (height / PAPER_BORDER).max(1),
+        );
+
+        let mut histogram = [0u64; 256];
+        for (y, row) in self.data.chunks_exact(width as usize).enumerate() {
+            let y = y as u32;
+            let border_row = y < band_y || y >= height - band_y;
+            for (x, &pixel) in row.iter().enumerate() {
+                let x = x as u32;
+                if border_row || x < band_x || x >= width - band_x {
+                    histogram[pixel as usize] += 1;
+                }
+            }
+        }
+
+        // the 90th percentile, past the art or page numbers that reach into the border
+        let total: u64 = histogram.iter().sum();
+        let mut seen = 0;
+        let paper = histogram.iter().position(|count| {
+            seen += count;
+            seen * 10 >= total * 9
+        })? as u8;
+        (MIN_PAPER..WHITE_THRESHOLD)
+            .contains(&paper)
+            .then_some(paper)
+    }
+
+    /// Apply an S-curve around mid-gray
+    ///
+    /// contrast - -100 to 100, where 0 = no change, >0 = deeper shadows and brighter
+    /// highlights, <0 = flatter midtones. Black and white stay put, unlike with gamma
+    #[inline]
+    pub fn contrast(mut self, contrast: i32) -> Image {
+        if contrast == 0 {
+            return self;
+        }
+        let lut = contrast_lut(contrast);
+        for pixel in self.data.iter_mut() {
+            *pixel = lut[*pixel as usize];
+        }
+        self
+    }
+
+    /// Floyd-Steinberg dithering down to `levels` evenly spaced gray levels, so the
+    /// gradients of the art don't band on screens that show few of them
+    pub fn dither(mut self, levels: u8) -> Image {
+        let width = self.width as usize;
+        if width == 0 || levels < 2 {
+            return self;
+        }
+        let step = 255.0 / f32::from(levels - 1);
+        // the error spread to the row being dithered and the one under it, offset by one
+        // so the pixels on the left edge have a left neighbor
+        let mut current = vec![0.0f32; width + 2];
+        let mut below = vec![0.0f32; width + 2];
+        for row in self.data.chunks_exact_mut(width) {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let value = (f32::from(*pixel) + current[x + 1]).clamp(0.0, 255.0);
+                let quantized = (value / step).round() * step;
+                *pixel = quantized.round() as u8;
+                let error = value - quantized;
+                current[x + 2] += error * 7.0 / 16.0;
+                below[x] += error * 3.0 / 16.0;
+                below[x + 1] += error * 5.0 / 16.0;
+                below[x + 2] += error / 16.0;
+            }
+            std::mem::swap(&mut current, &mut below);
+            below.fill(0.0);
+        }
+        self
+    }
+
     /// Apply autocontrast to an image
     ///
     /// This function stretches the contrast of the image to the full range of 0-255
@@ -153,11 +399,20 @@ impl Image {
     /// Auto-crop white margins from all sides of the image
     pub fn auto_crop(&self) -> CroppedImage<'_> {
         let (width, height) = self.dimensions();
+        match self.crop_bounds() {
+            Some((left, top, crop_width, crop_height)) => {
+                self.crop(left, top, crop_width, crop_height)
+            }
+            // If there's no margin worth cropping, keep the original image
+            None => self.crop(0, 0, width, height),
+        }
+    }
 
-        let Some(margins) = find_margins(self) else {
-            // If we didn't find any content, return the original image
-            return self.crop(0, 0, width, height);
-        };
+    /// What [`Image::auto_crop`] keeps, as left, top, width and height, `None` when it
+    /// keeps the whole image
+    pub fn crop_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let (width, height) = self.dimensions();
+        let margins = find_margins(self)?;
 
         let crop_width = margins.right.saturating_sub(margins.left).saturating_add(1);
         let crop_height = margins.bottom.saturating_sub(margins.top).saturating_add(1);
@@ -177,11 +432,12 @@ impl Image {
             && crop_height > 0
             && crop_height < height;
 
-        if should_crop_horizontal || should_crop_vertical {
-            self.crop(margins.left, margins.top, crop_width, crop_height)
-        } else {
-            self.crop(0, 0, width, height)
-        }
+        (should_crop_horizontal || should_crop_vertical).then_some((
+            margins.left,
+            margins.top,
+            crop_width,
+            crop_height,
+        ))
     }
 }
 
@@ -196,6 +452,12 @@ impl Img for Image {
         self.data[y as usize * self.width as usize + x as usize]
     }
 
+    #[inline(always)]
+    fn row(&self, y: u32) -> &[u8] {
+        let start = y as usize * self.width as usize;
+        &self.data[start..start + self.width as usize]
+    }
+
     #[inline(always)]
     fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> CroppedImage<'_> {
         let image = FrImageRef::new(
@@ -274,6 +536,13 @@ impl Img for CroppedImage<'_> {
         self.image.buffer()[y as usize * self.image.width() as usize + x as usize]
     }
 
+    #[inline(always)]
+    fn row(&self, y: u32) -> &[u8] {
+        assert!(y < self.height);
+        let start = (y + self.top) as usize * self.image.width() as usize + self.left as usize;
+        &self.image.buffer()[start..start + self.width as usize]
+    }
+
     #[inline(always)]
     fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> CroppedImage<'_> {
         let left = self.left + x;
@@ -299,50 +568,86 @@ impl Img for CroppedImage<'_> {
 /// and resizes the resulting images to fit the target device dimensions.
 ///
 /// # Returns
-/// A [`Split`] containing 1-3 processed images depending on the strategy.
-pub fn split_rotate<I: Img>(img: I, c: &ComicConfig) -> Split<Image> {
-    let target = c.device_dimensions();
+/// A [`Split`] containing 1-3 processed images depending on the strategy, each with
+/// the part of the source page it shows.
+pub fn split_rotate<I: Img>(img: I, c: &ComicConfig) -> Split<(Image, PagePart)> {
+    split_rotate_fitted(img, c, fit(c))
+}
+
+/// [`split_rotate`] with `margin` in place of the config's margin color, where the
+/// config has one, for the chroma planes of [`super::color`]
+pub fn split_rotate_filled<I: Img>(
+    img: I,
+    c: &ComicConfig,
+    margin: MarginColor,
+) -> Split<(Image, PagePart)> {
+    let (target, margin_color) = fit(c);
+    split_rotate_fitted(img, c, (target, margin_color.map(|_| margin)))
+}
+
+fn split_rotate_fitted<I: Img>(
+    img: I,
+    c: &ComicConfig,
+    (target, margin): ((u32, u32), Option<MarginColor>),
+) -> Split<(Image, PagePart)> {
     let (width, height) = img.dimensions();
     let is_double_page = width > height;
 
-    let margin = c.margin_color;
-
     match c.split {
+        SplitStrategy::None if is_double_page && c.landscape_spreads => {
+            // fit the screen turned sideways
+            let landscape = (target.1, target.0);
+            Split::one((resize(img, landscape, margin, c.backend), PagePart::Spread))
+        }
         SplitStrategy::None => {
             // Just resize, no splitting or rotation
-            Split::one(resize(img, target, margin))
+            Split::one((resize(img, target, margin, c.backend), PagePart::Whole))
         }
         SplitStrategy::Split => {
             if is_double_page {
-                split(&img, c)
+                split(&img, c, (target, margin))
             } else {
-                Split::one(resize(img, target, margin))
+                Split::one((resize(img, target, margin, c.backend), PagePart::Whole))
             }
         }
         SplitStrategy::Rotate => {
             if is_double_page {
                 let rotated = rotate_image_90(&img, c.right_to_left);
-                Split::one(resize(rotated, target, margin))
+                Split::one((
+                    resize(rotated, target, margin, c.backend),
+                    PagePart::Rotated,
+                ))
             } else {
-                Split::one(resize(img, target, margin))
+                Split::one((resize(img, target, margin, c.backend), PagePart::Whole))
             }
         }
         SplitStrategy::RotateAndSplit => {
             if is_double_page {
-                split_rotate_inner(&img, c)
+                split_rotate_inner(&img, c, (target, margin))
             } else {
-                Split::one(resize(img, target, margin))
+                Split::one((resize(img, target, margin, c.backend), PagePart::Whole))
             }
         }
     }
 }
 
-fn split<I: Img>(img: &I, c: &ComicConfig) -> Split<Image> {
+// the box pages are resized to and the color of the margins filling it, none when the
+// box is only a limit on the longest side
+fn fit(c: &ComicConfig) -> ((u32, u32), Option<MarginColor>) {
+    let margin = c.margin_color.filter(|_| c.max_dimension.is_none());
+    (c.page_box(), margin)
+}
+
+fn split<I: Img>(
+    img: &I,
+    c: &ComicConfig,
+    (target, margin): ((u32, u32), Option<MarginColor>),
+) -> Split<(Image, PagePart)> {
     // Split double pages
     let (left, right) = split_double_pages(img);
 
-    let left_resized = resize(left, c.device_dimensions(), c.margin_color);
-    let right_resized = resize(right, c.device_dimensions(), c.margin_color);
+    let left_resized = (resize(left, target, margin, c.backend), PagePart::Left);
+    let right_resized = (resize(right, target, margin, c.backend), PagePart::Right);
 
     // Determine order based on right_to_left setting
     let (first, second) = if c.right_to_left {
@@ -354,14 +659,21 @@ fn split<I: Img>(img: &I, c: &ComicConfig) -> Split<Image> {
     Split::two(first, second)
 }
 
-fn split_rotate_inner<I: Img>(img: &I, c: &ComicConfig) -> Split<Image> {
+fn split_rotate_inner<I: Img>(
+    img: &I,
+    c: &ComicConfig,
+    (target, margin): ((u32, u32), Option<MarginColor>),
+) -> Split<(Image, PagePart)> {
     let (left, right) = split_double_pages(img);
 
     let rotated = rotate_image_90(img, c.right_to_left);
-    let rotated_resized = resize(rotated, c.device_dimensions(), c.margin_color);
+    let rotated_resized = (
+        resize(rotated, target, margin, c.backend),
+        PagePart::Rotated,
+    );
 
-    let left_resized = resize(left, c.device_dimensions(), c.margin_color);
-    let right_resized = resize(right, c.device_dimensions(), c.margin_color);
+    let left_resized = (resize(left, target, margin, c.backend), PagePart::Left);
+    let right_resized = (resize(right, target, margin, c.backend), PagePart::Right);
 
     let (first, second) = if c.right_to_left {
         (right_resized, left_resized)
@@ -372,6 +684,15 @@ fn split_rotate_inner<I: Img>(img: &I, c: &ComicConfig) -> Split<Image> {
     Split::three(rotated_resized, first, second)
 }
 
+/// Applies the output rotation to a page that was already resized for the device.
+pub fn rotate_output(img: Image, rotation: Rotation) -> Image {
+    match rotation {
+        Rotation::None => img,
+        Rotation::Clockwise => rotate_image_90(&img, true),
+        Rotation::CounterClockwise => rotate_image_90(&img, false),
+    }
+}
+
 /// Splits a double-page spread into left and right halves (zero-copy).
 fn split_double_pages<I: Img>(img: &I) -> (CroppedImage<'_>, CroppedImage<'_>) {
     let (width, height) = img.dimensions();
@@ -403,12 +724,24 @@ fn rotate_image_90<I: Img>(img: &I, clockwise: bool) -> Image {
     rotated.into()
 }
 
+/// Shrinks `img` so its longest side is `longest`, `None` when it's already no larger
+pub fn shrink(img: Image, longest: u32) -> Option<Image> {
+    let (width, height) = img.dimensions();
+    (width.max(height) > longest).then(|| resize(img, (longest, longest), None, Backend::Cpu))
+}
+
 /// Resizes image to fit device dimensions with optional margins.
 ///
-/// Uses `fast_image_resize` with Lanczos3 for downscaling and CatmullRom for upscaling.
+/// Uses `fast_image_resize` with Lanczos3 for downscaling and CatmullRom for upscaling,
+/// or the same filters on the GPU for [`Backend::Gpu`], see [`super::gpu`].
 /// If the resized image doesn't exactly match the target dimensions and `margin_color`
 /// is specified, adds centered margins of the specified color.
-fn resize<I: Img>(img: I, device_dimensions: (u32, u32), margin_color: Option<u8>) -> Image {
+fn resize<I: Img>(
+    img: I,
+    device_dimensions: (u32, u32),
+    margin_color: Option<MarginColor>,
+    backend: Backend,
+) -> Image {
     let (target_width, target_height) = device_dimensions;
     let (width, height) = img.dimensions();
 
@@ -420,8 +753,35 @@ fn resize<I: Img>(img: I, device_dimensions: (u32, u32), margin_color: Option<u8
     let new_width = (width as f32 * ratio) as u32;
     let new_height = (height as f32 * ratio) as u32;
 
+    let resized = match super::gpu::resize(&img, (new_width, new_height), backend) {
+        Some(resized) => resized,
+        None => resample(&img, new_width, new_height, ratio < 1.0),
+    };
+
+    // If exact fit, return as-is
+    if new_width == target_width && new_height == target_height {
+        return resized.into();
+    }
+
+    // Add margins if requested
+    match margin_color {
+        Some(color) => {
+            let color = color.luma(|| border_luma(&resized));
+            let mut result = GrayImage::from_pixel(target_width, target_height, Luma([color]));
+            let x_offset = (target_width - new_width) / 2;
+            let y_offset = (target_height - new_height) / 2;
+            imageops::overlay(&mut result, &resized, x_offset.into(), y_offset.into());
+            result
+        }
+        None => resized,
+    }
+    .into()
+}
+
+// the CPU half of `resize`
+pub(crate) fn resample<I: Img>(img: &I, width: u32, height: u32, downscaling: bool) -> GrayImage {
     // Choose algorithm based on scaling direction
-    let algorithm = if ratio < 1.0 {
+    let algorithm = if downscaling {
         // Downscaling: Lanczos3 preserves detail
         fr::ResizeAlg::Convolution(fr::FilterType::Lanczos3)
     } else {
@@ -430,9 +790,9 @@ fn resize<I: Img>(img: I, device_dimensions: (u32, u32), margin_color: Option<u8
     };
 
     // Create destination buffer
-    let mut dst_buffer = vec![0u8; (new_width * new_height) as usize];
+    let mut dst_buffer = vec![0u8; (width * height) as usize];
     let mut dst_image =
-        FrImage::from_slice_u8(new_width, new_height, &mut dst_buffer, fr::PixelType::U8).unwrap();
+        FrImage::from_slice_u8(width, height, &mut dst_buffer, fr::PixelType::U8).unwrap();
 
     // Perform resize
     let mut resizer = fr::Resizer::new();
@@ -445,25 +805,35 @@ fn resize<I: Img>(img: I, device_dimensions: (u32, u32), margin_color: Option<u8
         .unwrap();
 
     // Convert back to GrayImage
-    let resized = GrayImage::from_raw(new_width, new_height, dst_buffer).unwrap();
+    GrayImage::from_raw(width, height, dst_buffer).unwrap()
+}
 
-    // If exact fit, return as-is
-    if new_width == target_width && new_height == target_height {
-        return resized.into();
+/// Median gray level of the outermost pixels, used to blend in auto margins
+fn border_luma(img: &GrayImage) -> u8 {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return 255;
     }
 
-    // Add margins if requested
-    match margin_color {
-        Some(color) => {
-            let mut result = GrayImage::from_pixel(target_width, target_height, Luma([color]));
-            let x_offset = (target_width - new_width) / 2;
-            let y_offset = (target_height - new_height) / 2;
-            imageops::overlay(&mut result, &resized, x_offset.into(), y_offset.into());
-            result
+    let mut histogram = [0u32; 256];
+    for x in 0..width {
+        histogram[img.get_pixel(x, 0)[0] as usize] += 1;
+        histogram[img.get_pixel(x, height - 1)[0] as usize] += 1;
+    }
+    for y in 0..height {
+        histogram[img.get_pixel(0, y)[0] as usize] += 1;
+        histogram[img.get_pixel(width - 1, y)[0] as usize] += 1;
+    }
+
+    let half = histogram.iter().sum::<u32>() / 2;
+    let mut seen = 0;
+    for (luma, count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen > half {
+            return luma as u8;
         }
-        None => resized,
     }
-    .into()
+    255
 }
 
 struct Margins {
@@ -0,0 +1,142 @@
+//! Super-resolution upscaling of low resolution scans, which stay blurry on 300ppi
+//! screens when they're only resampled.
+//!
+//! Pages go through [Real-ESRGAN](https://github.com/xinntao/Real-ESRGAN)'s
+//! `realesrgan-ncnn-vulkan` executable, the way MOBI files are built by running
+//! KindleGen. It's looked up in the PATH, or at `COMICALLY_REALESRGAN`, and only run when
+//! comically is built with the `super-resolution` feature. Otherwise pages are upscaled
+//! conventionally, with a warning saying why.
+
+use std::sync::OnceLock;
+
+use super::transform::Image;
+use crate::comic::{ComicConfig, UpscalePolicy};
+
+/// Whether [`UpscalePolicy::SuperResolution`] can run, or why not
+pub fn check() -> Result<(), String> {
+    static CHECK: OnceLock<Result<(), String>> = OnceLock::new();
+    CHECK.get_or_init(realesrgan::check).clone()
+}
+
+/// The page upscaled for the device when the policy asks for super-resolution and the
+/// page is smaller than the screen. `None` leaves it to the conventional resize.
+pub fn upscale(img: &Image, config: &ComicConfig) -> Option<Image> {
+    if config.upscale != UpscalePolicy::SuperResolution {
+        return None;
+    }
+    let scale = scale_for(img.dimensions(), config.page_box())?;
+
+    if let Err(reason) = check() {
+        static WARNED: OnceLock<()> = OnceLock::new();
+        WARNED.get_or_init(|| log::warn!("{reason}, upscaling conventionally"));
+        return None;
+    }
+
+    let _span = crate::trace::span!("upscale", scale).entered();
+    match realesrgan::run(img, scale, &crate::temp::TempRoot::for_config(config)) {
+        Ok(upscaled) => Some(upscaled),
+        Err(e) => {
+            log::warn!("Super-resolution failed, upscaling conventionally: {e:#}");
+            None
+        }
+    }
+}
+
+// pages of a comic share their height, spreads included, so it decides the scale
+fn scale_for((_, height): (u32, u32), (_, device_height): (u32, u32)) -> Option<u32> {
+    (height > 0 && height < device_height).then(|| device_height.div_ceil(height).clamp(2, 4))
+}
+
+#[cfg(feature = "super-resolution")]
+mod realesrgan {
+    use anyhow::{bail, Context, Result};
+    use imageproc::image::{ExtendedColorType, GrayImage};
+
+    use std::ffi::OsString;
+    use std::process::Command;
+
+    use crate::image::transform::Image;
+    use crate::temp::TempRoot;
+
+    // trained on anime and manga, and takes scales 2 to 4
+    const MODEL: &str = "realesr-animevideov3";
+
+    fn executable() -> OsString {
+        std::env::var_os("COMICALLY_REALESRGAN")
+            .unwrap_or_else(|| OsString::from("realesrgan-ncnn-vulkan"))
+    }
+
+    pub fn check() -> Result<(), String> {
+        match Command::new(executable()).arg("-h").output() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Can't run {}: {e}", executable().to_string_lossy())),
+        }
+    }
+
+    pub fn run(img: &Image, scale: u32, temp: &TempRoot) -> Result<Image> {
+        let dir = temp.create("upscale")?;
+        let (input, output) = (dir.path().join("page.png"), dir.path().join("upscaled.png"));
+        let (width, height) = img.dimensions();
+        imageproc::image::save_buffer(&input, img.as_raw(), width, height, ExtendedColorType::L8)
+            .context("Failed to write the page")?;
+
+        let result = Command::new(executable())
+            .arg("-i")
+            .arg(&input)
+            .arg("-o")
+            .arg(&output)
+            .args(["-n", MODEL, "-s", &scale.to_string()])
+            .output()
+            .context("Failed to run realesrgan-ncnn-vulkan")?;
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            bail!(
+                "realesrgan-ncnn-vulkan exited with {}: {}",
+                result.status,
+                stderr.lines().last().unwrap_or_default()
+            );
+        }
+
+        let upscaled: GrayImage = imageproc::image::open(&output)
+            .context("Failed to read the upscaled page")?
+            .into_luma8();
+        Ok(upscaled.into())
+    }
+}
+
+#[cfg(not(feature = "super-resolution"))]
+mod realesrgan {
+    use crate::image::transform::Image;
+    use crate::temp::TempRoot;
+
+    pub fn check() -> Result<(), String> {
+        Err("comically was built without the `super-resolution` feature".into())
+    }
+
+    pub fn run(_: &Image, _: u32, _: &TempRoot) -> anyhow::Result<Image> {
+        anyhow::bail!("comically was built without the `super-resolution` feature")
+    }
+}
+
+#[test]
+fn upscales_pages_smaller_than_the_screen() {
+    use imageproc::image::{GrayImage, Luma};
+
+    let device = (1236, 1648);
+    assert_eq!(scale_for((1000, 1400), device), Some(2));
+    assert_eq!(scale_for((300, 400), device), Some(4));
+    assert_eq!(scale_for((2000, 1400), device), Some(2));
+    assert_eq!(scale_for((1236, 1648), device), None);
+    assert_eq!(scale_for((1800, 2400), device), None);
+
+    // resampled like before when super-resolution can't run
+    let config = ComicConfig {
+        upscale: UpscalePolicy::SuperResolution,
+        ..ComicConfig::default()
+    };
+    let page = Image::from(GrayImage::from_pixel(100, 140, Luma([128])));
+    if check().is_err() {
+        assert!(upscale(&page, &config).is_none());
+    }
+    assert!(upscale(&page, &ComicConfig::default()).is_none());
+}
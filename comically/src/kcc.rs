@@ -0,0 +1,264 @@
+//! Compatibility with Kindle Comic Converter (KCC), for users moving over from it: its
+//! device profile names, its output file names and how settings map to its flags.
+
+use std::path::PathBuf;
+
+use crate::comic::{ComicConfig, ComicFile, GammaCurve, MarginColor, OutputFormat};
+use crate::device::{Device, Preset};
+use crate::image::ImageFormat;
+use crate::{SplitStrategy, UpscalePolicy};
+
+/// KCC profiles with their screen size and the preset for the same device, if any
+const PROFILES: [(&str, (u32, u32), Option<Preset>); 27] = [
+    ("K1", (600, 670), None),
+    ("K2", (600, 670), None),
+    ("K34", (600, 800), None),
+    ("K578", (600, 800), Some(Preset::KindleBasic)),
+    ("KDX", (824, 1000), None),
+    ("KPW", (758, 1024), None),
+    ("KV", (1072, 1448), None),
+    ("K11", (1072, 1448), Some(Preset::Kindle11)),
+    ("KPW5", (1236, 1648), Some(Preset::KindlePw11)),
+    ("KO", (1264, 1680), Some(Preset::KindleOasis)),
+    ("KS", (1860, 2480), Some(Preset::KindleScribe)),
+    ("KoMT", (600, 800), None),
+    ("KoG", (768, 1024), None),
+    ("KoGHD", (1072, 1448), None),
+    ("KoA", (758, 1024), None),
+    ("KoAHD", (1080, 1440), None),
+    ("KoAH2O", (1080, 1430), None),
+    ("KoAO", (1404, 1872), None),
+    ("KoN", (758, 1024), None),
+    ("KoC", (1072, 1448), Some(Preset::KoboClaraHd)),
+    ("KoL", (1264, 1680), Some(Preset::KoboLibra2)),
+    ("KoF", (1440, 1920), None),
+    ("KoS", (1440, 1920), Some(Preset::KoboSage)),
+    ("KoE", (1404, 1872), Some(Preset::KoboElipsa)),
+    ("Rmk1", (1404, 1872), None),
+    ("Rmk2", (1404, 1872), Some(Preset::Remarkable2)),
+    ("RmkPP", (1620, 2160), None),
+];
+
+/// The device of a KCC profile such as `KPW5` or `KoAHD`, ignoring case. Profiles
+/// without a preset become custom devices of the same size
+pub fn device(profile: &str) -> Option<Device> {
+    let (_, (width, height), preset) = PROFILES
+        .iter()
+        .find(|(name, ..)| name.eq_ignore_ascii_case(profile))?;
+    Some(match preset {
+        Some(preset) => Device::Preset(*preset),
+        None => Device::Custom {
+            width: *width,
+            height: *height,
+        },
+    })
+}
+
+/// The KCC profile of `device`, `None` when KCC has no profile for it
+pub fn profile(device: &Device) -> Option<&'static str> {
+    let preset = match device {
+        // the Clara 2E shares its profile with the Clara HD
+        Device::Preset(Preset::KoboClara2e) => Preset::KoboClaraHd,
+        Device::Preset(preset) => *preset,
+        Device::Custom { .. } => return None,
+    };
+    PROFILES
+        .iter()
+        .find(|(.., profile_preset)| *profile_preset == Some(preset))
+        .map(|(name, ..)| *name)
+}
+
+fn is_kobo(device: &Device) -> bool {
+    matches!(device, Device::Preset(preset) if preset.id().starts_with("kobo"))
+}
+
+/// The name KCC gives the output of `comic`: the title with the extension of the format,
+/// except EPUBs for Kobo, which are kepubs named after the title up to its first dot
+/// with everything but letters and digits left out
+pub fn output_name(comic: &ComicFile, config: &ComicConfig) -> PathBuf {
+    if config.output_format == OutputFormat::Epub && is_kobo(&config.device) {
+        let stem = comic.title().split('.').next().unwrap_or_default();
+        let mut name: String = stem.chars().filter(|c| c.is_alphanumeric()).collect();
+        if name.is_empty() {
+            name.push_str("KCCPlaceholder");
+        }
+        return format!("{name}.kepub.epub").into();
+    }
+    comic.with_extension(config.output_format)
+}
+
+/// How a setting of comically maps to KCC
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub setting: &'static str,
+    pub value: String,
+    /// The KCC flags doing the same, empty when KCC's default does
+    pub flags: Vec<String>,
+    /// Where KCC differs, `None` when the flags match the setting
+    pub note: Option<&'static str>,
+}
+
+impl Mapping {
+    fn new(setting: &'static str, value: impl ToString, flags: &[&str]) -> Self {
+        Self {
+            setting,
+            value: value.to_string(),
+            flags: flags.iter().map(|flag| flag.to_string()).collect(),
+            note: None,
+        }
+    }
+
+    fn note(mut self, note: &'static str) -> Self {
+        self.note = Some(note);
+        self
+    }
+}
+
+/// The settings of `config` next to the KCC flags closest to them
+pub fn compare(config: &ComicConfig) -> Vec<Mapping> {
+    let (width, height) = config.device_dimensions();
+    let device = match profile(&config.device) {
+        Some(profile) => Mapping::new("device", config.device.name(), &["-p", profile]),
+        None => Mapping::new(
+            "device",
+            format!("{width}x{height}"),
+            &[
+                "-p",
+                "OTHER",
+                "--customwidth",
+                &width.to_string(),
+                "--customheight",
+                &height.to_string(),
+            ],
+        ),
+    };
+
+    let format = match config.output_format {
+        OutputFormat::Mobi => "MOBI",
+        OutputFormat::Epub => "EPUB",
+        OutputFormat::Cbz => "CBZ",
+    };
+    let mut mappings = vec![
+        device,
+        Mapping::new("format", config.output_format.as_str(), &["-f", format]),
+        Mapping::new(
+            "right to left",
+            config.right_to_left,
+            if config.right_to_left { &["-m"] } else { &[] },
+        ),
+    ];
+
+    mappings.push(match config.split {
+        SplitStrategy::Split => Mapping::new("split", "split", &["-r", "0"]),
+        SplitStrategy::Rotate => Mapping::new("split", "rotate", &["-r", "1"]),
+        SplitStrategy::RotateAndSplit => Mapping::new("split", "rotate and split", &["-r", "2"]),
+        SplitStrategy::None => Mapping::new("split", "none", &["-r", "0"])
+            .note("KCC always splits or rotates double pages"),
+    });
+
+    mappings.push(match config.auto_crop {
+        true => Mapping::new("auto crop", true, &["-c", "1"])
+            .note("KCC crops page numbers too by default (-c 2)"),
+        false => Mapping::new("auto crop", false, &["-c", "0"]),
+    });
+
+    mappings.push(match &config.gamma_curve {
+        GammaCurve::Power => {
+            Mapping::new("gamma", config.gamma, &["-g", &config.gamma.to_string()])
+        }
+        curve => Mapping::new("gamma", curve.as_str(), &[])
+            .note("KCC only has a power curve, -g 0 picks one per page"),
+    });
+    if config.brightness != 0 {
+        mappings.push(
+            Mapping::new("brightness", config.brightness, &[])
+                .note("KCC has no brightness setting"),
+        );
+    }
+    if config.contrast != 0 {
+        mappings.push(
+            Mapping::new("contrast", config.contrast, &[]).note("KCC has no contrast setting"),
+        );
+    }
+
+    mappings.push(match config.margin_color {
+        Some(MarginColor::BLACK) => Mapping::new("margin color", "black", &["--blackborders"]),
+        Some(MarginColor::WHITE) => Mapping::new("margin color", "white", &["--whiteborders"]),
+        Some(color) => Mapping::new("margin color", color, &[])
+            .note("KCC only fills margins with black or white"),
+        None => Mapping::new("margin color", "none", &[])
+            .note("KCC picks black or white borders from each page"),
+    });
+
+    mappings.push(match config.image_format {
+        ImageFormat::Jpeg { quality } => {
+            Mapping::new("image format", "jpeg", &[]).note(match quality {
+                90.. => "KCC picks the JPEG quality itself, --hq is closest",
+                _ => "KCC picks the JPEG quality itself",
+            })
+        }
+        ImageFormat::Png { .. } => Mapping::new("image format", "png", &["--forcepng"]),
+        ImageFormat::WebP { .. } => {
+            Mapping::new("image format", "webp", &[]).note("KCC doesn't write WebP")
+        }
+    });
+
+    mappings.push(match config.upscale {
+        UpscalePolicy::Resample => Mapping::new("upscale", "resample", &["-u"])
+            .note("comically always enlarges small pages, KCC only with -u"),
+        UpscalePolicy::SuperResolution => Mapping::new("upscale", "super resolution", &["-u"])
+            .note("KCC resamples, it has no super-resolution"),
+    });
+
+    if config.keep_color {
+        mappings.push(Mapping::new("keep color", true, &["--forcecolor"]));
+    }
+    if !config.dither {
+        mappings
+            .push(Mapping::new("dither", false, &[]).note("KCC always dithers grayscale pages"));
+    }
+    if config.landscape_spreads {
+        mappings.push(
+            Mapping::new("landscape spreads", true, &[]).note("KCC has no landscape spreads"),
+        );
+    }
+    mappings
+}
+
+/// A KCC command line converting `input` like `config` does, as far as KCC can
+pub fn command_line(config: &ComicConfig, input: &str) -> String {
+    let flags: Vec<String> = compare(config)
+        .into_iter()
+        .flat_map(|mapping| mapping.flags)
+        .collect();
+    format!("kcc-c2e {} {input}", flags.join(" "))
+}
+
+#[test]
+fn kcc_profiles_and_names() {
+    assert_eq!(device("kpw5"), Some(Device::Preset(Preset::KindlePw11)));
+    assert_eq!(
+        device("KoAHD"),
+        Some(Device::Custom {
+            width: 1080,
+            height: 1440
+        })
+    );
+    assert_eq!(device("nope"), None);
+    assert_eq!(profile(&Preset::KoboClara2e.into()), Some("KoC"));
+    assert_eq!(profile(&Preset::IpadMini.into()), None);
+
+    let comic = ComicFile::new(PathBuf::from("Dr. STONE v01 (2018).cbz"));
+    let mut config = ComicConfig::default();
+    assert_eq!(
+        output_name(&comic, &config),
+        PathBuf::from("Dr. STONE v01 (2018).mobi")
+    );
+    config.set_device(Preset::KoboLibra2.into());
+    config.output_format = OutputFormat::Epub;
+    assert_eq!(output_name(&comic, &config), PathBuf::from("Dr.kepub.epub"));
+
+    config.margin_color = Some(MarginColor::BLACK);
+    let command = command_line(&config, "in.cbz");
+    assert!(command.starts_with("kcc-c2e -p KoL -f EPUB -m -r 2 -c 1 -g 1.8 --blackborders"));
+}
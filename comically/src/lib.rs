@@ -1,12 +1,45 @@
 pub mod archive;
+pub mod batch;
+pub mod cache;
 pub mod cbz;
+pub mod checkpoint;
 pub mod comic;
+pub mod contact_sheet;
+pub mod cover;
 pub mod device;
+pub mod dir_config;
+pub mod direction;
 pub mod epub;
+pub mod filename;
 pub mod image;
+pub mod kcc;
+pub mod metadata;
+pub mod migrate;
 pub mod mobi;
+pub mod nice;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod opds;
+pub mod output;
+pub mod repair;
+#[cfg(any(test, feature = "sample"))]
+pub mod sample;
+pub mod source;
+pub mod temp;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
+mod trace;
+pub mod tune;
 
 // Re-export commonly used types
-pub use comic::{ComicConfig, ComicFile, OutputFormat, ProcessedImage, SplitStrategy};
-pub use image::{ImageFormat, PngCompression};
-pub use mobi::is_kindlegen_available;
+pub use comic::{
+    AnimatedPages, Backend, ComicConfig, ComicFile, Concurrency, FixedCrop, GammaCurve, Lut,
+    MarginColor, OutputFormat, OutputNaming, PageBackground, PagePart, PageRange, PageRanges,
+    PageSide, PageStamp, PlaceholderPages, ProcessedImage, Rotation, Series, SmallCopy,
+    SplitStrategy, StampPosition, TitlePage, TitlePagePosition, TranslationOverlay, UpscalePolicy,
+    Watermark,
+};
+pub use image::filter::PageFilter;
+pub use image::{AdaptiveQuality, ImageFormat, PngCompression};
+pub use metadata::Metadata;
+pub use mobi::{is_kindlegen_available, kindlegen_version};
@@ -0,0 +1,123 @@
+//! Details about a volume beyond its file name, written to the OPF of EPUBs and to a
+//! `ComicInfo.xml` in CBZs. They can be looked up online with [`fetch`] when built with
+//! the `fetch` feature.
+
+#[cfg(feature = "fetch")]
+pub mod fetch;
+
+use crate::comic::ComicConfig;
+use crate::opds::escape;
+
+/// Book metadata, every field is optional
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Metadata {
+    /// Replaces the file name as the title of the book
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+    pub cover_artists: Vec<String>,
+    pub publisher: Option<String>,
+    /// Page the metadata was taken from
+    pub source_url: Option<String>,
+}
+
+impl Metadata {
+    /// Fills the fields this one doesn't have from `other`
+    pub fn merge(&mut self, other: Metadata) {
+        self.title = self.title.take().or(other.title);
+        if self.authors.is_empty() {
+            self.authors = other.authors;
+        }
+        self.description = self.description.take().or(other.description);
+        if self.cover_artists.is_empty() {
+            self.cover_artists = other.cover_artists;
+        }
+        self.publisher = self.publisher.take().or(other.publisher);
+        self.source_url = self.source_url.take().or(other.source_url);
+    }
+}
+
+/// Title of the book, the metadata's when there is one
+pub fn title<'a>(file_title: &'a str, config: &'a ComicConfig) -> &'a str {
+    config
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.title.as_deref())
+        .unwrap_or(file_title)
+}
+
+/// `ComicInfo.xml` describing the output, `None` without any series or metadata
+pub fn comic_info_xml(file_title: &str, config: &ComicConfig) -> Option<String> {
+    if config.series.is_none() && config.metadata.is_none() {
+        return None;
+    }
+
+    let mut fields = Vec::new();
+    let mut field = |tag: &str, value: &str| {
+        fields.push(format!("  <{tag}>{}</{tag}>", escape(value)));
+    };
+
+    field("Title", title(file_title, config));
+    if let Some(series) = &config.series {
+        field("Series", &series.name);
+        if let Some(index) = series.index {
+            field("Number", &index.to_string());
+        }
+    }
+    if let Some(metadata) = &config.metadata {
+        if !metadata.authors.is_empty() {
+            field("Writer", &metadata.authors.join(", "));
+        }
+        if let Some(description) = &metadata.description {
+            field("Summary", description);
+        }
+        if !metadata.cover_artists.is_empty() {
+            field("CoverArtist", &metadata.cover_artists.join(", "));
+        }
+        if let Some(publisher) = &metadata.publisher {
+            field("Publisher", publisher);
+        }
+        if let Some(url) = &metadata.source_url {
+            field("Web", url);
+        }
+    }
+    field(
+        "Manga",
+        if config.right_to_left {
+            "YesAndRightToLeft"
+        } else {
+            "No"
+        },
+    );
+
+    Some(format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ComicInfo xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\">\n{}\n</ComicInfo>\n",
+        fields.join("\n")
+    ))
+}
+
+#[test]
+fn comic_info_round_trips_series() {
+    let config = ComicConfig {
+        series: Some(crate::Series {
+            name: "Blame!".into(),
+            index: Some(2.0),
+        }),
+        metadata: Some(Metadata {
+            authors: vec!["Tsutomu Nihei".into()],
+            description: Some("Killy <wanders> the City".into()),
+            ..Metadata::default()
+        }),
+        ..ComicConfig::default()
+    };
+
+    let xml = comic_info_xml("Blame! v02", &config).unwrap();
+    assert!(xml.contains("<Writer>Tsutomu Nihei</Writer>"));
+    assert!(xml.contains("<Summary>Killy &lt;wanders&gt; the City</Summary>"));
+    assert_eq!(
+        crate::Series::from_comic_info(&xml),
+        config.series,
+        "the series is read back by later conversions"
+    );
+    assert!(comic_info_xml("Blame! v02", &ComicConfig::default()).is_none());
+}
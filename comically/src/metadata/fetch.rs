@@ -0,0 +1,298 @@
+//! Looking up a series online to fill in its [`Metadata`]. AniList works without an
+//! account, ComicVine needs an API key from the `metadata_sources` section of the
+//! config file:
+//!
+//! ```json
+//! "metadata_sources": { "default": "comicvine", "comicvine_api_key": "..." }
+//! ```
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use std::path::Path;
+use std::time::Duration;
+
+use super::Metadata;
+use crate::comic::{ComicConfig, Series};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, strum::Display)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    #[default]
+    #[strum(to_string = "AniList")]
+    AniList,
+    #[strum(to_string = "ComicVine")]
+    ComicVine,
+}
+
+/// The `metadata_sources` section of the config file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SourceConfig {
+    /// Source used when none is picked explicitly
+    #[serde(default)]
+    pub default: Source,
+    #[serde(default)]
+    pub comicvine_api_key: Option<String>,
+}
+
+impl SourceConfig {
+    /// Reads the section from the config file, empty when the file doesn't have one
+    pub fn load(config_path: Option<&Path>) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct File {
+            #[serde(default)]
+            metadata_sources: SourceConfig,
+        }
+
+        let path = match config_path {
+            Some(path) => path.to_path_buf(),
+            None => match ComicConfig::config_path() {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+        };
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && config_path.is_none() => {
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read config {}", path.display()))
+            }
+        };
+        let file: File = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse config {}", path.display()))?;
+        Ok(file.metadata_sources)
+    }
+}
+
+/// A series matching the search, to be confirmed before it's applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub series: String,
+    /// Year the series started
+    pub year: Option<u32>,
+    pub metadata: Metadata,
+}
+
+impl Candidate {
+    /// Name and year, to pick between candidates
+    pub fn label(&self) -> String {
+        match self.year {
+            Some(year) => format!("{} ({year})", self.series),
+            None => self.series.clone(),
+        }
+    }
+
+    /// Fills in the series and metadata of `config`, keeping what was set explicitly
+    pub fn apply(&self, config: &mut ComicConfig) {
+        if config.series.is_none() {
+            config.series = Some(Series {
+                name: self.series.clone(),
+                index: None,
+            });
+        }
+        config
+            .metadata
+            .get_or_insert_with(Metadata::default)
+            .merge(self.metadata.clone());
+    }
+}
+
+/// Series matching `query`, best match first
+pub fn search(source: Source, sources: &SourceConfig, query: &str) -> Result<Vec<Candidate>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(20)))
+        .build()
+        .into();
+
+    match source {
+        Source::AniList => {
+            let response: Value = agent
+                .post("https://graphql.anilist.co")
+                .send_json(json!({ "query": ANILIST_QUERY, "variables": { "search": query } }))
+                .context("AniList request failed")?
+                .body_mut()
+                .read_json()
+                .context("Unexpected AniList response")?;
+            parse_anilist(&response)
+        }
+        Source::ComicVine => {
+            let Some(key) = &sources.comicvine_api_key else {
+                bail!(
+                    "ComicVine needs an API key, set `comicvine_api_key` in the `metadata_sources` section of the config"
+                );
+            };
+            let response: Value = agent
+                .get("https://comicvine.gamespot.com/api/search/")
+                .header(
+                    "User-Agent",
+                    concat!("comically/", env!("CARGO_PKG_VERSION")),
+                )
+                .query("api_key", key)
+                .query("format", "json")
+                .query("resources", "volume")
+                .query("limit", "5")
+                .query(
+                    "field_list",
+                    "name,start_year,publisher,deck,description,site_detail_url",
+                )
+                .query("query", query)
+                .call()
+                .context("ComicVine request failed")?
+                .body_mut()
+                .read_json()
+                .context("Unexpected ComicVine response")?;
+            parse_comicvine(&response)
+        }
+    }
+}
+
+const ANILIST_QUERY: &str = "query ($search: String) {
+  Page(perPage: 5) {
+    media(search: $search, type: MANGA) {
+      siteUrl
+      title { romaji english }
+      description(asHtml: false)
+      startDate { year }
+      staff { edges { role node { name { full } } } }
+    }
+  }
+}";
+
+fn parse_anilist(response: &Value) -> Result<Vec<Candidate>> {
+    if let Some(message) = response["errors"][0]["message"].as_str() {
+        bail!("AniList: {message}");
+    }
+    let media = response["data"]["Page"]["media"]
+        .as_array()
+        .context("Unexpected AniList response")?;
+
+    Ok(media
+        .iter()
+        .filter_map(|media| {
+            let title = &media["title"];
+            let series = title["english"].as_str().or(title["romaji"].as_str())?;
+
+            // roles read "Story & Art", "Story", "Art", "Art (eps 1-3)", ...
+            let staff = media["staff"]["edges"].as_array();
+            let with_role = |role: &str| -> Vec<String> {
+                staff
+                    .into_iter()
+                    .flatten()
+                    .filter(|edge| edge["role"].as_str().is_some_and(|r| r.contains(role)))
+                    .filter_map(|edge| edge["node"]["name"]["full"].as_str().map(String::from))
+                    .collect()
+            };
+
+            Some(Candidate {
+                series: series.to_string(),
+                year: media["startDate"]["year"].as_u64().map(|year| year as u32),
+                metadata: Metadata {
+                    title: None,
+                    authors: with_role("Story"),
+                    description: media["description"].as_str().map(plain_text),
+                    cover_artists: with_role("Art"),
+                    publisher: None,
+                    source_url: media["siteUrl"].as_str().map(String::from),
+                },
+            })
+        })
+        .collect())
+}
+
+// ComicVine's search doesn't list the creators of a volume
+fn parse_comicvine(response: &Value) -> Result<Vec<Candidate>> {
+    if response["status_code"].as_u64() != Some(1) {
+        let error = response["error"].as_str().unwrap_or("unknown error");
+        bail!("ComicVine: {error}");
+    }
+    let results = response["results"]
+        .as_array()
+        .context("Unexpected ComicVine response")?;
+
+    Ok(results
+        .iter()
+        .filter_map(|volume| {
+            let description = volume["deck"]
+                .as_str()
+                .or(volume["description"].as_str())
+                .map(plain_text)
+                .filter(|description| !description.is_empty());
+            Some(Candidate {
+                series: volume["name"].as_str()?.to_string(),
+                year: volume["start_year"]
+                    .as_str()
+                    .and_then(|year| year.parse().ok()),
+                metadata: Metadata {
+                    description,
+                    publisher: volume["publisher"]["name"].as_str().map(String::from),
+                    source_url: volume["site_detail_url"].as_str().map(String::from),
+                    ..Metadata::default()
+                },
+            })
+        })
+        .collect())
+}
+
+// descriptions come with html line breaks and links
+fn plain_text(html: &str) -> String {
+    let html = html.replace("<br>", "\n").replace("<br />", "\n");
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+#[test]
+fn parses_search_results() {
+    let anilist = json!({ "data": { "Page": { "media": [{
+        "siteUrl": "https://anilist.co/manga/86635",
+        "title": { "romaji": "Dungeon Meshi", "english": "Delicious in Dungeon" },
+        "description": "Adventurers cook<br>the monsters they fight.",
+        "startDate": { "year": 2014 },
+        "staff": { "edges": [
+            { "role": "Story & Art", "node": { "name": { "full": "Ryoko Kui" } } },
+            { "role": "Translator", "node": { "name": { "full": "Taylor Engel" } } },
+        ] },
+    }] } } });
+    let candidates = parse_anilist(&anilist).unwrap();
+    assert_eq!(candidates[0].label(), "Delicious in Dungeon (2014)");
+    assert_eq!(candidates[0].metadata.authors, ["Ryoko Kui"]);
+    assert_eq!(candidates[0].metadata.cover_artists, ["Ryoko Kui"]);
+    assert_eq!(
+        candidates[0].metadata.description.as_deref(),
+        Some("Adventurers cook\nthe monsters they fight.")
+    );
+
+    let comicvine = json!({ "status_code": 1, "error": "OK", "results": [{
+        "name": "Saga",
+        "start_year": "2012",
+        "publisher": { "name": "Image" },
+        "deck": null,
+        "description": "<p>An epic space opera.</p>",
+        "site_detail_url": "https://comicvine.gamespot.com/saga/4050-48942/",
+    }] });
+    let candidates = parse_comicvine(&comicvine).unwrap();
+    assert_eq!(candidates[0].year, Some(2012));
+    assert_eq!(candidates[0].metadata.publisher.as_deref(), Some("Image"));
+    assert_eq!(
+        candidates[0].metadata.description.as_deref(),
+        Some("An epic space opera.")
+    );
+
+    let invalid_key = json!({ "status_code": 100, "error": "Invalid API Key", "results": [] });
+    assert!(parse_comicvine(&invalid_key).is_err());
+
+    let mut config = ComicConfig::default();
+    candidates[0].apply(&mut config);
+    assert_eq!(config.series.unwrap().name, "Saga");
+}
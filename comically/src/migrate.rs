@@ -0,0 +1,189 @@
+//! Upgrades config files written by older releases to the current shape, so settings
+//! survive format changes instead of silently resetting to the defaults.
+//!
+//! Migrations work on the raw JSON, which also holds sections owned by the frontends
+//! (keymap, themes, servers) that must be kept as-is.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::comic::ComicConfig;
+use crate::device::{Device, Preset};
+
+/// Version written to config files, bumped with every migration
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Brings a parsed config file to [`CONFIG_VERSION`], returning whether anything changed
+pub fn migrate(config: &mut Value) -> bool {
+    let Some(fields) = config.as_object_mut() else {
+        return false;
+    };
+
+    let version = fields.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > CONFIG_VERSION as u64 {
+        log::warn!("config version {version} is newer than this release's ({CONFIG_VERSION})");
+        return false;
+    }
+
+    let mut changed = false;
+    if version < 1 {
+        v0_to_v1(fields);
+        changed = true;
+    }
+
+    // settings that depend on the screen start from the tuning of the file's device
+    let device = fields
+        .get("device")
+        .cloned()
+        .map(serde_json::from_value::<Device>);
+    if let Some(Ok(device)) = device {
+        let tuning = device.tuning();
+        for (key, value) in [("dither", tuning.dither), ("keep_color", tuning.keep_color)] {
+            if !fields.contains_key(key) {
+                fields.insert(key.into(), value.into());
+                changed = true;
+            }
+        }
+    }
+
+    // settings added since the file was written
+    let defaults = serde_json::to_value(ComicConfig::default()).expect("config always serializes");
+    if let Value::Object(defaults) = defaults {
+        for (key, value) in defaults {
+            if !fields.contains_key(&key) {
+                fields.insert(key, value);
+                changed = true;
+            }
+        }
+    }
+
+    fields.insert("version".into(), CONFIG_VERSION.into());
+    changed
+}
+
+// the first releases stored the screen size and a JPEG quality instead of a device and format
+fn v0_to_v1(fields: &mut Map<String, Value>) {
+    if let Some(dimensions) = fields.remove("device_dimensions") {
+        let size: Option<(u32, u32)> = serde_json::from_value(dimensions).ok();
+        if let (Some((width, height)), false) = (size, fields.contains_key("device")) {
+            let device = Preset::iter()
+                .find(|preset| preset.dimensions() == (width, height))
+                .map_or(Device::Custom { width, height }, Device::Preset);
+            let device = serde_json::to_value(device).expect("devices always serialize");
+            fields.insert("device".into(), device);
+        }
+    }
+
+    if let Some(quality) = fields.remove("compression_quality") {
+        if !fields.contains_key("image_format") {
+            fields.insert(
+                "image_format".into(),
+                json!({ "Jpeg": { "quality": quality } }),
+            );
+        }
+    }
+}
+
+/// Parses the config file at `path`, migrating it when it's from an older release.
+/// Upgraded files are written back with the original kept next to them as `.bak`.
+pub fn upgrade_file(path: &Path, contents: &str) -> Result<Value> {
+    let mut config: Value = serde_json::from_str(contents)
+        .with_context(|| format!("failed to parse config {}", path.display()))?;
+    if !migrate(&mut config) {
+        return Ok(config);
+    }
+
+    log::info!(
+        "upgrading config {} to version {CONFIG_VERSION}",
+        path.display()
+    );
+    let backup = backup_path(path);
+    let written = fs::write(&backup, contents)
+        .and_then(|_| fs::write(path, serde_json::to_string_pretty(&config)?));
+    if let Err(e) = written {
+        log::warn!("failed to write upgraded config {}: {e}", path.display());
+    }
+    Ok(config)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+#[test]
+fn migrates_screen_size_configs() {
+    let mut config = json!({
+        "device_dimensions": [1236, 1648],
+        "right_to_left": false,
+        "split": "Split",
+        "auto_crop": true,
+        "brightness": 0,
+        "gamma": 1.4,
+        "output_format": "Epub",
+        "margin_color": null,
+        "compression_quality": 90,
+    });
+    assert!(migrate(&mut config));
+    let migrated: ComicConfig = serde_json::from_value(config).unwrap();
+    assert_eq!(migrated.device, Device::Preset(Preset::KindlePw11));
+    assert_eq!(
+        migrated.image_format,
+        crate::ImageFormat::Jpeg { quality: 90 }
+    );
+    assert!(!migrated.right_to_left);
+    assert_eq!(migrated.gamma, 1.4);
+    assert_eq!(migrated.version, CONFIG_VERSION);
+
+    // sizes that aren't a preset become a custom device
+    let mut config = json!({ "device_dimensions": [1000, 1400] });
+    migrate(&mut config);
+    let migrated: ComicConfig = serde_json::from_value(config).unwrap();
+    assert_eq!(
+        migrated.device,
+        Device::Custom {
+            width: 1000,
+            height: 1400
+        }
+    );
+}
+
+#[test]
+fn migrates_unversioned_device_configs() {
+    // written before the version field, with a gray level margin and a few settings missing
+    let mut config = json!({
+        "device": { "Preset": "KoboLibra2" },
+        "right_to_left": true,
+        "split": "RotateAndSplit",
+        "auto_crop": false,
+        "brightness": -10,
+        "gamma": 1.8,
+        "output_format": "Mobi",
+        "margin_color": 30,
+        "image_format": { "Png": { "compression": "Best" } },
+        "keymap": { "start": ["enter"] },
+    });
+    assert!(migrate(&mut config));
+    assert_eq!(config["keymap"]["start"][0], "enter");
+    let migrated: ComicConfig = serde_json::from_value(config).unwrap();
+    assert_eq!(migrated.device, Device::Preset(Preset::KoboLibra2));
+    assert_eq!(migrated.margin_color, Some(crate::MarginColor::Gray(30)));
+    assert!(!migrated.auto_crop);
+    assert!(migrated.page_cache);
+
+    assert!(migrated.dither && !migrated.keep_color);
+
+    // color screens keep color pages
+    let mut config = json!({ "version": 1, "device": { "Preset": "Ipad109" } });
+    assert!(migrate(&mut config));
+    let migrated: ComicConfig = serde_json::from_value(config).unwrap();
+    assert!(!migrated.dither && migrated.keep_color);
+
+    // current configs are left alone
+    let mut current = serde_json::to_value(ComicConfig::default()).unwrap();
+    assert!(!migrate(&mut current));
+}
@@ -1,30 +1,70 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use imageproc::image::imageops::FilterType;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
 use std::fs;
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::comic::{ComicConfig, ProcessedImage, Series};
 
 /// Converts an EPUB file to MOBI using Amazon's KindleGen
 pub fn create(epub_path: PathBuf, output_mobi: PathBuf) -> Result<SpawnedKindleGen> {
+    let _span = crate::trace::span!("package", format = "mobi").entered();
     log::info!("Creating MOBI from: {:?}", epub_path);
     if !epub_path.exists() {
         anyhow::bail!("EPUB file does not exist: {}", epub_path.display());
     }
 
-    let child = Command::new("kindlegen")
+    // KindleGen writes next to the EPUB, under a temporary name until it's moved into place
+    let partial_name = format!(
+        ".{}.partial.mobi",
+        epub_path
+            .file_stem()
+            .context("EPUB path has no file name")?
+            .to_string_lossy()
+    );
+
+    let mut child = Command::new("kindlegen")
         .arg("-dont_append_source")
         .arg("-c1")
         .arg("-locale")
         .arg("en")
         .arg(&epub_path)
+        .arg("-o")
+        .arg(&partial_name)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()
         .context("Failed to execute KindleGen")?;
 
+    // read as it's written, for the progress and so KindleGen never blocks on a full pipe
+    let stdout = child.stdout.take().context("KindleGen has no stdout")?;
+    let output = Arc::new(Mutex::new(Output::default()));
+    let reader = thread::spawn({
+        let output = output.clone();
+        move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(progress) = progress_of(&line) {
+                    output.progress = output.progress.max(progress);
+                }
+                output.text.push_str(&line);
+                output.text.push('\n');
+            }
+        }
+    });
+
     let spawned = SpawnedKindleGen {
         child,
-        mobi_file: epub_path.with_extension("mobi"),
+        output,
+        reader,
+        mobi_file: epub_path.with_file_name(partial_name),
         output_mobi,
     };
 
@@ -33,6 +73,8 @@ pub fn create(epub_path: PathBuf, output_mobi: PathBuf) -> Result<SpawnedKindleG
 
 pub struct SpawnedKindleGen {
     child: std::process::Child,
+    output: Arc<Mutex<Output>>,
+    reader: thread::JoinHandle<()>,
     /// generated by KindleGen
     mobi_file: PathBuf,
     /// where the mobi file will be moved to
@@ -40,27 +82,46 @@ pub struct SpawnedKindleGen {
 }
 
 impl SpawnedKindleGen {
+    /// Where the MOBI file ends up once KindleGen is done
+    pub fn output(&self) -> &std::path::Path {
+        &self.output_mobi
+    }
+
+    /// How far along the conversion is, from 0 to 1, going by the steps KindleGen printed
+    pub fn progress(&self) -> f32 {
+        self.output
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .progress
+    }
+
     pub fn try_wait(&mut self) -> Result<Option<std::process::ExitStatus>> {
         let output = self.child.try_wait()?;
         Ok(output)
     }
 
-    pub fn wait(self) -> Result<()> {
+    /// Waits for KindleGen to finish and moves the MOBI file into place, returning the
+    /// warnings KindleGen printed about the book
+    pub fn wait(self) -> Result<Vec<String>> {
         let output = self.child.wait_with_output()?;
-        let output_str = String::from_utf8_lossy(&output.stdout);
+        // KindleGen closed its stdout when it exited
+        let _ = self.reader.join();
+        let output_str =
+            std::mem::take(&mut self.output.lock().unwrap_or_else(|e| e.into_inner()).text);
         let has_error_output = output_str.lines().any(|line| line.starts_with("Error("));
         let kindlegen_status = output.status.code();
 
         match (self.mobi_file.exists(), self.mobi_file == self.output_mobi) {
             // File exists and needs to be moved
             (true, false) => {
-                fs::rename(&self.mobi_file, &self.output_mobi).with_context(|| {
-                    format!(
-                        "Failed to move MOBI file from {} to {}",
-                        self.mobi_file.display(),
-                        self.output_mobi.display()
-                    )
-                })?;
+                fs::rename(&self.mobi_file, crate::output::long_path(&self.output_mobi))
+                    .with_context(|| {
+                        format!(
+                            "Failed to move MOBI file from {} to {}",
+                            self.mobi_file.display(),
+                            self.output_mobi.display()
+                        )
+                    })?;
                 log::debug!("MOBI file moved to: {}", self.output_mobi.display());
             }
             // File doesn't exist
@@ -72,11 +133,12 @@ impl SpawnedKindleGen {
 
                 // If KindleGen also failed, this is a real error
                 if !output.status.success() || has_error_output {
-                    anyhow::bail!(
-                        "KindleGen failed with code {:?} and no MOBI file was created: {}",
-                        kindlegen_status,
-                        output_str
-                    );
+                    log::error!("KindleGen output: {}", output_str);
+                    return Err(KindleGenError {
+                        code: kindlegen_status,
+                        output: output_str,
+                    }
+                    .into());
                 }
 
                 anyhow::bail!("KindleGen reported success but MOBI file was not created");
@@ -93,7 +155,462 @@ impl SpawnedKindleGen {
         }
 
         log::debug!("MOBI creation successful: {}", self.output_mobi.display());
-        Ok(())
+        let warnings = output_str
+            .lines()
+            .filter(|line| line.starts_with("Warning("))
+            .map(str::to_string)
+            .collect();
+        Ok(warnings)
+    }
+}
+
+#[derive(Default)]
+struct Output {
+    // everything KindleGen wrote to stdout so far
+    text: String,
+    progress: f32,
+}
+
+// The steps KindleGen 2.9 prints, with how far along the conversion is when they start.
+// Most of the time goes into compressing the PRC file.
+const STEPS: [(&str, f32); 7] = [
+    ("Parsing files", 0.05),
+    ("Building PHYSICAL page table", 0.15),
+    ("Computing UNICODE ranges", 0.2),
+    ("Building PRC file", 0.25),
+    ("Building enhanced PRC file", 0.6),
+    ("Resolving hyperlinks", 0.65),
+    ("Mobi file built", 1.0),
+];
+
+// `Info(prcgen):I1015: Building PHYSICAL page table`, `None` for lines that aren't a step
+fn progress_of(line: &str) -> Option<f32> {
+    let (_, message) = line.strip_prefix("Info(")?.split_once("):")?;
+    let (_, message) = message.split_once(": ")?;
+    STEPS
+        .iter()
+        .find(|(step, _)| message.starts_with(step))
+        .map(|&(_, progress)| progress)
+}
+
+/// KindleGen exited without creating a MOBI file
+#[derive(Debug)]
+pub struct KindleGenError {
+    pub code: Option<i32>,
+    /// everything KindleGen wrote to stdout
+    pub output: String,
+}
+
+impl std::fmt::Display for KindleGenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "KindleGen failed with code {:?} and no MOBI file was created",
+            self.code
+        )
+    }
+}
+
+impl std::error::Error for KindleGenError {}
+
+/// Where the Kindle thumbnails of the MOBI files of a directory are kept, see
+/// [`kindle_thumbnail`]
+pub const THUMBNAILS_DIR: &str = ".thumbnails";
+// what the Kindle home screen shows covers at
+const THUMBNAIL_SIZE: (u32, u32) = (330, 470);
+
+// EXTH records, the metadata of a MOBI header
+const EXTH_AUTHOR: u32 = 100;
+const EXTH_PUBLISHER: u32 = 101;
+const EXTH_DESCRIPTION: u32 = 103;
+const EXTH_ASIN: u32 = 113;
+const EXTH_KF8_BOUNDARY: u32 = 121;
+// of the cover and the thumbnail, from the first image record
+const EXTH_COVER_OFFSET: u32 = 201;
+const EXTH_THUMBNAIL_OFFSET: u32 = 202;
+const EXTH_CDE_TYPE: u32 = 501;
+const EXTH_UPDATED_TITLE: u32 = 503;
+const EXTH_CDE_CONTENT_KEY: u32 = 504;
+// what the Kindle sorts titles by, `title pronunciation` in KindleGen's terms
+const EXTH_TITLE_SORT: u32 = 508;
+
+/// Writes the title, authors, publisher and description of the book to the EXTH
+/// headers of a MOBI file, and marks it as an ebook.
+///
+/// KindleGen keeps only some of the EPUB's metadata and none of its series, so
+/// sideloaded volumes would be listed apart and out of order. The series is written as
+/// the title the Kindle sorts by, `Series 0003` for volume 3, so volumes sort together.
+pub fn embed_metadata(mobi: &Path, file_title: &str, config: &ComicConfig) -> Result<()> {
+    let data = fs::read(mobi).with_context(|| format!("Failed to read {}", mobi.display()))?;
+    let records = metadata_records(file_title, config);
+    let records: Vec<(u32, &[u8])> = records
+        .iter()
+        .map(|(kind, data)| (*kind, data.as_slice()))
+        .collect();
+    let data = set_records(&data, &records)?;
+    crate::output::write_atomic(mobi, &data)
+}
+
+// the EXTH records of `embed_metadata`
+fn metadata_records(file_title: &str, config: &ComicConfig) -> Vec<ExthRecord> {
+    let title = crate::metadata::title(file_title, config);
+    let mut records = vec![
+        (EXTH_UPDATED_TITLE, title.as_bytes().to_vec()),
+        (EXTH_CDE_TYPE, b"EBOK".to_vec()),
+    ];
+    if let Some(metadata) = &config.metadata {
+        let text = |kind, text: &str| (kind, text.as_bytes().to_vec());
+        records.extend(
+            metadata
+                .authors
+                .iter()
+                .map(|author| text(EXTH_AUTHOR, author)),
+        );
+        records.extend(
+            metadata
+                .publisher
+                .as_deref()
+                .map(|p| text(EXTH_PUBLISHER, p)),
+        );
+        records.extend(
+            metadata
+                .description
+                .as_deref()
+                .map(|description| text(EXTH_DESCRIPTION, description)),
+        );
+    }
+    if let Some(series) = &config.series {
+        records.push((EXTH_TITLE_SORT, sort_title(series).into_bytes()));
+    }
+    records
+}
+
+// volumes numbered to sort in order, the fraction kept for extras like 3.5
+fn sort_title(series: &Series) -> String {
+    let Some(index) = series.index else {
+        return series.name.clone();
+    };
+    // `.5` of `0.500`
+    let fraction = format!("{:.3}", index.fract());
+    let fraction = fraction
+        .trim_start_matches('0')
+        .trim_end_matches(['0', '.']);
+    format!("{} {:04}{fraction}", series.name, index.trunc() as u32)
+}
+
+/// Prepares a MOBI file for sideloading on a Kindle, which shows a generic cover on its
+/// home screen unless the book has an ASIN and a thumbnail named after it is in
+/// `system/thumbnails`.
+///
+/// The book is given an ASIN derived from its unique identifier and marked as an ebook
+/// (rather than a personal document), and the thumbnail is written from the cover to
+/// [`THUMBNAILS_DIR`] next to it, for copying to the device along with the book.
+pub fn add_kindle_thumbnail(mobi: &Path, uid: &Uuid, cover: &ProcessedImage) -> Result<PathBuf> {
+    let asin = asin(uid);
+    let data = fs::read(mobi).with_context(|| format!("Failed to read {}", mobi.display()))?;
+    let data = set_asin(&data, &asin)?;
+    crate::output::write_atomic(mobi, &data)?;
+
+    let cover = crate::image::decode::decode(&cover.data).context("Failed to decode the cover")?;
+    let (width, height) = THUMBNAIL_SIZE;
+    let mut jpeg = Vec::new();
+    crate::image::compress_to_jpeg(
+        &cover.resize(width, height, FilterType::Lanczos3),
+        &mut jpeg,
+        85,
+    )?;
+
+    let dir = mobi.parent().unwrap_or(Path::new("")).join(THUMBNAILS_DIR);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(thumbnail_name(&asin));
+    crate::output::write_atomic(&path, &jpeg)?;
+    Ok(path)
+}
+
+/// The thumbnail [`add_kindle_thumbnail`] wrote for a MOBI file, named the way the
+/// Kindle looks for it
+pub fn kindle_thumbnail(mobi: &Path) -> Option<PathBuf> {
+    let asin = read_asin(&fs::read(mobi).ok()?)?;
+    let path = mobi
+        .parent()?
+        .join(THUMBNAILS_DIR)
+        .join(thumbnail_name(&asin));
+    path.exists().then_some(path)
+}
+
+/// An ASIN-like identifier for a book: `B0` and 8 letters and digits hashed from its
+/// unique identifier, so the same book keeps its thumbnail
+pub fn asin(uid: &Uuid) -> String {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let hash = Sha256::digest(uid.as_bytes());
+    let suffix: String = hash[..8]
+        .iter()
+        .map(|byte| ALPHABET[*byte as usize % ALPHABET.len()] as char)
+        .collect();
+    format!("B0{suffix}")
+}
+
+fn thumbnail_name(asin: &str) -> String {
+    format!("thumbnail_{asin}_EBOK_portrait.jpg")
+}
+
+// MOBI files are Palm databases: a header, a table of record offsets, then the records
+struct PalmDb {
+    // up to the first record, with the offset table
+    header: Vec<u8>,
+    records: Vec<Vec<u8>>,
+}
+
+impl PalmDb {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.get(60..68) != Some(b"BOOKMOBI") {
+            bail!("Not a MOBI file");
+        }
+        let count = u16::from_be_bytes(read(data, 76)?) as usize;
+        let offsets = (0..count)
+            .map(|i| read(data, 78 + i * 8).map(|offset| u32::from_be_bytes(offset) as usize))
+            .collect::<Result<Vec<_>>>()?;
+        let first = *offsets.first().context("MOBI file has no records")?;
+
+        let ends = offsets.iter().skip(1).copied().chain([data.len()]);
+        let records = offsets
+            .iter()
+            .zip(ends)
+            .map(|(&start, end)| data.get(start..end).map(<[u8]>::to_vec))
+            .collect::<Option<_>>()
+            .context("MOBI record out of bounds")?;
+        Ok(Self {
+            header: data
+                .get(..first)
+                .context("MOBI record out of bounds")?
+                .to_vec(),
+            records,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.header.clone();
+        let mut offset = self.header.len();
+        for (i, record) in self.records.iter().enumerate() {
+            data[78 + i * 8..82 + i * 8].copy_from_slice(&(offset as u32).to_be_bytes());
+            offset += record.len();
+        }
+        for record in &self.records {
+            data.extend_from_slice(record);
+        }
+        data
+    }
+}
+
+fn read<const N: usize>(data: &[u8], at: usize) -> Result<[u8; N]> {
+    data.get(at..at + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .context("MOBI header is truncated")
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32> {
+    read(data, at).map(u32::from_be_bytes)
+}
+
+// type and data
+type ExthRecord = (u32, Vec<u8>);
+
+// the EXTH records of a header record, and where they end
+fn exth(record: &[u8]) -> Result<(Vec<ExthRecord>, usize)> {
+    if record.get(16..20) != Some(b"MOBI") {
+        bail!("Not a MOBI header");
+    }
+    let start = 16 + read_u32(record, 20)? as usize;
+    if read_u32(record, 0x80)? & 0x40 == 0 {
+        return Ok((Vec::new(), start));
+    }
+    if record.get(start..start + 4) != Some(b"EXTH") {
+        bail!("MOBI header has no EXTH header");
+    }
+
+    let mut entries = Vec::new();
+    let mut at = start + 12;
+    for _ in 0..read_u32(record, start + 8)? {
+        let (kind, size) = (read_u32(record, at)?, read_u32(record, at + 4)? as usize);
+        let data = record
+            .get(at + 8..at + size)
+            .context("EXTH record is truncated")?;
+        entries.push((kind, data.to_vec()));
+        at += size;
+    }
+    // padded to 4 bytes, which the length leaves out
+    let end = start + (read_u32(record, start + 4)? as usize).next_multiple_of(4);
+    Ok((entries, end))
+}
+
+// replaces the EXTH records of the kinds of `replacements` in a header record, kinds
+// can repeat, e.g. for each author
+fn set_exth(record: &mut Vec<u8>, replacements: &[(u32, &[u8])]) -> Result<()> {
+    let (mut entries, end) = exth(record)?;
+    let start = 16 + read_u32(record, 20)? as usize;
+    entries.retain(|(kind, _)| !replacements.iter().any(|(replaced, _)| replaced == kind));
+    entries.extend(
+        replacements
+            .iter()
+            .map(|(kind, data)| (*kind, data.to_vec())),
+    );
+
+    let length = 12
+        + entries
+            .iter()
+            .map(|(_, data)| 8 + data.len())
+            .sum::<usize>();
+    let mut header = Vec::with_capacity(length.next_multiple_of(4));
+    header.extend_from_slice(b"EXTH");
+    header.extend_from_slice(&(length as u32).to_be_bytes());
+    header.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for (kind, data) in &entries {
+        header.extend_from_slice(&kind.to_be_bytes());
+        header.extend_from_slice(&(8 + data.len() as u32).to_be_bytes());
+        header.extend_from_slice(data);
+    }
+    header.resize(length.next_multiple_of(4), 0);
+
+    // the full name comes after the EXTH header, which moves it
+    let shift = header.len() as i64 - (end - start) as i64;
+    let name_offset = read_u32(record, 0x54)?;
+    if name_offset as usize >= end {
+        let moved = (name_offset as i64 + shift) as u32;
+        record[0x54..0x58].copy_from_slice(&moved.to_be_bytes());
+    }
+    let flags = read_u32(record, 0x80)? | 0x40;
+    record[0x80..0x84].copy_from_slice(&flags.to_be_bytes());
+    record.splice(start..end, header);
+    Ok(())
+}
+
+// in the header of both the old MOBI format and the KF8 one after it, which KindleGen writes
+fn set_records(data: &[u8], replacements: &[(u32, &[u8])]) -> Result<Vec<u8>> {
+    let mut db = PalmDb::parse(data)?;
+    let (entries, _) = exth(&db.records[0])?;
+    let kf8 = entries
+        .iter()
+        .find(|(kind, _)| *kind == EXTH_KF8_BOUNDARY)
+        .and_then(|(_, data)| Some(u32::from_be_bytes(data.as_slice().try_into().ok()?)))
+        .map(|index| index as usize)
+        .filter(|&index| index > 0 && index < db.records.len());
+
+    for index in [Some(0), kf8].into_iter().flatten() {
+        set_exth(&mut db.records[index], replacements)?;
+    }
+    Ok(db.to_bytes())
+}
+
+fn set_asin(data: &[u8], asin: &str) -> Result<Vec<u8>> {
+    set_records(
+        data,
+        &[
+            (EXTH_ASIN, asin.as_bytes()),
+            (EXTH_CDE_CONTENT_KEY, asin.as_bytes()),
+            (EXTH_CDE_TYPE, b"EBOK"),
+        ],
+    )
+}
+
+fn read_asin(data: &[u8]) -> Option<String> {
+    let db = PalmDb::parse(data).ok()?;
+    let (entries, _) = exth(db.records.first()?).ok()?;
+    let (_, asin) = entries.into_iter().find(|(kind, _)| *kind == EXTH_ASIN)?;
+    String::from_utf8(asin).ok()
+}
+
+/// What the headers of a MOBI or AZW3 file say about the book, read without KindleGen
+/// or a Kindle, to check what was built
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MobiInfo {
+    /// The updated title of the EXTH header, or the full name of the MOBI header
+    pub title: String,
+    pub authors: Vec<String>,
+    pub sort_title: Option<String>,
+    pub asin: Option<String>,
+    /// `EBOK` for ebooks, `PDOC` for personal documents
+    pub cde_type: Option<String>,
+    /// Whether there's a KF8 (AZW3) part, after the old MOBI one or on its own
+    pub kf8: bool,
+    /// Whether the cover the header points at is an image of the book
+    pub cover: bool,
+    /// Images of the book, without the thumbnail KindleGen adds, the pages of a comic
+    pub pages: usize,
+}
+
+impl MobiInfo {
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let db = PalmDb::parse(data)?;
+        let record = &db.records[0];
+        let (entries, _) = exth(record)?;
+        let text = |kind| {
+            entries
+                .iter()
+                .filter(move |(k, _)| *k == kind)
+                .map(|(_, data)| String::from_utf8_lossy(data).into_owned())
+        };
+        let offset = |kind| {
+            entries
+                .iter()
+                .find(|(k, _)| *k == kind)
+                .and_then(|(_, data)| Some(u32::from_be_bytes(data.as_slice().try_into().ok()?)))
+                .map(|offset| offset as usize)
+        };
+
+        let name_offset = read_u32(record, 0x54)? as usize;
+        let name_len = read_u32(record, 0x58)? as usize;
+        let name = record
+            .get(name_offset..name_offset + name_len)
+            .context("MOBI full name out of bounds")?;
+        let title = text(EXTH_UPDATED_TITLE)
+            .next()
+            .unwrap_or_else(|| String::from_utf8_lossy(name).into_owned());
+
+        // no images is written as 0xFFFFFFFF
+        let first_image = read_u32(record, 0x6C)? as usize;
+        let is_image = |index: usize| {
+            index >= first_image
+                && db.records.get(index).is_some_and(|record| {
+                    record.starts_with(&[0xFF, 0xD8])
+                        || record.starts_with(b"\x89PNG")
+                        || record.starts_with(b"GIF8")
+                })
+        };
+        let thumbnail = offset(EXTH_THUMBNAIL_OFFSET).map(|offset| first_image + offset);
+        let pages = (first_image.min(db.records.len())..db.records.len())
+            .filter(|&index| is_image(index) && Some(index) != thumbnail)
+            .count();
+        let cover = offset(EXTH_COVER_OFFSET)
+            .is_some_and(|offset| is_image(first_image.saturating_add(offset)));
+
+        Ok(Self {
+            title,
+            authors: text(EXTH_AUTHOR).collect(),
+            sort_title: text(EXTH_TITLE_SORT).next(),
+            asin: text(EXTH_ASIN).next(),
+            cde_type: text(EXTH_CDE_TYPE).next(),
+            kf8: read_u32(record, 0x24)? == 8 || offset(EXTH_KF8_BOUNDARY).is_some(),
+            cover,
+            pages,
+        })
+    }
+
+    /// Checks what a comic needs to show up on a Kindle: a title, a cover, and `pages`
+    /// pages when given
+    pub fn check(&self, pages: Option<usize>) -> Result<()> {
+        if self.title.trim().is_empty() {
+            bail!("The book has no title");
+        }
+        if !self.cover {
+            bail!("The book has no cover");
+        }
+        match pages {
+            Some(pages) if pages != self.pages => {
+                bail!("The book has {} pages, not {pages}", self.pages)
+            }
+            _ if self.pages == 0 => bail!("The book has no pages"),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -101,3 +618,215 @@ impl SpawnedKindleGen {
 pub fn is_kindlegen_available() -> bool {
     Command::new("kindlegen").arg("-version").output().is_ok()
 }
+
+/// Version of the KindleGen in the PATH, e.g. `V2.9 build 1028-0897292`, `None` when it
+/// can't be run
+pub fn kindlegen_version() -> Option<String> {
+    let output = Command::new("kindlegen").arg("-version").output().ok()?;
+    let banner = String::from_utf8_lossy(&output.stdout);
+    Some(version_of(&banner).unwrap_or("unknown version").to_string())
+}
+
+// from the banner KindleGen prints first, `Amazon kindlegen(Linux) V2.9 build 1028-0897292`
+fn version_of(banner: &str) -> Option<&str> {
+    banner.lines().find_map(|line| {
+        let start = line.find(" V")? + 1;
+        let version = line[start..].trim_end();
+        version[1..]
+            .starts_with(|c: char| c.is_ascii_digit())
+            .then_some(version)
+    })
+}
+
+#[test]
+fn reads_the_kindlegen_version() {
+    let banner = "*************************************************************\n \
+                  Amazon kindlegen(Linux) V2.9 build 1028-0897292 \n \
+                  A command line e-book compiler \n";
+    assert_eq!(version_of(banner), Some("V2.9 build 1028-0897292"));
+    assert_eq!(version_of("Usage : kindlegen [filename]"), None);
+}
+
+#[test]
+fn follows_kindlegen_steps() {
+    assert_eq!(
+        progress_of("Info(prcgen):I1002: Parsing files  0000023"),
+        Some(0.05)
+    );
+    assert_eq!(
+        progress_of("Info(prcgen):I1016: Building enhanced PRC file"),
+        Some(0.6)
+    );
+    assert_eq!(
+        progress_of("Info(prcgen):I1036: Mobi file built successfully"),
+        Some(1.0)
+    );
+    assert_eq!(
+        progress_of("Info(prcgen):I1037: Mobi file built with WARNINGS!"),
+        Some(1.0)
+    );
+    assert_eq!(
+        progress_of("Info(pagemap):I8000: No Page map found in the book"),
+        None
+    );
+    assert_eq!(
+        progress_of("Warning(prcgen):W14001: Hyperlink not resolved"),
+        None
+    );
+    assert_eq!(
+        progress_of(" Amazon kindlegen(Linux) V2.9 build 1028-0897292"),
+        None
+    );
+}
+
+// a MOBI header record with the full name after its EXTH header
+#[cfg(test)]
+fn header_record(exth: &[(u32, &[u8])], name: &[u8]) -> Vec<u8> {
+    let mut record = vec![0; 16 + 232];
+    record[16..20].copy_from_slice(b"MOBI");
+    record[20..24].copy_from_slice(&232u32.to_be_bytes());
+    set_exth(&mut record, exth).unwrap();
+    let name_offset = record.len() as u32;
+    record[0x54..0x58].copy_from_slice(&name_offset.to_be_bytes());
+    record.extend_from_slice(name);
+    record
+}
+
+#[cfg(test)]
+fn palm_db(records: &[Vec<u8>]) -> Vec<u8> {
+    let mut header = vec![0; 78 + records.len() * 8 + 2];
+    header[60..68].copy_from_slice(b"BOOKMOBI");
+    header[76..78].copy_from_slice(&(records.len() as u16).to_be_bytes());
+    PalmDb {
+        header,
+        records: records.to_vec(),
+    }
+    .to_bytes()
+}
+
+#[test]
+fn gives_books_an_asin() {
+    let book = asin(&Uuid::nil());
+    assert_eq!(book.len(), 10);
+    assert!(book.starts_with("B0"));
+    assert_ne!(book, asin(&Uuid::from_u128(1)));
+
+    let boundary = 2u32.to_be_bytes();
+    let mobi = palm_db(&[
+        header_record(
+            &[(EXTH_KF8_BOUNDARY, &boundary), (EXTH_CDE_TYPE, b"PDOC")],
+            b"Vol. 1",
+        ),
+        b"BOUNDARY".to_vec(),
+        header_record(&[(EXTH_CDE_TYPE, b"PDOC")], b"Vol. 1"),
+        b"text".to_vec(),
+    ]);
+    assert_eq!(read_asin(&mobi), None);
+
+    let patched = set_asin(&mobi, &book).unwrap();
+    assert_eq!(read_asin(&patched).as_deref(), Some(book.as_str()));
+    let db = PalmDb::parse(&patched).unwrap();
+    assert_eq!(db.records[1], b"BOUNDARY");
+    assert_eq!(db.records[3], b"text");
+    for index in [0, 2] {
+        let record = &db.records[index];
+        let (entries, _) = exth(record).unwrap();
+        assert!(entries.contains(&(EXTH_CDE_TYPE, b"EBOK".to_vec())));
+        assert!(entries.contains(&(EXTH_CDE_CONTENT_KEY, book.clone().into_bytes())));
+        let name_offset = read_u32(record, 0x54).unwrap() as usize;
+        assert_eq!(&record[name_offset..], b"Vol. 1");
+    }
+
+    // series, authors and title in both headers, the ASIN kept
+    let config = ComicConfig {
+        series: Some(Series {
+            name: "Dungeon Meshi".into(),
+            index: Some(3.0),
+        }),
+        metadata: Some(crate::Metadata {
+            title: Some("Dungeon Meshi, Vol. 3".into()),
+            authors: vec!["Ryoko Kui".into(), "Assistant".into()],
+            ..crate::Metadata::default()
+        }),
+        ..ComicConfig::default()
+    };
+    let records = metadata_records("vol3", &config);
+    let records: Vec<(u32, &[u8])> = records
+        .iter()
+        .map(|(kind, data)| (*kind, data.as_slice()))
+        .collect();
+    let patched = set_records(&patched, &records).unwrap();
+    assert_eq!(read_asin(&patched).as_deref(), Some(book.as_str()));
+    let db = PalmDb::parse(&patched).unwrap();
+    for index in [0, 2] {
+        let (entries, _) = exth(&db.records[index]).unwrap();
+        let texts = |kind| -> Vec<&[u8]> {
+            entries
+                .iter()
+                .filter(|(k, _)| *k == kind)
+                .map(|(_, data)| data.as_slice())
+                .collect()
+        };
+        assert_eq!(texts(EXTH_AUTHOR), [b"Ryoko Kui".as_slice(), b"Assistant"]);
+        assert_eq!(texts(EXTH_UPDATED_TITLE), [b"Dungeon Meshi, Vol. 3"]);
+        assert_eq!(texts(EXTH_TITLE_SORT), [b"Dungeon Meshi 0003"]);
+        assert_eq!(texts(EXTH_CDE_TYPE), [b"EBOK"]);
+    }
+
+    let extra = Series {
+        name: "Dungeon Meshi".into(),
+        index: Some(3.5),
+    };
+    assert_eq!(sort_title(&extra), "Dungeon Meshi 0003.5");
+}
+
+#[test]
+fn reads_what_a_book_is_made_of() {
+    let jpeg = crate::testing::sample_page(30, 40, 1);
+    let cover = 0u32.to_be_bytes();
+    let thumbnail = 2u32.to_be_bytes();
+    let mut header = header_record(
+        &[
+            (EXTH_COVER_OFFSET, &cover),
+            (EXTH_THUMBNAIL_OFFSET, &thumbnail),
+            (EXTH_AUTHOR, b"Ryoko Kui"),
+            (EXTH_CDE_TYPE, b"EBOK"),
+        ],
+        b"Vol. 1",
+    );
+    header[0x58..0x5C].copy_from_slice(&6u32.to_be_bytes());
+    // the text, then two pages and the thumbnail
+    header[0x6C..0x70].copy_from_slice(&2u32.to_be_bytes());
+    let mobi = palm_db(&[
+        header,
+        b"text".to_vec(),
+        jpeg.clone(),
+        jpeg.clone(),
+        jpeg,
+        b"FLIS".to_vec(),
+    ]);
+
+    let info = MobiInfo::read(&mobi).unwrap();
+    assert_eq!(info.title, "Vol. 1");
+    assert_eq!(info.authors, ["Ryoko Kui"]);
+    assert_eq!(info.cde_type.as_deref(), Some("EBOK"));
+    assert_eq!((info.cover, info.pages, info.kf8), (true, 2, false));
+    info.check(Some(2)).unwrap();
+    assert!(info.check(Some(3)).is_err());
+
+    // the updated title and ASIN written after KindleGen
+    let patched = set_asin(&mobi, "B000000000").unwrap();
+    let patched = set_records(&patched, &[(EXTH_UPDATED_TITLE, b"Dungeon Meshi")]).unwrap();
+    let info = MobiInfo::read(&patched).unwrap();
+    assert_eq!(info.title, "Dungeon Meshi");
+    assert_eq!(info.asin.as_deref(), Some("B000000000"));
+    assert_eq!(info.pages, 2);
+
+    // a cover past the images
+    let missing = 5u32.to_be_bytes();
+    let patched = set_records(&mobi, &[(EXTH_COVER_OFFSET, &missing)]).unwrap();
+    let info = MobiInfo::read(&patched).unwrap();
+    assert!(!info.cover);
+    assert!(info.check(None).is_err());
+    assert!(MobiInfo::read(b"not a mobi").is_err());
+}
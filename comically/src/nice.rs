@@ -0,0 +1,102 @@
+//! Background conversions: a long batch run at a low priority on half the cores, with
+//! a short pause between pages, so the machine stays usable while it converts.
+//!
+//! Priorities are lowered with `setpriority`, which on Linux changes the calling thread
+//! and the threads and processes it starts afterwards, like KindleGen, and on other unix
+//! systems the whole process. Elsewhere only the fewer threads and the pauses apply.
+
+use anyhow::{Context, Result};
+
+use std::cell::Cell;
+use std::time::Duration;
+
+/// Niceness of background conversions, from 0 for normal priority to 19 for the lowest
+pub const NICENESS: i32 = 10;
+
+/// Pause between the pages a background thread processes
+pub const PAUSE: Duration = Duration::from_millis(10);
+
+thread_local! {
+    // set on the threads of background pools
+    static BACKGROUND: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Threads background conversions process pages on, half the cores
+pub fn threads() -> usize {
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    (cores / 2).max(1)
+}
+
+/// Lowers the priority of the calling thread to [`NICENESS`], see the module docs for
+/// what else it applies to
+pub fn lower_priority() {
+    #[cfg(unix)]
+    // SAFETY: setpriority only reads its arguments
+    unsafe {
+        // who 0 is the calling thread on Linux, the process elsewhere
+        if libc::setpriority(libc::PRIO_PROCESS, 0, NICENESS) != 0 {
+            log::warn!(
+                "Failed to lower the priority: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// A pool of [`threads`] low priority threads that pause between pages, for running
+/// conversions in with [`rayon::ThreadPool::install`] once the global pool is in use
+pub fn pool() -> Result<rayon::ThreadPool> {
+    builder()
+        .build()
+        .context("Failed to start the background threads")
+}
+
+/// Makes the global pool a background one, before anything runs on it, and lowers the
+/// priority of the calling thread, for programs that only convert in the background
+pub fn init_global() -> Result<()> {
+    lower_priority();
+    builder()
+        .build_global()
+        .context("Failed to start the background threads")
+}
+
+fn builder() -> rayon::ThreadPoolBuilder {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads())
+        .thread_name(|index| format!("comically-background-{index}"))
+        .start_handler(|_| {
+            lower_priority();
+            BACKGROUND.with(|background| background.set(true));
+        })
+}
+
+/// Whether the calling thread belongs to a background pool
+pub fn in_background() -> bool {
+    BACKGROUND.with(Cell::get)
+}
+
+/// Lets the rest of the machine catch up between two pages of a background conversion
+pub(crate) fn pause() {
+    if in_background() {
+        std::thread::sleep(PAUSE);
+    }
+}
+
+#[test]
+fn background_threads_pause_at_a_low_priority() {
+    assert!(!in_background());
+    let pool = pool().unwrap();
+    assert_eq!(pool.current_num_threads(), threads());
+    assert!(pool.install(in_background));
+
+    let start = std::time::Instant::now();
+    pool.install(pause);
+    assert!(start.elapsed() >= PAUSE);
+
+    #[cfg(target_os = "linux")]
+    // SAFETY: getpriority only reads its arguments
+    pool.install(|| unsafe {
+        // threads already nicer than that stay as they were
+        assert!(libc::getpriority(libc::PRIO_PROCESS, 0) >= NICENESS);
+    });
+}
@@ -0,0 +1,135 @@
+//! Async versions of the slow parts of a conversion, for servers on tokio that can't
+//! block the threads of their executor.
+//!
+//! Blocking I/O, reading archives and writing files, runs on tokio's blocking threads.
+//! Processing and packaging stay on rayon's pool like the pages themselves, the futures
+//! only wait for them. They have to be awaited within a tokio runtime.
+
+use anyhow::Result;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::archive::{ArchiveFile, ArchiveOptions};
+use crate::batch::{Batch, ComicReport, Input};
+use crate::comic::{ComicConfig, ComicFile, OutputFormat, ProcessedImage};
+
+// CPU bound work, on rayon's pool. Dropping the future doesn't cancel the work
+async fn spawn_cpu<T, F>(work: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    rayon::spawn(move || {
+        let _ = tx.send(panic::catch_unwind(AssertUnwindSafe(work)));
+    });
+    match rx.await.expect("rayon runs every spawned closure") {
+        Ok(value) => value,
+        // the work panicked, so does the future awaiting it
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+// blocking I/O, on tokio's blocking threads so it doesn't hold up the executor or the pool
+async fn spawn_io<T, F>(work: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(e) => match e.try_into_panic() {
+            Ok(panic) => panic::resume_unwind(panic),
+            // only when the runtime shuts down under the future
+            Err(e) => panic!("{e}"),
+        },
+    }
+}
+
+/// Reads and decompresses the pages of a comic
+pub async fn read_archive(comic: ComicFile, options: ArchiveOptions) -> Result<Vec<ArchiveFile>> {
+    spawn_io(move || crate::archive::unarchive_comic_iter_with(&comic, options)?.collect()).await
+}
+
+/// Processes the pages, see [`crate::image::process_batch`]
+pub async fn process(files: Vec<ArchiveFile>, config: ComicConfig) -> Result<Vec<ProcessedImage>> {
+    spawn_cpu(move || crate::image::process_batch(files, &config)).await
+}
+
+/// Builds the CBZ or EPUB of the processed pages. MOBI files need KindleGen, which
+/// [`convert`] runs: this builds the EPUB they're converted from.
+pub async fn package(title: String, config: ComicConfig, images: Vec<ProcessedImage>) -> Vec<u8> {
+    spawn_cpu(move || match config.output_format {
+        OutputFormat::Cbz => crate::cbz::build(&title, &config, &images),
+        OutputFormat::Epub | OutputFormat::Mobi => crate::epub::build(&title, &config, &images),
+    })
+    .await
+}
+
+/// Writes a built file, see [`crate::output::write_atomic`]
+pub async fn write(path: PathBuf, data: Vec<u8>, write_checksum: bool) -> Result<()> {
+    spawn_io(move || {
+        crate::output::write_atomic(&path, &data)?;
+        if write_checksum {
+            crate::output::write_checksum(&path, &data)?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Converts a comic for every target of the batch, see [`Batch::convert`]. Its pages
+/// are processed on rayon's pool, a blocking thread waits for them and writes the outputs
+pub async fn convert(batch: Arc<Batch>, input: Input) -> ComicReport {
+    spawn_io(move || batch.convert(&input, &|_| {})).await
+}
+
+#[test]
+fn conversions_run_off_the_executor() {
+    use crate::testing::{Package, SampleArchive};
+
+    let dir = tempfile::tempdir().unwrap();
+    let comic = SampleArchive::with_pages(2)
+        .write(dir.path().join("sample.cbz"))
+        .unwrap();
+    let config = ComicConfig {
+        output_format: OutputFormat::Epub,
+        page_cache: false,
+        ..ComicConfig::default()
+    };
+
+    // a single executor thread, which a blocking call would stall
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let files = read_archive(comic.clone(), config.archive_options())
+            .await
+            .unwrap();
+        let images = process(files, config.clone()).await.unwrap();
+        let epub = package(comic.title().into(), config.clone(), images).await;
+        Package::read(&epub).unwrap().check_epub().unwrap();
+
+        let path = dir.path().join("sample.epub");
+        write(path.clone(), epub, true).await.unwrap();
+        assert_eq!(crate::output::verify_checksum(&path).unwrap(), Some(true));
+
+        let out = dir.path().join("out");
+        std::fs::create_dir(&out).unwrap();
+        let batch = Arc::new(Batch::new(config, out));
+        let missing = Input::new(ComicFile::new(dir.path().join("missing.cbz")));
+        let missing = tokio::spawn(convert(batch.clone(), missing));
+        let report = convert(batch, Input::new(comic)).await;
+        assert!(report.is_ok(), "{report:?}");
+        assert!(!missing.await.unwrap().is_ok());
+    });
+
+    // panics reach the future awaiting the work
+    let panicked = std::thread::spawn(move || {
+        runtime.block_on(spawn_cpu(|| panic!("page")));
+    })
+    .join();
+    assert!(panicked.is_err());
+}
@@ -0,0 +1,255 @@
+//! OPDS 1.2 catalog of a directory of converted comics, so e-reader apps like KOReader
+//! can browse and download them from a plain file server
+
+use anyhow::{Context, Result};
+use zip::ZipArchive;
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the feed written in the catalog directory
+pub const CATALOG_FILE: &str = "catalog.xml";
+// extracted covers, relative to the catalog directory
+const COVERS_DIR: &str = ".covers";
+
+struct Entry {
+    title: String,
+    file_name: String,
+    media_type: &'static str,
+    size: u64,
+    updated: u64,
+    cover: Option<String>,
+}
+
+/// Writes (or refreshes) `catalog.xml` in `dir`, listing every CBZ, EPUB and MOBI in it.
+///
+/// Covers are extracted from CBZ and EPUB files into a hidden directory next to the
+/// feed, and only re-extracted when the comic is newer than its cover.
+pub fn write_catalog(dir: &Path) -> Result<PathBuf> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(dir).context("Failed to read catalog directory")? {
+        let path = entry.context("Failed to read directory entry")?.path();
+        let Some(media_type) = media_type(&path) else {
+            continue;
+        };
+        let (Some(title), Some(file_name)) = (path.file_stem(), path.file_name()) else {
+            continue;
+        };
+
+        let metadata = fs::metadata(&path)?;
+        let updated = modified_secs(&metadata);
+
+        let cover = extract_cover(dir, &path, updated)
+            .inspect_err(|e| log::warn!("no cover for {}: {e}", path.display()))
+            .ok()
+            .flatten();
+
+        entries.push(Entry {
+            title: title.to_string_lossy().into_owned(),
+            file_name: file_name.to_string_lossy().into_owned(),
+            media_type,
+            size: metadata.len(),
+            updated,
+            cover,
+        });
+    }
+
+    entries.sort_by(|a, b| a.title.cmp(&b.title));
+
+    let feed = feed(dir, &entries);
+    let catalog_path = dir.join(CATALOG_FILE);
+    crate::output::write_atomic(&catalog_path, feed.as_bytes())?;
+
+    log::info!(
+        "Wrote OPDS catalog with {} entries: {}",
+        entries.len(),
+        catalog_path.display()
+    );
+    Ok(catalog_path)
+}
+
+fn media_type(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()? {
+        "cbz" => Some("application/vnd.comicbook+zip"),
+        "epub" => Some("application/epub+zip"),
+        "mobi" => Some("application/x-mobipocket-ebook"),
+        _ => None,
+    }
+}
+
+fn modified_secs(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs())
+}
+
+// first image in archive order, which is the cover for both CBZ and our EPUBs
+fn extract_cover(dir: &Path, path: &Path, updated: u64) -> Result<Option<String>> {
+    if path.extension().is_some_and(|ext| ext == "mobi") {
+        return Ok(None);
+    }
+
+    let file = fs::File::open(path)?;
+    let mut archive = ZipArchive::new(std::io::BufReader::new(file))?;
+
+    let mut names: Vec<String> = archive
+        .file_names()
+        .filter(|name| is_image(name))
+        .map(str::to_string)
+        .collect();
+    names.sort();
+    let Some(name) = names.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let extension = Path::new(&name)
+        .extension()
+        .map_or("jpg".into(), |ext| ext.to_string_lossy().to_lowercase());
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let cover = format!("{COVERS_DIR}/{file_name}.{extension}");
+    let cover_path = dir.join(&cover);
+
+    let is_fresh = fs::metadata(&cover_path).is_ok_and(|meta| modified_secs(&meta) >= updated);
+    if !is_fresh {
+        let mut data = Vec::new();
+        archive.by_name(&name)?.read_to_end(&mut data)?;
+        fs::create_dir_all(dir.join(COVERS_DIR))?;
+        crate::output::write_atomic(&cover_path, &data)?;
+    }
+
+    Ok(Some(cover))
+}
+
+fn is_image(name: &str) -> bool {
+    let name = name.to_lowercase();
+    !name.contains("__macosx")
+        && [".jpg", ".jpeg", ".png", ".webp"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+}
+
+fn image_type(path: &str) -> &'static str {
+    match path.rsplit('.').next() {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+fn feed(dir: &Path, entries: &[Entry]) -> String {
+    let title = dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "comically".to_string());
+    let updated = entries
+        .iter()
+        .map(|entry| entry.updated)
+        .max()
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xmlns:dc="http://purl.org/dc/terms/" xmlns:opds="http://opds-spec.org/2010/catalog">
+  <id>urn:comically:{id}</id>
+  <title>{title}</title>
+  <updated>{updated}</updated>
+  <author><name>comically</name></author>
+  <link rel="self" href="{CATALOG_FILE}" type="application/atom+xml;profile=opds-catalog;kind=acquisition"/>
+  <link rel="start" href="{CATALOG_FILE}" type="application/atom+xml;profile=opds-catalog;kind=acquisition"/>
+"#,
+        id = &crate::output::sha256(title.as_bytes())[..32],
+        title = escape(&title),
+        updated = rfc3339(updated),
+    );
+
+    for entry in entries {
+        xml.push_str(&format!(
+            r#"  <entry>
+    <id>urn:comically:{id}</id>
+    <title>{title}</title>
+    <updated>{updated}</updated>
+    <dc:format>{media_type}</dc:format>
+    <link rel="http://opds-spec.org/acquisition" href="{href}" type="{media_type}" length="{size}"/>
+"#,
+            id = &crate::output::sha256(entry.file_name.as_bytes())[..32],
+            title = escape(&entry.title),
+            updated = rfc3339(entry.updated),
+            media_type = entry.media_type,
+            href = escape(&url_encode(&entry.file_name)),
+            size = entry.size,
+        ));
+
+        if let Some(cover) = &entry.cover {
+            let href = escape(&url_encode(cover));
+            let kind = image_type(cover);
+            xml.push_str(&format!(
+                r#"    <link rel="http://opds-spec.org/image" href="{href}" type="{kind}"/>
+    <link rel="http://opds-spec.org/image/thumbnail" href="{href}" type="{kind}"/>
+"#
+            ));
+        }
+
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// percent-encodes everything but unreserved characters and path separators
+pub(crate) fn url_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+// UTC timestamp from seconds since the epoch, using Howard Hinnant's civil_from_days
+pub(crate) fn rfc3339(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (hour, minute, second) = (rem / 3600, rem % 3600 / 60, rem % 60);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+}
+
+#[test]
+fn rfc3339_timestamps() {
+    assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+    assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(rfc3339(1_700_000_000), "2023-11-14T22:13:20Z");
+}
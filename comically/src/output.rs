@@ -0,0 +1,168 @@
+//! Writing converted files so that an interrupted conversion never leaves a partial
+//! file behind, with an optional `sha256sum`-style checksum next to each output
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use std::borrow::Cow;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Writes `data` to a temporary file next to `path` and renames it into place once complete
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let long = long_path(path);
+    let dir = match long.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut file = tempfile::Builder::new()
+        .prefix(".comically-")
+        .suffix(".partial")
+        .tempfile_in(dir)
+        .with_context(|| format!("Failed to create temporary file in {}", dir.display()))?;
+    file.write_all(data)
+        .and_then(|_| file.as_file().sync_all())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.persist(&long)
+        .with_context(|| format!("Failed to move output into place: {}", path.display()))?;
+
+    Ok(())
+}
+
+/// `path` in the `\\?\` form Windows takes past `MAX_PATH` (260 characters), which
+/// outputs deep in a library with long Japanese titles reach. Other paths, and every
+/// path on other platforms, are left as they are.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        const MAX_PATH: usize = 260;
+        // the length in bytes, never shorter than in the UTF-16 Windows counts in
+        if path.as_os_str().len() >= MAX_PATH {
+            let verbatim = std::path::absolute(path)
+                .ok()
+                .and_then(|absolute| verbatim(&absolute.to_string_lossy()));
+            if let Some(verbatim) = verbatim {
+                return Cow::Owned(PathBuf::from(verbatim));
+            }
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+// the `\\?\` form of an absolute Windows path, `None` when it already is or isn't absolute
+#[cfg(any(windows, test))]
+fn verbatim(absolute: &str) -> Option<String> {
+    if absolute.starts_with(r"\\?\") {
+        return None;
+    }
+    // verbatim paths aren't normalized, `std::path::absolute` already resolved `..`
+    let absolute = absolute.replace('/', "\\");
+    match absolute.strip_prefix(r"\\") {
+        Some(share) => Some(format!(r"\\?\UNC\{share}")),
+        None if absolute.as_bytes().get(1) == Some(&b':') => Some(format!(r"\\?\{absolute}")),
+        None => None,
+    }
+}
+
+/// Where the checksum of an output file is stored, e.g. `comic.epub.sha256`
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+/// Options for entries of an output zip. Deterministic entries get the zip epoch
+/// (1980-01-01) instead of the current time.
+pub(crate) fn zip_options(deterministic: bool) -> zip::write::SimpleFileOptions {
+    let options = zip::write::SimpleFileOptions::default();
+    if deterministic {
+        options.last_modified_time(zip::DateTime::default())
+    } else {
+        options
+    }
+}
+
+/// Hex encoded SHA-256 of the data
+pub fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Hex encoded SHA-256 of `value` serialized, for hashing settings
+pub fn sha256_of(value: &impl serde::Serialize) -> String {
+    let json = serde_json::to_vec(value).expect("settings always serialize");
+    sha256(&json)
+}
+
+/// Writes the checksum of `data`, the contents of `path`, in the format `sha256sum -c` reads
+pub fn write_checksum(path: &Path, data: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .context("Output path has no file name")?
+        .to_string_lossy();
+    let line = format!("{}  {file_name}\n", sha256(data));
+    write_atomic(&checksum_path(path), line.as_bytes())
+}
+
+/// Writes the checksum of a file that is already on disk
+pub fn write_checksum_of(path: &Path) -> Result<()> {
+    let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    write_checksum(path, &data)
+}
+
+/// Checks a file against its checksum file, `None` when it doesn't have one
+pub fn verify_checksum(path: &Path) -> Result<Option<bool>> {
+    let sidecar = checksum_path(path);
+    if !sidecar.exists() {
+        return Ok(None);
+    }
+
+    let expected = fs::read_to_string(&sidecar)
+        .with_context(|| format!("Failed to read {}", sidecar.display()))?;
+    let expected = expected
+        .split_whitespace()
+        .next()
+        .with_context(|| format!("Empty checksum file: {}", sidecar.display()))?;
+
+    let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Some(sha256(&data).eq_ignore_ascii_case(expected)))
+}
+
+#[test]
+fn checksum_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("comic.cbz");
+
+    write_atomic(&path, b"pages").unwrap();
+    assert_eq!(verify_checksum(&path).unwrap(), None);
+
+    write_checksum(&path, b"pages").unwrap();
+    assert_eq!(verify_checksum(&path).unwrap(), Some(true));
+
+    write_atomic(&path, b"truncated").unwrap();
+    assert_eq!(verify_checksum(&path).unwrap(), Some(false));
+    // only the outputs are left, no temporary files
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn long_windows_paths() {
+    assert_eq!(
+        verbatim(r"C:\Manga\Series/v01.epub").as_deref(),
+        Some(r"\\?\C:\Manga\Series\v01.epub")
+    );
+    assert_eq!(
+        verbatim(r"\\nas\books\v01.epub").as_deref(),
+        Some(r"\\?\UNC\nas\books\v01.epub")
+    );
+    assert_eq!(verbatim(r"\\?\C:\v01.epub"), None);
+    assert_eq!(verbatim("relative/v01.epub"), None);
+
+    // only rewritten where it's needed
+    let short = Path::new("out/v01.epub");
+    assert!(matches!(long_path(short), Cow::Borrowed(_)));
+}
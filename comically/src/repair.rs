@@ -0,0 +1,197 @@
+//! Salvaging the readable pages of a damaged CBZ or CBR into a fresh CBZ.
+//!
+//! Pages are read like a conversion reads them, each on its own so a damaged entry only
+//! loses that page. Zips whose central directory is broken or missing, like truncated
+//! downloads, are read entry by entry from the start of the file instead. Pages are kept
+//! as they are, as long as they still decode.
+
+use anyhow::{bail, Context, Result};
+use zip::ZipWriter;
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::archive::{ArchiveFile, FilenameEncoding};
+use crate::comic::ArchiveExt;
+use crate::ComicFile;
+
+/// What [`repair`] salvaged
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    /// Pages written to the new CBZ
+    pub recovered: usize,
+    pub lost: Vec<LostPage>,
+}
+
+/// A page that couldn't be salvaged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LostPage {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Writes the readable pages of `comic` to a new CBZ at `output`, failing when none are
+pub fn repair(comic: &ComicFile, output: &Path) -> Result<RepairReport> {
+    let (pages, mut lost) = match crate::archive::unarchive_comic_iter(comic) {
+        Ok(archive) => read_listed(archive),
+        Err(e) if matches!(comic.extension(), ArchiveExt::Cbz | ArchiveExt::Zip) => {
+            log::warn!("{e:#}, reading the entries one by one");
+            let file = File::open(crate::output::long_path(comic.as_path()))
+                .with_context(|| format!("Failed to open {}", comic.as_path().display()))?;
+            read_stream(BufReader::new(file))
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut pages: Vec<ArchiveFile> = pages
+        .into_iter()
+        .filter(|page| match crate::image::decode::decode(&page.data) {
+            Ok(_) => true,
+            Err(e) => {
+                lost.push(LostPage {
+                    path: page.file_name.clone(),
+                    reason: format!("{e:#}"),
+                });
+                false
+            }
+        })
+        .collect();
+    if pages.is_empty() {
+        bail!("No readable pages in {}", comic.as_path().display());
+    }
+    pages.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options =
+        crate::output::zip_options(false).compression_method(zip::CompressionMethod::Stored);
+    for page in &pages {
+        zip.start_file(page.file_name.to_string_lossy().replace('\\', "/"), options)?;
+        zip.write_all(&page.data)?;
+    }
+    let data = zip.finish()?.into_inner();
+    crate::output::write_atomic(output, &data)?;
+
+    Ok(RepairReport {
+        recovered: pages.len(),
+        lost,
+    })
+}
+
+// every page of the listing, reading past the ones that fail where the format allows
+fn read_listed(archive: crate::archive::ArchiveIter) -> (Vec<ArchiveFile>, Vec<LostPage>) {
+    let names = archive.images().to_vec();
+    let (mut pages, mut lost) = (Vec::new(), Vec::new());
+    for (i, page) in archive.enumerate() {
+        match page {
+            Ok(page) => pages.push(page),
+            Err(e) => lost.push(LostPage {
+                path: names.get(i).cloned().unwrap_or_default(),
+                reason: format!("{e:#}"),
+            }),
+        }
+    }
+
+    // RAR archives stop at the first damaged entry
+    let read: HashSet<&Path> = pages
+        .iter()
+        .map(|page| page.file_name.as_path())
+        .chain(lost.iter().map(|page| page.path.as_path()))
+        .collect();
+    let unread: Vec<LostPage> = names
+        .iter()
+        .filter(|name| !read.contains(name.as_path()))
+        .map(|name| LostPage {
+            path: name.clone(),
+            reason: "after a damaged entry".into(),
+        })
+        .collect();
+    lost.extend(unread);
+    (pages, lost)
+}
+
+// the entries of a zip from their local headers, up to the first one that can't be read
+fn read_stream(mut reader: impl Read) -> (Vec<ArchiveFile>, Vec<LostPage>) {
+    let (mut pages, mut lost) = (Vec::new(), Vec::new());
+    loop {
+        let mut entry = match zip::read::read_zipfile_from_stream(&mut reader) {
+            Ok(Some(entry)) => entry,
+            // the central directory, or the end of what's left of the file
+            Ok(None) => break,
+            Err(e) => {
+                log::warn!("Stopped reading after {} entries: {e}", pages.len());
+                break;
+            }
+        };
+        let Some(path) = crate::archive::entry_path(&entry, FilenameEncoding::Auto)
+            .and_then(crate::archive::validate_file)
+        else {
+            continue;
+        };
+
+        let mut data = Vec::new();
+        if let Err(e) = entry.read_to_end(&mut data) {
+            lost.push(LostPage {
+                path,
+                reason: e.to_string(),
+            });
+            break;
+        }
+        pages.push(ArchiveFile {
+            file_name: path,
+            data,
+        });
+    }
+    (pages, lost)
+}
+
+#[test]
+fn salvages_readable_pages() {
+    use crate::testing::SampleArchive;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bytes = SampleArchive::with_pages(3)
+        .file("ComicInfo.xml", "<ComicInfo/>")
+        .to_bytes();
+    let repaired = dir.path().join("repaired.cbz");
+    let pages = |path: &Path| {
+        crate::archive::list_images(&ComicFile::new(path.to_path_buf()))
+            .unwrap()
+            .len()
+    };
+
+    // a damaged page, the others are still listed by the central directory
+    let mut damaged = bytes.clone();
+    let second = damaged
+        .windows(7)
+        .position(|window| window == b"002.jpg")
+        .unwrap();
+    for byte in &mut damaged[second + 100..second + 400] {
+        *byte = !*byte;
+    }
+    let comic = ComicFile::new(dir.path().join("damaged.cbz"));
+    std::fs::write(comic.as_path(), &damaged).unwrap();
+    let report = repair(&comic, &repaired).unwrap();
+    assert_eq!(report.recovered, 2);
+    assert_eq!(report.lost.len(), 1);
+    assert_eq!(report.lost[0].path, Path::new("002.jpg"));
+    assert_eq!(pages(&repaired), 2);
+
+    // cut off in the middle of the last page, without a central directory
+    let third = bytes
+        .windows(7)
+        .position(|window| window == b"003.jpg")
+        .unwrap();
+    let comic = ComicFile::new(dir.path().join("truncated.cbz"));
+    std::fs::write(comic.as_path(), &bytes[..third + 200]).unwrap();
+    assert!(crate::archive::unarchive_comic_iter(&comic).is_err());
+    let report = repair(&comic, &repaired).unwrap();
+    assert_eq!(report.recovered, 2);
+    assert_eq!(report.lost[0].path, Path::new("003.jpg"));
+    assert_eq!(pages(&repaired), 2);
+
+    let comic = ComicFile::new(dir.path().join("empty.cbz"));
+    std::fs::write(comic.as_path(), &bytes[..20]).unwrap();
+    assert!(repair(&comic, &repaired).is_err());
+}
@@ -0,0 +1,236 @@
+//! Generated comics: pages, and archives mixing spreads, color pages, broken pages and
+//! junk files. They reproduce bugs without sharing copyrighted comics, see
+//! `comically-cli sample`.
+//!
+//! Enabled by the `sample` feature, and always in this crate's own tests.
+
+use anyhow::{Context, Result};
+use imageproc::image::{codecs::jpeg::JpegEncoder, GrayImage, Luma, Rgb, RgbImage};
+use zip::ZipWriter;
+
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+
+use crate::comic::ComicFile;
+
+/// A grayscale JPEG with a diagonal gradient, different for every `seed`
+pub fn sample_page(width: u32, height: u32, seed: u8) -> Vec<u8> {
+    let img = GrayImage::from_fn(width, height, |x, y| {
+        Luma([((x + y) % 256) as u8 ^ seed.wrapping_mul(37)])
+    });
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, 90)
+        .encode_image(&img)
+        .unwrap();
+    jpeg
+}
+
+/// A color JPEG with gradients running across and down, different for every `seed`
+pub fn sample_color_page(width: u32, height: u32, seed: u8) -> Vec<u8> {
+    let img = RgbImage::from_fn(width, height, |x, y| {
+        let seed = seed.wrapping_mul(37);
+        Rgb([
+            (x * 255 / width.max(1)) as u8 ^ seed,
+            (y * 255 / height.max(1)) as u8,
+            ((x + y) % 256) as u8,
+        ])
+    });
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, 90)
+        .encode_image(&img)
+        .unwrap();
+    jpeg
+}
+
+/// What [`SampleArchive::generate`] puts in an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleOptions {
+    pub pages: usize,
+    /// Sizes of the pages, taken in turn
+    pub sizes: Vec<(u32, u32)>,
+    /// Every `n`th page is a double page spread, twice as wide
+    pub spread_every: Option<usize>,
+    /// Every `n`th page is in color
+    pub color_every: Option<usize>,
+    /// Pages that can't be decoded, spread through the archive
+    pub broken: usize,
+    /// Adds the files archivers and operating systems leave behind, which aren't pages
+    pub junk: bool,
+}
+
+impl Default for SampleOptions {
+    fn default() -> Self {
+        Self {
+            pages: 10,
+            sizes: vec![(600, 800)],
+            spread_every: None,
+            color_every: None,
+            broken: 0,
+            junk: false,
+        }
+    }
+}
+
+/// A comic archive to convert, built in memory
+#[derive(Debug, Clone, Default)]
+pub struct SampleArchive {
+    entries: Vec<(String, Vec<u8>)>,
+    // names of entries stored as is, by the ASCII name they're written under first
+    raw_names: Vec<(String, Vec<u8>)>,
+}
+
+impl SampleArchive {
+    /// `pages` portrait pages named `001.jpg`, `002.jpg`, ...
+    pub fn with_pages(pages: usize) -> Self {
+        (1..=pages).fold(Self::default(), |archive, page| {
+            archive.page(&format!("{page:03}.jpg"), 60, 80)
+        })
+    }
+
+    /// An archive with the pages of `options`, named `001.jpg`, `002.jpg`, ... in
+    /// reading order
+    pub fn generate(options: &SampleOptions) -> Self {
+        let every =
+            |n: Option<usize>, page: usize| n.is_some_and(|n| n > 0 && page.is_multiple_of(n));
+        // broken pages are spread evenly, none of them is the cover
+        let broken_every = (options.broken > 0).then(|| (options.pages / options.broken).max(1));
+        let mut broken = 0;
+
+        let mut archive = Self::default();
+        for page in 1..=options.pages {
+            let name = format!("{page:03}.jpg");
+            let sizes = &options.sizes;
+            let (width, height) = match sizes.is_empty() {
+                true => (600, 800),
+                false => sizes[(page - 1) % sizes.len()],
+            };
+            let width = match every(options.spread_every, page) {
+                true => width * 2,
+                false => width,
+            };
+            archive = if broken < options.broken && every(broken_every, page) {
+                broken += 1;
+                archive.broken_page(&name)
+            } else if every(options.color_every, page) {
+                archive.color_page(&name, width, height)
+            } else {
+                archive.page(&name, width, height)
+            };
+        }
+        if options.junk {
+            archive = archive.junk();
+        }
+        archive
+    }
+
+    /// Adds a JPEG page, see [`sample_page`]
+    pub fn page(self, name: &str, width: u32, height: u32) -> Self {
+        let seed = self.entries.len() as u8;
+        self.file(name, sample_page(width, height, seed))
+    }
+
+    /// Adds a color JPEG page, see [`sample_color_page`]
+    pub fn color_page(self, name: &str, width: u32, height: u32) -> Self {
+        let seed = self.entries.len() as u8;
+        self.file(name, sample_color_page(width, height, seed))
+    }
+
+    /// Adds a JPEG page cut off before its pixels, the way interrupted downloads leave
+    /// them
+    pub fn broken_page(self, name: &str) -> Self {
+        let mut jpeg = sample_page(600, 800, self.entries.len() as u8);
+        // the headers and the tables, decoders read partly written pixels as gray
+        jpeg.truncate(200);
+        self.file(name, jpeg)
+    }
+
+    /// Adds what macOS, Windows and scanlators leave in archives next to the pages
+    pub fn junk(self) -> Self {
+        self.file("__MACOSX/._001.jpg", [0; 64])
+            .file(".DS_Store", [0; 64])
+            .file("thumbs.db", [0; 64])
+            .file("credits.txt", "Scanned by nobody")
+    }
+
+    /// Adds a JPEG page named with `name` as is, without the UTF-8 flag, the way zips
+    /// made on Japanese versions of Windows store their Shift-JIS names
+    pub fn raw_page(mut self, name: &[u8], width: u32, height: u32) -> Self {
+        // an ASCII name of the same length, swapped for the raw one once written
+        let placeholder = format!("{:~<1$}", self.raw_names.len(), name.len());
+        self.raw_names.push((placeholder.clone(), name.to_vec()));
+        self.page(&placeholder, width, height)
+    }
+
+    /// Adds any other entry, e.g. a `ComicInfo.xml`
+    pub fn file(mut self, name: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.entries.push((name.to_string(), data.into()));
+        self
+    }
+
+    /// The archive as CBZ bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = crate::output::zip_options(true);
+        for (name, data) in &self.entries {
+            zip.start_file(name.as_str(), options).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        let mut bytes = zip.finish().unwrap().into_inner();
+
+        // in the local header and the central directory, the file name is the only
+        // thing the CRC doesn't cover
+        for (placeholder, name) in &self.raw_names {
+            while let Some(at) = bytes
+                .windows(name.len())
+                .position(|window| window == placeholder.as_bytes())
+            {
+                bytes[at..at + name.len()].copy_from_slice(name);
+            }
+        }
+        bytes
+    }
+
+    /// Writes the archive to `path`, which should end in `.cbz`
+    pub fn write(&self, path: impl Into<PathBuf>) -> Result<ComicFile> {
+        let path = path.into();
+        std::fs::write(&path, self.to_bytes())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(ComicFile::new(path))
+    }
+}
+
+#[test]
+fn sample_options_generate_archives() {
+    let options = SampleOptions {
+        pages: 6,
+        sizes: vec![(60, 80), (90, 120)],
+        spread_every: Some(3),
+        color_every: Some(2),
+        broken: 1,
+        junk: true,
+    };
+    let archive = SampleArchive::generate(&options);
+    let names: Vec<&str> = archive
+        .entries
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(names.len(), 10);
+    assert_eq!(names[5], "006.jpg");
+
+    let dimensions = |page: usize| {
+        let img = imageproc::image::load_from_memory(&archive.entries[page - 1].1).unwrap();
+        (img.width(), img.height(), img.color())
+    };
+    use imageproc::image::ColorType;
+    assert_eq!(dimensions(1), (60, 80, ColorType::L8));
+    assert_eq!(dimensions(2), (90, 120, ColorType::Rgb8));
+    assert_eq!(dimensions(3), (120, 80, ColorType::L8));
+    // the broken page stands in for the 6th, a color spread
+    assert!(imageproc::image::load_from_memory(&archive.entries[5].1).is_err());
+
+    let dir = tempfile::tempdir().unwrap();
+    let comic = archive.write(dir.path().join("sample.cbz")).unwrap();
+    let pages = crate::archive::unarchive_comic_iter(&comic).unwrap();
+    assert_eq!(pages.num_images(), 6);
+}
@@ -0,0 +1,175 @@
+//! Moving the source of a converted comic out of the input folder, so what's left there
+//! is what still needs converting.
+//!
+//! Sources are only moved once every output they were converted to is on disk, and
+//! never over another file: a name that's taken gets a number, like file managers do.
+
+use anyhow::{bail, Context, Result};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Folder next to the source that [`MoveSource::Converted`] moves it to
+pub const CONVERTED_DIR: &str = "converted";
+
+/// Where to move a source after it was converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveSource {
+    /// To a `converted` folder next to it
+    Converted,
+    /// To the trash of the desktop, on Linux and macOS
+    Trash,
+}
+
+/// Moves `source` once the files it was converted to are written, returning where it went
+pub fn move_source(source: &Path, outputs: &[PathBuf], to: MoveSource) -> Result<PathBuf> {
+    check_outputs(source, outputs)?;
+    match to {
+        MoveSource::Converted => {
+            let dir = source
+                .parent()
+                .context("The source has no parent folder")?
+                .join(CONVERTED_DIR);
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+            let destination = free_path(&dir, source)?;
+            move_file(source, &destination)?;
+            Ok(destination)
+        }
+        MoveSource::Trash => trash(source, &trash_dir()?),
+    }
+}
+
+// the outputs are all written and none of them is the source itself
+fn check_outputs(source: &Path, outputs: &[PathBuf]) -> Result<()> {
+    if !source.is_file() {
+        bail!("{} isn't a file", source.display());
+    }
+    if outputs.is_empty() {
+        bail!("{} wasn't converted to anything", source.display());
+    }
+    let source = source.canonicalize()?;
+    for output in outputs {
+        let written = fs::metadata(output).is_ok_and(|meta| meta.is_file() && meta.len() > 0);
+        if !written {
+            bail!("{} wasn't written", output.display());
+        }
+        if output.canonicalize()? == source {
+            bail!("{} was converted in place", output.display());
+        }
+    }
+    Ok(())
+}
+
+// `dir` joined with the name of `file`, numbered when it's taken
+fn free_path(dir: &Path, file: &Path) -> Result<PathBuf> {
+    let name = file.file_name().context("The source has no file name")?;
+    let candidate = dir.join(name);
+    if !candidate.exists() {
+        return Ok(candidate);
+    }
+
+    let stem = file.file_stem().unwrap_or(name).to_string_lossy();
+    let extension = file
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| dir.join(format!("{stem} ({n}){extension}")))
+        .find(|candidate| !candidate.exists())
+        .context("No free file name")
+}
+
+// renames, or copies when the destination is on another drive
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).with_context(|| format!("Failed to move {}", from.display()))?;
+    fs::remove_file(from).with_context(|| format!("Failed to remove {}", from.display()))
+}
+
+fn trash_dir() -> Result<PathBuf> {
+    let home = std::env::home_dir().context("No home folder to find the trash in")?;
+    if cfg!(target_os = "macos") {
+        Ok(home.join(".Trash"))
+    } else if cfg!(windows) {
+        bail!("Moving to the trash isn't supported on Windows, move to a converted folder instead")
+    } else {
+        // https://specifications.freedesktop.org/trash-spec/latest/
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .unwrap_or_else(|| home.join(".local").join("share"));
+        Ok(data.join("Trash"))
+    }
+}
+
+// the freedesktop layout, where the original location is kept in `info` for restoring,
+// which macOS ignores
+fn trash(source: &Path, trash: &Path) -> Result<PathBuf> {
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    for dir in [&files, &info] {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    let destination = free_path(&files, source)?;
+    if cfg!(not(target_os = "macos")) {
+        let name = destination
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let deleted = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let contents = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            crate::opds::url_encode(&source.canonicalize()?.to_string_lossy()),
+            crate::opds::rfc3339(deleted).trim_end_matches('Z'),
+        );
+        fs::write(info.join(format!("{name}.trashinfo")), contents)
+            .context("Failed to write the trash info")?;
+    }
+    move_file(source, &destination)?;
+    Ok(destination)
+}
+
+#[test]
+fn moves_converted_sources() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out").join("vol 1.epub");
+    fs::create_dir_all(output.parent().unwrap()).unwrap();
+    fs::write(&output, b"epub").unwrap();
+    let outputs = [output];
+    let source = |name: &str| {
+        let path = dir.path().join(name);
+        fs::write(&path, b"cbz").unwrap();
+        path
+    };
+
+    let first = source("vol 1.cbz");
+    let moved = move_source(&first, &outputs, MoveSource::Converted).unwrap();
+    assert_eq!(moved, dir.path().join("converted").join("vol 1.cbz"));
+    assert!(!first.exists());
+    // a source with the same name doesn't replace the first one
+    let second = source("vol 1.cbz");
+    let moved = move_source(&second, &outputs, MoveSource::Converted).unwrap();
+    assert_eq!(moved, dir.path().join("converted").join("vol 1 (2).cbz"));
+
+    // not converted, not written, or converted in place
+    let third = source("vol 2.cbz");
+    let missing = dir.path().join("out").join("vol 2.epub");
+    assert!(move_source(&third, &[], MoveSource::Converted).is_err());
+    assert!(move_source(&third, &[missing], MoveSource::Converted).is_err());
+    assert!(move_source(&third, std::slice::from_ref(&third), MoveSource::Converted).is_err());
+    assert!(third.exists());
+
+    let trash_dir = dir.path().join("Trash");
+    let trashed = trash(&third, &trash_dir).unwrap();
+    assert_eq!(trashed, trash_dir.join("files").join("vol 2.cbz"));
+    assert!(!third.exists());
+    let info = fs::read_to_string(trash_dir.join("info").join("vol 2.cbz.trashinfo")).unwrap();
+    assert!(info.starts_with("[Trash Info]\nPath=/"));
+    assert!(info.contains("vol%202.cbz\nDeletionDate="));
+}
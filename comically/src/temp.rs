@@ -0,0 +1,185 @@
+//! Temporary directories of conversions, kept under one root. Each holds a marker with
+//! the PID of the process that created it, so the directories a crash or a kill left
+//! behind are told apart from those of running conversions and removed by
+//! [`TempRoot::sweep`], which the frontends run on start.
+
+use anyhow::{Context, Result};
+use tempfile::TempDir;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::comic::ComicConfig;
+
+// the PID of the process a directory belongs to
+const MARKER: &str = ".comically-pid";
+
+// directories without a marker are only removed once this old, a process may be about
+// to write it
+const UNMARKED_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct TempRoot {
+    dir: PathBuf,
+}
+
+/// What [`TempRoot::sweep`] removed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SweepStats {
+    pub dirs: usize,
+    pub bytes: u64,
+}
+
+impl TempRoot {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `comically` in the system's temporary directory
+    pub fn default_dir() -> PathBuf {
+        std::env::temp_dir().join("comically")
+    }
+
+    /// The root `config` asks for, e.g. on a fast SSD, or the default one
+    pub fn for_config(config: &ComicConfig) -> Self {
+        Self::new(config.temp_dir.clone().unwrap_or_else(Self::default_dir))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// A new directory, removed when dropped, with `name` starting its file name
+    pub fn create(&self, name: &str) -> Result<TempDir> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let dir = tempfile::Builder::new()
+            .prefix(&format!("{name}-"))
+            .tempdir_in(&self.dir)
+            .with_context(|| format!("Failed to create a directory in {}", self.dir.display()))?;
+        fs::write(dir.path().join(MARKER), std::process::id().to_string())
+            .context("Failed to mark the temporary directory")?;
+        Ok(dir)
+    }
+
+    /// Bytes taken by the directories of every process
+    pub fn size(&self) -> u64 {
+        size_of(&self.dir)
+    }
+
+    /// Removes the directories of processes that are gone. Errors are logged, a
+    /// directory that can't be removed now is tried again on the next sweep.
+    pub fn sweep(&self) -> SweepStats {
+        let mut stats = SweepStats::default();
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return stats;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if !path.is_dir() || !is_stale(&path) {
+                continue;
+            }
+            let bytes = size_of(&path);
+            match fs::remove_dir_all(&path) {
+                Ok(()) => {
+                    log::info!("Removed the stale temporary directory {}", path.display());
+                    stats.dirs += 1;
+                    stats.bytes += bytes;
+                }
+                Err(e) => log::warn!("Failed to remove {}: {e}", path.display()),
+            }
+        }
+        stats
+    }
+}
+
+fn is_stale(dir: &Path) -> bool {
+    let pid = fs::read_to_string(dir.join(MARKER))
+        .ok()
+        .and_then(|pid| pid.trim().parse::<u32>().ok());
+    match pid {
+        Some(pid) => pid != std::process::id() && !is_running(pid),
+        None => fs::metadata(dir)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .is_ok_and(|age| age > UNMARKED_AGE)
+            }),
+    }
+}
+
+fn size_of(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(target_os = "linux")]
+fn is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+// taken for running when it can't be told, so nothing in use is removed
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_running(pid: u32) -> bool {
+    // signal 0 only checks the process exists
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_or(true, |status| status.success())
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+        .output()
+        .map_or(true, |output| {
+            String::from_utf8_lossy(&output.stdout).contains(&pid.to_string())
+        })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_running(_: u32) -> bool {
+    true
+}
+
+#[test]
+fn sweeps_the_directories_of_gone_processes() {
+    let root = tempfile::tempdir().unwrap();
+    let temp = TempRoot::new(root.path().join("comically"));
+    assert_eq!(temp.sweep(), SweepStats::default());
+
+    let ours = temp.create("upscale").unwrap();
+    assert!(ours.path().starts_with(temp.dir()));
+    fs::write(ours.path().join("page.png"), [0; 100]).unwrap();
+
+    // left by a killed process, PIDs never get this high
+    let orphan = temp.dir().join("download-orphan");
+    fs::create_dir(&orphan).unwrap();
+    fs::write(orphan.join(MARKER), u32::MAX.to_string()).unwrap();
+    fs::write(orphan.join("book.cbz"), [0; 1000]).unwrap();
+    // being created by another process
+    fs::create_dir(temp.dir().join("unmarked")).unwrap();
+
+    let marker = std::process::id().to_string().len() as u64;
+    assert_eq!(
+        temp.size(),
+        100 + marker + 1000 + u32::MAX.to_string().len() as u64
+    );
+    let stats = temp.sweep();
+    assert_eq!(stats.dirs, 1);
+    assert!(stats.bytes >= 1000);
+    assert!(!orphan.exists());
+    assert!(ours.path().exists());
+    assert!(temp.dir().join("unmarked").exists());
+
+    let path = ours.path().to_path_buf();
+    drop(ours);
+    assert!(!path.exists());
+}
@@ -0,0 +1,247 @@
+//! Helpers for testing code that builds comics: sample archives to convert, see
+//! [`crate::sample`], a reader to check the structure of built EPUB and CBZ files, and
+//! golden files to compare them with.
+//!
+//! Enabled by the `test-utils` feature, and always in this crate's own tests. Golden
+//! files are rewritten instead of compared when `COMICALLY_UPDATE_GOLDEN` is set:
+//!
+//! ```sh
+//! COMICALLY_UPDATE_GOLDEN=1 cargo test -p comically
+//! ```
+
+use anyhow::{bail, Context, Result};
+use zip::{CompressionMethod, ZipArchive};
+
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use crate::comic::{PagePart, ProcessedImage};
+use crate::image::ImageFormat;
+
+pub use crate::sample::{sample_color_page, sample_page, SampleArchive, SampleOptions};
+
+/// A page as the EPUB and CBZ builders take it, with a few bytes standing in for the
+/// encoded image
+pub fn processed_page(page: usize, source: &str, part: PagePart) -> ProcessedImage {
+    let stem = Path::new(source).with_extension("");
+    let file_name = format!("{}_{part:?}.jpg", stem.to_string_lossy().replace('/', "_"));
+    ProcessedImage {
+        data: format!("page {page} of {source}").into_bytes(),
+        file_name,
+        dimensions: (1236, 1648),
+        format: ImageFormat::Jpeg { quality: 85 },
+        source: source.into(),
+        page,
+        part,
+        original_dimensions: (1200, 1600),
+        cropped: false,
+        small: None,
+        text: None,
+    }
+}
+
+/// The entries of a built EPUB or CBZ, in archive order
+#[derive(Debug, Clone)]
+pub struct Package {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    name: String,
+    compressed: bool,
+    data: Vec<u8>,
+}
+
+impl Package {
+    pub fn read(bytes: &[u8]) -> Result<Self> {
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).context("Not a zip archive")?;
+        let entries = (0..zip.len())
+            .map(|i| {
+                let mut file = zip.by_index(i)?;
+                let mut data = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut data)?;
+                Ok(Entry {
+                    name: file.name().to_string(),
+                    compressed: file.compression() != CompressionMethod::Stored,
+                    data,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.entry(name).map(|entry| entry.data.as_slice())
+    }
+
+    /// The entry as text, panicking when it's missing or not UTF-8
+    pub fn text(&self, name: &str) -> &str {
+        let data = self
+            .get(name)
+            .unwrap_or_else(|| panic!("no `{name}` in the package, it has {:?}", self.names()));
+        std::str::from_utf8(data).unwrap_or_else(|_| panic!("`{name}` isn't text"))
+    }
+
+    fn entry(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Checks what readers rely on: the `mimetype` entry first and stored, a container
+    /// pointing at the package document, and every file of its manifest present
+    pub fn check_epub(&self) -> Result<()> {
+        match self.entries.first() {
+            Some(entry) if entry.name == "mimetype" => {
+                if entry.compressed {
+                    bail!("`mimetype` is compressed");
+                }
+                if entry.data != b"application/epub+zip" {
+                    bail!("`mimetype` isn't application/epub+zip");
+                }
+            }
+            _ => bail!("`mimetype` isn't the first entry"),
+        }
+
+        let container = self
+            .get("META-INF/container.xml")
+            .context("No META-INF/container.xml")?;
+        let container = String::from_utf8_lossy(container);
+        let opf_path = *attribute_values(&container, "full-path")
+            .first()
+            .context("container.xml doesn't name the package document")?;
+        let opf = self
+            .get(opf_path)
+            .with_context(|| format!("No package document at {opf_path}"))?;
+        let opf = String::from_utf8_lossy(opf);
+
+        let dir = opf_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        for href in attribute_values(&opf, "href") {
+            let path = match dir {
+                "" => href.to_string(),
+                dir => format!("{dir}/{href}"),
+            };
+            if self.entry(&path).is_none() {
+                bail!("{opf_path} lists {href}, which isn't in the package");
+            }
+        }
+        Ok(())
+    }
+
+    /// A readable summary for golden files: the checksum of the whole package, then every
+    /// entry, text entries in full and the others by size and checksum
+    pub fn snapshot(bytes: &[u8]) -> Result<String> {
+        let package = Self::read(bytes)?;
+        let mut snapshot = format!("package sha256 {}\n", crate::output::sha256(bytes));
+        for entry in &package.entries {
+            let method = if entry.compressed {
+                "deflated"
+            } else {
+                "stored"
+            };
+            snapshot.push_str(&format!("\n=== {} ({method})\n", entry.name));
+            match std::str::from_utf8(&entry.data) {
+                Ok(text) if is_text(&entry.name) => {
+                    snapshot.push_str(text.trim_end());
+                    snapshot.push('\n');
+                }
+                _ => snapshot.push_str(&format!(
+                    "{} bytes, sha256 {}\n",
+                    entry.data.len(),
+                    crate::output::sha256(&entry.data)
+                )),
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+fn is_text(name: &str) -> bool {
+    let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+    name == "mimetype" || ["css", "html", "ncx", "opf", "xhtml", "xml"].contains(&extension)
+}
+
+// every `name="value"` in the document
+fn attribute_values<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let pattern = format!(" {name}=\"");
+    xml.match_indices(&pattern)
+        .filter_map(|(at, _)| {
+            let value = &xml[at + pattern.len()..];
+            value.split_once('"').map(|(value, _)| value)
+        })
+        .collect()
+}
+
+/// Compares `actual` with the golden file at `path`. When `COMICALLY_UPDATE_GOLDEN` is
+/// set, or the file doesn't exist yet, the golden file is written instead.
+pub fn assert_golden(path: &Path, actual: &str) {
+    if std::env::var_os("COMICALLY_UPDATE_GOLDEN").is_some() || !path.exists() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(path).unwrap();
+    if expected != actual {
+        let line = expected
+            .lines()
+            .zip(actual.lines())
+            .position(|(expected, actual)| expected != actual)
+            .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()));
+        panic!(
+            "output differs from {} from line {}:\n  expected: {:?}\n  actual:   {:?}\n\
+             rerun with COMICALLY_UPDATE_GOLDEN=1 if the change is intended",
+            path.display(),
+            line + 1,
+            expected.lines().nth(line).unwrap_or("<end of file>"),
+            actual.lines().nth(line).unwrap_or("<end of file>"),
+        );
+    }
+}
+
+/// Where this crate's golden files live
+#[cfg(test)]
+pub(crate) fn golden_path(name: &str) -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+}
+
+#[test]
+fn sample_archives_convert() {
+    let dir = tempfile::tempdir().unwrap();
+    let comic = SampleArchive::with_pages(3)
+        .file(
+            "ComicInfo.xml",
+            "<ComicInfo><Series>Sample</Series></ComicInfo>",
+        )
+        .write(dir.path().join("sample v01.cbz"))
+        .unwrap();
+
+    let files: Vec<_> = crate::archive::unarchive_comic_iter(&comic)
+        .unwrap()
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(files.len(), 3);
+    assert_ne!(files[0].data, files[1].data);
+
+    let config = crate::ComicConfig {
+        page_cache: false,
+        ..crate::ComicConfig::default()
+    };
+    let images = crate::image::process_batch(files, &config).unwrap();
+    let epub = crate::epub::build(comic.title(), &config, &images);
+    let package = Package::read(&epub).unwrap();
+    package.check_epub().unwrap();
+    assert!(package
+        .text("OEBPS/content.opf")
+        .contains("Images/image003.jpg"));
+}
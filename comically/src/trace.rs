@@ -0,0 +1,40 @@
+//! Spans around the stages of the pipeline (decode, prepare, render, encode, package).
+//!
+//! They carry per-page fields and, with a subscriber that reports span close events,
+//! how long each stage took. Everything here compiles to nothing without the
+//! `instrument` feature.
+
+/// `tracing::info_span!` when instrumentation is enabled, a no-op span otherwise
+#[cfg(feature = "instrument")]
+macro_rules! span {
+    ($($args:tt)*) => {
+        ::tracing::info_span!($($args)*)
+    };
+}
+
+#[cfg(not(feature = "instrument"))]
+macro_rules! span {
+    ($($args:tt)*) => {
+        $crate::trace::Span
+    };
+}
+
+pub(crate) use span;
+
+#[cfg(feature = "instrument")]
+pub(crate) type Span = tracing::Span;
+
+#[cfg(not(feature = "instrument"))]
+#[derive(Debug, Clone)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "instrument"))]
+impl Span {
+    pub(crate) fn entered(self) -> Self {
+        self
+    }
+
+    pub(crate) fn record<V>(&self, _field: &str, _value: V) -> &Self {
+        self
+    }
+}
@@ -0,0 +1,241 @@
+//! Auto-tuning: brightness, contrast and gamma suggested for a volume from the tones of
+//! a sample of its pages, instead of trying values in the preview until they look right.
+//!
+//! The device's own settings, see [`crate::device::Tuning`], are what a well scanned
+//! page needs. Suggestions start from them and make up for how far the sampled pages are
+//! from such a page: gamma for midtones that are too dark or too light, brightness for
+//! gray paper and contrast for washed out art.
+
+use anyhow::{bail, Context, Result};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use std::fmt;
+
+use crate::archive::ArchiveFile;
+use crate::comic::{ComicConfig, ComicFile, PageRange, PageRanges};
+use crate::image::transform::Image;
+
+/// Pages sampled across the comics
+pub const SAMPLED_PAGES: usize = 10;
+
+// lighter than this below the paper is ink, darker gray is the paper's grain
+const PAPER_MARGIN: usize = 24;
+
+// the paper of a well scanned page, paper darker than this is brightened up to it
+const PAPER: usize = 248;
+
+// share of the pages the paper has to take to be told apart from light art
+const MIN_PAPER: f32 = 0.2;
+
+// median of the ink of a well scanned page, before gamma
+const MIDTONES: f32 = 0.45;
+
+// pages with less ink than this are blank, or nearly so
+const MIN_INK: f32 = 0.01;
+
+// tones of the ink spread less than this from its 10th to its 90th percentile are washed
+// out
+const MIN_SPREAD: f32 = 0.5;
+
+/// Suggested tone settings, see [`suggest`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub brightness: i32,
+    pub contrast: i32,
+    pub gamma: f32,
+}
+
+impl Tone {
+    pub fn apply(&self, config: &mut ComicConfig) {
+        config.brightness = self.brightness;
+        config.contrast = self.contrast;
+        config.gamma = self.gamma;
+    }
+}
+
+impl fmt::Display for Tone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "brightness {}, contrast {}, gamma {:.2}",
+            self.brightness, self.contrast, self.gamma
+        )
+    }
+}
+
+/// Tone settings for `comics`, from [`SAMPLED_PAGES`] pages spread over them.
+///
+/// Covers are left out of the sample when there are pages enough without them, they're
+/// often in color and printed unlike the rest of the volume.
+pub fn suggest(comics: &[ComicFile], config: &ComicConfig) -> Result<Tone> {
+    let _span = crate::trace::span!("tune", comics = comics.len()).entered();
+    if comics.is_empty() {
+        bail!("No comics to tune");
+    }
+    let sampled = crate::contact_sheet::sample(comics.len(), SAMPLED_PAGES);
+    let per_comic = SAMPLED_PAGES.div_ceil(sampled.len());
+    let mut files = Vec::new();
+    for comic in sampled.into_iter().map(|i| &comics[i]) {
+        files.extend(sample_pages(comic, config, per_comic)?);
+    }
+
+    let pages: Vec<Image> = files
+        .into_par_iter()
+        .filter_map(|file| {
+            crate::image::decode::decode_luma(&file.data)
+                .inspect_err(|e| log::warn!("Failed to decode {}: {e}", file.file_name.display()))
+                .ok()
+        })
+        .collect();
+    let tone = suggest_pages(&pages, config)
+        .context("Not enough ink on the sampled pages to tune the tones")?;
+    log::info!("Suggested {tone} from {} pages", pages.len());
+    Ok(tone)
+}
+
+/// Tone settings for decoded grayscale pages, `None` when they're blank
+pub fn suggest_pages(pages: &[Image], config: &ComicConfig) -> Option<Tone> {
+    // the adjustments that come before the tuned ones, which are left neutral
+    let neutral = ComicConfig {
+        brightness: 0,
+        contrast: 0,
+        gamma: 1.0,
+        ..config.clone()
+    };
+    let mut histogram = [0u64; 256];
+    for page in pages {
+        let page = crate::image::adjust(page.clone(), &neutral);
+        for &pixel in page.as_raw() {
+            histogram[pixel as usize] += 1;
+        }
+    }
+    let total: u64 = histogram.iter().sum();
+
+    // the most common of the light levels, art printed to the edges has no paper to go by
+    let paper = (128..256)
+        .max_by_key(|&level| histogram[level])
+        .filter(|&paper| {
+            let around: u64 = histogram[paper.saturating_sub(PAPER_MARGIN / 4)..=paper]
+                .iter()
+                .sum();
+            around as f32 >= total as f32 * MIN_PAPER
+        });
+    let ink = match paper {
+        Some(paper) => &histogram[..paper - PAPER_MARGIN],
+        None => &histogram[..],
+    };
+    let ink_total: u64 = ink.iter().sum();
+    if total == 0 || (ink_total as f32) < total as f32 * MIN_INK {
+        return None;
+    }
+    let percentile = |share: f32| {
+        let mut seen = 0;
+        let level = ink.iter().position(|&count| {
+            seen += count;
+            seen as f32 >= ink_total as f32 * share
+        });
+        level.unwrap_or(0) as f32 / 255.0
+    };
+
+    let base = config.device.tuning();
+    // what the device's gamma makes of the midtones of a well scanned page, reached from
+    // the midtones of these pages
+    let midtones = percentile(0.5).clamp(0.05, 0.95);
+    let gamma = base.gamma * MIDTONES.ln() / midtones.ln();
+    let brightness = base.brightness + PAPER.saturating_sub(paper.unwrap_or(PAPER)) as i32;
+    let spread = percentile(0.9) - percentile(0.1);
+    let contrast = ((MIN_SPREAD - spread).max(0.0) * 100.0) as i32;
+
+    Some(Tone {
+        brightness: round_to(brightness, 5).clamp(-100, 100),
+        contrast: round_to(contrast, 5).clamp(0, 50),
+        gamma: ((gamma * 20.0).round() / 20.0).clamp(0.5, 3.0),
+    })
+}
+
+// up to `per_comic` pages spread over the comic, read from its archive without the others
+fn sample_pages(
+    comic: &ComicFile,
+    config: &ComicConfig,
+    per_comic: usize,
+) -> Result<Vec<ArchiveFile>> {
+    let archive = crate::archive::unarchive_comic_iter_with(comic, config.archive_options())
+        .with_context(|| format!("Failed to open {}", comic.title()))?;
+    let pages = archive.num_images();
+    // numbered from 1, past the cover
+    let (first, count) = match pages > per_comic {
+        true => (2, pages - 1),
+        false => (1, pages),
+    };
+    let ranges = crate::contact_sheet::sample(count, per_comic)
+        .into_iter()
+        .map(|i| PageRange {
+            first: first + i,
+            last: Some(first + i),
+        })
+        .collect();
+    Ok(archive
+        .select(&PageRanges::new(ranges))
+        .filter_map(|file| {
+            file.inspect_err(|e| log::warn!("Failed to load archive file: {e}"))
+                .ok()
+        })
+        .collect())
+}
+
+fn round_to(value: i32, step: i32) -> i32 {
+    (value as f32 / step as f32).round() as i32 * step
+}
+
+#[test]
+fn suggestions_make_up_for_the_scan() {
+    use crate::device::Tuning;
+    use imageproc::image::{GrayImage, Luma};
+
+    // paper around lines of ink, with a white speck in the corner
+    let page = |paper: u8, ink: fn(u32) -> u8| {
+        let img = GrayImage::from_fn(200, 300, |x, y| {
+            if (x, y) == (0, 0) {
+                Luma([255])
+            } else if (20..180).contains(&x) && y % 6 < 3 {
+                Luma([ink(x)])
+            } else {
+                Luma([paper])
+            }
+        });
+        Image::from(img)
+    };
+    let config = ComicConfig::default();
+    let base = config.device.tuning();
+    assert_eq!(base.gamma, Tuning::E_INK.gamma);
+
+    // ink from black to white
+    let well_scanned = page(255, |x| ((x - 20) * 256 / 160) as u8);
+    let tone = suggest_pages(&[well_scanned], &config).unwrap();
+    assert!((tone.gamma - base.gamma).abs() <= 0.1, "{tone}");
+    assert_eq!(tone.brightness, base.brightness);
+    assert_eq!(tone.contrast, 0);
+
+    // dark midtones need a lighter gamma, washed out ink more contrast
+    let dark = page(255, |x| ((x - 20) * 140 / 160) as u8);
+    let tone = suggest_pages(std::slice::from_ref(&dark), &config).unwrap();
+    assert!(tone.gamma < base.gamma - 0.3, "{tone}");
+    assert!(tone.contrast > 0, "{tone}");
+
+    // gray paper is brightened, the speck keeps autocontrast from making it white
+    let gray = page(200, |x| if x < 22 { 0 } else { 90 + (x % 40) as u8 });
+    let tone = suggest_pages(&[gray], &config).unwrap();
+    assert!(tone.brightness > base.brightness, "{tone}");
+
+    let blank = Image::from(GrayImage::from_pixel(100, 100, Luma([255])));
+    assert_eq!(suggest_pages(&[blank], &config), None);
+    // without paper, nothing is brightened
+    let full_bleed = Image::from(GrayImage::from_fn(100, 100, |x, y| Luma([(x + y) as u8])));
+    let tone = suggest_pages(&[full_bleed], &config).unwrap();
+    assert_eq!(tone.brightness, base.brightness);
+
+    let mut tuned = config.clone();
+    let tone = suggest_pages(&[dark], &config).unwrap();
+    tone.apply(&mut tuned);
+    assert_eq!((tuned.gamma, tuned.contrast), (tone.gamma, tone.contrast));
+}